@@ -0,0 +1,45 @@
+use eframe::egui;
+use egui::accesskit::Role;
+
+
+// Screen reader support, through egui's AccessKit integration. egui names most
+// widgets by their text, which isn't enough for image buttons (no text at all)
+// or for one of many identical "Open" buttons; these fill in the rest.
+
+// Names the widget for screen readers, in place of what it shows.
+pub fn name(resp: &egui::Response, typ: egui::WidgetType, name: &str) {
+    // widget_info() only reports clicks as events, without touching the node,
+    // so the name is set on the node directly as well.
+    resp.widget_info(|| egui::WidgetInfo::labeled(typ, name));
+    resp.ctx.accesskit_node_builder(resp.id, |node| node.set_name(name));
+}
+
+// Marks the widget as a heading, so screen readers can jump between them.
+pub fn heading(resp: &egui::Response) {
+    resp.ctx.accesskit_node_builder(resp.id, |node| node.set_role(Role::Heading));
+}
+
+// A heading only screen readers see, for where there isn't one on screen.
+pub fn hidden_heading(ui: &egui::Ui, id: egui::Id, text: &str) {
+    ui.ctx().accesskit_node_builder(id, |node| {
+        node.set_role(Role::Heading);
+        node.set_name(text);
+    });
+}
+
+// Scrolls a widget to the middle when it's tabbed to. Lists that only draw
+// what's visible have nothing past their edges to tab to, so this keeps the
+// widgets on either side of the focused one drawn.
+pub fn keep_focus_visible(resp: &egui::Response) {
+    if resp.gained_focus() {
+        resp.scroll_to_me(Some(egui::Align::Center));
+    }
+}
+
+// For one of many alike buttons in a list: names it for screen readers, and
+// keeps it in view when tabbed to.
+pub fn list_button(resp: egui::Response, text: &str) -> egui::Response {
+    name(&resp, egui::WidgetType::Button, text);
+    keep_focus_visible(&resp);
+    resp
+}
@@ -0,0 +1,106 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use eframe::egui;
+
+use image::io::Limits;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat};
+
+
+// Animated GIFs, WebPs, and PNGs, both for hashing (where an animation is
+// compared frame by frame) and for display (where egui would only show the
+// first frame).
+
+// The frames of the buffer, if it's in a format that can be animated and says
+// it is. GIFs can't say up front, so single-frame ones come through here too.
+// Also None if the limits rule it out.
+pub fn frames(format: ImageFormat, buffer: &[u8], limits: Limits) -> Option<image::Frames<'_>> {
+    match format {
+        ImageFormat::Gif => {
+            let mut decoder = image::codecs::gif::GifDecoder::new(Cursor::new(buffer)).ok()?;
+            decoder.set_limits(limits).ok()?;
+            Some(decoder.into_frames())
+        },
+        ImageFormat::WebP => {
+            let mut decoder = image::codecs::webp::WebPDecoder::new(Cursor::new(buffer)).ok()?;
+            decoder.set_limits(limits).ok()?;
+            decoder.has_animation().then(|| decoder.into_frames())
+        },
+        ImageFormat::Png => {
+            let mut decoder = image::codecs::png::PngDecoder::new(Cursor::new(buffer)).ok()?;
+            decoder.set_limits(limits).ok()?;
+            decoder.is_apng().then(|| decoder.apng().into_frames())
+        },
+        _ => None,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Frames uploaded as textures, cached in egui's temporary data so each
+// animation is only decoded once.
+pub struct Animation {
+    frames: Vec<(egui::TextureHandle, Duration)>,
+    total: Duration,
+}
+
+impl Animation {
+    // Plenty for a thumbnail, and it keeps long animations from using
+    // hundreds of MB of textures. Longer ones loop early.
+    const MAX_SIZE: u32 = 384;
+    const MAX_FRAMES: usize = 256;
+
+    // Browsers treat very short delays as "as fast as possible" and slow them
+    // down to this; animations are authored with that in mind.
+    const MIN_DELAY: Duration = Duration::from_millis(20);
+
+    fn decode(ctx: &egui::Context, uri: &str, buffer: &[u8]) -> Option<Animation> {
+        let format = image::guess_format(buffer).ok()?;
+        let mut textures = vec![];
+        for (idx, frame) in frames(format, buffer, Limits::no_limits())?.take(Self::MAX_FRAMES).enumerate() {
+            let frame = frame.ok()?;
+            let delay = Duration::from(frame.delay()).max(Self::MIN_DELAY);
+            let image = DynamicImage::ImageRgba8(frame.into_buffer())
+                .thumbnail(Self::MAX_SIZE, Self::MAX_SIZE)
+                .into_rgba8();
+            let size = [image.width() as usize, image.height() as usize];
+            let color = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+            let texture = ctx.load_texture(format!("{uri}#{idx}"), color, Default::default());
+            textures.push((texture, delay));
+        }
+
+        if textures.len() < 2 {
+            return None;
+        }
+        let total = textures.iter().map(|x| x.1).sum();
+        Some(Animation{frames: textures, total})
+    }
+
+    // None if it isn't animated after all, or couldn't be decoded (in which
+    // case the usual loader will show the first frame, or an error).
+    pub fn get(ctx: &egui::Context, uri: &str, buffer: &[u8]) -> Option<Arc<Animation>> {
+        let id = egui::Id::new(("animation", uri));
+        if let Some(cached) = ctx.data(|d| d.get_temp::<Option<Arc<Animation>>>(id)) {
+            return cached;
+        }
+        let animation = Self::decode(ctx, uri, buffer).map(Arc::new);
+        ctx.data_mut(|d| d.insert_temp(id, animation.clone()));
+        animation
+    }
+
+    // The frame to show now, scheduling a repaint for when it's time for the
+    // next one.
+    pub fn current_frame(&self, ctx: &egui::Context) -> &egui::TextureHandle {
+        let now = Duration::from_secs_f64(ctx.input(|i| i.time));
+        let mut offset = Duration::from_nanos((now.as_nanos() % self.total.as_nanos()) as u64);
+        for (texture, delay) in &self.frames {
+            if offset < *delay {
+                ctx.request_repaint_after(*delay - offset);
+                return texture;
+            }
+            offset -= *delay;
+        }
+        &self.frames[0].0
+    }
+}
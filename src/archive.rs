@@ -0,0 +1,318 @@
+use crate::bk_tree::{self, BkTree};
+use crate::misc::{data_dir, write_atomic};
+use crate::os_path;
+use crate::searcher::{self, HashIndex, SizeCap, HASH_SIZE};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use image_hasher::{HashAlg, ImageHash};
+
+use lazy_static::lazy_static;
+
+use serde::{Serialize, Deserialize};
+
+
+// Every file past searches hashed, by the folder searched, so a single image
+// can be checked against all of them without searching again. Each search
+// updates what was recorded for its folders with the way it hashed them; what
+// was recorded with other algorithms is kept alongside.
+
+const ARCHIVE_FILE: &str = "archive.json";
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    #[serde(default)]
+    pub roots: Vec<ArchiveRoot>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ArchiveRoot {
+    #[serde(with = "os_path::serde_path")]
+    pub root: PathBuf,
+    pub hash: HashAlg, // Hashes from different algorithms can't be compared
+    #[serde(default = "default_hash_size")]
+    pub hash_size: u32, // Nor can those of different sizes
+    pub indexed_at: SystemTime,
+    #[serde(with = "os_path::serde_path_pairs")]
+    pub entries: Vec<(PathBuf, String)>, // Path and base64 hash
+}
+
+// Entries recorded before the size was are at the only one there's been.
+fn default_hash_size() -> u32 {
+    HASH_SIZE
+}
+
+// How a folder's entries were hashed. Each folder can have entries under
+// several, which are only ever compared within one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Namespace {
+    pub hash: HashAlg,
+    pub hash_size: u32,
+}
+
+impl Namespace {
+    // How searches with the algorithm hash.
+    pub fn new(hash: HashAlg) -> Namespace {
+        Namespace{hash, hash_size: HASH_SIZE}
+    }
+
+    pub fn label(self) -> String {
+        match self.hash_size {
+            HASH_SIZE => format!("{:?}", self.hash),
+            size => format!("{:?} ({size}×{size})", self.hash),
+        }
+    }
+}
+
+impl ArchiveRoot {
+    pub fn namespace(&self) -> Namespace {
+        Namespace{hash: self.hash, hash_size: self.hash_size}
+    }
+
+    // Roughly what its entries take up in the file.
+    pub fn stored_bytes(&self) -> usize {
+        // Quotes, brackets and commas.
+        const PER_ENTRY: usize = 8;
+        self.entries.iter()
+            .map(|(path, hash)| path.as_os_str().len() + hash.len() + PER_ENTRY)
+            .sum()
+    }
+}
+
+pub struct Match {
+    pub path: PathBuf,
+    pub distance: u32,
+}
+
+// One BK-tree per hash length; see bk_tree::insert_by_length().
+type Trees = Arc<Vec<BkTree<PathBuf>>>;
+
+lazy_static! {
+    // Loaded on first use, and the trees built on the first lookup with each
+    // algorithm; both are dropped whenever a search records new entries.
+    static ref LOADED: Mutex<Option<Arc<Archive>>> = Mutex::new(None);
+    static ref TREES: Mutex<Vec<(Namespace, Trees)>> = Mutex::new(vec![]);
+    // Serializes recording, so concurrent searches don't drop each other's
+    // entries.
+    static ref RECORDING: Mutex<()> = Mutex::new(());
+}
+
+impl Archive {
+    fn path() -> Result<PathBuf, String> {
+        Ok(data_dir()?.join(ARCHIVE_FILE))
+    }
+
+    // Empty if nothing's been recorded yet, or what was can't be read. It's
+    // rebuilt by searching again, so there's nothing worth reporting.
+    fn load() -> Archive {
+        let archive = Self::path().ok()
+            .and_then(|x| std::fs::read(x).ok())
+            .and_then(|x| serde_json::from_slice::<Archive>(&x).ok());
+        match archive {
+            Some(x) if x.version == ARCHIVE_VERSION => x,
+            _ => Archive{version: ARCHIVE_VERSION, roots: vec![]},
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| format!("Error serializing the archive index: {e}"))?;
+        write_atomic(&Self::path()?, &json)
+    }
+
+    // Reads the file the first time; afterwards it's shared.
+    pub fn shared() -> Arc<Archive> {
+        LOADED.lock().unwrap().get_or_insert_with(|| Arc::new(Self::load())).clone()
+    }
+
+    // Merges the files in the index under each of the folders into what was
+    // recorded for it (and any folders under it) hashed the way the index
+    // was. Files the index has replace their old entries; the others are kept
+    // if they still exist, since the search may have left them out (by depth,
+    // date, extension, or by being stopped), not found them gone. Blocks while
+    // the file is written; it's expected to be called from a background
+    // thread.
+    pub fn record(roots: &[PathBuf], index: &HashIndex) -> Result<(), String> {
+        let _recording = RECORDING.lock().unwrap();
+        let mut archive = Self::load();
+        Self::merge(&mut archive, roots, index, |x| x.exists());
+        Self::replace(archive)
+    }
+
+    fn merge(archive: &mut Archive, roots: &[PathBuf], index: &HashIndex, exists: impl Fn(&Path) -> bool) {
+        let namespace = Namespace::new(index.alg);
+        let indexed_at = SystemTime::now();
+        for root in roots {
+            let mut entries: Vec<(PathBuf, String)> = index.hashes.iter()
+                .flat_map(|(hash, paths)| paths.iter().map(move |x| (x, hash)))
+                .filter(|(path, _)| path.starts_with(root))
+                .map(|(path, hash)| (path.clone(), hash.to_base64()))
+                .collect();
+            let hashed: HashSet<PathBuf> = entries.iter().map(|x| x.0.clone()).collect();
+            let (replaced, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut archive.roots).into_iter()
+                .partition(|x| x.namespace() == namespace && x.root.starts_with(root));
+            archive.roots = kept;
+            entries.extend(replaced.into_iter()
+                .flat_map(|x| x.entries)
+                .filter(|(path, _)| !hashed.contains(path) && exists(path)));
+            entries.sort();
+            archive.roots.push(ArchiveRoot {
+                root: root.clone(),
+                hash: namespace.hash,
+                hash_size: namespace.hash_size,
+                indexed_at,
+                entries,
+            });
+        }
+        archive.roots.sort_by(|a, b| a.root.cmp(&b.root));
+    }
+
+    // Drops what's recorded for the folder in the namespace, leaving any
+    // others. Blocks like record().
+    pub fn purge(root: &Path, namespace: Namespace) -> Result<(), String> {
+        let _recording = RECORDING.lock().unwrap();
+        let mut archive = Self::load();
+        archive.roots.retain(|x| x.namespace() != namespace || x.root != root);
+        Self::replace(archive)
+    }
+
+    fn replace(archive: Archive) -> Result<(), String> {
+        archive.save()?;
+        *LOADED.lock().unwrap() = Some(Arc::new(archive));
+        TREES.lock().unwrap().clear();
+        Ok(())
+    }
+
+    // If what's recorded for the folder (or for folders around it) was all
+    // hashed in other ways than the namespace, the most recent of those.
+    // None if there's nothing recorded, or something in the namespace.
+    pub fn mismatch(&self, root: &Path, namespace: Namespace) -> Option<Namespace> {
+        let overlapping: Vec<&ArchiveRoot> = self.roots.iter()
+            .filter(|x| x.root.starts_with(root) || root.starts_with(&x.root))
+            .collect();
+        if overlapping.iter().any(|x| x.namespace() == namespace) {
+            return None;
+        }
+        overlapping.into_iter()
+            .max_by_key(|x| x.indexed_at)
+            .map(ArchiveRoot::namespace)
+    }
+
+    // Recorded files within max_distance of the image, nearest first. The
+    // first lookup with an algorithm builds its trees, which takes a moment
+    // on a big archive; later ones are quick. Blocks, so it's expected to be
+    // called from a background thread.
+    pub fn lookup(path: &Path, hash: HashAlg, size_cap: SizeCap, max_distance: u32) -> Result<Vec<Match>, String> {
+        let hasher = searcher::hasher(hash);
+        let query = searcher::open_image(path, None, size_cap.for_hash(hash))
+            .map_err(|e| e.msg)?
+            .hash(&hasher, false, 100);
+
+        let mut matches = vec![];
+        for tree in Self::trees(Namespace::new(hash)).iter() {
+            tree.find(query.as_bytes(), max_distance, |path, distance| matches.push(Match{path: path.clone(), distance}));
+        }
+        matches.sort_by(|a, b| a.distance.cmp(&b.distance).then_with(|| a.path.cmp(&b.path)));
+        Ok(matches)
+    }
+
+    fn trees(namespace: Namespace) -> Trees {
+        if let Some((_, trees)) = TREES.lock().unwrap().iter().find(|(x, _)| *x == namespace) {
+            return trees.clone();
+        }
+
+        let archive = Self::shared();
+        let mut trees: Vec<BkTree<PathBuf>> = vec![];
+        for root in archive.roots.iter().filter(|x| x.namespace() == namespace) {
+            for (path, encoded) in &root.entries {
+                let Ok(decoded) = ImageHash::<Box<[u8]>>::from_base64(encoded) else {
+                    continue;
+                };
+                bk_tree::insert_by_length(&mut trees, decoded.as_bytes(), path.clone());
+            }
+        }
+
+        let trees = Arc::new(trees);
+        TREES.lock().unwrap().push((namespace, trees.clone()));
+        trees
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithms_are_kept_apart() {
+        let entry = |root: &str, hash, secs| ArchiveRoot {
+            root: root.into(),
+            hash,
+            hash_size: HASH_SIZE,
+            indexed_at: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs),
+            entries: vec![(PathBuf::from(root).join("a.png"), "AAAA".to_string())],
+        };
+        let archive = Archive {
+            version: ARCHIVE_VERSION,
+            roots: vec![
+                entry("/photos", HashAlg::Mean, 1),
+                entry("/photos/2020", HashAlg::Blockhash, 2),
+                entry("/other", HashAlg::Gradient, 3),
+            ],
+        };
+
+        let gradient = Namespace::new(HashAlg::Gradient);
+        assert_eq!(archive.mismatch(Path::new("/photos"), gradient), Some(Namespace::new(HashAlg::Blockhash)));
+        assert_eq!(archive.mismatch(Path::new("/photos/2021"), gradient), Some(Namespace::new(HashAlg::Mean)));
+        assert_eq!(archive.mismatch(Path::new("/photos"), Namespace::new(HashAlg::Mean)), None);
+        assert_eq!(archive.mismatch(Path::new("/other/x"), gradient), None);
+        assert_eq!(archive.mismatch(Path::new("/new"), gradient), None);
+
+        // Entries from before the size was recorded are at the default.
+        let json = r#"{"root": "/a", "hash": "Mean", "indexed_at": {"secs_since_epoch": 0, "nanos_since_epoch": 0}, "entries": []}"#;
+        let old: ArchiveRoot = serde_json::from_str(json).unwrap();
+        assert_eq!(old.namespace(), Namespace::new(HashAlg::Mean));
+    }
+
+    #[test]
+    fn recording_keeps_what_the_search_left_out() {
+        let hash = |byte| ImageHash::<Box<[u8]>>::from_bytes(&[byte; 8]).unwrap();
+        let root = |path: &str, entries: &[(&str, u8)]| ArchiveRoot {
+            root: path.into(),
+            hash: HashAlg::Gradient,
+            hash_size: HASH_SIZE,
+            indexed_at: SystemTime::UNIX_EPOCH,
+            entries: entries.iter().map(|(x, byte)| (PathBuf::from(x), hash(*byte).to_base64())).collect(),
+        };
+        let mut archive = Archive {
+            version: ARCHIVE_VERSION,
+            roots: vec![
+                root("/photos", &[("/photos/a.jpg", 1), ("/photos/deep/b.jpg", 2), ("/photos/gone.jpg", 3)]),
+                root("/photos/2020", &[("/photos/2020/c.jpg", 4)]),
+                root("/other", &[("/other/d.jpg", 5)]),
+            ],
+        };
+
+        // Only a.jpg was hashed this time (say, the search didn't go deep),
+        // and it's changed since.
+        let mut index = HashIndex::new(HashAlg::Gradient);
+        index.hashes.insert(hash(9), vec![PathBuf::from("/photos/a.jpg")]);
+        Archive::merge(&mut archive, &[PathBuf::from("/photos")], &index, |x| !x.ends_with("gone.jpg"));
+
+        let roots: Vec<_> = archive.roots.iter().map(|x| x.root.to_str().unwrap()).collect();
+        assert_eq!(roots, ["/other", "/photos"]);
+        let photos: Vec<_> = archive.roots[1].entries.iter()
+            .map(|(path, encoded)| (path.to_str().unwrap(), encoded.clone()))
+            .collect();
+        assert_eq!(photos, [
+            ("/photos/2020/c.jpg", hash(4).to_base64()),
+            ("/photos/a.jpg", hash(9).to_base64()),
+            ("/photos/deep/b.jpg", hash(2).to_base64()),
+        ]);
+        assert_eq!(archive.roots[0].entries.len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::searcher::{self, SizeCap};
+
+use std::collections::{HashSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use ignore::WalkBuilder;
+
+use image_hasher::HashAlg;
+
+use rayon::prelude::*;
+
+
+// Hashes a random sample of the user's own images with every algorithm, so
+// they can see the tradeoffs on their library rather than reading about them.
+
+pub const SAMPLE_SIZE: usize = 200;
+
+// Stop looking for more images to sample from after this many entries, so a
+// huge root doesn't make the benchmark slower than the search.
+const MAX_WALK: usize = 100_000;
+
+pub const ALGORITHMS: [HashAlg; 5] = [
+    HashAlg::Mean,
+    HashAlg::Gradient,
+    HashAlg::VertGradient,
+    HashAlg::DoubleGradient,
+    HashAlg::Blockhash,
+];
+
+pub struct AlgResult {
+    pub alg: HashAlg,
+    pub images_per_sec: f64, // Hashing only; decoding is the same for all of them
+    pub collisions: usize, // Pairs of sampled images with identical hashes
+}
+
+pub struct BenchmarkResults {
+    pub sampled: usize,
+    pub failed: usize, // Sampled files that couldn't be decoded
+    pub algs: Vec<AlgResult>,
+}
+
+impl BenchmarkResults {
+    // A random sample should contain few true duplicates, so collisions are
+    // mostly false positives. Fewest wins, then fastest.
+    pub fn recommended(&self) -> Option<HashAlg> {
+        self.algs.iter()
+            .min_by(|a, b| a.collisions.cmp(&b.collisions)
+                .then(b.images_per_sec.total_cmp(&a.images_per_sec)))
+            .map(|x| x.alg)
+    }
+}
+
+// Reservoir sample of up to SAMPLE_SIZE files with the given extensions.
+fn sample_paths(root: &Path, exts: &HashSet<String>, cancel: &AtomicBool) -> Vec<PathBuf> {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    let mut seen = 0;
+    let walk = WalkBuilder::new(root).standard_filters(false).build();
+    for entry in walk.take(MAX_WALK).filter_map(|x| x.ok()) {
+        if cancel.load(Ordering::Relaxed) {
+            return vec![];
+        }
+
+        let path = entry.path();
+        let ext = path.extension().map(|x| x.to_string_lossy().to_lowercase());
+        if !entry.file_type().is_some_and(|x| x.is_file()) || !ext.is_some_and(|x| exts.contains(&x)) {
+            continue;
+        }
+
+        seen += 1;
+        if sample.len() < SAMPLE_SIZE {
+            sample.push(path.to_path_buf());
+        } else {
+            let idx = fastrand::usize(..seen);
+            if idx < SAMPLE_SIZE {
+                sample[idx] = path.to_path_buf();
+            }
+        }
+    }
+    sample
+}
+
+// Runs synchronously; expected to be called from a background thread. Returns
+// None if canceled.
+pub fn run(root: PathBuf, exts: HashSet<String>, cap: SizeCap, cancel: Arc<AtomicBool>) -> Option<BenchmarkResults> {
+    let paths = sample_paths(&root, &exts, &cancel);
+    let hashers: Vec<_> = ALGORITHMS.iter()
+        .map(|alg| searcher::hasher(*alg))
+        .collect();
+
+    // Each image is decoded once (with the same safety nets as a real
+    // search), hashed with every algorithm, and dropped; keeping a couple
+    // hundred full-size decoded photos around would take gigabytes.
+    let hashed: Vec<_> = paths.par_iter()
+        .filter_map(|path| {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let opened = searcher::open_image(path, None, cap).ok()?;
+            Some(hashers.iter().map(|hasher| {
+                let start = Instant::now();
+                let hash = opened.hash(hasher, false, 100);
+                (hash, start.elapsed())
+            }).collect::<Vec<_>>())
+        })
+        .collect();
+
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let algs = ALGORITHMS.iter().enumerate().map(|(idx, alg)| {
+        let time: Duration = hashed.iter().map(|x| x[idx].1).sum();
+        let mut counts = HashMap::new();
+        for x in &hashed {
+            *counts.entry(&x[idx].0).or_insert(0usize) += 1;
+        }
+        AlgResult {
+            alg: *alg,
+            images_per_sec: hashed.len() as f64 / time.as_secs_f64().max(f64::EPSILON),
+            collisions: counts.values().map(|n| n * (n - 1) / 2).sum(),
+        }
+    }).collect();
+
+    Some(BenchmarkResults{sampled: paths.len(), failed: paths.len() - hashed.len(), algs})
+}
@@ -0,0 +1,128 @@
+// A BK-tree over hashes by Hamming distance. Each child is keyed by its
+// distance from its parent, so by the triangle inequality a search only has
+// to descend into children keyed within max_distance of the query's distance
+// from the parent.
+pub struct BkTree<T> {
+    pub hash_len: usize, // Bytes; every hash in the tree is this long
+    nodes: Vec<BkNode<T>>, // The first is the root
+}
+
+struct BkNode<T> {
+    hash: Box<[u8]>,
+    values: Vec<T>, // Everything with exactly this hash
+    children: Vec<(u32, usize)>, // Distance from this node, and index into nodes
+}
+
+impl<T> BkTree<T> {
+    pub fn new(hash_len: usize) -> BkTree<T> {
+        BkTree{hash_len, nodes: vec![]}
+    }
+
+    pub fn distance(a: &[u8], b: &[u8]) -> u32 {
+        a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum()
+    }
+
+    pub fn insert(&mut self, hash: &[u8], value: T) {
+        let new = BkNode{hash: hash.into(), values: vec![], children: vec![]};
+        if self.nodes.is_empty() {
+            self.nodes.push(new);
+            self.nodes[0].values.push(value);
+            return;
+        }
+
+        let mut idx = 0;
+        loop {
+            let distance = Self::distance(&self.nodes[idx].hash, hash);
+            if distance == 0 {
+                self.nodes[idx].values.push(value);
+                return;
+            }
+            match self.nodes[idx].children.iter().find(|(x, _)| *x == distance) {
+                Some(&(_, child)) => idx = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(new);
+                    self.nodes[child].values.push(value);
+                    self.nodes[idx].children.push((distance, child));
+                    return;
+                },
+            }
+        }
+    }
+
+    // Calls found with everything within max_distance of the hash, and its
+    // distance. A hash of another length finds nothing.
+    pub fn find(&self, hash: &[u8], max_distance: u32, mut found: impl FnMut(&T, u32)) {
+        if hash.len() != self.hash_len || self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let distance = Self::distance(&node.hash, hash);
+            if distance <= max_distance {
+                node.values.iter().for_each(|x| found(x, distance));
+            }
+            stack.extend(node.children.iter()
+                .filter(|(x, _)| x.abs_diff(distance) <= max_distance)
+                .map(|(_, child)| *child));
+        }
+    }
+}
+
+// Hashes of animations are longer than those of stills, and distance is only
+// defined between hashes of the same length, so each length gets its own tree.
+pub fn insert_by_length<T>(trees: &mut Vec<BkTree<T>>, hash: &[u8], value: T) {
+    match trees.iter_mut().find(|x| x.hash_len == hash.len()) {
+        Some(tree) => tree.insert(hash, value),
+        None => {
+            let mut tree = BkTree::new(hash.len());
+            tree.insert(hash, value);
+            trees.push(tree);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_what_a_scan_does() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let hashes: Vec<[u8; 8]> = (0..2000)
+            .map(|_| {
+                let mut x = [0; 8];
+                rng.fill(&mut x);
+                x
+            })
+            .collect();
+        let mut tree = BkTree::new(8);
+        for (idx, hash) in hashes.iter().enumerate() {
+            tree.insert(hash, idx);
+        }
+
+        for (query, max_distance) in [(&hashes[3], 0), (&hashes[10], 20), (&[0; 8], 24)] {
+            let mut found = vec![];
+            tree.find(query, max_distance, |x, _| found.push(*x));
+            found.sort();
+
+            let expected: Vec<_> = hashes.iter().enumerate()
+                .filter(|(_, x)| BkTree::<usize>::distance(*x, query) <= max_distance)
+                .map(|(idx, _)| idx)
+                .collect();
+            assert_eq!(found, expected);
+        }
+
+        // Other lengths are in trees of their own.
+        let mut trees = vec![];
+        insert_by_length(&mut trees, &[0; 8], 0);
+        insert_by_length(&mut trees, &[0; 32], 1);
+        insert_by_length(&mut trees, &[1; 8], 2);
+        assert_eq!(trees.iter().map(|x| x.hash_len).collect::<Vec<_>>(), [8, 32]);
+        let mut found = vec![];
+        trees.iter().for_each(|x| x.find(&[0; 8], 64, |x, _| found.push(*x)));
+        found.sort();
+        assert_eq!(found, [0, 2]);
+    }
+}
@@ -0,0 +1,165 @@
+use image::{DynamicImage, GenericImageView, RgbImage};
+
+
+// Trims letterboxing and padding (uniform bars of any color along the edges)
+// before hashing, so a copy that came back with bars added still matches the
+// original. Only ever applied to the copy that's hashed.
+
+// Borders are found on a copy this size, then mapped back. Being off by a
+// pixel or two at full size doesn't change the hash.
+const WORK_SIZE: u32 = 512;
+
+// Most of a dimension that can be trimmed, top and bottom (or left and right)
+// together. Past this, it's more likely a plain background than a border, and
+// that dimension is left alone.
+const MAX_TRIM_SHARE: f32 = 0.4;
+
+// A line of pixels is part of a border if it's this close to a single color:
+// the mean squared difference from its average, per channel. Allows for
+// compression noise.
+const MAX_LINE_VARIANCE: f32 = 40.0;
+
+// And if its average is this close to the edge's, so a uniform sky next to a
+// black bar isn't trimmed too.
+const MAX_COLOR_DIFFERENCE: f32 = 24.0;
+
+// The image without its borders, or None if it has none worth trimming.
+pub fn trim(image: &DynamicImage) -> Option<DynamicImage> {
+    let (width, height) = image.dimensions();
+    if width < 2 || height < 2 {
+        return None;
+    }
+    let work = if width > WORK_SIZE || height > WORK_SIZE {
+        image.thumbnail(WORK_SIZE, WORK_SIZE).to_rgb8()
+    } else {
+        image.to_rgb8()
+    };
+    let (work_width, work_height) = work.dimensions();
+
+    // Bars along one side run through those along the others, so each pair
+    // of sides is measured within what the other pair leaves, going back and
+    // forth until nothing changes.
+    let (mut top, mut bottom, mut left, mut right) = (0, 0, 0, 0);
+    loop {
+        let (x0, x1) = (left, work_width - right);
+        let rows = |y: u32| (x0..x1).map(move |x| (x, y));
+        let new_top = border_len(&work, work_height, rows);
+        let new_bottom = border_len(&work, work_height, |i| rows(work_height - 1 - i));
+
+        let (y0, y1) = (new_top, work_height - new_bottom);
+        let cols = |x: u32| (y0..y1).map(move |y| (x, y));
+        let new_left = border_len(&work, work_width, cols);
+        let new_right = border_len(&work, work_width, |i| cols(work_width - 1 - i));
+
+        let new = (new_top, new_bottom, new_left, new_right);
+        if new == (top, bottom, left, right) {
+            break;
+        }
+        (top, bottom, left, right) = new;
+    }
+
+    let within = |a: u32, b: u32, len: u32| (a + b) as f32 <= len as f32 * MAX_TRIM_SHARE;
+    let (top, bottom) = if within(top, bottom, work_height) { (top, bottom) } else { (0, 0) };
+    let (left, right) = if within(left, right, work_width) { (left, right) } else { (0, 0) };
+    if top + bottom + left + right == 0 {
+        return None;
+    }
+
+    // Back to full size, rounding inward so no border is left behind.
+    let scale_x = width as f32 / work_width as f32;
+    let scale_y = height as f32 / work_height as f32;
+    let x0 = (left as f32 * scale_x).ceil() as u32;
+    let y0 = (top as f32 * scale_y).ceil() as u32;
+    let x1 = width - (right as f32 * scale_x).ceil() as u32;
+    let y1 = height - (bottom as f32 * scale_y).ceil() as u32;
+    (x1 > x0 && y1 > y0).then(|| image.crop_imm(x0, y0, x1 - x0, y1 - y0))
+}
+
+// The middle of the image, percent of its width and height, or None to hash
+// it whole. Unlike trim(), it cuts the edges whatever's there, for copies that
+// differ by a watermark or frame that isn't uniform; so it also matches images
+// that only share their middles.
+pub fn central(image: &DynamicImage, percent: u32) -> Option<DynamicImage> {
+    if percent >= 100 {
+        return None;
+    }
+    let (width, height) = image.dimensions();
+    let (crop_width, crop_height) = ((width * percent / 100).max(1), (height * percent / 100).max(1));
+    Some(image.crop_imm((width - crop_width) / 2, (height - crop_height) / 2, crop_width, crop_height))
+}
+
+// How many lines in from an edge are border, by line(i), the pixels of the
+// i'th line in. Stops at the share that can be trimmed at most.
+fn border_len<I>(image: &RgbImage, len: u32, line: impl Fn(u32) -> I) -> u32
+where
+    I: Iterator<Item = (u32, u32)>,
+{
+    let max = (len as f32 * MAX_TRIM_SHARE) as u32;
+    let mut edge = None;
+    for i in 0..max {
+        let Some(mean) = uniform_color(image, line(i)) else {
+            return i;
+        };
+        let edge = *edge.get_or_insert(mean);
+        let difference = (0..3).map(|c| (mean[c] - edge[c]).abs()).fold(0.0, f32::max);
+        if difference > MAX_COLOR_DIFFERENCE {
+            return i;
+        }
+    }
+    max
+}
+
+// The line's average color, if it's uniform.
+fn uniform_color(image: &RgbImage, line: impl Iterator<Item = (u32, u32)>) -> Option<[f32; 3]> {
+    let pixels: Vec<[f32; 3]> = line
+        .map(|(x, y)| image.get_pixel(x, y).0.map(|c| c as f32))
+        .collect();
+    let count = pixels.len().max(1) as f32;
+    let mut mean = [0.0; 3];
+    for pixel in &pixels {
+        for c in 0..3 {
+            mean[c] += pixel[c] / count;
+        }
+    }
+    let variance = pixels.iter()
+        .map(|x| (0..3).map(|c| (x[c] - mean[c]).powi(2)).sum::<f32>() / 3.0)
+        .sum::<f32>() / count;
+    (variance <= MAX_LINE_VARIANCE).then_some(mean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32) -> RgbImage {
+        let mut rng = fastrand::Rng::with_seed(3);
+        RgbImage::from_fn(width, height, |_, _| image::Rgb([rng.u8(..), rng.u8(..), rng.u8(..)]))
+    }
+
+    #[test]
+    fn trims_bars_of_different_colors() {
+        let inner = noise(200, 100);
+        let padded = RgbImage::from_fn(260, 140, |x, y| match (x, y) {
+            (_, 0..=14) => image::Rgb([0, 0, 0]),
+            (_, 115..) => image::Rgb([255, 255, 255]),
+            (0..=29, _) | (230.., _) => image::Rgb([10, 10, 10]),
+            _ => *inner.get_pixel(x - 30, y - 15),
+        });
+        let trimmed = trim(&DynamicImage::ImageRgb8(padded)).unwrap();
+        assert_eq!(trimmed.dimensions(), (200, 100));
+        assert_eq!(trimmed.to_rgb8(), inner);
+    }
+
+    #[test]
+    fn leaves_plain_backgrounds_alone() {
+        assert!(trim(&DynamicImage::ImageRgb8(noise(100, 100))).is_none());
+
+        // Bars covering half the height look more like a background.
+        let inner = noise(100, 50);
+        let padded = RgbImage::from_fn(100, 100, |x, y| match y {
+            25..=74 => *inner.get_pixel(x, y - 25),
+            _ => image::Rgb([0, 0, 0]),
+        });
+        assert!(trim(&DynamicImage::ImageRgb8(padded)).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use image::{DynamicImage, GenericImageView};
+
+
+// Files that are images in name only, mostly left behind by interrupted copies
+// and downloads: nothing at all, a file cut short, or a picture of nothing.
+// They're worth telling apart from other decode errors, as there's nothing to
+// recover from them and they're safe to clear out in bulk. Judged from the
+// bytes the search reads anyway, plus the decoded image if there is one, so it
+// costs next to nothing.
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum BrokenKind {
+    Empty, // No bytes, or nothing but zeros (space reserved for a copy that never came)
+    Truncated, // Cut off before its format's end marker, whether or not some of it decodes
+    Solid, // Decodes, but to a single flat color
+}
+
+impl BrokenKind {
+    pub const ALL: [BrokenKind; 3] = [BrokenKind::Empty, BrokenKind::Truncated, BrokenKind::Solid];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BrokenKind::Empty => tr!("Empty"),
+            BrokenKind::Truncated => tr!("Truncated"),
+            BrokenKind::Solid => tr!("Solid color"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BrokenImage {
+    pub path: PathBuf,
+    pub size: u64, // Bytes
+    pub kind: BrokenKind,
+}
+
+// Channel difference from the first sample that still counts as the same
+// color, for noise from lossy compression.
+const SOLID_TOLERANCE: u8 = 2;
+
+// Pixels sampled along each side for Solid, as in ContentKind::from_pixels().
+const GRID: u32 = 32;
+
+// What's wrong with the file, from its contents, and the image, if they
+// decoded. None for a file that's fine, or whose decode failed for some other
+// reason. A file that decoded is never Truncated, whatever follows its end
+// marker: cameras put metadata there, and motion photos a whole video.
+pub fn check(buffer: &[u8], image: Option<&DynamicImage>) -> Option<BrokenKind> {
+    if buffer.iter().all(|x| *x == 0) {
+        return Some(BrokenKind::Empty);
+    }
+    match image {
+        Some(image) => solid(image).then_some(BrokenKind::Solid),
+        None => truncated(buffer).then_some(BrokenKind::Truncated),
+    }
+}
+
+// Whether the file's a format with an end marker, but ends before it. The
+// marker's found by walking the file's structure, as the same bytes can turn
+// up in the data. Other formats can't be told to be cut short this way.
+fn truncated(buffer: &[u8]) -> bool {
+    match image::guess_format(buffer) {
+        Ok(image::ImageFormat::Jpeg) => jpeg_end(buffer).is_none(),
+        Ok(image::ImageFormat::Png) => png_end(buffer).is_none(),
+        // GIF's blocks are small and many; its trailer is just the last byte,
+        // before any padding.
+        Ok(image::ImageFormat::Gif) => buffer.iter().rev().find(|x| **x != 0) != Some(&0x3B),
+        // The RIFF header gives the size of everything after its first 8
+        // bytes.
+        Ok(image::ImageFormat::WebP) => buffer.get(4..8)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()) as usize)
+            .is_some_and(|size| buffer.len() < size.saturating_add(8)),
+        _ => false,
+    }
+}
+
+// Where the JPEG's EOI marker ends, following its segments from the start,
+// and through entropy-coded data after each SOS, where FF is only a marker if
+// it isn't followed by 00 (a stuffed byte) or a restart marker.
+fn jpeg_end(buffer: &[u8]) -> Option<usize> {
+    let mut pos = 2; // SOI
+    loop {
+        if *buffer.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *buffer.get(pos + 1)?;
+        match marker {
+            0xD9 => return Some(pos + 2),
+            0xFF => pos += 1, // Fill byte
+            0x01 | 0xD0..=0xD7 => pos += 2, // No length
+            _ => {
+                let len = u16::from_be_bytes([*buffer.get(pos + 2)?, *buffer.get(pos + 3)?]) as usize;
+                pos += 2 + len;
+                if marker == 0xDA {
+                    while !(buffer.get(pos) == Some(&0xFF)
+                        && buffer.get(pos + 1).is_some_and(|x| *x != 0 && !(0xD0..=0xD7).contains(x))) {
+                        if pos >= buffer.len() {
+                            return None;
+                        }
+                        pos += 1;
+                    }
+                }
+            },
+        }
+    }
+}
+
+// Where the PNG's IEND chunk ends, following its chunks from the signature.
+fn png_end(buffer: &[u8]) -> Option<usize> {
+    let mut pos = 8; // Signature
+    loop {
+        let len = u32::from_be_bytes(buffer.get(pos..pos + 4)?.try_into().unwrap()) as usize;
+        let end = pos.checked_add(12 + len)?; // Length, type, data, CRC
+        if end > buffer.len() {
+            return None;
+        }
+        if &buffer[pos + 4..pos + 8] == b"IEND" {
+            return Some(end);
+        }
+        pos = end;
+    }
+}
+
+fn solid(image: &DynamicImage) -> bool {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return true;
+    }
+    let first = image.get_pixel(0, 0).0;
+    let xs = (0..GRID.min(width)).map(|i| i * width / GRID.min(width));
+    xs.flat_map(|x| (0..GRID.min(height)).map(move |j| (x, j * height / GRID.min(height))))
+        .all(|(x, y)| {
+            let pixel = image.get_pixel(x, y).0;
+            pixel.iter().zip(first).all(|(a, b)| a.abs_diff(b) <= SOLID_TOLERANCE)
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::search_harness::encode_image;
+
+    fn photo() -> image::RgbImage {
+        image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8]))
+    }
+
+    fn decode(buffer: &[u8]) -> Option<DynamicImage> {
+        image::load_from_memory(buffer).ok()
+    }
+
+    #[test]
+    fn empty_files() {
+        assert_eq!(check(&[], None), Some(BrokenKind::Empty));
+        assert_eq!(check(&[0; 4096], None), Some(BrokenKind::Empty));
+    }
+
+    #[test]
+    fn truncated_files() {
+        for ext in ["jpg", "png"] {
+            let whole = encode_image(&photo(), ext);
+            assert_eq!(check(&whole, decode(&whole).as_ref()), None, "{ext}");
+
+            let half = &whole[..whole.len() / 2];
+            assert_eq!(check(half, decode(half).as_ref()), Some(BrokenKind::Truncated), "{ext}");
+
+            // A copy that stopped partway through the space it had reserved
+            let mut padded = half.to_vec();
+            padded.resize(whole.len(), 0);
+            assert_eq!(check(&padded, None), Some(BrokenKind::Truncated), "{ext}");
+        }
+
+        // Garbage isn't a format, so it can't be said to be cut short
+        assert_eq!(check(b"not an image at all", None), None);
+    }
+
+    #[test]
+    fn data_after_the_end_marker_isnt_truncation() {
+        // As motion photos and some cameras' metadata have, with marker-like
+        // bytes in it
+        let mut trailer = vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x10];
+        trailer.extend((0..4096).map(|x| (x * 31 % 251) as u8));
+        for ext in ["jpg", "png"] {
+            let mut buffer = encode_image(&photo(), ext);
+            buffer.extend(&trailer);
+            let decoded = decode(&buffer);
+            assert!(decoded.is_some(), "{ext}");
+            assert_eq!(check(&buffer, decoded.as_ref()), None, "{ext}");
+            // Even if it hadn't decoded, its end marker's where it should be
+            assert_eq!(check(&buffer, None), None, "{ext}");
+        }
+    }
+
+    #[test]
+    fn solid_images() {
+        let gray = image::RgbImage::from_pixel(64, 48, image::Rgb([128, 128, 128]));
+        for ext in ["jpg", "png"] {
+            let buffer = encode_image(&gray, ext);
+            assert_eq!(check(&buffer, decode(&buffer).as_ref()), Some(BrokenKind::Solid), "{ext}");
+        }
+
+        // Mostly flat, like a scan of a blank page with a mark on it
+        let mut marked = gray.clone();
+        marked.put_pixel(32, 24, image::Rgb([0, 0, 0]));
+        let buffer = encode_image(&marked, "png");
+        assert_eq!(check(&buffer, decode(&buffer).as_ref()), None);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::misc::{data_dir, write_atomic};
+use crate::content::ContentKind;
+use crate::os_path;
+use crate::session::to_secs;
+
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+const CHECKPOINT_VERSION: u32 = 1;
+
+// A search's progress so far, saved periodically so a scan that's canceled or
+// killed partway through can pick up where it left off. There's only ever one
+// (so only one search runs at a time; see searcher::search_running()); starting
+// a new search replaces it, and finishing one deletes it.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub fingerprint: String, // See SearchOpts::fingerprint()
+    #[serde(with = "os_path::serde_path")]
+    pub root: PathBuf,
+    #[serde(default)]
+    pub files: Vec<CheckpointFile>, // Every file hashed so far
+    #[serde(default)]
+    pub total: Option<usize>, // Images the search found, if its walk got to the end
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CheckpointFile {
+    #[serde(with = "os_path::serde_path")]
+    pub path: PathBuf,
+    pub hash: String, // Base64
+    pub bytes: u64,
+    #[serde(default)]
+    pub mtime: Option<u64>, // Seconds since the Unix epoch
+    #[serde(default)]
+    pub frame_count: Option<usize>, // Only for animations
+    #[serde(default)]
+    pub color: Option<Vec<u8>>, // Only with verify_color
+    #[serde(default)]
+    pub content: Option<ContentKind>,
+}
+
+impl Checkpoint {
+    pub fn new(fingerprint: String, root: PathBuf, files: Vec<CheckpointFile>, total: Option<usize>) -> Checkpoint {
+        Checkpoint{version: CHECKPOINT_VERSION, fingerprint, root, files, total}
+    }
+
+    // How much of the search is done, if it's known how big it is.
+    pub fn percent_done(&self) -> Option<usize> {
+        let total = self.total.filter(|&x| x > 0)?;
+        Some((self.files.len() * 100 / total).min(100))
+    }
+
+    fn path() -> Result<PathBuf, String> {
+        Ok(data_dir()?.join(CHECKPOINT_FILE))
+    }
+
+    // None if there isn't one, or it can't be used; a checkpoint is only ever
+    // a head start, so there's nothing worth reporting.
+    pub fn load() -> Option<Checkpoint> {
+        Self::load_from(&Self::path().ok()?)
+    }
+
+    fn load_from(path: &Path) -> Option<Checkpoint> {
+        let json = std::fs::read(path).ok()?;
+        let checkpoint: Checkpoint = serde_json::from_slice(&json).ok()?;
+        (checkpoint.version == CHECKPOINT_VERSION).then_some(checkpoint)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&Self::path()?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| format!("Error serializing checkpoint: {e}"))?;
+        write_atomic(path, &json)
+    }
+
+    pub fn delete() {
+        if let Ok(path) = Self::path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl CheckpointFile {
+    // Whether the file's as it was hashed, going by its size and mtime (to
+    // the second, as sessions keep them). One without an mtime, e.g. from an
+    // older checkpoint, is hashed again to be safe.
+    pub fn is_current(&self, meta: &Metadata) -> bool {
+        meta.len() == self.bytes
+            && self.mtime.is_some()
+            && meta.modified().ok().and_then(to_secs) == self.mtime
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoints_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("a.jpg");
+        std::fs::write(&image, b"not really").unwrap();
+        let meta = std::fs::metadata(&image).unwrap();
+        let file = CheckpointFile {
+            path: image.clone(),
+            hash: "AAAA".to_string(),
+            bytes: meta.len(),
+            mtime: meta.modified().ok().and_then(to_secs),
+            frame_count: Some(3),
+            color: Some(vec![1, 2, 3]),
+            content: None,
+        };
+        let path = dir.path().join(CHECKPOINT_FILE);
+        Checkpoint::new("fingerprint".to_string(), dir.path().to_path_buf(), vec![file], Some(4))
+            .save_to(&path)
+            .unwrap();
+
+        let loaded = Checkpoint::load_from(&path).unwrap();
+        assert_eq!(loaded.fingerprint, "fingerprint");
+        assert_eq!(loaded.root, dir.path());
+        assert_eq!(loaded.total, Some(4));
+        assert_eq!(loaded.percent_done(), Some(25));
+        let file = &loaded.files[0];
+        assert_eq!((&file.path, &*file.hash, file.frame_count), (&image, "AAAA", Some(3)));
+        assert_eq!(file.color.as_deref(), Some(&[1, 2, 3][..]));
+        assert!(file.is_current(&meta));
+
+        std::fs::write(&image, b"rewritten, longer").unwrap();
+        assert!(!file.is_current(&std::fs::metadata(&image).unwrap()));
+
+        // Another version's isn't used
+        let newer = serde_json::to_vec(&Checkpoint{version: CHECKPOINT_VERSION + 1, ..loaded}).unwrap();
+        std::fs::write(&path, newer).unwrap();
+        assert!(Checkpoint::load_from(&path).is_none());
+    }
+}
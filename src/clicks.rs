@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use eframe::egui;
+
+
+// What clicking a result's thumbnail does, for each kind of click. App-wide,
+// like the language, so it can be changed from either phase mid-review; App
+// loads and saves it.
+static ACTIONS: [AtomicU8; 3] = [
+    AtomicU8::new(ClickAction::Select as u8),
+    AtomicU8::new(ClickAction::Open as u8),
+    AtomicU8::new(ClickAction::Nothing as u8),
+];
+
+// egui counts a second click as a double-click if it comes within this long
+// of the first (its own constant is private). A single click waits this long
+// before doing anything, in case it turns out to be the start of one.
+pub const DOUBLE_CLICK_DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Click {
+    Single,
+    Double,
+    Middle,
+}
+
+impl Click {
+    pub const ALL: [Click; 3] = [Click::Single, Click::Double, Click::Middle];
+
+    fn label(self) -> &'static str {
+        match self {
+            Click::Single => tr!("Click:"),
+            Click::Double => tr!("Double-click:"),
+            Click::Middle => tr!("Middle-click:"),
+        }
+    }
+
+    // What it's currently set to do.
+    pub fn action(self) -> ClickAction {
+        ClickAction::ALL[ACTIONS[self as usize].load(Ordering::Relaxed) as usize]
+    }
+
+    fn set_action(self, action: ClickAction) {
+        ACTIONS[self as usize].store(action as u8, Ordering::Relaxed);
+    }
+
+    // The response's click, if it was clicked this frame. A double-click is
+    // also reported as a click on its second release, so it's checked first.
+    pub fn of(resp: &egui::Response) -> Option<Click> {
+        if resp.double_clicked() {
+            Some(Click::Double)
+        } else if resp.clicked() {
+            Some(Click::Single)
+        } else if resp.middle_clicked() {
+            Some(Click::Middle)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClickAction {
+    Nothing, // E.g., to avoid opening things by accident while scrolling
+    Select,
+    Open, // In the default viewer
+    Reveal, // In the file manager
+    Preview, // In a window in the app
+}
+
+impl ClickAction {
+    pub const ALL: [ClickAction; 5] = [
+        ClickAction::Nothing,
+        ClickAction::Select,
+        ClickAction::Open,
+        ClickAction::Reveal,
+        ClickAction::Preview,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ClickAction::Nothing => tr!("Nothing"),
+            ClickAction::Select => tr!("Select"),
+            ClickAction::Open => tr!("Open"),
+            ClickAction::Reveal => tr!("Show in file manager"),
+            ClickAction::Preview => tr!("Preview in app"),
+        }
+    }
+
+    fn to_key(self) -> &'static str {
+        match self {
+            ClickAction::Nothing => "nothing",
+            ClickAction::Select => "select",
+            ClickAction::Open => "open",
+            ClickAction::Reveal => "reveal",
+            ClickAction::Preview => "preview",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<ClickAction> {
+        ClickAction::ALL.into_iter().find(|x| x.to_key() == key)
+    }
+}
+
+// The current actions, for storage: one key per click, in order, separated by
+// commas.
+pub fn to_key() -> String {
+    Click::ALL.map(|x| x.action().to_key()).join(",")
+}
+
+// Whether every action in it is one there is. Older versions may have stored
+// fewer, which leaves the rest as they are.
+pub fn is_valid_key(key: &str) -> bool {
+    key.split(',').count() <= Click::ALL.len() && key.split(',').all(|x| ClickAction::from_key(x).is_some())
+}
+
+// Ignores anything it doesn't recognize, leaving those clicks as they were.
+pub fn load_key(key: &str) {
+    for (click, action) in Click::ALL.into_iter().zip(key.split(',')) {
+        if let Some(action) = ClickAction::from_key(action) {
+            click.set_action(action);
+        }
+    }
+}
+
+// A button for the phases' headers, opening the settings.
+pub fn settings_button(ui: &mut egui::Ui) {
+    ui.menu_button("🖱", |ui| {
+        egui::Grid::new("click_actions").num_columns(2).show(ui, |ui| {
+            for click in Click::ALL {
+                ui.label(click.label());
+                let mut action = click.action();
+                egui::ComboBox::from_id_source(("click_action", click as u8))
+                    .selected_text(action.label())
+                    .show_ui(ui, |ui| {
+                        for x in ClickAction::ALL {
+                            ui.selectable_value(&mut action, x, x.label());
+                        }
+                    });
+                click.set_action(action);
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        crate::frame_stats::toggle(ui);
+    }).response.on_hover_text(tr!("What clicking a thumbnail does"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip() {
+        assert_eq!(to_key(), "select,open,nothing");
+        load_key("reveal,bogus,preview,extra");
+        assert_eq!(Click::Single.action(), ClickAction::Reveal);
+        assert_eq!(Click::Double.action(), ClickAction::Open);
+        assert_eq!(Click::Middle.action(), ClickAction::Preview);
+        assert_eq!(to_key(), "reveal,open,preview");
+        load_key("select,open,nothing");
+    }
+}
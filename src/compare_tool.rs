@@ -0,0 +1,83 @@
+use crate::hook;
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+
+// A user-configured tool to open a pair of images in, e.g. to pixel-diff
+// them. The template is split into words like the hook's (see hook::parse),
+// and never run through a shell. {a} and {b} become the two paths; as words
+// by themselves, each is passed as one argument exactly, whatever's in it.
+
+pub const A: &str = "{a}";
+pub const B: &str = "{b}";
+
+// Splits the template into words, checking both paths go somewhere.
+pub fn parse(template: &str) -> Result<Vec<String>, String> {
+    let words = hook::parse(template)?;
+    for placeholder in [A, B] {
+        if !words.iter().any(|x| x.contains(placeholder)) {
+            return Err(format!("The command needs {placeholder} for one of the images"));
+        }
+    }
+    Ok(words)
+}
+
+// The command's arguments with the placeholders filled in. Words with a
+// placeholder among other text get the path as text.
+fn expand(words: &[String], a: &Path, b: &Path) -> Vec<OsString> {
+    words.iter()
+        .map(|word| match word.as_str() {
+            A => a.as_os_str().to_owned(),
+            B => b.as_os_str().to_owned(),
+            _ => word.replace(A, &a.to_string_lossy()).replace(B, &b.to_string_lossy()).into(),
+        })
+        .collect()
+}
+
+// Starts the tool on the pair, without waiting for it. It's reaped in the
+// background, whenever it's closed.
+pub fn launch(template: &str, a: &Path, b: &Path) -> Result<(), String> {
+    let args = expand(&parse(template)?, a, b);
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Couldn't run {}: {e}", args[0].to_string_lossy()))?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_separate_arguments() {
+        let words = parse(r#"'/Applications/Beyond Compare.app/bcomp' "{a}" {b} --title="{a} vs {b}""#).unwrap();
+        assert_eq!(words, ["/Applications/Beyond Compare.app/bcomp", "{a}", "{b}", "--title={a} vs {b}"]);
+
+        assert!(parse("ksdiff {a}").is_err());
+        assert!(parse("ksdiff {a} 'oops").is_err());
+        assert!(parse("").is_err());
+
+        let (a, b) = (Path::new("/photos/my \"best\" shot.jpg"), Path::new("/it's a copy.jpg"));
+        let args = expand(&words, a, b);
+        assert_eq!(args, [
+            "/Applications/Beyond Compare.app/bcomp",
+            "/photos/my \"best\" shot.jpg",
+            "/it's a copy.jpg",
+            "--title=/photos/my \"best\" shot.jpg vs /it's a copy.jpg",
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn launching_reports_missing_tools() {
+        let err = launch("/nonexistent/ksdiff {a} {b}", Path::new("/a.jpg"), Path::new("/b.jpg")).unwrap_err();
+        assert!(err.starts_with("Couldn't run /nonexistent/ksdiff"), "{err}");
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use image::{DynamicImage, GenericImageView};
+
+use serde::{Serialize, Deserialize};
+
+
+// A rough idea of what an image is, so screenshots and saved graphics can be
+// told apart from photos at a glance. Only ever shown and filtered on; it has
+// no say in what's a duplicate.
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Photo,
+    Screenshot, // A PNG exactly the size of a common screen
+    Graphic, // Few colors or large flat areas, e.g. memes, diagrams and text
+}
+
+// Common screen sizes in pixels, landscape; portrait is checked too.
+const SCREEN_SIZES: &[(u32, u32)] = &[
+    // Desktops and laptops
+    (1280, 720), (1280, 800), (1280, 1024), (1366, 768), (1440, 900), (1536, 864),
+    (1600, 900), (1680, 1050), (1920, 1080), (1920, 1200), (2560, 1080), (2560, 1440),
+    (2560, 1600), (2880, 1800), (3024, 1964), (3440, 1440), (3456, 2234), (3840, 2160),
+    (5120, 2880),
+    // Phones
+    (1136, 640), (1334, 750), (1792, 828), (2208, 1242), (2340, 1080),
+    (2400, 1080), (2436, 1125), (2532, 1170), (2556, 1179), (2688, 1242), (2778, 1284),
+    (2796, 1290), (3120, 1440), (3200, 1440),
+    // Tablets
+    (2048, 1536), (2160, 1620), (2360, 1640), (2388, 1668), (2732, 2048),
+];
+
+impl ContentKind {
+    pub const ALL: [ContentKind; 3] = [ContentKind::Photo, ContentKind::Screenshot, ContentKind::Graphic];
+
+    // Pixels sampled along each side. Sampling, rather than scaling, keeps
+    // this to a few thousand reads whatever the image's size.
+    const GRID: u32 = 48;
+
+    // Eyeballed. Photos, even dull ones, have hundreds of distinct colors at
+    // this quantization and little exact repetition between neighbors.
+    const MAX_GRAPHIC_COLORS: usize = 48;
+    const MIN_GRAPHIC_FLAT_SHARE: f32 = 0.6;
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ContentKind::Photo => tr!("Photo"),
+            ContentKind::Screenshot => tr!("Screenshot"),
+            ContentKind::Graphic => tr!("Graphic"),
+        }
+    }
+
+    pub fn plural_label(self) -> &'static str {
+        match self {
+            ContentKind::Photo => tr!("Photos"),
+            ContentKind::Screenshot => tr!("Screenshots"),
+            ContentKind::Graphic => tr!("Graphics"),
+        }
+    }
+
+    // For exports, which shouldn't change with the language.
+    pub fn key(self) -> &'static str {
+        match self {
+            ContentKind::Photo => "photo",
+            ContentKind::Screenshot => "screenshot",
+            ContentKind::Graphic => "graphic",
+        }
+    }
+
+    // What a file of these dimensions is, given what its pixels said (see
+    // from_pixels()), and whether it's a PNG. Apart, since byte-identical
+    // copies share their pixels but not their names.
+    pub fn with_format(self, (width, height): (u32, u32), png: bool) -> ContentKind {
+        let screen_sized = SCREEN_SIZES.iter()
+            .any(|&(w, h)| (width, height) == (w, h) || (width, height) == (h, w));
+        if png && screen_sized { ContentKind::Screenshot } else { self }
+    }
+
+    // Photo or Graphic, going by the pixels alone.
+    pub fn from_pixels(image: &DynamicImage) -> ContentKind {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return ContentKind::Photo;
+        }
+
+        // Colors are quantized to 4 bits a channel, so noise and compression
+        // artifacts don't count as distinct colors.
+        let (cols, rows) = (Self::GRID.min(width), Self::GRID.min(height));
+        let mut colors = HashSet::new();
+        let mut flat = 0;
+        for row in 0..rows {
+            let y = row * height / rows;
+            let mut last = None;
+            for col in 0..cols {
+                let [r, g, b, _] = image.get_pixel(col * width / cols, y).0;
+                let color = (r >> 4, g >> 4, b >> 4);
+                colors.insert(color);
+                if last == Some(color) {
+                    flat += 1;
+                }
+                last = Some(color);
+            }
+        }
+        let flat_share = flat as f32 / (rows * cols.saturating_sub(1)).max(1) as f32;
+        if colors.len() <= Self::MAX_GRAPHIC_COLORS || flat_share >= Self::MIN_GRAPHIC_FLAT_SHARE {
+            ContentKind::Graphic
+        } else {
+            ContentKind::Photo
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32) -> DynamicImage {
+        let mut rng = fastrand::Rng::with_seed(7);
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |_, _| {
+            image::Rgb([rng.u8(..), rng.u8(..), rng.u8(..)])
+        }))
+    }
+
+    // A few flat shapes on a white background, like a diagram.
+    fn diagram(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            match (x * 4 / width, y * 3 / height) {
+                (1, 1) => image::Rgb([200, 30, 30]),
+                (2, 2) => image::Rgb([30, 30, 200]),
+                _ => image::Rgb([255, 255, 255]),
+            }
+        }))
+    }
+
+    #[test]
+    fn pixels_tell_photos_from_graphics() {
+        assert_eq!(ContentKind::from_pixels(&noise(640, 480)), ContentKind::Photo);
+        assert_eq!(ContentKind::from_pixels(&noise(20, 10)), ContentKind::Photo);
+        assert_eq!(ContentKind::from_pixels(&diagram(640, 480)), ContentKind::Graphic);
+        assert_eq!(ContentKind::from_pixels(&diagram(20, 10)), ContentKind::Graphic);
+
+        // Mostly flat, but with a photo pasted in: still many colors, but
+        // the flat share alone makes it a graphic.
+        let mut meme = diagram(640, 480).to_rgb8();
+        let photo = noise(160, 480).to_rgb8();
+        image::imageops::replace(&mut meme, &photo, 0, 0);
+        assert_eq!(ContentKind::from_pixels(&DynamicImage::ImageRgb8(meme)), ContentKind::Graphic);
+
+        assert_eq!(ContentKind::from_pixels(&DynamicImage::new_rgb8(0, 0)), ContentKind::Photo);
+    }
+
+    #[test]
+    fn screen_sized_pngs_are_screenshots() {
+        for kind in [ContentKind::Photo, ContentKind::Graphic] {
+            assert_eq!(kind.with_format((1920, 1080), true), ContentKind::Screenshot);
+            assert_eq!(kind.with_format((1170, 2532), true), ContentKind::Screenshot);
+            assert_eq!(kind.with_format((1920, 1080), false), kind);
+            assert_eq!(kind.with_format((1921, 1080), true), kind);
+            assert_eq!(kind.with_format((4000, 3000), true), kind);
+        }
+    }
+}
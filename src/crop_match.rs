@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use image::DynamicImage;
+
+use rayon::prelude::*;
+
+
+// Experimental detection of crops: images that are part of another image,
+// which no whole-image hash will match. Each image gets a small grayscale
+// thumbnail; for a pair that passes a cheap color pre-filter, the would-be
+// crop is cut into a grid of tiles, and each tile's average hash is compared
+// against the corresponding region of the other image, at a range of scales
+// and offsets. If enough tiles line up under one placement, it's a match.
+
+#[derive(Clone)]
+pub struct CropSig {
+    width: u32, // Of the thumbnail
+    height: u32,
+    gray: Vec<u8>, // The thumbnail, row major
+    mean: [u8; 3], // Mean color
+}
+
+// A thumbnail's summed-area table, for quick means of arbitrary boxes. Built
+// per comparison rather than kept with each signature, since it's four times
+// the size.
+struct Summed {
+    width: u32,
+    height: u32,
+    table: Vec<u32>, // (width + 1) * (height + 1)
+}
+
+// Grid of tiles the would-be crop is cut into, per side.
+const TILES: u32 = 4;
+
+// Cells per side of each tile's average hash.
+const CELLS: u32 = 8;
+
+impl CropSig {
+    // Longest side of the thumbnail. Crops are searched for at this
+    // resolution, so it trades precision for time and memory (this many
+    // squared bytes per image).
+    const SIZE: u32 = 64;
+
+    // Thumbnails with a shorter side than this are too small to tile.
+    const MIN_SIDE: u32 = TILES * 4;
+
+    pub fn new(image: &DynamicImage) -> CropSig {
+        let thumb = image.thumbnail(Self::SIZE, Self::SIZE);
+        let rgb = thumb.to_rgb8();
+        let pixels = (rgb.width() * rgb.height()).max(1) as u64;
+        let mut sums = [0u64; 3];
+        for pixel in rgb.pixels() {
+            for (sum, x) in sums.iter_mut().zip(pixel.0) {
+                *sum += x as u64;
+            }
+        }
+        let mean = sums.map(|x| (x / pixels) as u8);
+
+        let gray = thumb.to_luma8();
+        let (width, height) = gray.dimensions();
+        CropSig{width, height, gray: gray.into_raw(), mean}
+    }
+
+    fn summed(&self) -> Summed {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let stride = width + 1;
+        let mut table = vec![0u32; stride * (height + 1)];
+        for y in 0..height {
+            let mut row = 0;
+            for x in 0..width {
+                row += self.gray[y * width + x] as u32;
+                table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row;
+            }
+        }
+        Summed{width: self.width, height: self.height, table}
+    }
+
+    fn luminance(&self) -> u32 {
+        self.mean.iter().map(|x| *x as u32).sum::<u32>() / 3
+    }
+
+    // Cropping off the edges shifts the average color a little, but not much.
+    fn similar_color(&self, other: &CropSig) -> bool {
+        const THRESHOLD: u32 = 24;
+        let diff: u32 = self.mean.iter().zip(other.mean)
+            .map(|(a, b)| a.abs_diff(b) as u32)
+            .sum();
+        diff / 3 <= THRESHOLD
+    }
+
+    // Whether crop looks like part of self.
+    fn contains(&self, crop: &CropSig) -> bool {
+        let min_side = |x: &CropSig| x.width.min(x.height);
+        if min_side(self) < Self::MIN_SIDE || min_side(crop) < Self::MIN_SIDE {
+            return false;
+        }
+        self.summed().contains(&crop.summed())
+    }
+}
+
+impl Summed {
+    // Mean brightness of the box, in thumbnail coordinates. Rounded to whole
+    // pixels, and never empty.
+    fn box_mean(&self, x0: f32, y0: f32, x1: f32, y1: f32) -> u32 {
+        let clamp = |v: f32, max: u32| (v.round().max(0.0) as u32).min(max);
+        let (x0, y0) = (clamp(x0, self.width - 1), clamp(y0, self.height - 1));
+        let (x1, y1) = (clamp(x1, self.width).max(x0 + 1), clamp(y1, self.height).max(y0 + 1));
+        let stride = self.width as usize + 1;
+        let at = |x: u32, y: u32| self.table[y as usize * stride + x as usize];
+        let sum = at(x1, y1) + at(x0, y0) - at(x1, y0) - at(x0, y1);
+        sum / ((x1 - x0) * (y1 - y0))
+    }
+
+    // Average hash of the region: a bit per cell, set if it's brighter than
+    // the region as a whole.
+    fn region_hash(&self, x: f32, y: f32, width: f32, height: f32) -> u64 {
+        let (cell_w, cell_h) = (width / CELLS as f32, height / CELLS as f32);
+        let mut cells = [0u32; (CELLS * CELLS) as usize];
+        for row in 0..CELLS {
+            for col in 0..CELLS {
+                let cx = x + col as f32 * cell_w;
+                let cy = y + row as f32 * cell_h;
+                cells[(row * CELLS + col) as usize] = self.box_mean(cx, cy, cx + cell_w, cy + cell_h);
+            }
+        }
+        let mean = cells.iter().sum::<u32>() / cells.len() as u32;
+        cells.iter().enumerate().fold(0, |hash, (idx, x)| hash | (((*x > mean) as u64) << idx))
+    }
+
+    fn contains(&self, crop: &Summed) -> bool {
+        // Max differing bits for a tile to match.
+        const TILE_THRESHOLD: u32 = 10;
+        // Min matching tiles, and min tiles with enough detail to compare
+        // at all. Flat tiles (sky, walls) match anything.
+        const MIN_MATCHES: usize = 12;
+        const MIN_DETAILED: usize = 8;
+        // Fraction of self's longer side the crop covers at each scale tried.
+        // Much below half, there's too little left of it to go on; at 1.0,
+        // it's not a crop, and the hash would have caught it.
+        const SCALES: [f32; 6] = [0.5, 0.6, 0.7, 0.8, 0.9, 0.95];
+        // Pixels between offsets tried. Tiles are several pixels across, and
+        // the threshold absorbs being a pixel off.
+        const STEP: usize = 2;
+
+        let (tile_w, tile_h) = (crop.width as f32 / TILES as f32, crop.height as f32 / TILES as f32);
+        let tiles: Vec<(u32, u32, u64)> = (0..TILES * TILES)
+            .map(|idx| (idx % TILES, idx / TILES))
+            .map(|(col, row)| (col, row, crop.region_hash(col as f32 * tile_w, row as f32 * tile_h, tile_w, tile_h)))
+            .filter(|(_, _, hash)| (CELLS..=CELLS * CELLS - CELLS).contains(&hash.count_ones()))
+            .collect();
+        if tiles.len() < MIN_DETAILED {
+            return false;
+        }
+        let allowed_misses = tiles.len().saturating_sub(MIN_MATCHES.min(tiles.len()));
+
+        for scale in SCALES {
+            let longer = crop.width.max(crop.height) as f32;
+            let factor = scale * self.width.max(self.height) as f32 / longer;
+            let (width, height) = (crop.width as f32 * factor, crop.height as f32 * factor);
+            if width > self.width as f32 || height > self.height as f32 {
+                continue;
+            }
+            let (tile_w, tile_h) = (width / TILES as f32, height / TILES as f32);
+
+            for y in (0..=(self.height as f32 - height) as u32).step_by(STEP) {
+                for x in (0..=(self.width as f32 - width) as u32).step_by(STEP) {
+                    let mut misses = 0;
+                    for (col, row, hash) in &tiles {
+                        let region = self.region_hash(
+                            x as f32 + *col as f32 * tile_w,
+                            y as f32 + *row as f32 * tile_h,
+                            tile_w,
+                            tile_h,
+                        );
+                        if (region ^ hash).count_ones() > TILE_THRESHOLD {
+                            misses += 1;
+                            if misses > allowed_misses {
+                                break;
+                            }
+                        }
+                    }
+                    if misses <= allowed_misses {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+// Each image is only pre-filtered against this many others, the nearest in
+// brightness, so the pre-filter stays linear.
+const NEIGHBORS: usize = 200;
+
+// Returns pairs of images where one looks like a crop of the other, and
+// whether the budget ran out (so some may have been missed). At most budget
+// pairs get the full comparison. Pairs for which skip() returns true (e.g.
+// because they're already grouped) aren't considered.
+pub fn find_crops(
+    sigs: &[(PathBuf, CropSig)],
+    skip: impl Fn(&Path, &Path) -> bool,
+    budget: usize,
+    cancel: &AtomicBool,
+) -> (Vec<(PathBuf, PathBuf)>, bool) {
+    let mut order: Vec<usize> = (0..sigs.len()).collect();
+    order.sort_by_key(|x| sigs[*x].1.luminance());
+
+    let mut candidates = vec![];
+    let mut exhausted = false;
+    'outer: for (pos, a) in order.iter().enumerate() {
+        for b in order.iter().skip(pos + 1).take(NEIGHBORS) {
+            let ((path_a, sig_a), (path_b, sig_b)) = (&sigs[*a], &sigs[*b]);
+            if !sig_a.similar_color(sig_b) || skip(path_a, path_b) {
+                continue;
+            }
+            if candidates.len() == budget {
+                exhausted = true;
+                break 'outer;
+            }
+            candidates.push((*a, *b));
+        }
+    }
+
+    let pairs = candidates.into_par_iter()
+        .filter(|(a, b)| {
+            if cancel.load(Ordering::Relaxed) {
+                return false;
+            }
+            let (sig_a, sig_b) = (&sigs[*a].1, &sigs[*b].1);
+            sig_a.contains(sig_b) || sig_b.contains(sig_a)
+        })
+        .map(|(a, b)| (sigs[a].0.clone(), sigs[b].0.clone()))
+        .collect();
+    (pairs, exhausted)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Random gray blocks, so every tile has detail and no two seeds look
+    // alike.
+    fn blocks(seed: u64) -> DynamicImage {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let cells: Vec<u8> = (0..16 * 12).map(|_| rng.u8(..)).collect();
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(512, 384, |x, y| {
+            image::Rgb([cells[(y / 32 * 16 + x / 32) as usize]; 3])
+        }))
+    }
+
+    fn find(images: &[(&str, DynamicImage)], budget: usize) -> (Vec<(PathBuf, PathBuf)>, bool) {
+        let sigs: Vec<(PathBuf, CropSig)> = images.iter()
+            .map(|(name, image)| (PathBuf::from(name), CropSig::new(image)))
+            .collect();
+        find_crops(&sigs, |_, _| false, budget, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn crops_are_found_in_their_originals() {
+        let original = blocks(1);
+        // 0.7 of the original's width, one of the scales tried.
+        let crop = original.crop_imm(96, 64, 358, 268);
+        let (pairs, exhausted) = find(&[("original", original), ("crop", crop)], 100);
+        assert_eq!(pairs.len(), 1);
+        let (a, b) = &pairs[0];
+        let mut names = [a.to_str().unwrap(), b.to_str().unwrap()];
+        names.sort();
+        assert_eq!(names, ["crop", "original"]);
+        assert!(!exhausted);
+    }
+
+    #[test]
+    fn unrelated_images_are_not_crops() {
+        let (a, b) = (blocks(1), blocks(2));
+        // Close enough in color to be compared at all.
+        assert!(CropSig::new(&a).similar_color(&CropSig::new(&b)));
+        let crop = b.crop_imm(96, 64, 358, 268);
+        let (pairs, _) = find(&[("a", a), ("b crop", crop)], 100);
+        assert!(pairs.is_empty());
+    }
+
+    #[test]
+    fn flat_and_tiny_images_are_never_crops() {
+        let flat = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(512, 384, image::Rgb([128; 3])));
+        let flat_crop = flat.crop_imm(0, 0, 300, 200);
+        assert!(!CropSig::new(&flat).contains(&CropSig::new(&flat_crop)));
+
+        let original = blocks(3);
+        let sliver = original.crop_imm(0, 0, 512, 20);
+        assert!(!CropSig::new(&original).contains(&CropSig::new(&sliver)));
+    }
+
+    #[test]
+    fn the_budget_limits_comparisons() {
+        let original = blocks(1);
+        let crop = original.crop_imm(96, 64, 358, 268);
+        let images = [("original", original), ("crop", crop), ("other", blocks(2))];
+        let (pairs, exhausted) = find(&images, 0);
+        assert!(pairs.is_empty());
+        assert!(exhausted);
+    }
+}
@@ -0,0 +1,331 @@
+use image::ImageFormat;
+
+use serde::{Serialize, Deserialize};
+
+
+// How an image file is encoded, so copies of the same picture in different
+// formats or at different qualities can be told apart, and the best one
+// suggested as the keeper. Everything here is best effort: a file that can't
+// be parsed just has no encoding.
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Encoding {
+    pub format: String, // e.g. "PNG"
+    pub lossless: bool,
+    #[serde(default)]
+    pub jpeg: Option<JpegInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct JpegInfo {
+    pub quality: u8, // 1-100, as estimated from the luminance quantization table
+    #[serde(default)]
+    pub subsampling: Option<Subsampling>, // None for grayscale
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Subsampling {
+    S444,
+    S422,
+    S420,
+    Other,
+}
+
+impl Subsampling {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Subsampling::S444 => "4:4:4",
+            Subsampling::S422 => "4:2:2",
+            Subsampling::S420 => "4:2:0",
+            Subsampling::Other => "other",
+        }
+    }
+}
+
+impl Encoding {
+    pub fn detect(buffer: &[u8]) -> Option<Encoding> {
+        let format = image::guess_format(buffer).ok()?;
+        let lossless = match format {
+            ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tiff | ImageFormat::Gif
+                | ImageFormat::Qoi | ImageFormat::Pnm | ImageFormat::Tga => true,
+            // "VP8L" is the lossless bitstream; "VP8 " (or "VP8X" with one
+            // inside) is lossy.
+            ImageFormat::WebP => buffer.get(12..16) == Some(b"VP8L"),
+            _ => false,
+        };
+        let jpeg = (format == ImageFormat::Jpeg).then(|| parse_jpeg(buffer)).flatten();
+        Some(Encoding{format: format_name(format).to_string(), lossless, jpeg})
+    }
+
+    // e.g. "PNG", or "JPEG q92".
+    pub fn label(&self) -> String {
+        match self.jpeg {
+            Some(info) => format!("{} q{}", self.format, info.quality),
+            None => self.format.clone(),
+        }
+    }
+
+    // Higher is better: anything lossless beats anything lossy, and among
+    // JPEGs, higher quality and less subsampling win.
+    fn rank(&self) -> (bool, u8, u8) {
+        let (quality, chroma) = match self.jpeg {
+            Some(info) => {
+                let chroma = match info.subsampling {
+                    None | Some(Subsampling::S444) => 3,
+                    Some(Subsampling::S422) => 2,
+                    Some(Subsampling::S420) => 1,
+                    Some(Subsampling::Other) => 0,
+                };
+                (info.quality, chroma)
+            },
+            None => (0, 0),
+        };
+        (self.lossless, quality, chroma)
+    }
+}
+
+// Describes how a group's members' encodings differ, e.g. "PNG + JPEG" or
+// "JPEG q92 / q60", and picks the index of the one to keep. None if they
+// don't differ, or any member's encoding is unknown.
+pub fn compare_group(encodings: &[Option<&Encoding>]) -> Option<(String, usize)> {
+    let encodings: Vec<&Encoding> = encodings.iter().copied().collect::<Option<_>>()?;
+
+    let mut formats: Vec<&str> = vec![];
+    for encoding in &encodings {
+        if !formats.contains(&encoding.format.as_str()) {
+            formats.push(&encoding.format);
+        }
+    }
+
+    let badge = if formats.len() > 1 {
+        formats.join(" + ")
+    } else {
+        let mut qualities: Vec<u8> = encodings.iter().filter_map(|x| x.jpeg.map(|x| x.quality)).collect();
+        qualities.sort_unstable_by(|a, b| b.cmp(a));
+        qualities.dedup();
+        if qualities.len() < 2 {
+            return None;
+        }
+        let qualities: Vec<String> = qualities.iter().map(|x| format!("q{x}")).collect();
+        format!("{} {}", formats[0], qualities.join(" / "))
+    };
+
+    // The first of the best, so ties go to whichever sorts first.
+    let best = encodings.iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, x)| x.rank())
+        .map(|(idx, _)| idx)?;
+    Some((badge, best))
+}
+
+fn format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "PNG",
+        ImageFormat::Jpeg => "JPEG",
+        ImageFormat::Gif => "GIF",
+        ImageFormat::WebP => "WebP",
+        ImageFormat::Pnm => "PNM",
+        ImageFormat::Tiff => "TIFF",
+        ImageFormat::Tga => "TGA",
+        ImageFormat::Dds => "DDS",
+        ImageFormat::Bmp => "BMP",
+        ImageFormat::Ico => "ICO",
+        ImageFormat::Hdr => "HDR",
+        ImageFormat::OpenExr => "EXR",
+        ImageFormat::Farbfeld => "Farbfeld",
+        ImageFormat::Avif => "AVIF",
+        ImageFormat::Qoi => "QOI",
+        _ => "Other",
+    }
+}
+
+// The IJG (libjpeg) standard luminance quantization table, at quality 50.
+const STD_LUMINANCE: [u32; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61,
+    12, 12, 14, 19, 26, 58, 60, 55,
+    14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62,
+    18, 22, 37, 56, 68, 109, 103, 77,
+    24, 35, 55, 64, 81, 104, 113, 92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+// Walks the markers up to the first scan, reading the luminance quantization
+// table and the frame's sampling factors.
+fn parse_jpeg(buffer: &[u8]) -> Option<JpegInfo> {
+    if buffer.get(0..2) != Some(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut quality = None;
+    let mut subsampling = None;
+    let mut pos = 2;
+    loop {
+        if *buffer.get(pos)? != 0xFF {
+            return None;
+        }
+        // Any number of fill bytes can precede a marker.
+        while *buffer.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        let marker = buffer[pos + 1];
+        pos += 2;
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            continue; // No payload
+        }
+        if marker == 0xDA || marker == 0xD9 {
+            break; // Start of scan or end of image; the headers are over
+        }
+        let len = u16::from_be_bytes([*buffer.get(pos)?, *buffer.get(pos + 1)?]) as usize;
+        let segment = buffer.get(pos + 2..pos + len)?;
+        match marker {
+            0xDB => quality = quality.or(dqt_quality(segment)),
+            // Baseline, extended and progressive frames (0xC4 is a Huffman
+            // table, not a frame).
+            0xC0..=0xC2 => subsampling = Some(sof_subsampling(segment)?),
+            _ => (),
+        }
+        pos += len;
+    }
+    Some(JpegInfo{quality: quality?, subsampling: subsampling.flatten()})
+}
+
+// The quality the luminance table would have been scaled to by libjpeg. Other
+// encoders use other tables, but scale them similarly enough to compare.
+fn dqt_quality(mut segment: &[u8]) -> Option<u8> {
+    // A segment can hold several tables.
+    while let Some((&info, rest)) = segment.split_first() {
+        let (precision, id) = (info >> 4, info & 0x0F);
+        let size = if precision == 0 { 64 } else { 128 };
+        let table = rest.get(..size)?;
+        if id == 0 {
+            let sum: u32 = if precision == 0 {
+                table.iter().map(|x| *x as u32).sum()
+            } else {
+                table.chunks(2).map(|x| u16::from_be_bytes([x[0], x[1]]) as u32).sum()
+            };
+            // The inverse of libjpeg's scaling: 5000 / q below 50, 200 - 2q
+            // above.
+            let scale = sum as f32 * 100.0 / STD_LUMINANCE.iter().sum::<u32>() as f32;
+            let quality = if scale <= 100.0 { (200.0 - scale) / 2.0 } else { 5000.0 / scale };
+            return Some(quality.round().clamp(1.0, 100.0) as u8);
+        }
+        segment = &rest[size..];
+    }
+    None
+}
+
+// Some(None) for grayscale.
+fn sof_subsampling(segment: &[u8]) -> Option<Option<Subsampling>> {
+    let components = *segment.get(5)? as usize;
+    if components != 3 {
+        return Some(None);
+    }
+    // Each component is an id, sampling factors (h << 4 | v), and a table.
+    let factors = |idx: usize| segment.get(6 + idx * 3 + 1).map(|x| (x >> 4, x & 0x0F));
+    let (luma, cb, cr) = (factors(0)?, factors(1)?, factors(2)?);
+    if cb != cr || cb != (1, 1) {
+        return Some(Some(Subsampling::Other));
+    }
+    Some(Some(match luma {
+        (1, 1) => Subsampling::S444,
+        (2, 1) => Subsampling::S422,
+        (2, 2) => Subsampling::S420,
+        _ => Subsampling::Other,
+    }))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_harness::gradient;
+
+    fn jpeg<I>(image: &I, quality: u8) -> Vec<u8>
+    where
+        I: image::GenericImageView,
+        I::Pixel: image::PixelWithColorType,
+    {
+        let mut buffer = vec![];
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality)
+            .encode_image(image)
+            .unwrap();
+        buffer
+    }
+
+    #[test]
+    fn jpeg_quality_is_recovered_from_the_tables() {
+        let image = gradient(32, 24, false);
+        // Below about q25 baseline tables clamp at 255, and read as higher.
+        for quality in [25, 50, 75, 90, 95] {
+            let info = parse_jpeg(&jpeg(&image, quality)).unwrap();
+            // libjpeg rounds the scaled tables, so the inverse is only close.
+            assert!(info.quality.abs_diff(quality) <= 1, "q{quality} read as q{}", info.quality);
+            assert_eq!(info.subsampling, Some(Subsampling::S444));
+        }
+
+        let encoding = Encoding::detect(&jpeg(&image, 60)).unwrap();
+        assert_eq!(encoding.label(), "JPEG q60");
+        assert!(!encoding.lossless);
+    }
+
+    #[test]
+    fn grayscale_jpegs_have_no_subsampling() {
+        let image = image::DynamicImage::ImageRgb8(gradient(16, 16, false)).into_luma8();
+        let info = parse_jpeg(&jpeg(&image, 80)).unwrap();
+        assert_eq!(info.subsampling, None);
+    }
+
+    #[test]
+    fn tables_are_read_at_either_precision() {
+        // Quality 50 is the standard table unscaled.
+        let mut eight = vec![0x00];
+        eight.extend(STD_LUMINANCE.iter().map(|x| *x as u8));
+        assert_eq!(dqt_quality(&eight), Some(50));
+
+        let mut sixteen = vec![0x10];
+        sixteen.extend(STD_LUMINANCE.iter().flat_map(|x| (*x as u16).to_be_bytes()));
+        assert_eq!(dqt_quality(&sixteen), Some(50));
+
+        // The chrominance table is skipped to get to the luminance one.
+        let mut both = vec![0x01];
+        both.extend([99; 64]);
+        both.extend(&eight);
+        assert_eq!(dqt_quality(&both), Some(50));
+
+        assert_eq!(dqt_quality(&[0x01; 65]), None);
+        assert_eq!(dqt_quality(&eight[..40]), None);
+    }
+
+    #[test]
+    fn sampling_factors_are_named() {
+        let sof = |luma: u8, chroma: u8| {
+            vec![8, 0, 16, 0, 16, 3, 1, luma, 0, 2, chroma, 1, 3, chroma, 1]
+        };
+        assert_eq!(sof_subsampling(&sof(0x11, 0x11)), Some(Some(Subsampling::S444)));
+        assert_eq!(sof_subsampling(&sof(0x21, 0x11)), Some(Some(Subsampling::S422)));
+        assert_eq!(sof_subsampling(&sof(0x22, 0x11)), Some(Some(Subsampling::S420)));
+        assert_eq!(sof_subsampling(&sof(0x22, 0x21)), Some(Some(Subsampling::Other)));
+        assert_eq!(sof_subsampling(&sof(0x41, 0x11)), Some(Some(Subsampling::Other)));
+        assert_eq!(sof_subsampling(&sof(0x22, 0x11)[..10]), None);
+    }
+
+    #[test]
+    fn other_files_have_no_jpeg_info() {
+        use image::ImageEncoder;
+        let mut png = vec![];
+        image::codecs::png::PngEncoder::new(&mut png)
+            .write_image(gradient(8, 8, false).as_raw(), 8, 8, image::ColorType::Rgb8)
+            .unwrap();
+        assert_eq!(parse_jpeg(&png), None);
+        let encoding = Encoding::detect(&png).unwrap();
+        assert_eq!((encoding.label().as_str(), encoding.lossless), ("PNG", true));
+
+        let full = jpeg(&gradient(16, 16, false), 80);
+        // Cut off inside the quantization tables.
+        let dqt = full.windows(2).position(|x| x == [0xFF, 0xDB]).unwrap();
+        assert_eq!(parse_jpeg(&full[..dqt + 10]), None);
+        assert_eq!(parse_jpeg(b"not a jpeg"), None);
+    }
+}
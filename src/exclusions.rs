@@ -0,0 +1,212 @@
+use crate::archive::Namespace;
+use crate::misc::{Image, VersionedFile, group_id};
+use crate::os_path;
+
+use std::path::{Path, PathBuf};
+
+use image_hasher::HashAlg;
+
+use serde::{Serialize, Deserialize};
+
+
+// Single files the user has said aren't duplicates of the rest of their group
+// (e.g. deliberate exports next to a real duplicate pair), so they're taken
+// out of the group, in these results and future ones, without ignoring the
+// whole group the way the ignore list does. A group left with fewer than two
+// members is hidden.
+//
+// An exclusion is for the file in its group: it applies to the group as found
+// then, by group_id(), and, since that changes with any member coming or
+// going, to any group that still has one of the other members it had then, by
+// path. The file itself is
+// recognized by its path, or, once nothing's at that path any more, by its
+// hash and size, so it stays excluded after being moved or renamed. Files
+// excluded without a hash (e.g. ones matched by crop detection) are only ever
+// recognized by their path.
+
+const EXCLUDED_FILE: VersionedFile = VersionedFile{file: "excluded.json", name: "excluded files list", version: 1};
+
+#[derive(Serialize, Deserialize)]
+pub struct ExclusionList {
+    pub version: u32,
+    #[serde(default)]
+    pub entries: Vec<Exclusion>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Exclusion {
+    #[serde(with = "os_path::serde_path")]
+    pub path: PathBuf, // Canonical, as of when it was excluded
+    pub content: Option<ContentKey>,
+    #[serde(default)]
+    pub group: String, // group_id() of the group, whole, as it was found; empty from older versions
+    #[serde(with = "os_path::serde_paths")]
+    pub others: Vec<PathBuf>, // The rest of the group then, canonical, sorted
+}
+
+// The file's perceptual hash, as hashed in the namespace, and its size in
+// bytes.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ContentKey {
+    pub hash: HashAlg,
+    pub hash_size: u32,
+    pub value: String, // Base64
+    pub file_size: usize,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Exclusion {
+    // The member of the group, as hashed in the namespace. The group's whole,
+    // as the search found it, so it's recognized in the next one.
+    pub fn new(group: &[Image], member: usize, namespace: Namespace) -> Exclusion {
+        let image = &group[member];
+        let content = image.hash.as_ref().map(|hash| ContentKey {
+            hash: namespace.hash,
+            hash_size: namespace.hash_size,
+            value: hash.to_base64(),
+            file_size: image.file_size,
+        });
+        let mut others: Vec<PathBuf> = group.iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != member)
+            .map(|(_, x)| canonical(&x.path))
+            .collect();
+        others.sort();
+        Exclusion{path: canonical(&image.path), content, group: group_id(group), others}
+    }
+
+    // Whether it's for the group, given its members' canonical paths.
+    fn is_group(&self, id: &str, paths: &[PathBuf], member: usize) -> bool {
+        (!self.group.is_empty() && self.group == id)
+            || paths.iter().enumerate().any(|(idx, path)| idx != member && self.others.binary_search(path).is_ok())
+    }
+
+    // Whether it's the file, given its canonical path. Moved says nothing's
+    // at the excluded path any more.
+    fn is_file(&self, image: &Image, path: &Path, namespace: Namespace, moved: bool) -> bool {
+        if path == self.path {
+            return true;
+        }
+        let Some(content) = self.content.as_ref().filter(|_| moved) else {
+            return false;
+        };
+        Namespace{hash: content.hash, hash_size: content.hash_size} == namespace
+            && content.file_size == image.file_size
+            && image.hash.as_ref().is_some_and(|x| x.to_base64() == content.value)
+    }
+}
+
+impl Default for ExclusionList {
+    fn default() -> Self {
+        ExclusionList{version: EXCLUDED_FILE.version, entries: vec![]}
+    }
+}
+
+impl ExclusionList {
+    pub fn load() -> Result<ExclusionList, String> {
+        EXCLUDED_FILE.load()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        EXCLUDED_FILE.save(self)
+    }
+
+    // Which members of the group are excluded from it, by index.
+    fn excluded(&self, group: &[Image], namespace: Namespace, moved: &[bool]) -> Vec<usize> {
+        let paths: Vec<_> = group.iter().map(|x| canonical(&x.path)).collect();
+        let id = group_id(group);
+        (0..group.len())
+            .filter(|idx| self.entries.iter().zip(moved).any(|(entry, moved)| {
+                entry.is_file(&group[*idx], &paths[*idx], namespace, *moved)
+                    && entry.is_group(&id, &paths, *idx)
+            }))
+            .collect()
+    }
+
+    // Takes the excluded members out of the groups. Returns the groups to
+    // show, and, whole, those that had members taken out, to put back
+    // together from should an exclusion be removed.
+    pub fn apply(&self, groups: Vec<Vec<Image>>, namespace: Namespace) -> (Vec<Vec<Image>>, Vec<Vec<Image>>) {
+        if self.entries.is_empty() {
+            return (groups, vec![]); // Skip canonicalizing
+        }
+        let moved: Vec<bool> = self.entries.iter().map(|x| !x.path.exists()).collect();
+        let mut shown = vec![];
+        let mut whole = vec![];
+        for group in groups {
+            let excluded = self.excluded(&group, namespace, &moved);
+            if excluded.is_empty() {
+                shown.push(group);
+                continue;
+            }
+            let rest: Vec<Image> = group.iter()
+                .enumerate()
+                .filter(|(idx, _)| !excluded.contains(idx))
+                .map(|(_, x)| x.clone())
+                .collect();
+            if rest.len() >= 2 {
+                shown.push(rest);
+            }
+            whole.push(group);
+        }
+        (shown, whole)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image_hasher::ImageHash;
+
+    fn image(path: &Path, hash: &str, size: usize) -> Image {
+        let mut image = Image::new_lazy(path.to_path_buf(), size, None);
+        image.hash = Some(ImageHash::from_base64(hash).unwrap());
+        image
+    }
+
+    #[test]
+    fn excluded_files_leave_their_group_and_follow_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let paths: Vec<PathBuf> = ["a.jpg", "b.jpg", "export1.jpg", "export2.jpg"].iter().map(|x| dir.join(x)).collect();
+        for path in &paths {
+            std::fs::write(path, b"").unwrap();
+        }
+        let namespace = Namespace::new(HashAlg::Gradient);
+        let group: Vec<Image> = paths.iter()
+            .zip([100, 100, 200, 300])
+            .map(|(path, size)| image(path, "AAAAAAAAAAA", size))
+            .collect();
+
+        let mut list = ExclusionList::default();
+        list.entries.push(Exclusion::new(&group, 2, namespace));
+        list.entries.push(Exclusion::new(&group, 3, namespace));
+        let (shown, whole) = list.apply(vec![group.clone()], namespace);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].iter().map(|x| &x.path).collect::<Vec<_>>(), vec![&paths[0], &paths[1]]);
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].len(), 4);
+
+        // Not from a group with none of the others in it
+        let elsewhere = vec![image(&paths[2], "AAAAAAAAAAA", 200), image(&dir.join("c.jpg"), "AAAAAAAAAAA", 200)];
+        let (shown, whole) = list.apply(vec![elsewhere], namespace);
+        assert_eq!((shown.len(), whole.len()), (1, 0));
+
+        // Moved, it's recognized by content; a group left with one member is
+        // hidden.
+        let moved = dir.join("renamed.jpg");
+        std::fs::rename(&paths[2], &moved).unwrap();
+        let pair = vec![image(&paths[0], "AAAAAAAAAAA", 100), image(&moved, "AAAAAAAAAAA", 200)];
+        let (shown, whole) = list.apply(vec![pair], namespace);
+        assert_eq!((shown.len(), whole.len()), (0, 1));
+
+        // But not by a same-sized file from another namespace
+        let (shown, _) = list.apply(vec![whole[0].clone()], Namespace::new(HashAlg::Mean));
+        assert_eq!(shown.len(), 1);
+    }
+}
@@ -0,0 +1,354 @@
+use std::path::Path;
+
+use eframe::egui;
+
+
+// Finder tags on macOS, kept in an extended attribute as a binary property
+// list of strings. Elsewhere there's nowhere to put them, and tagging isn't
+// offered at all.
+
+pub const SUPPORTED: bool = cfg!(target_os = "macos");
+
+#[cfg(target_os = "macos")]
+const TAGS_ATTR: &str = "com.apple.metadata:_kMDItemUserTags";
+
+// The standard label colors, numbered the way Finder stores them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LabelColor {
+    Gray = 1,
+    Green = 2,
+    Purple = 3,
+    Blue = 4,
+    Yellow = 5,
+    Red = 6,
+    Orange = 7,
+}
+
+impl LabelColor {
+    // In the order Finder lists them.
+    pub const ALL: [LabelColor; 7] = [
+        LabelColor::Red,
+        LabelColor::Orange,
+        LabelColor::Yellow,
+        LabelColor::Green,
+        LabelColor::Blue,
+        LabelColor::Purple,
+        LabelColor::Gray,
+    ];
+
+    fn from_index(index: u32) -> Option<LabelColor> {
+        LabelColor::ALL.into_iter().find(|x| *x as u32 == index)
+    }
+
+    // The tag's name, as Finder writes it; stored, so never translated.
+    pub fn name(self) -> &'static str {
+        match self {
+            LabelColor::Gray => "Gray",
+            LabelColor::Green => "Green",
+            LabelColor::Purple => "Purple",
+            LabelColor::Blue => "Blue",
+            LabelColor::Yellow => "Yellow",
+            LabelColor::Red => "Red",
+            LabelColor::Orange => "Orange",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LabelColor::Gray => tr!("Gray"),
+            LabelColor::Green => tr!("Green"),
+            LabelColor::Purple => tr!("Purple"),
+            LabelColor::Blue => tr!("Blue"),
+            LabelColor::Yellow => tr!("Yellow"),
+            LabelColor::Red => tr!("Red"),
+            LabelColor::Orange => tr!("Orange"),
+        }
+    }
+
+    // Finder's swatches, for text. On a light background it's the darker,
+    // higher-contrast ones macOS uses there, as the yellow and orange
+    // swatches can barely be read on white.
+    pub fn color(self, visuals: &egui::Visuals) -> egui::Color32 {
+        if !visuals.dark_mode {
+            return match self {
+                LabelColor::Gray => egui::Color32::from_rgb(0x6C, 0x6C, 0x70),
+                LabelColor::Green => egui::Color32::from_rgb(0x24, 0x8A, 0x3D),
+                LabelColor::Purple => egui::Color32::from_rgb(0x89, 0x44, 0xAB),
+                LabelColor::Blue => egui::Color32::from_rgb(0x00, 0x40, 0xDD),
+                LabelColor::Yellow => egui::Color32::from_rgb(0xA0, 0x5A, 0x00),
+                LabelColor::Red => egui::Color32::from_rgb(0xD7, 0x00, 0x15),
+                LabelColor::Orange => egui::Color32::from_rgb(0xC9, 0x34, 0x00),
+            };
+        }
+        match self {
+            LabelColor::Gray => egui::Color32::from_rgb(0x8E, 0x8E, 0x93),
+            LabelColor::Green => egui::Color32::from_rgb(0x28, 0xCD, 0x41),
+            LabelColor::Purple => egui::Color32::from_rgb(0xAF, 0x52, 0xDE),
+            LabelColor::Blue => egui::Color32::from_rgb(0x00, 0x7A, 0xFF),
+            LabelColor::Yellow => egui::Color32::from_rgb(0xFF, 0xCC, 0x00),
+            LabelColor::Red => egui::Color32::from_rgb(0xFF, 0x3B, 0x30),
+            LabelColor::Orange => egui::Color32::from_rgb(0xFF, 0x95, 0x00),
+        }
+    }
+}
+
+// A tag is stored as its name, then a newline and its color's number if it
+// has one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FinderTag {
+    pub name: String,
+    pub color: Option<LabelColor>,
+}
+
+impl FinderTag {
+    pub fn new(color: LabelColor) -> FinderTag {
+        FinderTag{name: color.name().to_string(), color: Some(color)}
+    }
+
+    fn parse(stored: &str) -> FinderTag {
+        match stored.rsplit_once('\n') {
+            Some((name, color)) => FinderTag {
+                name: name.to_string(),
+                color: color.parse().ok().and_then(LabelColor::from_index),
+            },
+            None => FinderTag{name: stored.to_string(), color: None},
+        }
+    }
+
+    fn stored(&self) -> String {
+        match self.color {
+            Some(color) => format!("{}\n{}", self.name, color as u32),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// The file's tags; none if it has none, or they can't be made sense of.
+pub fn read(path: &Path) -> Result<Vec<FinderTag>, String> {
+    let Some(data) = read_attr(path)? else {
+        return Ok(vec![]);
+    };
+    Ok(decode_plist(&data)
+        .unwrap_or_default()
+        .iter()
+        .map(|x| FinderTag::parse(x))
+        .collect())
+}
+
+// Adds the tag to the file's others, unless it already has one by that name.
+// Returns all of them. The others are written back exactly as they were read,
+// except tags that couldn't be made sense of, which are replaced.
+pub fn add(path: &Path, tag: &FinderTag) -> Result<Vec<FinderTag>, String> {
+    let mut stored = read_attr(path)?.and_then(|x| decode_plist(&x)).unwrap_or_default();
+    let mut tags: Vec<FinderTag> = stored.iter().map(|x| FinderTag::parse(x)).collect();
+    if !tags.iter().any(|x| x.name == tag.name) {
+        stored.push(tag.stored());
+        tags.push(tag.clone());
+        write_attr(path, &encode_plist(&stored))?;
+    }
+    Ok(tags)
+}
+
+#[cfg(target_os = "macos")]
+fn read_attr(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    xattr::get(path, TAGS_ATTR).map_err(|e| format!("Error reading the tags of {}: {e}", path.display()))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read_attr(_path: &Path) -> Result<Option<Vec<u8>>, String> {
+    Ok(None)
+}
+
+#[cfg(target_os = "macos")]
+fn write_attr(path: &Path, data: &[u8]) -> Result<(), String> {
+    xattr::set(path, TAGS_ATTR, data).map_err(|e| format!("Error tagging {}: {e}", path.display()))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn write_attr(path: &Path, _data: &[u8]) -> Result<(), String> {
+    Err(format!("Error tagging {}: Finder tags are only supported on macOS", path.display()))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Just enough of Apple's binary property list format for an array of strings:
+// a header, the objects, a table of their offsets, and a trailer saying where
+// everything is. All integers are big-endian.
+
+const PLIST_MAGIC: &[u8] = b"bplist00";
+const PLIST_TRAILER_LEN: usize = 32;
+
+const MARKER_INT: u8 = 0x1;
+const MARKER_ASCII: u8 = 0x5;
+const MARKER_UTF16: u8 = 0x6;
+const MARKER_ARRAY: u8 = 0xA;
+
+// The fewest of 1, 2, 4 or 8 bytes that hold the value.
+fn int_size(value: u64) -> usize {
+    match value {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFFFF_FFFF => 4,
+        _ => 8,
+    }
+}
+
+fn be_bytes(value: u64, size: usize) -> Vec<u8> {
+    value.to_be_bytes()[8 - size..].to_vec()
+}
+
+fn from_be_bytes(bytes: &[u8]) -> Option<u64> {
+    (bytes.len() <= 8).then(|| bytes.iter().fold(0, |n, x| n << 8 | *x as u64))
+}
+
+// An object's marker: its type in the high nibble and its length in the low
+// one, or 0xF and an integer object holding the length.
+fn encode_marker(kind: u8, len: usize) -> Vec<u8> {
+    if len < 0xF {
+        return vec![kind << 4 | len as u8];
+    }
+    let size = int_size(len as u64);
+    let mut ret = vec![kind << 4 | 0xF, MARKER_INT << 4 | size.trailing_zeros() as u8];
+    ret.extend(be_bytes(len as u64, size));
+    ret
+}
+
+// The type, length, and where the contents start.
+fn decode_marker(data: &[u8], at: usize) -> Option<(u8, usize, usize)> {
+    let marker = *data.get(at)?;
+    let (kind, len) = (marker >> 4, (marker & 0xF) as usize);
+    if len < 0xF {
+        return Some((kind, len, at + 1));
+    }
+    let int = *data.get(at + 1)?;
+    if int >> 4 != MARKER_INT || int & 0xF > 3 {
+        return None;
+    }
+    let size = 1 << (int & 0xF);
+    let len = from_be_bytes(data.get(at + 2..at + 2 + size)?)? as usize;
+    Some((kind, len, at + 2 + size))
+}
+
+fn encode_plist(strings: &[String]) -> Vec<u8> {
+    let count = strings.len() + 1; // The array comes first
+    let ref_size = int_size(count as u64);
+    let mut out = PLIST_MAGIC.to_vec();
+    let mut offsets = vec![out.len() as u64];
+    out.extend(encode_marker(MARKER_ARRAY, strings.len()));
+    for idx in 1..count {
+        out.extend(be_bytes(idx as u64, ref_size));
+    }
+    for string in strings {
+        offsets.push(out.len() as u64);
+        if string.is_ascii() {
+            out.extend(encode_marker(MARKER_ASCII, string.len()));
+            out.extend(string.as_bytes());
+        } else {
+            let units: Vec<u16> = string.encode_utf16().collect();
+            out.extend(encode_marker(MARKER_UTF16, units.len()));
+            out.extend(units.iter().flat_map(|x| x.to_be_bytes()));
+        }
+    }
+
+    let table = out.len() as u64;
+    let offset_size = int_size(table);
+    for offset in offsets {
+        out.extend(be_bytes(offset, offset_size));
+    }
+    out.extend([0; 6]);
+    out.extend([offset_size as u8, ref_size as u8]);
+    out.extend((count as u64).to_be_bytes());
+    out.extend(0u64.to_be_bytes()); // The top object, the array
+    out.extend(table.to_be_bytes());
+    out
+}
+
+// None if it isn't a binary property list holding an array of strings.
+fn decode_plist(data: &[u8]) -> Option<Vec<String>> {
+    if !data.starts_with(PLIST_MAGIC) || data.len() < PLIST_MAGIC.len() + PLIST_TRAILER_LEN {
+        return None;
+    }
+    let trailer = &data[data.len() - PLIST_TRAILER_LEN..];
+    let offset_size = trailer[6] as usize;
+    let ref_size = trailer[7] as usize;
+    let count = from_be_bytes(&trailer[8..16])? as usize;
+    let top = from_be_bytes(&trailer[16..24])? as usize;
+    let table = from_be_bytes(&trailer[24..32])? as usize;
+    if offset_size == 0 || ref_size == 0 {
+        return None;
+    }
+
+    let offset = |idx: usize| -> Option<usize> {
+        if idx >= count {
+            return None;
+        }
+        let start = table.checked_add(idx.checked_mul(offset_size)?)?;
+        from_be_bytes(data.get(start..start.checked_add(offset_size)?)?).map(|x| x as usize)
+    };
+
+    let (kind, len, refs) = decode_marker(data, offset(top)?)?;
+    if kind != MARKER_ARRAY {
+        return None;
+    }
+    let mut strings = vec![];
+    for idx in 0..len {
+        let at = refs.checked_add(idx.checked_mul(ref_size)?)?;
+        let child = from_be_bytes(data.get(at..at.checked_add(ref_size)?)?)? as usize;
+        let (kind, len, start) = decode_marker(data, offset(child)?)?;
+        let string = match kind {
+            MARKER_ASCII => String::from_utf8(data.get(start..start.checked_add(len)?)?.to_vec()).ok()?,
+            MARKER_UTF16 => {
+                let bytes = data.get(start..start.checked_add(len.checked_mul(2)?)?)?;
+                let units: Vec<u16> = bytes.chunks(2).map(|x| u16::from_be_bytes([x[0], x[1]])).collect();
+                String::from_utf16(&units).ok()?
+            },
+            _ => return None,
+        };
+        strings.push(string);
+    }
+    Some(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tagged Red and "Projekt Ärger" (no color), as written by Python's
+    // plistlib, which encodes the same way Finder does.
+    const TAGS_PLIST: &[u8] = &[
+        0x62, 0x70, 0x6C, 0x69, 0x73, 0x74, 0x30, 0x30, 0xA2, 0x01, 0x02, 0x55, 0x52, 0x65, 0x64, 0x0A,
+        0x36, 0x6F, 0x10, 0x0F, 0x00, 0x50, 0x00, 0x72, 0x00, 0x6F, 0x00, 0x6A, 0x00, 0x65, 0x00, 0x6B,
+        0x00, 0x74, 0x00, 0x20, 0x00, 0xC4, 0x00, 0x72, 0x00, 0x67, 0x00, 0x65, 0x00, 0x72, 0x00, 0x0A,
+        0x00, 0x30, 0x08, 0x0B, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x32,
+    ];
+
+    #[test]
+    fn plist_round_trips() {
+        let long = "x".repeat(300);
+        let cases = [vec![], vec!["Red\n6".to_string()], vec!["Grün\n2".to_string(), long, "Work".to_string()]];
+        for strings in cases {
+            assert_eq!(decode_plist(&encode_plist(&strings)), Some(strings));
+        }
+    }
+
+    #[test]
+    fn reads_finder_tags() {
+        let stored = decode_plist(TAGS_PLIST).unwrap();
+        let tags: Vec<FinderTag> = stored.iter().map(|x| FinderTag::parse(x)).collect();
+        assert_eq!(tags, vec![
+            FinderTag::new(LabelColor::Red),
+            FinderTag{name: "Projekt Ärger".to_string(), color: None},
+        ]);
+        assert_eq!(encode_plist(&stored), TAGS_PLIST);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(decode_plist(b"bplist00"), None);
+        let mut truncated = encode_plist(&["Red\n6".to_string()]);
+        truncated.drain(10..14);
+        assert_eq!(decode_plist(&truncated), None);
+    }
+}
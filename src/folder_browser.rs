@@ -0,0 +1,206 @@
+use crate::misc::{Image, folder_name, wasted_members};
+use crate::i18n::format_size;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use egui::collapsing_header::CollapsingState;
+
+
+// The folders under the roots that have duplicates in them, as a tree beside
+// the results, for narrowing them down to one place. It's built from the
+// results' own paths rather than the filesystem, so it only has folders worth
+// going to, and costs nothing to read.
+
+#[derive(Default)]
+struct Folder {
+    children: Vec<PathBuf>, // Sorted
+    groups: usize, // With a member anywhere under it
+    wasted: usize, // Bytes, charged the way the by-folder view charges them
+}
+
+#[derive(Default)]
+pub struct FolderBrowser {
+    roots: Vec<PathBuf>,
+    folders: HashMap<PathBuf, Folder>, // Every folder from a root down to a member's
+}
+
+// A folder drawn this frame, in the order drawn, for moving through them with
+// the keyboard.
+struct Row {
+    folder: PathBuf,
+    state_id: egui::Id,
+    open: Option<bool>, // None if it has no children
+    resp: egui::Response,
+}
+
+impl FolderBrowser {
+    pub fn new(roots: &[&Path], images: &[Vec<Image>]) -> FolderBrowser {
+        let roots: Vec<PathBuf> = roots.iter()
+            .filter(|x| !x.as_os_str().is_empty())
+            .map(|x| x.to_path_buf())
+            .collect();
+
+        // The folders from the root a file was found under down to its own,
+        // going by the innermost root if they nest.
+        let folders_of = |path: &Path| -> Vec<PathBuf> {
+            let Some(root) = roots.iter().filter(|x| path.starts_with(x)).max_by_key(|x| x.components().count()) else {
+                return vec![];
+            };
+            path.ancestors()
+                .skip(1)
+                .take_while(|x| x.starts_with(root))
+                .map(Path::to_path_buf)
+                .collect()
+        };
+
+        let mut folders: HashMap<PathBuf, Folder> = HashMap::new();
+        for group in images {
+            let under: HashSet<PathBuf> = group.iter().flat_map(|x| folders_of(&x.path)).collect();
+            for folder in under {
+                folders.entry(folder).or_default().groups += 1;
+            }
+            for image in wasted_members(group) {
+                for folder in folders_of(&image.path) {
+                    folders.entry(folder).or_default().wasted += image.file_size;
+                }
+            }
+        }
+
+        let children: Vec<PathBuf> = folders.keys().filter(|x| !roots.contains(x)).cloned().collect();
+        for child in children {
+            // Anything but a root has a parent at or under one.
+            let parent = child.parent().unwrap().to_path_buf();
+            folders.entry(parent).or_default().children.push(child);
+        }
+        for folder in folders.values_mut() {
+            folder.children.sort();
+        }
+        FolderBrowser{roots, folders}
+    }
+
+    // Returns the folder clicked, if any; selected is the one the results are
+    // filtered to. Each folder's expansion is kept in egui's memory, so it
+    // lasts as long as the session does. With a folder focused, up and down
+    // move between the folders shown, right expands, and left collapses or
+    // goes up to the parent.
+    pub fn draw(&self, ui: &mut egui::Ui, selected: Option<&Path>) -> Option<PathBuf> {
+        if self.folders.is_empty() {
+            ui.label(tr!("No folders with duplicates"));
+            return None;
+        }
+
+        let mut rows = vec![];
+        for root in self.roots.iter().filter(|x| self.folders.contains_key(*x)) {
+            self.draw_folder(ui, root, selected, &mut rows);
+        }
+        let clicked = rows.iter().find(|x| x.resp.clicked()).map(|x| x.folder.clone());
+
+        let Some(idx) = rows.iter().position(|x| x.resp.has_focus()) else {
+            return clicked;
+        };
+        let row = &rows[idx];
+        let arrows = egui::EventFilter{horizontal_arrows: true, vertical_arrows: true, ..Default::default()};
+        ui.memory_mut(|x| x.set_focus_lock_filter(row.resp.id, arrows));
+        let [up, down, left, right] = [egui::Key::ArrowUp, egui::Key::ArrowDown, egui::Key::ArrowLeft, egui::Key::ArrowRight]
+            .map(|key| ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key)));
+        let set_open = |open: bool| {
+            let mut state = CollapsingState::load_with_default_open(ui.ctx(), row.state_id, false);
+            state.set_open(open);
+            state.store(ui.ctx());
+        };
+        if up && idx > 0 {
+            rows[idx - 1].resp.request_focus();
+        }
+        if down && idx + 1 < rows.len() {
+            rows[idx + 1].resp.request_focus();
+        }
+        if right && row.open == Some(false) {
+            set_open(true);
+        }
+        if left && row.open == Some(true) {
+            set_open(false);
+        } else if left {
+            if let Some(parent) = rows.iter().find(|x| Some(x.folder.as_path()) == row.folder.parent()) {
+                parent.resp.request_focus();
+            }
+        }
+        clicked
+    }
+
+    fn draw_folder(&self, ui: &mut egui::Ui, folder: &Path, selected: Option<&Path>, rows: &mut Vec<Row>) {
+        let node = &self.folders[folder];
+        let text = tr!(
+            "{folder} — {groups} groups, {size}",
+            folder = folder_name(folder),
+            groups = node.groups,
+            size = format_size(node.wasted),
+        );
+        let label = |ui: &mut egui::Ui| {
+            ui.selectable_label(selected == Some(folder), text)
+                .on_hover_text(folder.display().to_string())
+        };
+
+        let state_id = ui.make_persistent_id(("folder browser", folder));
+        if node.children.is_empty() {
+            ui.horizontal(|ui| {
+                ui.add_space(ui.spacing().icon_width + ui.spacing().item_spacing.x);
+                let resp = label(ui);
+                rows.push(Row{folder: folder.to_path_buf(), state_id, open: None, resp});
+            });
+            return;
+        }
+
+        // Roots start out open, so there's something to pick from.
+        let state = CollapsingState::load_with_default_open(ui.ctx(), state_id, self.roots.iter().any(|x| x == folder));
+        let open = state.is_open();
+        state
+            .show_header(ui, |ui| {
+                let resp = label(ui);
+                rows.push(Row{folder: folder.to_path_buf(), state_id, open: Some(open), resp});
+            })
+            .body(|ui| {
+                for child in &node.children {
+                    self.draw_folder(ui, child, selected, rows);
+                }
+            });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(members: &[(&str, usize)]) -> Vec<Image> {
+        members.iter().map(|(path, size)| Image::new_lazy(PathBuf::from(path), *size, None)).collect()
+    }
+
+    #[test]
+    fn folders_count_the_groups_and_bytes_under_them() {
+        let images = [
+            group(&[("/photos/2021/a.jpg", 300), ("/photos/2021/trip/a.jpg", 100)]),
+            group(&[("/photos/2021/b.jpg", 40), ("/photos/2021/b copy.jpg", 40)]),
+            // The phone's folder is searched as a root of its own, and the
+            // archive's the reference. Files outside the roots have no folder.
+            group(&[("/photos/phone/c.jpg", 50), ("/archive/c.jpg", 200), ("/elsewhere/c.jpg", 10)]),
+        ];
+        let roots = [Path::new("/photos"), Path::new("/photos/phone"), Path::new("/archive"), Path::new("")];
+        let browser = FolderBrowser::new(&roots, &images);
+        assert_eq!(browser.roots, [Path::new("/photos"), Path::new("/photos/phone"), Path::new("/archive")]);
+
+        let node = |path: &str| {
+            let folder = &browser.folders[Path::new(path)];
+            (folder.groups, folder.wasted, folder.children.clone())
+        };
+        let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(node("/photos"), (2, 140, paths(&["/photos/2021"])));
+        assert_eq!(node("/photos/2021"), (2, 140, paths(&["/photos/2021/trip"])));
+        assert_eq!(node("/photos/2021/trip"), (1, 100, vec![]));
+        // Not under /photos as well, which goes by the innermost root
+        assert_eq!(node("/photos/phone"), (1, 50, vec![]));
+        assert_eq!(node("/archive"), (1, 0, vec![]));
+        assert_eq!(browser.folders.len(), 5);
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+
+
+// A checkable tree of the folders under a root, for limiting a search to some
+// of them. Each folder's children are only read when it's first expanded, so
+// a huge tree costs nothing until it's browsed.
+
+#[derive(Default)]
+pub struct FolderTree {
+    children: HashMap<PathBuf, Result<Vec<PathBuf>, String>>, // Sorted by name
+}
+
+impl FolderTree {
+    fn read_children(folder: &Path) -> Result<Vec<PathBuf>, String> {
+        let mut children: Vec<PathBuf> = std::fs::read_dir(folder)
+            .map_err(|e| e.to_string())?
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| x.is_dir())
+            .collect();
+        children.sort();
+        Ok(children)
+    }
+
+    // Checking a folder includes everything under it, so its descendants are
+    // shown checked (and can't be unchecked) rather than listed separately.
+    pub fn draw(&mut self, ui: &mut egui::Ui, root: &Path, selected: &mut Vec<PathBuf>) {
+        self.draw_children(ui, root, selected, false);
+    }
+
+    fn draw_children(&mut self, ui: &mut egui::Ui, folder: &Path, selected: &mut Vec<PathBuf>, inherited: bool) {
+        let children = self.children
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| Self::read_children(folder))
+            .clone();
+        let children = match children {
+            Ok(x) => x,
+            Err(e) => {
+                ui.label(egui::RichText::new(tr!("Can't read folder: {error}", error = e))
+                    .small()
+                    .color(ui.visuals().error_fg_color));
+                return;
+            },
+        };
+        if children.is_empty() {
+            ui.label(egui::RichText::new(tr!("No subfolders")).small().italics());
+        }
+
+        for child in children {
+            let name = child.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+            let checked = inherited || selected.contains(&child);
+            let id = ui.make_persistent_id(&child);
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| {
+                    let mut value = checked;
+                    let resp = ui.add_enabled(!inherited, egui::Checkbox::new(&mut value, name));
+                    if resp.changed() {
+                        if value {
+                            selected.retain(|x| !x.starts_with(&child));
+                            selected.push(child.clone());
+                        } else {
+                            selected.retain(|x| x != &child);
+                        }
+                    }
+                })
+                .body(|ui| self.draw_children(ui, &child, selected, checked));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+
+// A debug overlay with the frame rate and what asked for the last repaint,
+// for catching anything that keeps the app repainting while idle. App-wide,
+// like the click actions, and toggled from the same settings menu; it isn't
+// saved, since it's only for debugging.
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+// Frames are counted over this long.
+const WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct FrameStats {
+    frames: VecDeque<Instant>, // Within the window, oldest first
+}
+
+impl FrameStats {
+    // Call once per frame.
+    pub fn record(&mut self, now: Instant) {
+        self.frames.push_back(now);
+        while self.frames.front().is_some_and(|x| now.duration_since(*x) > WINDOW) {
+            self.frames.pop_front();
+        }
+    }
+
+    // Over the last window, as of the last frame. Drawing this doesn't repaint,
+    // so once idle, it stays at whatever it was when repaints stopped.
+    pub fn fps(&self) -> f32 {
+        self.frames.len() as f32 / WINDOW.as_secs_f32()
+    }
+
+    pub fn draw(&self, ctx: &egui::Context) {
+        if !SHOWN.load(Ordering::Relaxed) {
+            return;
+        }
+        // No causes means the frame was for input.
+        let causes: Vec<String> = ctx.repaint_causes().iter()
+            .map(|x| {
+                let file = x.file.rsplit(['/', '\\']).next().unwrap_or(x.file);
+                format!("{file}:{}", x.line)
+            })
+            .collect();
+        let causes = if causes.is_empty() { tr!("input").to_string() } else { causes.join(", ") };
+        egui::Area::new(egui::Id::new("frame stats"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+            .interactable(false)
+            .order(egui::Order::Debug)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.monospace(tr!("{fps} fps", fps = format!("{:.0}", self.fps())));
+                    ui.monospace(tr!("repaint: {causes}", causes = causes));
+                });
+            });
+    }
+}
+
+// A checkbox for the settings menu.
+pub fn toggle(ui: &mut egui::Ui) {
+    let mut shown = SHOWN.load(Ordering::Relaxed);
+    if ui.checkbox(&mut shown, tr!("Show frame rate")).on_hover_text(tr!("For debugging; shows how often the window repaints, and why")).changed() {
+        SHOWN.store(shown, Ordering::Relaxed);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_counted_over_the_last_window() {
+        let mut stats = FrameStats::default();
+        assert_eq!(stats.fps(), 0.0);
+
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        for ms in (0..1000).step_by(10) {
+            stats.record(at(ms));
+        }
+        assert_eq!(stats.fps(), 100.0);
+
+        // A frame exactly a window after the first still counts it.
+        stats.record(at(1000));
+        assert_eq!(stats.fps(), 101.0);
+
+        // After a pause, only what's within the window is left.
+        stats.record(at(1500));
+        assert_eq!(stats.fps(), 52.0);
+        stats.record(at(5000));
+        assert_eq!(stats.fps(), 1.0);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::bk_tree;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use image_hasher::ImageHash;
+
+use rayon::prelude::*;
+
+
+// Turning hashes into groups when near matches count too. Within a distance,
+// matching isn't transitive: A can be close to B and B to C while A and C are
+// far apart. Strict grouping only puts hashes together if every pair of them
+// matches, so a chain like that is split up; chained grouping merges anything
+// connected by matches, so it's kept whole, but so can be a long run of
+// gradually different photos. Either way, every hash ends up in exactly one
+// group.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum Grouping {
+    #[default]
+    Strict, // Every pair in a group is within the distance
+    Chain, // Groups are everything transitively within the distance
+}
+
+// Partitions the hashes, which must be distinct, into groups of indices, each
+// in order. Singletons are included, since a hash can stand for several files.
+// Near matches are looked up in a BK-tree for each hash length: distance is
+// only defined between hashes of the same length, so an animation's never
+// matches a still's. None if canceled.
+pub fn cluster(
+    hashes: &[&ImageHash],
+    max_distance: u32,
+    grouping: Grouping,
+    cancel: &AtomicBool,
+) -> Option<Vec<Vec<usize>>> {
+    if max_distance == 0 {
+        return Some((0..hashes.len()).map(|x| vec![x]).collect());
+    }
+
+    let mut trees = vec![];
+    for (idx, hash) in hashes.iter().enumerate() {
+        bk_tree::insert_by_length(&mut trees, hash.as_bytes(), idx);
+    }
+    let neighbors: Option<Vec<Vec<usize>>> = hashes.par_iter()
+        .enumerate()
+        .map(|(idx, hash)| {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let mut found = vec![];
+            for tree in &trees {
+                tree.find(hash.as_bytes(), max_distance, |x, _| if *x != idx { found.push(*x) });
+            }
+            found.sort();
+            Some(found)
+        })
+        .collect();
+    let neighbors = neighbors?;
+
+    let mut groups = match grouping {
+        Grouping::Strict => cliques(hashes, &neighbors, max_distance),
+        Grouping::Chain => components(&neighbors),
+    };
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort();
+    Some(groups)
+}
+
+// Greedily, starting from the most connected hash, so the larger cliques get
+// their members before smaller ones can take them. Candidates join closest
+// first, if they're within the distance of everything already in.
+fn cliques(hashes: &[&ImageHash], neighbors: &[Vec<usize>], max_distance: u32) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..hashes.len()).collect();
+    order.sort_by_key(|x| (std::cmp::Reverse(neighbors[*x].len()), *x));
+
+    let mut taken = vec![false; hashes.len()];
+    let mut groups = vec![];
+    for seed in order {
+        if taken[seed] {
+            continue;
+        }
+        taken[seed] = true;
+
+        let mut candidates: Vec<usize> = neighbors[seed].iter()
+            .copied()
+            .filter(|x| !taken[*x])
+            .collect();
+        candidates.sort_by_key(|x| (hashes[*x].dist(hashes[seed]), *x));
+
+        let mut group = vec![seed];
+        for candidate in candidates {
+            if group.iter().all(|x| hashes[*x].dist(hashes[candidate]) <= max_distance) {
+                taken[candidate] = true;
+                group.push(candidate);
+            }
+        }
+        groups.push(group);
+    }
+    groups
+}
+
+// Connected components, by union-find.
+fn components(neighbors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    fn find(parents: &mut [usize], mut x: usize) -> usize {
+        while parents[x] != x {
+            parents[x] = parents[parents[x]];
+            x = parents[x];
+        }
+        x
+    }
+
+    let mut parents: Vec<usize> = (0..neighbors.len()).collect();
+    for (a, adjacent) in neighbors.iter().enumerate() {
+        for b in adjacent {
+            let (root_a, root_b) = (find(&mut parents, a), find(&mut parents, *b));
+            parents[root_a.max(root_b)] = root_a.min(root_b);
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![vec![]; neighbors.len()];
+    for x in 0..neighbors.len() {
+        let root = find(&mut parents, x);
+        groups[root].push(x);
+    }
+    groups.retain(|x| !x.is_empty());
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A hash with the given bits set, out of 64.
+    fn hash(bits: &[u32]) -> ImageHash {
+        let mut bytes = [0u8; 8];
+        for bit in bits {
+            bytes[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        ImageHash::from_bytes(&bytes).unwrap()
+    }
+
+    fn groups(hashes: &[ImageHash], grouping: Grouping) -> Vec<Vec<usize>> {
+        let hashes: Vec<_> = hashes.iter().collect();
+        let groups = cluster(&hashes, 4, grouping, &AtomicBool::new(false)).unwrap();
+
+        let mut seen: Vec<usize> = groups.iter().flatten().copied().collect();
+        seen.sort();
+        assert_eq!(seen, (0..hashes.len()).collect::<Vec<_>>(), "not a partition: {groups:?}");
+        groups
+    }
+
+    #[test]
+    fn chains_are_split_or_merged() {
+        // A-B and B-C are 4 apart, A-C 8.
+        let hashes = [hash(&[]), hash(&[0, 1, 2, 3]), hash(&[0, 1, 2, 3, 4, 5, 6, 7])];
+        assert_eq!(groups(&hashes, Grouping::Strict), vec![vec![0, 1], vec![2]]);
+        assert_eq!(groups(&hashes, Grouping::Chain), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn cliques_stay_whole() {
+        let hashes = [hash(&[]), hash(&[0, 1]), hash(&[2, 3]), hash(&[40, 41, 42, 43, 44, 45])];
+        assert_eq!(groups(&hashes, Grouping::Strict), vec![vec![0, 1, 2], vec![3]]);
+        assert_eq!(groups(&hashes, Grouping::Chain), vec![vec![0, 1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn animations_never_match_stills() {
+        // An animation's hash is its sampled frames' end to end, so its start
+        // is its first frame's.
+        let still = hash(&[0, 1]);
+        let mut frames = still.as_bytes().to_vec();
+        frames.extend(hash(&[8, 9]).as_bytes());
+        frames.extend(hash(&[16, 17]).as_bytes());
+        frames.extend(hash(&[24, 25]).as_bytes());
+        let animation = ImageHash::from_bytes(&frames).unwrap();
+        assert_eq!(animation.dist(&still), 0);
+
+        let hashes = [still, animation, hash(&[0])];
+        assert_eq!(groups(&hashes, Grouping::Strict), vec![vec![0, 2], vec![1]]);
+        assert_eq!(groups(&hashes, Grouping::Chain), vec![vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn diamonds_split_into_pairs() {
+        // A-B, A-C, B-D and C-D are 4 apart; A-D and B-C are 8.
+        let hashes = [
+            hash(&[]),
+            hash(&[0, 1, 2, 3]),
+            hash(&[4, 5, 6, 7]),
+            hash(&[0, 1, 2, 3, 4, 5, 6, 7]),
+        ];
+        assert_eq!(groups(&hashes, Grouping::Strict), vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(groups(&hashes, Grouping::Chain), vec![vec![0, 1, 2, 3]]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::misc::Image;
+use crate::plan::{Action, Step};
+
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+
+// What happened to a single file during hard link deduplication.
+pub struct LinkOutcome {
+    pub path: PathBuf,
+    pub result: Result<(), String>,
+}
+
+impl LinkOutcome {
+    fn ok(path: &Path) -> LinkOutcome {
+        LinkOutcome{ path: path.to_path_buf(), result: Ok(()) }
+    }
+
+    fn err(path: &Path, msg: String) -> LinkOutcome {
+        LinkOutcome{ path: path.to_path_buf(), result: Err(msg) }
+    }
+}
+
+pub const SUPPORTED: bool = cfg!(unix);
+
+// Where the new link is created before being renamed over the duplicate. Lives
+// in the same directory so the rename is on one filesystem (and so atomic).
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|x| x.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{name}.deckard-link"))
+}
+
+// The steps to replace every member of the group but the first with a hard
+// link to the first. Members that are already links to it are left out.
+pub fn plan_group(group: &[Image]) -> Vec<Step> {
+    let Some((keeper, rest)) = group.split_first() else {
+        return vec![];
+    };
+
+    let Some(keeper_id) = keeper.file_id else {
+        return rest.iter()
+            .map(|x| Step::skip(&x.path, "Couldn't identify file".to_string()))
+            .collect();
+    };
+
+    if rest.iter().any(|x| x.file_id.map(|id| id.dev) != Some(keeper_id.dev)) {
+        return rest.iter()
+            .map(|x| Step::skip(&x.path, "Group spans multiple filesystems (cross-device)".to_string()))
+            .collect();
+    }
+
+    rest.iter()
+        .filter(|x| x.file_id != Some(keeper_id))
+        .map(|x| Step::new(&x.path, Action::Link{target: keeper.path.clone()}))
+        .collect()
+}
+
+// Carries out planned links (see plan_group()). Skipped steps are reported as
+// failures.
+//
+// Each target's temporary links are all created before any file is
+// replaced, so if any can't be, the temporaries are removed and that group is
+// left untouched. Failures after that point only affect the file in question,
+// which is left as it was.
+pub fn execute(steps: &[Step]) -> Vec<LinkOutcome> {
+    let mut outcomes = vec![];
+    for step in steps.iter().filter(|x| x.action == Action::Skip) {
+        let reason = step.problem.clone().unwrap_or_default();
+        outcomes.push(LinkOutcome::err(&step.path, format!("Skipped: {reason}")));
+    }
+
+    let links = steps.iter().filter_map(|step| match &step.action {
+        Action::Link{target} => Some((target, &step.path)),
+        _ => None,
+    });
+    for (target, group) in &links.chunk_by(|x| x.0) {
+        let paths: Vec<&PathBuf> = group.map(|x| x.1).collect();
+        outcomes.extend(link_to(target, &paths));
+    }
+    outcomes
+}
+
+fn link_to(target: &Path, paths: &[&PathBuf]) -> Vec<LinkOutcome> {
+    let mut pending = vec![];
+    for path in paths {
+        let tmp = temp_path(path);
+        if let Err(e) = std::fs::hard_link(target, &tmp) {
+            for (_, tmp) in &pending {
+                let _ = std::fs::remove_file(tmp);
+            }
+            let msg = format!("Error linking {} (group rolled back): {e}", path.display());
+            return paths.iter().map(|x| LinkOutcome::err(x, msg.clone())).collect();
+        }
+        pending.push((path, tmp));
+    }
+
+    pending.into_iter().map(|(path, tmp)| match std::fs::rename(&tmp, path) {
+        Ok(()) => LinkOutcome::ok(path),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            LinkOutcome::err(path, format!("Error replacing file: {e}"))
+        },
+    }).collect()
+}
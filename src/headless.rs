@@ -0,0 +1,141 @@
+use crate::startup_phase::UserOpts;
+use crate::searcher::Searcher;
+use crate::searching_phase::SearchingPhase;
+use crate::search_lock::{SearchLock, LockResult};
+use crate::session::Session;
+use crate::archive::Archive;
+use crate::ignored::IgnoreList;
+use crate::junk::JunkList;
+use crate::exclusions::ExclusionList;
+use crate::archive::Namespace;
+use crate::misc::{format_duration, StoredSettings};
+use crate::options;
+use crate::review::Reviews;
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+
+// `deckard --headless-scan <preset name or options.json>`: runs a search
+// without opening a window (e.g. from cron), and saves the results (apart
+// from the session), for the app to offer on its next launch. Options are checked
+// exactly as the form checks them.
+
+pub const FLAG: &str = "--headless-scan";
+
+// Exit statuses, like grep's.
+const EXIT_FOUND: i32 = 0;
+const EXIT_NONE: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+// Often enough for a log to show it's alive, rarely enough not to flood it.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// A path to a .json file of options (as saved in a session, with anything
+// missing at its default), or else the name of a preset.
+fn load_opts(arg: &OsString) -> Result<UserOpts, String> {
+    let path = Path::new(arg);
+    if path.extension().is_some_and(|x| x.eq_ignore_ascii_case("json")) {
+        let json = std::fs::read(path)
+            .map_err(|e| format!("Error reading {}: {e}", path.display()))?;
+        let mut opts: UserOpts = serde_json::from_slice(&json)
+            .map_err(|e| format!("Error parsing {}: {e}", path.display()))?;
+        opts.clamp_center_crop();
+        return Ok(opts);
+    }
+    let name = arg.to_str().ok_or_else(|| format!("Invalid preset name {}", path.display()))?;
+    UserOpts::from_preset(&StoredSettings::load(), name)
+}
+
+// Returns the process's exit status.
+pub fn run(arg: Option<OsString>) -> i32 {
+    let Some(arg) = arg else {
+        eprintln!("Usage: deckard {FLAG} <preset name or options.json>");
+        return EXIT_ERROR;
+    };
+    match scan(&arg) {
+        Ok(0) => {
+            println!("No duplicates found");
+            EXIT_NONE
+        },
+        Ok(groups) => {
+            println!("Groups of duplicates found: {groups}. Open Deckard to review them.");
+            EXIT_FOUND
+        },
+        Err(e) => {
+            eprintln!("Error: {e}");
+            EXIT_ERROR
+        },
+    }
+}
+
+// Returns how many groups were found, not counting ignored ones or ones left
+// with too few members by excluded files.
+fn scan(arg: &OsString) -> Result<usize, String> {
+    let opts = load_opts(arg)?;
+    let search_opts = options::search_opts(&opts).map_err(|e| e.to_string())?;
+
+    // Nobody's there to ask, so another search of the same folders is only
+    // mentioned.
+    let mut locks = vec![];
+    for folder in [Some(&opts.root), opts.compare.then_some(&opts.reference)].into_iter().flatten() {
+        match SearchLock::acquire(folder) {
+            Ok(LockResult::Locked(lock)) => locks.push(lock),
+            Ok(LockResult::Busy) => println!("{} is already being searched; continuing anyway", folder.display()),
+            Err(_) => (),
+        }
+    }
+
+    println!("Searching {}", opts.root.display());
+    let start = Instant::now();
+    let mut searcher = Searcher::new(search_opts);
+    searcher.launch_search();
+    let mut last_report = Instant::now();
+    while !searcher.is_finished() {
+        std::thread::sleep(POLL_INTERVAL);
+        if last_report.elapsed() < PROGRESS_INTERVAL {
+            continue;
+        }
+        last_report = Instant::now();
+        let elapsed = format_duration(start.elapsed());
+        match searcher.finalizing() {
+            Some((done, total)) => println!("{elapsed}: finalizing results, {done} of {total}"),
+            None => {
+                let (found, hashed) = searcher.progress();
+                println!("{elapsed}: {found} images found, {hashed} hashed");
+            },
+        }
+    }
+    let results = searcher.wait_for_search()
+        .map_err(|e| format!("The search crashed: {}", e.msg))?;
+    drop(locks);
+    println!("Searched in {}", format_duration(start.elapsed()));
+    // To the millisecond, for benchmarks; see tests/finalize_benchmark.rs.
+    if let Some(stats) = &results.stats {
+        println!("Results finalized in {} ms", stats.finalize_time.as_millis());
+    }
+
+    // As in SearchingPhase::record()
+    if opts.center_crop == 100 {
+        if let Err(e) = Archive::record(&SearchingPhase::walked_roots(&opts), &results.index) {
+            println!("Couldn't update the archive index: {e}");
+        }
+    }
+    let mut session = Session::new(&opts, &results.duplicates, &Reviews::default());
+    session.unattended = true;
+    session.save_scan()?;
+
+    // The results keep ignored groups, system images and excluded files, to
+    // show on request, but they aren't worth reporting as found.
+    let ignore_list = IgnoreList::load().unwrap_or_default();
+    let junk_list = JunkList::load().unwrap_or_default();
+    let exclusions = ExclusionList::load().unwrap_or_default();
+    let namespace = Namespace::new(results.index.alg);
+    let (shown, _) = exclusions.apply(results.duplicates, namespace);
+    Ok(shown.iter()
+        .filter(|x| !ignore_list.matches(x))
+        .filter(|x| !opts.junk_filter || junk_list.matches(x, namespace).is_none())
+        .count())
+}
@@ -0,0 +1,128 @@
+use crate::misc::format_time;
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+
+// What's been done to the files in the results: trashing, linking and tagging
+// them, undoing that, and ignoring groups. Opening files isn't recorded,
+// since there'd be too many. Kept with the session, so results reopened in a
+// later sitting still show what was done to them, and exported as CSV for an
+// audit trail.
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub time: u64, // Seconds since the Unix epoch
+    pub action: String, // e.g. "Trash 12 candidates"
+    #[serde(default)]
+    pub groups: Vec<String>, // IDs of the groups it touched; see group_id()
+    #[serde(default)]
+    pub files: usize, // Acted on successfully
+    #[serde(default)]
+    pub bytes: u64, // Their total size
+    #[serde(default)]
+    pub failed: usize,
+    // The files that failed, for showing just their errors. The errors
+    // themselves aren't kept with the session, so neither are these.
+    #[serde(skip)]
+    pub failed_paths: Vec<PathBuf>,
+}
+
+impl HistoryEntry {
+    // Happening now, with nothing done yet.
+    pub fn new(action: String, groups: Vec<String>) -> HistoryEntry {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+        HistoryEntry{time, action, groups, files: 0, bytes: 0, failed: 0, failed_paths: vec![]}
+    }
+
+    pub fn fail(&mut self, path: PathBuf) {
+        self.failed += 1;
+        self.failed_paths.push(path);
+    }
+
+    pub fn time_text(&self) -> String {
+        format_time(UNIX_EPOCH + Duration::from_secs(self.time))
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        match (self.files, self.failed) {
+            (_, 0) => Outcome::Succeeded,
+            (0, _) => Outcome::Failed,
+            _ => Outcome::Partial,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Succeeded,
+    Partial, // Some files failed
+    Failed,
+}
+
+impl Outcome {
+    // For exports, untranslated.
+    fn key(self) -> &'static str {
+        match self {
+            Outcome::Succeeded => "succeeded",
+            Outcome::Partial => "partly failed",
+            Outcome::Failed => "failed",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Succeeded => tr!("Succeeded"),
+            Outcome::Partial => tr!("Partly failed"),
+            Outcome::Failed => tr!("Failed"),
+        }
+    }
+}
+
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    // As in ActionPlan::to_csv(), text fields are all quoted.
+    let quote = |x: &str| format!("\"{}\"", x.replace('"', "\"\""));
+    let mut csv = "time,action,groups,files,bytes,failed,outcome\n".to_string();
+    for entry in entries {
+        csv += &[
+            quote(&entry.time_text()),
+            quote(&entry.action),
+            quote(&entry.groups.join(" ")),
+            entry.files.to_string(),
+            entry.bytes.to_string(),
+            entry.failed.to_string(),
+            quote(entry.outcome().key()),
+        ].join(",");
+        csv.push('\n');
+    }
+    csv
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_exports_one_row_per_action() {
+        let mut trash = HistoryEntry::new("Trash 2 \"extra\" copies".to_string(), vec!["0a1b".to_string(), "2c3d".to_string()]);
+        trash.time = 86400 + 3661;
+        trash.files = 2;
+        trash.bytes = 2048;
+        trash.fail(PathBuf::from("/photos/a.jpg"));
+        let ignore = HistoryEntry::new("Ignore group".to_string(), vec!["4e5f".to_string()]);
+
+        let json = serde_json::to_string(&[&trash, &ignore]).unwrap();
+        let restored: Vec<HistoryEntry> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored[0].failed, 1);
+        assert!(restored[0].failed_paths.is_empty());
+
+        let csv = to_csv(&restored);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "\"1970-01-02 01:01:01 UTC\",\"Trash 2 \"\"extra\"\" copies\",\"0a1b 2c3d\",2,2048,1,\"partly failed\"");
+        assert!(lines[2].ends_with(",\"4e5f\",0,0,0,\"succeeded\""));
+    }
+}
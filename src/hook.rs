@@ -0,0 +1,310 @@
+use crate::misc::{Image, group_id};
+use crate::session::SessionImage;
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+
+// A user-configured command run once per duplicate group, to hand results to
+// other tools. Only ever run when the user asks, never when a search finishes.
+//
+// The template is split into words like a shell would (quotes and backslashes,
+// but nothing else; it isn't run through one). Placeholders:
+//   {paths}       every member's path, one argument each; must be a word by
+//                 itself. The command's stdin also gets them, each followed by
+//                 a NUL, as `xargs -0` wants.
+//   {group_json}  a temporary file with the group as JSON, removed once the
+//                 command exits.
+
+pub const PATHS: &str = "{paths}";
+pub const GROUP_JSON: &str = "{group_json}";
+
+// Long enough for a script that uploads or converts a group, short enough
+// that a hung one doesn't hold up the rest.
+const TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Kept to the end, where the error usually is.
+const MAX_STDERR: usize = 4096;
+
+// How long to wait for the rest of stderr once the command's exited.
+const STDERR_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct GroupJson<'a> {
+    group: usize, // As numbered in the results, from 1
+    id: String, // See misc::group_id()
+    members: &'a [SessionImage],
+}
+
+// How running the hook on one group went.
+pub struct HookOutcome {
+    pub group: usize, // As numbered in the results, from 1
+    pub result: Result<(), String>,
+    pub stderr: String,
+}
+
+// Splits the template into words, checking the placeholders are usable.
+pub fn parse(template: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None; // None between words
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                words.extend(word.take());
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unclosed single quote".to_string()),
+                    }
+                }
+            },
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(chars.next()),
+                        Some(c) => word.push(c),
+                        None => return Err("Unclosed double quote".to_string()),
+                    }
+                }
+            },
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    if words.is_empty() {
+        return Err("No command given".to_string());
+    }
+    if words.iter().any(|x| x.contains(PATHS) && x != PATHS) {
+        return Err(format!("{PATHS} has to be a word by itself"));
+    }
+    Ok(words)
+}
+
+// The command's arguments with the placeholders filled in.
+fn expand(words: &[String], paths: &[PathBuf], json: &Path) -> Vec<String> {
+    let json = json.display().to_string();
+    words.iter()
+        .flat_map(|word| match word.as_str() {
+            PATHS => paths.iter().map(|x| x.display().to_string()).collect(),
+            _ => vec![word.replace(GROUP_JSON, &json)],
+        })
+        .collect()
+}
+
+// Runs the command on one group, waiting for it to exit (or time out).
+fn run_group(words: &[String], group: usize, members: &[Image], timeout: Duration, cancel: &AtomicBool) -> HookOutcome {
+    let mut stderr = String::new();
+    let result = run_group_inner(words, group, members, timeout, cancel, &mut stderr);
+    HookOutcome{group, result, stderr}
+}
+
+fn run_group_inner(
+    words: &[String],
+    group: usize,
+    members: &[Image],
+    timeout: Duration,
+    cancel: &AtomicBool,
+    stderr: &mut String,
+) -> Result<(), String> {
+    let id = group_id(members);
+    let members: Vec<_> = members.iter().map(SessionImage::from_image).collect();
+    let json = serde_json::to_vec_pretty(&GroupJson{group, id, members: &members})
+        .map_err(|e| format!("Error serializing the group: {e}"))?;
+    // Under a random name, created only if nothing's there (so not through a
+    // symlink someone else planted), readable only by the user, and deleted
+    // once the command's done with it.
+    let mut file = tempfile::Builder::new()
+        .prefix("deckard-hook-")
+        .suffix(".json")
+        .tempfile()
+        .map_err(|e| format!("Error creating the group's file: {e}"))?;
+    file.write_all(&json)
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("Error writing {}: {e}", file.path().display()))?;
+
+    let paths: Vec<_> = members.into_iter().map(|x| x.path).collect();
+    let args = expand(words, &paths, file.path());
+    run_command(&args, &paths, timeout, cancel, stderr)
+}
+
+fn run_command(
+    args: &[String],
+    paths: &[PathBuf],
+    timeout: Duration,
+    cancel: &AtomicBool,
+    stderr: &mut String,
+) -> Result<(), String> {
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Couldn't run {}: {e}", args[0]))?;
+
+    // Fed and drained on their own threads, so a command that doesn't read
+    // its input, or writes a lot of errors, can't block on a full pipe. They
+    // aren't joined: anything the command left running in the background can
+    // hold the pipes open indefinitely.
+    let mut input = vec![];
+    for path in paths {
+        input.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        input.push(0);
+    }
+    let mut stdin = child.stdin.take().unwrap();
+    std::thread::spawn(move || {
+        let _ = stdin.write_all(&input); // The command doesn't have to read it
+    });
+    let mut pipe = child.stderr.take().unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = vec![];
+        let _ = pipe.read_to_end(&mut buf);
+        let _ = sender.send(buf);
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if cancel.load(Ordering::Relaxed) => break Err("Canceled".to_string()),
+            Ok(None) if start.elapsed() > timeout => {
+                break Err(format!("Timed out after {} seconds", timeout.as_secs()));
+            },
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => break Err(format!("Error waiting for {}: {e}", args[0])),
+        }
+    };
+    if status.is_err() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    let output = receiver.recv_timeout(STDERR_GRACE).unwrap_or_default();
+    let output = String::from_utf8_lossy(&output);
+    let skip = output.len().saturating_sub(MAX_STDERR);
+    let skip = (skip..output.len()).find(|x| output.is_char_boundary(*x)).unwrap_or(output.len());
+    *stderr = output[skip..].trim_end().to_string();
+
+    let status = status?;
+    if status.success() {
+        return Ok(());
+    }
+    match status.code() {
+        Some(code) => Err(format!("Exited with status {code}")),
+        None => Err("Killed by a signal".to_string()),
+    }
+}
+
+// The hook being run on a batch of groups in the background, one at a time.
+pub struct HookJob {
+    handle: JoinHandle<Vec<HookOutcome>>,
+    cancel: Arc<AtomicBool>,
+    done: Arc<AtomicUsize>, // Groups finished so far
+    pub total: usize,
+}
+
+impl HookJob {
+    // Groups are given with their numbers in the results.
+    pub fn start(words: Vec<String>, groups: Vec<(usize, Vec<Image>)>) -> HookJob {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicUsize::new(0));
+        let total = groups.len();
+        let handle = {
+            let (cancel, done) = (cancel.clone(), done.clone());
+            std::thread::spawn(move || {
+                let mut outcomes = vec![];
+                for (group, members) in groups {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    outcomes.push(run_group(&words, group, &members, TIMEOUT, &cancel));
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+                outcomes
+            })
+        };
+        HookJob{handle, cancel, done, total}
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Groups skipped after canceling have no outcome.
+    pub fn join(self) -> Vec<HookOutcome> {
+        self.handle.join().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_like_a_shell() {
+        let words = parse(r#"  my-dedupe.sh --out 'a b' "c \"d\"" e\ f {paths} x={group_json} "#).unwrap();
+        assert_eq!(words, ["my-dedupe.sh", "--out", "a b", "c \"d\"", "e f", "{paths}", "x={group_json}"]);
+
+        assert!(parse("   ").is_err());
+        assert!(parse("echo 'oops").is_err());
+        assert!(parse("echo --files={paths}").is_err());
+
+        let paths = [PathBuf::from("/a b.jpg"), PathBuf::from("/c.png")];
+        let args = expand(&words[4..], &paths, Path::new("/tmp/g.json"));
+        assert_eq!(args, ["e f", "/a b.jpg", "/c.png", "x=/tmp/g.json"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_failures_and_timeouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let members = vec![Image::new(dir.path().join("a.png"), vec![], None)];
+        let cancel = AtomicBool::new(false);
+        let run = |script: &str, timeout| {
+            let words = ["sh", "-c", script, "hook", GROUP_JSON].map(String::from);
+            run_group(&words, 3, &members, timeout, &cancel)
+        };
+
+        let out = dir.path().join("out");
+        let ok = run(&format!("tr '\\0' '\\n' > '{}'.paths && cp \"$1\" '{}'.json", out.display(), out.display()), TIMEOUT);
+        assert_eq!(ok.result, Ok(()), "{}", ok.stderr);
+        let paths = std::fs::read_to_string(out.with_extension("paths")).unwrap();
+        assert_eq!(paths, format!("{}\n", dir.path().join("a.png").display()));
+        let json: serde_json::Value = serde_json::from_slice(&std::fs::read(out.with_extension("json")).unwrap()).unwrap();
+        assert_eq!(json["group"], 3);
+        assert_eq!(json["members"][0]["path"], dir.path().join("a.png").display().to_string());
+
+        let failed = run("echo nope >&2; exit 3", TIMEOUT);
+        assert_eq!(failed.result, Err("Exited with status 3".to_string()));
+        assert_eq!(failed.stderr, "nope");
+
+        let hung = run("exec sleep 10", Duration::from_millis(200));
+        assert!(hung.result.unwrap_err().starts_with("Timed out"));
+    }
+}
@@ -0,0 +1,1077 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use humansize::{ToF64, Unsigned, DECIMAL, BINARY};
+use lazy_static::lazy_static;
+
+
+// Looks up the translation of an English string in the current language, or
+// returns it unchanged if there isn't one. Placeholders like {count} can be
+// filled in by name, since format!() needs its format string at compile time.
+macro_rules! tr {
+    ($key:literal) => {
+        $crate::i18n::tr($key)
+    };
+    ($key:literal, $($name:ident = $val:expr),+ $(,)?) => {{
+        let mut s = $crate::i18n::tr($key).to_string();
+        $( s = s.replace(concat!("{", stringify!($name), "}"), &$val.to_string()); )+
+        s
+    }};
+}
+
+// App-wide display settings. They're read from all over the place (including
+// while formatting), so they're globals rather than being threaded through
+// every phase; App loads and saves them.
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+static UNITS: AtomicU8 = AtomicU8::new(SizeUnits::Decimal as u8);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    // Always shown in the language itself, so it can be found in the picker
+    // whatever the current language is.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    pub fn to_key(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Language> {
+        Language::ALL.into_iter().find(|x| x.to_key() == key)
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Language::English => '.',
+            Language::German => ',',
+        }
+    }
+
+    pub fn current() -> Language {
+        Language::ALL[LANGUAGE.load(Ordering::Relaxed) as usize]
+    }
+
+    pub fn set_current(self) {
+        LANGUAGE.store(self as u8, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SizeUnits {
+    Decimal, // kB, MB; powers of 1000
+    Binary, // KiB, MiB; powers of 1024
+}
+
+impl SizeUnits {
+    pub fn to_key(self) -> &'static str {
+        match self {
+            SizeUnits::Decimal => "decimal",
+            SizeUnits::Binary => "binary",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<SizeUnits> {
+        match key {
+            "decimal" => Some(SizeUnits::Decimal),
+            "binary" => Some(SizeUnits::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn current() -> SizeUnits {
+        match UNITS.load(Ordering::Relaxed) {
+            x if x == SizeUnits::Binary as u8 => SizeUnits::Binary,
+            _ => SizeUnits::Decimal,
+        }
+    }
+
+    pub fn set_current(self) {
+        UNITS.store(self as u8, Ordering::Relaxed);
+    }
+}
+
+// humansize::format_size() in the current units and language.
+pub fn format_size(bytes: impl ToF64 + Unsigned) -> String {
+    let opts = match SizeUnits::current() {
+        SizeUnits::Decimal => DECIMAL,
+        SizeUnits::Binary => BINARY,
+    };
+    let text = humansize::format_size(bytes, opts);
+    match Language::current().decimal_separator() {
+        '.' => text,
+        sep => text.replace('.', &sep.to_string()),
+    }
+}
+
+pub fn tr(key: &'static str) -> &'static str {
+    let lang = Language::current();
+    if lang == Language::English {
+        return key;
+    }
+    CATALOG.get(&(lang, key)).copied().unwrap_or(key)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Keyed by the English text, so untranslated strings still read fine.
+const GERMAN: &[(&str, &str)] = &[
+    // StartupPhase
+    ("Dismiss", "Ausblenden"),
+    ("Compare two folders", "Zwei Ordner vergleichen"),
+    ("Find images in the candidates folder that already exist in the reference folder",
+        "Bilder im Kandidatenordner finden, die bereits im Referenzordner vorhanden sind"),
+    ("Reference: ", "Referenz: "),
+    ("Candidates: ", "Kandidaten: "),
+    ("Root Path: ", "Stammordner: "),
+    ("Choose...", "Auswählen..."),
+    ("Advanced", "Erweitert"),
+    ("Follow Symlinks:", "Symbolischen Links folgen:"),
+    ("Entry Limit:", "Eintragslimit:"),
+    ("Limit to subfolders…", "Auf Unterordner beschränken…"),
+    ("Limit to subfolders", "Auf Unterordner beschränken"),
+    ("Searching the whole folder", "Der ganze Ordner wird durchsucht"),
+    ("Only {count} subfolders", "Nur {count} Unterordner"),
+    ("Only the checked folders will be searched. With none checked, the whole folder is.",
+        "Nur die markierten Ordner werden durchsucht. Ist keiner markiert, der ganze Ordner."),
+    ("Can't read folder: {error}", "Ordner nicht lesbar: {error}"),
+    ("No subfolders", "Keine Unterordner"),
+    ("Clear", "Leeren"),
+    ("Done", "Fertig"),
+    ("Modified After:", "Geändert nach:"),
+    ("Modified Before:", "Geändert vor:"),
+    ("Include copies outside the dates", "Kopien außerhalb des Zeitraums einbeziehen"),
+    ("Instead of ignoring files outside the dates, show any group \
+        with at least one file inside them",
+        "Statt Dateien außerhalb des Zeitraums zu ignorieren, jede Gruppe zeigen, \
+        in der mindestens eine Datei im Zeitraum liegt"),
+    ("Size Cap:", "Größenlimit:"),
+    ("Larger images are scaled down before hashing, which finds \
+        the same duplicates much faster",
+        "Größere Bilder werden vor dem Hashen verkleinert, was dieselben \
+        Duplikate viel schneller findet"),
+    ("Skip instead", "Stattdessen überspringen"),
+    ("Respect Ignore Files:", "Ignore-Dateien beachten:"),
+    ("I/O Parallelism:", "Parallele Lesezugriffe:"),
+    ("Hash Algorithm:", "Hash-Algorithmus:"),
+    ("Verify Matches by Color:", "Treffer per Farbe prüfen:"),
+    ("Detect Crops (experimental):", "Ausschnitte erkennen (experimentell):"),
+    ("Also look for images that are crops of others. Much slower, and matches \
+        should be checked by eye.",
+        "Auch nach Bildern suchen, die Ausschnitte anderer sind. Deutlich langsamer, \
+        und Treffer sollten von Auge geprüft werden."),
+    ("Collapse Bursts:", "Serienbilder einklappen:"),
+    ("Collapse groups whose images are all in one folder and were taken within \
+        a few seconds of each other",
+        "Gruppen einklappen, deren Bilder alle in einem Ordner liegen und innerhalb \
+        weniger Sekunden aufgenommen wurden"),
+    ("More Info:", "Mehr Infos:"),
+    ("link", "Link"),
+    ("Compare Algorithms:", "Algorithmen vergleichen:"),
+    ("Compare algorithms on a sample", "Algorithmen an einer Stichprobe vergleichen"),
+    ("Hash a few hundred random images from the root with each algorithm \
+        and compare their speed and false matches",
+        "Einige hundert zufällige Bilder aus dem Stammordner mit jedem Algorithmus hashen \
+        und Geschwindigkeit und Fehltreffer vergleichen"),
+    ("Algorithm comparison", "Algorithmenvergleich"),
+    ("Hashed {count} images sampled from {root} ({failed} couldn't be read)",
+        "{count} Bilder aus {root} gehasht ({failed} nicht lesbar)"),
+    ("Algorithm", "Algorithmus"),
+    ("Speed", "Geschwindigkeit"),
+    ("Collisions", "Kollisionen"),
+    ("{rate} img/s", "{rate} Bilder/s"),
+    ("Collisions are pairs of sampled images with identical hashes. A random sample \
+        rarely contains real duplicates, so fewer is usually better.",
+        "Kollisionen sind Paare von Bildern mit identischem Hash. Eine Zufallsstichprobe \
+        enthält selten echte Duplikate, daher ist weniger meist besser."),
+    ("Use {alg}", "{alg} verwenden"),
+    ("Extensions:", "Dateiendungen:"),
+    ("Detect Type by Content:", "Typ am Inhalt erkennen:"),
+    ("UI Scale:", "Skalierung:"),
+    ("Language:", "Sprache:"),
+    ("Size Units:", "Größeneinheiten:"),
+    ("Decimal (MB)", "Dezimal (MB)"),
+    ("Binary (MiB)", "Binär (MiB)"),
+    ("Preset:", "Voreinstellung:"),
+    ("Name", "Name"),
+    ("Save", "Speichern"),
+    ("Cancel", "Abbrechen"),
+    ("Save current as…", "Aktuelle speichern als…"),
+    ("Save preset", "Voreinstellung speichern"),
+    ("Delete preset", "Voreinstellung löschen"),
+    ("A preset named \"{name}\" already exists. Replace it?",
+        "Eine Voreinstellung namens \"{name}\" existiert bereits. Ersetzen?"),
+    ("Delete the preset \"{name}\"?", "Die Voreinstellung \"{name}\" löschen?"),
+    ("Search", "Suchen"),
+    ("Resume the unfinished scan ({count} images already hashed)?",
+        "Den unfertigen Scan fortsetzen ({count} Bilder bereits gehasht)?"),
+    ("Resume the unfinished scan ({percent}% done)?", "Den unfertigen Scan fortsetzen ({percent} % erledigt)?"),
+    ("Resume", "Fortsetzen"),
+    ("Discard", "Verwerfen"),
+    ("Restore last results", "Letzte Ergebnisse wiederherstellen"),
+    ("Reopen the results saved from the last session",
+        "Die in der letzten Sitzung gespeicherten Ergebnisse öffnen"),
+
+    // SearchingPhase
+    ("<- New Search", "<- Neue Suche"),
+    ("Searching", "Suche läuft"),
+    ("Finalizing results… {done} of {total}", "Ergebnisse werden zusammengestellt… {done} von {total}"),
+
+    // OutputPhase
+    ("Candidates in", "Kandidaten in"),
+    ("found in", "gefunden in"),
+    ("Results for", "Ergebnisse für"),
+    ("Undo", "Rückgängig"),
+    ("Nothing to undo", "Nichts rückgängig zu machen"),
+    ("Trash matching candidates", "Passende Kandidaten löschen"),
+    ("Export HTML report…", "HTML-Bericht exportieren…"),
+    ("Write a single page with thumbnails of every group, to review anywhere",
+        "Eine einzelne Seite mit Vorschaubildern aller Gruppen schreiben, um sie überall durchzusehen"),
+    ("Exporting report", "Bericht wird exportiert"),
+    ("Making thumbnails… {done} of {total}", "Vorschaubilder werden erstellt… {done} von {total}"),
+    ("Save session", "Sitzung speichern"),
+    ("Save these results so they can be restored after restarting",
+        "Diese Ergebnisse speichern, um sie nach einem Neustart wiederherzustellen"),
+    ("Deduplicate via hard links", "Per Hardlinks deduplizieren"),
+    ("Replace duplicates with hard links to a single copy",
+        "Duplikate durch Hardlinks auf eine einzige Kopie ersetzen"),
+    ("Layout:", "Ansicht:"),
+    ("List", "Liste"),
+    ("Cards", "Karten"),
+    ("Results", "Ergebnisse"),
+    ("By folder", "Nach Ordner"),
+    ("Depth:", "Tiefe:"),
+    ("Nothing reclaimable", "Nichts freizugeben"),
+    ("(top level)", "(oberste Ebene)"),
+    ("Show groups in {folder}", "Gruppen in {folder} anzeigen"),
+    ("Showing {count} groups with files in", "{count} Gruppen mit Dateien in"),
+    ("Show all", "Alle anzeigen"),
+    ("{groups} groups, {images} images, {size} reclaimable, {errors} errors",
+        "{groups} Gruppen, {images} Bilder, {size} freizugeben, {errors} Fehler"),
+    ("Done on {root}, found no duplicates", "{root} durchsucht, keine Duplikate gefunden"),
+    ("Scan details", "Scan-Details"),
+    ("Directories visited:", "Besuchte Ordner:"),
+    ("Files seen:", "Gefundene Dateien:"),
+    ("Images hashed:", "Gehashte Bilder:"),
+    ("Bytes hashed:", "Gehashte Daten:"),
+    ("Detected by content:", "Am Inhalt erkannt:"),
+    ("Decode failures:", "Dekodierfehler:"),
+    ("Split by color:", "Per Farbe getrennt:"),
+    ("Possible crops:", "Mögliche Ausschnitte:"),
+    ("Skipped, over size cap:", "Übersprungen, über Größenlimit:"),
+    ("Skipped, no extension:", "Übersprungen, ohne Endung:"),
+    ("Skipped by date:", "Übersprungen nach Datum:"),
+    ("Unknown modification time:", "Unbekanntes Änderungsdatum:"),
+    ("Skipped by extension:", "Übersprungen nach Endung:"),
+    ("Hard link results", "Hardlink-Ergebnisse"),
+    ("{linked} linked, {failed} failed", "{linked} verlinkt, {failed} fehlgeschlagen"),
+    ("Reference", "Referenz"),
+    ("Candidate", "Kandidat"),
+    ("Changed since scan", "Seit dem Scan geändert"),
+    ("Hard link of another copy", "Hardlink auf eine andere Kopie"),
+    ("Open", "Öffnen"),
+    ("Show", "Zeigen"),
+    ("Quick Look", "Übersicht"),
+    ("Preview with Quick Look (Space)", "Mit Übersicht anzeigen (Leertaste)"),
+    ("Quick Look last hovered image", "Zuletzt berührtes Bild in Übersicht anzeigen"),
+    ("Copy path", "Pfad kopieren"),
+    ("Group {number}", "Gruppe {number}"),
+    ("Modified {time}", "Geändert {time}"),
+    ("Type a command…", "Befehl eingeben…"),
+    ("No matching commands", "Keine passenden Befehle"),
+    ("Cancel search", "Suche abbrechen"),
+    ("Choose reference folder…", "Referenzordner wählen…"),
+    ("Choose candidates folder…", "Kandidatenordner wählen…"),
+    ("Choose root…", "Stammordner wählen…"),
+    ("Start search", "Suche starten"),
+    ("Resume unfinished scan", "Unvollständigen Scan fortsetzen"),
+    ("New search", "Neue Suche"),
+    ("New tab", "Neuer Tab"),
+    ("Close tab", "Tab schließen"),
+    ("Searching {folder}", "Suche in {folder}"),
+    ("Continue anyway", "Trotzdem fortfahren"),
+    ("Already being searched", "Wird bereits durchsucht"),
+    ("Another Deckard window is already searching {folder}. Searching it twice \
+        at once will slow both down.",
+        "Ein anderes Deckard-Fenster durchsucht {folder} bereits. Zwei Suchen gleichzeitig \
+        verlangsamen beide."),
+    ("Turn off dry run", "Probelauf ausschalten"),
+    ("Turn on dry run", "Probelauf einschalten"),
+    ("Stop watching for changes", "Nicht mehr auf Änderungen achten"),
+    ("Show wasted space by folder", "Verschwendeten Platz nach Ordner zeigen"),
+    ("Show results", "Ergebnisse zeigen"),
+    ("Show results as cards", "Ergebnisse als Karten zeigen"),
+    ("Show results as a list", "Ergebnisse als Liste zeigen"),
+    ("Manage ignored groups", "Ignorierte Gruppen verwalten"),
+    ("The search crashed: {msg}", "Die Suche ist abgestürzt: {msg}"),
+    ("No extensions selected", "Keine Dateiendungen ausgewählt"),
+    ("Not supported: {exts}", "Nicht unterstützt: {exts}"),
+    ("All", "Alle"),
+    ("None", "Keine"),
+    ("Common photos only", "Nur gängige Fotoformate"),
+    ("Choose…", "Auswählen…"),
+    ("Search anyway", "Trotzdem suchen"),
+    ("Search a system folder?", "Einen Systemordner durchsuchen?"),
+    ("{folder} is a system folder, a drive, or contains home folders. Duplicates \
+        there can include files the system and other programs need, and trashing them \
+        can break things. Consider searching a specific folder of photos instead.",
+        "{folder} ist ein Systemordner, ein Laufwerk oder enthält Benutzerordner. Duplikate \
+        dort können Dateien sein, die das System und andere Programme brauchen, und sie in \
+        den Papierkorb zu verschieben kann Schäden anrichten. Durchsuche besser einen \
+        bestimmten Fotoordner."),
+    ("System file", "Systemdatei"),
+    ("In a folder the OS manages; bulk actions leave it alone", "In einem vom Betriebssystem verwalteten Ordner; Massenaktionen lassen sie unangetastet"),
+    ("Search took {time} ({shares})", "Suche dauerte {time} ({shares})"),
+    ("Search took {time}", "Suche dauerte {time}"),
+    ("Most of the time went to reading and decoding images. A lower size cap \
+        downscales large images sooner, and on a network volume, fewer I/O threads \
+        can help.", "Die meiste Zeit ging in das Lesen und Dekodieren der Bilder. Eine niedrigere \
+        Größengrenze verkleinert große Bilder früher, und auf einem Netzlaufwerk können \
+        weniger E/A-Threads helfen."),
+    ("decode", "Dekodierung"),
+    ("hash", "Hashing"),
+    ("walk", "Durchlauf"),
+    ("Walk", "Durchlauf"),
+    ("I/O", "E/A"),
+    ("Decode", "Dekodierung"),
+    ("Panic", "Absturz"),
+    ("Too large", "Zu groß"),
+    ("Filter…", "Filtern…"),
+    ("Copy all visible", "Alle sichtbaren kopieren"),
+    ("Showing {shown} of {total}", "{shown} von {total} angezeigt"),
+    ("Category", "Kategorie"),
+    ("Path", "Pfad"),
+    ("Message", "Meldung"),
+    ("Reveal", "Zeigen"),
+    ("Retry", "Wiederholen"),
+    ("Recommended keeper", "Empfohlen zum Behalten"),
+    ("The lossless or highest quality copy in its group; between otherwise equal copies, the \
+        one that wasn't renamed",
+        "Die verlustfreie oder hochwertigste Kopie der Gruppe; bei sonst gleichen Kopien die, \
+        die nicht umbenannt wurde"),
+    ("The same picture in different formats or qualities", "Dasselbe Bild in verschiedenen Formaten oder Qualitäten"),
+    ("{subsampling} chroma subsampling", "{subsampling} Chroma-Subsampling"),
+    ("exact", "exakt"),
+    ("distance {distance}", "Abstand {distance}"),
+    ("How many bits of its hash differ from the rest of the group's", "Wie viele Bits seines Hashes von denen der übrigen Gruppe abweichen"),
+    ("Photo", "Foto"),
+    ("Screenshot", "Bildschirmfoto"),
+    ("Graphic", "Grafik"),
+    ("Photos", "Fotos"),
+    ("Screenshots", "Bildschirmfotos"),
+    ("Graphics", "Grafiken"),
+    ("Show:", "Zeigen:"),
+    ("A guess from the image's size and colors; only used for display and filtering",
+        "Eine Schätzung anhand von Größe und Farben des Bildes; nur für Anzeige und Filter"),
+    ("Possible crop", "Möglicher Ausschnitt"),
+    ("Matched by crop detection; check that it really is the same photo",
+        "Per Ausschnitterkennung gefunden; prüfen, ob es wirklich dasselbe Foto ist"),
+    ("Animated, {count} frames", "Animiert, {count} Bilder"),
+    ("already hard-linked", "bereits per Hardlink verbunden"),
+    ("Errors", "Fehler"),
+    ("Ignore group", "Gruppe ignorieren"),
+    ("Don't show this group again in future searches",
+        "Diese Gruppe in künftigen Suchen nicht mehr anzeigen"),
+    ("{count} ignored groups hidden", "{count} ignorierte Gruppen ausgeblendet"),
+    ("Manage", "Verwalten"),
+    ("Ignored groups", "Ignorierte Gruppen"),
+    ("No ignored groups", "Keine ignorierten Gruppen"),
+    ("Unignore", "Nicht mehr ignorieren"),
+    ("Burst of {count}", "Serie mit {count} Bildern"),
+    ("Expand", "Aufklappen"),
+    ("Collapse", "Einklappen"),
+    ("Keep first of each burst", "Nur erstes Bild jeder Serie behalten"),
+    ("Move every image but the earliest in each burst to the trash",
+        "Alle Bilder außer dem frühesten jeder Serie in den Papierkorb verschieben"),
+    ("Watch for changes", "Auf Änderungen achten"),
+    ("Keep watching the folders, and add new duplicates as they turn up",
+        "Die Ordner weiter beobachten und neue Duplikate hinzufügen, sobald sie auftauchen"),
+    ("Notify", "Benachrichtigen"),
+    ("New", "Neu"),
+    ("Found while watching for changes", "Beim Beobachten auf Änderungen gefunden"),
+    ("Found {count} new duplicates", "{count} neue Duplikate gefunden"),
+    ("Dry run", "Probelauf"),
+    ("Show what bulk actions would do, without doing it",
+        "Zeigen, was Sammelaktionen tun würden, ohne es zu tun"),
+    ("Dry run: {description}", "Probelauf: {description}"),
+    ("{count} files, {problems} with problems", "{count} Dateien, {problems} mit Problemen"),
+    ("Export CSV…", "Als CSV exportieren…"),
+    ("Export JSON…", "Als JSON exportieren…"),
+    ("Execute this plan now", "Diesen Plan jetzt ausführen"),
+    ("File", "Datei"),
+    ("Action", "Aktion"),
+    ("Destination", "Ziel"),
+    ("Problem", "Problem"),
+    ("Trash", "Papierkorb"),
+    ("Hard link", "Hardlink"),
+    ("Skip", "Überspringen"),
+    ("Retry failed files ({count})", "Fehlgeschlagene Dateien erneut versuchen ({count})"),
+
+    // Memory budget
+    ("Over memory budget", "Über dem Speicherbudget"),
+    ("Memory Budget:", "Speicherbudget:"),
+    ("Automatic", "Automatisch"),
+    ("A quarter of the memory that was free when Deckard started",
+        "Ein Viertel des Speichers, der beim Start von Deckard frei war"),
+    ("Images that would need more memory than this to decode are skipped. Raise it if you have memory to spare.",
+        "Bilder, die zum Dekodieren mehr Speicher bräuchten, werden übersprungen. Erhöhen, wenn genug Speicher frei ist."),
+    ("Skipped, over memory budget:", "Übersprungen, über Speicherbudget:"),
+    ("{count} images needed more memory to decode than the budget allows. If you have memory to spare, raise the memory budget under Advanced and search again.",
+        "{count} Bilder bräuchten zum Dekodieren mehr Speicher als das Budget erlaubt. Wenn genug Speicher frei ist, das Speicherbudget unter Erweitert erhöhen und erneut suchen."),
+
+    // Finder tags
+    ("Gray", "Grau"),
+    ("Green", "Grün"),
+    ("Purple", "Lila"),
+    ("Blue", "Blau"),
+    ("Yellow", "Gelb"),
+    ("Red", "Rot"),
+    ("Orange", "Orange"),
+    ("Tag", "Markieren"),
+    ("Finder tags", "Finder-Tags"),
+    ("Only untagged", "Nur ohne Tags"),
+    ("Hide groups with any file that has a Finder tag", "Gruppen ausblenden, in denen eine Datei ein Finder-Tag hat"),
+    ("Tag duplicates…", "Duplikate markieren…"),
+    ("Tag every shown duplicate in Finder, to review them there",
+        "Alle angezeigten Duplikate im Finder markieren, um sie dort zu prüfen"),
+
+    // Lookup
+    ("Look up an image…", "Bild nachschlagen…"),
+    ("Check whether an image is already in a folder searched before",
+        "Prüfen, ob ein Bild schon in einem früher durchsuchten Ordner liegt"),
+    ("Look up an image", "Bild nachschlagen"),
+    ("Choose image…", "Bild wählen…"),
+    ("or drop one here", "oder hierher ziehen"),
+    ("Max distance:", "Maximaler Abstand:"),
+    ("How many bits of the hash may differ. 0 finds only images the search would group together.",
+        "Wie viele Bits des Hashs abweichen dürfen. Mit 0 werden nur Bilder gefunden, die die Suche gruppieren würde."),
+    ("Indexed folders", "Indizierte Ordner"),
+    ("Looking up", "Nachschlagen"),
+    ("Searching the index…", "Index wird durchsucht…"),
+    ("Choose an image to check whether it's already in a folder searched before.",
+        "Ein Bild wählen, um zu prüfen, ob es schon in einem früher durchsuchten Ordner liegt."),
+    ("Not found in any indexed folder", "In keinem indizierten Ordner gefunden"),
+    ("Found {count} matches", "{count} Treffer gefunden"),
+    ("Nothing indexed yet. Every search adds the folders it searched.",
+        "Noch nichts indiziert. Jede Suche fügt die durchsuchten Ordner hinzu."),
+    ("Saving the index…", "Index wird gespeichert…"),
+    ("Folder", "Ordner"),
+    ("Indexed", "Indiziert"),
+    ("Images", "Bilder"),
+    ("Re-index", "Neu indizieren"),
+
+    // Borders
+    ("Trim Uniform Borders:", "Einfarbige Ränder entfernen:"),
+    ("Ignore solid-color bars along the edges when comparing, so letterboxed or \
+        padded copies match the original",
+        "Einfarbige Balken an den Rändern beim Vergleich ignorieren, damit Kopien mit \
+        Letterbox oder Rand zum Original passen"),
+
+    // Hook
+    ("Hook", "Hook"),
+    ("Post-search Hook:", "Hook nach der Suche:"),
+    ("A command to run on a duplicate group when you ask for it from the results. \
+        {paths} becomes the group's paths, which are also passed on stdin, NUL-separated; \
+        {group_json} becomes a file with the group as JSON. Never run automatically.",
+        "Ein Befehl, der auf Wunsch aus den Ergebnissen für eine Duplikatgruppe ausgeführt \
+        wird. {paths} wird zu den Pfaden der Gruppe, die auch NUL-getrennt über stdin \
+        übergeben werden; {group_json} wird zu einer Datei mit der Gruppe als JSON. Wird nie \
+        automatisch ausgeführt."),
+    ("Run hook", "Hook ausführen"),
+    ("Run hook on all groups", "Hook für alle Gruppen ausführen"),
+    ("Run the post-search hook once for each shown group",
+        "Den Hook einmal für jede angezeigte Gruppe ausführen"),
+    ("Running hook", "Hook läuft"),
+    ("Group {done} of {total}", "Gruppe {done} von {total}"),
+    ("Hook results", "Hook-Ergebnisse"),
+    ("{succeeded} succeeded, {failed} failed", "{succeeded} erfolgreich, {failed} fehlgeschlagen"),
+
+    // Recent roots
+    ("Recent folders", "Zuletzt durchsuchte Ordner"),
+    ("Pinned folders stay in the list", "Angeheftete Ordner bleiben in der Liste"),
+    ("Not found. If it's on a drive that isn't connected, connect it before searching.",
+        "Nicht gefunden. Liegt er auf einem nicht verbundenen Laufwerk, dieses vor der Suche verbinden."),
+    ("Clear history", "Verlauf löschen"),
+    ("Forget every folder that isn't pinned", "Alle nicht angehefteten Ordner vergessen"),
+
+    // Folder spread
+    ("All groups", "Alle Gruppen"),
+    ("Same-folder groups", "Gruppen in einem Ordner"),
+    ("Cross-folder groups", "Ordnerübergreifende Gruppen"),
+    ("{same} same-folder, {cross} cross-folder", "{same} in einem Ordner, {cross} ordnerübergreifend"),
+    ("Renamed copy", "Umbenannte Kopie"),
+    ("Named like an automatic copy, e.g. \"IMG_1234 (1)\", of another file in this folder",
+        "Benannt wie eine automatische Kopie, z. B. „IMG_1234 (1)“, einer anderen Datei in diesem Ordner"),
+
+    // Onboarding
+    ("Welcome to Deckard", "Willkommen bei Deckard"),
+    ("Deckard finds images that look the same, even when they've been resized, recompressed \
+        or converted to another format. Pick a folder and press Search.",
+        "Deckard findet Bilder, die gleich aussehen, auch wenn sie verkleinert, neu komprimiert \
+        oder in ein anderes Format umgewandelt wurden. Ordner wählen und auf Suchen drücken."),
+    ("Nothing is moved, linked or deleted without your confirmation. Turn on Dry run in the \
+        results to see what an action would do first.",
+        "Nichts wird ohne Ihre Bestätigung verschoben, verlinkt oder gelöscht. Mit Probelauf in \
+        den Ergebnissen sehen Sie vorher, was eine Aktion tun würde."),
+    ("Under Advanced, the hash algorithm trades speed against strictness. Gradient, the default, \
+        suits most photo libraries; Mean is the loosest, and DoubleGradient and Blockhash are \
+        stricter, for collections of very similar images. Compare algorithms tries them all on a \
+        sample of your own folder.",
+        "Unter Erweitert wägt der Hash-Algorithmus Geschwindigkeit gegen Strenge ab. Gradient, \
+        die Voreinstellung, passt zu den meisten Fotosammlungen; Mean ist am lockersten, \
+        DoubleGradient und Blockhash sind strenger, für Sammlungen sehr ähnlicher Bilder. \
+        Algorithmen vergleichen probiert alle an einer Stichprobe Ihres Ordners aus."),
+    ("Don't show this again", "Nicht mehr anzeigen"),
+    ("Got it", "Verstanden"),
+    ("If you expected duplicates, some options may have ruled them out:",
+        "Falls Sie Duplikate erwartet haben, könnten einige Optionen sie ausgeschlossen haben:"),
+    ("{count} files were skipped for their extension. Include more formats, or detect them by content.",
+        "{count} Dateien wurden wegen ihrer Endung übersprungen. Mehr Formate einschließen oder nach Inhalt erkennen."),
+    ("Change extensions", "Endungen ändern"),
+    ("{count} matches were split up because their colors differed. Turn off color verification, \
+        or try a looser hash algorithm.",
+        "{count} Treffer wurden wegen abweichender Farben getrennt. Die Farbprüfung ausschalten \
+        oder einen lockereren Hash-Algorithmus versuchen."),
+    ("Change matching", "Abgleich ändern"),
+    ("{count} images were skipped for their size or the memory they'd need. Raise the limits to \
+        include them.",
+        "{count} Bilder wurden wegen ihrer Größe oder ihres Speicherbedarfs übersprungen. Die \
+        Grenzen anheben, um sie einzuschließen."),
+    ("Change size limits", "Größengrenzen ändern"),
+    ("Scan details, above, has the full counts.", "Die Scan-Details oben enthalten alle Zahlen."),
+
+    // Click actions
+    ("Click:", "Klick:"),
+    ("Double-click:", "Doppelklick:"),
+    ("Middle-click:", "Mittelklick:"),
+    ("Nothing", "Nichts"),
+    ("Select", "Auswählen"),
+    ("Show in file manager", "Im Dateimanager zeigen"),
+    ("Preview in app", "In der App anzeigen"),
+    ("What clicking a thumbnail does", "Was ein Klick auf ein Vorschaubild bewirkt"),
+
+    // Hash algorithm changes
+    ("Size", "Größe"),
+    ("Purge", "Löschen"),
+    ("Forget what's indexed for this folder with this algorithm",
+        "Den Index dieses Ordners für diesen Algorithmus verwerfen"),
+    ("Purge index", "Index löschen"),
+    ("Forget what's indexed for {folder} with {algorithm}? Searching it again with \
+        that algorithm indexes it again.",
+        "Den Index von {folder} für {algorithm} verwerfen? Eine erneute Suche mit diesem \
+        Algorithmus indexiert ihn wieder."),
+    ("Different hash algorithm", "Anderer Hash-Algorithmus"),
+    ("Index with {algorithm}", "Mit {algorithm} indexieren"),
+    ("Use {algorithm} this once", "Einmalig {algorithm} verwenden"),
+    ("{folder} was indexed with {old}, and this search uses {new}. Their hashes can't be \
+        compared, so searching with {new} indexes it again from scratch; what's indexed \
+        with {old} is kept, for lookups and for switching back.",
+        "{folder} wurde mit {old} indexiert, diese Suche verwendet {new}. Ihre Hashes sind nicht \
+        vergleichbar, daher wird der Ordner mit {new} komplett neu indexiert; der Index für {old} \
+        bleibt erhalten, für Nachschlagen und zum Zurückwechseln."),
+
+    // Screen reader labels
+    ("Image", "Bild"),
+    ("{width} by {height}", "{width} mal {height}"),
+    ("duplicate {member} of {count} in group {group}", "Duplikat {member} von {count} in Gruppe {group}"),
+    ("Open {file}", "{file} öffnen"),
+    ("Show {file} in file manager", "{file} im Dateimanager zeigen"),
+    ("Copy path of {file}", "Pfad von {file} kopieren"),
+    ("Quick Look {file}", "Quick Look für {file}"),
+    ("Retry {file}", "{file} erneut versuchen"),
+    ("Ignore group {number}", "Gruppe {number} ignorieren"),
+    ("Run hook on group {number}", "Hook für Gruppe {number} ausführen"),
+
+    // Scheduled scans
+    ("Results from a scheduled scan of {folder} are ready (finished {time}, {groups} groups)",
+        "Ergebnisse einer geplanten Suche in {folder} liegen vor (fertig {time}, {groups} Gruppen)"),
+    ("View", "Ansehen"),
+
+    // Trash
+    ("Moved to the trash", "In den Papierkorb verschoben"),
+    ("Open Trash", "Papierkorb öffnen"),
+    ("{count} files ({size}) moved to the trash. Empty it to free the space.",
+        "{count} Dateien ({size}) in den Papierkorb verschoben. Leeren Sie ihn, um den Platz freizugeben."),
+
+    // Review
+    ("Unreviewed", "Nicht geprüft"),
+    ("Kept", "Behalten"),
+    ("Needs attention", "Nacharbeit nötig"),
+    ("Mark group {number} as {mark}", "Gruppe {number} markieren als {mark}"),
+    ("Note", "Notiz"),
+    ("Note on group {number}", "Notiz zu Gruppe {number}"),
+    ("Hide reviewed", "Geprüfte ausblenden"),
+    ("Only needs attention", "Nur mit Nacharbeit"),
+    ("{reviewed} of {total} groups reviewed", "{reviewed} von {total} Gruppen geprüft"),
+    ("Press M to cycle the selected image's group through the marks",
+        "M schaltet die Markierung der Gruppe des ausgewählten Bildes weiter"),
+    ("Bulk actions: only shown groups", "Sammelaktionen: nur angezeigte Gruppen"),
+    ("Trash, link and keep-first only touch the groups the filters show",
+        "Papierkorb, Verknüpfen und Erstes-behalten betreffen nur die von den Filtern angezeigten Gruppen"),
+
+    // Derivatives
+    ("Derivative Patterns:", "Ableitungsmuster:"),
+    ("Comma-separated parts of a file name that mark an intentional \
+        derivative, e.g. \"_web\" for foo_web.jpg next to foo.jpg. Groups of just an \
+        original and its derivatives are hidden.",
+        "Durch Kommas getrennte Teile eines Dateinamens, die eine gewollte Ableitung kennzeichnen, \
+        z. B. \"_web\" für foo_web.jpg neben foo.jpg. Gruppen aus nur einem Original und seinen \
+        Ableitungen werden ausgeblendet."),
+    ("Show intentional derivatives ({count})", "Gewollte Ableitungen anzeigen ({count})"),
+    ("Groups of just an original and copies named by a derivative pattern, e.g. foo.jpg and foo_web.jpg",
+        "Gruppen aus nur einem Original und nach einem Ableitungsmuster benannten Kopien, z. B. foo.jpg und foo_web.jpg"),
+    ("Derivative", "Ableitung"),
+    ("Named by a derivative pattern after another file in this group and folder",
+        "Nach einem Ableitungsmuster benannt nach einer anderen Datei dieser Gruppe und dieses Ordners"),
+
+    // Compact list
+    ("Compact", "Kompakt"),
+    ("Tiny thumbnails and one line per file, for skimming many groups",
+        "Winzige Vorschaubilder und eine Zeile pro Datei, um viele Gruppen schnell durchzusehen"),
+    ("Show a compact list", "Kompakte Liste anzeigen"),
+    ("Show a comfortable list", "Großzügige Liste anzeigen"),
+    ("Actions for group {number}", "Aktionen für Gruppe {number}"),
+    ("Actions for {file}", "Aktionen für {file}"),
+    ("Expand burst", "Serie aufklappen"),
+    ("Collapse burst", "Serie zuklappen"),
+
+    // Sidecars
+    ("Sidecar Files:", "Begleitdateien:"),
+    ("Trash sidecar files, e.g. IMG_1234.xmp or IMG_1234.jpg.xmp, \
+        along with their images",
+        "Begleitdateien wie IMG_1234.xmp oder IMG_1234.jpg.xmp zusammen \
+        mit ihren Bildern in den Papierkorb legen"),
+    ("Include sidecars", "Begleitdateien einbeziehen"),
+    ("Trash .xmp and other sidecar files along with their images",
+        ".xmp- und andere Begleitdateien zusammen mit ihren Bildern in den Papierkorb legen"),
+    ("Along with {count} sidecar files", "Zusammen mit {count} Begleitdateien"),
+    ("with {file}", "mit {file}"),
+
+    // Refining
+    ("Re-scan…", "Neu durchsuchen…"),
+    ("Re-scan this folder with different settings…", "Diesen Ordner mit anderen Einstellungen neu durchsuchen…"),
+    ("Re-scan the folder of group {number}", "Ordner von Gruppe {number} neu durchsuchen"),
+    ("Refining previous search", "Verfeinerung der vorigen Suche"),
+    ("Back to full results", "Zurück zu allen Ergebnissen"),
+    ("Results for {root}, as they were", "Ergebnisse für {root}, wie sie waren"),
+
+    // Grouping
+    ("Match Distance:", "Übereinstimmungsabstand:"),
+    ("How many bits of the hash can differ for images to still \
+        match. 0 only matches identical hashes; higher finds edited copies, and \
+        compares every pair of images, which is slow on big libraries.",
+        "Wie viele Bits des Hashs sich unterscheiden dürfen, damit Bilder noch \
+        übereinstimmen. 0 findet nur identische Hashs; höhere Werte finden bearbeitete \
+        Kopien und vergleichen jedes Bildpaar, was bei großen Bibliotheken langsam ist."),
+    ("Strict", "Streng"),
+    ("Only group images that all match each other", "Nur Bilder gruppieren, die alle miteinander übereinstimmen"),
+    ("Chain", "Kette"),
+    ("Also group images that only match through others, \
+        which can join quite different images by way of similar ones",
+        "Auch Bilder gruppieren, die nur über andere übereinstimmen, \
+        was recht verschiedene Bilder über ähnliche verbinden kann"),
+    ("(exact matches)", "(exakte Übereinstimmungen)"),
+    ("(strict groups within {distance} bits)", "(strenge Gruppen innerhalb von {distance} Bits)"),
+    ("(chained groups within {distance} bits)", "(verkettete Gruppen innerhalb von {distance} Bits)"),
+    ("Every image in a group matches every other", "Jedes Bild einer Gruppe stimmt mit jedem anderen überein"),
+    ("Images can be grouped through others they both match, \
+        even if they don't match each other",
+        "Bilder können über andere gruppiert werden, mit denen beide übereinstimmen, \
+        auch wenn sie nicht miteinander übereinstimmen"),
+
+    // External compare tool
+    ("External Compare Tool:", "Externes Vergleichsprogramm:"),
+    ("A command to open a pair of images in, e.g. to diff them. {a} \
+        and {b} become their paths. Offered on groups of two, and in the compact \
+        list's menus when another image is selected.",
+        "Ein Befehl, der ein Bildpaar öffnet, z. B. um es zu vergleichen. {a} \
+        und {b} werden zu ihren Pfaden. Angeboten bei Zweiergruppen und in den Menüs \
+        der kompakten Liste, wenn ein anderes Bild ausgewählt ist."),
+    ("Compare", "Vergleichen"),
+    ("Compare externally", "Extern vergleichen"),
+    ("Compare externally with selected", "Extern mit ausgewähltem vergleichen"),
+    ("Compare group {number} externally", "Gruppe {number} extern vergleichen"),
+    ("Open both in the external compare tool", "Beide im externen Vergleichsprogramm öffnen"),
+    ("Open this and {file} in the external compare tool", "Dieses und {file} im externen Vergleichsprogramm öffnen"),
+    ("Set an external compare tool under Advanced in the search options",
+        "Ein externes Vergleichsprogramm unter „Erweitert“ in den Suchoptionen festlegen"),
+
+    // Notifications
+    ("Notify When Done:", "Benachrichtigen, wenn fertig:"),
+    ("Show a notification when a search that took a while finishes \
+        while Deckard's in the background",
+        "Eine Benachrichtigung zeigen, wenn eine längere Suche fertig wird, \
+        während Deckard im Hintergrund ist"),
+    ("Search of {folder} finished: {count} duplicate groups, {size} reclaimable",
+        "Suche in {folder} abgeschlossen: {count} Duplikatgruppen, {size} freizugeben"),
+
+    // Thumbnail memory
+    ("Thumbnail Memory:", "Vorschaubild-Speicher:"),
+    ("The most graphics memory the results' thumbnails can take. \
+        Lower it if scrolling through many results slows everything else down.",
+        "Höchstens so viel Grafikspeicher dürfen die Vorschaubilder der Ergebnisse belegen. \
+        Verringern, wenn das Scrollen durch viele Ergebnisse alles andere verlangsamt."),
+    ("Show usage", "Nutzung zeigen"),
+    ("Show how much is in use above the results", "Über den Ergebnissen zeigen, wie viel belegt ist"),
+    ("thumb cache: {used} / {budget}", "Vorschau-Cache: {used} / {budget}"),
+
+    // Hashes
+    ("Details", "Details"),
+    ("The perceptual hash it was grouped by", "Der Wahrnehmungs-Hash, nach dem gruppiert wurde"),
+    ("Copy hash", "Hash kopieren"),
+    ("Copy group debug info", "Debug-Infos der Gruppe kopieren"),
+    ("Paths, sizes, dimensions and hashes, for a bug report", "Pfade, Größen, Abmessungen und Hashs, für einen Fehlerbericht"),
+    ("More actions for this group", "Weitere Aktionen für diese Gruppe"),
+
+    // Resolution variants
+    ("Resolution Variants:", "Auflösungsvarianten:"),
+    ("Groups of the same picture where one copy is at least this many \
+        times wider and taller than the rest are marked as resolution variants, and can \
+        be resolved by keeping the largest",
+        "Gruppen desselben Bildes, in denen eine Kopie mindestens so viele Male breiter \
+        und höher ist als die übrigen, werden als Auflösungsvarianten markiert und können \
+        aufgelöst werden, indem die größte behalten wird"),
+    ("Only resolution variants ({count})", "Nur Auflösungsvarianten ({count})"),
+    ("Groups of the same picture at different sizes, e.g. an original and a copy downscaled for email",
+        "Gruppen desselben Bildes in verschiedenen Größen, z. B. ein Original und eine für E-Mails verkleinerte Kopie"),
+    ("Resolution variants", "Auflösungsvarianten"),
+    ("The same picture at different sizes; the largest can be kept", "Dasselbe Bild in verschiedenen Größen; das größte kann behalten werden"),
+    ("Resolve resolution variants (keep largest)", "Auflösungsvarianten auflösen (größte behalten)"),
+    ("Move every image but the largest in each group of resolution variants to the trash",
+        "Alle Bilder außer dem größten jeder Gruppe von Auflösungsvarianten in den Papierkorb legen"),
+
+    // Repaints
+    ("{hashed} of {found} images hashed", "{hashed} von {found} Bildern gehasht"),
+    ("Show frame rate", "Bildrate anzeigen"),
+    ("For debugging; shows how often the window repaints, and why",
+        "Zur Fehlersuche; zeigt, wie oft das Fenster neu gezeichnet wird, und warum"),
+    ("{fps} fps", "{fps} fps"),
+    ("repaint: {causes}", "Neuzeichnen: {causes}"),
+    ("input", "Eingabe"),
+
+    // Partial results
+    ("Review partial results", "Teilergebnisse prüfen"),
+    ("Start reviewing the duplicates found so far while the search finishes", "Die bisher gefundenen Duplikate prüfen, während die Suche weiterläuft"),
+    ("Scan still running — {percent}%", "Suche läuft noch — {percent} %"),
+    ("Stop the scan, keeping what it's found so far", "Suche beenden und das bisher Gefundene behalten"),
+    ("Bulk actions wait for the scan to finish, since near matches can still regroup", "Sammelaktionen warten auf das Ende der Suche, da sich ähnliche Treffer noch neu gruppieren können"),
+    ("{count} reviewed groups gained files after you marked them, and need attention again", "{count} geprüfte Gruppen haben nach der Markierung Dateien hinzubekommen und müssen erneut geprüft werden"),
+    ("Show them", "Anzeigen"),
+    ("Available once the scan is done", "Verfügbar, sobald die Suche fertig ist"),
+    ("Found since the results were first shown; click to clear", "Gefunden, nachdem die Ergebnisse angezeigt wurden; zum Entfernen klicken"),
+    ("No duplicates found yet", "Noch keine Duplikate gefunden"),
+    ("The search crashed: {msg}. These results are only what it found before then.", "Die Suche ist abgestürzt: {msg}. Diese Ergebnisse enthalten nur, was sie bis dahin gefunden hat."),
+
+    // Portable sessions
+    ("Portable Sessions:", "Portable Sitzungen:"),
+    ("Save sessions in the root, and plan exports, with paths relative to where they're saved, so they still open when the library's mounted somewhere else", "Sitzungen im Stammordner und Plan-Exporte mit Pfaden relativ zum Speicherort sichern, damit sie sich auch öffnen lassen, wenn die Bibliothek woanders eingehängt ist"),
+    ("Save these results in the root, with relative paths, to open wherever it's mounted", "Diese Ergebnisse mit relativen Pfaden im Stammordner sichern, um sie überall öffnen zu können, wo er eingehängt ist"),
+    ("Open session…", "Sitzung öffnen…"),
+
+    // Stopping early
+    ("Stop and show what you've found", "Anhalten und Gefundenes zeigen"),
+    ("Finish the images in progress, then show the duplicates among those searched so far. The rest can be resumed later.", "Die gerade bearbeiteten Bilder abschließen und dann die Duplikate unter den bisher durchsuchten zeigen. Der Rest kann später fortgesetzt werden."),
+    ("Stopping after {hashed} files hashed; counting what's left…", "Wird nach {hashed} gehashten Dateien angehalten; der Rest wird gezählt…"),
+    ("partial scan — stopped early at {percent}%", "Teilsuche — vorzeitig bei {percent} % angehalten"),
+    ("{hashed} of the {total} images found were searched before the scan was stopped; duplicates of the rest weren't looked for. The search form offers to resume it.", "{hashed} der {total} gefundenen Bilder wurden durchsucht, bevor die Suche angehalten wurde; nach Duplikaten der übrigen wurde nicht gesucht. Das Suchformular bietet an, sie fortzusetzen."),
+    ("Open saved results from a file, e.g. a portable session saved in a library", "Gespeicherte Ergebnisse aus einer Datei öffnen, z. B. eine in einer Bibliothek gespeicherte portable Sitzung"),
+
+    // Folder browser
+    ("Folders", "Ordner"),
+    ("Show the folders with duplicates in a tree, to pick one to show", "Die Ordner mit Duplikaten als Baum zeigen, um einen zur Anzeige auszuwählen"),
+    ("Show folder tree", "Ordnerbaum zeigen"),
+    ("Hide folder tree", "Ordnerbaum ausblenden"),
+    ("No folders with duplicates", "Keine Ordner mit Duplikaten"),
+    ("{folder} — {groups} groups, {size}", "{folder} — {groups} Gruppen, {size}"),
+
+    // Checking files before opening
+    ("Check Before Opening:", "Vor dem Öffnen prüfen:"),
+    ("Warn before opening a file whose contents don't match its extension, e.g. a .jpg that's really a program or a web page", "Vor dem Öffnen einer Datei warnen, deren Inhalt nicht zu ihrer Endung passt, z. B. eine .jpg, die eigentlich ein Programm oder eine Webseite ist"),
+    ("Contents don't match extension:", "Inhalt passt nicht zur Endung:"),
+    ("Files whose contents don't match their extension", "Dateien, deren Inhalt nicht zu ihrer Endung passt"),
+    ("{format} image", "{format}-Bild"),
+    ("Windows program", "Windows-Programm"),
+    ("macOS program", "macOS-Programm"),
+    ("Linux program", "Linux-Programm"),
+    ("script", "Skript"),
+    ("web page", "Webseite"),
+    ("SVG image", "SVG-Bild"),
+
+    // Photo libraries
+    ("{name} library detected — scanning everything in it", "{name}-Mediathek erkannt — alles darin wird durchsucht"),
+    ("{name} library detected — scanning originals only, {count} other folders skipped", "{name}-Mediathek erkannt — nur Originale werden durchsucht, {count} andere Ordner übersprungen"),
+    ("{name} library detected — {count} derivative folders skipped", "{name}-Mediathek erkannt — {count} Ordner mit abgeleiteten Dateien übersprungen"),
+    ("Scan All of Photo Libraries:", "Fotomediatheken ganz durchsuchen:"),
+    ("Apple Photos, Lightroom and digiKam libraries keep their own previews and thumbnails of every picture, which would match the originals. Normally only the originals are searched; this searches everything in them.", "Apple-Fotos-, Lightroom- und digiKam-Mediatheken enthalten eigene Vorschauen und Miniaturen jedes Bildes, die zu den Originalen passen würden. Normalerweise werden nur die Originale durchsucht; hiermit wird alles darin durchsucht."),
+
+    // Group IDs
+    ("This group's ID, as in exports. It's the same in every search, as long as the group has the same files.", "Die ID dieser Gruppe, wie in Exporten. Sie bleibt bei jeder Suche gleich, solange die Gruppe dieselben Dateien enthält."),
+
+    // Keeper preview
+    ("No preview", "Keine Vorschau"),
+    ("Recommended", "Empfohlen"),
+    ("Largest file", "Größte Datei"),
+    ("Highest resolution", "Höchste Auflösung"),
+    ("Oldest", "Älteste"),
+    ("Newest", "Neueste"),
+    ("Shortest path", "Kürzester Pfad"),
+    ("Keep:", "Behalten:"),
+    ("Apply", "Anwenden"),
+    ("Apply keeper choices", "Auswahl der zu behaltenden Bilder anwenden"),
+    ("Move every image but the one marked Keep in each group to the trash", "Alle Bilder außer dem mit „Behalten“ markierten jeder Gruppe in den Papierkorb verschieben"),
+    ("Which image in each group to mark to keep. Only a preview until applied; click Keep this on another image to pin it instead.", "Welches Bild jeder Gruppe zum Behalten markiert wird. Bis zum Anwenden nur eine Vorschau; mit „Dieses behalten“ bei einem anderen Bild wird stattdessen dieses festgelegt."),
+    ("✔ Keep", "✔ Behalten"),
+    ("📌 Keep", "📌 Behalten"),
+    ("Keep this", "Dieses behalten"),
+    ("Keep {file}", "{file} behalten"),
+    ("Pin this as the group's keeper instead", "Stattdessen dieses als zu behaltendes Bild der Gruppe festlegen"),
+    ("Pinned as this group's keeper. Click to go back to the keeper strategy's choice.", "Als zu behaltendes Bild dieser Gruppe festgelegt. Klicken, um zur Auswahl der Strategie zurückzukehren."),
+    ("The keeper strategy's choice. Nothing happens to any file until the choices are applied.", "Die Auswahl der Strategie. Mit den Dateien passiert nichts, bis die Auswahl angewendet wird."),
+    ("would reclaim {size} with current choices", "mit der aktuellen Auswahl würden {size} frei"),
+
+    // Large groups
+    ("Collapse Groups Over:", "Gruppen einklappen ab:"),
+    (" images", " Bildern"),
+    ("Show bigger groups as a summary, with their members a page at a time on request. 0 shows every group in full.", "Größere Gruppen als Zusammenfassung zeigen, ihre Mitglieder auf Wunsch seitenweise. 0 zeigt jede Gruppe vollständig."),
+    ("{count} matching images in {folder} — {size}", "{count} übereinstimmende Bilder in {folder} — {size}"),
+    ("{count} matching images — {size}", "{count} übereinstimmende Bilder — {size}"),
+    ("Show {count} more", "{count} weitere zeigen"),
+    ("{shown} of {total} shown", "{shown} von {total} angezeigt"),
+    ("Keep first, trash the other {count}", "Erstes behalten, die anderen {count} in den Papierkorb"),
+    ("Keep {file}, the first shown, and move the rest of the group to the trash", "{file}, das zuerst gezeigte, behalten und den Rest der Gruppe in den Papierkorb verschieben"),
+
+    // Reduced decoding
+    ("Accurate Decode:", "Genaues Dekodieren:"),
+    ("Decode JPEGs at full size before hashing. Much slower; only worth it if scaled-down decoding seems to miss or mismatch images.", "JPEGs vor dem Hashen in voller Größe dekodieren. Viel langsamer; nur sinnvoll, wenn beim verkleinerten Dekodieren Bilder fehlen oder falsch zugeordnet werden."),
+
+    // History
+    ("History", "Verlauf"),
+    ("Show what's been done to these results, including in earlier sittings", "Zeigen, was mit diesen Ergebnissen gemacht wurde, auch in früheren Sitzungen"),
+    ("Show history", "Verlauf anzeigen"),
+    ("Hide history", "Verlauf ausblenden"),
+    ("Export history…", "Verlauf exportieren…"),
+    ("Save every action as CSV, for a record of what was changed on disk", "Jede Aktion als CSV speichern, als Nachweis, was auf der Festplatte geändert wurde"),
+    ("Nothing's been done to these results yet", "Mit diesen Ergebnissen wurde noch nichts gemacht"),
+    ("Time", "Zeit"),
+    ("Groups", "Gruppen"),
+    ("Files", "Dateien"),
+    ("Outcome", "Ergebnis"),
+    (" and {count} more", " und {count} weitere"),
+    ("{count} errors", "{count} Fehler"),
+    ("Succeeded", "Erfolgreich"),
+    ("Partly failed", "Teilweise fehlgeschlagen"),
+    ("Failed", "Fehlgeschlagen"),
+    ("Only errors from \"{action}\"", "Nur Fehler von „{action}“"),
+
+    // Walk ahead
+    ("Walk Ahead:", "Vorauslesen:"),
+    (" files", " Dateien"),
+    ("The most files the search lists before hashing catches up. Higher can keep slow disks busier; lower uses less memory on huge folders.",
+     "Wie viele Dateien die Suche höchstens auflistet, bevor das Hashen aufholt. Höher kann langsame Laufwerke besser auslasten; niedriger braucht bei riesigen Ordnern weniger Speicher."),
+
+    // System images
+    ("Hide System Images:", "Systembilder ausblenden:"),
+    ("Hide groups of app icons, stock wallpapers and the like, from a built-in list and the groups you've marked, behind a toggle in the results",
+     "Gruppen von App-Symbolen, Standard-Hintergrundbildern und Ähnlichem aus einer eingebauten Liste und den von dir markierten Gruppen hinter einem Schalter in den Ergebnissen verbergen"),
+    ("{count} system-image groups hidden", "{count} Gruppen mit Systembildern ausgeblendet"),
+    ("{count} system-image groups shown", "{count} Gruppen mit Systembildern eingeblendet"),
+    ("Hide", "Ausblenden"),
+    ("App icons, stock wallpapers and the like, from the built-in list or marked by you",
+     "App-Symbole, Standard-Hintergrundbilder und Ähnliches, aus der eingebauten Liste oder von dir markiert"),
+    ("Mark as system images everywhere", "Überall als Systembilder markieren"),
+    ("Hide groups of this image in every search, like app icons and stock wallpapers",
+     "Gruppen dieses Bildes in jeder Suche verbergen, wie App-Symbole und Standard-Hintergrundbilder"),
+    ("Not system images", "Keine Systembilder"),
+    ("On the built-in list; turn off Hide System Images in the search options instead",
+     "Steht auf der eingebauten Liste; schalte stattdessen „Systembilder ausblenden“ in den Suchoptionen aus"),
+    ("Show system images", "Systembilder einblenden"),
+    ("Hide system images", "Systembilder ausblenden"),
+
+    // Renaming
+    ("Rename…", "Umbenennen…"),
+    ("Rename the image marked Keep in each group, or the selected one, by a template",
+     "Das in jeder Gruppe zum Behalten markierte Bild oder das ausgewählte nach einer Vorlage umbenennen"),
+    ("Rename images…", "Bilder umbenennen…"),
+    ("Rename images", "Bilder umbenennen"),
+    ("The image to keep in each group", "Das zu behaltende Bild jeder Gruppe"),
+    ("The selected image", "Das ausgewählte Bild"),
+    ("Click an image to select it", "Klicke auf ein Bild, um es auszuwählen"),
+    ("New name:", "Neuer Name:"),
+    ("Placeholders: {list}. Dates are YYYY-MM-DD; each file keeps its extension.",
+     "Platzhalter: {list}. Datumsangaben als JJJJ-MM-TT; jede Datei behält ihre Endung."),
+    ("{count} files to rename, {problems} with problems", "{count} Dateien umzubenennen, {problems} mit Problemen"),
+    ("Rename", "Umbenennen"),
+    ("Every file needs a new name of its own that isn't taken already",
+     "Jede Datei braucht einen eigenen neuen Namen, der noch nicht vergeben ist"),
+    ("New name", "Neuer Name"),
+
+    // Central region
+    ("Hash Central Region:", "Nur Bildmitte vergleichen:"),
+    ("Only compare the middle of each image, so copies with a watermark or frame added at the edges still match. 100% compares all of it.",
+     "Nur die Mitte jedes Bildes vergleichen, damit Kopien mit Wasserzeichen oder Rahmen am Rand trotzdem übereinstimmen. 100 % vergleicht das ganze Bild."),
+    ("Images that differ only at their edges will match too, so expect more false positives among photos with busy edges",
+     "Bilder, die sich nur am Rand unterscheiden, stimmen dann ebenfalls überein; bei Fotos mit unruhigen Rändern ist mit mehr Fehltreffern zu rechnen"),
+    // Settings
+    ("Settings can't be saved, so changes won't be kept after quitting. {error}",
+     "Einstellungen können nicht gespeichert werden, Änderungen gehen beim Beenden verloren. {error}"),
+    ("Settings were saved by a newer version of Deckard; changes won't be kept, so as not to overwrite them.",
+     "Die Einstellungen stammen von einer neueren Deckard-Version; Änderungen werden nicht gespeichert, um sie nicht zu überschreiben."),
+    ("Some stored settings couldn't be read and were reset to their defaults: {list}.",
+     "Einige gespeicherte Einstellungen waren nicht lesbar und wurden zurückgesetzt: {list}."),
+    ("Folder to search", "Zu durchsuchender Ordner"),
+    ("Results layout", "Ergebnislayout"),
+    ("Results density", "Ergebnisdichte"),
+    ("I/O threads", "E/A-Threads"),
+    ("Walk ahead", "Vorauslesen"),
+    ("Notify when done", "Benachrichtigung bei Abschluss"),
+    ("Portable reports", "Portable Berichte"),
+    ("Folder panel", "Ordnerleiste"),
+    ("Folder panel depth", "Tiefe der Ordnerleiste"),
+    ("History panel", "Verlaufsleiste"),
+    ("Advanced options", "Erweiterte Optionen"),
+    ("Check before opening", "Vor dem Öffnen prüfen"),
+    ("Large group size", "Größe großer Gruppen"),
+    ("Thumbnail memory", "Speicher für Vorschaubilder"),
+    ("Show thumbnail memory use", "Speicherverbrauch der Vorschaubilder anzeigen"),
+    ("Presets", "Voreinstellungen"),
+    ("Last preset", "Letzte Voreinstellung"),
+    ("Welcome screen", "Willkommensbildschirm"),
+    ("Zoom", "Zoom"),
+    ("Language", "Sprache"),
+    ("Size units", "Größeneinheiten"),
+    ("Click actions", "Klickaktionen"),
+    ("Window size and position", "Fenstergröße und -position"),
+    // Excluded files
+    ("Not a duplicate", "Kein Duplikat"),
+    ("Take this file out of its group, in this and future searches",
+     "Diese Datei aus ihrer Gruppe nehmen, in dieser und künftigen Suchen"),
+    ("{file} is not a duplicate", "{file} ist kein Duplikat"),
+    ("{count} files excluded from their groups", "{count} Dateien aus ihren Gruppen ausgenommen"),
+    ("Manage excluded files", "Ausgenommene Dateien verwalten"),
+    ("Excluded files", "Ausgenommene Dateien"),
+    ("No excluded files", "Keine ausgenommenen Dateien"),
+    ("Un-exclude", "Wieder aufnehmen"),
+    ("Excluded from the group with:", "Ausgenommen aus der Gruppe mit:"),
+    // Search events
+    ("Grouping similar images…", "Ähnliche Bilder werden gruppiert…"),
+    ("Looking for crops…", "Zuschnitte werden gesucht…"),
+    ("{count} errors so far", "Bisher {count} Fehler"),
+    // Theme
+    ("System", "System"),
+    ("Light", "Hell"),
+    ("Dark", "Dunkel"),
+    ("Theme:", "Design:"),
+    ("Theme", "Design"),
+    ("System follows the operating system's light or dark mode", "System folgt dem hellen oder dunklen Modus des Betriebssystems"),
+    // Broken images
+    ("Empty", "Leer"),
+    ("Truncated", "Abgeschnitten"),
+    ("Solid color", "Einfarbig"),
+    ("Broken images ({count})", "Defekte Bilder ({count})"),
+    ("Broken images:", "Defekte Bilder:"),
+    ("Empty, cut short, or a single flat color; mostly left by interrupted copies",
+        "Leer, abgeschnitten oder einfarbig; meist Reste abgebrochener Kopiervorgänge"),
+    ("Trash all broken images", "Alle defekten Bilder in den Papierkorb"),
+    // Exact copies
+    ("Decodes saved, exact copies:", "Eingesparte Dekodierungen, exakte Kopien:"),
+    // Group sizes
+    ("{count} images, {total}, {wasted} reclaimable", "{count} Bilder, {total}, {wasted} freizugeben"),
+    ("{count} images, size unknown", "{count} Bilder, Größe unbekannt"),
+    ("size unknown", "Größe unbekannt"),
+    ("+{size} if removed", "+{size} beim Entfernen"),
+    ("({count} groups of unknown size not counted)", "({count} Gruppen unbekannter Größe nicht mitgezählt)"),
+    ("Files changed since they were found, so their sizes aren't known", "Dateien haben sich seit dem Fund geändert, ihre Größe ist daher unbekannt"),
+    // Launch
+    ("{path} isn't a folder, so it can't be searched.", "{path} ist kein Ordner und kann daher nicht durchsucht werden."),
+    ("{path} can't be opened: {error}", "{path} kann nicht geöffnet werden: {error}"),
+    // Tabs
+    ("Another tab is still searching", "Ein anderer Tab sucht noch"),
+    ("Another tab is still searching. Wait for it to finish, or cancel it, first.", "Ein anderer Tab sucht noch. Warten Sie, bis die Suche fertig ist, oder brechen Sie sie zuerst ab."),
+    // Hooks
+    ("Run post-search hook", "Befehl nach der Suche ausführen"),
+    ("Deckard will run this command on {count} groups:\n\n{command}\n\nIt can do anything you can. Only continue if you trust it.", "Deckard führt diesen Befehl für {count} Gruppen aus:\n\n{command}\n\nEr kann alles, was Sie können. Fahren Sie nur fort, wenn Sie ihm vertrauen."),
+    // Live results
+    ("Scan still running", "Suche läuft noch"),
+    ("Near matches can still regroup until the scan is done, so files can't be trashed, linked, moved or renamed until then.", "Ähnliche Bilder können bis zum Ende der Suche noch anders gruppiert werden. Bis dahin können Dateien daher nicht in den Papierkorb gelegt, verknüpft, verschoben oder umbenannt werden."),
+    // Moving
+    ("Add a number", "Nummer anhängen"),
+    ("Skip it", "Überspringen"),
+    ("Replace it", "Ersetzen"),
+    ("Move images", "Bilder verschieben"),
+    ("Moves every image but the one marked Keep in each group into a folder, to look through before deleting them.", "Verschiebt alle Bilder außer dem zum Behalten markierten jeder Gruppe in einen Ordner, um sie vor dem Löschen durchzusehen."),
+    ("Folder:", "Ordner:"),
+    ("None chosen", "Keiner ausgewählt"),
+    ("Keep the folders they're in under {root}", "Ordnerstruktur unter {root} beibehalten"),
+    ("Otherwise they're all put straight into the folder", "Sonst werden alle direkt in den Ordner gelegt"),
+    ("When the folder already has a file by the same name:", "Wenn der Ordner bereits eine Datei mit demselben Namen enthält:"),
+    ("{count} files to move, {replaced} replacing files there, {skipped} skipped", "{count} Dateien zu verschieben, davon {replaced} als Ersatz vorhandener Dateien, {skipped} übersprungen"),
+    ("Move", "Verschieben"),
+    ("Choose a folder to move them to", "Wählen Sie einen Zielordner"),
+    ("With Dry run on, Move shows where each file would go instead.", "Mit aktiviertem Probelauf zeigt Verschieben stattdessen, wohin jede Datei käme."),
+    ("Move {count} images into {dest}?", "{count} Bilder nach {dest} verschieben?"),
+    ("{count} whose names are taken there will be left where they are.", "{count}, deren Namen dort vergeben sind, bleiben, wo sie sind."),
+    ("{count} files already there will be replaced, and can't be brought back.", "{count} dort vorhandene Dateien werden ersetzt und können nicht wiederhergestellt werden."),
+    ("Replace", "Ersetzen"),
+    ("Replace files", "Dateien ersetzen"),
+    ("Move…", "Verschieben…"),
+    ("Move every image but the one marked Keep in each group into a folder", "Alle Bilder außer dem zum Behalten markierten jeder Gruppe in einen Ordner verschieben"),
+    ("Move images to a folder…", "Bilder in einen Ordner verschieben…"),
+    // Opening
+    ("Contents don't match extension", "Inhalt passt nicht zur Endung"),
+    ("{path} looks like a {detected}, not what its extension says. Opening it may run it or show it in a browser. Open it anyway?", "{path} scheint ein(e) {detected} zu sein, nicht das, was die Endung angibt. Beim Öffnen wird die Datei möglicherweise ausgeführt oder in einem Browser angezeigt. Trotzdem öffnen?"),
+    // Free space
+    ("{size} will go to the trash. The space isn't freed until the trash is emptied.", "{size} werden in den Papierkorb verschoben. Der Speicherplatz wird erst frei, wenn der Papierkorb geleert wird."),
+    ("Proceed anyway", "Trotzdem fortfahren"),
+    ("Not enough space", "Nicht genug Speicherplatz"),
+    ("{what} needs about {required}, but only {available} is free on the drive holding {folder}.", "{what} benötigt etwa {required}, aber auf dem Laufwerk mit {folder} sind nur {available} frei."),
+    ("The report", "Der Bericht"),
+    // Changed files
+    ("File changed", "Datei geändert"),
+    ("{file} changed since the scan. Act on it anyway?", "{file} wurde seit dem Scan geändert. Trotzdem fortfahren?"),
+    // Opening sessions
+    ("Folder not found", "Ordner nicht gefunden"),
+    ("{name} isn't where it was relative to the session. Locate it?", "{name} liegt nicht mehr dort, wo es relativ zur Sitzung war. Suchen?"),
+    ("Locate {name}", "{name} suchen"),
+    // System folders
+    ("{count} files in system folders will be left alone.", "{count} Dateien in Systemordnern werden nicht angetastet."),
+    // Finder tags
+    ("Tag {count} duplicates with the {color} Finder tag?", "{count} Duplikate mit dem Finder-Tag „{color}“ markieren?"),
+    ("Tag duplicates", "Duplikate markieren"),
+    // Resolution variants
+    ("No resolution variants can be trashed.", "Es können keine Auflösungsvarianten in den Papierkorb verschoben werden."),
+    ("{count} groups have images of unknown dimensions.", "{count} Gruppen enthalten Bilder mit unbekannten Abmessungen."),
+    ("{count} groups have a smaller copy in a protected folder.", "{count} Gruppen haben eine kleinere Kopie in einem geschützten Ordner."),
+    ("Keep largest resolution variants", "Größte Auflösungsvarianten behalten"),
+    // Hover texts and the trash undo dialog
+    ("Stop after visiting this many files and folders, in case symlinks lead somewhere enormous. Leave empty for no limit.", "Nach so vielen besuchten Dateien und Ordnern aufhören, falls symbolische Links ins Uferlose führen. Leer lassen für keine Begrenzung."),
+    ("Skip anything excluded by .gitignore or .ignore files", "Alles überspringen, was .gitignore- oder .ignore-Dateien ausschließen"),
+    ("Split up matches whose colors differ, e.g. the same scene photographed at dusk and at noon. Costs a little time per image.", "Treffer mit unterschiedlichen Farben trennen, z. B. dieselbe Szene in der Dämmerung und am Mittag fotografiert. Kostet etwas Zeit pro Bild."),
+    ("Also check the first few KB of files with other extensions (or none) for the formats above. Slower on folders with many non-image files.", "Auch die ersten KB von Dateien mit anderen (oder ohne) Endungen auf die obigen Formate prüfen. Langsamer in Ordnern mit vielen Nicht-Bilddateien."),
+    ("Also Ctrl/Cmd +, -, and 0", "Auch Strg/Cmd +, - und 0"),
+    ("Can't undo moving files to the trash on this platform; they'll have to be put back by hand. Open the trash folder?", "Das Verschieben in den Papierkorb lässt sich auf dieser Plattform nicht rückgängig machen; die Dateien müssen von Hand zurückgelegt werden. Papierkorb öffnen?"),
+    // Stale root notice
+    ("The last searched folder, {folder}, is no longer available; using your home folder instead.", "Der zuletzt durchsuchte Ordner, {folder}, ist nicht mehr verfügbar; stattdessen wird Ihr Benutzerordner verwendet."),
+];
+
+lazy_static! {
+    static ref CATALOG: HashMap<(Language, &'static str), &'static str> = {
+        let mut catalog = HashMap::new();
+        for (en, de) in GERMAN {
+            catalog.insert((Language::German, *en), *de);
+        }
+        catalog
+    };
+}
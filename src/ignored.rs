@@ -0,0 +1,76 @@
+use crate::misc::{Image, VersionedFile};
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+
+const IGNORED_FILE: VersionedFile = VersionedFile{file: "ignored.json", name: "ignore list", version: 1};
+
+// Groups the user has said aren't really duplicates (e.g. a crop next to its
+// original), so they can be hidden from future results.
+#[derive(Serialize, Deserialize)]
+pub struct IgnoreList {
+    pub version: u32,
+    #[serde(default)]
+    pub groups: Vec<IgnoredGroup>,
+}
+
+// Canonical paths of every member when it was ignored.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct IgnoredGroup {
+    pub paths: BTreeSet<PathBuf>,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl IgnoredGroup {
+    pub fn new(group: &[Image]) -> IgnoredGroup {
+        IgnoredGroup{paths: group.iter().map(|x| canonical(&x.path)).collect()}
+    }
+
+    // A group still matches if some members have since been deleted, but not
+    // if a new copy has turned up, since that's worth a look.
+    fn matches(&self, paths: &[PathBuf]) -> bool {
+        paths.len() >= 2 && paths.iter().all(|x| self.paths.contains(x))
+    }
+}
+
+impl Default for IgnoreList {
+    fn default() -> Self {
+        IgnoreList{version: IGNORED_FILE.version, groups: vec![]}
+    }
+}
+
+impl IgnoreList {
+    pub fn load() -> Result<IgnoreList, String> {
+        IGNORED_FILE.load()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        IGNORED_FILE.save(self)
+    }
+
+    // Adds the groups not already on the list, e.g. from a portable session.
+    // Returns how many that was.
+    pub fn add(&mut self, groups: Vec<IgnoredGroup>) -> usize {
+        let before = self.groups.len();
+        for group in groups {
+            if !self.groups.contains(&group) {
+                self.groups.push(group);
+            }
+        }
+        self.groups.len() - before
+    }
+
+    pub fn matches(&self, group: &[Image]) -> bool {
+        if self.groups.is_empty() {
+            return false; // Skip canonicalizing
+        }
+        let paths: Vec<_> = group.iter().map(|x| canonical(&x.path)).collect();
+        self.groups.iter().any(|x| x.matches(&paths))
+    }
+}
@@ -0,0 +1,164 @@
+use crate::archive::Namespace;
+use crate::misc::{Image, VersionedFile};
+use crate::searcher::HASH_SIZE;
+
+use image_hasher::HashAlg;
+
+use serde::{Serialize, Deserialize};
+
+
+// Images installers scatter copies of: app icons, stock wallpapers, logos.
+// Scanning a whole home folder turns up hundreds of groups of them, which are
+// duplicates, but not ones anybody will act on. Groups whose first member
+// matches a signature here are hidden from the results by default. The
+// signatures are a built-in list plus the user's own, and only ever compared
+// within the namespace they were hashed in; signatures from other algorithms
+// or hash sizes are skipped.
+
+const JUNK_FILE: VersionedFile = VersionedFile{file: "junk.json", name: "system image list", version: 1};
+
+// Stock images, hashed as a search with the default algorithm hashes them,
+// and their sizes in bytes. Generated by searching a folder of copies of them
+// and reading the hashes back out of the index, as a user's signatures are.
+const BUILTIN_NAMESPACE: Namespace = Namespace{hash: HashAlg::Gradient, hash_size: HASH_SIZE};
+const BUILTIN: &[(&str, usize)] = &[
+    ("DjNpZS0ZAwY", 1678), // Debian logo
+    ("DP66vr6+usI", 15098), // Adwaita folder, 512×512
+    ("DP66sujwus4", 20781), // Adwaita pictures folder, 512×512
+    ("DP66srKyvtI", 16736), // Adwaita desktop folder, 512×512
+    ("DP66tqqyss4", 18948), // Adwaita home folder, 512×512
+    ("AFFFRUVtJZE", 72911), // Adwaita generic image, 512×512
+    ("iws7KysrKys", 7164), // Adwaita generic text file, 512×512
+    ("sjKSm6OyqrA", 347), // Vim, 32×32
+];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct JunkSignature {
+    pub hash: HashAlg,
+    pub hash_size: u32,
+    pub value: String, // Base64 hash
+    pub file_size: usize, // A member must be exactly this big
+}
+
+impl JunkSignature {
+    // Of the group's first member, as hashed in the namespace. None if it
+    // wasn't hashed.
+    pub fn new(group: &[Image], namespace: Namespace) -> Option<JunkSignature> {
+        let first = group.first()?;
+        Some(JunkSignature {
+            hash: namespace.hash,
+            hash_size: namespace.hash_size,
+            value: first.hash.as_ref()?.to_base64(),
+            file_size: first.file_size,
+        })
+    }
+
+    fn namespace(&self) -> Namespace {
+        Namespace{hash: self.hash, hash_size: self.hash_size}
+    }
+
+    fn matches(&self, group: &[Image], hash: &str) -> bool {
+        self.value == hash && group.iter().any(|x| x.file_size == self.file_size)
+    }
+}
+
+// The user's own signatures, from marking groups as system images.
+#[derive(Serialize, Deserialize)]
+pub struct JunkList {
+    pub version: u32,
+    #[serde(default)]
+    pub signatures: Vec<JunkSignature>,
+}
+
+impl Default for JunkList {
+    fn default() -> Self {
+        JunkList{version: JUNK_FILE.version, signatures: vec![]}
+    }
+}
+
+// Which list a group matched.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JunkSource {
+    Builtin,
+    User,
+}
+
+impl JunkList {
+    pub fn load() -> Result<JunkList, String> {
+        JUNK_FILE.load()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        JUNK_FILE.save(self)
+    }
+
+    // Checks the group, hashed in the namespace, against the built-in list and
+    // then this one.
+    pub fn matches(&self, group: &[Image], namespace: Namespace) -> Option<JunkSource> {
+        let hash = group.first()?.hash.as_ref()?.to_base64();
+        if namespace == BUILTIN_NAMESPACE
+            && BUILTIN.iter().any(|(value, size)| *value == hash && group.iter().any(|x| x.file_size == *size)) {
+            return Some(JunkSource::Builtin);
+        }
+        self.signatures.iter()
+            .filter(|x| x.namespace() == namespace)
+            .any(|x| x.matches(group, &hash))
+            .then_some(JunkSource::User)
+    }
+
+    // Drops the user's signatures matching the group. Returns whether there
+    // were any.
+    pub fn remove(&mut self, group: &[Image], namespace: Namespace) -> bool {
+        let Some(hash) = group.first().and_then(|x| x.hash.as_ref()).map(|x| x.to_base64()) else {
+            return false;
+        };
+        let before = self.signatures.len();
+        self.signatures.retain(|x| x.namespace() != namespace || !x.matches(group, &hash));
+        self.signatures.len() != before
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use image_hasher::ImageHash;
+
+    fn group(hash: &str, sizes: &[usize]) -> Vec<Image> {
+        let hash = ImageHash::from_base64(hash).unwrap();
+        sizes.iter()
+            .enumerate()
+            .map(|(idx, size)| {
+                let mut image = Image::new_lazy(PathBuf::from(format!("/{idx}.png")), *size, None);
+                image.hash = Some(hash.clone());
+                image
+            })
+            .collect()
+    }
+
+    #[test]
+    fn junk_matches_by_hash_size_and_namespace() {
+        let gradient = Namespace::new(HashAlg::Gradient);
+        let mean = Namespace::new(HashAlg::Mean);
+
+        let logo = group("DjNpZS0ZAwY", &[2000, 1678]);
+        let list = JunkList::default();
+        assert_eq!(list.matches(&logo, gradient), Some(JunkSource::Builtin));
+        assert_eq!(list.matches(&logo, mean), None);
+        assert_eq!(list.matches(&group("DjNpZS0ZAwY", &[2000, 2001]), gradient), None);
+
+        let mut list = JunkList::default();
+        let photo = group("AAAAAAAAAAA", &[5000, 5000]);
+        assert_eq!(list.matches(&photo, mean), None);
+        list.signatures.push(JunkSignature::new(&photo, mean).unwrap());
+        assert_eq!(list.matches(&photo, mean), Some(JunkSource::User));
+        assert_eq!(list.matches(&photo, gradient), None);
+
+        assert!(!list.remove(&photo, gradient));
+        assert!(list.remove(&photo, mean));
+        assert!(list.signatures.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::misc::Image;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+
+// Rules for picking which member of each group to keep, previewed on the
+// results before anything's done about them. The user can pin a different
+// member of any group (see Reviews::pin()); applying the choices trashes the
+// rest of each group, exactly as previewed.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeeperStrategy {
+    #[default]
+    Off, // No preview; only pinned groups have a keeper
+    Recommended, // See OutputPhase::keepers, or failing that the largest file
+    LargestFile,
+    HighestResolution,
+    Oldest,
+    Newest,
+    ShortestPath,
+}
+
+impl KeeperStrategy {
+    pub const ALL: [KeeperStrategy; 7] = [
+        KeeperStrategy::Off,
+        KeeperStrategy::Recommended,
+        KeeperStrategy::LargestFile,
+        KeeperStrategy::HighestResolution,
+        KeeperStrategy::Oldest,
+        KeeperStrategy::Newest,
+        KeeperStrategy::ShortestPath,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeeperStrategy::Off => tr!("No preview"),
+            KeeperStrategy::Recommended => tr!("Recommended"),
+            KeeperStrategy::LargestFile => tr!("Largest file"),
+            KeeperStrategy::HighestResolution => tr!("Highest resolution"),
+            KeeperStrategy::Oldest => tr!("Oldest"),
+            KeeperStrategy::Newest => tr!("Newest"),
+            KeeperStrategy::ShortestPath => tr!("Shortest path"),
+        }
+    }
+
+    // The member to keep, by index; None when Off. Ties go to whichever
+    // comes first, so the choice only changes when the group does.
+    // recommended is every group's recommended keeper.
+    pub fn pick(self, group: &[Image], recommended: &HashSet<PathBuf>) -> Option<usize> {
+        let first_max = |key: &dyn Fn(&Image) -> (u64, u64)| -> Option<usize> {
+            // max_by_key() would take the last of equals.
+            group.iter().enumerate().rev().max_by_key(|(_, x)| key(x)).map(|(idx, _)| idx)
+        };
+        let secs = |x: &Image| x.mtime.and_then(|x| x.duration_since(std::time::UNIX_EPOCH).ok()).map(|x| x.as_secs());
+        match self {
+            KeeperStrategy::Off => None,
+            KeeperStrategy::Recommended => group.iter()
+                .position(|x| recommended.contains(&x.path))
+                .or_else(|| KeeperStrategy::LargestFile.pick(group, recommended)),
+            KeeperStrategy::LargestFile => first_max(&|x| (x.file_size as u64, 0)),
+            KeeperStrategy::HighestResolution => {
+                first_max(&|x| (x.dimm.map_or(0, |(w, h)| w as u64 * h as u64), x.file_size as u64))
+            },
+            // Files whose time isn't known are never picked over ones whose is.
+            KeeperStrategy::Oldest => first_max(&|x| (secs(x).map_or(0, |x| u64::MAX - x), 0)),
+            KeeperStrategy::Newest => first_max(&|x| (secs(x).map_or(0, |x| x + 1), 0)),
+            KeeperStrategy::ShortestPath => first_max(&|x| {
+                let len = x.path.as_os_str().len() as u64;
+                (u64::MAX - x.path.components().count() as u64, u64::MAX - len)
+            }),
+        }
+    }
+}
+
+// What trashing every member but the keeper would free: hard links to the
+// keeper free nothing, and other links to one file only count once.
+pub fn reclaimed(group: &[Image], keeper: usize) -> usize {
+    let mut seen: HashSet<_> = group[keeper].file_id.into_iter().collect();
+    group.iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != keeper)
+        .filter(|(_, x)| x.file_id.is_none_or(|id| seen.insert(id)))
+        .map(|(_, x)| x.file_size)
+        .sum()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn strategies_pick_one_member_each() {
+        let image = |path: &str, size: usize, dimm: (u32, u32), secs: Option<u64>| {
+            let mut image = Image::new_lazy(PathBuf::from(path), size, Some(dimm));
+            image.mtime = secs.map(|x| UNIX_EPOCH + Duration::from_secs(x));
+            image
+        };
+        let group = vec![
+            image("/photos/2020/a.jpg", 300, (400, 300), None),
+            image("/photos/a.png", 900, (400, 300), Some(200)),
+            image("/backup/old/stuff/a.jpg", 500, (800, 600), Some(100)),
+            image("/b.jpg", 900, (80, 60), Some(300)),
+        ];
+        let none = HashSet::new();
+        let pick = |x: KeeperStrategy| x.pick(&group, &none);
+
+        assert_eq!(pick(KeeperStrategy::Off), None);
+        assert_eq!(pick(KeeperStrategy::LargestFile), Some(1));
+        assert_eq!(pick(KeeperStrategy::HighestResolution), Some(2));
+        assert_eq!(pick(KeeperStrategy::Oldest), Some(2));
+        assert_eq!(pick(KeeperStrategy::Newest), Some(3));
+        assert_eq!(pick(KeeperStrategy::ShortestPath), Some(3));
+        assert_eq!(pick(KeeperStrategy::Recommended), Some(1));
+        let recommended = HashSet::from([PathBuf::from("/photos/2020/a.jpg")]);
+        assert_eq!(KeeperStrategy::Recommended.pick(&group, &recommended), Some(0));
+
+        assert_eq!(reclaimed(&group, 1), 300 + 500 + 900);
+    }
+}
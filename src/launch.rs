@@ -0,0 +1,106 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+
+// `deckard [--search] [[--root] <folder>]`: opens on the folder rather than
+// the one last searched, e.g. from a file manager's "Open with" or a script,
+// and with --search, starts searching it straight away. A folder that won't
+// do is said once the window's open, rather than before there is one, and the
+// app starts as usual. The headless scan has its own flag; see headless.
+
+pub const USAGE: &str = "\
+Usage: deckard [--search] [[--root] <folder>]
+       deckard --headless-scan <preset name or options.json>
+
+Options:
+  --root <folder>  Open with the folder filled in, rather than the last one searched
+  --search         Start searching the folder as soon as the window opens
+  -h, --help       Show this help";
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Launch {
+    pub root: Option<PathBuf>, // As given, unchecked; see checked_root()
+    pub search: bool,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum Args {
+    Run(Launch),
+    Help,
+}
+
+// Everything after the program name. Errors are for arguments that make no
+// sense, not for folders that don't exist; see checked_root().
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Args, String> {
+    let mut launch = Launch::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let root = match arg.to_str() {
+            Some("-h" | "--help") => return Ok(Args::Help),
+            Some("--search") => {
+                launch.search = true;
+                continue;
+            },
+            Some("--root") => args.next().ok_or("--root needs a folder")?,
+            // Added by macOS to apps opened from the Finder, on older versions
+            Some(x) if x.starts_with("-psn_") => continue,
+            Some(x) if x.starts_with('-') && x != "-" => return Err(format!("Unknown option {x}")),
+            _ => arg,
+        };
+        if launch.root.is_some() {
+            return Err("Only one folder can be given".to_string());
+        }
+        launch.root = Some(PathBuf::from(root));
+    }
+    Ok(Args::Run(launch))
+}
+
+impl Launch {
+    // The folder to open with, if one was given, or what's wrong with it.
+    pub fn checked_root(&self) -> Option<Result<PathBuf, String>> {
+        self.root.as_deref().map(checked_root)
+    }
+}
+
+// Absolute, as a folder searched from the form would be, since the working
+// directory means nothing once the app's running.
+pub fn checked_root(path: &Path) -> Result<PathBuf, String> {
+    match path.canonicalize() {
+        Ok(root) if root.is_dir() => Ok(root),
+        Ok(_) => Err(tr!("{path} isn't a folder, so it can't be searched.", path = path.display())),
+        Err(e) => Err(tr!("{path} can't be opened: {error}", path = path.display(), error = e)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_strs(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn arguments() {
+        let run = |root: Option<&str>, search| Ok(Args::Run(Launch{root: root.map(PathBuf::from), search}));
+        assert_eq!(parse_strs(&[]), run(None, false));
+        assert_eq!(parse_strs(&["/photos"]), run(Some("/photos"), false));
+        assert_eq!(parse_strs(&["--search", "--root", "/photos"]), run(Some("/photos"), true));
+        assert_eq!(parse_strs(&["-psn_0_12345", "/photos"]), run(Some("/photos"), false));
+        assert_eq!(parse_strs(&["/photos", "--help"]), Ok(Args::Help));
+        assert!(parse_strs(&["--root"]).is_err());
+        assert!(parse_strs(&["--bogus"]).is_err());
+        assert!(parse_strs(&["/photos", "/more"]).is_err());
+    }
+
+    #[test]
+    fn roots_must_be_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.jpg");
+        std::fs::write(&file, b"").unwrap();
+        assert_eq!(checked_root(dir.path()), Ok(dir.path().canonicalize().unwrap()));
+        assert!(checked_root(&file).is_err());
+        assert!(checked_root(&dir.path().join("missing")).is_err());
+    }
+}
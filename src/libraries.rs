@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+
+// Photo managers' libraries keep their own copies of every picture next to
+// the originals: previews, thumbnails and renders, which match the originals
+// but aren't anything the user could delete. A folder that's one of these
+// libraries is recognized by the rules below, and by default only its
+// originals are searched; see SearchOpts::scan_libraries.
+
+// What marks a folder as a library's top.
+enum Marker {
+    Extension(&'static str), // Its own name, e.g. a macOS bundle
+    Contains(&'static str), // A file directly in it
+    ContainsExtension(&'static str), // A file directly in it, by extension
+}
+
+impl Marker {
+    fn matches(&self, dir: &Path) -> bool {
+        let has_ext = |path: &Path, ext: &str| path.extension().is_some_and(|x| x.eq_ignore_ascii_case(ext));
+        match self {
+            Marker::Extension(ext) => has_ext(dir, ext),
+            Marker::Contains(name) => dir.join(name).is_file(),
+            Marker::ContainsExtension(ext) => std::fs::read_dir(dir).is_ok_and(|entries| {
+                entries.filter_map(|x| x.ok()).any(|x| has_ext(&x.path(), ext))
+            }),
+        }
+    }
+}
+
+// A folder under a library's top holding the app's own copies.
+enum Derivative {
+    Path(&'static str), // Relative to the top, with forward slashes
+    Name(&'static str), // At any depth
+    Extension(&'static str), // Directly in the top
+}
+
+impl Derivative {
+    fn matches(&self, rel: &Path) -> bool {
+        match self {
+            Derivative::Path(path) => rel == Path::new(path),
+            Derivative::Name(name) => rel.file_name().is_some_and(|x| x == *name),
+            Derivative::Extension(ext) => rel.components().count() == 1
+                && rel.extension().is_some_and(|x| x.eq_ignore_ascii_case(ext)),
+        }
+    }
+}
+
+pub struct LibraryKind {
+    pub name: &'static str,
+    marker: Marker,
+    originals: &'static [&'static str], // Folders directly in the top; if any, nothing else in it is searched
+    derivatives: &'static [Derivative],
+}
+
+const KINDS: &[LibraryKind] = &[
+    LibraryKind {
+        name: "Apple Photos",
+        marker: Marker::Extension("photoslibrary"),
+        originals: &["originals", "Masters"], // Masters in libraries from before Photos 5
+        derivatives: &[
+            Derivative::Path("resources/derivatives"),
+            Derivative::Path("resources/renders"),
+            Derivative::Path("resources/proxies"),
+            Derivative::Name("Thumbnails"),
+            Derivative::Name("Previews"),
+        ],
+    },
+    LibraryKind {
+        name: "Lightroom",
+        marker: Marker::ContainsExtension("lrcat"),
+        originals: &[],
+        derivatives: &[
+            Derivative::Extension("lrdata"), // Previews, Smart Previews and Helper
+        ],
+    },
+    LibraryKind {
+        name: "digiKam",
+        marker: Marker::Contains("digikam4.db"),
+        originals: &[],
+        derivatives: &[
+            Derivative::Name(".dtrash"),
+        ],
+    },
+];
+
+// What to do with a folder in a library.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Scan,
+    Derivative,
+    NotOriginals, // Beside the originals, in a library that has a folder for them
+}
+
+pub struct Library {
+    pub top: PathBuf,
+    pub kind: &'static LibraryKind,
+}
+
+impl Library {
+    // The library whose top this folder is, if it's one.
+    pub fn detect(dir: &Path) -> Option<Library> {
+        let kind = KINDS.iter().find(|x| x.marker.matches(dir))?;
+        Some(Library{top: dir.to_path_buf(), kind})
+    }
+
+    // The library a folder being searched is in, looking up from it, e.g. a
+    // search of just a library's originals folder.
+    pub fn containing(dir: &Path) -> Option<Library> {
+        dir.ancestors().find_map(Self::detect)
+    }
+
+    // Like detect(), but for every folder a search walks, so it can't cost
+    // more than a stat or two. Markers that need the folder's listing are
+    // only looked for beside a folder that looks like one of the kind's
+    // derivatives, so the library found can be this folder's parent.
+    fn detect_walked(dir: &Path) -> Option<Library> {
+        KINDS.iter().find_map(|kind| {
+            let top = match kind.marker {
+                Marker::ContainsExtension(_) => {
+                    let parent = dir.parent()?;
+                    let rel = dir.strip_prefix(parent).ok()?;
+                    if !kind.derivatives.iter().any(|x| x.matches(rel)) {
+                        return None;
+                    }
+                    parent
+                },
+                _ => dir,
+            };
+            kind.marker.matches(top).then(|| Library{top: top.to_path_buf(), kind})
+        })
+    }
+
+    pub fn originals_only(&self) -> bool {
+        !self.kind.originals.is_empty()
+    }
+
+    // For a folder under the top. Only called for folders the walk reaches,
+    // so anything under a skipped one never is.
+    pub fn verdict(&self, dir: &Path) -> Verdict {
+        let Ok(rel) = dir.strip_prefix(&self.top) else {
+            return Verdict::Scan;
+        };
+        let first = rel.components().next();
+        if self.originals_only() && rel.components().count() == 1
+            && !self.kind.originals.iter().any(|x| first.is_some_and(|first| first.as_os_str() == *x)) {
+            return Verdict::NotOriginals;
+        }
+        if self.kind.derivatives.iter().any(|x| x.matches(rel)) {
+            return Verdict::Derivative;
+        }
+        Verdict::Scan
+    }
+}
+
+// A library a search came across, for the scan details.
+#[derive(Clone, Debug)]
+pub struct LibraryReport {
+    pub top: PathBuf,
+    pub name: &'static str,
+    pub originals_only: bool,
+    pub skipped: usize, // Folders left out
+    pub scanned_all: bool, // Overridden; see SearchOpts::scan_libraries
+}
+
+impl LibraryReport {
+    pub fn describe(&self) -> String {
+        if self.scanned_all {
+            return tr!("{name} library detected — scanning everything in it", name = self.name);
+        }
+        match self.originals_only {
+            true => tr!(
+                "{name} library detected — scanning originals only, {count} other folders skipped",
+                name = self.name,
+                count = self.skipped,
+            ),
+            false => tr!(
+                "{name} library detected — {count} derivative folders skipped",
+                name = self.name,
+                count = self.skipped,
+            ),
+        }
+    }
+}
+
+// The libraries a search has come across so far, deciding which of the
+// folders it walks to leave out. Shared with the walker's filter, which sees
+// each folder before anything in it.
+pub struct Libraries {
+    found: Mutex<Vec<(Library, usize)>>, // With the number of folders skipped in each
+    scan_all: bool, // Only report them
+}
+
+impl Libraries {
+    // Starts with any library the roots are in (or are), which the walk
+    // doesn't filter.
+    pub fn new(roots: &[&Path], scan_all: bool) -> Libraries {
+        let mut found: Vec<(Library, usize)> = vec![];
+        for library in roots.iter().filter_map(|x| Library::containing(x)) {
+            if !found.iter().any(|(x, _)| x.top == library.top) {
+                found.push((library, 0));
+            }
+        }
+        Libraries{found: Mutex::new(found), scan_all}
+    }
+
+    // Whether to walk into the folder.
+    pub fn allows(&self, dir: &Path) -> bool {
+        let mut found = self.found.lock().unwrap();
+        let known = found.iter().position(|(x, _)| dir.starts_with(&x.top));
+        let idx = match known {
+            Some(idx) => idx,
+            None => match Library::detect_walked(dir) {
+                Some(library) => {
+                    found.push((library, 0));
+                    found.len() - 1
+                },
+                None => return true,
+            },
+        };
+        let (library, skipped) = &mut found[idx];
+        if self.scan_all || library.verdict(dir) == Verdict::Scan {
+            return true;
+        }
+        *skipped += 1;
+        false
+    }
+
+    pub fn report(&self) -> Vec<LibraryReport> {
+        self.found.lock().unwrap().iter()
+            .map(|(library, skipped)| LibraryReport {
+                top: library.top.clone(),
+                name: library.kind.name,
+                originals_only: library.originals_only(),
+                skipped: *skipped,
+                scanned_all: self.scan_all,
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_dirs(root: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+    }
+
+    #[test]
+    fn photos_libraries_are_searched_for_originals_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let top = tmp.path().join("Photos Library.photoslibrary");
+        make_dirs(&top, &["originals/A", "resources/derivatives/A", "database"]);
+        assert!(Library::detect(tmp.path()).is_none());
+
+        let library = Library::containing(&top.join("originals/A")).unwrap();
+        assert_eq!(library.kind.name, "Apple Photos");
+        assert_eq!(library.top, top);
+        assert_eq!(library.verdict(&top.join("originals")), Verdict::Scan);
+        assert_eq!(library.verdict(&top.join("originals/A")), Verdict::Scan);
+        assert_eq!(library.verdict(&top.join("resources")), Verdict::NotOriginals);
+        assert_eq!(library.verdict(&top.join("database")), Verdict::NotOriginals);
+    }
+
+    #[test]
+    fn catalogs_skip_their_previews() {
+        let tmp = tempfile::tempdir().unwrap();
+        make_dirs(tmp.path(), &["lr/2024/01", "lr/Catalog Previews.lrdata/A", "dk/Trip/.dtrash", "plain/Previews.lrdata"]);
+        std::fs::write(tmp.path().join("lr/Catalog.lrcat"), b"").unwrap();
+        std::fs::write(tmp.path().join("dk/digikam4.db"), b"").unwrap();
+
+        let lightroom = Library::detect(&tmp.path().join("lr")).unwrap();
+        assert_eq!(lightroom.kind.name, "Lightroom");
+        assert!(!lightroom.originals_only());
+        assert_eq!(lightroom.verdict(&tmp.path().join("lr/2024/01")), Verdict::Scan);
+        assert_eq!(lightroom.verdict(&tmp.path().join("lr/Catalog Previews.lrdata")), Verdict::Derivative);
+
+        let digikam = Library::detect(&tmp.path().join("dk")).unwrap();
+        assert_eq!(digikam.kind.name, "digiKam");
+        assert_eq!(digikam.verdict(&tmp.path().join("dk/Trip")), Verdict::Scan);
+        assert_eq!(digikam.verdict(&tmp.path().join("dk/Trip/.dtrash")), Verdict::Derivative);
+
+        // Without a catalog, it's just a folder.
+        assert!(Library::containing(&tmp.path().join("plain/Previews.lrdata")).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::misc::{Image, Side};
+use crate::searcher::{self, Searcher, SearchResults, SearchPanicked, Hashed};
+use crate::search_lock::SearchLock;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use eframe::egui;
+use image_hasher::ImageHash;
+
+
+// A search that's still running, while its partial results are reviewed. A
+// worker gathers the files the search streams (see Searcher::stream()) by
+// hash, splitting them by color like the search will, and loads each match as
+// soon as there is one. Only exact matches turn up this way, and the search's
+// own results, once it's done, are the final word; see
+// OutputPhase::finish_live().
+
+// Files to add to the results: to the group with the given member, or if
+// None, as a new group.
+pub struct LiveUpdate {
+    pub joins: Option<PathBuf>,
+    pub images: Vec<Image>,
+}
+
+pub struct LiveSearch {
+    searcher: Searcher,
+    _locks: Vec<SearchLock>, // Held until the search is over, as in SearchingPhase
+    updates: Receiver<LiveUpdate>,
+}
+
+impl LiveSearch {
+    // Takes the stream the searcher was asked for. reference is the folder
+    // of the reference side in compare mode, whose matches need a file from
+    // each side.
+    pub fn new(
+        searcher: Searcher,
+        stream: Receiver<Hashed>,
+        locks: Vec<SearchLock>,
+        reference: Option<PathBuf>,
+        ctx: egui::Context,
+    ) -> LiveSearch {
+        let (tx, updates) = mpsc::channel();
+        // Ends when the stream does (hashing's done, or the search was
+        // canceled) or nobody's listening anymore.
+        std::thread::spawn(move || {
+            let mut buckets: HashMap<ImageHash, Vec<Hashed>> = HashMap::new();
+            let mut sent = HashSet::new();
+            for file in stream {
+                let bucket = buckets.entry(file.hash.clone()).or_default();
+                if bucket.iter().any(|x| x.path == file.path) {
+                    continue; // Sent twice while catching up
+                }
+                let path = file.path.clone();
+                bucket.push(file);
+                let Some(update) = Self::update(bucket, &path, reference.as_deref(), &mut sent) else {
+                    continue;
+                };
+                if tx.send(update).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+        LiveSearch{searcher, _locks: locks, updates}
+    }
+
+    // What the file just hashed into the bucket adds to the results, if
+    // anything: the files in its cluster not yet sent, once the cluster's a
+    // match. Files that fail to load are left to the final results.
+    fn update(bucket: &[Hashed], path: &Path, reference: Option<&Path>, sent: &mut HashSet<PathBuf>) -> Option<LiveUpdate> {
+        let file = |path: &Path| bucket.iter().find(|x| x.path == path).unwrap();
+        let paths = bucket.iter().map(|x| x.path.clone()).collect();
+        let (clusters, _) = searcher::split_by_color(paths, |x| file(x).color.clone());
+        let cluster = clusters.into_iter().find(|x| x.iter().any(|x| x == path))?;
+        let side = |path: &Path| reference.map(|x| if path.starts_with(x) { Side::Reference } else { Side::Candidate });
+        let sides: HashSet<_> = cluster.iter().map(|x| side(x)).collect();
+        if cluster.len() < 2 || (reference.is_some() && sides.len() < 2) || cluster.iter().all(|x| !file(x).in_range) {
+            return None;
+        }
+
+        let joins = cluster.iter().find(|x| sent.contains(*x)).cloned();
+        let images: Vec<Image> = cluster.into_iter()
+            .filter(|x| !sent.contains(x))
+            .filter_map(|path| {
+                let hashed = file(&path);
+                let mut image = searcher::load_image(path.clone(), hashed.hash.clone(), hashed.frame_count).ok()?;
+                image.content = hashed.content;
+                image.side = side(&path);
+                Some(image)
+            })
+            .collect();
+        if images.is_empty() || (joins.is_none() && images.len() < 2) {
+            return None;
+        }
+        sent.extend(images.iter().map(|x| x.path.clone()));
+        Some(LiveUpdate{joins, images})
+    }
+
+    // Everything found since the last poll.
+    pub fn poll(&self) -> Vec<LiveUpdate> {
+        self.updates.try_iter().collect()
+    }
+
+    // How far along the search is, as a whole percentage of the files found
+    // so far that have been hashed; it can go down as the walk finds more.
+    // 100 once it's putting its results together.
+    pub fn percent(&self) -> usize {
+        if self.searcher.finalizing().is_some() {
+            return 100;
+        }
+        let (found, hashed) = self.searcher.progress();
+        (hashed * 100).checked_div(found).unwrap_or(0).min(99)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.searcher.is_finished()
+    }
+
+    // Call once is_finished().
+    pub fn finish(mut self) -> Result<SearchResults, SearchPanicked> {
+        self.searcher.wait_for_search()
+    }
+
+    // Waits for the search to stop, like SearchingPhase does, so its
+    // checkpoint is saved.
+    pub fn cancel(mut self) {
+        self.searcher.cancel();
+        let _ = self.searcher.wait_for_search();
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{Result, Error};
+use crate::archive::{Archive, ArchiveRoot, Namespace};
+use crate::misc::{Image, format_time};
+use crate::os_path::{self, long_path};
+use crate::searcher::{SearchOpts, Searcher, SizeCap};
+use crate::i18n::format_size;
+use crate::sniff;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use eframe::egui;
+
+use image_hasher::HashAlg;
+
+
+// "Have I seen this image before?": checks a single image against every file
+// past searches recorded in the archive index, and manages what's recorded.
+
+const THUMB_SIZE: f32 = 96.0;
+
+// Thumbnails are loaded for this many matches; the rest are listed.
+const MAX_THUMBNAILS: usize = 50;
+
+// The most bits a lookup can allow to differ; much past this, unrelated
+// images start to match.
+const MAX_DISTANCE: u32 = 16;
+
+struct Found {
+    path: PathBuf,
+    distance: u32,
+    image: Option<Image>, // None past MAX_THUMBNAILS, or if it couldn't be read
+}
+
+// What the window needs from the form; it doesn't own any options.
+pub enum LookupAction {
+    Reindex(PathBuf, HashAlg),
+}
+
+pub struct Lookup {
+    query: Option<PathBuf>,
+    max_distance: u32, // 0 by default, so matches are what a search would group
+    running: Option<JoinHandle<std::result::Result<Vec<Found>, String>>>,
+    results: Option<std::result::Result<Vec<Found>, String>>,
+    archive: Option<Arc<Archive>>,
+    loading: Option<JoinHandle<Arc<Archive>>>, // Reading the archive, for the folder list
+    reindexing: Option<(PathBuf, HashAlg, Searcher)>,
+    recording: Option<JoinHandle<std::result::Result<Arc<Archive>, String>>>,
+}
+
+impl Lookup {
+    pub fn new() -> Lookup {
+        Lookup {
+            query: None,
+            max_distance: 0,
+            running: None,
+            results: None,
+            archive: None,
+            loading: Some(std::thread::spawn(Archive::shared)),
+            reindexing: None,
+            recording: None,
+        }
+    }
+
+    // The image is read with the cap a search would read it with.
+    fn start(&mut self, path: PathBuf, hash: HashAlg, size_cap: SizeCap) {
+        let query = path.clone();
+        let max_distance = self.max_distance;
+        self.running = Some(std::thread::spawn(move || {
+            let matches = Archive::lookup(&query, hash, size_cap, max_distance)?;
+            Ok(matches.into_iter()
+                .enumerate()
+                .map(|(idx, x)| Found {
+                    image: (idx < MAX_THUMBNAILS).then(|| Image::load(x.path.clone()).ok()).flatten(),
+                    path: x.path,
+                    distance: x.distance,
+                })
+                .collect())
+        }));
+        self.query = Some(path);
+        self.results = None;
+    }
+
+    // Searches the folder again with the given options, updating what's
+    // recorded for it once it's done.
+    pub fn reindex(&mut self, opts: SearchOpts) {
+        let (root, hash) = (opts.root.clone(), opts.hash);
+        let mut searcher = Searcher::new(opts);
+        searcher.launch_search();
+        self.reindexing = Some((root, hash, searcher));
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) -> Result<()> {
+        let busy = [
+            self.running.as_ref().map(JoinHandle::is_finished),
+            self.loading.as_ref().map(JoinHandle::is_finished),
+            self.reindexing.as_ref().map(|(_, _, x)| x.is_finished()),
+            self.recording.as_ref().map(JoinHandle::is_finished),
+        ];
+        if busy.contains(&Some(false)) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        if self.running.as_ref().is_some_and(JoinHandle::is_finished) {
+            self.results = self.running.take().unwrap().join().ok();
+        }
+        if self.loading.as_ref().is_some_and(JoinHandle::is_finished) {
+            self.archive = self.loading.take().unwrap().join().ok();
+        }
+        if self.reindexing.as_ref().is_some_and(|(_, _, x)| x.is_finished()) {
+            let (root, _, mut searcher) = self.reindexing.take().unwrap();
+            let results = searcher.wait_for_search().map_err(|e| Error::new(
+                "Error re-indexing".to_string(),
+                tr!("The search crashed: {msg}", msg = e.msg),
+            ))?;
+            self.recording = Some(std::thread::spawn(move || {
+                Archive::record(&[root], &results.index)?;
+                Ok(Archive::shared())
+            }));
+        }
+        if self.recording.as_ref().is_some_and(JoinHandle::is_finished) {
+            match self.recording.take().unwrap().join() {
+                Ok(Ok(archive)) => self.archive = Some(archive),
+                Ok(Err(e)) => return Err(Error::new("Error re-indexing".to_string(), e)),
+                Err(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the window's still open, and anything the form needs to do.
+    // With check_opens, warns before opening a file whose contents don't
+    // match its extension, as the results do.
+    pub fn draw(&mut self, ctx: &egui::Context, hash: HashAlg, size_cap: SizeCap, check_opens: bool) -> Result<(bool, Option<LookupAction>)> {
+        self.poll(ctx)?;
+
+        let mut open = true;
+        let mut action = None;
+        let mut ret = Ok(());
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Look up an image"));
+        let vid = egui::viewport::ViewportId::from_hash_of("lookup window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                open = !ctx.input(|i| i.viewport().close_requested());
+
+                let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|x| x.path.clone()));
+                let mut query = dropped;
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Choose image…")).clicked() {
+                        query = rfd::FileDialog::new().pick_file();
+                    }
+                    ui.label(tr!("or drop one here"));
+                    ui.separator();
+                    ui.label(tr!("Max distance:"));
+                    ui.add(egui::DragValue::new(&mut self.max_distance).clamp_range(0..=MAX_DISTANCE))
+                        .on_hover_text(tr!("How many bits of the hash may differ. 0 finds only \
+                            images the search would group together."));
+                });
+                if let Some(path) = query.filter(|_| self.running.is_none()) {
+                    self.start(path, hash, size_cap);
+                }
+
+                ui.separator();
+                egui::ScrollArea::vertical().id_source("lookup results").max_height(ui.available_height() * 0.65).show(ui, |ui| {
+                    ret = self.draw_results(ui, check_opens);
+                });
+
+                ui.separator();
+                egui::CollapsingHeader::new(tr!("Indexed folders")).id_source("indexed folders").show(ui, |ui| {
+                    action = self.draw_roots(ui);
+                });
+            });
+        });
+        ret?;
+        Ok((open, action))
+    }
+
+    fn draw_results(&self, ui: &mut egui::Ui, check_opens: bool) -> Result<()> {
+        if let Some(query) = &self.query {
+            ui.horizontal(|ui| {
+                ui.strong(tr!("Looking up"));
+                ui.monospace(query.display().to_string());
+            });
+        }
+        if self.running.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(tr!("Searching the index…"));
+            });
+            return Ok(());
+        }
+        let found = match &self.results {
+            None => {
+                ui.label(tr!("Choose an image to check whether it's already in a folder searched before."));
+                return Ok(());
+            },
+            Some(Err(e)) => {
+                ui.label(egui::RichText::new(e).color(ui.visuals().error_fg_color));
+                return Ok(());
+            },
+            Some(Ok(x)) => x,
+        };
+        if found.is_empty() {
+            ui.label(tr!("Not found in any indexed folder"));
+            return Ok(());
+        }
+
+        let mut ret = Ok(());
+        ui.label(tr!("Found {count} matches", count = found.len()));
+        for x in found {
+            ui.horizontal(|ui| {
+                match &x.image {
+                    Some(image) => {
+                        let uri = os_path::image_uri(&image.path);
+                        ui.add(egui::Image::from_bytes(uri, image.buffer())
+                            .max_size(egui::Vec2::splat(THUMB_SIZE)));
+                    },
+                    None => {
+                        ui.allocate_exact_size(egui::Vec2::splat(THUMB_SIZE), egui::Sense::hover());
+                    },
+                }
+                ui.vertical(|ui| {
+                    ui.monospace(x.path.display().to_string());
+                    if x.distance == 0 {
+                        ui.label(tr!("exact"));
+                    } else {
+                        ui.label(tr!("distance {distance}", distance = x.distance));
+                    }
+                    ui.horizontal(|ui| {
+                        let err = if ui.button(tr!("Open")).clicked() {
+                            match !check_opens || sniff::confirm_contents(&x.path) {
+                                true => opener::open(long_path(&x.path)),
+                                false => Ok(()),
+                            }
+                        } else if ui.button(tr!("Show")).clicked() {
+                            opener::reveal(long_path(&x.path))
+                        } else {
+                            Ok(())
+                        };
+                        if let Err(e) = err {
+                            ret = Err(Error::new("Error showing file".to_string(), e.to_string()));
+                        }
+                    });
+                });
+            });
+        }
+        ret
+    }
+
+    fn draw_roots(&mut self, ui: &mut egui::Ui) -> Option<LookupAction> {
+        let Some(archive) = &self.archive else {
+            ui.spinner();
+            return None;
+        };
+        if archive.roots.is_empty() {
+            ui.label(tr!("Nothing indexed yet. Every search adds the folders it searched."));
+            return None;
+        }
+
+        if self.recording.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(tr!("Saving the index…"));
+            });
+        }
+
+        let busy = self.reindexing.is_some() || self.recording.is_some();
+        let mut action = None;
+        let mut purge = None;
+        egui::Grid::new("indexed folders").num_columns(6).striped(true).show(ui, |ui| {
+            ui.strong(tr!("Folder"));
+            ui.strong(tr!("Algorithm"));
+            ui.strong(tr!("Indexed"));
+            ui.strong(tr!("Images"));
+            ui.strong(tr!("Size"));
+            ui.label("");
+            ui.end_row();
+
+            for entry in &archive.roots {
+                let ArchiveRoot{root, hash, indexed_at, entries, ..} = entry;
+                ui.monospace(root.display().to_string());
+                ui.label(entry.namespace().label());
+                ui.label(format_time(*indexed_at));
+                ui.label(entries.len().to_string());
+                ui.label(format_size(entry.stored_bytes()));
+                ui.horizontal(|ui| {
+                    let this_one = self.reindexing.as_ref().is_some_and(|(x, y, _)| x == root && y == hash);
+                    if this_one {
+                        ui.spinner();
+                    } else if ui.add_enabled(!busy, egui::Button::new(tr!("Re-index"))).clicked() {
+                        action = Some(LookupAction::Reindex(root.clone(), *hash));
+                    }
+                    if ui.add_enabled(!busy, egui::Button::new(tr!("Purge")))
+                        .on_hover_text(tr!("Forget what's indexed for this folder with this algorithm"))
+                        .clicked() {
+                        purge = Some((root.clone(), entry.namespace()));
+                    }
+                });
+                ui.end_row();
+            }
+        });
+
+        if let Some((root, namespace)) = purge {
+            self.purge(root, namespace);
+        }
+        action
+    }
+
+    fn purge(&mut self, root: PathBuf, namespace: Namespace) {
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(tr!("Purge index"))
+            .set_description(tr!(
+                "Forget what's indexed for {folder} with {algorithm}? Searching it again with \
+                that algorithm indexes it again.",
+                folder = root.display(),
+                algorithm = namespace.label(),
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if confirmed == rfd::MessageDialogResult::Yes {
+            self.recording = Some(std::thread::spawn(move || {
+                Archive::purge(&root, namespace)?;
+                Ok(Archive::shared())
+            }));
+        }
+    }
+}
@@ -1,21 +1,85 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+#[macro_use]
+mod i18n;
 mod startup_phase;
 mod searching_phase;
 mod output_phase;
 mod searcher;
 mod misc;
+mod hardlink;
+mod session;
+mod undo;
+mod benchmark;
+mod ignored;
+mod exclusions;
+mod animation;
+mod checkpoint;
+mod crop_match;
+mod watcher;
+mod plan;
+mod encoding;
+mod content;
+mod palette;
+mod quick_look;
+mod folder_tree;
+mod search_lock;
+mod report;
+mod archive;
+mod bk_tree;
+mod lookup;
+mod finder_tags;
+mod borders;
+mod hook;
+mod os_path;
+mod clicks;
+mod a11y;
+mod review;
+mod options;
+mod headless;
+mod thumbnails;
+mod grouping;
+mod compare_tool;
+mod frame_stats;
+mod live_search;
+mod search_events;
+mod folder_browser;
+mod sniff;
+mod window;
+mod libraries;
+mod keepers;
+mod history;
+mod junk;
+mod rename;
+mod move_files;
+mod settings;
+mod theme;
+mod broken;
+mod launch;
+mod sources;
+#[cfg(test)]
+mod search_harness;
 
 use std::sync::Arc;
 
 use startup_phase::StartupPhase;
+use palette::{Command, Palette};
+use frame_stats::FrameStats;
+use window::WindowGeometry;
+use misc::StoredSettings;
+use i18n::{Language, SizeUnits};
+use theme::Theme;
+use launch::Launch;
 
 use eframe::egui;
 use egui::viewport::IconData;
 
 const MIN_INNER_SIZE: (f32, f32) = (550.0, 400.0);
-const ROOT_KEY: &str = "STARTUPPHASE_ROOT";
+
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 3.0;
+const ZOOM_STEP: f32 = 0.1;
 
 type DynPhase = Box<dyn Phase>;
 
@@ -25,11 +89,27 @@ trait Phase {
     // displayed in a modal dialog. The Phase must be in valid state when returning
     // an error, as render() will be called once the modal is dismissed.
     fn render(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>>;
+
+    // Called each frame instead of render() while the phase's tab isn't in
+    // front, for keeping up with work running in the background (e.g. a
+    // search finishing). Returns as from render().
+    fn poll(&mut self, _ctx: &egui::Context) -> Result<Option<DynPhase>> { Ok(None) }
     fn save(&mut self, _storage: &mut dyn eframe::Storage) {}
+
+    // Shown on its tab.
+    fn title(&self) -> String;
+
+    // What the command palette offers in this phase, right now. Only include
+    // commands that would work (e.g. no "Undo" with nothing to undo).
+    fn commands(&self) -> Vec<Command> { vec![] }
+
+    // Runs a command from commands(), by id; returns as from render().
+    fn run_command(&mut self, _ctx: &egui::Context, _id: &str) -> Result<Option<DynPhase>> { Ok(None) }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug)]
 struct Error {
     err: String,
     detail: String,
@@ -61,33 +141,202 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-struct App {
+// The zoom factor lives in the egui context (so phases can read and change it
+// directly); these keep it in range.
+fn set_zoom(ctx: &egui::Context, zoom: f32) {
+    ctx.set_zoom_factor(zoom.clamp(MIN_ZOOM, MAX_ZOOM));
+}
+
+// An independent search, with its own phase.
+struct Tab {
+    id: u64, // Scopes the phase's widget ids, so tabs don't share scroll offsets and such
     phase: DynPhase,
 }
 
+struct App {
+    tabs: Vec<Tab>, // Never empty
+    active: usize,
+    next_tab_id: u64,
+    ctx: egui::Context, // For reading the zoom factor in save()
+    applied_zoom: f32, // Zoom the minimum window size was last scaled for
+    palette: Palette,
+    frame_stats: FrameStats,
+    restored_window: Option<WindowGeometry>, // Until it's been checked against the monitor it's on
+}
+
 impl App {
 
-    fn new(cc: &eframe::CreationContext) -> App {
+    fn new(cc: &eframe::CreationContext, launch: &Launch) -> App {
+        // egui has its own zoom shortcuts, but they don't clamp, and handling
+        // them here keeps everything zoom-related in one place.
+        cc.egui_ctx.options_mut(|o| o.zoom_with_keyboard = false);
+        if let Some(zoom) = cc.storage.and_then(|x| settings::get(x, settings::ZOOM)) {
+            set_zoom(&cc.egui_ctx, zoom);
+        }
+        if let Some(lang) = cc.storage.and_then(|x| settings::get(x, settings::LANGUAGE)) {
+            lang.set_current();
+        }
+        if let Some(units) = cc.storage.and_then(|x| settings::get(x, settings::UNITS)) {
+            units.set_current();
+        }
+        if let Some(theme) = cc.storage.and_then(|x| settings::get(x, settings::THEME)) {
+            theme.set_current();
+        }
+        if let Some(clicks) = cc.storage.and_then(|x| settings::get(x, settings::CLICKS)) {
+            clicks::load_key(&clicks);
+        }
+
         App {
-            phase: Box::new(StartupPhase::new_with_cc(cc)),
+            tabs: vec![Tab{id: 0, phase: Box::new(StartupPhase::new_with_cc(cc, launch))}],
+            active: 0,
+            next_tab_id: 1,
+            ctx: cc.egui_ctx.clone(),
+            applied_zoom: 1.0,
+            palette: Palette::default(),
+            frame_stats: FrameStats::default(),
+            restored_window: cc.storage.and_then(|x| settings::get(x, settings::WINDOW)),
+        }
+    }
+
+    // Handled here, rather than per phase, so they work everywhere.
+    fn handle_zoom(&mut self, ctx: &egui::Context) {
+        use egui::gui_zoom::kb_shortcuts;
+
+        let zoom = ctx.zoom_factor();
+        if ctx.input_mut(|i| i.consume_shortcut(&kb_shortcuts::ZOOM_RESET)) {
+            set_zoom(ctx, 1.0);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&kb_shortcuts::ZOOM_IN)
+            || i.consume_shortcut(&kb_shortcuts::ZOOM_IN_SECONDARY)) {
+            set_zoom(ctx, zoom + ZOOM_STEP);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&kb_shortcuts::ZOOM_OUT)) {
+            set_zoom(ctx, zoom - ZOOM_STEP);
+        }
+
+        // All layout sizes are in points, so they scale with the zoom on their
+        // own. So does the minimum window size, but only when it's set, so it
+        // has to be re-sent whenever the zoom changes.
+        if zoom != self.applied_zoom {
+            self.applied_zoom = zoom;
+            let (w, h) = MIN_INNER_SIZE;
+            ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(egui::vec2(w, h)));
+        }
+    }
+
+    fn handle_result(&mut self, ret: Result<Option<DynPhase>>) {
+        self.handle_tab_result(self.active, ret);
+    }
+
+    fn handle_tab_result(&mut self, idx: usize, ret: Result<Option<DynPhase>>) {
+        match ret {
+            Ok(Some(next_phase)) => self.tabs[idx].phase = next_phase,
+            Ok(None) => (),
+            Err(err) => err.show_modal(),
+        }
+    }
+
+    // Starts from the stored options, like the first tab did.
+    fn new_tab(&mut self, storage: Option<&dyn eframe::Storage>) {
+        self.tabs.push(Tab{id: self.next_tab_id, phase: Box::new(StartupPhase::new_with_storage(storage))});
+        self.next_tab_id += 1;
+        self.active = self.tabs.len() - 1;
+    }
+
+    fn draw_tabs(&mut self, ctx: &egui::Context, storage: Option<&dyn eframe::Storage>) {
+        let shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::T);
+        let mut new_tab = ctx.input_mut(|i| i.consume_shortcut(&shortcut));
+        let mut close = None;
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (idx, tab) in self.tabs.iter().enumerate() {
+                    if ui.selectable_label(idx == self.active, tab.phase.title()).clicked() {
+                        self.active = idx;
+                    }
+                    if self.tabs.len() > 1 && ui.small_button("×").on_hover_text(tr!("Close tab")).clicked() {
+                        close = Some(idx);
+                    }
+                    ui.separator();
+                }
+                new_tab |= ui.small_button("+").on_hover_text(tr!("New tab")).clicked();
+            });
+        });
+
+        if let Some(idx) = close {
+            self.tabs.remove(idx);
+            if self.active > idx || self.active == self.tabs.len() {
+                self.active -= 1;
+            }
+        }
+        if new_tab {
+            self.new_tab(storage);
         }
     }
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let ret = self.phase.render(ctx, ui);
-            match ret {
-                Ok(Some(next_phase)) => self.phase = next_phase,
-                Ok(None) => (),
-                Err(err) => err.show_modal(),
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.frame_stats.record(std::time::Instant::now());
+        self.handle_zoom(ctx);
+        // Before anything's drawn, so a change (from the settings, or the OS)
+        // restyles this frame.
+        if theme::apply(ctx, frame.info().system_theme) {
+            ctx.request_repaint_of(output_phase::errors_viewport());
+        }
+        if let Some(geometry) = self.restored_window {
+            if let Some(commands) = geometry.fit_to_monitor(ctx) {
+                commands.into_iter().for_each(|x| ctx.send_viewport_cmd(x));
+                self.restored_window = None;
             }
-        });
+        }
+
+        let mut commands = self.tabs[self.active].phase.commands();
+        commands.push(Command::new("new_tab", tr!("New tab")));
+        let entered = self.palette.handle_keys(ctx, &commands);
+        self.draw_tabs(ctx, frame.storage());
+        let tab = &mut self.tabs[self.active];
+        let (id, phase) = (tab.id, &mut tab.phase);
+        let ret = egui::CentralPanel::default()
+            .show(ctx, |ui| ui.push_id(id, |ui| phase.render(ctx, ui)).inner)
+            .inner;
+        self.handle_result(ret);
+        let active = self.active;
+        for idx in (0..self.tabs.len()).filter(|&x| x != active) {
+            let ret = self.tabs[idx].phase.poll(ctx);
+            self.handle_tab_result(idx, ret);
+        }
+
+        // Run after rendering, and not at all if rendering moved on to another
+        // phase, whose commands these aren't.
+        let clicked = self.palette.draw(ctx, &commands);
+        match entered.or(clicked) {
+            Some("new_tab") => self.new_tab(frame.storage()),
+            Some(id) => {
+                let phase = &mut self.tabs[self.active].phase;
+                if phase.commands().iter().any(|x| x.id == id) {
+                    let ret = phase.run_command(ctx, id);
+                    self.handle_result(ret);
+                }
+            },
+            None => (),
+        }
+        self.frame_stats.draw(ctx);
     }
 
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        self.phase.save(storage);
+        // Left as a newer version wrote them; see StartupPhase::new_with_cc().
+        if settings::is_newer(storage) {
+            return;
+        }
+        settings::set(storage, settings::ZOOM, &self.ctx.zoom_factor());
+        settings::set(storage, settings::LANGUAGE, &Language::current());
+        settings::set(storage, settings::UNITS, &SizeUnits::current());
+        settings::set(storage, settings::THEME, &Theme::current());
+        settings::set(storage, settings::CLICKS, &clicks::to_key());
+        if let Some(geometry) = WindowGeometry::capture(&self.ctx) {
+            settings::set(storage, settings::WINDOW, &geometry);
+        }
+        // Only the tab in front, so they don't take turns overwriting each
+        // other's root, or each other's results in the saved session.
+        self.tabs[self.active].phase.save(storage);
     }
 }
 
@@ -116,18 +365,42 @@ fn load_icon() -> Arc<IconData> {
 }
 
 fn main() -> Result<(), eframe::Error> {
+    let mut args = std::env::args_os().skip(1);
+    if args.next().is_some_and(|x| x == headless::FLAG) {
+        std::process::exit(headless::run(args.next()));
+    }
+    let launch = match launch::parse(std::env::args_os().skip(1)) {
+        Ok(launch::Args::Run(x)) => x,
+        Ok(launch::Args::Help) => {
+            println!("{}", launch::USAGE);
+            return Ok(());
+        },
+        Err(e) => {
+            eprintln!("{e}\n\n{}", launch::USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    // Read before there's a window to put where it was. Anything wrong with
+    // what's stored just means the default size.
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_min_inner_size(MIN_INNER_SIZE)
+        .with_icon(load_icon());
+    if let Some(geometry) = settings::get(&StoredSettings::load(), settings::WINDOW) {
+        viewport = geometry.apply(viewport, MIN_INNER_SIZE);
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_min_inner_size(MIN_INNER_SIZE)
-            .with_icon(load_icon()),
+        viewport,
+        persist_window: false, // See WindowGeometry
+        follow_system_theme: true, // Off by default on Linux, which leaves system_theme unknown; see theme::apply()
         ..Default::default()
     };
     eframe::run_native(
-        "Deckard",
+        settings::APP_ID,
         options,
-        Box::new(|cc| {
+        Box::new(move |cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Box::new(App::new(cc))
+            Box::new(App::new(cc, &launch))
         }),
     )
 }
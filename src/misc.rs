@@ -1,14 +1,69 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io::Read;
+use std::sync::{Arc, OnceLock, Mutex, Condvar};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::egui::load::Bytes;
+use crate::encoding::Encoding;
+use crate::content::ContentKind;
+use crate::sources;
+
+use image_hasher::ImageHash;
+
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
 #[derive(Clone)]
 pub struct Image {
     pub path: PathBuf,
-    pub buffer: Bytes,
+    buffer: Arc<OnceLock<Bytes>>, // Shared between clones; see buffer()
     pub file_size: usize, // In bytes
+    pub mtime: Option<SystemTime>, // As of when the image was found
     pub dimm: Option<(u32, u32)>, // Width x height
+    pub file_id: Option<FileId>, // None where unsupported, or if the metadata couldn't be read
+    pub hash: Option<ImageHash>, // The perceptual hash it was grouped by, if known
+    pub changed: bool, // Size or mtime differ from when it was first found (e.g., restored session)
+    pub side: Option<Side>, // Which folder it came from, in compare mode
+    pub frame_count: usize, // More than 1 for animations
+    pub taken: Option<SystemTime>, // EXIF capture time, if it has one
+    pub possible_crop: bool, // Matched by crop detection rather than by hash; needs a look
+    pub encoding: Option<Encoding>, // None if it couldn't be determined
+    pub content: Option<ContentKind>, // Classified while hashing, if it was
+}
+
+// How a file on disk compares to what was recorded when it was found.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Staleness {
+    Current,
+    Changed, // Its size or modification time differs
+    Missing,
+}
+
+// In compare mode, which of the two folders an image is from.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Side {
+    Reference, // Already organized; never acted on in bulk
+    Candidate, // Being checked against the reference
+}
+
+// Identifies the underlying file (rather than the path), so hard links to the
+// same data can be recognized.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FileId {
+    pub dev: u64,
+    pub ino: u64,
+}
+
+impl FileId {
+    #[cfg(unix)]
+    pub fn from_metadata(meta: &std::fs::Metadata) -> Option<FileId> {
+        use std::os::unix::fs::MetadataExt;
+        Some(FileId{ dev: meta.dev(), ino: meta.ino() })
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_metadata(_meta: &std::fs::Metadata) -> Option<FileId> {
+        None
+    }
 }
 
 impl Image {
@@ -16,12 +71,88 @@ impl Image {
         let file_size = buffer.len();
         Image{
             path,
-            buffer: Bytes::from(buffer),
+            buffer: Arc::new(OnceLock::from(Bytes::from(buffer))),
+            file_size,
+            mtime: None,
+            dimm,
+            file_id: None,
+            hash: None,
+            changed: false,
+            side: None,
+            frame_count: 1,
+            taken: None,
+            possible_crop: false,
+            encoding: None,
+            content: None,
+        }
+    }
+
+    // An image whose contents will only be read the first time they're needed
+    // for display.
+    pub fn new_lazy(path: PathBuf, file_size: usize, dimm: Option<(u32, u32)>) -> Image {
+        Image{
+            path,
+            buffer: Arc::new(OnceLock::new()),
             file_size,
+            mtime: None,
             dimm,
+            file_id: None,
+            hash: None,
+            changed: false,
+            side: None,
+            frame_count: 1,
+            taken: None,
+            possible_crop: false,
+            encoding: None,
+            content: None,
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frame_count > 1
+    }
+
+    // Checks the file is still as it was found, e.g. before acting on it,
+    // since something else may have moved or rewritten it since. Mtimes are
+    // compared to the second, since that's all sessions keep. A file whose
+    // metadata can't be read for some other reason counts as current; acting
+    // on it will say what's wrong.
+    pub fn staleness(&self) -> Staleness {
+        let meta = match std::fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Staleness::Missing,
+            Err(_) => return Staleness::Current,
+        };
+        let secs = |x: SystemTime| x.duration_since(UNIX_EPOCH).ok().map(|x| x.as_secs());
+        let mtime_changed = self.mtime.is_some_and(|recorded| {
+            meta.modified().ok().and_then(secs) != secs(recorded)
+        });
+        if meta.len() != self.file_size as u64 || mtime_changed {
+            Staleness::Changed
+        } else {
+            Staleness::Current
         }
     }
 
+    // Its size, unless the file's changed since it was found, since then
+    // the recorded size can't be trusted and nothing else says what it is.
+    pub fn known_size(&self) -> Option<usize> {
+        (!self.changed).then_some(self.file_size)
+    }
+
+    // Just the name, for labels.
+    pub fn file_name(&self) -> String {
+        self.path.file_name().unwrap_or(self.path.as_os_str()).to_string_lossy().into_owned()
+    }
+
+    // The file's contents, for display. Lazily loaded images are read here,
+    // and if that fails, the result is empty (which egui shows as an error).
+    pub fn buffer(&self) -> Bytes {
+        self.buffer.get_or_init(|| {
+            Bytes::from(std::fs::read(&self.path).unwrap_or_default())
+        }).clone()
+    }
+
     pub fn load(path: PathBuf) -> Result<Image, String> {
         // Manually loading the image and passing it as bytes is the only way I
         // could get it to handle URIs with spaces
@@ -34,9 +165,1233 @@ impl Image {
             format!("Error reading {}: {e}", path.display())
         })?;
 
-        let dimm = image::load_from_memory(&buffer).ok().map(|img| {
-            (img.width(), img.height())
-        });
-        Ok(Image::new(path.clone(), buffer, dimm))
+        let dimm = sources::for_file(&path, &buffer).dimensions(&path, &buffer).ok();
+        let taken = capture_time(&buffer);
+        let encoding = Encoding::detect(&buffer);
+        let mut image = Image::new(path.clone(), buffer, dimm);
+        image.taken = taken;
+        image.encoding = encoding;
+        if let Ok(meta) = file.metadata() {
+            image.file_id = FileId::from_metadata(&meta);
+            image.mtime = meta.modified().ok();
+        }
+        Ok(image)
+    }
+}
+
+// Folders the OS manages. Deleting "duplicates" in these breaks things,
+// whatever the hashes say. Both styles are checked on every platform, since
+// e.g. a Windows drive can be mounted elsewhere.
+const UNIX_SYSTEM_DIRS: &[&str] = &[
+    // macOS
+    "/System", "/Library", "/Applications", "/private",
+    // Linux and other Unixes
+    "/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc", "/var", "/opt", "/boot", "/proc", "/sys", "/dev",
+];
+const WINDOWS_SYSTEM_DIRS: &[&str] = &[
+    "/windows", "/program files", "/program files (x86)", "/programdata", // On any drive
+];
+
+// Folders that hold every user's home folder.
+const HOME_PARENTS: &[&str] = &["/Users", "/home", "/users"];
+
+// Slashes forward and no trailing slash. Windows paths lose their drive letter
+// (returned separately) and are lowercased, since they're case insensitive.
+fn normalize(path: &Path) -> (Option<char>, String) {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut chars = path.chars();
+    let (drive, rest) = match (chars.next(), chars.next()) {
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => {
+            (Some(letter.to_ascii_lowercase()), path[2..].to_lowercase())
+        },
+        _ => (None, path),
+    };
+    (drive, rest.trim_end_matches('/').to_string())
+}
+
+// Whether path is dir or inside it, both normalized.
+fn within(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir).is_some_and(|x| x.is_empty() || x.starts_with('/'))
+}
+
+// Whether the path is in a folder the OS manages.
+pub fn is_system_path(path: &Path) -> bool {
+    let (drive, path) = normalize(path);
+    let dirs = if drive.is_some() { WINDOWS_SYSTEM_DIRS } else { UNIX_SYSTEM_DIRS };
+    dirs.iter().any(|x| within(&path, x))
+}
+
+// Whether searching root, and then acting on what's found, could take out
+// files the system or other programs need: a filesystem root, a home folder
+// or anything containing one, or a system folder.
+pub fn is_sensitive_root(root: &Path, home: Option<&Path>) -> bool {
+    let (drive, normalized) = normalize(root);
+    if normalized.is_empty() {
+        return true; // "/" or "C:\"
+    }
+    if let Some(home) = home {
+        let (home_drive, home) = normalize(home);
+        if drive == home_drive && within(&home, &normalized) {
+            return true;
+        }
+    }
+    HOME_PARENTS.iter().any(|x| normalized == *x) || is_system_path(root)
+}
+
+#[cfg(test)]
+lazy_static::lazy_static! {
+    // Shared by every test in the run, and left behind, in the temp dir.
+    static ref TEST_DATA_DIR: tempfile::TempDir = tempfile::tempdir().unwrap();
+}
+
+// The platform's per-user data directory for Deckard (created if needed), for
+// files too big or too structured for eframe storage. Tests get one of their
+// own, so they neither read the user's files nor write over them.
+pub fn data_dir() -> Result<PathBuf, String> {
+    #[cfg(test)]
+    let dir = TEST_DATA_DIR.path().to_path_buf();
+    #[cfg(not(test))]
+    let dir = directories::ProjectDirs::from("com", "lockerman", "Deckard")
+        .ok_or_else(|| "Couldn't determine the data directory".to_string())?
+        .data_dir()
+        .to_path_buf();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Error creating {}: {e}", dir.display()))?;
+    Ok(dir)
+}
+
+// The app's stored settings, read-only, for when eframe isn't running (yet):
+// e.g. the presets, for a headless scan, or the window's size, before it's
+// opened. Has to be the file eframe keeps them in, since it doesn't expose
+// its own reader.
+pub struct StoredSettings(std::collections::HashMap<String, String>);
+
+impl StoredSettings {
+    pub fn load() -> StoredSettings {
+        let kv = eframe::storage_dir(crate::settings::APP_ID)
+            .and_then(|x| std::fs::read_to_string(x.join("app.ron")).ok())
+            .and_then(|x| ron::from_str(&x).ok())
+            .unwrap_or_default();
+        StoredSettings(kv)
+    }
+}
+
+impl eframe::Storage for StoredSettings {
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.0.get(key).cloned()
+    }
+
+    fn set_string(&mut self, _key: &str, _value: String) {}
+
+    fn flush(&mut self) {}
+}
+
+// Bytes free (to the user) on the filesystem holding the path, which needn't
+// exist yet, only some folder above it. None if it can't be told.
+pub fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find_map(|x| x.canonicalize().ok())?;
+    // canonicalize() gives extended-length paths on Windows, which no mount
+    // point is listed as.
+    #[cfg(windows)]
+    let existing = match existing.to_str().and_then(|x| x.strip_prefix(r"\\?\")) {
+        Some(rest) if rest.starts_with(r"UNC\") => PathBuf::from(format!(r"\\{}", &rest[4..])),
+        Some(rest) => PathBuf::from(rest),
+        None => existing,
+    };
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mounts: Vec<&Path> = disks.iter().map(|x| x.mount_point()).collect();
+    let idx = mount_point_of(&existing, &mounts)?;
+    Some(disks.list()[idx].available_space())
+}
+
+// Which of the mount points the path is on: the deepest one it's under.
+// Mounts nest (e.g. a drive mounted at /mnt/photos, on the disk mounted at /),
+// and paths are compared by component, so /mnt/photos2 isn't under
+// /mnt/photos.
+fn mount_point_of(path: &Path, mounts: &[&Path]) -> Option<usize> {
+    mounts.iter()
+        .enumerate()
+        .filter(|(_, mount)| path.starts_with(mount))
+        .max_by_key(|(_, mount)| mount.components().count())
+        .map(|(idx, _)| idx)
+}
+
+// The last component of a folder's path, for labels; the whole path if it
+// doesn't have one (e.g. "/").
+pub fn folder_name(folder: &Path) -> String {
+    folder.file_name()
+        .map_or_else(|| folder.display().to_string(), |x| x.to_string_lossy().to_string())
+}
+
+// The way from base to path, through .. as far up as it has to go. Both
+// should be absolute. None if they have nothing in common, e.g. they're on
+// different drives on Windows.
+pub fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path: Vec<_> = path.components().collect();
+    let base: Vec<_> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return None;
+    }
+    let mut relative: PathBuf = base[common..].iter().map(|_| Path::new("..")).collect();
+    relative.extend(&path[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    Some(relative)
+}
+
+// A folder as pasted from wherever, as a path: without surrounding quotes or
+// whitespace, from a file:// URL, with a leading ~ for home expanded and
+// without trailing separators. On Unix, shell-escaped spaces are unescaped;
+// on Windows, forward slashes become backslashes.
+pub fn normalize_path_input(text: &str, home: Option<&Path>, windows: bool) -> String {
+    let sep = if windows { '\\' } else { '/' };
+    let mut text = text.trim();
+    for quote in ['"', '\''] {
+        if let Some(x) = text.strip_prefix(quote).and_then(|x| x.strip_suffix(quote)) {
+            text = x.trim();
+        }
+    }
+
+    let mut path = match text.get(..7).filter(|x| x.eq_ignore_ascii_case("file://")) {
+        Some(_) => {
+            let rest = &text[7..];
+            let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            let path = percent_decode(path);
+            let local = host.is_empty() || host.eq_ignore_ascii_case("localhost");
+            match (windows, local) {
+                // file:///C:/Users/...
+                (true, true) => path.strip_prefix('/').unwrap_or(&path).to_string(),
+                (_, false) => format!("//{host}{path}"),
+                (false, true) => path,
+            }
+        },
+        None if !windows => text.replace("\\ ", " "),
+        None => text.to_string(),
+    };
+
+    if windows {
+        path = path.replace('/', "\\");
+    }
+    if let Some(home) = home {
+        let rest = path.strip_prefix('~').filter(|x| x.is_empty() || x.starts_with(sep));
+        if let Some(rest) = rest {
+            path = format!("{}{rest}", home.display().to_string().trim_end_matches(sep));
+        }
+    }
+
+    // Down to the root, but not past it: / on Unix, C:\ or \ on Windows.
+    let is_root = |x: &str| match windows {
+        true => x == "\\" || (x.len() == 3 && x.ends_with(":\\")),
+        false => x == "/",
+    };
+    while path.ends_with(sep) && !is_root(&path) {
+        path.pop();
+    }
+    path
+}
+
+// For this platform, with the user's home.
+pub fn normalize_path(text: &str) -> PathBuf {
+    let home = homedir::get_my_home().ok().flatten();
+    normalize_path_input(text, home.as_deref(), cfg!(windows)).into()
+}
+
+// Decodes %XX escapes, e.g. from a URL. Invalid ones are left as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = bytes.get(idx + 1..idx + 3)
+            .filter(|x| x.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .and_then(|x| u8::from_str_radix(x, 16).ok());
+        match (bytes[idx], hex) {
+            (b'%', Some(x)) => {
+                out.push(x);
+                idx += 3;
+            },
+            (x, _) => {
+                out.push(x);
+                idx += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Writes the file by way of a temporary file and a rename, so readers (or a
+// later launch, if the app is killed mid-write) never see it half written.
+pub fn write_atomic(path: &std::path::Path, contents: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("Error writing {}: {e}", path.display()))
+}
+
+// One of the lists kept as JSON in the data dir (the ignore list and the
+// like), with a format version so an older Deckard won't misread a newer one's.
+pub struct VersionedFile {
+    pub file: &'static str,
+    pub name: &'static str, // For errors, e.g. "ignore list"
+    pub version: u32,
+}
+
+impl VersionedFile {
+    // A missing file is just an empty list. One that can't be parsed, or is
+    // from a newer version, is an error.
+    pub fn load<T: DeserializeOwned + Default>(&self) -> Result<T, String> {
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+
+        let path = data_dir()?.join(self.file);
+        let json = match std::fs::read(&path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+            Err(e) => return Err(format!("Error reading {}: {e}", path.display())),
+        };
+        let parse_error = |e| format!("Error parsing {}: {e}", path.display());
+        let Versioned{version} = serde_json::from_slice(&json).map_err(parse_error)?;
+        if version > self.version {
+            return Err(format!("The {} was saved by a newer version of Deckard (format {version})", self.name));
+        }
+        serde_json::from_slice(&json).map_err(parse_error)
+    }
+
+    // Refuses to write over a file load() can't read, since the caller has
+    // only had an empty list to add to, and what was in it would be lost.
+    pub fn save<T: Serialize + DeserializeOwned + Default>(&self, list: &T) -> Result<(), String> {
+        self.load::<T>()?;
+        let json = serde_json::to_vec(list)
+            .map_err(|e| format!("Error serializing {}: {e}", self.name))?;
+        write_atomic(&data_dir()?.join(self.file), &json)
+    }
+}
+
+// Bytes that would be freed by keeping only the largest member of the group.
+// Hard links to the same file only count once, since removing one frees
+// nothing.
+pub fn wasted_bytes(group: &[Image]) -> usize {
+    let mut seen = std::collections::HashSet::new();
+    let sizes: Vec<usize> = group.iter()
+        .filter(|x| x.file_id.is_none_or(|id| seen.insert(id)))
+        .map(|x| x.file_size)
+        .collect();
+    sizes.iter().sum::<usize>() - sizes.iter().max().unwrap_or(&0)
+}
+
+// The members whose bytes wasted_bytes() counts: every distinct file but the
+// largest, the one that would be kept.
+pub fn wasted_members(group: &[Image]) -> Vec<&Image> {
+    let mut seen = std::collections::HashSet::new();
+    let unique: Vec<&Image> = group.iter()
+        .filter(|x| x.file_id.is_none_or(|id| seen.insert(id)))
+        .collect();
+    let Some(keep) = unique.iter().map(|x| x.file_size).enumerate().max_by_key(|(_, x)| *x) else {
+        return vec![];
+    };
+    unique.into_iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != keep.0)
+        .map(|(_, x)| x)
+        .collect()
+}
+
+// What a group is worth acting on, for its header. A member of unknown size
+// leaves the sizes unknown, rather than wrong, along with which member is the
+// largest, and so what removing any of them would free.
+#[derive(Clone, Debug, Default)]
+pub struct GroupSizes {
+    pub total: Option<usize>, // Every distinct file's bytes
+    pub wasted: Option<usize>, // As in wasted_bytes()
+    pub gains: Vec<Option<usize>>, // Parallel to the group; bytes removing each would free, but for the largest
+}
+
+pub fn group_sizes(group: &[Image]) -> GroupSizes {
+    let sizes: Option<Vec<usize>> = group.iter().map(Image::known_size).collect();
+    let Some(sizes) = sizes else {
+        return GroupSizes{total: None, wasted: None, gains: vec![None; group.len()]};
+    };
+    let wasted = wasted_bytes(group);
+    let wasted_paths: std::collections::HashSet<&Path> = wasted_members(group).iter().map(|x| x.path.as_path()).collect();
+    // A file linked to from elsewhere in the group isn't freed by removing
+    // one of its paths.
+    let mut links = std::collections::HashMap::new();
+    for id in group.iter().filter_map(|x| x.file_id) {
+        *links.entry(id).or_insert(0) += 1;
+    }
+    let gains = group.iter()
+        .zip(sizes)
+        .map(|(image, size)| {
+            let linked = image.file_id.is_some_and(|id| links[&id] > 1);
+            (wasted_paths.contains(image.path.as_path()) && !linked).then_some(size)
+        })
+        .collect();
+    GroupSizes{total: Some(wasted + group.iter().map(|x| x.file_size).max().unwrap_or(0)), wasted: Some(wasted), gains}
+}
+
+// A name for the group that's the same from one search to the next, for
+// telling groups apart in exports and scripts, where its position in the
+// results means nothing. It's a digest of the members' paths, so it stays the
+// same as long as exactly the same files are in the group, in whatever order
+// they were found; a file joining or leaving the group, or being renamed or
+// moved, gives it a new one.
+pub fn group_id(group: &[Image]) -> String {
+    // FNV-1a, since std's hashers are seeded differently on every run.
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut paths: Vec<String> = group.iter().map(|x| crate::os_path::encode(&x.path)).collect();
+    paths.sort();
+    let mut hash = OFFSET;
+    for path in &paths {
+        // Each followed by a NUL, which paths can't contain.
+        for byte in path.bytes().chain([0]) {
+            hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+        }
+    }
+    format!("{:012x}", hash >> 16)
+}
+
+// True if at least two members of the group are the same underlying file.
+pub fn has_hard_links(group: &[Image]) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    group.iter().filter_map(|x| x.file_id).any(|id| !seen.insert(id))
+}
+
+// The EXIF DateTimeOriginal, if the file has one. It has no time zone, so it's
+// taken as UTC; that's only good for comparing photos from the same camera,
+// which is all it's used for.
+pub fn capture_time(buffer: &[u8]) -> Option<SystemTime> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(buffer))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(ref values) = field.value else {
+        return None;
+    };
+    let time = exif::DateTime::from_ascii(values.first()?).ok()?;
+    let secs = time.hour as u64 * 3600 + time.minute as u64 * 60 + time.second as u64;
+    Some(date_to_time(time.year as i64, time.month as i64, time.day as i64)? + Duration::from_secs(secs))
+}
+
+// Midnight UTC at the start of a proleptic Gregorian date. None before 1970.
+fn date_to_time(year: i64, month: i64, day: i64) -> Option<SystemTime> {
+    // Days since the epoch, per Howard Hinnant's days_from_civil().
+    let year = year - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    Some(UNIX_EPOCH + Duration::from_secs((days * 86400).try_into().ok()?))
+}
+
+// Midnight UTC at the start of an ISO date, e.g. "2024-05-31".
+pub fn parse_date(text: &str) -> Option<SystemTime> {
+    let mut parts = text.trim().splitn(3, '-').map(|x| x.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    date_to_time(year, month, day)
+}
+
+// e.g. "4m 12s"; to a tenth of a second under a minute.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{:.1}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+// As "YYYY-MM-DD HH:MM:SS UTC". Without a time zone database, UTC is the only
+// zone that can be done right.
+pub fn format_time(time: SystemTime) -> String {
+    let Ok(since) = time.duration_since(UNIX_EPOCH) else {
+        return "before 1970".to_string();
+    };
+    let secs = since.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Howard Hinnant's civil_from_days(); the inverse of capture_time()'s.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+    )
+}
+
+// Whether the group looks like a burst of shots rather than true duplicates:
+// every member in the same folder, each taken within the window of the one
+// before. Capture times are used if every member has one, otherwise mtimes,
+// since the two aren't comparable.
+pub fn is_burst(group: &[Image], window: Duration) -> bool {
+    let Some(parent) = group.first().and_then(|x| x.path.parent()) else {
+        return false;
+    };
+    if group.len() < 2 || group.iter().any(|x| x.path.parent() != Some(parent)) {
+        return false;
+    }
+
+    let times: Option<Vec<SystemTime>> = if group.iter().all(|x| x.taken.is_some()) {
+        group.iter().map(|x| x.taken).collect()
+    } else {
+        group.iter().map(|x| x.mtime).collect()
+    };
+    let Some(mut times) = times else {
+        return false;
+    };
+    times.sort();
+    times.windows(2).all(|x| x[1].duration_since(x[0]).is_ok_and(|gap| gap <= window))
+}
+
+// The deepest folder holding every member, however deep within it.
+pub fn common_folder(group: &[Image]) -> Option<PathBuf> {
+    let mut folder = group.first()?.path.parent()?;
+    while !group.iter().all(|x| x.path.starts_with(folder)) {
+        folder = folder.parent()?;
+    }
+    Some(folder.to_path_buf())
+}
+
+// Whether every member of the group is in the same folder.
+pub fn same_folder(group: &[Image]) -> bool {
+    let parent = group.first().and_then(|x| x.path.parent());
+    group.iter().all(|x| x.path.parent() == parent)
+}
+
+// The name a file would have had if it weren't a copy Finder, Explorer or a
+// browser renamed to avoid a clash: "IMG_1234" for "IMG_1234 (1)",
+// "IMG_1234 copy 2", "IMG_1234 - Copy" or "IMG_1234 Kopie". None if the stem
+// doesn't look renamed.
+pub fn copy_base(stem: &str) -> Option<&str> {
+    fn strip_number(s: &str, parens: bool) -> Option<&str> {
+        let (base, number) = if parens {
+            s.strip_suffix(')')?.rsplit_once(" (")?
+        } else {
+            s.rsplit_once(' ')?
+        };
+        (!number.is_empty() && number.bytes().all(|x| x.is_ascii_digit())).then_some(base)
+    }
+    fn strip_copy(s: &str) -> Option<&str> {
+        ["copy", "kopie"].into_iter().find_map(|word| {
+            let split = s.len().checked_sub(word.len() + 1)?;
+            let (base, suffix) = (s.get(..split)?, s.get(split..)?);
+            let matches = suffix.starts_with(' ') && suffix[1..].eq_ignore_ascii_case(word);
+            matches.then(|| base.strip_suffix(" -").unwrap_or(base))
+        })
+    }
+
+    let base = match strip_number(stem, true) {
+        Some(base) => strip_copy(base).unwrap_or(base),
+        None => strip_number(stem, false).and_then(strip_copy).or_else(|| strip_copy(stem))?,
+    };
+    (!base.is_empty()).then_some(base)
+}
+
+// Parallel to the group; whether each member looks like a renamed copy (see
+// copy_base()) of another member in the same folder.
+pub fn renamed_copies(group: &[Image]) -> Vec<bool> {
+    group.iter()
+        .map(|image| {
+            let Some(base) = image.path.file_stem().and_then(|x| x.to_str()).and_then(copy_base) else {
+                return false;
+            };
+            group.iter().any(|other| {
+                other.path.parent() == image.path.parent()
+                    && other.path.file_stem().and_then(|x| x.to_str()) == Some(base)
+            })
+        })
+        .collect()
+}
+
+// Parallel to the group; whether each member is an intentional derivative of
+// another member in the same folder: named the same, ignoring case and the
+// extension, but for one of the patterns inserted somewhere, e.g. "foo_web.jpg"
+// for "foo.JPG" with the pattern "_web". Patterns should be lowercase.
+pub fn derivative_members(group: &[Image], patterns: &[String]) -> Vec<bool> {
+    let stems: Vec<Option<String>> = group.iter()
+        .map(|x| x.path.file_stem().map(|x| x.to_string_lossy().to_lowercase()))
+        .collect();
+    // Looked up rather than compared pairwise, for groups of thousands.
+    let known: std::collections::HashSet<(Option<&Path>, &str)> = group.iter().zip(&stems)
+        .filter_map(|(image, stem)| Some((image.path.parent(), stem.as_deref()?)))
+        .collect();
+    group.iter().zip(&stems)
+        .map(|(image, stem)| {
+            let Some(stem) = stem else {
+                return false;
+            };
+            patterns.iter().any(|pattern| {
+                stem.match_indices(pattern.as_str()).any(|(idx, _)| {
+                    let original = format!("{}{}", &stem[..idx], &stem[idx + pattern.len()..]);
+                    known.contains(&(image.path.parent(), original.as_str()))
+                })
+            })
+        })
+        .collect()
+}
+
+// Whether file is a sidecar of image: next to it, with one of the extensions
+// (lowercase, without the dot), and named either for the whole file, e.g.
+// "IMG_1234.jpg.xmp", or just its stem, e.g. "IMG_1234.xmp". Names are
+// compared ignoring case, since cameras and editors disagree.
+pub fn is_sidecar_of(image: &Path, file: &Path, exts: &[String]) -> bool {
+    let lower = |x: Option<&std::ffi::OsStr>| x.map(|x| x.to_string_lossy().to_lowercase());
+    let Some(ext) = lower(file.extension()) else {
+        return false;
+    };
+    let stem = lower(file.file_stem());
+    file != image
+        && file.parent() == image.parent()
+        && exts.contains(&ext)
+        && (stem == lower(image.file_name()) || stem == lower(image.file_stem()))
+}
+
+// The image's sidecars (see is_sidecar_of()), each with the other files in the
+// folder it could belong to just as well, e.g. the RAW of a RAW+JPEG pair for
+// "IMG_1234.xmp".
+pub fn find_sidecars(image: &Path, exts: &[String]) -> Vec<(PathBuf, Vec<PathBuf>)> {
+    let Some(entries) = image.parent().and_then(|x| std::fs::read_dir(x).ok()) else {
+        return vec![];
+    };
+    let files: Vec<PathBuf> = entries.filter_map(|x| Some(x.ok()?.path())).collect();
+    files.iter()
+        .filter(|x| is_sidecar_of(image, x, exts))
+        .map(|sidecar| {
+            // Other sidecars, e.g. "IMG_1234.aae" for "IMG_1234.xmp", don't count.
+            let sharers = files.iter()
+                .filter(|x| *x != image && is_sidecar_of(x, sidecar, exts))
+                .filter(|x| !x.extension().is_some_and(|x| exts.contains(&x.to_string_lossy().to_lowercase())))
+                .cloned()
+                .collect();
+            (sidecar.clone(), sharers)
+        })
+        .collect()
+}
+
+// Moves the pick off a renamed copy, onto a member with the original name
+// that's otherwise the same: dimensions, size and encoding.
+pub fn prefer_original(group: &[Image], pick: usize, renamed: &[bool]) -> usize {
+    if !renamed[pick] {
+        return pick;
+    }
+    let picked = &group[pick];
+    (0..group.len())
+        .find(|idx| {
+            let x = &group[*idx];
+            !renamed[*idx] && x.dimm == picked.dimm && x.file_size == picked.file_size && x.encoding == picked.encoding
+        })
+        .unwrap_or(pick)
+}
+
+// Each member's hash distance from the group's medoid, the member closest to
+// all the others. None for members whose hash isn't known.
+//
+// Members are compared by distinct hash, of which a big group of near-identical
+// images has far fewer than members. With more than MEDOID_CANDIDATES of
+// those, only the first so many are considered for the medoid, so a group of
+// thousands doesn't take billions of comparisons.
+pub fn hash_distances(group: &[Image]) -> Vec<Option<u32>> {
+    const MEDOID_CANDIDATES: usize = 64;
+
+    let mut distinct: Vec<(&ImageHash, u32)> = vec![]; // With how many members have it, in order of first appearance
+    let mut seen: std::collections::HashMap<&ImageHash, usize> = std::collections::HashMap::new();
+    for hash in group.iter().filter_map(|x| x.hash.as_ref()) {
+        match seen.get(hash) {
+            Some(idx) => distinct[*idx].1 += 1,
+            None => {
+                seen.insert(hash, distinct.len());
+                distinct.push((hash, 1));
+            },
+        }
+    }
+    let total = |hash: &ImageHash| -> u32 {
+        distinct.iter().map(|(x, count)| x.dist(hash) * count).sum()
+    };
+    let medoid = distinct.iter()
+        .take(MEDOID_CANDIDATES)
+        .map(|(x, _)| *x)
+        .min_by_key(|x| total(x));
+    group.iter()
+        .map(|x| Some(x.hash.as_ref()?.dist(medoid?)))
+        .collect()
+}
+
+// Whether a group is the same picture at different resolutions, e.g. an
+// original and a copy downscaled for email.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Variants {
+    No,
+    Largest(usize), // They are; the member to keep
+    Unknown, // They might be, but some member's dimensions aren't known
+}
+
+// How far apart members' aspect ratios can be and still count as the same
+// picture, allowing for rounding when downscaling.
+const ASPECT_TOLERANCE: f64 = 0.01;
+
+// Whether the group is resolution variants: every member the same shape, and
+// one bigger than each of the others by at least the factor, on each side.
+// A group is only Unknown if the members whose dimensions are known don't
+// already rule it out.
+pub fn resolution_variants(group: &[Image], factor: f32) -> Variants {
+    if group.len() < 2 {
+        return Variants::No;
+    }
+    let known: Vec<(usize, (u32, u32))> = group.iter()
+        .enumerate()
+        .filter_map(|(idx, x)| Some((idx, x.dimm?)))
+        .filter(|(_, (w, h))| *w > 0 && *h > 0)
+        .collect();
+    let aspect = |(w, h): (u32, u32)| w as f64 / h as f64;
+    let Some(&(largest, size)) = known.iter().max_by_key(|(_, (w, h))| *w as u64 * *h as u64) else {
+        return Variants::Unknown;
+    };
+    let variant = |(w, h): (u32, u32)| {
+        (aspect((w, h)) / aspect(size) - 1.0).abs() <= ASPECT_TOLERANCE
+            && w as f64 * factor as f64 <= size.0 as f64
+            && h as f64 * factor as f64 <= size.1 as f64
+    };
+    if !known.iter().all(|(idx, x)| *idx == largest || variant(*x)) {
+        return Variants::No;
+    }
+    if known.len() < group.len() {
+        return Variants::Unknown;
+    }
+    Variants::Largest(largest)
+}
+
+// Closest matches first, keeping the order otherwise.
+pub fn sort_by_distance(group: &mut Vec<Image>) {
+    let distances = hash_distances(group);
+    let mut members: Vec<_> = std::mem::take(group).into_iter().zip(distances).collect();
+    members.sort_by_key(|(_, distance)| distance.unwrap_or(u32::MAX));
+    *group = members.into_iter().map(|(x, _)| x).collect();
+}
+
+// Earliest first, by the same times is_burst() goes by.
+pub fn sort_burst(group: &mut [Image]) {
+    if group.iter().all(|x| x.taken.is_some()) {
+        group.sort_by_key(|x| x.taken);
+    } else {
+        group.sort_by_key(|x| x.mtime);
+    }
+}
+
+// A minimal counting semaphore; std doesn't have one.
+pub struct Semaphore {
+    count: Mutex<usize>,
+    capacity: usize,
+    cvar: Condvar,
+}
+
+pub struct SemaphoreGuard<'a> {
+    sem: &'a Semaphore,
+    weight: usize,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Semaphore {
+        Semaphore{ count: Mutex::new(count), capacity: count, cvar: Condvar::new() }
+    }
+
+    // Blocks until a permit is available; it's released when the guard drops.
+    pub fn acquire(&self) -> SemaphoreGuard<'_> {
+        self.acquire_weighted(1)
+    }
+
+    // Blocks until this many permits are available, and takes them all at
+    // once. Asking for more than there are waits for all of them, rather than
+    // forever.
+    pub fn acquire_weighted(&self, weight: usize) -> SemaphoreGuard<'_> {
+        let weight = weight.min(self.capacity);
+        let mut count = self.cvar
+            .wait_while(self.count.lock().unwrap(), |count| *count < weight)
+            .unwrap();
+        *count -= weight;
+        SemaphoreGuard{ sem: self, weight }
+    }
+}
+
+impl Drop for SemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        *self.sem.count.lock().unwrap() += self.weight;
+        // Waiters want different numbers of permits, so any of them might
+        // now have enough.
+        self.sem.cvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pasted_paths_are_normalized() {
+        let unix = [
+            ("/Users/alice/Pictures", "/Users/alice/Pictures"),
+            ("  '/Users/alice/My Pictures'\n", "/Users/alice/My Pictures"),
+            ("\"/Users/alice/Pictures/\"", "/Users/alice/Pictures"),
+            ("/Users/alice/My\\ Pictures", "/Users/alice/My Pictures"),
+            ("file:///Users/alice/My%20Pictures/", "/Users/alice/My Pictures"),
+            ("FILE://localhost/Users/alice/Fotos%C3%A9", "/Users/alice/Fotosé"),
+            ("file:///tmp/100%", "/tmp/100%"),
+            ("~/Pictures", "/Users/alice/Pictures"),
+            ("~", "/Users/alice"),
+            ("~bob/Pictures", "~bob/Pictures"),
+            ("/", "/"),
+            ("///", "/"),
+        ];
+        for (input, expected) in unix {
+            assert_eq!(normalize_path_input(input, Some(Path::new("/Users/alice/")), false), expected, "{input:?}");
+        }
+
+        let windows = [
+            (r"C:\Users\alice\Pictures\", r"C:\Users\alice\Pictures"),
+            (r#""C:\Users\alice\My Pictures""#, r"C:\Users\alice\My Pictures"),
+            ("C:/Users/alice/Pictures/", r"C:\Users\alice\Pictures"),
+            ("file:///C:/Users/alice/My%20Pictures", r"C:\Users\alice\My Pictures"),
+            ("file://nas/photos/2023", r"\\nas\photos\2023"),
+            (r"~\Pictures", r"C:\Users\alice\Pictures"),
+            ("~/Pictures", r"C:\Users\alice\Pictures"),
+            (r"C:\", r"C:\"),
+            (r"\\nas\photos\", r"\\nas\photos"),
+        ];
+        for (input, expected) in windows {
+            assert_eq!(normalize_path_input(input, Some(Path::new(r"C:\Users\alice")), true), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn unix_sensitive_roots() {
+        let home = Some(Path::new("/home/alice"));
+        assert!(is_sensitive_root(Path::new("/"), home));
+        assert!(is_sensitive_root(Path::new("/home"), home));
+        assert!(is_sensitive_root(Path::new("/home/alice"), home));
+        assert!(is_sensitive_root(Path::new("/home/alice/"), home));
+        assert!(is_sensitive_root(Path::new("/usr/share"), home));
+        assert!(!is_sensitive_root(Path::new("/home/alice/Pictures"), home));
+        assert!(!is_sensitive_root(Path::new("/mnt/photos"), home));
+        assert!(!is_sensitive_root(Path::new("/usrdata"), home));
+    }
+
+    #[test]
+    fn macos_sensitive_roots() {
+        let home = Some(Path::new("/Users/alice"));
+        assert!(is_sensitive_root(Path::new("/Users"), home));
+        assert!(is_sensitive_root(Path::new("/Users/alice"), home));
+        assert!(is_sensitive_root(Path::new("/System/Library"), home));
+        assert!(!is_sensitive_root(Path::new("/Users/alice/Pictures"), home));
+        assert!(!is_sensitive_root(Path::new("/Volumes/Backup"), home));
+    }
+
+    #[test]
+    fn windows_sensitive_roots() {
+        let home = Some(Path::new(r"C:\Users\alice"));
+        assert!(is_sensitive_root(Path::new(r"C:\"), home));
+        assert!(is_sensitive_root(Path::new("D:"), home));
+        assert!(is_sensitive_root(Path::new(r"C:\Users"), home));
+        assert!(is_sensitive_root(Path::new(r"c:\users\ALICE"), home));
+        assert!(is_sensitive_root(Path::new(r"C:\Windows\System32"), home));
+        assert!(!is_sensitive_root(Path::new(r"C:\Users\alice\Pictures"), home));
+        assert!(!is_sensitive_root(Path::new(r"D:\Users\alice"), home));
+        assert!(!is_sensitive_root(Path::new(r"D:\Photos"), home));
+    }
+
+    #[test]
+    fn renamed_copies_are_recognized() {
+        for (stem, base) in [
+            ("IMG_1234 (1)", Some("IMG_1234")),
+            ("IMG_1234 copy", Some("IMG_1234")),
+            ("IMG_1234 copy 2", Some("IMG_1234")),
+            ("IMG_1234 - Copy", Some("IMG_1234")),
+            ("IMG_1234 - Copy (3)", Some("IMG_1234")),
+            ("Urlaub Kopie", Some("Urlaub")),
+            ("Urlaub - Kopie (2)", Some("Urlaub")),
+            ("Größe Kopie", Some("Größe")),
+            ("IMG_1234", None),
+            ("IMG_1234 2", None),
+            ("IMG_1234 (a)", None),
+            ("photocopy", None),
+            ("copy", None),
+            (" (1)", None),
+        ] {
+            assert_eq!(copy_base(stem), base, "{stem}");
+        }
+
+        let group: Vec<Image> = ["/a/IMG_1.jpg", "/a/IMG_1 (1).jpg", "/b/IMG_1 copy.jpg", "/a/IMG_2 (1).jpg"]
+            .into_iter()
+            .map(|x| Image::new(PathBuf::from(x), vec![0; 10], Some((4, 3))))
+            .collect();
+        let renamed = renamed_copies(&group);
+        assert_eq!(renamed, [false, true, false, false]);
+        assert!(!same_folder(&group));
+        assert!(same_folder(&group[..2]));
+        assert_eq!(common_folder(&group), Some(PathBuf::from("/")));
+        assert_eq!(common_folder(&group[..2]), Some(PathBuf::from("/a")));
+        assert_eq!(prefer_original(&group, 1, &renamed), 0);
+        assert_eq!(prefer_original(&group, 2, &renamed), 2);
+    }
+
+    #[test]
+    fn sidecars_are_recognized() {
+        let exts = ["xmp".to_string(), "aae".to_string(), "dop".to_string()];
+        let image = Path::new("/a/IMG_1234.jpg");
+        assert!(is_sidecar_of(image, Path::new("/a/IMG_1234.xmp"), &exts));
+        assert!(is_sidecar_of(image, Path::new("/a/IMG_1234.jpg.xmp"), &exts));
+        assert!(is_sidecar_of(image, Path::new("/a/img_1234.JPG.DOP"), &exts));
+        assert!(is_sidecar_of(image, Path::new("/a/IMG_1234.AAE"), &exts));
+        assert!(!is_sidecar_of(image, Path::new("/b/IMG_1234.xmp"), &exts));
+        assert!(!is_sidecar_of(image, Path::new("/a/IMG_12345.xmp"), &exts));
+        assert!(!is_sidecar_of(image, Path::new("/a/IMG_1234.png.xmp"), &exts));
+        assert!(!is_sidecar_of(image, Path::new("/a/IMG_1234.txt"), &exts));
+        assert!(!is_sidecar_of(image, Path::new("/a/IMG_1234"), &exts));
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["IMG_1.jpg", "IMG_1.cr2", "IMG_1.xmp", "IMG_1.aae", "IMG_1.jpg.dop", "IMG_1.jpg.xmp", "IMG_2.xmp"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let mut found = find_sidecars(&dir.path().join("IMG_1.jpg"), &exts);
+        found.sort();
+        let names = |x: &[PathBuf]| x.iter().map(|x| x.file_name().unwrap().to_str().unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(found.iter().map(|x| x.0.clone()).collect::<Vec<_>>(), [
+            dir.path().join("IMG_1.aae"),
+            dir.path().join("IMG_1.jpg.dop"),
+            dir.path().join("IMG_1.jpg.xmp"),
+            dir.path().join("IMG_1.xmp"),
+        ]);
+        assert!(found[1].1.is_empty() && found[2].1.is_empty());
+        assert_eq!(names(&found[0].1), ["IMG_1.cr2"]);
+        assert_eq!(names(&found[3].1), ["IMG_1.cr2"]);
+    }
+
+    #[test]
+    fn derivatives_are_recognized() {
+        let patterns = ["_web".to_string(), "-edited".to_string()];
+        let group = |names: &[&str]| -> Vec<Image> {
+            names.iter().map(|x| Image::new(PathBuf::from(x), vec![0; 10], Some((4, 3)))).collect()
+        };
+        assert_eq!(derivative_members(&group(&["/a/foo.JPG", "/a/Foo_Web.jpg"]), &patterns), [false, true]);
+        assert_eq!(derivative_members(&group(&["/a/foo.jpg", "/a/foo-edited_web.png"]), &patterns), [false, false]);
+        assert_eq!(derivative_members(&group(&["/a/foo_2.jpg", "/a/foo-edited_2.jpg"]), &patterns), [false, true]);
+        assert_eq!(derivative_members(&group(&["/a/foo.jpg", "/b/foo_web.jpg"]), &patterns), [false, false]);
+        assert_eq!(derivative_members(&group(&["/a/foo.jpg", "/a/foo_web.jpg", "/a/bar.jpg"]), &patterns), [false, true, false]);
+        assert_eq!(derivative_members(&group(&["/a/web.jpg", "/a/_web.jpg"]), &patterns), [false, false]);
+
+        // Thousands of members, only some with originals beside them
+        let names: Vec<String> = (0..3000)
+            .map(|x| match x % 3 {
+                0 => format!("/a/{x}.jpg"),
+                1 => format!("/a/{}_web.jpg", x - 1),
+                _ => format!("/b/{}-edited.jpg", x - 2),
+            })
+            .collect();
+        let derivatives = derivative_members(&group(&names.iter().map(String::as_str).collect::<Vec<_>>()), &patterns);
+        assert!(derivatives.iter().enumerate().all(|(idx, x)| *x == (idx % 3 == 1)));
+    }
+
+    // A bare TIFF with just the capture time, which is all the EXIF reader
+    // needs.
+    fn exif(taken: &str) -> Vec<u8> {
+        let field = exif::Field {
+            tag: exif::Tag::DateTimeOriginal,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Ascii(vec![taken.as_bytes().to_vec()]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&field);
+        let mut tiff = std::io::Cursor::new(vec![]);
+        writer.write(&mut tiff, false).unwrap();
+        tiff.into_inner()
+    }
+
+    #[test]
+    fn capture_times_come_from_exif() {
+        let taken = capture_time(&exif("2021:06:15 13:45:30")).unwrap();
+        assert_eq!(format_time(taken), "2021-06-15 13:45:30 UTC");
+        assert_eq!(format_time(capture_time(&exif("2024:02:29 00:00:00")).unwrap()), "2024-02-29 00:00:00 UTC");
+
+        assert_eq!(capture_time(&exif("1969:12:31 23:59:59")), None);
+        assert_eq!(capture_time(&exif("sometime")), None);
+        assert_eq!(capture_time(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(capture_time(b""), None);
+    }
+
+    #[test]
+    fn bursts_are_close_together_in_one_folder() {
+        let window = Duration::from_secs(3);
+        let at = |secs: u64| Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000 + secs));
+        let image = |path: &str, mtime: Option<SystemTime>, taken: Option<SystemTime>| {
+            let mut image = Image::new_lazy(PathBuf::from(path), 1, None);
+            (image.mtime, image.taken) = (mtime, taken);
+            image
+        };
+
+        // Each within the window of the one before, in any order, even if the
+        // first and last aren't
+        let burst = [image("/a/3.jpg", at(6), None), image("/a/1.jpg", at(0), None), image("/a/2.jpg", at(3), None)];
+        assert!(is_burst(&burst, window));
+        assert!(!is_burst(&[image("/a/1.jpg", at(0), None), image("/a/2.jpg", at(4), None)], window));
+        assert!(!is_burst(&burst[..1], window));
+        assert!(!is_burst(&[], window));
+        assert!(!is_burst(&[image("/a/1.jpg", at(0), None), image("/b/1.jpg", at(0), None)], window));
+        assert!(!is_burst(&[image("/a/1.jpg", at(0), None), image("/a/2.jpg", None, None)], window));
+
+        // Capture times if every member has one, else mtimes
+        let copied = [image("/a/1.jpg", at(0), at(1000)), image("/a/2.jpg", at(1), at(2000))];
+        assert!(!is_burst(&copied, window));
+        let partly = [image("/a/1.jpg", at(0), at(1000)), image("/a/2.jpg", at(1), None)];
+        assert!(is_burst(&partly, window));
+    }
+
+    #[test]
+    fn distances_are_from_the_medoid() {
+        let image = |hash: Option<u64>| {
+            let mut image = Image::new_lazy(PathBuf::from("/a/x.jpg"), 1, None);
+            image.hash = hash.map(|x| ImageHash::from_bytes(&x.to_le_bytes()).unwrap());
+            image
+        };
+        let group: Vec<Image> = [Some(0b00), Some(0b01), Some(0b11), None, Some(0b00)].into_iter().map(image).collect();
+        assert_eq!(hash_distances(&group), [Some(0), Some(1), Some(2), None, Some(0)]);
+        assert_eq!(hash_distances(&[image(None), image(None)]), [None, None]);
+
+        // Most of the group has the one hash, but only the first so many
+        // distinct hashes are candidates.
+        let group = |outliers: u64| -> Vec<Image> {
+            (1..=outliers).map(Some)
+                .chain(std::iter::repeat_n(Some(u64::MAX), 1000))
+                .map(image)
+                .collect()
+        };
+        assert_eq!(hash_distances(&group(63)).last(), Some(&Some(0)));
+        assert!(hash_distances(&group(64)).last().unwrap().unwrap() > 0);
+    }
+
+    #[test]
+    fn groups_are_sorted_closest_first() {
+        let image = |name: &str, hash: Option<u64>| {
+            let mut image = Image::new_lazy(PathBuf::from(format!("/a/{name}.jpg")), 1, None);
+            image.hash = hash.map(|x| ImageHash::from_bytes(&x.to_le_bytes()).unwrap());
+            image
+        };
+        let mut group = vec![
+            image("far", Some(0b111)),
+            image("unknown", None),
+            image("near", Some(0b001)),
+            image("first", Some(0)),
+            image("second", Some(0)),
+            image("third", Some(0)),
+        ];
+        sort_by_distance(&mut group);
+        let names: Vec<&str> = group.iter().map(|x| x.path.file_stem().unwrap().to_str().unwrap()).collect();
+        // Ties keep their order, and unknown hashes go last.
+        assert_eq!(names, ["first", "second", "third", "near", "far", "unknown"]);
+    }
+
+    #[test]
+    fn resolution_variants_keep_the_largest() {
+        let group = |dimms: &[Option<(u32, u32)>]| -> Vec<Image> {
+            dimms.iter().enumerate().map(|(idx, x)| Image::new(PathBuf::from(format!("/a/{idx}.jpg")), vec![0; 10], *x)).collect()
+        };
+        let variants = |dimms: &[Option<(u32, u32)>]| resolution_variants(&group(dimms), 1.5);
+        assert_eq!(variants(&[Some((1333, 1000)), Some((4000, 3000))]), Variants::Largest(1));
+        assert_eq!(variants(&[Some((4000, 3000)), Some((800, 600)), Some((2000, 1500))]), Variants::Largest(0));
+        assert_eq!(variants(&[Some((3000, 4000)), Some((150, 200))]), Variants::Largest(0));
+        // Not different enough, the same size, or a different shape.
+        assert_eq!(variants(&[Some((4000, 3000)), Some((3000, 2250))]), Variants::No);
+        assert_eq!(variants(&[Some((4000, 3000)), Some((4000, 3000)), Some((800, 600))]), Variants::No);
+        assert_eq!(variants(&[Some((4000, 3000)), Some((1000, 1000))]), Variants::No);
+        assert_eq!(variants(&[Some((4000, 3000))]), Variants::No);
+        // Unknown only if the rest don't settle it.
+        assert_eq!(variants(&[Some((4000, 3000)), Some((800, 600)), None]), Variants::Unknown);
+        assert_eq!(variants(&[Some((4000, 3000)), Some((4000, 3000)), None]), Variants::No);
+        assert_eq!(variants(&[None, None]), Variants::Unknown);
+    }
+
+    #[test]
+    fn group_sizes_count_what_removal_frees() {
+        let mut group: Vec<Image> = [300, 100, 200, 200].iter()
+            .enumerate()
+            .map(|(idx, size)| Image::new(PathBuf::from(format!("/a/{idx}.jpg")), vec![0; *size], None))
+            .collect();
+        // The last two are one file, so removing either frees nothing
+        group[2].file_id = Some(FileId{dev: 1, ino: 7});
+        group[3].file_id = Some(FileId{dev: 1, ino: 7});
+        let sizes = group_sizes(&group);
+        assert_eq!((sizes.total, sizes.wasted), (Some(600), Some(300)));
+        assert_eq!(sizes.gains, [None, Some(100), None, None]);
+
+        group[1].changed = true;
+        let sizes = group_sizes(&group);
+        assert_eq!((sizes.total, sizes.wasted), (None, None));
+        assert_eq!(sizes.gains, [None; 4]);
+    }
+
+    #[test]
+    fn wasted_members_are_all_but_the_largest_file() {
+        let mut group: Vec<Image> = [100, 300, 200, 200].iter()
+            .enumerate()
+            .map(|(idx, size)| Image::new_lazy(PathBuf::from(format!("/a/{idx}.jpg")), *size, None))
+            .collect();
+        let paths = |group: &[Image]| wasted_members(group).iter().map(|x| x.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&group), [Path::new("/a/0.jpg"), Path::new("/a/2.jpg"), Path::new("/a/3.jpg")]);
+        assert_eq!(wasted_members(&group).iter().map(|x| x.file_size).sum::<usize>(), wasted_bytes(&group));
+
+        // Hard links to one file are counted once, by their first member.
+        group[2].file_id = Some(FileId{dev: 1, ino: 7});
+        group[3].file_id = Some(FileId{dev: 1, ino: 7});
+        assert_eq!(paths(&group), [Path::new("/a/0.jpg"), Path::new("/a/2.jpg")]);
+        assert_eq!(wasted_members(&group).iter().map(|x| x.file_size).sum::<usize>(), wasted_bytes(&group));
+
+        assert!(wasted_members(&group[1..2]).is_empty());
+        assert!(wasted_members(&[]).is_empty());
+    }
+
+    #[test]
+    fn stale_files_are_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.jpg");
+        std::fs::write(&path, b"1234").unwrap();
+        let image = Image::load(path.clone()).unwrap();
+        assert_eq!(image.staleness(), Staleness::Current);
+
+        // Rewritten with the same size, but later.
+        std::fs::write(&path, b"5678").unwrap();
+        let later = image.mtime.unwrap() + Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert_eq!(image.staleness(), Staleness::Changed);
+
+        let image = Image::load(path.clone()).unwrap();
+        assert_eq!(image.staleness(), Staleness::Current);
+        std::fs::write(&path, b"56789").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert_eq!(image.staleness(), Staleness::Changed);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(image.staleness(), Staleness::Missing);
+
+        // Restored sessions only keep whole seconds.
+        std::fs::write(&path, b"1234").unwrap();
+        let mut image = Image::load(path.clone()).unwrap();
+        let secs = image.mtime.unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        image.mtime = Some(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(image.staleness(), Staleness::Current);
+    }
+
+    #[test]
+    fn dates_are_parsed_as_midnight_utc() {
+        let days = |text: &str| parse_date(text).map(|x| x.duration_since(UNIX_EPOCH).unwrap().as_secs() / 86400);
+        assert_eq!(days("1970-01-01"), Some(0));
+        assert_eq!(days("2024-05-31"), Some(19874));
+        assert_eq!(days(" 2024-5-31\n"), Some(19874));
+        assert_eq!(parse_date("2024-05-31").unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs() % 86400, 0);
+
+        // Month lengths, and leap years by all three rules.
+        assert_eq!(days("2024-01-31"), Some(19753));
+        assert_eq!(days("2024-04-31"), None);
+        assert_eq!(days("2024-02-29"), Some(19782));
+        assert_eq!(days("2023-02-29"), None);
+        assert_eq!(days("2000-02-29"), Some(11016));
+        assert_eq!(days("1900-02-29"), None);
+        assert_eq!(days("2024-12-31"), Some(20088));
+        assert_eq!(days("2025-01-01"), Some(20089));
+
+        for text in ["", "2024", "2024-05", "2024-00-10", "2024-13-01", "2024-05-00", "2024-05-32",
+            "2024-05-31x", "2024/05/31", "May 31 2024", "1969-12-31"]
+        {
+            assert_eq!(parse_date(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn times_are_formatted_in_utc() {
+        let at = |secs: u64| format_time(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(at(1_717_171_199), "2024-05-31 15:59:59 UTC");
+        assert_eq!(at(1_709_164_800), "2024-02-29 00:00:00 UTC");
+        assert_eq!(at(1_735_689_599), "2024-12-31 23:59:59 UTC");
+        assert_eq!(at(951_782_400), "2000-02-29 00:00:00 UTC");
+        // The inverse of parse_date().
+        assert_eq!(format_time(parse_date("2038-01-19").unwrap()), "2038-01-19 00:00:00 UTC");
+        assert_eq!(format_time(UNIX_EPOCH - Duration::from_secs(1)), "before 1970");
+    }
+
+    #[test]
+    fn system_paths() {
+        assert!(is_system_path(Path::new("/System/Library/Desktop Pictures/a.heic")));
+        assert!(is_system_path(Path::new("/usr/share/backgrounds/a.png")));
+        assert!(is_system_path(Path::new(r"C:\Windows\Web\Wallpaper\a.jpg")));
+        assert!(is_system_path(Path::new(r"E:\Program Files\App\icon.png")));
+        assert!(!is_system_path(Path::new("/home/alice/Pictures/a.png")));
+        assert!(!is_system_path(Path::new(r"C:\Users\alice\Pictures\a.png")));
+        // Windows folders only count on Windows paths, and vice versa.
+        assert!(!is_system_path(Path::new("/home/alice/windows/a.png")));
+        assert!(!is_system_path(Path::new(r"C:\usr\a.png")));
+    }
+
+    #[test]
+    fn paths_resolve_to_the_deepest_mount() {
+        let mounts = [Path::new("/"), Path::new("/mnt/photos"), Path::new("/mnt"), Path::new("/home")];
+        let mount = |path: &str| mount_point_of(Path::new(path), &mounts).map(|x| mounts[x]);
+        assert_eq!(mount("/mnt/photos/2024/a.jpg"), Some(Path::new("/mnt/photos")));
+        assert_eq!(mount("/mnt/photos"), Some(Path::new("/mnt/photos")));
+        assert_eq!(mount("/mnt/photos2/a.jpg"), Some(Path::new("/mnt")));
+        assert_eq!(mount("/homework"), Some(Path::new("/")));
+        assert_eq!(mount("relative/a.jpg"), None);
+        assert_eq!(mount_point_of(Path::new("/a"), &[]), None);
+    }
+
+    #[test]
+    fn unreadable_lists_are_reported_and_kept() {
+        #[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+        struct List {
+            version: u32,
+            entries: Vec<String>,
+        }
+        let file = VersionedFile{file: "versioned_test.json", name: "test list", version: 1};
+        let path = data_dir().unwrap().join(file.file);
+
+        // Missing is empty
+        assert_eq!(file.load::<List>().unwrap(), List::default());
+        let list = List{version: 1, entries: vec!["a".to_string()]};
+        file.save(&list).unwrap();
+        assert_eq!(file.load::<List>().unwrap(), list);
+
+        // Neither garbage nor a newer version's list is taken for an empty
+        // one, or written over.
+        for json in ["{not json", r#"{"version": 2, "entries": {"a": 1}}"#] {
+            std::fs::write(&path, json).unwrap();
+            assert!(file.load::<List>().is_err());
+            assert!(file.save(&list).is_err());
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), json);
+        }
+        assert!(file.load::<List>().unwrap_err().contains("newer version"));
     }
 }
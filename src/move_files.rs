@@ -0,0 +1,249 @@
+use crate::rename::collision_key;
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+
+// Moving the images that aren't being kept into a folder of their own, to go
+// through before deleting, rather than trashing them. Files from different
+// folders often share a name (every camera has an IMG_0001.jpg), and a folder
+// moved into before often has some already, so what happens when a name's
+// taken is chosen per batch; see CollisionPolicy. The plan only predicts
+// collisions; move_file() checks again as it goes.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CollisionPolicy {
+    #[default]
+    Rename, // With a number, as in "IMG_0001 (1).jpg"
+    Skip, // Left where it is, and in the results
+    Overwrite, // Replaces the file that's there, once confirmed
+}
+
+impl CollisionPolicy {
+    pub const ALL: [CollisionPolicy; 3] = [CollisionPolicy::Rename, CollisionPolicy::Skip, CollisionPolicy::Overwrite];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CollisionPolicy::Rename => tr!("Add a number"),
+            CollisionPolicy::Skip => tr!("Skip it"),
+            CollisionPolicy::Overwrite => tr!("Replace it"),
+        }
+    }
+}
+
+// How many numbered names are tried before giving up on a file.
+const MAX_NUMBER: usize = 9999;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Resolved {
+    To(PathBuf),
+    Replace(PathBuf), // The file there, with Overwrite
+    Skip(PathBuf), // The name's taken, by a file there or another in the batch
+}
+
+// Where the file would go before collisions: into dest by name, or with root
+// given, under the same folders it's in under root. Files outside root go by
+// name.
+pub fn destination(path: &Path, dest: &Path, root: Option<&Path>) -> PathBuf {
+    match root.and_then(|x| path.strip_prefix(x).ok()) {
+        Some(relative) if relative.file_name().is_some() => dest.join(relative),
+        _ => dest.join(path.file_name().unwrap_or_default()),
+    }
+}
+
+// The name with " (n)" after its stem. It goes on the whole stem, so a file
+// already named "IMG_0001 (1).jpg" becomes "IMG_0001 (1) (1).jpg" rather than
+// being taken for one of the numbered copies.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.file_stem().unwrap_or_default());
+    name.push(format!(" ({n})"));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+// Where each of the files, given as their paths and where they'd go (see
+// destination()), ends up under the policy, in the given order. A name is
+// taken if exists() says a file is there, or an earlier file in the batch is
+// going there, ignoring case, as in rename. A file's own path counts as
+// taken, so one already in the destination is left alone. Overwrite only
+// replaces files that are there, never another from the batch.
+pub fn resolve(files: &[(PathBuf, PathBuf)], policy: CollisionPolicy, exists: impl Fn(&Path) -> bool) -> Vec<Resolved> {
+    let mut claimed: HashSet<String> = HashSet::new();
+    files.iter()
+        .map(|(from, wanted)| {
+            let mine = collision_key(from) == collision_key(wanted);
+            let taken = |x: &Path| claimed.contains(&collision_key(x)) || exists(x);
+            let resolved = match policy {
+                _ if !taken(wanted) => Resolved::To(wanted.clone()),
+                _ if mine => Resolved::Skip(wanted.clone()),
+                CollisionPolicy::Rename => (1..=MAX_NUMBER)
+                    .map(|n| numbered(wanted, n))
+                    .find(|x| !taken(x))
+                    .map_or(Resolved::Skip(wanted.clone()), Resolved::To),
+                CollisionPolicy::Skip => Resolved::Skip(wanted.clone()),
+                CollisionPolicy::Overwrite if claimed.contains(&collision_key(wanted)) => Resolved::Skip(wanted.clone()),
+                CollisionPolicy::Overwrite => Resolved::Replace(wanted.clone()),
+            };
+            if let Resolved::To(to) | Resolved::Replace(to) = &resolved {
+                claimed.insert(collision_key(to));
+            }
+            resolved
+        })
+        .collect()
+}
+
+// Moves the file to the path given, or if replace, over the file there.
+// Otherwise the name is claimed by creating an empty file at it first, which
+// only one of any number of racing creators can do, and the file is moved
+// over that. If another file got there since the plan was made, Rename moves
+// on to the next free numbered name, and the others fail with AlreadyExists.
+// Returns where the file ended up.
+pub fn move_file(from: &Path, to: &Path, replace: bool, policy: CollisionPolicy) -> std::io::Result<PathBuf> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if replace {
+        relocate(from, to)?;
+        return Ok(to.to_path_buf());
+    }
+
+    let numbers = match policy {
+        CollisionPolicy::Rename => MAX_NUMBER,
+        _ => 0,
+    };
+    let candidates = std::iter::once(to.to_path_buf()).chain((1..=numbers).map(|n| numbered(to, n)));
+    for candidate in candidates {
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+        if let Err(e) = relocate(from, &candidate) {
+            let _ = std::fs::remove_file(&candidate);
+            return Err(e);
+        }
+        return Ok(candidate);
+    }
+    Err(std::io::Error::new(ErrorKind::AlreadyExists, format!("{} is taken", to.display())))
+}
+
+// Renames the file, or where that can't be done across drives, copies it and
+// deletes the original.
+fn relocate(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from).inspect_err(|_| {
+                let _ = std::fs::remove_file(to);
+            })
+        },
+        result => result,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(names: &[&str]) -> Vec<(PathBuf, PathBuf)> {
+        names.iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let from = PathBuf::from(format!("/photos/{idx}/{name}"));
+                let to = destination(&from, Path::new("/moved"), None);
+                (from, to)
+            })
+            .collect()
+    }
+
+    fn to(path: &str) -> Resolved {
+        Resolved::To(PathBuf::from(path))
+    }
+
+    #[test]
+    fn destinations_keep_the_structure_under_the_root() {
+        let dest = Path::new("/moved");
+        let path = Path::new("/photos/2021/trip/IMG_0001.jpg");
+        assert_eq!(destination(path, dest, None), Path::new("/moved/IMG_0001.jpg"));
+        assert_eq!(destination(path, dest, Some(Path::new("/photos"))), Path::new("/moved/2021/trip/IMG_0001.jpg"));
+        assert_eq!(destination(path, dest, Some(Path::new("/elsewhere"))), Path::new("/moved/IMG_0001.jpg"));
+        assert_eq!(numbered(Path::new("/moved/notes"), 2), Path::new("/moved/notes (2)"));
+    }
+
+    #[test]
+    fn taken_names_are_numbered() {
+        let existing = ["/moved/IMG_0001.jpg", "/moved/img_0001 (1).JPG"].map(PathBuf::from);
+        let exists = |x: &Path| existing.iter().any(|y| collision_key(x) == collision_key(y));
+
+        // Taken on disk (in another case, too) and in the batch
+        let files = pairs(&["IMG_0001.jpg", "IMG_0001.jpg", "IMG_0002.jpg", "IMG_0001 (1).jpg", "IMG_0001 (2).jpg"]);
+        assert_eq!(resolve(&files, CollisionPolicy::Rename, exists), [
+            to("/moved/IMG_0001 (2).jpg"),
+            to("/moved/IMG_0001 (3).jpg"),
+            to("/moved/IMG_0002.jpg"),
+            to("/moved/IMG_0001 (1) (1).jpg"),
+            // Its own name went to the first file, which couldn't know it was coming.
+            to("/moved/IMG_0001 (2) (1).jpg"),
+        ]);
+
+        // Files already there stay where they are.
+        let there = vec![(PathBuf::from("/moved/IMG_0001.jpg"), PathBuf::from("/moved/IMG_0001.jpg"))];
+        assert_eq!(resolve(&there, CollisionPolicy::Rename, exists), [Resolved::Skip(PathBuf::from("/moved/IMG_0001.jpg"))]);
+    }
+
+    #[test]
+    fn taken_names_can_be_skipped_or_replaced() {
+        let exists = |x: &Path| x == Path::new("/moved/IMG_0001.jpg");
+        let files = pairs(&["IMG_0001.jpg", "IMG_0002.jpg", "img_0002.JPG"]);
+        assert_eq!(resolve(&files, CollisionPolicy::Skip, exists), [
+            Resolved::Skip(PathBuf::from("/moved/IMG_0001.jpg")),
+            to("/moved/IMG_0002.jpg"),
+            Resolved::Skip(PathBuf::from("/moved/img_0002.JPG")),
+        ]);
+        // Never one that's just been moved there
+        assert_eq!(resolve(&files, CollisionPolicy::Overwrite, exists), [
+            Resolved::Replace(PathBuf::from("/moved/IMG_0001.jpg")),
+            to("/moved/IMG_0002.jpg"),
+            Resolved::Skip(PathBuf::from("/moved/img_0002.JPG")),
+        ]);
+    }
+
+    #[test]
+    fn moves_check_again_for_files_that_turned_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("moved");
+        let source = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, name).unwrap();
+            path
+        };
+        std::fs::create_dir(&dest).unwrap();
+        std::fs::write(dest.join("a.jpg"), "there first").unwrap();
+
+        let a = source("a.jpg");
+        let err = move_file(&a, &dest.join("a.jpg"), false, CollisionPolicy::Skip).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(a.exists());
+        assert_eq!(std::fs::read(dest.join("a.jpg")).unwrap(), b"there first");
+
+        assert_eq!(move_file(&a, &dest.join("a.jpg"), false, CollisionPolicy::Rename).unwrap(), dest.join("a (1).jpg"));
+        assert_eq!(std::fs::read(dest.join("a (1).jpg")).unwrap(), b"a.jpg");
+        assert!(!a.exists());
+
+        let b = source("b.jpg");
+        std::fs::write(dest.join("b.jpg"), "replaced").unwrap();
+        assert_eq!(move_file(&b, &dest.join("b.jpg"), true, CollisionPolicy::Overwrite).unwrap(), dest.join("b.jpg"));
+        assert_eq!(std::fs::read(dest.join("b.jpg")).unwrap(), b"b.jpg");
+
+        // Folders kept from under the root are made as needed.
+        let c = source("c.jpg");
+        let nested = dest.join("2021/trip/c.jpg");
+        assert_eq!(move_file(&c, &nested, false, CollisionPolicy::Rename).unwrap(), nested);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{Error, Result};
+use crate::searcher::{SearchOpts, DateRange, SUPPORTED_EXTS};
+use crate::startup_phase::UserOpts;
+use crate::misc;
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+
+// Turning the options as entered into a search's, checking them on the way.
+// Shared by the form and --headless-scan, so options one accepts (e.g. a
+// preset) the other does too.
+
+// Returns a description of what's wrong with the folder, if anything.
+pub fn folder_problem(folder: &Path) -> Option<String> {
+    if folder.as_os_str().is_empty() {
+        Some("Enter a folder to search".to_owned())
+    } else if !folder.exists() {
+        Some(format!("{} doesn't exist", folder.display()))
+    } else if !folder.is_dir() {
+        Some(format!("{} isn't a folder", folder.display()))
+    } else {
+        None
+    }
+}
+
+pub fn root_problem(opts: &UserOpts) -> Option<String> {
+    folder_problem(&opts.root)
+}
+
+// Only applicable in compare mode.
+pub fn reference_problem(opts: &UserOpts) -> Option<String> {
+    if !opts.compare {
+        return None;
+    }
+
+    folder_problem(&opts.reference).or_else(|| {
+        let root = opts.root.canonicalize().ok()?;
+        let reference = opts.reference.canonicalize().ok()?;
+        (root.starts_with(&reference) || reference.starts_with(&root))
+            .then(|| "The reference and candidate folders can't contain one another".to_owned())
+    })
+}
+
+pub fn parse_max_depth(opts: &UserOpts) -> Result<Option<usize>> {
+    let mut max_depth = None;
+    if !opts.max_depth.is_empty() {
+        let depth = opts.max_depth.parse::<usize>().map_err(|e|
+            Error::new(
+                "Error parsing depth limit".to_string(),
+                e.to_string(),
+        ))?;
+        if depth == 0usize {
+            return Err(Error::new(
+                "Invalid depth limit".to_string(),
+                "A depth limit of 0 doesn't search at all".to_string(),
+            ));
+        }
+        max_depth = Some(depth);
+    }
+    Ok(max_depth)
+}
+
+pub fn parse_dates(opts: &UserOpts) -> Result<Option<DateRange>> {
+    let parse = |text: &str| -> Result<Option<SystemTime>> {
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        misc::parse_date(text).map(Some).ok_or_else(|| Error::new(
+            "Error parsing date".to_string(),
+            format!("\"{}\" isn't a date like 2024-05-31", text.trim()),
+        ))
+    };
+    let after = parse(&opts.modified_after)?;
+    // Inclusive, so anything before the start of the next day.
+    let before = parse(&opts.modified_before)?
+        .map(|x| x + Duration::from_secs(86400) - Duration::from_nanos(1));
+    if after.is_none() && before.is_none() {
+        return Ok(None);
+    }
+    if let (Some(a), Some(b)) = (after, before) {
+        if a > b {
+            return Err(Error::new(
+                "Invalid date range".to_string(),
+                "The \"modified after\" date is later than the \"modified before\" date".to_string(),
+            ));
+        }
+    }
+    Ok(Some(DateRange{after, before, any_member: opts.date_any_member}))
+}
+
+pub fn parse_max_entries(opts: &UserOpts) -> Result<Option<usize>> {
+    let text = opts.max_entries.trim().replace([',', '_'], "");
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    let max = text.parse::<usize>().map_err(|e|
+        Error::new(
+            "Error parsing entry limit".to_string(),
+            e.to_string(),
+    ))?;
+    if max == 0 {
+        return Err(Error::new(
+            "Invalid entry limit".to_string(),
+            "An entry limit of 0 doesn't search at all".to_string(),
+        ));
+    }
+    Ok(Some(max))
+}
+
+pub fn parse_exts(opts: &UserOpts) -> Result<HashSet<String>> {
+    let exts = opts.ext_set();
+
+    for ext in &exts {
+        if !SUPPORTED_EXTS.contains(ext.as_str()) {
+            return Err(Error::new(
+                "Extension Error".to_owned(),
+                format!("Extension {ext} is not supported"),
+            ));
+        }
+    }
+
+    Ok(exts)
+}
+
+pub fn search_opts(opts: &UserOpts) -> Result<SearchOpts> {
+    if let Some(problem) = root_problem(opts).or_else(|| reference_problem(opts)) {
+        return Err(Error::new("Path Error".into(), problem));
+    }
+
+    let max_depth = parse_max_depth(opts)?;
+    let max_entries = parse_max_entries(opts)?;
+    let exts = parse_exts(opts)?;
+    let modified = parse_dates(opts)?;
+
+    Ok(SearchOpts {
+        root: opts.root.clone(),
+        subfolders: opts.subfolders.iter()
+            .filter(|x| x.starts_with(&opts.root))
+            .cloned()
+            .collect(),
+        hash: opts.hash,
+        follow_sym: opts.follow_sym,
+        max_depth,
+        exts,
+        respect_ignore: opts.respect_ignore,
+        reference: opts.compare.then(|| opts.reference.clone()),
+        io_threads: opts.io_threads,
+        queue_size: opts.queue_size,
+        sniff: opts.sniff,
+        max_entries,
+        verify_color: opts.verify_color,
+        size_cap: opts.size_cap(),
+        detect_crops: opts.detect_crops,
+        trim_borders: opts.trim_borders,
+        center: opts.center_crop,
+        checkpoint: true,
+        resume: None,
+        modified,
+        max_distance: opts.max_distance,
+        grouping: opts.grouping,
+        scan_libraries: opts.scan_libraries,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::misc::parse_date;
+
+    fn with(f: impl FnOnce(&mut UserOpts)) -> UserOpts {
+        let mut opts = UserOpts::new(std::env::temp_dir());
+        f(&mut opts);
+        opts
+    }
+
+    #[test]
+    fn limits_are_positive_numbers_or_empty() {
+        assert_eq!(parse_max_depth(&with(|x| x.max_depth = String::new())).unwrap(), None);
+        assert_eq!(parse_max_depth(&with(|x| x.max_depth = "3".to_string())).unwrap(), Some(3));
+        assert!(parse_max_depth(&with(|x| x.max_depth = "0".to_string())).is_err());
+        assert!(parse_max_depth(&with(|x| x.max_depth = "deep".to_string())).is_err());
+
+        assert_eq!(parse_max_entries(&with(|x| x.max_entries = " ".to_string())).unwrap(), None);
+        assert_eq!(parse_max_entries(&with(|x| x.max_entries = "1,000_000".to_string())).unwrap(), Some(1_000_000));
+        assert!(parse_max_entries(&with(|x| x.max_entries = "0".to_string())).is_err());
+        assert!(parse_max_entries(&with(|x| x.max_entries = "-5".to_string())).is_err());
+    }
+
+    #[test]
+    fn date_ranges_include_their_last_day() {
+        assert!(parse_dates(&with(|_| ())).unwrap().is_none());
+        let range = parse_dates(&with(|x| {
+            x.modified_after = "2024-05-01".to_string();
+            x.modified_before = " 2024-05-31 ".to_string();
+        })).unwrap().unwrap();
+        assert_eq!(range.after, parse_date("2024-05-01"));
+        let last = parse_date("2024-05-31").unwrap() + Duration::from_secs(86399);
+        assert!(range.before.unwrap() > last && range.before < parse_date("2024-06-01"));
+
+        assert!(parse_dates(&with(|x| x.modified_after = "May 1st".to_string())).is_err());
+        assert!(parse_dates(&with(|x| {
+            x.modified_after = "2024-06-01".to_string();
+            x.modified_before = "2024-05-31".to_string();
+        })).is_err());
+    }
+
+    #[test]
+    fn options_are_checked_before_searching() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("photos");
+        std::fs::create_dir_all(root.join("2021")).unwrap();
+        let file = dir.path().join("a.jpg");
+        std::fs::write(&file, "").unwrap();
+
+        assert!(folder_problem(Path::new("")).is_some());
+        assert!(folder_problem(&dir.path().join("missing")).is_some());
+        assert!(folder_problem(&file).is_some());
+        assert!(folder_problem(&root).is_none());
+
+        let mut opts = UserOpts::new(root.clone());
+        opts.compare = true;
+        opts.reference = root.join("2021");
+        assert!(reference_problem(&opts).unwrap().contains("contain one another"));
+        opts.reference = dir.path().to_path_buf();
+        assert!(reference_problem(&opts).is_some());
+        opts.compare = false;
+        assert!(reference_problem(&opts).is_none());
+
+        opts.exts = "jpg, .PNG_NOT, png".to_string();
+        assert_eq!(parse_exts(&opts).unwrap_err().detail, "Extension PNG_NOT is not supported");
+        opts.exts = "jpg, .png".to_string();
+        assert_eq!(parse_exts(&opts).unwrap(), HashSet::from(["jpg".to_string(), "png".to_string()]));
+
+        // Subfolders outside the root are dropped.
+        opts.subfolders = vec![root.join("2021"), dir.path().join("elsewhere")];
+        let search = search_opts(&opts).unwrap();
+        assert_eq!(search.subfolders, [root.join("2021")]);
+        assert_eq!(search.reference, None);
+        opts.root = dir.path().join("missing");
+        assert!(search_opts(&opts).is_err());
+    }
+}
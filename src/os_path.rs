@@ -0,0 +1,264 @@
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsString;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+
+// Paths aren't always valid UTF-8 (Unix filenames are any bytes but / and NUL,
+// Windows ones can hold unpaired surrogates), and on Windows they can be
+// longer than most APIs take. Everything here keeps the real path around,
+// converting lossily only for display.
+
+// Marks a path stored as its raw encoding, in hex. Paths can't contain NUL,
+// so no real path starts with it, and plain strings from older versions still
+// read as themselves.
+const RAW_PREFIX: &str = "\0hex:";
+
+// The longest path Windows APIs take without the extended-length prefix.
+const MAX_PATH: usize = 259;
+
+// What egui caches an image's texture under. Lossy strings of two different
+// non-UTF-8 paths can be the same, and would show one image for both, so it
+// goes by the raw path's hash, which leaves nothing to keep for every image
+// shown. There's deliberately no extension: the loader goes by the bytes.
+pub fn image_uri(path: &Path) -> String {
+    format!("bytes://deckard/image/{:016x}", raw_hash(path))
+}
+
+// Of the path's raw encoding, so paths that only differ in bytes that aren't
+// UTF-8 differ. The same for as long as the app runs.
+pub fn raw_hash(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.as_os_str().hash(&mut hasher);
+    hasher.finish()
+}
+
+// The path as text for the clipboard. Where it isn't UTF-8, it's quoted the
+// way bash and zsh read back as the original bytes ($'...'), rather than
+// pasting as something that doesn't exist.
+pub fn clipboard_text(path: &Path) -> String {
+    if let Some(text) = path.to_str() {
+        return text.to_string();
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let mut text = String::from("$'");
+        for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+            for c in chunk.valid().chars() {
+                match c {
+                    '\'' | '\\' => { text.push('\\'); text.push(c) },
+                    c if c.is_control() => text.push_str(&format!("\\x{:02x}", c as u32)),
+                    c => text.push(c),
+                }
+            }
+            for byte in chunk.invalid() {
+                text.push_str(&format!("\\x{byte:02x}"));
+            }
+        }
+        text.push('\'');
+        text
+    }
+    #[cfg(not(unix))]
+    path.to_string_lossy().into_owned()
+}
+
+// For storing a path as a string without losing anything: UTF-8 paths as
+// they are, others as their raw encoding.
+pub fn encode(path: &Path) -> String {
+    if let Some(text) = path.to_str() {
+        return text.to_string();
+    }
+    let mut text = RAW_PREFIX.to_string();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        for byte in path.as_os_str().as_bytes() {
+            text.push_str(&format!("{byte:02x}"));
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        for unit in path.as_os_str().encode_wide() {
+            text.push_str(&format!("{unit:04x}"));
+        }
+    }
+    text
+}
+
+// The reverse of encode. None if it's marked as raw but isn't valid.
+pub fn decode(text: &str) -> Option<PathBuf> {
+    let Some(hex) = text.strip_prefix(RAW_PREFIX) else {
+        return Some(PathBuf::from(text));
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        let bytes = parse_hex(hex, 2)?.into_iter().map(|x| x as u8).collect();
+        Some(OsString::from_vec(bytes).into())
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStringExt;
+        let units: Vec<u16> = parse_hex(hex, 4)?.into_iter().map(|x| x as u16).collect();
+        Some(OsString::from_wide(&units).into())
+    }
+}
+
+// Fixed-width hex numbers, run together.
+fn parse_hex(hex: &str, width: usize) -> Option<Vec<u32>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(width) {
+        return None;
+    }
+    (0..hex.len()).step_by(width)
+        .map(|i| u32::from_str_radix(&hex[i..i + width], 16).ok())
+        .collect()
+}
+
+// The path as the open, reveal and delete operations should be given it. On
+// Windows, long ones need the extended-length prefix; elsewhere it's as is.
+pub fn long_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(extended) = path.to_str().and_then(extended_length) {
+        return extended.into();
+    }
+    path.to_path_buf()
+}
+
+// The extended-length form of an absolute Windows path, if it's too long to
+// use otherwise. Paths that aren't absolute can't take the prefix.
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn extended_length(path: &str) -> Option<String> {
+    if path.chars().count() <= MAX_PATH || path.starts_with(r"\\?\") {
+        return None;
+    }
+    // The prefix turns off all normalization, so separators have to be
+    // backslashes already.
+    let path = path.replace('/', "\\");
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{share}"));
+    }
+    let bytes = path.as_bytes();
+    let absolute = bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\';
+    absolute.then(|| format!(r"\\?\{path}"))
+}
+
+// For #[serde(with = "os_path::serde_path")] on PathBuf fields, which serde
+// otherwise refuses to write if they aren't UTF-8.
+pub mod serde_path {
+    use std::path::{Path, PathBuf};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::encode(path))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        super::decode(&text).ok_or_else(|| serde::de::Error::custom("invalid raw path"))
+    }
+}
+
+// The same for Vec<PathBuf>.
+pub mod serde_paths {
+    use std::path::PathBuf;
+    use serde::{Deserialize, Deserializer, Serializer, ser::SerializeSeq};
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(paths.len()))?;
+        for path in paths {
+            seq.serialize_element(&super::encode(path))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|x| super::decode(x).ok_or_else(|| serde::de::Error::custom("invalid raw path")))
+            .collect()
+    }
+}
+
+// The same for Vec<(PathBuf, T)>, e.g. paths with what was recorded about
+// each; written as [path, value] pairs, as serde writes tuples.
+pub mod serde_path_pairs {
+    use std::path::PathBuf;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, ser::SerializeSeq};
+
+    pub fn serialize<S: Serializer, T: Serialize>(pairs: &[(PathBuf, T)], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(pairs.len()))?;
+        for (path, value) in pairs {
+            seq.serialize_element(&(super::encode(path), value))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Vec<(PathBuf, T)>, D::Error> {
+        Vec::<(String, T)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(path, value)| {
+                let path = super::decode(&path).ok_or_else(|| serde::de::Error::custom("invalid raw path"))?;
+                Ok((path, value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn non_utf8(bytes: &[u8]) -> PathBuf {
+        use std::os::unix::ffi::OsStrExt;
+        std::ffi::OsStr::from_bytes(bytes).into()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_survive() {
+        let (a, b) = (non_utf8(b"/photos/a\xff.png"), non_utf8(b"/photos/a\xfe.png"));
+        assert_eq!(a.to_string_lossy(), b.to_string_lossy());
+        assert_ne!(image_uri(&a), image_uri(&b));
+        assert_eq!(image_uri(&a), image_uri(&a));
+
+        assert_eq!(encode(Path::new("/photos/b.png")), "/photos/b.png");
+        assert_eq!(decode(&encode(&a)).unwrap(), a);
+        assert_eq!(decode("/photos/b.png").unwrap(), Path::new("/photos/b.png"));
+        assert!(decode("\0hex:zz").is_none());
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Paths {
+            #[serde(with = "serde_path")]
+            one: PathBuf,
+            #[serde(with = "serde_paths")]
+            many: Vec<PathBuf>,
+            #[serde(with = "serde_path_pairs")]
+            pairs: Vec<(PathBuf, u32)>,
+        }
+        let pairs = vec![(a.clone(), 1), ("/c.png".into(), 2)];
+        let json = serde_json::to_string(&Paths{one: a.clone(), many: vec![b.clone(), "/c.png".into()], pairs: pairs.clone()}).unwrap();
+        let back: Paths = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.one, a);
+        assert_eq!(back.many, [b, PathBuf::from("/c.png")]);
+        assert_eq!(back.pairs, pairs);
+        // As serde writes tuples, so UTF-8 ones read as they did without it
+        assert!(json.contains(r#"["/c.png",2]"#));
+
+        assert_eq!(clipboard_text(&non_utf8(b"/it's/a\xff\n.png")), r"$'/it\'s/a\xff\x0a.png'");
+        assert_eq!(clipboard_text(Path::new("/it's.png")), "/it's.png");
+    }
+
+    #[test]
+    fn long_windows_paths_get_the_prefix() {
+        let name = "x".repeat(MAX_PATH);
+        assert_eq!(extended_length(r"C:\short.png"), None);
+        assert_eq!(extended_length(&format!(r"C:\{name}")), Some(format!(r"\\?\C:\{name}")));
+        assert_eq!(extended_length(&format!("C:/a/{name}")), Some(format!(r"\\?\C:\a\{name}")));
+        assert_eq!(extended_length(&format!(r"\\server\share\{name}")), Some(format!(r"\\?\UNC\server\share\{name}")));
+        assert_eq!(extended_length(&format!(r"relative\{name}")), None);
+        assert_eq!(extended_length(&format!(r"\\?\C:\{name}")), None);
+    }
+}
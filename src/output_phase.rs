@@ -1,27 +1,388 @@
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::collections::HashSet;
-use std::num::Wrapping;
+use std::collections::{HashSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::ops::Range;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use crate::ROOT_KEY;
 use crate::{Phase, DynPhase, Result, Error};
-use crate::startup_phase::{StartupPhase, UserOpts};
-use crate::misc::Image;
+use crate::startup_phase::{StartupPhase, UserOpts, FocusHint};
+use crate::misc::{Image, FileId, Side, Staleness, has_hard_links, is_burst, sort_burst, format_time, format_duration};
+use crate::misc::{hash_distances, sort_by_distance, folder_name, same_folder, renamed_copies, prefer_original, group_id};
+use crate::misc::{derivative_members, common_folder, resolution_variants, Variants};
+use crate::misc::{self, is_sensitive_root, is_system_path, wasted_members, GroupSizes, group_sizes};
+use crate::hardlink::{self, LinkOutcome};
+use crate::archive::Namespace;
+use crate::searcher::{self, SearchResults, SearchError, SearchStats, HashIndex, RetryResults, ErrorKind, HASH_SIZE};
+use crate::session::{Session, PORTABLE_FILE};
+use crate::undo::{self, UndoEntry};
+use crate::ignored::{IgnoreList, IgnoredGroup};
+use crate::exclusions::{ExclusionList, Exclusion};
+use crate::junk::{JunkList, JunkSignature, JunkSource};
+use crate::animation::Animation;
+use crate::watcher::{Watch, WatchOpts};
+use crate::plan::{ActionPlan, PlanKind, Action, Step};
+use crate::encoding;
+use crate::palette::Command;
+use crate::quick_look::{self, QuickLook};
+use crate::content::ContentKind;
+use crate::report::{ReportJob, ReportInput};
+use crate::finder_tags::{self, FinderTag, LabelColor};
+use crate::hook::{self, HookJob, HookOutcome};
+use crate::compare_tool;
+use crate::os_path::{self, long_path};
+use crate::clicks::{self, Click, ClickAction};
+use crate::a11y;
+use crate::review::{Reviews, Mark};
+use crate::keepers::{self, KeeperStrategy};
+use crate::history::{self, HistoryEntry, Outcome};
+use crate::rename::{self, Rename, RenameFile, Placeholder};
+use crate::move_files::{self, CollisionPolicy};
+use crate::thumbnails::{Thumbnails, Thumbnail, CacheLimits};
+use crate::grouping::Grouping;
+use crate::live_search::LiveSearch;
+use crate::sniff;
+use crate::folder_browser::FolderBrowser;
+use crate::searching_phase::SearchingPhase;
+use crate::theme;
+use crate::broken::{BrokenImage, BrokenKind};
 
 use eframe::egui;
+use eframe::egui::load::SizedTexture;
 
-use humansize::{format_size, DECIMAL};
+use crate::i18n::format_size;
+
+use image_hasher::ImageHash;
+
+use itertools::Itertools;
+
+
+// How the results are laid out. Persisted with the other user options.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum ResultsLayout {
+    #[default]
+    List, // One row per image, groups separated by lines
+    Cards, // One card per group, thumbnails in a wrapping strip
+}
+
+impl ResultsLayout {
+    pub fn to_key(self) -> &'static str {
+        match self {
+            ResultsLayout::List => "list",
+            ResultsLayout::Cards => "cards",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<ResultsLayout> {
+        match key {
+            "list" => Some(ResultsLayout::List),
+            "cards" => Some(ResultsLayout::Cards),
+            _ => None,
+        }
+    }
+}
+
+// How tightly the list layout packs its rows. Persisted like the layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum ResultsDensity {
+    #[default]
+    Comfortable, // Full-size thumbnails beside their metadata and buttons
+    Compact, // One line per file under a header per group, actions in a context menu
+}
+
+impl ResultsDensity {
+    pub fn to_key(self) -> &'static str {
+        match self {
+            ResultsDensity::Comfortable => "comfortable",
+            ResultsDensity::Compact => "compact",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<ResultsDensity> {
+        match key {
+            "comfortable" => Some(ResultsDensity::Comfortable),
+            "compact" => Some(ResultsDensity::Compact),
+            _ => None,
+        }
+    }
+}
+
+// A line of the compact list.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum CompactRow {
+    Header(usize), // Index into group_ends, i.e. the nth shown group
+    Member(usize), // Index into rows
+}
+
+// The results a refined search (see OutputPhase::refine()) drilled down from,
+// carried through the search so they can be gone back to without searching
+// again.
+pub struct FullResults {
+    opts: UserOpts,
+    results: SearchResults,
+    reviews: Reviews,
+    history: Vec<HistoryEntry>, // Including what's done to the refined results
+    folder: PathBuf, // Being refined
+}
+
+impl FullResults {
+    // Display preferences are global, so they're kept from the current options.
+    fn restore(self, current: &UserOpts) -> OutputPhase {
+        let mut opts = self.opts;
+        opts.layout = current.layout;
+        opts.density = current.density;
+        opts.presets = current.presets.clone();
+        opts.recent_roots = current.recent_roots.clone();
+        OutputPhase::new(opts, self.results).with_reviews(self.reviews).with_history(self.history)
+    }
+
+    // Returns the phase to go back to, if asked.
+    pub fn draw_banner(refining: &mut Option<FullResults>, ui: &mut egui::Ui, current: &UserOpts) -> Option<DynPhase> {
+        let full = refining.as_ref()?;
+        let mut back = false;
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(tr!("Refining previous search")).strong().color(ui.visuals().hyperlink_color));
+            ui.monospace(full.folder.display().to_string());
+            back = ui.button(tr!("Back to full results"))
+                .on_hover_text(tr!("Results for {root}, as they were", root = full.opts.root.display()))
+                .clicked();
+        });
+        ui.separator();
+        back.then(|| refining.take().unwrap().restore(current).into_dyn())
+    }
+}
+
+// Groups by whether their members share a folder. Same-folder duplicates are
+// usually a download or copy made twice, and safe to clean up; cross-folder
+// ones need more thought.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum FolderSpread {
+    #[default]
+    All, // As a filter, either
+    Same,
+    Cross,
+}
+
+impl FolderSpread {
+    const ALL: [FolderSpread; 3] = [FolderSpread::All, FolderSpread::Same, FolderSpread::Cross];
+
+    fn label(self) -> &'static str {
+        match self {
+            FolderSpread::All => tr!("All groups"),
+            FolderSpread::Same => tr!("Same-folder groups"),
+            FolderSpread::Cross => tr!("Cross-folder groups"),
+        }
+    }
+}
+
+// Groups by how far their review has got.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum ReviewFilter {
+    #[default]
+    All,
+    HideReviewed, // Only unreviewed groups
+    NeedsAttention,
+}
+
+// Which groups are shown. Every set criterion must match.
+#[derive(Default)]
+struct ResultsFilter {
+    folder: Option<PathBuf>, // Only groups with a member somewhere under this folder
+    content: Option<ContentKind>, // Only groups with a member of this kind
+    untagged: bool, // Only groups with no Finder-tagged members
+    spread: FolderSpread,
+    review: ReviewFilter,
+    derivatives: bool, // Also groups of just an original and its intentional derivatives
+    variants: bool, // Only groups of resolution variants
+    junk: bool, // Also groups of system images
+}
+
+// What's shown in a group's controls, under its last image in the list
+// layout.
+struct GroupControls<'a> {
+    burst: Option<(usize, bool)>, // Size, and whether it's expanded, if the group is a burst
+    large: bool, // Over UserOpts::large_group, so shown a page at a time
+    encoding: Option<&'a str>, // Badge, if members' encodings differ
+    variants: bool, // The group is resolution variants
+    hook: bool, // A post-search hook is configured
+}
+
+// Where an image is in the results, for screen readers. All counted from 1.
+struct Place {
+    group: usize,
+    member: usize,
+    count: usize, // Members in the group
+}
+
+// Something done to a whole group from one of its controls.
+enum GroupAction {
+    Ignore,
+    ToggleBurst, // Expand or collapse
+    Focus(PathBuf), // Pointer is over a member
+    Click(Click, PathBuf), // On a member's thumbnail
+    Open(PathBuf),
+    Reveal(PathBuf), // In the file manager
+    QuickLook(PathBuf),
+    RunHook,
+    Refine, // Re-scan the group's folder with other settings
+    CompareExternally(PathBuf, PathBuf),
+    Mark(Mark),
+    Note(String),
+    PinKeeper(PathBuf), // Or unpin it, if it's the pinned one
+    ShowMore, // Another page of a large group
+    CollapseLarge,
+    KeepFirst, // Trash every other member, without showing them
+    MarkJunk, // Add to the user's system images
+    UnmarkJunk, // Remove from them
+    Exclude(PathBuf), // Take the member out of the group
+}
+
+// What the errors window asked to have retried.
+enum RetryRequest {
+    All,
+    One(PathBuf),
+}
+
+// The rename window's state; see rename.
+struct RenameDialog {
+    template: String,
+    keepers: bool, // Renames each group's keeper, rather than the selected image
+    renames: Vec<Rename>, // Preview, as of when the template or scope last changed
+    error: Option<String>, // From parsing the template
+}
+
+// The move window's state; see move_files.
+struct MoveDialog {
+    dest: Option<PathBuf>,
+    keep_structure: bool, // Keeps the folders files are in under the root
+    policy: CollisionPolicy,
+    preview: Option<ActionPlan>, // As of when the settings last changed; None without a destination
+}
+
+// Which errors the errors window shows. Shared with its deferred viewport,
+// which can't borrow from the phase.
+struct ErrorFilter {
+    kinds: HashSet<ErrorKind>,
+    text: String, // Case-insensitive, in the path or message
+    action: Option<(String, HashSet<PathBuf>)>, // Only the errors of an action in the history, by its description and the files that failed
+}
+
+impl Default for ErrorFilter {
+    fn default() -> ErrorFilter {
+        ErrorFilter{kinds: ErrorKind::ALL.into_iter().collect(), text: String::new(), action: None}
+    }
+}
+
+impl ErrorFilter {
+    fn matches(&self, err: &SearchError) -> bool {
+        if !self.kinds.contains(&err.kind) {
+            return false;
+        }
+        if let Some((_, paths)) = &self.action {
+            if !err.path.as_ref().is_some_and(|x| paths.contains(x)) {
+                return false;
+            }
+        }
+        let text = self.text.to_lowercase();
+        text.is_empty()
+            || err.msg.to_lowercase().contains(&text)
+            || err.path.as_ref().is_some_and(|x| x.to_string_lossy().to_lowercase().contains(&text))
+    }
+}
+
+// The errors window's. It's deferred, so it only repaints for its own input,
+// or when asked to by id.
+pub fn errors_viewport() -> egui::viewport::ViewportId {
+    egui::viewport::ViewportId::from_hash_of("error window")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum View {
+    Results,
+    Folders, // Wasted space by folder
+}
 
 
 pub struct OutputPhase {
     opts: UserOpts,
     first_update: bool,
     images: Vec<Vec<Image>>, // [set of duplicates][duplicate in set]
-    flattened_images: Vec<Image>,
-    last_indices: HashSet<usize>, // Index in flattened_images of last image in hash bucket
-    errors: Vec<String>,
+    shown_groups: Vec<usize>, // Indices in images of the groups passing the filter
+    rows: Vec<(usize, usize)>, // List layout rows, as (group, member) indices into images
+    group_ends: Vec<usize>, // Sorted indices in rows of the last row of each shown group
+    lines: Vec<Range<usize>>, // Ranges of rows drawn side by side, for lines_per per line
+    lines_per: usize, // 0 if lines needs rebuilding
+    compact_rows: Vec<CompactRow>, // The same rows, with group headers, for the compact list
+    top_row: usize, // Index in rows of the first drawn in the list, for switching density
+    scroll_to_row: Option<usize>, // Index in rows to bring to the top on the next draw
+    filter: ResultsFilter,
+    view: View,
+    folder_waste: Vec<(PathBuf, usize)>, // Wasted bytes per folder, largest first
+    folders: FolderBrowser, // The results' folders, for the panel beside them
+    linked_ids: HashSet<FileId>, // Files that appear more than once in a group via hard links
+    link_outcomes: Option<Vec<LinkOutcome>>, // Results of the last hard link pass, until dismissed
+    trashed: Option<(usize, u64, usize)>, // Files, their bytes, and sidecars the last trash action moved, until dismissed
+    errors: Vec<SearchError>,
     show_errors: Arc<AtomicBool>,
+    broken: Vec<BrokenImage>, // Empty, cut short or flat files the search found; see broken
+    index: HashIndex, // Every file hashed, so retried files can be matched against them
+    retry: Option<JoinHandle<RetryResults>>, // Running retry of failed files
+    retry_requested: Arc<Mutex<Option<RetryRequest>>>, // Set by the errors viewport
+    error_filter: Arc<Mutex<ErrorFilter>>,
+    session_dirty: bool, // Results have changed since the session was last saved
+    stats: Option<SearchStats>,
+    undo: Vec<UndoEntry>, // Most recent last
+    history: Vec<HistoryEntry>, // Everything done to the results, most recent last; see history
+    ignore_list: IgnoreList,
+    ignored: Vec<Vec<Image>>, // Groups from these results hidden by the ignore list
+    show_ignored: bool, // Ignore list management window
+    exclusions: ExclusionList, // Single files taken out of their groups
+    excluded: Vec<Vec<Image>>, // Groups the exclusions took files out of, whole; see reapply_exclusions()
+    show_exclusions: bool, // Exclusion list management window
+    junk_list: JunkList, // The user's system image signatures; the built-in ones are checked too
+    junk_groups: Vec<Option<JunkSource>>, // Parallel to images; which list, if any, says the group is system images
+    bursts: Vec<bool>, // Parallel to images; whether each group looks like a burst
+    expanded_bursts: HashSet<PathBuf>, // By first member, so it survives groups moving
+    large_pages: HashMap<PathBuf, usize>, // Pages of each large group shown, by first member like expanded_bursts
+    variants: Vec<Variants>, // Parallel to images; whether each group is one picture at different resolutions
+    encoding_badges: Vec<Option<String>>, // Parallel to images; how members' encodings differ, if they do
+    keepers: HashSet<PathBuf>, // Best-encoded member of each group with a badge, or the original of a renamed copy
+    keeper_preview: Vec<Option<usize>>, // Parallel to images; the member a pin or UserOpts::keeper_strategy keeps
+    preview_reclaim: usize, // Bytes applying the previewed keepers would free
+    same_folder: Vec<bool>, // Parallel to images; whether all members share a folder
+    renamed: HashSet<PathBuf>, // Named like an automatic copy of another member in its folder
+    derivatives: HashSet<PathBuf>, // Named like an intentional derivative of another member; see UserOpts::derivative_patterns()
+    derivative_groups: Vec<bool>, // Parallel to images; whether all members but one are derivatives
+    distances: HashMap<PathBuf, u32>, // Hash distance from the rest of its group; see hash_distances()
+    group_sizes: Vec<GroupSizes>, // Parallel to images
+    gains: HashMap<PathBuf, usize>, // Bytes removing each member would free, where that's known; see group_sizes()
+    watch: Option<Watch>, // Watching the roots for new duplicates
+    watch_new: HashSet<PathBuf>, // Added by the watch, highlighted
+    live: Option<LiveSearch>, // The search, still running to fill these results in; see new_live()
+    live_new: HashSet<PathBuf>, // Found by the running search since the results were shown, highlighted until clicked
+    regrouped: usize, // Reviewed groups that gained files since they were marked, until dismissed
+    dry_run: bool, // Bulk actions only show what they would do
+    plan: Option<ActionPlan>, // From a dry run, being shown
+    sensitive_root: bool, // A root is a system folder, drive or home; bulk actions spare system files
+    focused: Option<PathBuf>, // Image last under the pointer, for Quick Look's Space key
+    selected: Option<PathBuf>, // Picked by clicking; highlighted, and what Space previews over the focused one
+    pending_click: Option<(PathBuf, Instant)>, // A single click, until it's clear it isn't a double-click
+    preview: Option<Image>, // Shown in the in-app preview window
+    preview_uri: Option<String>, // Of the full-size image it last loaded, to free when it's done with
+    thumbnails: Thumbnails,
+    report: Option<ReportJob>, // HTML report being written
+    quick_look: QuickLook,
+    finder_tags: HashMap<PathBuf, Vec<FinderTag>>, // Read once per file; only on macOS
+    hook: Option<HookJob>, // Post-search hook running on groups the user picked
+    hook_outcomes: Option<Vec<HookOutcome>>, // Of the last hook run, until dismissed
+    reviews: Reviews,
+    bulk_shown_only: bool, // Bulk actions only touch the groups the filter shows
+    refining: Option<FullResults>, // What these results drilled down from
+    refine: Option<PathBuf>, // Folder to re-scan, once this frame's drawn
+    rename: Option<RenameDialog>, // Open rename window
+    moving: Option<MoveDialog>, // Open move window
 }
 
 impl OutputPhase {
@@ -33,223 +394,5495 @@ impl OutputPhase {
     const CELL_2_TOP_SPACING: f32 = 15.0;
     const CELL_2_BOTTOM_SPACING: f32 = 15.0;
     const CELL_2_DATA_SPACING: f32 = 3.0;
+    const MIN_METADATA_WIDTH: f32 = 2.0 * Self::MIN_CELL_SIZE; // Before a second pair fits on a line
+    const PREVIEW_SIZE: f32 = 400.0; // Hover tooltip
+    const DECODE_HINT_SHARE: f64 = 70.0; // Percent of search time in decoding to suggest speedups
+    const DROPPED_OPACITY: f32 = 0.4; // Thumbnails of members the previewed keepers would trash
 
-    pub fn new(opts: UserOpts, images: Vec<Vec<Image>>, errors: Vec<String>) -> OutputPhase {
-        let last_indices = images.iter()
-            .scan(Wrapping(usize::MAX), |total, dups| {*total += dups.len(); Some(total.0)})
-            .collect();
+    // Card layout. Heights are estimates used for culling; a card is always
+    // given exactly its estimated height so the scroll math stays consistent.
+    const CARD_THUMB_SIZE: f32 = 150.0;
+    const CARD_CELL_HEIGHT: f32 = 250.0; // Thumbnail plus metadata and buttons
+    const CARD_HEADER_HEIGHT: f32 = 24.0;
+    const CARD_MARGIN: f32 = 8.0;
+    const CARD_GAP: f32 = 10.0;
+
+    // Review controls
+    const NOTE_WIDTH: f32 = 140.0;
+
+    // Compact list
+    const COMPACT_THUMB_SIZE: f32 = 32.0;
+    const COMPACT_ROW_HEIGHT: f32 = 36.0;
+    const COMPACT_INDENT: f32 = 16.0;
+
+    // Often enough for the running search's progress to look live; new
+    // results wake the UI themselves.
+    const LIVE_INTERVAL: Duration = Duration::from_millis(500);
+
+    // By-folder view
+    const MAX_FOLDER_BARS: usize = 20;
+    const FOLDER_BAR_WIDTH: f32 = 400.0;
+    const FOLDER_BAR_HEIGHT: f32 = 16.0;
+    const FOLDER_PANEL_WIDTH: f32 = 240.0;
+
+    // History panel
+    const HISTORY_PANEL_HEIGHT: f32 = 160.0;
+
+    const BROKEN_LIST_HEIGHT: f32 = 200.0; // Scrolls past this
+    const HISTORY_GROUP_IDS: usize = 3; // Listed before the rest are left to the tooltip
+
+    pub fn new(opts: UserOpts, results: SearchResults) -> OutputPhase {
+        let SearchResults{duplicates, mut errors, broken, index, stats} = results;
+        let thumbnails = Thumbnails::new(CacheLimits::new(opts.thumb_memory_mb));
+
+        // Filtered here, rather than in the searcher, since it's a matter of
+        // presentation; the index still has everything.
+        let ignore_list = IgnoreList::load().unwrap_or_else(|e| {
+            errors.push(SearchError::new(ErrorKind::Io, None, e));
+            IgnoreList::default()
+        });
+        let junk_list = JunkList::load().unwrap_or_else(|e| {
+            errors.push(SearchError::new(ErrorKind::Io, None, e));
+            JunkList::default()
+        });
+        let exclusions = ExclusionList::load().unwrap_or_else(|e| {
+            errors.push(SearchError::new(ErrorKind::Io, None, e));
+            ExclusionList::default()
+        });
+        let (ignored, images): (Vec<_>, Vec<_>) = duplicates.into_iter()
+            .partition(|group| ignore_list.matches(group));
+        let (mut images, excluded) = exclusions.apply(images, Namespace::new(index.alg));
+
+        for group in &mut images {
+            Self::sort_group(group, opts.burst_window());
+        }
 
-        OutputPhase {
+        let mut phase = OutputPhase {
             opts,
             first_update: true,
-            flattened_images: images.iter().flat_map(|x| x.clone()).collect(),
-            last_indices,
+            shown_groups: vec![],
+            rows: vec![],
+            group_ends: vec![],
+            lines: vec![],
+            lines_per: 0,
+            compact_rows: vec![],
+            top_row: 0,
+            scroll_to_row: None,
+            filter: ResultsFilter::default(),
+            view: View::Results,
+            folder_waste: vec![],
+            folders: FolderBrowser::default(),
+            linked_ids: HashSet::new(),
+            link_outcomes: None,
+            trashed: None,
             images,
             errors,
             show_errors: Arc::new(AtomicBool::new(true)),
-        }
+            broken,
+            index,
+            retry: None,
+            retry_requested: Arc::new(Mutex::new(None)),
+            error_filter: Arc::new(Mutex::new(ErrorFilter::default())),
+            session_dirty: true,
+            stats,
+            undo: vec![],
+            history: vec![],
+            ignore_list,
+            ignored,
+            show_ignored: false,
+            exclusions,
+            excluded,
+            show_exclusions: false,
+            junk_list,
+            junk_groups: vec![],
+            bursts: vec![],
+            expanded_bursts: HashSet::new(),
+            large_pages: HashMap::new(),
+            variants: vec![],
+            encoding_badges: vec![],
+            keepers: HashSet::new(),
+            keeper_preview: vec![],
+            preview_reclaim: 0,
+            same_folder: vec![],
+            renamed: HashSet::new(),
+            derivatives: HashSet::new(),
+            derivative_groups: vec![],
+            distances: HashMap::new(),
+            group_sizes: vec![],
+            gains: HashMap::new(),
+            watch: None,
+            watch_new: HashSet::new(),
+            live: None,
+            live_new: HashSet::new(),
+            regrouped: 0,
+            dry_run: false,
+            plan: None,
+            sensitive_root: false,
+            focused: None,
+            selected: None,
+            pending_click: None,
+            preview: None,
+            preview_uri: None,
+            thumbnails,
+            report: None,
+            quick_look: QuickLook::default(),
+            finder_tags: HashMap::new(),
+            hook: None,
+            hook_outcomes: None,
+            reviews: Reviews::default(),
+            bulk_shown_only: false,
+            refining: None,
+            refine: None,
+            rename: None,
+            moving: None,
+        };
+        let home = homedir::get_my_home().ok().flatten();
+        phase.sensitive_root = is_sensitive_root(&phase.opts.root, home.as_deref())
+            || (phase.opts.compare && is_sensitive_root(&phase.opts.reference, home.as_deref()));
+        phase.rebuild_index();
+        phase
     }
 
-    pub fn into_dyn(self) -> DynPhase {
-        Box::new(self)
+    // Results to review while the search is still running, filled in as it
+    // finds more; see poll_live().
+    pub fn new_live(opts: UserOpts, live: LiveSearch) -> OutputPhase {
+        let alg = opts.hash;
+        let mut phase = OutputPhase::new(opts, SearchResults::restored(vec![], alg));
+        phase.live = Some(live);
+        phase
     }
 
-    fn draw_output_row(&self, ui: &mut egui::Ui, image: &Image, last_in_group: bool) -> Result<()> {
-
-        // It shouldn't be (reasonably) possible to clobber one Some() modal
-        // with another; see comment in draw_output_table().
-        let mut ret = Ok(());
+    // A collapsed burst shows its earliest shot; anything else, its closest
+    // matches first.
+    fn sort_group(group: &mut Vec<Image>, window: Option<Duration>) {
+        if window.is_some_and(|x| is_burst(group, x)) {
+            sort_burst(group);
+        } else {
+            sort_by_distance(group);
+        }
+    }
 
-        let resp = ui.centered_and_justified(|ui| {
-            let resp = ui.add(egui::widgets::ImageButton::new(egui::Image::from_bytes(
-                image.path.display().to_string(),
-                image.buffer.clone()
-            )));
-            if last_in_group {
-                ui.separator();
-            }
-            resp
-        });
+    // Back to the form, still refining if these are refined results.
+    fn new_search(&mut self) -> DynPhase {
+        if let Some(live) = self.live.take() {
+            live.cancel();
+        }
+        StartupPhase::new_with_opts(self.opts.take()).with_refining(self.refining.take()).into_dyn()
+    }
 
-        if resp.inner.clicked() {
-            if let Err(e) = opener::open(&image.path) {
-                ret = Err(Error::new(
-                    "Error showing file".to_string(),
-                    e.to_string(),
-                ));
-            }
+    // Refined results pick up the full results' history, and add to it.
+    pub fn with_refining(mut self, refining: Option<FullResults>) -> OutputPhase {
+        if let Some(full) = &refining {
+            self.history = full.history.clone();
         }
+        self.refining = refining;
+        self
+    }
 
-        ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
-            let stripped = image.path.strip_prefix(&self.opts.root).unwrap_or(&image.path);
+    // What earlier sittings did to these results.
+    pub fn with_history(mut self, history: Vec<HistoryEntry>) -> OutputPhase {
+        self.history = history;
+        self
+    }
 
-            ui.add_space(Self::CELL_2_TOP_SPACING);
+    fn record(&mut self, entry: HistoryEntry) {
+        if let Some(full) = &mut self.refining {
+            full.history.push(entry.clone());
+        }
+        self.history.push(entry);
+        self.session_dirty = true;
+    }
 
-            ui.label(
-                egui::RichText::new(stripped.display().to_string())
-                    .monospace()
-                    .size(Self::HEADER_SIZE)
-            );
-            ui.add_space(Self::CELL_2_DATA_SPACING);
+    // The IDs of the groups with any of the files in them.
+    fn group_ids_with(&self, paths: &HashSet<&PathBuf>) -> Vec<String> {
+        self.images.iter()
+            .filter(|group| group.iter().any(|x| paths.contains(&x.path)))
+            .map(|group| group_id(group))
+            .collect()
+    }
 
-            if let Some((width, height)) = image.dimm {
-                ui.label(format!("{width}×{height}"));
-                ui.add_space(Self::CELL_2_DATA_SPACING);
-            }
-            ui.label(format_size(image.file_size, DECIMAL));
+    // Back to the form, to search just the folder again with other settings,
+    // keeping these results to come back to. Refining refined results still
+    // comes back to the first. Drilling into one folder looks for duplicates
+    // within it, so compare mode is turned off.
+    fn refine(&mut self, folder: PathBuf) -> DynPhase {
+        let mut opts = self.opts.clone();
+        opts.root = folder.clone();
+        opts.subfolders.clear();
+        opts.compare = false;
+        let full = self.take_full(folder);
+        StartupPhase::new_with_opts(opts).with_refining(Some(full)).into_dyn()
+    }
 
-            ui.with_layout(egui::Layout::bottom_up(egui::Align::Min), |ui| {
-                let sep_height = if last_in_group {
-                    ui.separator().rect.height().clamp(0.0, Self::CELL_2_BOTTOM_SPACING)
-                } else {
-                    0.0
+    // The results to come back to from refining the folder, leaving this
+    // phase empty.
+    fn take_full(&mut self, folder: PathBuf) -> FullResults {
+        match self.refining.take() {
+            Some(full) => FullResults{folder, ..full},
+            None => {
+                self.restore_excluded();
+                let mut duplicates = std::mem::take(&mut self.images);
+                duplicates.append(&mut self.ignored);
+                let results = SearchResults {
+                    duplicates,
+                    errors: std::mem::take(&mut self.errors),
+                    broken: std::mem::take(&mut self.broken),
+                    index: std::mem::replace(&mut self.index, HashIndex::new(self.opts.hash)),
+                    stats: self.stats.take(),
                 };
-                ui.add_space(Self::CELL_2_BOTTOM_SPACING - sep_height);
-                ui.horizontal(|ui| {
-                    let err = if ui.button("Open").clicked() {
-                        opener::open(&image.path)
-                    } else if ui.button("Show").clicked() {
-                        opener::reveal(&image.path)
-                    } else {
-                        Ok(())
-                    };
+                FullResults {
+                    opts: self.opts.take(),
+                    results,
+                    reviews: std::mem::take(&mut self.reviews),
+                    history: std::mem::take(&mut self.history),
+                    folder,
+                }
+            },
+        }
+    }
 
-                    if let Err(e) = err {
-                        ret = Err(Error::new(
-                                "Error showing file".to_string(),
-                                e.to_string(),
-                        ));
-                    }
+    // The folder to refine a group in: the deepest holding all of it, but no
+    // further up than what was searched, so a group spanning the root and
+    // reference folder, say, doesn't re-scan the whole drive above them. Then
+    // it's the searched folder holding the most of the group.
+    fn refine_folder(&self, group: &[Image]) -> Option<PathBuf> {
+        let folder = common_folder(group)?;
+        let mut roots = vec![&self.opts.root];
+        if self.opts.compare {
+            roots.push(&self.opts.reference);
+        }
+        if roots.iter().any(|x| folder.starts_with(x)) {
+            return Some(folder);
+        }
+        roots.into_iter()
+            .max_by_key(|root| group.iter().filter(|x| x.path.starts_with(root)).count())
+            .cloned()
+    }
+
+    // Picks up where an earlier review of these results left off.
+    pub fn with_reviews(mut self, reviews: Reviews) -> OutputPhase {
+        self.reviews = reviews;
+        self.refresh_keepers();
+        self.apply_filter();
+        self
+    }
+
+    // Recomputes the previewed keepers; call after changing the strategy or a
+    // pin. Nothing's done about them until apply_keepers().
+    fn refresh_keepers(&mut self) {
+        let strategy = self.opts.keeper_strategy;
+        self.keeper_preview = self.images.iter()
+            .map(|group| self.reviews.pinned(group).or_else(|| strategy.pick(group, &self.keepers)))
+            .collect();
+        self.preview_reclaim = self.images.iter()
+            .zip(&self.keeper_preview)
+            .filter_map(|(group, keeper)| Some(keepers::reclaimed(group, (*keeper)?)))
+            .sum();
+    }
+
+    // Whether the image would be kept, if its group has a keeper previewed.
+    fn kept(&self, group_idx: usize, image: &Image) -> Option<bool> {
+        let keeper = self.keeper_preview.get(group_idx).copied().flatten()?;
+        Some(self.images[group_idx][keeper].path == image.path)
+    }
+
+    // Recomputes everything derived from images; call after modifying them.
+    fn rebuild_index(&mut self) {
+        self.session_dirty = true;
+        let window = self.opts.burst_window();
+        self.bursts = self.images.iter()
+            .map(|group| window.is_some_and(|x| is_burst(group, x)))
+            .collect();
+        self.same_folder = self.images.iter().map(|x| same_folder(x)).collect();
+        self.refresh_junk();
+        self.variants = self.images.iter()
+            .map(|group| resolution_variants(group, self.opts.variant_factor))
+            .collect();
+        let patterns = self.opts.derivative_patterns();
+        self.derivatives.clear();
+        self.derivative_groups = self.images.iter()
+            .map(|group| {
+                let derived = derivative_members(group, &patterns);
+                self.derivatives.extend(group.iter().zip(&derived).filter(|(_, x)| **x).map(|(x, _)| x.path.clone()));
+                derived.iter().filter(|x| !**x).count() == 1
+            })
+            .collect();
+        self.keepers.clear();
+        self.renamed.clear();
+        self.encoding_badges = self.images.iter()
+            .map(|group| {
+                let renamed = renamed_copies(group);
+                self.renamed.extend(group.iter().zip(&renamed).filter(|(_, x)| **x).map(|(x, _)| x.path.clone()));
 
-                    if ui.button("Copy path").clicked() {
-                        ui.output_mut(|out| 
-                            out.copied_text = image.path.as_os_str().to_string_lossy().to_string()
-                        );
+                // With nothing better to go by, the original name is the one
+                // to keep.
+                let encodings: Vec<_> = group.iter().map(|x| x.encoding.as_ref()).collect();
+                let Some((badge, best)) = encoding::compare_group(&encodings) else {
+                    if let Some(copy) = renamed.iter().position(|x| *x) {
+                        let original = prefer_original(group, copy, &renamed);
+                        if original != copy {
+                            self.keepers.insert(group[original].path.clone());
+                        }
                     }
-                });
-            });
-        });
+                    return None;
+                };
+                self.keepers.insert(group[prefer_original(group, best, &renamed)].path.clone());
+                Some(badge)
+            })
+            .collect();
+        self.distances = self.images.iter()
+            .flat_map(|group| group.iter().zip(hash_distances(group)))
+            .filter_map(|(image, distance)| Some((image.path.clone(), distance?)))
+            .collect();
+        self.group_sizes = self.images.iter().map(|x| group_sizes(x)).collect();
+        self.gains = self.images.iter()
+            .zip(&self.group_sizes)
+            .flat_map(|(group, sizes)| group.iter().zip(&sizes.gains))
+            .filter_map(|(image, gain)| Some((image.path.clone(), (*gain)?)))
+            .collect();
+        self.refresh_keepers();
+        if finder_tags::SUPPORTED {
+            for image in self.images.iter().flatten() {
+                if !self.finder_tags.contains_key(&image.path) {
+                    let tags = finder_tags::read(&image.path).unwrap_or_default();
+                    self.finder_tags.insert(image.path.clone(), tags);
+                }
+            }
+        }
+        self.rebuild_folder_waste();
+        let mut roots = vec![self.opts.root.as_path()];
+        if self.opts.compare {
+            roots.push(&self.opts.reference);
+        }
+        self.folders = FolderBrowser::new(&roots, &self.images);
+        self.apply_filter();
 
-        ret
+        self.linked_ids.clear();
+        for group in &self.images {
+            let mut seen = HashSet::new();
+            for id in group.iter().filter_map(|x| x.file_id) {
+                if !seen.insert(id) {
+                    self.linked_ids.insert(id);
+                }
+            }
+        }
     }
 
-    // Actually draws multiple tables, one per set of duplicates, but it looks
-    // like one big table with multiple sections. Also draws all errors reported
-    // by Searcher.
-    fn draw_output_table(&mut self, ui: &mut egui::Ui) -> Result<()> {
+    // Which groups are system images, unless the filter's off; call after
+    // changing the list.
+    fn refresh_junk(&mut self) {
+        let namespace = Namespace::new(self.index.alg);
+        self.junk_groups = self.images.iter()
+            .map(|group| match self.opts.junk_filter {
+                true => self.junk_list.matches(group, namespace),
+                false => None,
+            })
+            .collect();
+    }
 
-        // These errors are only in response to user actions, and it would be
-        // inhuman to produce more than one event per update, so I'm assuming a
-        // Some() ret will never be clobbered. These functions don't update any
-        // critical state, so if they somehow did, and the first had an error,
-        // the worst that would happen is they would try again.
-        let mut ret = Ok(());
+    fn toggle_junk(&mut self) {
+        self.filter.junk = !self.filter.junk;
+        self.apply_filter();
+    }
 
-        let mut scroll = egui::ScrollArea::vertical().drag_to_scroll(false);
+    fn mark_junk(&mut self, group_idx: usize) -> Result<()> {
+        let group = &self.images[group_idx];
+        let Some(signature) = JunkSignature::new(group, Namespace::new(self.index.alg)) else {
+            return Err(Error::new("Error marking system images".to_string(), "The group wasn't hashed".to_string()));
+        };
+        let id = group_id(group);
+        self.junk_list.signatures.push(signature);
+        if let Err(e) = self.junk_list.save() {
+            self.junk_list.signatures.pop();
+            return Err(Error::new("Error saving system image list".to_string(), e));
+        }
+        self.record(HistoryEntry::new("Mark group as system images".to_string(), vec![id]));
+        self.refresh_junk();
+        self.apply_filter();
+        Ok(())
+    }
 
-        // Scroll offset is persistent, and I can't find a way to opt-out for
-        // a single widget. This overrides it manually.
-        if self.first_update {
-            scroll = scroll.vertical_scroll_offset(0.0);
-            self.first_update = false;
+    fn unmark_junk(&mut self, group_idx: usize) -> Result<()> {
+        let group = &self.images[group_idx];
+        let before = self.junk_list.signatures.clone();
+        if !self.junk_list.remove(group, Namespace::new(self.index.alg)) {
+            return Ok(());
+        }
+        let id = group_id(group);
+        if let Err(e) = self.junk_list.save() {
+            self.junk_list.signatures = before;
+            return Err(Error::new("Error saving system image list".to_string(), e));
         }
+        self.record(HistoryEntry::new("Unmark group as system images".to_string(), vec![id]));
+        self.refresh_junk();
+        self.apply_filter();
+        Ok(())
+    }
 
-        let total_rows = self.flattened_images.len();
-        scroll.show_rows(ui, Self::MIN_CELL_SIZE, total_rows, |ui, range| {
-            egui::Grid::new(0)
-                .striped(true)
-                .min_col_width(Self::MIN_CELL_SIZE)
-                .min_row_height(Self::MIN_CELL_SIZE)
-                .spacing((Self::H_SPACING, ui.spacing().item_spacing.y))
-                .num_columns(2)
-                .show(ui, |ui| {
+    // Recomputes which groups are shown; call after changing the filter. Doesn't
+    // touch the results themselves.
+    fn apply_filter(&mut self) {
+        self.shown_groups = (0..self.images.len())
+            .filter(|idx| self.group_matches(*idx))
+            .collect();
+        let sizes: Vec<usize> = self.shown_groups.iter()
+            .map(|idx| self.visible_members(*idx).len())
+            .collect();
+        self.rows = self.shown_groups.iter()
+            .zip(&sizes)
+            .flat_map(|(group, size)| (0..*size).map(|member| (*group, member)))
+            .collect();
+        self.group_ends = group_ends(sizes);
+        self.compact_rows = compact_rows(&self.group_ends);
+        self.lines_per = 0;
+    }
 
-                for idx in range {
-                    let last = self.last_indices.contains(&idx);
-                    if let Err(m) = self.draw_output_row(ui, &self.flattened_images[idx], last) {
-                        ret = Err(m);
-                    }
-                    ui.end_row();
-                }
-            });
-        });
+    fn is_collapsed(&self, group_idx: usize) -> bool {
+        self.bursts[group_idx] && !self.expanded_bursts.contains(&self.images[group_idx][0].path)
+    }
 
-        ret
+    // Too big to lay out every member of; see UserOpts::large_group.
+    fn is_large(&self, group_idx: usize) -> bool {
+        self.opts.large_group > 0 && self.images[group_idx].len() > self.opts.large_group
     }
 
-    fn draw_errors(&mut self, ctx: &egui::Context) {
-        if self.errors.is_empty() || !self.show_errors.load(Ordering::Relaxed) {
-            return;
+    // The members drawn for the group: just the first if it's a collapsed
+    // burst or large group, and of an expanded large group, the pages asked
+    // for so far. The rows are built from these, so members not shown cost
+    // nothing to lay out.
+    fn visible_members(&self, group_idx: usize) -> &[Image] {
+        let group = &self.images[group_idx];
+        if self.is_collapsed(group_idx) {
+            return &group[..1];
+        }
+        if self.is_large(group_idx) {
+            let pages = self.large_pages.get(&group[0].path).copied().unwrap_or(0);
+            return &group[..(pages * self.opts.large_group).clamp(1, group.len())];
         }
+        group
+    }
 
-        let vb = egui::viewport::ViewportBuilder::default().with_title("Errors");
-        let vid = egui::viewport::ViewportId::from_hash_of("error window");
-        let show_errors = self.show_errors.clone();
-        let errors = self.errors.clone();
-        ctx.show_viewport_deferred(vid, vb, move |ctx, _| {
-            egui::CentralPanel::default().show(ctx, |ui| {
-                if ctx.input(|i| i.viewport().close_requested()) {
-                    show_errors.store(false, Ordering::Relaxed);
-                    return;
-                }
+    fn show_more(&mut self, group_idx: usize) {
+        *self.large_pages.entry(self.images[group_idx][0].path.clone()).or_default() += 1;
+        self.apply_filter();
+    }
 
-                egui::ScrollArea::vertical().drag_to_scroll(false).show(ui, |ui| {
-                    ui.heading(egui::RichText::new("Errors").color(egui::Color32::RED));
-                    for err in &errors {
-                        ui.label(err);
-                    }
-                });
-            });
-        });
+    fn collapse_large(&mut self, group_idx: usize) {
+        self.large_pages.remove(&self.images[group_idx][0].path);
+        self.apply_filter();
     }
-}
 
-impl Phase for OutputPhase {
-    fn render(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
-        let resp = ui.horizontal(|ui| {
-            if ui.button("<- New Search").clicked() {
-                return Some(StartupPhase::new_with_opts(self.opts.take()).into_dyn());
+    fn toggle_burst(&mut self, group_idx: usize) {
+        let first = self.images[group_idx][0].path.clone();
+        if !self.expanded_bursts.remove(&first) {
+            self.expanded_bursts.insert(first);
+        }
+        self.apply_filter();
+    }
+
+    fn group_matches(&self, group_idx: usize) -> bool {
+        let group = &self.images[group_idx];
+        let spread_matches = match self.filter.spread {
+            FolderSpread::All => true,
+            FolderSpread::Same => self.same_folder[group_idx],
+            FolderSpread::Cross => !self.same_folder[group_idx],
+        };
+        spread_matches
+            && self.filter.folder.as_ref()
+            .is_none_or(|folder| group.iter().any(|x| x.path.starts_with(folder)))
+            && self.filter.content.is_none_or(|content| group.iter().any(|x| x.content == Some(content)))
+            && (!self.filter.untagged || group.iter().all(|x| !self.is_tagged(&x.path)))
+            && (self.filter.derivatives || !self.derivative_groups[group_idx])
+            && (self.filter.junk || self.junk_groups[group_idx].is_none())
+            && (!self.filter.variants || matches!(self.variants[group_idx], Variants::Largest(_)))
+            && match self.filter.review {
+                ReviewFilter::All => true,
+                ReviewFilter::HideReviewed => self.reviews.mark(group) == Mark::Unreviewed,
+                ReviewFilter::NeedsAttention => self.reviews.mark(group) == Mark::NeedsAttention,
             }
+    }
+
+    // Whether the groups could still change in more than size before the
+    // search is done. It only streams exact matches, and each file's
+    // checked against its group's like the search will, so those are only
+    // ever added to; near matches and crops are only found at the end, and
+    // can regroup everything.
+    fn is_provisional(&self) -> bool {
+        self.live.is_some() && (self.opts.max_distance > 0 || self.opts.detect_crops)
+    }
+
+    // Trashing, linking, moving or renaming files while the groups could still
+    // regroup could act on files the search ends up grouping differently, so
+    // none of it's done until the search is. Tags are only labels, so they
+    // can be applied any time.
+    fn check_settled(&self) -> Result<()> {
+        if self.is_provisional() {
+            return Err(Error::new(
+                tr!("Scan still running").to_string(),
+                tr!("Near matches can still regroup until the scan is done, so files can't be trashed, linked, \
+                    moved or renamed until then.").to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // The groups bulk actions apply to, by index into images. None while
+    // they're provisional.
+    fn bulk_groups(&self) -> Vec<usize> {
+        if self.is_provisional() {
+            return vec![];
+        }
+        // Hidden system images are spared either way, since nobody means to
+        // act on them.
+        match self.bulk_shown_only {
+            true => self.shown_groups.clone(),
+            false => (0..self.images.len())
+                .filter(|x| self.filter.junk || self.junk_groups[*x].is_none())
+                .collect(),
+        }
+    }
 
-            ui.strong("Results for");
-            ui.monospace(self.opts.root.display().to_string());
+    fn is_tagged(&self, path: &Path) -> bool {
+        self.finder_tags.get(path).is_some_and(|x| !x.is_empty())
+    }
 
-            None
+    fn draw_content_filter(&mut self, ui: &mut egui::Ui) {
+        let mut content = self.filter.content;
+        let mut spread = self.filter.spread;
+        ui.horizontal(|ui| {
+            ui.label(tr!("Show:"));
+            egui::ComboBox::from_id_source("folder spread filter")
+                .selected_text(spread.label())
+                .show_ui(ui, |ui| {
+                    for x in FolderSpread::ALL {
+                        ui.selectable_value(&mut spread, x, x.label());
+                    }
+                });
+            egui::ComboBox::from_id_source("content filter")
+                .selected_text(content.map_or(tr!("All"), ContentKind::plural_label))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut content, None, tr!("All"));
+                    for kind in ContentKind::ALL {
+                        ui.selectable_value(&mut content, Some(kind), kind.plural_label());
+                    }
+                });
+            if finder_tags::SUPPORTED {
+                let mut untagged = self.filter.untagged;
+                if ui.checkbox(&mut untagged, tr!("Only untagged"))
+                    .on_hover_text(tr!("Hide groups with any file that has a Finder tag"))
+                    .changed() {
+                    self.filter.untagged = untagged;
+                    self.apply_filter();
+                }
+            }
+            let derivative_groups = self.derivative_groups.iter().filter(|x| **x).count();
+            if derivative_groups > 0 {
+                let mut derivatives = self.filter.derivatives;
+                if ui.checkbox(&mut derivatives, tr!("Show intentional derivatives ({count})", count = derivative_groups))
+                    .on_hover_text(tr!("Groups of just an original and copies named by a derivative pattern, e.g. foo.jpg and foo_web.jpg"))
+                    .changed() {
+                    self.filter.derivatives = derivatives;
+                    self.apply_filter();
+                }
+            }
+            let variant_groups = self.variants.iter().filter(|x| matches!(x, Variants::Largest(_))).count();
+            if variant_groups > 0 {
+                let mut variants = self.filter.variants;
+                if ui.checkbox(&mut variants, tr!("Only resolution variants ({count})", count = variant_groups))
+                    .on_hover_text(tr!("Groups of the same picture at different sizes, e.g. an original and a copy downscaled for email"))
+                    .changed() {
+                    self.filter.variants = variants;
+                    self.apply_filter();
+                }
+            }
         });
-        
-        if resp.inner.is_some() {
-            return Ok(resp.inner);
+        if content != self.filter.content || spread != self.filter.spread {
+            self.filter.content = content;
+            self.filter.spread = spread;
+            self.apply_filter();
         }
 
-        // A button to toggle showing the error window (if there were any errors).
-        // I can't figure out where to put the button, and I'm not sure its really
-        // necessary, but I'm leaving it here for the future.
-        /*
-        if !self.errors.is_empty() {
-            ui.add_space(4.0);
-            let old_show_errors = self.show_errors.load(Ordering::Relaxed);
-            let text = if old_show_errors { "Hide Errors" } else { "Show Errors" };
-            if ui.button(text).clicked() {
-                // TODO: change to fetch_not() once stable.
-                self.show_errors.store(!old_show_errors, Ordering::Relaxed);
+        let mut review = self.filter.review;
+        ui.horizontal(|ui| {
+            for (filter, label) in [
+                (ReviewFilter::HideReviewed, tr!("Hide reviewed")),
+                (ReviewFilter::NeedsAttention, tr!("Only needs attention")),
+            ] {
+                if ui.selectable_label(review == filter, label).clicked() {
+                    review = if review == filter { ReviewFilter::All } else { filter };
+                }
             }
+            let reviewed = self.images.iter()
+                .filter(|x| self.reviews.mark(x) != Mark::Unreviewed)
+                .count();
+            ui.label(tr!("{reviewed} of {total} groups reviewed", reviewed = reviewed, total = self.images.len()))
+                .on_hover_text(tr!("Press M to cycle the selected image's group through the marks"));
+            ui.checkbox(&mut self.bulk_shown_only, tr!("Bulk actions: only shown groups"))
+                .on_hover_text(tr!("Trash, link and keep-first only touch the groups the filters show"));
+        });
+        if review != self.filter.review {
+            self.filter.review = review;
+            self.apply_filter();
         }
-        */
+    }
 
-        ui.separator();
+    // The folder a file's wasted bytes are attributed to: its parent, cut off
+    // at UserOpts::folder_depth levels below whichever root it was found under. Files
+    // directly in a root are attributed to the root itself.
+    fn folder_key(&self, path: &Path) -> PathBuf {
+        let parent = path.parent().unwrap_or(path);
+        let roots = [&self.opts.root, &self.opts.reference];
+        let Some(root) = roots.into_iter().find(|x| !x.as_os_str().is_empty() && parent.starts_with(x)) else {
+            return parent.to_path_buf();
+        };
+
+        let rel = parent.strip_prefix(root).unwrap();
+        root.join(rel.components().take(self.opts.folder_depth).collect::<PathBuf>())
+    }
 
-        if self.images.is_empty() {
-            ui.label(format!("Done on {}, found no duplicates", self.opts.root.display()));
+    // Each group's wasted bytes are charged to the folders of every member but
+    // the largest (the one that would be kept), so the totals add up to the
+    // reclaimable total.
+    fn rebuild_folder_waste(&mut self) {
+        let mut totals: HashMap<PathBuf, usize> = HashMap::new();
+        for image in self.images.iter().flat_map(|x| wasted_members(x)) {
+            *totals.entry(self.folder_key(&image.path)).or_default() += image.file_size;
         }
 
-        self.draw_output_table(ui)?;
-        self.draw_errors(ctx);
+        let mut totals: Vec<_> = totals.into_iter().filter(|(_, x)| *x > 0).collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        self.folder_waste = totals;
+    }
 
-        Ok(None)
+    fn folder_label(&self, folder: &Path) -> String {
+        match folder.strip_prefix(&self.opts.root) {
+            Ok(rel) if rel.as_os_str().is_empty() => tr!("(top level)").to_string(),
+            Ok(rel) => rel.display().to_string(),
+            Err(_) => folder.display().to_string(),
+        }
     }
 
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        storage.set_string(ROOT_KEY, self.opts.root.to_string_lossy().into());
+    // The folders from the root down to the given one, for the filter's
+    // breadcrumb.
+    fn breadcrumbs(&self, folder: &Path) -> Vec<PathBuf> {
+        let roots = [&self.opts.root, &self.opts.reference];
+        let Some(root) = roots.into_iter().find(|x| !x.as_os_str().is_empty() && folder.starts_with(x)) else {
+            return vec![folder.to_path_buf()];
+        };
+        let mut crumbs: Vec<PathBuf> = folder.ancestors()
+            .take_while(|x| x.starts_with(root))
+            .map(Path::to_path_buf)
+            .collect();
+        crumbs.reverse();
+        crumbs
+    }
+
+    fn set_folder_filter(&mut self, folder: Option<PathBuf>) {
+        self.filter.folder = folder;
+        self.apply_filter();
+        self.first_update = true; // Scroll back to the top
+    }
+
+    fn draw_folder_panel(&mut self, ui: &mut egui::Ui) {
+        let clicked = egui::SidePanel::left("folder browser")
+            .resizable(true)
+            .default_width(Self::FOLDER_PANEL_WIDTH)
+            .show_inside(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .drag_to_scroll(false)
+                    .show(ui, |ui| self.folders.draw(ui, self.filter.folder.as_deref()))
+                    .inner
+            })
+            .inner;
+        // Clicking the folder shown again shows everything.
+        if let Some(folder) = clicked {
+            let filter = (self.filter.folder.as_ref() != Some(&folder)).then_some(folder);
+            self.set_folder_filter(filter);
+        }
     }
-}
 
+    // Most recent first. An action that failed for some files links to just
+    // their errors.
+    fn draw_history_panel(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        let mut export = false;
+        let mut show_errors = None;
+        egui::TopBottomPanel::bottom("history")
+            .resizable(true)
+            .default_height(Self::HISTORY_PANEL_HEIGHT)
+            .show_inside(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(tr!("History"));
+                    export = ui.add_enabled(!self.history.is_empty(), egui::Button::new(tr!("Export history…")))
+                        .on_hover_text(tr!("Save every action as CSV, for a record of what was changed on disk"))
+                        .clicked();
+                });
+                if self.history.is_empty() {
+                    ui.weak(tr!("Nothing's been done to these results yet"));
+                    return;
+                }
+                egui::ScrollArea::vertical().drag_to_scroll(false).auto_shrink([false, true]).show(ui, |ui| {
+                    egui::Grid::new("history").striped(true).num_columns(6).show(ui, |ui| {
+                        ui.strong(tr!("Time"));
+                        ui.strong(tr!("Action"));
+                        ui.strong(tr!("Groups"));
+                        ui.strong(tr!("Files"));
+                        ui.strong(tr!("Size"));
+                        ui.strong(tr!("Outcome"));
+                        ui.end_row();
+
+                        for entry in self.history.iter().rev() {
+                            ui.label(entry.time_text());
+                            ui.label(&entry.action);
+                            let mut ids = entry.groups.iter().take(Self::HISTORY_GROUP_IDS).join(" ");
+                            if entry.groups.len() > Self::HISTORY_GROUP_IDS {
+                                ids += &tr!(" and {count} more", count = entry.groups.len() - Self::HISTORY_GROUP_IDS);
+                            }
+                            let resp = ui.weak(ids);
+                            if entry.groups.len() > Self::HISTORY_GROUP_IDS {
+                                resp.on_hover_text(entry.groups.join("\n"));
+                            }
+                            ui.label(entry.files.to_string());
+                            ui.label(format_size(entry.bytes as usize));
+                            ui.horizontal(|ui| {
+                                let outcome = entry.outcome();
+                                match outcome {
+                                    Outcome::Succeeded => ui.label(outcome.label()),
+                                    _ => ui.colored_label(ui.visuals().warn_fg_color, outcome.label()),
+                                };
+                                if entry.failed_paths.is_empty() {
+                                    return;
+                                }
+                                if ui.link(tr!("{count} errors", count = entry.failed)).clicked() {
+                                    show_errors = Some((entry.action.clone(), entry.failed_paths.iter().cloned().collect()));
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        if let Some(action) = show_errors {
+            self.error_filter.lock().unwrap().action = Some(action);
+            self.show_errors.store(true, Ordering::Relaxed);
+            ui.ctx().request_repaint_of(errors_viewport());
+        }
+        if export {
+            self.export_history()?;
+        }
+        Ok(())
+    }
+
+    fn export_history(&self) -> Result<()> {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("deckard history.csv")
+            .add_filter("CSV", &["csv"])
+            .save_file() else {
+            return Ok(());
+        };
+        std::fs::write(&path, history::to_csv(&self.history)).map_err(|e| {
+            Error::new("Error exporting history".to_string(), format!("Error writing {}: {e}", path.display()))
+        })
+    }
+
+    fn draw_folder_waste(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(tr!("Depth:"));
+            let resp = ui.add(egui::DragValue::new(&mut self.opts.folder_depth).clamp_range(0..=16));
+            if resp.changed() {
+                // Buckets at the old depth don't mean anything anymore.
+                self.filter.folder = None;
+                self.rebuild_folder_waste();
+                self.apply_filter();
+            }
+        });
+
+        if self.folder_waste.is_empty() {
+            ui.label(tr!("Nothing reclaimable"));
+            return;
+        }
+
+        let max = self.folder_waste[0].1 as f32;
+        let mut clicked = None;
+        let mut refine = None;
+        egui::ScrollArea::vertical().drag_to_scroll(false).show(ui, |ui| {
+            egui::Grid::new("folder waste").num_columns(4).striped(true).show(ui, |ui| {
+                for (folder, bytes) in self.folder_waste.iter().take(Self::MAX_FOLDER_BARS) {
+                    let label = self.folder_label(folder);
+                    if ui.link(&label).on_hover_text(folder.display().to_string()).clicked() {
+                        clicked = Some(folder.clone());
+                    }
+
+                    let size = egui::vec2(Self::FOLDER_BAR_WIDTH, Self::FOLDER_BAR_HEIGHT);
+                    let (rect, resp) = ui.allocate_exact_size(size, egui::Sense::click());
+                    let bar = egui::Rect::from_min_size(
+                        rect.min,
+                        egui::vec2(rect.width() * (*bytes as f32 / max), rect.height()),
+                    );
+                    let color = if resp.hovered() {
+                        ui.visuals().selection.bg_fill
+                    } else {
+                        ui.visuals().widgets.inactive.bg_fill
+                    };
+                    ui.painter().rect_filled(bar, 2.0, color);
+                    if resp.on_hover_text(tr!("Show groups in {folder}", folder = label)).clicked() {
+                        clicked = Some(folder.clone());
+                    }
+
+                    ui.label(format_size(*bytes));
+                    if ui.small_button(tr!("Re-scan…"))
+                        .on_hover_text(tr!("Re-scan this folder with different settings…"))
+                        .clicked() {
+                        refine = Some(folder.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        if refine.is_some() {
+            self.refine = refine;
+        }
+        if let Some(folder) = clicked {
+            self.set_folder_filter(Some(folder));
+            self.view = View::Results;
+        }
+    }
+
+    fn ignore_group(&mut self, group_idx: usize) -> Result<()> {
+        self.ignore_list.groups.push(IgnoredGroup::new(&self.images[group_idx]));
+        if let Err(e) = self.ignore_list.save() {
+            self.ignore_list.groups.pop();
+            return Err(Error::new("Error saving ignore list".to_string(), e));
+        }
+        let group = self.images.remove(group_idx);
+        self.record(HistoryEntry::new("Ignore group".to_string(), vec![group_id(&group)]));
+        self.ignored.push(group);
+        self.rebuild_index();
+        Ok(())
+    }
+
+    // Hidden groups no longer matched by anything on the list come back.
+    fn unignore(&mut self, entry_idx: usize) -> Result<()> {
+        let entry = self.ignore_list.groups.remove(entry_idx);
+        if let Err(e) = self.ignore_list.save() {
+            self.ignore_list.groups.insert(entry_idx, entry);
+            return Err(Error::new("Error saving ignore list".to_string(), e));
+        }
+
+        let (still, restored): (Vec<_>, Vec<_>) = std::mem::take(&mut self.ignored)
+            .into_iter()
+            .partition(|group| self.ignore_list.matches(group));
+        self.ignored = still;
+        if !restored.is_empty() {
+            self.record(HistoryEntry::new("Stop ignoring group".to_string(), restored.iter().map(|x| group_id(x)).collect()));
+            self.images.extend(restored);
+            self.rebuild_index();
+        }
+        Ok(())
+    }
+
+    // Where the group's shown, if it is, with whatever was excluded from it
+    // taken out.
+    fn shown_copy(&self, whole: &[Image]) -> Option<usize> {
+        self.images.iter().position(|group| group.iter().all(|x| whole.iter().any(|y| y.path == x.path)))
+    }
+
+    // Rebuilds the shown copies of the groups from them whole, as the
+    // exclusion list now has it, hiding any left with fewer than two members.
+    // Reviews follow the groups.
+    fn reapply_exclusions(&mut self, wholes: Vec<Vec<Image>>) {
+        let namespace = Namespace::new(self.index.alg);
+        let window = self.opts.burst_window();
+        for whole in wholes {
+            let shown = self.shown_copy(&whole);
+            let (mut rebuilt, still) = self.exclusions.apply(vec![whole], namespace);
+            self.excluded.extend(still);
+            match (shown, rebuilt.pop()) {
+                (Some(idx), Some(mut group)) => {
+                    Self::sort_group(&mut group, window);
+                    self.reviews.rekey(&self.images[idx], &group);
+                    self.images[idx] = group;
+                },
+                (Some(idx), None) => {
+                    self.images.remove(idx);
+                },
+                (None, Some(mut group)) => {
+                    Self::sort_group(&mut group, window);
+                    self.images.push(group);
+                },
+                (None, None) => (),
+            }
+        }
+        self.rebuild_index();
+    }
+
+    // Puts the groups files were excluded from back together, e.g. to hand
+    // the results back to the form whole.
+    fn restore_excluded(&mut self) {
+        for whole in std::mem::take(&mut self.excluded) {
+            match self.shown_copy(&whole) {
+                Some(idx) => self.images[idx] = whole,
+                None => self.images.push(whole),
+            }
+        }
+    }
+
+    // Takes the file out of its group, here and in future searches.
+    fn exclude_file(&mut self, group_idx: usize, path: PathBuf) -> Result<()> {
+        let group = &self.images[group_idx];
+        // Keyed by the group as it was found, since that's what the next
+        // search will find.
+        let whole_idx = self.excluded.iter().position(|whole| group.iter().all(|x| whole.iter().any(|y| y.path == x.path)));
+        let whole = whole_idx.map_or(group, |idx| &self.excluded[idx]);
+        let Some(member) = whole.iter().position(|x| x.path == path) else {
+            return Ok(());
+        };
+        self.exclusions.entries.push(Exclusion::new(whole, member, Namespace::new(self.index.alg)));
+        if let Err(e) = self.exclusions.save() {
+            self.exclusions.entries.pop();
+            return Err(Error::new("Error saving excluded files list".to_string(), e));
+        }
+        let whole = match whole_idx {
+            Some(idx) => self.excluded.remove(idx),
+            None => group.clone(),
+        };
+        self.record(HistoryEntry::new("Not a duplicate".to_string(), vec![group_id(&self.images[group_idx])]));
+        self.reapply_exclusions(vec![whole]);
+        Ok(())
+    }
+
+    fn unexclude(&mut self, entry_idx: usize) -> Result<()> {
+        let entry = self.exclusions.entries.remove(entry_idx);
+        if let Err(e) = self.exclusions.save() {
+            self.exclusions.entries.insert(entry_idx, entry);
+            return Err(Error::new("Error saving excluded files list".to_string(), e));
+        }
+        let wholes = std::mem::take(&mut self.excluded);
+        self.record(HistoryEntry::new("Stop excluding file".to_string(), vec![]));
+        self.reapply_exclusions(wholes);
+        Ok(())
+    }
+
+    fn draw_exclusions(&mut self, ctx: &egui::Context) -> Result<()> {
+        if !self.show_exclusions {
+            return Ok(());
+        }
+
+        let mut unexclude = None;
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Excluded files"));
+        let vid = egui::viewport::ViewportId::from_hash_of("exclusions window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_exclusions = false;
+                }
+
+                if self.exclusions.entries.is_empty() {
+                    ui.label(tr!("No excluded files"));
+                }
+                egui::ScrollArea::vertical().drag_to_scroll(false).show(ui, |ui| {
+                    for (idx, entry) in self.exclusions.entries.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.button(tr!("Un-exclude")).clicked() {
+                                unexclude = Some(idx);
+                            }
+                            ui.vertical(|ui| {
+                                ui.monospace(entry.path.display().to_string());
+                                ui.label(egui::RichText::new(tr!("Excluded from the group with:")).small());
+                                for path in &entry.others {
+                                    ui.monospace(egui::RichText::new(path.display().to_string()).weak());
+                                }
+                            });
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+        });
+
+        match unexclude {
+            Some(idx) => self.unexclude(idx),
+            None => Ok(()),
+        }
+    }
+
+    fn draw_ignored(&mut self, ctx: &egui::Context) -> Result<()> {
+        if !self.show_ignored {
+            return Ok(());
+        }
+
+        let mut unignore = None;
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Ignored groups"));
+        let vid = egui::viewport::ViewportId::from_hash_of("ignored window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_ignored = false;
+                }
+
+                if self.ignore_list.groups.is_empty() {
+                    ui.label(tr!("No ignored groups"));
+                }
+                egui::ScrollArea::vertical().drag_to_scroll(false).show(ui, |ui| {
+                    for (idx, entry) in self.ignore_list.groups.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.button(tr!("Unignore")).clicked() {
+                                unignore = Some(idx);
+                            }
+                            ui.vertical(|ui| {
+                                for path in &entry.paths {
+                                    ui.monospace(path.display().to_string());
+                                }
+                            });
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+        });
+
+        match unignore {
+            Some(idx) => self.unignore(idx),
+            None => Ok(()),
+        }
+    }
+
+    // Drops the given paths from the results, along with any groups that are
+    // no longer duplicates (or, in compare mode, no longer span both folders).
+    fn remove_images(&mut self, removed: &HashSet<PathBuf>) {
+        self.broken.retain(|x| !removed.contains(&x.path));
+        self.live_new.retain(|x| !removed.contains(x));
+        for whole in &mut self.excluded {
+            whole.retain(|x| !removed.contains(&x.path));
+        }
+        self.excluded.retain(|x| x.len() >= 2);
+        for group in &mut self.images {
+            if !group.iter().any(|x| removed.contains(&x.path)) {
+                continue;
+            }
+            let before = group.clone();
+            group.retain(|x| !removed.contains(&x.path));
+            self.reviews.rekey(&before, group);
+        }
+        if self.focused.as_ref().is_some_and(|x| removed.contains(x)) {
+            self.focused = None;
+        }
+        if self.selected.as_ref().is_some_and(|x| removed.contains(x)) {
+            self.selected = None;
+        }
+        if self.pending_click.as_ref().is_some_and(|(x, _)| removed.contains(x)) {
+            self.pending_click = None;
+        }
+        if self.preview.as_ref().is_some_and(|x| removed.contains(&x.path)) {
+            self.preview = None;
+        }
+
+        let compare = self.opts.compare;
+        self.images.retain(|group| {
+            let spans = !compare || group.iter().map(|x| x.side).collect::<HashSet<_>>().len() >= 2;
+            group.len() >= 2 && spans
+        });
+        self.rebuild_index();
+    }
+
+    // Broken images that decoded go from their groups too.
+    fn trash_broken(&mut self) -> Result<()> {
+        let paths: Vec<PathBuf> = self.broken.iter().map(|x| x.path.clone()).collect();
+        let confirm = format!(
+            "Move {} broken images (empty, cut short, or a single flat color) to the trash?",
+            paths.len(),
+        );
+        self.run_plan(ActionPlan::trash(&paths, "broken images"), "Trash broken images", confirm)
+    }
+
+    fn trash_matching_candidates(&mut self) -> Result<()> {
+        let candidates: Vec<PathBuf> = self.bulk_groups().into_iter()
+            .flat_map(|idx| &self.images[idx])
+            .filter(|x| x.side == Some(Side::Candidate))
+            .map(|x| x.path.clone())
+            .collect();
+
+        let confirm = format!(
+            "Move {} candidate files that already exist in the reference folder to the trash?",
+            candidates.len(),
+        );
+        self.run_plan(ActionPlan::trash(&candidates, "candidates"), "Trash matching candidates", confirm)
+    }
+
+    // Adds what the running search found since the last frame, keeping the
+    // list scrolled to the same file, and swaps in its own results once it's
+    // done.
+    fn poll_live(&mut self, ctx: &egui::Context) {
+        let Some(live) = &self.live else {
+            return;
+        };
+        if live.is_finished() {
+            let live = self.live.take().unwrap();
+            self.finish_live(live);
+            return;
+        }
+        ctx.request_repaint_after(Self::LIVE_INTERVAL);
+        let updates = live.poll();
+        if updates.is_empty() {
+            return;
+        }
+
+        let anchor = self.top_path();
+        let window = self.opts.burst_window();
+        for update in updates {
+            self.live_new.extend(update.images.iter().map(|x| x.path.clone()));
+            let joins = update.joins.and_then(|path| {
+                self.images.iter().position(|group| group.iter().any(|x| x.path == path))
+            });
+            match joins {
+                Some(idx) => {
+                    let before = self.images[idx].clone();
+                    let group = &mut self.images[idx];
+                    group.extend(update.images);
+                    Self::sort_group(group, window);
+                    self.reviews.rekey(&before, group);
+                    if self.reviews.mark(group) == Mark::Kept {
+                        self.reviews.update(group, |x| x.mark = Mark::NeedsAttention);
+                        self.regrouped += 1;
+                    }
+                },
+                // Its group is ignored, or was acted on already.
+                None if update.images.len() < 2 => (),
+                None if self.ignore_list.matches(&update.images) => self.ignored.push(update.images),
+                None => {
+                    let (shown, whole) = self.exclusions.apply(vec![update.images], Namespace::new(self.index.alg));
+                    self.excluded.extend(whole);
+                    for mut group in shown {
+                        Self::sort_group(&mut group, window);
+                        self.images.push(group);
+                    }
+                },
+            }
+        }
+        self.rebuild_index();
+        self.scroll_to_path(anchor);
+    }
+
+    // Swaps the partial results for the search's own, which can group files
+    // differently (e.g. near matches, which it only finds at the end). Reviews
+    // follow their groups as always, but a reviewed group that gained files
+    // since goes back to needing attention. Files acted on since are dropped,
+    // along with any others that have gone missing.
+    fn finish_live(&mut self, live: LiveSearch) {
+        let results = match live.finish() {
+            Ok(results) => results,
+            Err(e) => {
+                Error::new(
+                    "Search failed".to_string(),
+                    tr!("The search crashed: {msg}. These results are only what it found before then.", msg = e.msg),
+                ).show_modal();
+                return;
+            },
+        };
+        SearchingPhase::record(&self.opts, &results.index);
+        self.reconcile_live(results);
+    }
+
+    // Puts the search's results in place of the partial ones; see
+    // finish_live().
+    fn reconcile_live(&mut self, results: SearchResults) {
+        let SearchResults{duplicates, errors, broken, index, stats} = results;
+        let anchor = self.top_path();
+        let before = std::mem::take(&mut self.images);
+        let seen: HashSet<PathBuf> = before.iter().flatten().map(|x| x.path.clone()).collect();
+        let (ignored, images): (Vec<_>, Vec<_>) = duplicates.into_iter()
+            .partition(|group| self.ignore_list.matches(group));
+        let (mut images, excluded) = self.exclusions.apply(images, Namespace::new(index.alg));
+        self.excluded = excluded;
+        for group in &mut images {
+            Self::sort_group(group, self.opts.burst_window());
+        }
+        for old in &before {
+            if self.reviews.get(old).is_none() {
+                continue;
+            }
+            let members: HashSet<&PathBuf> = old.iter().map(|x| &x.path).collect();
+            let Some(new) = images.iter().find(|group| group.iter().any(|x| members.contains(&x.path))) else {
+                continue;
+            };
+            let mark = self.reviews.mark(old);
+            self.reviews.rekey(old, new);
+            if mark == Mark::Kept && new.iter().any(|x| !members.contains(&x.path)) {
+                self.reviews.update(new, |x| x.mark = Mark::NeedsAttention);
+                self.regrouped += 1;
+            }
+        }
+        self.live_new.extend(images.iter().flatten().map(|x| x.path.clone()).filter(|x| !seen.contains(x)));
+        self.images = images;
+        self.ignored = ignored;
+        if !errors.is_empty() {
+            self.errors.extend(errors);
+            self.show_errors.store(true, Ordering::Relaxed);
+        }
+        self.index = index;
+        self.stats = stats;
+        self.broken = broken;
+
+        let missing: HashSet<PathBuf> = self.images.iter()
+            .flatten()
+            .filter(|x| x.staleness() == Staleness::Missing)
+            .map(|x| x.path.clone())
+            .collect();
+        self.remove_images(&missing);
+        self.scroll_to_path(anchor);
+    }
+
+    // The file at the top of the list, to keep there while rows are added.
+    fn top_path(&self) -> Option<PathBuf> {
+        self.rows.get(self.top_row).map(|(group, member)| self.images[*group][*member].path.clone())
+    }
+
+    fn scroll_to_path(&mut self, path: Option<PathBuf>) {
+        let Some(path) = path else {
+            return;
+        };
+        let row = self.rows.iter().position(|(group, member)| self.images[*group][*member].path == path);
+        if let Some(row) = row.filter(|x| *x != self.top_row) {
+            self.scroll_to_row = Some(row);
+        }
+    }
+
+    fn draw_live(&mut self, ui: &mut egui::Ui) {
+        let mut cancel = false;
+        if let Some(live) = &self.live {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(tr!("Scan still running — {percent}%", percent = live.percent()))
+                    .strong()
+                    .color(ui.visuals().hyperlink_color));
+                cancel = ui.button(tr!("Cancel"))
+                    .on_hover_text(tr!("Stop the scan, keeping what it's found so far"))
+                    .clicked();
+                if self.is_provisional() {
+                    ui.weak(tr!("Bulk actions wait for the scan to finish, since near matches can still regroup"));
+                }
+            });
+        }
+        if cancel {
+            self.live.take().unwrap().cancel();
+        }
+
+        if self.regrouped > 0 {
+            let mut dismiss = false;
+            let mut show = false;
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(tr!(
+                    "{count} reviewed groups gained files after you marked them, and need attention again",
+                    count = self.regrouped,
+                )).color(ui.visuals().warn_fg_color));
+                show = ui.button(tr!("Show them")).clicked();
+                dismiss = ui.button(tr!("Dismiss")).clicked();
+            });
+            if show {
+                self.filter.review = ReviewFilter::NeedsAttention;
+                self.apply_filter();
+            }
+            if dismiss || show {
+                self.regrouped = 0;
+            }
+        }
+    }
+
+    // Tags every shown duplicate, i.e. every member but the first of each
+    // shown group, or in compare mode every candidate, for review in Finder.
+    fn tag_duplicates(&mut self, color: LabelColor) -> Result<()> {
+        let duplicates: Vec<PathBuf> = self.shown_groups.iter()
+            .flat_map(|idx| {
+                let group = &self.images[*idx];
+                let skip = if self.opts.compare { 0 } else { 1 };
+                group.iter()
+                    .skip(skip)
+                    .filter(|x| !self.opts.compare || x.side == Some(Side::Candidate))
+                    .map(|x| x.path.clone())
+            })
+            .collect();
+
+        let confirm = tr!(
+            "Tag {count} duplicates with the {color} Finder tag?",
+            count = duplicates.len(),
+            color = color.label(),
+        );
+        self.run_plan(ActionPlan::tag(&duplicates, color), tr!("Tag duplicates"), confirm)
+    }
+
+    // A file that can't be tagged doesn't stop the rest.
+    fn execute_tags(&mut self, plan: &ActionPlan, color: LabelColor) {
+        let tag = FinderTag::new(color);
+        let steps: Vec<&Step> = plan.steps.iter().filter(|x| matches!(x.action, Action::Tag{..})).collect();
+        let paths: HashSet<&PathBuf> = steps.iter().map(|x| &x.path).collect();
+        let mut entry = HistoryEntry::new(
+            format!("Tag {} files {}", steps.len(), color.name()),
+            self.group_ids_with(&paths),
+        );
+        let mut tagged = HashSet::new();
+        for step in steps {
+            match finder_tags::add(&step.path, &tag) {
+                Ok(tags) => {
+                    self.finder_tags.insert(step.path.clone(), tags);
+                    tagged.insert(&step.path);
+                },
+                Err(e) => {
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(step.path.clone()), e));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                    entry.fail(step.path.clone());
+                },
+            }
+        }
+        (entry.files, entry.bytes) = (tagged.len(), self.bytes_of(&tagged));
+        self.record(entry);
+        self.apply_filter();
+    }
+
+    // Runs the post-search hook on the groups, by index into images, in the
+    // background. The first time a command is run, the user's asked to
+    // confirm it.
+    fn run_hook(&mut self, groups: Vec<usize>) -> Result<()> {
+        if self.hook.is_some() || groups.is_empty() || self.opts.hook_command.trim().is_empty() {
+            return Ok(());
+        }
+        let words = hook::parse(&self.opts.hook_command)
+            .map_err(|e| Error::new("Invalid hook command".to_string(), e))?;
+
+        if self.opts.hook_confirmed != self.opts.hook_command {
+            let confirm = tr!(
+                "Deckard will run this command on {count} groups:\n\n{command}\n\nIt can do anything you \
+                can. Only continue if you trust it.",
+                count = groups.len(),
+                command = self.opts.hook_command,
+            );
+            let confirmed = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title(tr!("Run post-search hook"))
+                .set_description(confirm)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if confirmed != rfd::MessageDialogResult::Yes {
+                return Ok(());
+            }
+            self.opts.hook_confirmed = self.opts.hook_command.clone();
+        }
+
+        let groups = groups.into_iter()
+            .map(|idx| (idx + 1, self.images[idx].clone()))
+            .collect();
+        self.hook = Some(HookJob::start(words, groups));
+        self.hook_outcomes = None;
+        Ok(())
+    }
+
+    // Shows the hook's progress while it runs, and collects failures into the
+    // errors once it's done.
+    fn poll_hook(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.hook else {
+            return;
+        };
+        if !job.is_finished() {
+            egui::Window::new(tr!("Running hook"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(tr!("Group {done} of {total}", done = job.done() + 1, total = job.total));
+                    ui.add(egui::ProgressBar::new(job.done() as f32 / job.total.max(1) as f32));
+                    if ui.button(tr!("Cancel")).clicked() {
+                        job.cancel();
+                    }
+                });
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+
+        let outcomes = self.hook.take().unwrap().join();
+        for outcome in &outcomes {
+            if let Err(e) = &outcome.result {
+                let mut msg = format!("Hook failed on group {}: {e}", outcome.group);
+                if !outcome.stderr.is_empty() {
+                    msg += &format!("\n{}", outcome.stderr);
+                }
+                self.errors.push(SearchError::new(ErrorKind::Hook, None, msg));
+                self.show_errors.store(true, Ordering::Relaxed);
+            }
+        }
+        self.hook_outcomes = Some(outcomes);
+    }
+
+    fn draw_hook_outcomes(&mut self, ctx: &egui::Context) {
+        let Some(outcomes) = &self.hook_outcomes else {
+            return;
+        };
+
+        let failed = outcomes.iter().filter(|x| x.result.is_err()).count();
+        let mut open = true;
+        egui::Window::new(tr!("Hook results")).open(&mut open).show(ctx, |ui| {
+            ui.label(tr!("{succeeded} succeeded, {failed} failed", succeeded = outcomes.len() - failed, failed = failed));
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for outcome in outcomes {
+                    let group = tr!("Group {number}", number = outcome.group);
+                    match &outcome.result {
+                        Ok(()) => ui.label(format!("{group}: OK")),
+                        Err(e) => ui.colored_label(ui.visuals().error_fg_color, format!("{group}: {e}")),
+                    };
+                    if !outcome.stderr.is_empty() {
+                        ui.monospace(&outcome.stderr);
+                    }
+                }
+            });
+        });
+
+        if !open {
+            self.hook_outcomes = None;
+        }
+    }
+
+    // Trashes every member but the previewed keeper of each group that has
+    // one, exactly as shown.
+    fn apply_keepers(&mut self) -> Result<()> {
+        let (groups, extra) = self.unkept();
+        let confirm = format!(
+            "Move {} images to the trash, keeping the chosen one in each of {} groups?",
+            extra.len(),
+            groups.len(),
+        );
+        self.run_plan(ActionPlan::trash(&extra, "images"), "Keep chosen images", confirm)
+    }
+
+    // The groups bulk actions touch that have a previewed keeper, and every
+    // other member of them.
+    fn unkept(&self) -> (Vec<usize>, Vec<PathBuf>) {
+        let groups: Vec<usize> = self.bulk_groups().into_iter()
+            .filter(|idx| self.keeper_preview[*idx].is_some())
+            .collect();
+        let extra = groups.iter()
+            .flat_map(|idx| {
+                let keeper = self.keeper_preview[*idx];
+                self.images[*idx].iter()
+                    .enumerate()
+                    .filter(move |(member, _)| Some(*member) != keeper)
+                    .map(|(_, x)| x.path.clone())
+            })
+            .collect();
+        (groups, extra)
+    }
+
+    // For a large group, without looking through it: trashes everything but
+    // the member shown first.
+    fn keep_first_of_group(&mut self, group_idx: usize) -> Result<()> {
+        let extra: Vec<PathBuf> = self.images[group_idx][1..].iter().map(|x| x.path.clone()).collect();
+        let confirm = format!(
+            "Move {} images to the trash, keeping only {}?",
+            extra.len(),
+            self.images[group_idx][0].path.display(),
+        );
+        self.run_plan(ActionPlan::trash(&extra, "images"), "Keep first of group", confirm)
+    }
+
+    fn keep_first_of_bursts(&mut self) -> Result<()> {
+        let extra: Vec<PathBuf> = self.bulk_groups().into_iter()
+            .filter(|idx| self.bursts[*idx])
+            .flat_map(|idx| self.images[idx][1..].iter().map(|x| x.path.clone()))
+            .collect();
+
+        let confirm = format!(
+            "Move {} images to the trash, keeping only the earliest shot of each burst?",
+            extra.len(),
+        );
+        self.run_plan(ActionPlan::trash(&extra, "burst images"), "Keep first of each burst", confirm)
+    }
+
+    // Trashes all but the largest of each group of resolution variants.
+    // Groups it can't be sure about are left for review, and listed in the
+    // plan as skipped: ones with members of unknown dimensions, and ones with
+    // a smaller copy that's protected, so keeping the largest wouldn't
+    // resolve them.
+    fn keep_largest_variants(&mut self) -> Result<()> {
+        let protected = |x: &&Image| {
+            x.side == Some(Side::Reference) || (self.sensitive_root && is_system_path(&x.path))
+        };
+        let mut steps = vec![];
+        let (mut resolved, mut unknown, mut guarded) = (0, 0, 0);
+        for idx in self.bulk_groups() {
+            let group = &self.images[idx];
+            match self.variants[idx] {
+                Variants::Largest(largest) => {
+                    let smaller = group.iter().enumerate().filter(|(x, _)| *x != largest).map(|(_, x)| x);
+                    if smaller.clone().any(|x| protected(&x)) {
+                        guarded += 1;
+                        steps.extend(smaller.map(|x| Step::skip(&x.path, "A smaller copy is protected".to_string())));
+                    } else {
+                        resolved += 1;
+                        steps.extend(smaller.map(|x| Step::new(&x.path, Action::Trash)));
+                    }
+                },
+                Variants::Unknown => {
+                    unknown += 1;
+                    steps.extend(group.iter().map(|x| Step::skip(&x.path, "Dimensions unknown".to_string())));
+                },
+                Variants::No => (),
+            }
+        }
+
+        let trashed = steps.iter().filter(|x| x.action == Action::Trash).count();
+        if trashed == 0 {
+            // Nothing to confirm; just say why.
+            let mut notice = tr!("No resolution variants can be trashed.").to_string();
+            if unknown > 0 {
+                notice += "\n\n";
+                notice += &tr!("{count} groups have images of unknown dimensions.", count = unknown);
+            }
+            if guarded > 0 {
+                notice += "\n\n";
+                notice += &tr!("{count} groups have a smaller copy in a protected folder.", count = guarded);
+            }
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Info)
+                .set_title(tr!("Keep largest resolution variants"))
+                .set_description(notice)
+                .set_buttons(rfd::MessageButtons::Ok)
+                .show();
+            return Ok(());
+        }
+        let mut confirm = format!(
+            "Move {trashed} images to the trash, keeping only the largest of each of {resolved} groups of resolution variants?",
+        );
+        if unknown > 0 {
+            confirm += &format!("\n\n{unknown} groups with images of unknown dimensions will be left for review.");
+        }
+        if guarded > 0 {
+            confirm += &format!("\n\n{guarded} groups with a smaller copy in a protected folder will be left for review.");
+        }
+        let plan = ActionPlan{kind: PlanKind::Trash{what: "resolution variants".to_string()}, steps};
+        self.run_plan(plan, "Keep largest resolution variants", confirm)
+    }
+
+    // In a dry run, shows the plan; otherwise asks for confirmation and
+    // carries it out.
+    fn run_plan(&mut self, mut plan: ActionPlan, title: &str, mut confirm: String) -> Result<()> {
+        if !matches!(plan.kind, PlanKind::Tag(_)) {
+            self.check_settled()?;
+        }
+        let images = self.images.iter()
+            .flatten()
+            .map(|x| (x.path.as_path(), x))
+            .collect();
+        let groups = self.images.iter()
+            .flat_map(|group| {
+                let id = group_id(group);
+                group.iter().map(move |x| (x.path.as_path(), id.clone()))
+            })
+            .collect();
+        plan.annotate(&self.distances, &images, &groups);
+        if self.sensitive_root {
+            let spared = plan.protect(is_system_path, "In a system folder");
+            if spared > 0 {
+                confirm += "\n\n";
+                confirm += &tr!("{count} files in system folders will be left alone.", count = spared);
+            }
+        }
+        if self.opts.sidecars && matches!(plan.kind, PlanKind::Trash{..}) {
+            let sidecars = plan.add_sidecars(&self.opts.sidecar_exts());
+            if sidecars > 0 {
+                confirm += &format!("\n\n{sidecars} sidecar files (e.g. .xmp) will go with them.");
+            }
+            let shared = plan.steps.iter().filter(|x| x.sidecar_of.is_some() && x.action == Action::Skip).count();
+            if shared > 0 {
+                confirm += &format!("\n\n{shared} sidecar files that also belong to files being kept will be left alone.");
+            }
+        }
+
+        if self.dry_run {
+            self.plan = Some(plan);
+            return Ok(());
+        }
+        // Trashing frees nothing until the trash is emptied, which is easy to
+        // forget when it's the space that was wanted.
+        if matches!(plan.kind, PlanKind::Trash{..}) {
+            let paths: HashSet<&PathBuf> = plan.steps.iter()
+                .filter(|x| x.action == Action::Trash)
+                .map(|x| &x.path)
+                .collect();
+            let bytes = self.bytes_of(&paths);
+            confirm += "\n\n";
+            confirm += &tr!(
+                "{size} will go to the trash. The space isn't freed until the trash is emptied.",
+                size = format_size(bytes),
+            );
+        }
+
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(title)
+            .set_description(confirm)
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if confirmed != rfd::MessageDialogResult::Yes {
+            return Ok(());
+        }
+        self.execute_plan(plan)
+    }
+
+    fn execute_plan(&mut self, mut plan: ActionPlan) -> Result<()> {
+        if !matches!(plan.kind, PlanKind::Tag(_)) {
+            self.check_settled()?;
+        }
+        let missing = self.skip_stale(&mut plan);
+        match &plan.kind {
+            PlanKind::Trash{what} => self.execute_trash(&plan, what),
+            PlanKind::Link => self.execute_links(&plan),
+            PlanKind::Tag(color) => self.execute_tags(&plan, *color),
+            PlanKind::Move{dest, policy} => self.execute_moves(&plan, dest, *policy),
+        }
+        if !missing.is_empty() {
+            self.remove_images(&missing);
+        }
+        Ok(())
+    }
+
+    // Right before carrying out a plan, skips the files that changed or
+    // disappeared since the scan, rather than acting on something else or
+    // failing the whole batch. A link is skipped if either end is stale. Each
+    // skipped file is reported as an error; returns the missing ones, to drop
+    // from the results.
+    fn skip_stale(&mut self, plan: &mut ActionPlan) -> HashSet<PathBuf> {
+        let images: HashMap<&Path, &Image> = self.images.iter()
+            .flatten()
+            .map(|x| (x.path.as_path(), x))
+            .collect();
+        let mut checked: HashMap<PathBuf, Staleness> = HashMap::new();
+        let mut staleness = |path: &Path| *checked.entry(path.to_path_buf())
+            .or_insert_with(|| images.get(path).map_or(Staleness::Current, |x| x.staleness()));
+
+        let mut missing = HashSet::new();
+        for step in plan.steps.iter_mut().filter(|x| x.action != Action::Skip && x.sidecar_of.is_none()) {
+            let mut paths = vec![step.path.clone()];
+            if let Action::Link{target} = &step.action {
+                paths.push(target.clone());
+            }
+            for path in paths {
+                let problem = match staleness(&path) {
+                    Staleness::Current => continue,
+                    Staleness::Changed => "changed since the scan",
+                    Staleness::Missing => {
+                        missing.insert(path.clone());
+                        "no longer exists"
+                    },
+                };
+                let msg = format!("Skipped {}: {} {problem}", step.path.display(), path.display());
+                self.errors.push(SearchError::new(ErrorKind::Io, Some(step.path.clone()), msg));
+                self.show_errors.store(true, Ordering::Relaxed);
+                *step = Step::skip(&step.path, format!("{} {problem}", path.display()));
+                break;
+            }
+        }
+        missing
+    }
+
+    // Moves the files to the trash, recording an undo entry described as
+    // "Trash N {what}", and drops them from the results.
+    // Sidecars only follow images that made it to the trash.
+    fn execute_trash(&mut self, plan: &ActionPlan, what: &str) {
+        let mut removed = HashSet::new();
+        let mut sidecars = vec![];
+        let mut sidecar_bytes = 0;
+        let trash_steps = plan.steps.iter().filter(|x| x.action == Action::Trash);
+        let mut entry = HistoryEntry::new(
+            format!("Trash {} {what}", trash_steps.clone().filter(|x| x.sidecar_of.is_none()).count()),
+            self.group_ids_with(&trash_steps.clone().map(|x| &x.path).collect()),
+        );
+        for step in trash_steps.clone().filter(|x| x.sidecar_of.is_none()) {
+            let path = step.path.clone();
+            match trash::delete(long_path(&path)) {
+                Ok(()) => { removed.insert(path); },
+                Err(e) => {
+                    let msg = format!("Error trashing {}: {e}", path.display());
+                    entry.fail(path.clone());
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(path), msg));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                },
+            }
+        }
+        for step in trash_steps.filter(|x| x.sidecar_of.as_ref().is_some_and(|x| removed.contains(x))) {
+            let path = step.path.clone();
+            // Not in the results, so sized before it's gone.
+            let size = std::fs::metadata(&path).map_or(0, |x| x.len());
+            match trash::delete(long_path(&path)) {
+                Ok(()) => {
+                    sidecar_bytes += size;
+                    sidecars.push(path);
+                },
+                Err(e) => {
+                    let image = step.sidecar_of.as_ref().unwrap();
+                    let msg = format!("Error trashing {}, a sidecar of {}: {e}", path.display(), image.display());
+                    entry.fail(path.clone());
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(path), msg));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                },
+            }
+        }
+
+        let bytes = self.bytes_of(&removed.iter().collect());
+        (entry.files, entry.bytes) = (removed.len() + sidecars.len(), bytes + sidecar_bytes);
+        self.record(entry);
+        if !removed.is_empty() {
+            self.trashed = Some((removed.len(), bytes, sidecars.len()));
+            self.undo.push(UndoEntry {
+                description: format!("Trash {} {what}", removed.len()),
+                trashed: removed.iter().cloned().chain(sidecars).collect(),
+                groups: self.images.iter()
+                    .filter(|group| group.iter().any(|x| removed.contains(&x.path)))
+                    .cloned()
+                    .collect(),
+                renamed: vec![],
+            });
+        }
+        self.remove_images(&removed);
+    }
+
+    // The total size of the given results, broken images included.
+    fn bytes_of(&self, paths: &HashSet<&PathBuf>) -> u64 {
+        let mut sizes: HashMap<&PathBuf, u64> = self.broken.iter().map(|x| (&x.path, x.size)).collect();
+        sizes.extend(self.images.iter().flatten().map(|x| (&x.path, x.file_size as u64)));
+        paths.iter().filter_map(|x| sizes.get(x)).sum()
+    }
+
+    // Asks before writing more to a folder than its drive has room for,
+    // rather than failing partway. True to go ahead: there's room, how much
+    // there is can't be told, or the user chose to try anyway.
+    fn confirm_space(dest: &Path, required: u64, what: &str) -> bool {
+        let Some(available) = misc::free_space(dest) else {
+            return true;
+        };
+        if required <= available {
+            return true;
+        }
+        let anyway = tr!("Proceed anyway").to_string();
+        let answer = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(tr!("Not enough space"))
+            .set_description(tr!(
+                "{what} needs about {required}, but only {available} is free on the drive holding {folder}.",
+                what = what,
+                required = format_size(required),
+                available = format_size(available),
+                folder = dest.display(),
+            ))
+            .set_buttons(rfd::MessageButtons::OkCancelCustom(anyway.clone(), tr!("Cancel").to_string()))
+            .show();
+        // Some backends report custom buttons as such, others as Ok.
+        matches!(answer, rfd::MessageDialogResult::Ok)
+            || answer == rfd::MessageDialogResult::Custom(anyway)
+    }
+
+    // Reverses the most recent destructive action. Restored files go back into
+    // the group they came from if it's still around, otherwise the group is
+    // recreated as it was.
+    fn undo_last(&mut self) -> Result<()> {
+        let Some(entry) = self.undo.pop() else {
+            return Ok(());
+        };
+        if !entry.renamed.is_empty() && !entry.groups.is_empty() {
+            return self.undo_move(entry);
+        }
+        if !entry.renamed.is_empty() {
+            return self.undo_rename(entry);
+        }
+
+        if !undo::TRASH_RESTORE_SUPPORTED {
+            let open = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Info)
+                .set_title(tr!("Undo"))
+                .set_description(tr!(
+                    "Can't undo moving files to the trash on this platform; they'll have to be \
+                    put back by hand. Open the trash folder?"
+                ))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if open == rfd::MessageDialogResult::Yes {
+                undo::open_trash().map_err(|e| Error::new("Error opening the trash".to_string(), e))?;
+            }
+            return Ok(());
+        }
+
+        let ids = entry.groups.iter().map(|x| group_id(x)).collect();
+        let mut record = HistoryEntry::new(format!("Undo \"{}\"", entry.description), ids);
+        if let Err(e) = undo::restore_from_trash(&entry.trashed) {
+            for path in &entry.trashed {
+                record.fail(path.clone());
+            }
+            self.record(record);
+            let description = entry.description.clone();
+            self.undo.push(entry); // Nothing was restored, so it can be tried again
+            return Err(Error::new(format!("Couldn't undo \"{description}\""), e));
+        }
+        record.files = entry.trashed.len();
+        // Sidecars aren't in the groups, but are back where they can be sized.
+        let sizes: HashMap<&PathBuf, u64> = entry.groups.iter()
+            .flatten()
+            .map(|x| (&x.path, x.file_size as u64))
+            .collect();
+        record.bytes = entry.trashed.iter()
+            .map(|x| sizes.get(x).copied().unwrap_or_else(|| std::fs::metadata(x).map_or(0, |x| x.len())))
+            .sum();
+        self.record(record);
+
+        for group in entry.groups {
+            let restored = group.iter().filter(|x| entry.trashed.contains(&x.path)).cloned();
+            let existing = self.images.iter_mut()
+                .find(|x| x.iter().any(|a| group.iter().any(|b| a.path == b.path)));
+            match existing {
+                Some(existing) => existing.extend(restored),
+                None => self.images.push(group),
+            }
+        }
+        self.rebuild_index();
+        Ok(())
+    }
+
+    // Opens the rename window, on the selected image if there is one.
+    fn open_rename(&mut self) {
+        let mut dialog = RenameDialog {
+            template: self.opts.rename_template.clone(),
+            keepers: self.selected.is_none(),
+            renames: vec![],
+            error: None,
+        };
+        self.preview_rename(&mut dialog);
+        self.rename = Some(dialog);
+    }
+
+    // What the rename window would rename: the keeper of each group bulk
+    // actions touch, or the selected image.
+    fn rename_files(&self, keepers: bool) -> Vec<RenameFile> {
+        let members: Vec<(usize, &Image)> = match keepers {
+            true => self.bulk_groups().into_iter()
+                .filter_map(|idx| Some((idx, &self.images[idx][self.keeper_preview[idx]?])))
+                .collect(),
+            false => self.images.iter()
+                .enumerate()
+                .flat_map(|(idx, group)| group.iter().map(move |x| (idx, x)))
+                .filter(|(_, x)| self.selected.as_ref() == Some(&x.path))
+                .take(1)
+                .collect(),
+        };
+        members.into_iter()
+            .map(|(idx, image)| RenameFile {
+                path: image.path.clone(),
+                taken: image.taken,
+                mtime: image.mtime,
+                group_id: group_id(&self.images[idx]),
+            })
+            .collect()
+    }
+
+    fn preview_rename(&self, dialog: &mut RenameDialog) {
+        match rename::Template::parse(&dialog.template) {
+            Ok(template) => {
+                let files = self.rename_files(dialog.keepers);
+                dialog.renames = rename::plan(&template, &files, |x| long_path(x).exists(), |a, b| rename::same_file_on_disk(&long_path(a), &long_path(b)));
+                dialog.error = None;
+            },
+            Err(e) => {
+                dialog.renames.clear();
+                dialog.error = Some(e);
+            },
+        }
+    }
+
+    fn draw_rename(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some(mut dialog) = self.rename.take() else {
+            return Ok(());
+        };
+
+        let (template, keepers) = (dialog.template.clone(), dialog.keepers);
+        let has_selected = self.selected.is_some();
+        let mut close = false;
+        let mut execute = false;
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Rename images"));
+        let vid = egui::viewport::ViewportId::from_hash_of("rename window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                close = ctx.input(|i| i.viewport().close_requested());
+
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut dialog.keepers, true, tr!("The image to keep in each group"));
+                    ui.add_enabled_ui(has_selected, |ui| {
+                        ui.radio_value(&mut dialog.keepers, false, tr!("The selected image"))
+                            .on_disabled_hover_text(tr!("Click an image to select it"));
+                    });
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr!("New name:"));
+                    ui.add(egui::TextEdit::singleline(&mut dialog.template).desired_width(300.0));
+                });
+                let placeholders = Placeholder::ALL.iter().map(|x| format!("{{{}}}", x.key())).join(" ");
+                ui.label(egui::RichText::new(tr!(
+                    "Placeholders: {list}. Dates are YYYY-MM-DD; each file keeps its extension.",
+                    list = placeholders,
+                )).small().weak());
+                if let Some(e) = &dialog.error {
+                    ui.colored_label(ui.visuals().warn_fg_color, e);
+                }
+
+                let count = dialog.renames.iter().filter(|x| x.changes()).count();
+                let problems = dialog.renames.iter().filter(|x| x.problem.is_some()).count();
+                ui.label(tr!("{count} files to rename, {problems} with problems", count = count, problems = problems));
+                ui.horizontal(|ui| {
+                    execute = ui.add_enabled(rename::is_ready(&dialog.renames), egui::Button::new(tr!("Rename")))
+                        .on_disabled_hover_text(tr!("Every file needs a new name of its own that isn't taken already"))
+                        .clicked();
+                    close |= ui.button(tr!("Cancel")).clicked();
+                });
+                ui.separator();
+
+                egui::ScrollArea::both().drag_to_scroll(false).show(ui, |ui| {
+                    egui::Grid::new("renames").striped(true).num_columns(3).show(ui, |ui| {
+                        ui.strong(tr!("File"));
+                        ui.strong(tr!("New name"));
+                        ui.strong(tr!("Problem"));
+                        ui.end_row();
+                        let warn = ui.visuals().warn_fg_color;
+                        for rename in &dialog.renames {
+                            ui.monospace(rename.from.display().to_string());
+                            let name = rename.to.as_ref()
+                                .and_then(|x| x.file_name())
+                                .map_or(String::new(), |x| x.to_string_lossy().to_string());
+                            let name = egui::RichText::new(name).monospace();
+                            match (&rename.problem, rename.changes()) {
+                                (Some(_), _) => ui.label(name.color(warn)),
+                                (None, false) => ui.label(name.weak()),
+                                (None, true) => ui.label(name),
+                            };
+                            match &rename.problem {
+                                Some(x) => ui.colored_label(warn, x),
+                                None => ui.label(""),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        });
+
+        if dialog.template != template || dialog.keepers != keepers {
+            self.opts.rename_template = dialog.template.clone();
+            self.session_dirty = true;
+            self.preview_rename(&mut dialog);
+        }
+        if execute {
+            if let Err(e) = self.check_settled() {
+                self.rename = Some(dialog);
+                return Err(e);
+            }
+            // Checked again, in case files have turned up since
+            self.preview_rename(&mut dialog);
+            if rename::is_ready(&dialog.renames) {
+                self.execute_rename(dialog.renames);
+                return Ok(());
+            }
+        }
+        if !close {
+            self.rename = Some(dialog);
+        }
+        Ok(())
+    }
+
+    // Renames the files, and has everything that refers to them follow.
+    // Failures are reported, and the rest carried on with.
+    fn execute_rename(&mut self, renames: Vec<Rename>) {
+        let moves: Vec<(PathBuf, PathBuf)> = renames.into_iter()
+            .filter(Rename::changes)
+            .map(|x| (x.from, x.to.unwrap()))
+            .collect();
+        let mut entry = HistoryEntry::new(
+            format!("Rename {} images", moves.len()),
+            self.group_ids_with(&moves.iter().map(|x| &x.0).collect()),
+        );
+        let mut done = vec![];
+        for (from, to) in moves {
+            // rename() would replace it on some platforms.
+            let result = match rename::would_replace(&from, &to, |x| long_path(x).exists(), |a, b| rename::same_file_on_disk(&long_path(a), &long_path(b))) {
+                true => Err("a file with that name already exists".to_string()),
+                false => std::fs::rename(long_path(&from), long_path(&to)).map_err(|e| e.to_string()),
+            };
+            match result {
+                Ok(()) => done.push((from, to)),
+                Err(e) => {
+                    let msg = format!("Error renaming {} to {}: {e}", from.display(), to.display());
+                    entry.fail(from.clone());
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(from), msg));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                },
+            }
+        }
+
+        (entry.files, entry.bytes) = (done.len(), self.bytes_of(&done.iter().map(|x| &x.0).collect()));
+        self.record(entry);
+        if !done.is_empty() {
+            self.undo.push(UndoEntry {
+                description: format!("Rename {} images", done.len()),
+                trashed: vec![],
+                groups: vec![],
+                renamed: done.clone(),
+            });
+            self.move_paths(&done);
+        }
+    }
+
+    // Puts renamed files back under their old names. Any that can't be are
+    // left to undo again.
+    fn undo_rename(&mut self, mut entry: UndoEntry) -> Result<()> {
+        let ids = self.group_ids_with(&entry.renamed.iter().map(|x| &x.1).collect());
+        let mut record = HistoryEntry::new(format!("Undo \"{}\"", entry.description), ids);
+        let mut restored = vec![];
+        let mut failed = vec![];
+        for (old, new) in std::mem::take(&mut entry.renamed) {
+            let result = match rename::would_replace(&new, &old, |x| long_path(x).exists(), |a, b| rename::same_file_on_disk(&long_path(a), &long_path(b))) {
+                true => Err("another file has that name now".to_string()),
+                false => std::fs::rename(long_path(&new), long_path(&old)).map_err(|e| e.to_string()),
+            };
+            match result {
+                Ok(()) => restored.push((new, old)),
+                Err(e) => {
+                    let msg = format!("Error renaming {} back to {}: {e}", new.display(), old.display());
+                    record.fail(new.clone());
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(new.clone()), msg));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                    failed.push((old, new));
+                },
+            }
+        }
+
+        (record.files, record.bytes) = (restored.len(), self.bytes_of(&restored.iter().map(|x| &x.0).collect()));
+        self.record(record);
+        if !failed.is_empty() {
+            entry.renamed = failed;
+            self.undo.push(entry);
+        }
+        self.move_paths(&restored);
+        Ok(())
+    }
+
+    // Opens the move window, with no folder chosen yet.
+    fn open_move(&mut self) {
+        let mut dialog = MoveDialog {
+            dest: None,
+            keep_structure: false,
+            policy: CollisionPolicy::default(),
+            preview: None,
+        };
+        self.preview_move(&mut dialog);
+        self.moving = Some(dialog);
+    }
+
+    fn preview_move(&self, dialog: &mut MoveDialog) {
+        dialog.preview = dialog.dest.as_ref().map(|dest| {
+            let root = dialog.keep_structure.then_some(self.opts.root.as_path());
+            ActionPlan::move_to(&self.unkept().1, dest, root, dialog.policy, |x| long_path(x).exists())
+        });
+    }
+
+    fn draw_move(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some(mut dialog) = self.moving.take() else {
+            return Ok(());
+        };
+
+        let (dest, keep_structure, policy) = (dialog.dest.clone(), dialog.keep_structure, dialog.policy);
+        let mut close = false;
+        let mut execute = false;
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Move images"));
+        let vid = egui::viewport::ViewportId::from_hash_of("move window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                close = ctx.input(|i| i.viewport().close_requested());
+
+                ui.label(tr!("Moves every image but the one marked Keep in each group into a folder, to look through before deleting them."));
+                ui.horizontal(|ui| {
+                    ui.label(tr!("Folder:"));
+                    match &dialog.dest {
+                        Some(dest) => ui.monospace(dest.display().to_string()),
+                        None => ui.weak(tr!("None chosen")),
+                    };
+                    if ui.button(tr!("Choose…")).clicked() {
+                        if let Some(dest) = rfd::FileDialog::new().pick_folder() {
+                            dialog.dest = Some(dest);
+                        }
+                    }
+                });
+                ui.checkbox(&mut dialog.keep_structure, tr!("Keep the folders they're in under {root}", root = self.opts.root.display()))
+                    .on_hover_text(tr!("Otherwise they're all put straight into the folder"));
+                ui.label(tr!("When the folder already has a file by the same name:"));
+                ui.horizontal(|ui| {
+                    for x in CollisionPolicy::ALL {
+                        ui.radio_value(&mut dialog.policy, x, x.label());
+                    }
+                });
+
+                if let Some(plan) = &dialog.preview {
+                    let count = |f: fn(&Action) -> bool| plan.steps.iter().filter(|x| f(&x.action)).count();
+                    ui.label(tr!(
+                        "{count} files to move, {replaced} replacing files there, {skipped} skipped",
+                        count = count(|x| matches!(x, Action::Move{..})),
+                        replaced = count(|x| matches!(x, Action::Move{replace: true, ..})),
+                        skipped = count(|x| *x == Action::Skip),
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    execute = ui.add_enabled(dialog.preview.is_some(), egui::Button::new(tr!("Move")))
+                        .on_disabled_hover_text(tr!("Choose a folder to move them to"))
+                        .clicked();
+                    close |= ui.button(tr!("Cancel")).clicked();
+                });
+                ui.label(egui::RichText::new(tr!("With Dry run on, Move shows where each file would go instead.")).small().weak());
+            });
+        });
+
+        if dialog.dest != dest || dialog.keep_structure != keep_structure || dialog.policy != policy {
+            self.preview_move(&mut dialog);
+        }
+        if execute {
+            // Made again, in case files have turned up since
+            self.preview_move(&mut dialog);
+            if let Some(plan) = dialog.preview.take() {
+                return self.move_unkept(plan);
+            }
+        }
+        if !close {
+            self.moving = Some(dialog);
+        }
+        Ok(())
+    }
+
+    fn move_unkept(&mut self, plan: ActionPlan) -> Result<()> {
+        let PlanKind::Move{dest, ..} = &plan.kind else {
+            return Ok(());
+        };
+        let moved = plan.steps.iter().filter(|x| matches!(x.action, Action::Move{..})).count();
+        let mut confirm = tr!("Move {count} images into {dest}?", count = moved, dest = dest.display());
+        let skipped = plan.steps.iter().filter(|x| x.action == Action::Skip).count();
+        if skipped > 0 {
+            confirm += "\n\n";
+            confirm += &tr!("{count} whose names are taken there will be left where they are.", count = skipped);
+        }
+        let replaced = Self::replaced(&plan);
+        if replaced > 0 {
+            confirm += "\n\n";
+            confirm += &tr!("{count} files already there will be replaced, and can't be brought back.", count = replaced);
+        }
+        self.run_plan(plan, tr!("Move images"), confirm)
+    }
+
+    // How many files the plan replaces.
+    fn replaced(plan: &ActionPlan) -> usize {
+        plan.steps.iter().filter(|x| matches!(x.action, Action::Move{replace: true, ..})).count()
+    }
+
+    // Moves the files, recording an undo entry that moves them back, and
+    // drops them from the results. Files whose names turn out to be taken by
+    // now are reported, and stay.
+    fn execute_moves(&mut self, plan: &ActionPlan, dest: &Path, policy: CollisionPolicy) {
+        let moves: Vec<(&PathBuf, &PathBuf, bool)> = plan.steps.iter()
+            .filter_map(|x| match &x.action {
+                Action::Move{target, replace} => Some((&x.path, target, *replace)),
+                _ => None,
+            })
+            .collect();
+        let mut entry = HistoryEntry::new(
+            format!("Move {} images to {}", moves.len(), dest.display()),
+            self.group_ids_with(&moves.iter().map(|x| x.0).collect()),
+        );
+        let mut done = vec![];
+        for (from, to, replace) in moves {
+            match move_files::move_file(&long_path(from), &long_path(to), replace, policy) {
+                // Possibly under another number, if one turned up
+                Ok(moved) => done.push((from.clone(), to.with_file_name(moved.file_name().unwrap_or_default()))),
+                Err(e) => {
+                    let msg = format!("Error moving {} to {}: {e}", from.display(), to.display());
+                    entry.fail(from.clone());
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(from.clone()), msg));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                },
+            }
+        }
+
+        let removed: HashSet<PathBuf> = done.iter().map(|x| x.0.clone()).collect();
+        (entry.files, entry.bytes) = (done.len(), self.bytes_of(&removed.iter().collect()));
+        self.record(entry);
+        if !done.is_empty() {
+            self.undo.push(UndoEntry {
+                description: format!("Move {} images", done.len()),
+                trashed: vec![],
+                groups: self.images.iter()
+                    .filter(|group| group.iter().any(|x| removed.contains(&x.path)))
+                    .cloned()
+                    .collect(),
+                renamed: done,
+            });
+        }
+        self.remove_images(&removed);
+    }
+
+    // Moves files back where they came from, and into their groups. Any that
+    // can't be are left to undo again.
+    fn undo_move(&mut self, mut entry: UndoEntry) -> Result<()> {
+        let ids = entry.groups.iter().map(|x| group_id(x)).collect();
+        let mut record = HistoryEntry::new(format!("Undo \"{}\"", entry.description), ids);
+        let mut restored = HashSet::new();
+        let mut failed = vec![];
+        for (old, new) in std::mem::take(&mut entry.renamed) {
+            match move_files::move_file(&long_path(&new), &long_path(&old), false, CollisionPolicy::Skip) {
+                Ok(_) => { restored.insert(old); },
+                Err(e) => {
+                    let msg = format!("Error moving {} back to {}: {e}", new.display(), old.display());
+                    record.fail(new.clone());
+                    self.errors.push(SearchError::new(ErrorKind::Io, Some(new.clone()), msg));
+                    self.show_errors.store(true, Ordering::Relaxed);
+                    failed.push((old, new));
+                },
+            }
+        }
+
+        record.files = restored.len();
+        record.bytes = entry.groups.iter()
+            .flatten()
+            .filter(|x| restored.contains(&x.path))
+            .map(|x| x.file_size as u64)
+            .sum();
+        self.record(record);
+        let stayed: HashSet<&PathBuf> = failed.iter().map(|x| &x.0).collect();
+        for group in &entry.groups {
+            let back = group.iter().filter(|x| restored.contains(&x.path)).cloned();
+            let existing = self.images.iter_mut()
+                .find(|x| x.iter().any(|a| group.iter().any(|b| a.path == b.path)));
+            match existing {
+                Some(existing) => existing.extend(back),
+                None => self.images.push(group.iter().filter(|x| !stayed.contains(&x.path)).cloned().collect()),
+            }
+        }
+        if !failed.is_empty() {
+            entry.renamed = failed;
+            self.undo.push(entry);
+        }
+        self.rebuild_index();
+        Ok(())
+    }
+
+    // Points everything that refers to files by path at their new ones, given
+    // as old and new.
+    fn move_paths(&mut self, moves: &[(PathBuf, PathBuf)]) {
+        if moves.is_empty() {
+            return;
+        }
+        let by_old: HashMap<&PathBuf, &PathBuf> = moves.iter().map(|(old, new)| (old, new)).collect();
+        let follow = |path: &mut PathBuf| {
+            if let Some(new) = by_old.get(path) {
+                *path = (*new).clone();
+            }
+        };
+        self.images.iter_mut().flatten().for_each(|x| follow(&mut x.path));
+        self.index.hashes.values_mut().flatten().for_each(follow);
+        self.selected.iter_mut().for_each(follow);
+        self.focused.iter_mut().for_each(follow);
+        for (old, new) in moves {
+            self.reviews.rename(old, new);
+            if self.expanded_bursts.remove(old) {
+                self.expanded_bursts.insert(new.clone());
+            }
+            if let Some(pages) = self.large_pages.remove(old) {
+                self.large_pages.insert(new.clone(), pages);
+            }
+            if let Some(tags) = self.finder_tags.remove(old) {
+                self.finder_tags.insert(new.clone(), tags);
+            }
+        }
+        self.rebuild_index();
+    }
+
+    fn save_session(&mut self) -> Result<()> {
+        let mut session = Session::new(&self.opts, &self.images, &self.reviews);
+        session.history = self.history.clone();
+        session.save()
+            .map_err(|e| Error::new("Error saving session".to_string(), e))?;
+        self.session_dirty = false;
+        Ok(())
+    }
+
+    // Saves where the user picks, in the root by default, if sessions are
+    // portable; see Session::make_portable(). Either way, they're saved for
+    // restoring after restarting too.
+    fn save_session_clicked(&mut self) -> Result<()> {
+        self.save_session()?;
+        if !self.opts.portable {
+            return Ok(());
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .set_directory(&self.opts.root)
+            .set_file_name(PORTABLE_FILE)
+            .add_filter("JSON", &["json"])
+            .save_file() else {
+            return Ok(());
+        };
+        let mut session = Session::new(&self.opts, &self.images, &self.reviews);
+        session.history = self.history.clone();
+        let dir = path.parent().unwrap_or(Path::new("."));
+        session.make_portable(dir, &self.ignore_list)
+            .and_then(|()| session.save_to(&path))
+            .map_err(|e| Error::new("Error saving session".to_string(), e))
+    }
+
+    fn export_report(&mut self) {
+        if self.report.is_some() {
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("deckard report.html")
+            .add_filter("HTML", &["html"])
+            .save_file() else {
+            return;
+        };
+        let dest = path.parent().unwrap_or(&path);
+        if !Self::confirm_space(dest, ReportJob::estimated_size(&self.images), tr!("The report")) {
+            return;
+        }
+
+        let mut options = vec![];
+        if self.opts.compare {
+            options.push(("Reference".to_string(), self.opts.reference.display().to_string()));
+            options.push(("Candidates".to_string(), self.opts.root.display().to_string()));
+        } else {
+            options.push(("Root".to_string(), self.opts.root.display().to_string()));
+        }
+        for folder in &self.opts.subfolders {
+            options.push(("Subfolder".to_string(), folder.display().to_string()));
+        }
+        options.extend([
+            ("Hash".to_string(), format!("{:?}", self.opts.hash)),
+            ("Extensions".to_string(), self.opts.exts.clone()),
+            ("Check colors".to_string(), self.opts.verify_color.to_string()),
+            ("Follow symlinks".to_string(), self.opts.follow_sym.to_string()),
+            ("Size cap".to_string(), format!("{} MP", self.opts.max_megapixels)),
+            ("Accurate decode".to_string(), self.opts.accurate_decode.to_string()),
+            ("Detect crops".to_string(), self.opts.detect_crops.to_string()),
+            ("Trim borders".to_string(), self.opts.trim_borders.to_string()),
+            ("Central region hashed".to_string(), format!("{}%", self.opts.center_crop)),
+        ]);
+        let input = ReportInput{groups: self.images.clone(), stats: self.stats.clone(), options};
+        self.report = Some(ReportJob::start(input, path));
+    }
+
+    // Shows the report's progress while it's being written, and any error
+    // once it's done.
+    fn poll_report(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some(job) = &self.report else {
+            return Ok(());
+        };
+        if !job.is_finished() {
+            egui::Window::new(tr!("Exporting report"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(tr!("Making thumbnails… {done} of {total}", done = job.done(), total = job.total));
+                    ui.add(egui::ProgressBar::new(job.done() as f32 / job.total.max(1) as f32));
+                    if ui.button(tr!("Cancel")).clicked() {
+                        job.cancel();
+                    }
+                });
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return Ok(());
+        }
+
+        let job = self.report.take().unwrap();
+        let path = job.path.clone();
+        match job.join() {
+            Ok(()) | Err(None) => Ok(()),
+            Err(Some(e)) => Err(Error::new(
+                "Error exporting report".to_string(),
+                format!("Error writing {}: {e}", path.display()),
+            )),
+        }
+    }
+
+    fn launch_retry(&mut self, request: RetryRequest) {
+        assert!(self.retry.is_none());
+        let (retry, errors) = std::mem::take(&mut self.errors)
+            .into_iter()
+            .partition::<Vec<_>, _>(|x| match &request {
+                RetryRequest::All => x.retryable(),
+                RetryRequest::One(path) => x.retryable() && x.path.as_ref() == Some(path),
+            });
+        self.errors = errors;
+
+        let paths = retry.into_iter().filter_map(|x| x.path).collect();
+        let cap = self.opts.size_cap();
+        let (trim_borders, center) = (self.opts.trim_borders, self.opts.center_crop);
+        let index = self.index.clone();
+        self.retry = Some(std::thread::spawn(move || searcher::retry_files(paths, cap, trim_borders, center, index)));
+    }
+
+    // Merges in the results of the retry once it's done. Only the derived
+    // indices are rebuilt, so the scroll position is kept.
+    fn poll_retry(&mut self, ctx: &egui::Context) {
+        let request = self.retry_requested.lock().unwrap().take();
+        if let (Some(request), None) = (request, &self.retry) {
+            self.launch_retry(request);
+        }
+
+        if !self.retry.as_ref().is_some_and(|x| x.is_finished()) {
+            if self.retry.is_some() {
+                ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            }
+            return;
+        }
+
+        let results = match self.retry.take().unwrap().join() {
+            Ok(x) => x,
+            Err(_) => return, // Nothing was modified, so the old state still stands
+        };
+
+        self.merge_additions(results.additions);
+        self.errors.extend(results.errors);
+        self.index = results.index;
+        self.rebuild_index();
+    }
+
+    // Adds newly hashed images to the groups with their hash, or as new
+    // groups. Doesn't rebuild the index.
+    fn merge_additions(&mut self, additions: Vec<(ImageHash, Vec<Image>)>) {
+        for (hash, mut images) in additions {
+            if self.opts.compare {
+                for image in &mut images {
+                    let reference = image.path.starts_with(&self.opts.reference);
+                    image.side = Some(if reference { Side::Reference } else { Side::Candidate });
+                }
+            }
+
+            let existing = self.images.iter_mut()
+                .find(|group| group.first().and_then(|x| x.hash.as_ref()) == Some(&hash));
+            match existing {
+                Some(group) => group.extend(images),
+                None if images.len() > 1 => self.images.push(images),
+                None => (),
+            }
+        }
+    }
+
+    fn toggle_watch(&mut self, ctx: &egui::Context) {
+        if self.watch.take().is_some() {
+            return;
+        }
+
+        let mut roots = if self.opts.subfolders.is_empty() {
+            vec![self.opts.root.clone()]
+        } else {
+            self.opts.subfolders.clone()
+        };
+        if self.opts.compare {
+            roots.push(self.opts.reference.clone());
+        }
+        let opts = WatchOpts {
+            roots,
+            exts: self.opts.ext_set(),
+            size_cap: self.opts.size_cap(),
+            trim_borders: self.opts.trim_borders,
+            center: self.opts.center_crop,
+        };
+        match Watch::start(opts, self.index.clone(), ctx.clone()) {
+            Ok(x) => self.watch = Some(x),
+            Err(e) => {
+                self.errors.push(SearchError::new(ErrorKind::Io, None, e));
+                self.show_errors.store(true, Ordering::Relaxed);
+            },
+        }
+    }
+
+    // Deleted and modified files are dropped from their groups, and new (or
+    // rehashed) ones added. The index isn't updated; the watch keeps its own.
+    fn poll_watch(&mut self) {
+        let Some(watch) = &self.watch else {
+            return;
+        };
+        let updates = watch.poll();
+        if updates.is_empty() {
+            return;
+        }
+
+        for update in updates {
+            self.remove_images(&update.removed);
+            self.watch_new.extend(update.additions.iter().flat_map(|x| &x.1).map(|x| x.path.clone()));
+            self.merge_additions(update.additions);
+            if !update.errors.is_empty() {
+                self.errors.extend(update.errors);
+                self.show_errors.store(true, Ordering::Relaxed);
+            }
+        }
+        self.rebuild_index();
+    }
+
+    fn link_duplicates(&mut self) -> Result<()> {
+        if !hardlink::SUPPORTED {
+            return Err(Error::new(
+                "Unsupported".to_string(),
+                "Deduplicating via hard links is only supported on Unix-like systems".to_string(),
+            ));
+        }
+
+        // Links take no room for data, so unlike other bulk actions, there's
+        // no free space to check first.
+        let confirm = "In each group, every file but the first will be replaced with a hard link to \
+            the first. Groups spanning multiple filesystems are skipped. Edits to any copy \
+            will affect all of them afterwards. Continue?".to_string();
+        let groups: Vec<Vec<Image>> = self.bulk_groups().into_iter()
+            .map(|idx| self.images[idx].clone())
+            .collect();
+        self.run_plan(ActionPlan::link(&groups), "Deduplicate via hard links", confirm)
+    }
+
+    // Linked files take on the identity of the file they're now links to.
+    fn execute_links(&mut self, plan: &ActionPlan) {
+        let outcomes = hardlink::execute(&plan.steps);
+        let linked: HashSet<&PathBuf> = outcomes.iter()
+            .filter(|x| x.result.is_ok())
+            .map(|x| &x.path)
+            .collect();
+        let mut entry = HistoryEntry::new(
+            format!("Hard link {} files", outcomes.len()),
+            self.group_ids_with(&outcomes.iter().map(|x| &x.path).collect()),
+        );
+        (entry.files, entry.bytes) = (linked.len(), self.bytes_of(&linked));
+        for outcome in outcomes.iter().filter(|x| x.result.is_err()) {
+            entry.fail(outcome.path.clone());
+        }
+        let targets: HashMap<&PathBuf, &PathBuf> = plan.steps.iter()
+            .filter_map(|step| match &step.action {
+                Action::Link{target} if linked.contains(&step.path) => Some((&step.path, target)),
+                _ => None,
+            })
+            .collect();
+        let ids: HashMap<PathBuf, Option<FileId>> = self.images.iter()
+            .flatten()
+            .map(|x| (x.path.clone(), x.file_id))
+            .collect();
+        for image in self.images.iter_mut().flatten() {
+            if let Some(target) = targets.get(&image.path) {
+                image.file_id = ids.get(*target).copied().flatten();
+            }
+        }
+
+        self.record(entry);
+        self.link_outcomes = Some(outcomes);
+        self.rebuild_index();
+    }
+
+    // Portable exports have paths relative to where they're saved.
+    fn export_plan(plan: &ActionPlan, csv: bool, portable: bool) -> Result<()> {
+        let (ext, name) = if csv { ("csv", "CSV") } else { ("json", "JSON") };
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name(format!("plan.{ext}"))
+            .add_filter(name, &[ext])
+            .save_file() else {
+            return Ok(());
+        };
+        let relative;
+        let plan = match path.parent() {
+            Some(dir) if portable => {
+                relative = plan.relative_to(dir);
+                &relative
+            },
+            _ => plan,
+        };
+
+        let contents = if csv {
+            plan.to_csv()
+        } else {
+            plan.to_json().map_err(|e| Error::new("Error exporting plan".to_string(), e))?
+        };
+        std::fs::write(&path, contents).map_err(|e| {
+            Error::new("Error exporting plan".to_string(), format!("Error writing {}: {e}", path.display()))
+        })
+    }
+
+    fn draw_plan(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some(plan) = &self.plan else {
+            return Ok(());
+        };
+
+        let mut close = false;
+        let mut execute = false;
+        let mut export = None;
+        let title = tr!("Dry run: {description}", description = plan.description());
+        let vb = egui::viewport::ViewportBuilder::default().with_title(&title);
+        let vid = egui::viewport::ViewportId::from_hash_of("plan window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                close = ctx.input(|i| i.viewport().close_requested());
+
+                ui.strong(&title);
+                let problems = plan.steps.iter().filter(|x| x.problem.is_some()).count();
+                ui.label(tr!("{count} files, {problems} with problems", count = plan.steps.len(), problems = problems));
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Export CSV…")).clicked() {
+                        export = Some(true);
+                    }
+                    if ui.button(tr!("Export JSON…")).clicked() {
+                        export = Some(false);
+                    }
+                    execute = ui.button(tr!("Execute this plan now")).clicked();
+                });
+                ui.separator();
+
+                egui::ScrollArea::both().drag_to_scroll(false).show(ui, |ui| {
+                    egui::Grid::new("plan").striped(true).num_columns(4).show(ui, |ui| {
+                        ui.strong(tr!("File"));
+                        ui.strong(tr!("Action"));
+                        ui.strong(tr!("Destination"));
+                        ui.strong(tr!("Problem"));
+                        ui.end_row();
+                        for step in &plan.steps {
+                            ui.monospace(step.path.display().to_string());
+                            match &step.action {
+                                Action::Trash => {
+                                    ui.label(tr!("Trash"));
+                                    match &step.sidecar_of {
+                                        Some(image) => ui.label(tr!("with {file}", file = image.display())),
+                                        None => ui.label(""),
+                                    };
+                                },
+                                Action::Link{target} => {
+                                    ui.label(tr!("Hard link"));
+                                    ui.monospace(target.display().to_string());
+                                },
+                                Action::Tag{tag} => { ui.label(tr!("Tag")); ui.label(tag); },
+                                Action::Move{target, replace} => {
+                                    ui.label(if *replace { tr!("Replace") } else { tr!("Move") });
+                                    ui.monospace(target.display().to_string());
+                                },
+                                Action::Skip => { ui.label(tr!("Skip")); ui.label(""); },
+                            }
+                            match &step.problem {
+                                Some(x) => ui.colored_label(ui.visuals().warn_fg_color, x),
+                                None => ui.label(""),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        });
+
+        if let Some(csv) = export {
+            Self::export_plan(plan, csv, self.opts.portable)?;
+        }
+        if execute {
+            let replaced = Self::replaced(plan);
+            if replaced > 0 {
+                let confirmed = rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_title(tr!("Replace files"))
+                    .set_description(tr!("{count} files already there will be replaced, and can't be brought back.", count = replaced))
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+                if confirmed != rfd::MessageDialogResult::Yes {
+                    return Ok(());
+                }
+            }
+            let plan = self.plan.take().unwrap();
+            return self.execute_plan(plan);
+        }
+        if close {
+            self.plan = None;
+        }
+        Ok(())
+    }
+
+    // Files found broken, apart from the errors, as there's nothing to do
+    // about them but clear them out.
+    fn draw_broken(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        if self.broken.is_empty() {
+            return Ok(());
+        }
+
+        let mut trash = false;
+        egui::CollapsingHeader::new(tr!("Broken images ({count})", count = self.broken.len()))
+            .id_source("broken images")
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(tr!(
+                    "Empty, cut short, or a single flat color; mostly left by interrupted copies"
+                )).small());
+                trash = ui.button(tr!("Trash all broken images")).clicked();
+                egui::ScrollArea::vertical().max_height(Self::BROKEN_LIST_HEIGHT).show(ui, |ui| {
+                    egui::Grid::new("broken images").num_columns(3).striped(true).show(ui, |ui| {
+                        for image in &self.broken {
+                            ui.label(image.kind.label());
+                            ui.label(format_size(image.size));
+                            ui.monospace(image.path.display().to_string());
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        match trash {
+            true => self.trash_broken(),
+            false => Ok(()),
+        }
+    }
+
+    fn draw_stats(&self, ui: &mut egui::Ui) {
+        let Some(stats) = &self.stats else {
+            return;
+        };
+
+        egui::CollapsingHeader::new(tr!("Scan details")).id_source("scan details").show(ui, |ui| {
+            let shares = stats.stage_shares();
+            match shares {
+                Some(shares) => {
+                    let shares = shares.iter()
+                        .map(|(stage, share)| format!("{} {share:.0}%", tr_stage(stage)))
+                        .join(", ");
+                    ui.label(tr!("Search took {time} ({shares})", time = format_duration(stats.elapsed), shares = shares));
+                },
+                None => {
+                    ui.label(tr!("Search took {time}", time = format_duration(stats.elapsed)));
+                },
+            }
+            // Decoding is mostly disk for big files on slow volumes, and CPU
+            // for huge images; either way, these are the knobs.
+            if shares.is_some_and(|x| x[0].1 >= Self::DECODE_HINT_SHARE) {
+                ui.label(egui::RichText::new(tr!(
+                    "Most of the time went to reading and decoding images. A lower size cap \
+                    downscales large images sooner, and on a network volume, fewer I/O threads \
+                    can help."
+                )).small().color(ui.visuals().warn_fg_color));
+            }
+            if stats.skipped_over_budget > 0 {
+                ui.label(egui::RichText::new(tr!(
+                    "{count} images needed more memory to decode than the budget allows. If you \
+                    have memory to spare, raise the memory budget under Advanced and search again.",
+                    count = stats.skipped_over_budget,
+                )).small().color(ui.visuals().warn_fg_color));
+            }
+            for library in &stats.libraries {
+                ui.label(library.describe()).on_hover_text(library.top.display().to_string());
+            }
+            egui::Grid::new("scan details").num_columns(2).show(ui, |ui| {
+                let mut row = |label: &str, value: String| {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                };
+                row(tr!("Directories visited:"), stats.dirs_visited.to_string());
+                row(tr!("Files seen:"), stats.files_seen.to_string());
+                row(tr!("Images hashed:"), stats.files_hashed.to_string());
+                row(tr!("Bytes hashed:"), format_size(stats.bytes_hashed));
+                row(tr!("Detected by content:"), stats.sniffed.to_string());
+                row(tr!("Decode failures:"), stats.decode_failures.to_string());
+                row(tr!("Decodes saved, exact copies:"), stats.decodes_saved.to_string());
+                row(tr!("Split by color:"), stats.color_rejected.to_string());
+                row(tr!("Possible crops:"), stats.crop_matches.to_string());
+                row(tr!("Skipped, over size cap:"), stats.skipped_too_large.to_string());
+                row(tr!("Skipped, over memory budget:"), stats.skipped_over_budget.to_string());
+                row(tr!("Skipped, no extension:"), stats.skipped_no_ext.to_string());
+                row(tr!("Skipped by date:"), stats.skipped_by_date.to_string());
+                row(tr!("Unknown modification time:"), stats.unknown_mtime.to_string());
+                let skipped: usize = stats.skipped_by_ext.values().sum();
+                row(tr!("Skipped by extension:"), skipped.to_string());
+                for (ext, count) in &stats.skipped_by_ext {
+                    row("", format!(".{ext}: {count}"));
+                }
+                row(tr!("Contents don't match extension:"), stats.mismatched.len().to_string());
+                row(tr!("Broken images:"), stats.broken.values().sum::<usize>().to_string());
+                for kind in BrokenKind::ALL {
+                    row("", format!("{}: {}", kind.label(), stats.broken.get(&kind).copied().unwrap_or(0)));
+                }
+            });
+            if !stats.mismatched.is_empty() {
+                egui::CollapsingHeader::new(tr!("Files whose contents don't match their extension"))
+                    .id_source("mismatched files")
+                    .show(ui, |ui| {
+                        for (path, detected) in &stats.mismatched {
+                            ui.label(format!("{}: {}", path.display(), detected.label()));
+                        }
+                    });
+            }
+        });
+    }
+
+    // Suggestions for a search that found nothing, each with a way back to
+    // the options to change. Not shown once the results have been acted on,
+    // when empty is the point.
+    fn draw_empty_state(&self, ui: &mut egui::Ui) -> Option<FocusHint> {
+        if !self.undo.is_empty() || !self.ignored.is_empty() || !self.excluded.is_empty() {
+            return None;
+        }
+        let suggestions = empty_state_suggestions(self.stats.as_ref()?);
+        if suggestions.is_empty() {
+            return None;
+        }
+
+        let mut hint = None;
+        ui.add_space(8.0);
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.strong(tr!("If you expected duplicates, some options may have ruled them out:"));
+            egui::Grid::new("empty state").num_columns(2).spacing([20.0, 8.0]).show(ui, |ui| {
+                for (suggestion, count) in suggestions {
+                    let (text, button) = match suggestion {
+                        FocusHint::Extensions => (tr!(
+                            "{count} files were skipped for their extension. Include more formats, or detect them by content.",
+                            count = count,
+                        ), tr!("Change extensions")),
+                        FocusHint::Matching => (tr!(
+                            "{count} matches were split up because their colors differed. Turn off color \
+                            verification, or try a looser hash algorithm.",
+                            count = count,
+                        ), tr!("Change matching")),
+                        FocusHint::SizeLimits => (tr!(
+                            "{count} images were skipped for their size or the memory they'd need. Raise the \
+                            limits to include them.",
+                            count = count,
+                        ), tr!("Change size limits")),
+                    };
+                    ui.label(text);
+                    if ui.button(button).clicked() {
+                        hint = Some(suggestion);
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.label(egui::RichText::new(tr!("Scan details, above, has the full counts.")).small());
+        });
+        hint
+    }
+
+    fn draw_link_outcomes(&mut self, ctx: &egui::Context) {
+        let Some(outcomes) = &self.link_outcomes else {
+            return;
+        };
+
+        let failed = outcomes.iter().filter(|x| x.result.is_err()).count();
+        let mut open = true;
+        egui::Window::new(tr!("Hard link results")).open(&mut open).show(ctx, |ui| {
+            ui.label(tr!("{linked} linked, {failed} failed", linked = outcomes.len() - failed, failed = failed));
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for outcome in outcomes {
+                    let path = outcome.path.display().to_string();
+                    match &outcome.result {
+                        Ok(()) => ui.label(format!("Linked {path}")),
+                        Err(e) => ui.colored_label(ui.visuals().error_fg_color, format!("{path}: {e}")),
+                    };
+                }
+            });
+        });
+
+        if !open {
+            self.link_outcomes = None;
+        }
+    }
+
+    // What the last trash action moved, with a way to get to the trash to
+    // empty it.
+    fn draw_trashed(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some((count, bytes, sidecars)) = self.trashed else {
+            return Ok(());
+        };
+
+        let mut open = true;
+        let mut open_trash = false;
+        egui::Window::new(tr!("Moved to the trash")).open(&mut open).show(ctx, |ui| {
+            ui.label(tr!(
+                "{count} files ({size}) moved to the trash. Empty it to free the space.",
+                count = count,
+                size = format_size(bytes),
+            ));
+            if sidecars > 0 {
+                ui.label(tr!("Along with {count} sidecar files", count = sidecars));
+            }
+            open_trash = ui.button(tr!("Open Trash")).clicked();
+        });
+
+        if !open {
+            self.trashed = None;
+        }
+        if open_trash {
+            undo::open_trash().map_err(|e| Error::new("Error opening the trash".to_string(), e))?;
+        }
+        Ok(())
+    }
+
+    pub fn into_dyn(self) -> DynPhase {
+        Box::new(self)
+    }
+
+    // The full-size image, for the preview window. egui decodes and caches it
+    // by URI, until it's forgotten.
+    fn image_widget<'a>(ctx: &egui::Context, image: &Image) -> egui::Image<'a> {
+        let uri = os_path::image_uri(&image.path);
+        match Self::animation(ctx, image) {
+            Some(x) => egui::Image::from_texture(SizedTexture::from_handle(x.current_frame(ctx))),
+            None => egui::Image::from_bytes(uri, image.buffer()),
+        }
+    }
+
+    fn animation(ctx: &egui::Context, image: &Image) -> Option<Arc<Animation>> {
+        let uri = os_path::image_uri(&image.path);
+        image.is_animated()
+            .then(|| Animation::get(ctx, &uri, &image.buffer()))
+            .flatten()
+    }
+
+    // The image widget for a result's thumbnail, at whatever size, or None
+    // while it's still being decoded. Ones that couldn't be decoded get the
+    // bytes anyway, for egui to show its error.
+    fn thumbnail_widget<'a>(&self, ctx: &egui::Context, image: &Image) -> Option<egui::Image<'a>> {
+        if let Some(x) = Self::animation(ctx, image) {
+            return Some(egui::Image::from_texture(SizedTexture::from_handle(x.current_frame(ctx))));
+        }
+        match self.thumbnails.get(ctx, image) {
+            Thumbnail::Ready(texture) => Some(egui::Image::from_texture(SizedTexture::from_handle(&texture))),
+            Thumbnail::Loading => None,
+            Thumbnail::Failed => Some(Self::image_widget(ctx, image)),
+        }
+    }
+
+    // Stands in for a thumbnail that isn't ready yet: a grey box of its shape.
+    fn draw_placeholder(ui: &mut egui::Ui, image: &Image, max_size: Option<f32>, selected: bool) -> egui::Response {
+        let side = max_size.unwrap_or(Self::MIN_CELL_SIZE);
+        let size = match image.dimm {
+            Some((width, height)) if width > 0 && height > 0 => {
+                let scale = side / width.max(height) as f32;
+                egui::vec2(width as f32 * scale, height as f32 * scale)
+            },
+            _ => egui::vec2(side, side),
+        };
+        let (rect, resp) = ui.allocate_exact_size(size, egui::Sense::click());
+        let visuals = ui.visuals();
+        ui.painter().rect_filled(rect, visuals.widgets.noninteractive.rounding, visuals.faint_bg_color);
+        if selected {
+            ui.painter().rect_stroke(rect, visuals.widgets.noninteractive.rounding, visuals.selection.stroke);
+        }
+        resp
+    }
+
+    // The thumbnail's tooltip. The metadata is all already known; the image is
+    // the thumbnail's (and a spinner until it's ready).
+    fn draw_preview(&self, ui: &mut egui::Ui, image: &Image) {
+        match self.thumbnail_widget(ui.ctx(), image) {
+            Some(img) => ui.add(img.max_size(egui::vec2(Self::PREVIEW_SIZE, Self::PREVIEW_SIZE))),
+            None => ui.spinner(),
+        };
+        ui.monospace(image.path.display().to_string());
+        if let Some((width, height)) = image.dimm {
+            ui.label(format!("{width}×{height}"));
+        }
+        ui.label(format_size(image.file_size));
+        if let Some(mtime) = image.mtime {
+            ui.label(tr!("Modified {time}", time = format_time(mtime)));
+        }
+    }
+
+    // What a screen reader says for a thumbnail, e.g. "Photo IMG_1234.jpg,
+    // 4032 by 3024, 3.1 MB, duplicate 2 of 3 in group 7".
+    fn thumbnail_name(image: &Image, place: &Place) -> String {
+        let kind = image.content.map_or(tr!("Image"), ContentKind::label);
+        let mut parts = vec![format!("{kind} {}", image.file_name())];
+        if let Some((width, height)) = image.dimm {
+            parts.push(tr!("{width} by {height}", width = width, height = height));
+        }
+        parts.push(format_size(image.file_size));
+        parts.push(tr!(
+            "duplicate {member} of {count} in group {group}",
+            member = place.member,
+            count = place.count,
+            group = place.group,
+        ));
+        parts.join(", ")
+    }
+
+    // What clicking it does is up to the user; see clicks. Returns the group
+    // action for the pointer being over it or clicking it, if either.
+    fn draw_thumbnail(&self, ui: &mut egui::Ui, image: &Image, place: &Place, max_size: Option<f32>) -> Option<GroupAction> {
+        let selected = self.selected.as_ref() == Some(&image.path);
+        let dropped = self.kept(place.group - 1, image) == Some(false);
+        let mut resp = ui.scope(|ui| {
+            if dropped {
+                ui.set_opacity(Self::DROPPED_OPACITY);
+            }
+            match self.thumbnail_widget(ui.ctx(), image) {
+                Some(mut img) => {
+                    if let Some(size) = max_size {
+                        img = img.max_size(egui::vec2(size, size));
+                    }
+                    let button = egui::widgets::ImageButton::new(img)
+                        .selected(selected)
+                        .sense(egui::Sense::click());
+                    ui.add(button)
+                },
+                None => Self::draw_placeholder(ui, image, max_size, selected),
+            }
+        }).inner;
+        a11y::name(&resp, egui::WidgetType::ImageButton, &Self::thumbnail_name(image, place));
+        a11y::keep_focus_visible(&resp);
+
+        // Tooltips popping up and down under a moving pointer would flicker.
+        let moving = ui.input(|i| i.pointer.is_decidedly_dragging() || i.smooth_scroll_delta != egui::Vec2::ZERO);
+        if !moving {
+            resp = resp.on_hover_ui(|ui| self.draw_preview(ui, image));
+        }
+
+        match Click::of(&resp) {
+            Some(click) => Some(GroupAction::Click(click, image.path.clone())),
+            None => resp.hovered().then(|| GroupAction::Focus(image.path.clone())),
+        }
+    }
+
+    // Only in groups with a keeper previewed: a badge on the keeper, and a
+    // button on the rest to pin one instead.
+    fn draw_keeper_badge(&self, ui: &mut egui::Ui, group_idx: usize, image: &Image) -> Option<GroupAction> {
+        let kept = self.kept(group_idx, image)?;
+        let resp = if kept {
+            let group = &self.images[group_idx];
+            let pinned = self.reviews.pinned(group).is_some_and(|x| group[x].path == image.path);
+            let (text, hint) = match pinned {
+                true => (tr!("📌 Keep"), tr!("Pinned as this group's keeper. Click to go back to the keeper strategy's choice.")),
+                false => (tr!("✔ Keep"), tr!("The keeper strategy's choice. Nothing happens to any file until the choices are applied.")),
+            };
+            let text = egui::RichText::new(text).strong().color(theme::success_color(ui.visuals()));
+            ui.add(egui::Button::new(text).small()).on_hover_text(hint)
+        } else {
+            ui.small_button(tr!("Keep this")).on_hover_text(tr!("Pin this as the group's keeper instead"))
+        };
+        a11y::list_button(resp, &tr!("Keep {file}", file = image.file_name()))
+            .clicked()
+            .then(|| GroupAction::PinKeeper(image.path.clone()))
+    }
+
+    // Under whichever folder it was found in.
+    fn relative_path<'a>(&self, image: &'a Image) -> &'a Path {
+        let base = match image.side {
+            Some(Side::Reference) => &self.opts.reference,
+            _ => &self.opts.root,
+        };
+        image.path.strip_prefix(base).unwrap_or(&image.path)
+    }
+
+    fn draw_metadata(&self, ui: &mut egui::Ui, image: &Image) {
+        let stripped = self.relative_path(image);
+
+        match image.side {
+            Some(Side::Reference) => {
+                ui.label(egui::RichText::new(tr!("Reference")).strong().color(ui.visuals().hyperlink_color));
+            },
+            Some(Side::Candidate) => {
+                ui.label(egui::RichText::new(tr!("Candidate")).strong().color(ui.visuals().warn_fg_color));
+            },
+            None => (),
+        }
+
+        ui.label(
+            egui::RichText::new(stripped.display().to_string())
+                .monospace()
+                .size(Self::HEADER_SIZE)
+        );
+        ui.add_space(Self::CELL_2_DATA_SPACING);
+
+        if let Some((width, height)) = image.dimm {
+            ui.horizontal(|ui| {
+                ui.label(format!("{width}×{height}"));
+                if let Some(content) = image.content.filter(|x| *x != ContentKind::Photo) {
+                    Self::draw_content_badge(ui, content);
+                }
+            });
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        // References are what's matched against, so only their matches say
+        // how close they are.
+        if let (Some(distance), false) = (self.distances.get(&image.path), image.side == Some(Side::Reference)) {
+            let label = match distance {
+                0 => egui::RichText::new(tr!("exact")).color(theme::success_color(ui.visuals())),
+                _ => egui::RichText::new(tr!("distance {distance}", distance = distance))
+                    .color(ui.visuals().warn_fg_color),
+            };
+            ui.label(label.small())
+                .on_hover_text(tr!("How many bits of its hash differ from the rest of the group's"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if self.sensitive_root && is_system_path(&image.path) {
+            ui.label(egui::RichText::new(tr!("System file")).strong().color(ui.visuals().error_fg_color))
+                .on_hover_text(tr!("In a folder the OS manages; bulk actions leave it alone"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if self.watch_new.contains(&image.path) {
+            ui.label(egui::RichText::new(tr!("New")).color(ui.visuals().hyperlink_color))
+                .on_hover_text(tr!("Found while watching for changes"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        } else if self.live_new.contains(&image.path) {
+            ui.label(egui::RichText::new(tr!("New")).color(ui.visuals().hyperlink_color))
+                .on_hover_text(tr!("Found since the results were first shown; click to clear"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if image.possible_crop {
+            ui.label(egui::RichText::new(tr!("Possible crop")).strong().color(ui.visuals().warn_fg_color))
+                .on_hover_text(tr!("Matched by crop detection; check that it really is the same photo"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if image.is_animated() {
+            ui.label(tr!("Animated, {count} frames", count = image.frame_count));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if let Some(tags) = self.finder_tags.get(&image.path).filter(|x| !x.is_empty()) {
+            Self::draw_finder_tags(ui, tags);
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if let Some(encoding) = &image.encoding {
+            let resp = ui.label(encoding.label());
+            if let Some(subsampling) = encoding.jpeg.and_then(|x| x.subsampling) {
+                resp.on_hover_text(tr!("{subsampling} chroma subsampling", subsampling = subsampling.label()));
+            }
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if self.derivatives.contains(&image.path) {
+            ui.label(egui::RichText::new(tr!("Derivative")).color(ui.visuals().hyperlink_color))
+                .on_hover_text(tr!("Named by a derivative pattern after another file in this group and folder"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if self.renamed.contains(&image.path) {
+            ui.label(egui::RichText::new(tr!("Renamed copy")).strong().color(ui.visuals().warn_fg_color))
+                .on_hover_text(tr!("Named like an automatic copy, e.g. \"IMG_1234 (1)\", of another file in this folder"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        if self.keepers.contains(&image.path) {
+            ui.label(egui::RichText::new(tr!("Recommended keeper")).strong().color(ui.visuals().hyperlink_color))
+                .on_hover_text(tr!("The lossless or highest quality copy in its group; between otherwise equal \
+                    copies, the one that wasn't renamed"));
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+        }
+        ui.label(Self::size_label(image));
+        self.draw_gain(ui, image);
+
+        if image.changed {
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+            ui.label(egui::RichText::new(tr!("Changed since scan")).color(ui.visuals().warn_fg_color));
+        }
+
+        if image.file_id.is_some_and(|id| self.linked_ids.contains(&id)) {
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+            ui.label(egui::RichText::new(tr!("Hard link of another copy")).italics());
+        }
+
+        if let Some(hash) = &image.hash {
+            ui.add_space(Self::CELL_2_DATA_SPACING);
+            egui::CollapsingHeader::new(egui::RichText::new(tr!("Details")).small())
+                .id_source(("details", &image.path))
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let hash = hash.to_base64();
+                        ui.label(egui::RichText::new(&hash).monospace().small())
+                            .on_hover_text(tr!("The perceptual hash it was grouped by"));
+                        if ui.small_button("📋").on_hover_text(tr!("Copy hash")).clicked() {
+                            ui.output_mut(|out| out.copied_text = hash);
+                        }
+                    });
+                });
+        }
+    }
+
+    fn draw_buttons(ui: &mut egui::Ui, image: &Image) -> Option<GroupAction> {
+        let mut action = None;
+        let file = image.file_name();
+        ui.horizontal(|ui| {
+            let open = a11y::list_button(ui.button(tr!("Open")), &tr!("Open {file}", file = file));
+            let show = a11y::list_button(ui.button(tr!("Show")), &tr!("Show {file} in file manager", file = file));
+            if open.clicked() {
+                action = Some(GroupAction::Open(image.path.clone()));
+            } else if show.clicked() {
+                action = Some(GroupAction::Reveal(image.path.clone()));
+            }
+
+            if a11y::list_button(ui.button(tr!("Copy path")), &tr!("Copy path of {file}", file = file)).clicked() {
+                ui.output_mut(|out| 
+                    out.copied_text = os_path::clipboard_text(&image.path)
+                );
+            }
+
+            if quick_look::SUPPORTED {
+                let resp = ui.button(tr!("Quick Look")).on_hover_text(tr!("Preview with Quick Look (Space)"));
+                if a11y::list_button(resp, &tr!("Quick Look {file}", file = file)).clicked() && action.is_none() {
+                    action = Some(GroupAction::QuickLook(image.path.clone()));
+                }
+            }
+
+            let exclude = ui.small_button(tr!("Not a duplicate"))
+                .on_hover_text(tr!("Take this file out of its group, in this and future searches"));
+            if a11y::list_button(exclude, &tr!("{file} is not a duplicate", file = file)).clicked() {
+                action = Some(GroupAction::Exclude(image.path.clone()));
+            }
+        });
+        action
+    }
+
+    // Only offered on groups of two; other pairs are compared from the compact
+    // list's menus. Shown even without a tool set, to say where to set one.
+    fn draw_compare_button(&self, ui: &mut egui::Ui, group_idx: usize) -> Option<GroupAction> {
+        let [a, b] = &self.images[group_idx][..] else {
+            return None;
+        };
+        let resp = ui.add_enabled(self.compare_configured(), egui::Button::new(tr!("Compare")).small())
+            .on_hover_text(tr!("Open both in the external compare tool"))
+            .on_disabled_hover_text(tr!("Set an external compare tool under Advanced in the search options"));
+        a11y::list_button(resp, &tr!("Compare group {number} externally", number = group_idx + 1))
+            .clicked()
+            .then(|| GroupAction::CompareExternally(a.path.clone(), b.path.clone()))
+    }
+
+    fn compare_configured(&self) -> bool {
+        !self.opts.compare_command.trim().is_empty()
+    }
+
+    fn draw_refine_button(ui: &mut egui::Ui, number: usize) -> bool {
+        let resp = ui.small_button(tr!("Re-scan…"))
+            .on_hover_text(tr!("Re-scan this folder with different settings…"));
+        a11y::list_button(resp, &tr!("Re-scan the folder of group {number}", number = number)).clicked()
+    }
+
+    // The group controls (only offered on the last row of a group) shown for a
+    // burst, given its size and whether it's expanded.
+    fn draw_burst_toggle(ui: &mut egui::Ui, count: usize, expanded: bool) -> bool {
+        let badge = egui::RichText::new(tr!("Burst of {count}", count = count))
+            .strong()
+            .color(ui.visuals().warn_fg_color);
+        ui.label(badge);
+        let text = if expanded { tr!("Collapse") } else { tr!("Expand") };
+        let resp = ui.small_button(text);
+        a11y::keep_focus_visible(&resp);
+        resp.clicked()
+    }
+
+    fn draw_finder_tags(ui: &mut egui::Ui, tags: &[FinderTag]) {
+        ui.horizontal_wrapped(|ui| {
+            for tag in tags {
+                let text = egui::RichText::new(format!("● {}", tag.name)).small();
+                match tag.color {
+                    Some(color) => ui.label(text.color(color.color(ui.visuals()))),
+                    None => ui.label(text),
+                };
+            }
+        }).response.on_hover_text(tr!("Finder tags"));
+    }
+
+    // Photos are the norm, so only the others get one.
+    fn draw_content_badge(ui: &mut egui::Ui, content: ContentKind) {
+        ui.label(egui::RichText::new(content.label()).small().strong().color(ui.visuals().hyperlink_color))
+            .on_hover_text(tr!("A guess from the image's size and colors; only used for display and filtering"));
+    }
+
+    fn draw_encoding_badge(ui: &mut egui::Ui, badge: &str) {
+        ui.label(egui::RichText::new(badge).strong())
+            .on_hover_text(tr!("The same picture in different formats or qualities"));
+    }
+
+    fn draw_variants_badge(ui: &mut egui::Ui) {
+        ui.label(egui::RichText::new(tr!("Resolution variants")).strong())
+            .on_hover_text(tr!("The same picture at different sizes; the largest can be kept"));
+    }
+
+    // Draws an image's pair of cells, the thumbnail and its metadata. Returns
+    // the action taken on the group, if any. Only the group's last image has
+    // group controls, but every cell on a group's last line draws a separator.
+    fn draw_output_cell(
+        &self,
+        ui: &mut egui::Ui,
+        image: &Image,
+        place: &Place,
+        last_line: bool,
+        controls: Option<GroupControls>,
+    ) -> Option<GroupAction> {
+        let mut action = None;
+
+        // The list has no group headers on screen, just separators.
+        if place.member == 1 {
+            let id = ui.id().with(("group heading", place.group));
+            a11y::hidden_heading(ui, id, &tr!("Group {number}", number = place.group));
+        }
+        ui.centered_and_justified(|ui| {
+            action = self.draw_thumbnail(ui, image, place, None);
+            if last_line {
+                ui.separator();
+            }
+        });
+
+        ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
+            ui.add_space(Self::CELL_2_TOP_SPACING);
+            if let Some(x) = self.draw_keeper_badge(ui, place.group - 1, image) {
+                action = Some(x);
+            }
+            self.draw_metadata(ui, image);
+
+            ui.with_layout(egui::Layout::bottom_up(egui::Align::Min), |ui| {
+                let sep_height = if last_line {
+                    ui.separator().rect.height().clamp(0.0, Self::CELL_2_BOTTOM_SPACING)
+                } else {
+                    0.0
+                };
+                ui.add_space(Self::CELL_2_BOTTOM_SPACING - sep_height);
+                if let Some(x) = Self::draw_buttons(ui, image) {
+                    action = Some(x);
+                }
+                if let Some(controls) = controls {
+                    ui.horizontal(|ui| {
+                        let group = place.group;
+                        let ignore = ui.small_button(tr!("Ignore group"))
+                            .on_hover_text(tr!("Don't show this group again in future searches"));
+                        if a11y::list_button(ignore, &tr!("Ignore group {number}", number = group)).clicked() {
+                            action = Some(GroupAction::Ignore);
+                        }
+                        if let Some((count, expanded)) = controls.burst {
+                            if Self::draw_burst_toggle(ui, count, expanded) {
+                                action = Some(GroupAction::ToggleBurst);
+                            }
+                        }
+                        if let Some(badge) = controls.encoding {
+                            Self::draw_encoding_badge(ui, badge);
+                        }
+                        if controls.variants {
+                            Self::draw_variants_badge(ui);
+                        }
+                        if controls.hook
+                            && a11y::list_button(ui.small_button(tr!("Run hook")), &tr!("Run hook on group {number}", number = group))
+                                .clicked() {
+                            action = Some(GroupAction::RunHook);
+                        }
+                        if Self::draw_refine_button(ui, group) {
+                            action = Some(GroupAction::Refine);
+                        }
+                        if let Some(x) = self.draw_compare_button(ui, group - 1) {
+                            action = Some(x);
+                        }
+                        ui.menu_button("…", |ui| {
+                            if let Some(x) = self.draw_group_menu(ui, group - 1) {
+                                action = Some(x);
+                            }
+                        }).response.on_hover_text(tr!("More actions for this group"));
+                    });
+                    ui.horizontal(|ui| {
+                        if let Some(x) = self.draw_review(ui, place.group - 1) {
+                            action = Some(x);
+                        }
+                        ui.label(self.group_size_label(place.group - 1));
+                    });
+                    if controls.large {
+                        ui.horizontal(|ui| {
+                            if let Some(x) = self.draw_large_summary(ui, place.group - 1) {
+                                action = Some(x);
+                            }
+                        });
+                    }
+                }
+            });
+        });
+
+        action
+    }
+
+    // Actually draws multiple tables, one per set of duplicates, but it looks
+    // like one big table with multiple sections. Also draws all errors reported
+    // by Searcher.
+    fn draw_output_table(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        let mut scroll = egui::ScrollArea::vertical().drag_to_scroll(false);
+
+        // Scroll offset is persistent, and I can't find a way to opt-out for
+        // a single widget. This overrides it manually.
+        if self.first_update {
+            scroll = scroll.vertical_scroll_offset(0.0);
+            self.first_update = false;
+        }
+
+        // Only rebuilt on resize (or when the rows change), not every frame.
+        let per_line = Self::pairs_per_line(ui.available_width());
+        if per_line != self.lines_per {
+            self.lines = chunk_groups(&self.group_ends, per_line);
+            self.lines_per = per_line;
+        }
+
+        // With one pair, the columns take whatever they need, as they always
+        // have; with more, they have to share.
+        let mut grid = egui::Grid::new(0)
+            .striped(true)
+            .min_col_width(Self::MIN_CELL_SIZE)
+            .min_row_height(Self::MIN_CELL_SIZE)
+            .spacing((Self::H_SPACING, ui.spacing().item_spacing.y))
+            .num_columns(2 * per_line);
+        if per_line > 1 {
+            let column = ui.available_width() / (2 * per_line) as f32 - Self::H_SPACING;
+            grid = grid.max_col_width(column.max(Self::MIN_CELL_SIZE));
+        }
+
+        if let Some(row) = self.scroll_to_row.take() {
+            let line = self.lines.partition_point(|x| x.end <= row);
+            scroll = scroll.vertical_scroll_offset(line as f32 * (Self::MIN_CELL_SIZE + ui.spacing().item_spacing.y));
+        }
+
+        let mut action = None;
+        let mut top_row = None;
+        let total_lines = self.lines.len();
+        scroll.show_rows(ui, Self::MIN_CELL_SIZE, total_lines, |ui, range| {
+            top_row = self.lines.get(range.start).map(|x| x.start);
+            grid.show(ui, |ui| {
+                for line in &self.lines[range] {
+                    let last_line = self.group_ends.binary_search(&(line.end - 1)).is_ok();
+                    for idx in line.clone() {
+                        let (group_idx, member) = self.rows[idx];
+                        let controls = (last_line && idx + 1 == line.end).then(|| GroupControls {
+                            burst: self.bursts[group_idx]
+                                .then(|| (self.images[group_idx].len(), !self.is_collapsed(group_idx))),
+                            large: self.is_large(group_idx),
+                            encoding: self.encoding_badges[group_idx].as_deref(),
+                            variants: matches!(self.variants[group_idx], Variants::Largest(_)),
+                            hook: !self.opts.hook_command.trim().is_empty(),
+                        });
+                        let image = &self.images[group_idx][member];
+                        let place = Place{group: group_idx + 1, member: member + 1, count: self.images[group_idx].len()};
+                        if let Some(x) = self.draw_output_cell(ui, image, &place, last_line, controls) {
+                            action = Some((group_idx, x));
+                        }
+                    }
+
+                    // A group ending mid-line still gets a separator all the
+                    // way across.
+                    if last_line {
+                        for _ in 0..2 * (per_line - line.len()) {
+                            ui.with_layout(egui::Layout::bottom_up(egui::Align::Min), |ui| {
+                                ui.separator();
+                            });
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        self.top_row = top_row.unwrap_or(0);
+
+        if let Some((idx, action)) = action {
+            self.handle_group_action(idx, action)?;
+        }
+        Ok(())
+    }
+
+    // The list at a glance: tiny thumbnails, one line per file, with every
+    // row the same height so show_rows() can skip straight to any of them.
+    fn draw_compact_table(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        let mut scroll = egui::ScrollArea::vertical().drag_to_scroll(false);
+        let row_height = Self::COMPACT_ROW_HEIGHT + ui.spacing().item_spacing.y;
+        if self.first_update {
+            scroll = scroll.vertical_scroll_offset(0.0);
+            self.first_update = false;
+        }
+        // A group's first member brings its header along; later ones, the
+        // member above.
+        if let Some(row) = self.scroll_to_row.take() {
+            let group = self.group_ends.partition_point(|x| *x < row);
+            scroll = scroll.vertical_scroll_offset((row + group) as f32 * row_height);
+        }
+
+        let mut action = None;
+        let mut top_row = None;
+        scroll.show_rows(ui, Self::COMPACT_ROW_HEIGHT, self.compact_rows.len(), |ui, range| {
+            top_row = self.compact_rows.get(range.start).map(|x| match *x {
+                CompactRow::Header(0) => 0,
+                CompactRow::Header(group) => self.group_ends[group - 1] + 1,
+                CompactRow::Member(row) => row,
+            });
+            for (idx, row) in self.compact_rows[range.clone()].iter().enumerate() {
+                let width = ui.available_width();
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(width, Self::COMPACT_ROW_HEIGHT), egui::Sense::hover());
+                let resp = ui.interact(rect, ui.id().with(("compact row", range.start + idx)), egui::Sense::click());
+                let mut row_ui = ui.child_ui(rect, egui::Layout::left_to_right(egui::Align::Center));
+                let drawn = match *row {
+                    CompactRow::Header(group) => {
+                        let group_idx = self.shown_groups[group];
+                        self.draw_compact_header(&mut row_ui, resp, group_idx).map(|x| (group_idx, x))
+                    },
+                    CompactRow::Member(row) => {
+                        let (group_idx, member) = self.rows[row];
+                        self.draw_compact_member(&mut row_ui, resp, group_idx, member).map(|x| (group_idx, x))
+                    },
+                };
+                if drawn.is_some() {
+                    action = drawn;
+                }
+            }
+        });
+        self.top_row = top_row.unwrap_or(0);
+
+        if let Some((idx, action)) = action {
+            self.handle_group_action(idx, action)?;
+        }
+        Ok(())
+    }
+
+    // What a large group is, since it isn't all shown, and the controls for
+    // paging through it or dealing with it as a whole.
+    fn draw_large_summary(&self, ui: &mut egui::Ui, group_idx: usize) -> Option<GroupAction> {
+        let group = &self.images[group_idx];
+        let size = format_size(group.iter().map(|x| x.file_size).sum::<usize>());
+        let summary = match common_folder(group) {
+            Some(folder) => tr!(
+                "{count} matching images in {folder} — {size}",
+                count = group.len(),
+                folder = self.folder_label(&folder),
+                size = size,
+            ),
+            None => tr!("{count} matching images — {size}", count = group.len(), size = size),
+        };
+        ui.label(egui::RichText::new(summary).strong());
+
+        let mut action = None;
+        let shown = self.visible_members(group_idx).len();
+        if shown < group.len() {
+            let next = self.opts.large_group.min(group.len() - shown);
+            if ui.small_button(tr!("Show {count} more", count = next))
+                .on_hover_text(tr!("{shown} of {total} shown", shown = shown, total = group.len()))
+                .clicked() {
+                action = Some(GroupAction::ShowMore);
+            }
+        }
+        if shown > 1 && ui.small_button(tr!("Collapse")).clicked() {
+            action = Some(GroupAction::CollapseLarge);
+        }
+        let keep_first = ui.small_button(tr!("Keep first, trash the other {count}", count = group.len() - 1))
+            .on_hover_text(tr!("Keep {file}, the first shown, and move the rest of the group to the trash", file = group[0].file_name()));
+        if keep_first.clicked() {
+            action = Some(GroupAction::KeepFirst);
+        }
+        action
+    }
+
+    // E.g. "3 images, 12.4 MB, 8.1 MB reclaimable".
+    fn group_size_label(&self, group_idx: usize) -> String {
+        let count = self.images[group_idx].len();
+        match &self.group_sizes[group_idx] {
+            GroupSizes{total: Some(total), wasted: Some(wasted), ..} => tr!(
+                "{count} images, {total}, {wasted} reclaimable",
+                count = count,
+                total = format_size(*total),
+                wasted = format_size(*wasted),
+            ),
+            _ => tr!("{count} images, size unknown", count = count),
+        }
+    }
+
+    fn size_label(image: &Image) -> String {
+        image.known_size().map_or_else(|| tr!("size unknown").to_string(), format_size)
+    }
+
+    // What removing the image would free, unless it's the one that'd be kept.
+    fn draw_gain(&self, ui: &mut egui::Ui, image: &Image) {
+        if let Some(gain) = self.gains.get(&image.path) {
+            ui.label(egui::RichText::new(tr!("+{size} if removed", size = format_size(*gain)))
+                .small()
+                .color(theme::success_color(ui.visuals())));
+        }
+    }
+
+    // The group's number is just its place in the results; this is what to
+    // call it anywhere else.
+    fn draw_group_id(ui: &mut egui::Ui, group: &[Image]) {
+        ui.weak(group_id(group)).on_hover_text(tr!(
+            "This group's ID, as in exports. It's the same in every search, as long as the group has the same files."
+        ));
+    }
+
+    fn draw_compact_header(&self, ui: &mut egui::Ui, resp: egui::Response, group_idx: usize) -> Option<GroupAction> {
+        let group = &self.images[group_idx];
+        let number = group_idx + 1;
+        a11y::heading(&ui.strong(tr!("Group {number}", number = number)));
+        Self::draw_group_id(ui, group);
+        ui.label(self.group_size_label(group_idx));
+        let mark = self.reviews.mark(group);
+        if mark != Mark::Unreviewed {
+            ui.label(mark.symbol()).on_hover_text(mark.label());
+        }
+        if self.bursts[group_idx] {
+            ui.label(egui::RichText::new(tr!("Burst of {count}", count = group.len()))
+                .strong()
+                .color(ui.visuals().warn_fg_color));
+        }
+        if matches!(self.variants[group_idx], Variants::Largest(_)) {
+            Self::draw_variants_badge(ui);
+        }
+        let mut action = None;
+        if self.is_large(group_idx) {
+            action = self.draw_large_summary(ui, group_idx);
+        }
+
+        let resp = a11y::list_button(resp, &tr!("Actions for group {number}", number = number));
+        resp.context_menu(|ui| {
+            if let Some(x) = self.draw_group_menu(ui, group_idx) {
+                action = Some(x);
+            }
+        });
+        action
+    }
+
+    fn draw_compact_member(&self, ui: &mut egui::Ui, resp: egui::Response, group_idx: usize, member: usize) -> Option<GroupAction> {
+        let group = &self.images[group_idx];
+        let image = &group[member];
+        let place = Place{group: group_idx + 1, member: member + 1, count: group.len()};
+
+        ui.add_space(Self::COMPACT_INDENT);
+        let mut action = self.draw_thumbnail(ui, image, &place, Some(Self::COMPACT_THUMB_SIZE));
+        if let Some(x) = self.draw_keeper_badge(ui, group_idx, image) {
+            action = Some(x);
+        }
+        match image.side {
+            Some(Side::Reference) => {
+                ui.label(egui::RichText::new(tr!("Reference")).small().color(ui.visuals().hyperlink_color));
+            },
+            Some(Side::Candidate) => {
+                ui.label(egui::RichText::new(tr!("Candidate")).small().color(ui.visuals().warn_fg_color));
+            },
+            None => (),
+        }
+        ui.monospace(self.relative_path(image).display().to_string());
+        ui.label(Self::size_label(image));
+        self.draw_gain(ui, image);
+        if let Some((width, height)) = image.dimm {
+            ui.label(format!("{width}×{height}"));
+        }
+        if let (Some(distance), false) = (self.distances.get(&image.path), image.side == Some(Side::Reference)) {
+            let label = match distance {
+                0 => egui::RichText::new(tr!("exact")).color(theme::success_color(ui.visuals())),
+                _ => egui::RichText::new(tr!("distance {distance}", distance = distance)).color(ui.visuals().warn_fg_color),
+            };
+            ui.label(label.small());
+        }
+
+        // The rest of the line acts like the thumbnail, but for the menu.
+        let resp = a11y::list_button(resp, &tr!("Actions for {file}", file = image.file_name()));
+        if let Some(click) = Click::of(&resp) {
+            action = Some(GroupAction::Click(click, image.path.clone()));
+        } else if resp.hovered() && action.is_none() {
+            action = Some(GroupAction::Focus(image.path.clone()));
+        }
+        resp.context_menu(|ui| {
+            if let Some(x) = Self::draw_file_menu(ui, image) {
+                action = Some(x);
+            }
+            if let Some(selected) = self.selected.as_ref().filter(|x| **x != image.path) {
+                let resp = ui.add_enabled(self.compare_configured(), egui::Button::new(tr!("Compare externally with selected")))
+                    .on_hover_text(tr!("Open this and {file} in the external compare tool", file = selected.file_name().unwrap_or_default().to_string_lossy()))
+                    .on_disabled_hover_text(tr!("Set an external compare tool under Advanced in the search options"));
+                if resp.clicked() {
+                    ui.close_menu();
+                    action = Some(GroupAction::CompareExternally(selected.clone(), image.path.clone()));
+                }
+            }
+            ui.separator();
+            if let Some(x) = self.draw_group_menu(ui, group_idx) {
+                action = Some(x);
+            }
+        });
+        action
+    }
+
+    // draw_buttons(), as menu items.
+    fn draw_file_menu(ui: &mut egui::Ui, image: &Image) -> Option<GroupAction> {
+        let mut action = None;
+        if ui.button(tr!("Open")).clicked() {
+            ui.close_menu();
+            action = Some(GroupAction::Open(image.path.clone()));
+        }
+        if ui.button(tr!("Show in file manager")).clicked() {
+            ui.close_menu();
+            action = Some(GroupAction::Reveal(image.path.clone()));
+        }
+        if ui.button(tr!("Copy path")).clicked() {
+            ui.close_menu();
+            ui.output_mut(|out| out.copied_text = os_path::clipboard_text(&image.path));
+        }
+        if let Some(hash) = &image.hash {
+            if ui.button(tr!("Copy hash")).clicked() {
+                ui.close_menu();
+                ui.output_mut(|out| out.copied_text = hash.to_base64());
+            }
+        }
+        if quick_look::SUPPORTED && ui.button(tr!("Quick Look")).clicked() {
+            ui.close_menu();
+            action = Some(GroupAction::QuickLook(image.path.clone()));
+        }
+        if ui.button(tr!("Not a duplicate"))
+            .on_hover_text(tr!("Take this file out of its group, in this and future searches"))
+            .clicked() {
+            ui.close_menu();
+            action = Some(GroupAction::Exclude(image.path.clone()));
+        }
+        action
+    }
+
+    // The group controls, as menu items.
+    fn draw_group_menu(&self, ui: &mut egui::Ui, group_idx: usize) -> Option<GroupAction> {
+        let mut action = None;
+        let current = self.reviews.mark(&self.images[group_idx]);
+        for mark in Mark::ALL {
+            if ui.radio(current == mark, format!("{} {}", mark.symbol(), mark.label())).clicked() {
+                action = Some(GroupAction::Mark(mark));
+            }
+        }
+        ui.separator();
+        if self.bursts[group_idx] {
+            let text = if self.is_collapsed(group_idx) { tr!("Expand burst") } else { tr!("Collapse burst") };
+            if ui.button(text).clicked() {
+                action = Some(GroupAction::ToggleBurst);
+            }
+        }
+        if !self.opts.hook_command.trim().is_empty() && ui.button(tr!("Run hook")).clicked() {
+            action = Some(GroupAction::RunHook);
+        }
+        if ui.button(tr!("Re-scan this folder with different settings…")).clicked() {
+            action = Some(GroupAction::Refine);
+        }
+        if let [a, b] = &self.images[group_idx][..] {
+            if ui.add_enabled(self.compare_configured(), egui::Button::new(tr!("Compare externally")))
+                .on_disabled_hover_text(tr!("Set an external compare tool under Advanced in the search options"))
+                .clicked() {
+                action = Some(GroupAction::CompareExternally(a.path.clone(), b.path.clone()));
+            }
+        }
+        if ui.button(tr!("Ignore group")).on_hover_text(tr!("Don't show this group again in future searches")).clicked() {
+            action = Some(GroupAction::Ignore);
+        }
+        match self.junk_groups[group_idx] {
+            None => if ui.add_enabled(self.images[group_idx][0].hash.is_some(), egui::Button::new(tr!("Mark as system images everywhere")))
+                .on_hover_text(tr!("Hide groups of this image in every search, like app icons and stock wallpapers"))
+                .clicked() {
+                action = Some(GroupAction::MarkJunk);
+            },
+            Some(JunkSource::User) => if ui.button(tr!("Not system images")).clicked() {
+                action = Some(GroupAction::UnmarkJunk);
+            },
+            Some(JunkSource::Builtin) => {
+                ui.add_enabled(false, egui::Button::new(tr!("Not system images")))
+                    .on_disabled_hover_text(tr!("On the built-in list; turn off Hide System Images in the search options instead"));
+            },
+        }
+        ui.separator();
+        if ui.button(tr!("Copy group debug info"))
+            .on_hover_text(tr!("Paths, sizes, dimensions and hashes, for a bug report"))
+            .clicked() {
+            ui.output_mut(|out| out.copied_text = self.debug_info(group_idx));
+            ui.close_menu();
+        }
+        if action.is_some() {
+            ui.close_menu();
+        }
+        action
+    }
+
+    // The group as a block to paste into an issue: what was searched with,
+    // and each member as it was hashed.
+    fn debug_info(&self, group_idx: usize) -> String {
+        let mut info = format!(
+            "Deckard {} on {}, group {} ({}): {:?} hashes, {HASH_SIZE}×{HASH_SIZE}, ",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            group_idx + 1,
+            group_id(&self.images[group_idx]),
+            self.index.alg,
+        );
+        info += &match self.opts.max_distance {
+            0 => "exact matches".to_string(),
+            distance => format!("{:?} grouping within {distance} bits", self.opts.grouping),
+        };
+        if self.opts.trim_borders {
+            info += ", borders trimmed";
+        }
+        if self.opts.center_crop < 100 {
+            info += &format!(", central {}% hashed", self.opts.center_crop);
+        }
+        info.push('\n');
+        for image in &self.images[group_idx] {
+            let dimm = image.dimm.map_or("unknown size".to_string(), |(width, height)| format!("{width}×{height}"));
+            let hash = image.hash.as_ref().map_or("unknown".to_string(), |x| x.to_base64());
+            info += &format!("{}\n    {} bytes, {dimm}, hash {hash}", image.path.display(), image.file_size);
+            if let Some(distance) = self.distances.get(&image.path) {
+                info += &format!(" (distance {distance})");
+            }
+            info.push('\n');
+        }
+        info
+    }
+
+    fn handle_group_action(&mut self, group_idx: usize, action: GroupAction) -> Result<()> {
+        match action {
+            GroupAction::Ignore => self.ignore_group(group_idx)?,
+            GroupAction::MarkJunk => self.mark_junk(group_idx)?,
+            GroupAction::UnmarkJunk => self.unmark_junk(group_idx)?,
+            GroupAction::Exclude(path) => self.exclude_file(group_idx, path)?,
+            GroupAction::ToggleBurst => self.toggle_burst(group_idx),
+            GroupAction::Focus(path) => self.focused = Some(path),
+            GroupAction::Click(click, path) => {
+                self.focused = Some(path.clone());
+                self.live_new.remove(&path);
+                match click {
+                    // Held back in case it's the first half of a double-click
+                    // that does something else; see poll_click().
+                    Click::Single if Click::Double.action() != ClickAction::Nothing => {
+                        self.pending_click = Some((path, Instant::now()));
+                    },
+                    Click::Double => {
+                        self.pending_click = None;
+                        self.run_click_action(click.action(), path)?;
+                    },
+                    _ => self.run_click_action(click.action(), path)?,
+                }
+            },
+            GroupAction::Open(path) => self.run_click_action(ClickAction::Open, path)?,
+            GroupAction::Reveal(path) => self.run_click_action(ClickAction::Reveal, path)?,
+            GroupAction::QuickLook(path) => {
+                self.focused = Some(path);
+                self.quick_look_focused()?;
+            },
+            GroupAction::RunHook => self.run_hook(vec![group_idx])?,
+            GroupAction::Refine => self.refine = self.refine_folder(&self.images[group_idx]),
+            GroupAction::CompareExternally(a, b) => {
+                compare_tool::launch(&self.opts.compare_command, &a, &b)
+                    .map_err(|e| Error::new("Error opening compare tool".to_string(), e))?;
+            },
+            GroupAction::Mark(mark) => self.set_mark(group_idx, mark),
+            GroupAction::Note(note) => {
+                self.reviews.update(&self.images[group_idx], |x| x.note = note);
+                self.session_dirty = true;
+            },
+            GroupAction::ShowMore => self.show_more(group_idx),
+            GroupAction::CollapseLarge => self.collapse_large(group_idx),
+            GroupAction::KeepFirst => self.keep_first_of_group(group_idx)?,
+            GroupAction::PinKeeper(path) => {
+                let group = &self.images[group_idx];
+                if let Some(member) = group.iter().position(|x| x.path == path) {
+                    self.reviews.toggle_pin(group, member);
+                    self.refresh_keepers();
+                    self.session_dirty = true;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // Refilters, since the group may no longer belong among those shown.
+    fn set_mark(&mut self, group_idx: usize, mark: Mark) {
+        self.reviews.update(&self.images[group_idx], |x| x.mark = mark);
+        self.session_dirty = true;
+        self.apply_filter();
+    }
+
+    // Cycles the mark of the group with the selected image, or failing that,
+    // the one last under the pointer.
+    fn cycle_mark(&mut self) {
+        let Some(path) = self.selected.as_ref().or(self.focused.as_ref()) else {
+            return;
+        };
+        let Some(idx) = self.images.iter().position(|x| x.iter().any(|x| x.path == *path)) else {
+            return;
+        };
+        let mark = self.reviews.mark(&self.images[idx]).next();
+        self.set_mark(idx, mark);
+    }
+
+    // The group's mark, as a row of toggles, and its note.
+    fn draw_review(&self, ui: &mut egui::Ui, group_idx: usize) -> Option<GroupAction> {
+        let mut action = None;
+        let group = &self.images[group_idx];
+        let review = self.reviews.get(group);
+        let current = review.map_or(Mark::Unreviewed, |x| x.mark);
+        let number = group_idx + 1;
+        for mark in Mark::ALL {
+            let resp = ui.selectable_label(current == mark, mark.symbol())
+                .on_hover_text(mark.label());
+            let name = tr!("Mark group {number} as {mark}", number = number, mark = mark.label());
+            if a11y::list_button(resp, &name).clicked() && current != mark {
+                action = Some(GroupAction::Mark(mark));
+            }
+        }
+        let mut note = review.map(|x| x.note.clone()).unwrap_or_default();
+        let resp = ui.add(egui::TextEdit::singleline(&mut note)
+            .hint_text(tr!("Note"))
+            .desired_width(Self::NOTE_WIDTH));
+        a11y::name(&resp, egui::WidgetType::TextEdit, &tr!("Note on group {number}", number = number));
+        a11y::keep_focus_visible(&resp);
+        if resp.changed() {
+            action = Some(GroupAction::Note(note));
+        }
+        action
+    }
+
+    // Runs a single click once the double-click window has passed without a
+    // second one.
+    fn poll_click(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some((_, at)) = &self.pending_click else {
+            return Ok(());
+        };
+        match clicks::DOUBLE_CLICK_DELAY.checked_sub(at.elapsed()) {
+            Some(left) => {
+                ctx.request_repaint_after(left);
+                Ok(())
+            },
+            None => {
+                let (path, _) = self.pending_click.take().unwrap();
+                self.run_click_action(Click::Single.action(), path)
+            },
+        }
+    }
+
+    fn run_click_action(&mut self, action: ClickAction, path: PathBuf) -> Result<()> {
+        let shown = |e: opener::OpenError| Error::new("Error showing file".to_string(), e.to_string());
+        match action {
+            ClickAction::Nothing => (),
+            ClickAction::Select => {
+                // Clicking the selected image again deselects it.
+                self.selected = (self.selected.as_ref() != Some(&path)).then_some(path);
+            },
+            ClickAction::Open | ClickAction::Reveal if !self.confirm_unchanged(&path)? => (),
+            ClickAction::Open if !self.confirm_contents(&path) => (),
+            ClickAction::Open => opener::open(long_path(&path)).map_err(shown)?,
+            ClickAction::Reveal => opener::reveal(long_path(&path)).map_err(shown)?,
+            ClickAction::Preview => {
+                self.preview = self.images.iter().flatten().find(|x| x.path == path).cloned();
+            },
+        }
+        Ok(())
+    }
+
+    // Before acting on a single file, checks it's still as it was found. A
+    // missing one is dropped from the results, and the error says so; acting
+    // on a changed one needs the user's say-so. False to leave it alone.
+    fn confirm_unchanged(&mut self, path: &Path) -> Result<bool> {
+        let Some(image) = self.images.iter().flatten().find(|x| x.path == path) else {
+            return Ok(true);
+        };
+        match image.staleness() {
+            Staleness::Current => Ok(true),
+            Staleness::Missing => {
+                self.remove_images(&HashSet::from([path.to_path_buf()]));
+                Err(Error::new(
+                    "File no longer exists".to_string(),
+                    format!("{} no longer exists, so it's been removed from the results.", path.display()),
+                ))
+            },
+            Staleness::Changed => {
+                let answer = rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_title(tr!("File changed"))
+                    .set_description(tr!("{file} changed since the scan. Act on it anyway?", file = path.display()))
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+                Ok(answer == rfd::MessageDialogResult::Yes)
+            },
+        }
+    }
+
+    // Before opening a file, checks its contents are what its extension says,
+    // since the OS may launch it by either. Showing it in its folder launches
+    // nothing, so isn't checked. False to leave it alone; a file that can't be
+    // read is left for opening to complain about.
+    fn confirm_contents(&self, path: &Path) -> bool {
+        !self.opts.check_opens || sniff::confirm_contents(path)
+    }
+
+    fn draw_preview_window(&mut self, ctx: &egui::Context) {
+        // Full-size images are big; only the one on screen is kept.
+        let uri = self.preview.as_ref().map(|x| os_path::image_uri(&x.path));
+        if self.preview_uri != uri {
+            if let Some(old) = std::mem::replace(&mut self.preview_uri, uri) {
+                ctx.forget_image(&old);
+            }
+        }
+        let Some(image) = &self.preview else {
+            return;
+        };
+
+        let mut open = !ctx.input(|i| i.key_pressed(egui::Key::Escape));
+        egui::Window::new(image.file_name())
+            .id(egui::Id::new("image_preview"))
+            .open(&mut open)
+            .default_size(egui::vec2(Self::PREVIEW_SIZE * 1.5, Self::PREVIEW_SIZE * 1.5))
+            .show(ctx, |ui| {
+                ui.monospace(image.path.display().to_string());
+                if let Some((width, height)) = image.dimm {
+                    ui.label(format!("{width}×{height}, {}", format_size(image.file_size)));
+                }
+                ui.centered_and_justified(|ui| {
+                    ui.add(Self::image_widget(ctx, image).max_size(ui.available_size()));
+                });
+            });
+
+        if !open {
+            self.preview = None;
+        }
+    }
+
+    fn quick_look_focused(&mut self) -> Result<()> {
+        let Some(path) = self.selected.as_ref().or(self.focused.as_ref()) else {
+            return Ok(());
+        };
+        self.quick_look.show(path).map_err(|e| Error::new(
+            "Error showing file".to_string(),
+            e.to_string(),
+        ))
+    }
+
+    // How many thumbnail and metadata pairs fit side by side in the list
+    // layout. Always at least one, however narrow the window.
+    fn pairs_per_line(width: f32) -> usize {
+        let pair = Self::MIN_CELL_SIZE + Self::MIN_METADATA_WIDTH + 2.0 * Self::H_SPACING;
+        (((width + Self::H_SPACING) / pair).floor() as usize).max(1)
+    }
+
+    fn cards_per_row(width: f32) -> usize {
+        let cell = Self::CARD_THUMB_SIZE + Self::H_SPACING;
+        (((width - 2.0 * Self::CARD_MARGIN) / cell).floor() as usize).max(1)
+    }
+
+    fn card_height(&self, group_idx: usize, per_row: usize) -> f32 {
+        let rows = self.visible_members(group_idx).len().div_ceil(per_row);
+        // A large group's summary is a second line of header.
+        let headers = if self.is_large(group_idx) { 2.0 } else { 1.0 };
+        headers * Self::CARD_HEADER_HEIGHT + rows as f32 * Self::CARD_CELL_HEIGHT + 2.0 * Self::CARD_MARGIN
+    }
+
+    // Returns the action taken on the group, if any.
+    fn draw_card(&self, ui: &mut egui::Ui, group_idx: usize) -> Option<GroupAction> {
+        let mut action = None;
+        let group = &self.images[group_idx];
+
+        egui::Frame::group(ui.style()).inner_margin(Self::CARD_MARGIN).show(ui, |ui| {
+            ui.set_min_size(ui.available_size());
+            ui.horizontal(|ui| {
+                // The rest of the group's controls are in its context menu, too.
+                let heading = egui::Label::new(egui::RichText::new(tr!("Group {number}", number = group_idx + 1)).strong())
+                    .sense(egui::Sense::click());
+                let heading = ui.add(heading);
+                a11y::heading(&heading);
+                heading.context_menu(|ui| {
+                    if let Some(x) = self.draw_group_menu(ui, group_idx) {
+                        action = Some(x);
+                    }
+                });
+                Self::draw_group_id(ui, group);
+                ui.label(self.group_size_label(group_idx));
+                if has_hard_links(group) {
+                    ui.label(egui::RichText::new(tr!("already hard-linked")).italics());
+                }
+                if group.iter().any(|x| x.possible_crop) {
+                    ui.label(egui::RichText::new(tr!("Possible crop")).strong().color(ui.visuals().warn_fg_color));
+                }
+                if let Some(badge) = &self.encoding_badges[group_idx] {
+                    Self::draw_encoding_badge(ui, badge);
+                }
+                if matches!(self.variants[group_idx], Variants::Largest(_)) {
+                    Self::draw_variants_badge(ui);
+                }
+                if self.bursts[group_idx]
+                    && Self::draw_burst_toggle(ui, group.len(), !self.is_collapsed(group_idx)) {
+                    action = Some(GroupAction::ToggleBurst);
+                }
+                if let Some(x) = self.draw_review(ui, group_idx) {
+                    action = Some(x);
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let number = group_idx + 1;
+                    let ignore = ui.small_button(tr!("Ignore group"))
+                        .on_hover_text(tr!("Don't show this group again in future searches"));
+                    if a11y::list_button(ignore, &tr!("Ignore group {number}", number = number)).clicked() {
+                        action = Some(GroupAction::Ignore);
+                    }
+                    if !self.opts.hook_command.trim().is_empty()
+                        && a11y::list_button(ui.small_button(tr!("Run hook")), &tr!("Run hook on group {number}", number = number))
+                            .clicked() {
+                        action = Some(GroupAction::RunHook);
+                    }
+                    if Self::draw_refine_button(ui, number) {
+                        action = Some(GroupAction::Refine);
+                    }
+                    if let Some(x) = self.draw_compare_button(ui, group_idx) {
+                        action = Some(x);
+                    }
+                });
+            });
+
+            if self.is_large(group_idx) {
+                ui.horizontal(|ui| {
+                    if let Some(x) = self.draw_large_summary(ui, group_idx) {
+                        action = Some(x);
+                    }
+                });
+            }
+
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing.x = Self::H_SPACING;
+                // Numbered by their index in the group, as the rows are, so
+                // the two views announce each member the same way.
+                let shown = self.visible_members(group_idx).len();
+                for (member, image) in group.iter().enumerate().take(shown) {
+                    let place = Place{group: group_idx + 1, member: member + 1, count: group.len()};
+                    ui.allocate_ui(egui::vec2(Self::CARD_THUMB_SIZE, Self::CARD_CELL_HEIGHT), |ui| {
+                        ui.set_width(Self::CARD_THUMB_SIZE);
+                        ui.vertical(|ui| {
+                            if let Some(thumb_action) = self.draw_thumbnail(ui, image, &place, Some(Self::CARD_THUMB_SIZE)) {
+                                action = Some(thumb_action);
+                            }
+                            if let Some(x) = self.draw_keeper_badge(ui, group_idx, image) {
+                                action = Some(x);
+                            }
+                            self.draw_metadata(ui, image);
+                            if let Some(x) = Self::draw_buttons(ui, image) {
+                                action = Some(x);
+                            }
+                        });
+                    });
+                }
+            });
+        });
+
+        action
+    }
+
+    // Groups have variable heights, so show_rows() doesn't apply. Heights are
+    // estimated from member counts, and only cards intersecting the viewport
+    // are actually drawn.
+    fn draw_output_cards(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        let mut action = None;
+
+        let mut scroll = egui::ScrollArea::vertical().drag_to_scroll(false);
+        if self.first_update {
+            scroll = scroll.vertical_scroll_offset(0.0);
+            self.first_update = false;
+        }
+
+        let per_row = Self::cards_per_row(ui.available_width());
+        let mut tops = Vec::with_capacity(self.shown_groups.len());
+        let mut total_height = 0.0;
+        for idx in &self.shown_groups {
+            tops.push(total_height);
+            total_height += self.card_height(*idx, per_row) + Self::CARD_GAP;
+        }
+
+        scroll.show_viewport(ui, |ui, viewport| {
+            ui.set_height(total_height);
+            let origin = ui.max_rect().min;
+            let width = ui.available_width();
+
+            let first = tops.partition_point(|top| *top <= viewport.min.y).saturating_sub(1);
+            for (idx, top) in tops.iter().enumerate().skip(first) {
+                if *top > viewport.max.y {
+                    break;
+                }
+
+                let idx = self.shown_groups[idx];
+                let height = self.card_height(idx, per_row);
+                let rect = egui::Rect::from_min_size(
+                    origin + egui::vec2(0.0, *top),
+                    egui::vec2(width, height),
+                );
+                ui.allocate_ui_at_rect(rect, |ui| {
+                    ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+                    if let Some(x) = self.draw_card(ui, idx) {
+                        action = Some((idx, x));
+                    }
+                });
+            }
+        });
+
+        if let Some((idx, action)) = action {
+            self.handle_group_action(idx, action)?;
+        }
+        Ok(())
+    }
+
+    fn draw_errors(&mut self, ctx: &egui::Context) {
+        if self.errors.is_empty() || !self.show_errors.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Errors"));
+        let vid = errors_viewport();
+        let show_errors = self.show_errors.clone();
+        let errors = self.errors.clone();
+        let retry_requested = self.retry_requested.clone();
+        let filter = self.error_filter.clone();
+        let roots = [self.opts.root.clone(), self.opts.reference.clone()];
+        let retrying = self.retry.is_some();
+        let retryable = errors.iter().filter(|x| x.retryable()).count();
+        // Registering the window every frame doesn't repaint it; it only
+        // repaints for its own input, or when this asks.
+        ctx.show_viewport_deferred(vid, vb, move |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    show_errors.store(false, Ordering::Relaxed);
+                    return;
+                }
+
+                let mut filter = filter.lock().unwrap();
+                ui.heading(egui::RichText::new(tr!("Errors")).color(ui.visuals().error_fg_color));
+                ui.horizontal(|ui| {
+                    if retryable > 0 || retrying {
+                        let text = tr!("Retry failed files ({count})", count = retryable);
+                        if ui.add_enabled(!retrying, egui::Button::new(text)).clicked() {
+                            *retry_requested.lock().unwrap() = Some(RetryRequest::All);
+                        }
+                        if retrying {
+                            ui.spinner();
+                        }
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    for kind in ErrorKind::ALL {
+                        let count = errors.iter().filter(|x| x.kind == kind).count();
+                        let mut shown = filter.kinds.contains(&kind);
+                        let label = format!("{} ({count})", kind.label());
+                        if ui.checkbox(&mut shown, label).changed() {
+                            if shown {
+                                filter.kinds.insert(kind);
+                            } else {
+                                filter.kinds.remove(&kind);
+                            }
+                        }
+                    }
+                });
+
+                let mut clear_action = false;
+                if let Some((action, _)) = &filter.action {
+                    ui.horizontal(|ui| {
+                        ui.label(tr!("Only errors from \"{action}\"", action = action));
+                        clear_action = ui.small_button("✕").on_hover_text(tr!("Show all")).clicked();
+                    });
+                }
+                if clear_action {
+                    filter.action = None;
+                }
+
+                let visible: Vec<&SearchError> = errors.iter().filter(|x| filter.matches(x)).collect();
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut filter.text).hint_text(tr!("Filter…")));
+                    if ui.button(tr!("Copy all visible")).clicked() {
+                        ui.output_mut(|out| out.copied_text = errors_text(&visible));
+                    }
+                    ui.label(tr!("Showing {shown} of {total}", shown = visible.len(), total = errors.len()));
+                });
+                drop(filter);
+                ui.separator();
+
+                egui::ScrollArea::vertical().drag_to_scroll(false).show(ui, |ui| {
+                    Self::draw_error_table(ui, &visible, &roots, retrying, &retry_requested);
+                });
+            });
+        });
+    }
+
+    fn draw_error_table(
+        ui: &mut egui::Ui,
+        errors: &[&SearchError],
+        roots: &[PathBuf],
+        retrying: bool,
+        retry_requested: &Mutex<Option<RetryRequest>>,
+    ) {
+        const KIND_WIDTH: f32 = 70.0;
+        const PATH_WIDTH: f32 = 250.0;
+        const BUTTONS_WIDTH: f32 = 180.0;
+        const MIN_MESSAGE_WIDTH: f32 = 200.0;
+
+        // Long paths and messages wrap within their columns, rather than
+        // making the whole table scroll sideways.
+        let message_width = (ui.available_width() - KIND_WIDTH - PATH_WIDTH - BUTTONS_WIDTH
+            - 3.0 * ui.spacing().item_spacing.x).max(MIN_MESSAGE_WIDTH);
+        let wrapped = |ui: &mut egui::Ui, text: egui::RichText, width: f32| {
+            ui.allocate_ui(egui::vec2(width, 0.0), |ui| {
+                ui.set_width(width);
+                ui.add(egui::Label::new(text).wrap(true));
+            });
+        };
+
+        egui::Grid::new("errors").striped(true).num_columns(4).show(ui, |ui| {
+            ui.strong(tr!("Category"));
+            ui.strong(tr!("Path"));
+            ui.strong(tr!("Message"));
+            ui.label("");
+            ui.end_row();
+
+            for err in errors {
+                wrapped(ui, egui::RichText::new(err.kind.label()), KIND_WIDTH);
+
+                let path = err.path.as_ref().map(|path| {
+                    roots.iter()
+                        .filter(|x| !x.as_os_str().is_empty())
+                        .find_map(|x| path.strip_prefix(x).ok())
+                        .unwrap_or(path)
+                });
+                let shown = path.map(|x| x.display().to_string()).unwrap_or_default();
+                wrapped(ui, egui::RichText::new(shown).monospace(), PATH_WIDTH);
+                wrapped(ui, egui::RichText::new(&err.msg), message_width);
+
+                ui.horizontal(|ui| {
+                    let Some(path) = &err.path else {
+                        return;
+                    };
+                    let file = folder_name(path);
+                    if a11y::list_button(ui.small_button(tr!("Reveal")), &tr!("Show {file} in file manager", file = file)).clicked() {
+                        // There's nowhere to show an error from here, and the
+                        // file is likely missing anyway, which the row says.
+                        let _ = opener::reveal(long_path(path));
+                    }
+                    if a11y::list_button(ui.small_button(tr!("Copy path")), &tr!("Copy path of {file}", file = file)).clicked() {
+                        ui.output_mut(|out| out.copied_text = os_path::clipboard_text(path));
+                    }
+                    if err.retryable() {
+                        let retry = ui.add_enabled(!retrying, egui::Button::new(tr!("Retry")).small());
+                        if a11y::list_button(retry, &tr!("Retry {file}", file = file)).clicked() {
+                            *retry_requested.lock().unwrap() = Some(RetryRequest::One(path.clone()));
+                        }
+                    }
+                });
+                ui.end_row();
+            }
+        });
+    }
+}
+
+impl Phase for OutputPhase {
+    fn render(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
+        if let Some(full) = FullResults::draw_banner(&mut self.refining, ui, &self.opts) {
+            return Ok(Some(full));
+        }
+
+        let resp = ui.horizontal(|ui| {
+            if ui.button(tr!("<- New Search")).clicked() {
+                return Ok(Some(self.new_search()));
+            }
+
+            if self.opts.compare {
+                ui.strong(tr!("Candidates in"));
+                ui.monospace(self.opts.root.display().to_string());
+                ui.strong(tr!("found in"));
+                ui.monospace(self.opts.reference.display().to_string());
+            } else {
+                ui.strong(tr!("Results for"));
+                ui.monospace(self.opts.root.display().to_string());
+            }
+            // Chained groups can look quite different from strict ones, so
+            // it's worth knowing which these are.
+            let distance = self.opts.max_distance;
+            match (distance, self.opts.grouping) {
+                (0, _) => ui.weak(tr!("(exact matches)")),
+                (_, Grouping::Strict) => ui.weak(tr!("(strict groups within {distance} bits)", distance = distance))
+                    .on_hover_text(tr!("Every image in a group matches every other")),
+                (_, Grouping::Chain) => ui.weak(tr!("(chained groups within {distance} bits)", distance = distance))
+                    .on_hover_text(tr!("Images can be grouped through others they both match, \
+                        even if they don't match each other")),
+            };
+            // Rounded down, so it never reads as complete when it isn't.
+            if let Some((stats, coverage)) = self.stats.as_ref().and_then(|x| Some((x, x.coverage()?))) {
+                let label = tr!("partial scan — stopped early at {percent}%", percent = coverage.floor());
+                ui.label(egui::RichText::new(label).strong().color(ui.visuals().warn_fg_color))
+                    .on_hover_text(tr!(
+                        "{hashed} of the {total} images found were searched before the scan was stopped; \
+                        duplicates of the rest weren't looked for. The search form offers to resume it.",
+                        hashed = stats.files_hashed,
+                        total = stats.files_hashed + stats.not_searched.unwrap_or(0),
+                    ));
+            }
+            if self.opts.show_thumb_usage {
+                let (used, budget) = self.thumbnails.usage();
+                ui.label(egui::RichText::new(tr!(
+                    "thumb cache: {used} / {budget}",
+                    used = format_size(used),
+                    budget = format_size(budget),
+                )).small().weak());
+            }
+
+            let mut link_clicked = false;
+            let mut save_clicked = false;
+            let mut trash_candidates_clicked = false;
+            let mut keep_first_clicked = false;
+            let mut keep_largest_clicked = false;
+            let mut apply_keepers_clicked = false;
+            let mut rename_clicked = false;
+            let mut move_clicked = false;
+            let mut strategy = self.opts.keeper_strategy;
+            let mut undo_clicked = false;
+            let mut report_clicked = false;
+            let mut tag_clicked = None;
+            let mut hook_clicked = false;
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                clicks::settings_button(ui);
+
+                let undo_hint = match self.undo.last() {
+                    Some(entry) => format!("Undo \"{}\"", entry.description),
+                    None => tr!("Nothing to undo").to_string(),
+                };
+                undo_clicked = ui.add_enabled(!self.undo.is_empty(), egui::Button::new(tr!("Undo")))
+                    .on_hover_text(&undo_hint)
+                    .on_disabled_hover_text(&undo_hint)
+                    .clicked();
+
+                ui.checkbox(&mut self.dry_run, tr!("Dry run"))
+                    .on_hover_text(tr!("Show what bulk actions would do, without doing it"));
+                ui.checkbox(&mut self.opts.sidecars, tr!("Include sidecars"))
+                    .on_hover_text(tr!("Trash .xmp and other sidecar files along with their images"));
+
+                if self.bursts.contains(&true) {
+                    keep_first_clicked = ui.button(tr!("Keep first of each burst"))
+                        .on_hover_text(tr!("Move every image but the earliest in each burst to the trash"))
+                        .clicked();
+                }
+                if self.variants.iter().any(|x| *x != Variants::No) {
+                    keep_largest_clicked = ui.button(tr!("Resolve resolution variants (keep largest)"))
+                        .on_hover_text(tr!("Move every image but the largest in each group of resolution variants to the trash"))
+                        .clicked();
+                }
+
+                rename_clicked = ui.add_enabled(
+                    self.rename.is_none() && (self.keeper_preview.iter().any(Option::is_some) || self.selected.is_some()),
+                    egui::Button::new(tr!("Rename…")),
+                ).on_hover_text(tr!("Rename the image marked Keep in each group, or the selected one, by a template")).clicked();
+                move_clicked = ui.add_enabled(
+                    self.moving.is_none() && self.keeper_preview.iter().any(Option::is_some),
+                    egui::Button::new(tr!("Move…")),
+                ).on_hover_text(tr!("Move every image but the one marked Keep in each group into a folder")).clicked();
+                apply_keepers_clicked = ui.add_enabled(
+                    self.keeper_preview.iter().any(Option::is_some),
+                    egui::Button::new(tr!("Apply")),
+                ).on_hover_text(tr!("Move every image but the one marked Keep in each group to the trash")).clicked();
+                egui::ComboBox::from_id_source("keeper strategy")
+                    .selected_text(strategy.label())
+                    .show_ui(ui, |ui| {
+                        for x in KeeperStrategy::ALL {
+                            ui.selectable_value(&mut strategy, x, x.label());
+                        }
+                    }).response.on_hover_text(tr!("Which image in each group to mark to keep. Only a preview until applied; click Keep this on another image to pin it instead."));
+                ui.label(tr!("Keep:"));
+                ui.separator();
+
+                if self.opts.compare {
+                    trash_candidates_clicked = ui.add_enabled(
+                        !self.images.is_empty(),
+                        egui::Button::new(tr!("Trash matching candidates")),
+                    ).clicked();
+                }
+
+                if finder_tags::SUPPORTED {
+                    ui.add_enabled_ui(!self.images.is_empty(), |ui| {
+                        ui.menu_button(tr!("Tag duplicates…"), |ui| {
+                            for color in LabelColor::ALL {
+                                let text = egui::RichText::new(format!("● {}", color.label())).color(color.color(ui.visuals()));
+                                if ui.button(text).clicked() {
+                                    tag_clicked = Some(color);
+                                    ui.close_menu();
+                                }
+                            }
+                        }).response.on_hover_text(tr!("Tag every shown duplicate in Finder, to review them there"));
+                    });
+                }
+
+                if !self.opts.hook_command.trim().is_empty() {
+                    hook_clicked = ui.add_enabled(
+                        !self.shown_groups.is_empty() && self.hook.is_none(),
+                        egui::Button::new(tr!("Run hook on all groups")),
+                    ).on_hover_text(tr!("Run the post-search hook once for each shown group")).clicked();
+                }
+
+                if ui.add_enabled(!self.images.is_empty() && self.report.is_none(), egui::Button::new(tr!("Export HTML report…")))
+                    .on_hover_text(tr!("Write a single page with thumbnails of every group, to review anywhere"))
+                    .clicked() {
+                    report_clicked = true;
+                }
+
+                let save_hint = match self.opts.portable {
+                    true => tr!("Save these results in the root, with relative paths, to open wherever it's mounted"),
+                    false => tr!("Save these results so they can be restored after restarting"),
+                };
+                save_clicked = ui.button(tr!("Save session")).on_hover_text(save_hint).clicked();
+
+                link_clicked = ui.add_enabled(!self.images.is_empty(), egui::Button::new(tr!("Deduplicate via hard links")))
+                    .on_hover_text(tr!("Replace duplicates with hard links to a single copy"))
+                    .clicked();
+                ui.separator();
+
+                let layout = &mut self.opts.layout;
+                let density = self.opts.density;
+                ui.add_enabled_ui(self.view == View::Results, |ui| {
+                    ui.toggle_value(&mut self.opts.folder_panel, tr!("Folders"))
+                        .on_hover_text(tr!("Show the folders with duplicates in a tree, to pick one to show"));
+                    let mut compact = density == ResultsDensity::Compact;
+                    let resp = ui.add_enabled(*layout == ResultsLayout::List, egui::SelectableLabel::new(compact, tr!("Compact")))
+                        .on_hover_text(tr!("Tiny thumbnails and one line per file, for skimming many groups"));
+                    if resp.clicked() {
+                        compact = !compact;
+                        self.opts.density = if compact { ResultsDensity::Compact } else { ResultsDensity::Comfortable };
+                        self.scroll_to_row = Some(self.top_row);
+                    }
+                    ui.selectable_value(layout, ResultsLayout::Cards, tr!("Cards"));
+                    ui.selectable_value(layout, ResultsLayout::List, tr!("List"));
+                    ui.label(tr!("Layout:"));
+                });
+                ui.separator();
+
+                ui.toggle_value(&mut self.opts.history_panel, tr!("History"))
+                    .on_hover_text(tr!("Show what's been done to these results, including in earlier sittings"));
+                ui.selectable_value(&mut self.view, View::Folders, tr!("By folder"));
+                ui.selectable_value(&mut self.view, View::Results, tr!("Results"));
+            });
+
+            if link_clicked {
+                self.link_duplicates()?;
+            }
+            if save_clicked {
+                self.save_session_clicked()?;
+            }
+            if report_clicked {
+                self.export_report();
+            }
+            if let Some(color) = tag_clicked {
+                self.tag_duplicates(color)?;
+            }
+            if hook_clicked {
+                self.run_hook(self.shown_groups.clone())?;
+            }
+            if trash_candidates_clicked {
+                self.trash_matching_candidates()?;
+            }
+            if keep_first_clicked {
+                self.keep_first_of_bursts()?;
+            }
+            if keep_largest_clicked {
+                self.keep_largest_variants()?;
+            }
+            if strategy != self.opts.keeper_strategy {
+                self.opts.keeper_strategy = strategy;
+                self.refresh_keepers();
+                self.session_dirty = true;
+            }
+            if apply_keepers_clicked {
+                self.apply_keepers()?;
+            }
+            if rename_clicked {
+                self.open_rename();
+            }
+            if move_clicked {
+                self.open_move();
+            }
+
+            let shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+            let undo_pressed = !ctx.wants_keyboard_input()
+                && ctx.input_mut(|i| i.consume_shortcut(&shortcut));
+            if undo_clicked || undo_pressed {
+                self.undo_last()?;
+            }
+
+            let mark_pressed = !ctx.wants_keyboard_input()
+                && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::M));
+            if mark_pressed {
+                self.cycle_mark();
+            }
+
+            // Space also presses whatever button has keyboard focus.
+            let quick_look_pressed = quick_look::SUPPORTED
+                && !ctx.wants_keyboard_input()
+                && ctx.memory(|x| x.focused().is_none())
+                && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Space));
+            if quick_look_pressed {
+                self.quick_look_focused()?;
+            }
+
+            Ok(None)
+        });
+        
+        let next = resp.inner?;
+        if next.is_some() {
+            return Ok(next);
+        }
+
+        // A button to toggle showing the error window (if there were any errors).
+        // I can't figure out where to put the button, and I'm not sure its really
+        // necessary, but I'm leaving it here for the future.
+        /*
+        if !self.errors.is_empty() {
+            ui.add_space(4.0);
+            let old_show_errors = self.show_errors.load(Ordering::Relaxed);
+            let text = if old_show_errors { "Hide Errors" } else { "Show Errors" };
+            if ui.button(text).clicked() {
+                // TODO: change to fetch_not() once stable.
+                self.show_errors.store(!old_show_errors, Ordering::Relaxed);
+            }
+        }
+        */
+
+        self.poll_retry(ctx);
+        self.poll_report(ctx)?;
+        self.poll_hook(ctx);
+        self.poll_watch();
+        self.poll_live(ctx);
+        self.poll_click(ctx)?;
+        self.draw_live(ui);
+
+        ui.horizontal(|ui| {
+            let images: usize = self.images.iter().map(Vec::len).sum();
+            let wasted: usize = self.group_sizes.iter().filter_map(|x| x.wasted).sum();
+            let unknown = self.group_sizes.iter().filter(|x| x.wasted.is_none()).count();
+            ui.label(tr!(
+                "{groups} groups, {images} images, {size} reclaimable, {errors} errors",
+                groups = self.images.len(),
+                images = images,
+                size = format_size(wasted),
+                errors = self.errors.len(),
+            ));
+            if unknown > 0 {
+                ui.weak(tr!("({count} groups of unknown size not counted)", count = unknown))
+                    .on_hover_text(tr!("Files changed since they were found, so their sizes aren't known"));
+            }
+            if self.keeper_preview.iter().any(Option::is_some) {
+                ui.separator();
+                ui.label(egui::RichText::new(tr!(
+                    "would reclaim {size} with current choices",
+                    size = format_size(self.preview_reclaim),
+                )).color(theme::success_color(ui.visuals())));
+            }
+            if !self.images.is_empty() {
+                let same = self.same_folder.iter().filter(|x| **x).count();
+                ui.separator();
+                ui.label(tr!(
+                    "{same} same-folder, {cross} cross-folder",
+                    same = same,
+                    cross = self.images.len() - same,
+                ));
+            }
+        });
+        let mut watch_clicked = false;
+        ui.horizontal(|ui| {
+            let watching = self.watch.is_some();
+            // It goes by the search's index, which isn't there till it's done.
+            watch_clicked = ui.add_enabled(self.live.is_none(), egui::SelectableLabel::new(watching, tr!("Watch for changes")))
+                .on_hover_text(tr!("Keep watching the folders, and add new duplicates as they turn up"))
+                .on_disabled_hover_text(tr!("Available once the scan is done"))
+                .clicked();
+            if let Some(watch) = &self.watch {
+                let mut notify = watch.notify.load(Ordering::Relaxed);
+                if ui.checkbox(&mut notify, tr!("Notify")).changed() {
+                    watch.notify.store(notify, Ordering::Relaxed);
+                }
+            }
+        });
+        if watch_clicked {
+            self.toggle_watch(ctx);
+        }
+        if !self.ignored.is_empty() || !self.ignore_list.groups.is_empty() {
+            ui.horizontal(|ui| {
+                if !self.ignored.is_empty() {
+                    ui.label(tr!("{count} ignored groups hidden", count = self.ignored.len()));
+                }
+                if ui.small_button(tr!("Manage")).clicked() {
+                    self.show_ignored = true;
+                }
+            });
+        }
+        if !self.exclusions.entries.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label(tr!("{count} files excluded from their groups", count = self.exclusions.entries.len()));
+                if ui.small_button(tr!("Manage")).clicked() {
+                    self.show_exclusions = true;
+                }
+            });
+        }
+        let junk_groups = self.junk_groups.iter().filter(|x| x.is_some()).count();
+        if junk_groups > 0 {
+            ui.horizontal(|ui| {
+                let (text, button) = match self.filter.junk {
+                    false => (tr!("{count} system-image groups hidden", count = junk_groups), tr!("Show")),
+                    true => (tr!("{count} system-image groups shown", count = junk_groups), tr!("Hide")),
+                };
+                ui.label(text);
+                if ui.small_button(button)
+                    .on_hover_text(tr!("App icons, stock wallpapers and the like, from the built-in list or marked by you"))
+                    .clicked() {
+                    self.toggle_junk();
+                }
+            });
+        }
+        self.draw_broken(ui)?;
+        self.draw_stats(ui);
+        if self.view == View::Results && !self.images.is_empty() {
+            self.draw_content_filter(ui);
+        }
+
+        if let (View::Results, Some(folder)) = (self.view, &self.filter.folder) {
+            let crumbs = self.breadcrumbs(folder);
+            let mut go = None;
+            ui.horizontal(|ui| {
+                ui.label(tr!("Showing {count} groups with files in", count = self.shown_groups.len()));
+                // Each folder above the one shown widens the filter to it.
+                for (idx, crumb) in crumbs.iter().enumerate() {
+                    if idx > 0 {
+                        ui.label("›");
+                    }
+                    if idx + 1 == crumbs.len() {
+                        ui.strong(folder_name(crumb)).on_hover_text(crumb.display().to_string());
+                    } else if ui.link(folder_name(crumb)).on_hover_text(crumb.display().to_string()).clicked() {
+                        go = Some(Some(crumb.clone()));
+                    }
+                }
+                if ui.small_button("✕").on_hover_text(tr!("Show all")).clicked() {
+                    go = Some(None);
+                }
+            });
+            if let Some(folder) = go {
+                self.set_folder_filter(folder);
+            }
+        }
+
+        ui.separator();
+
+        if self.images.is_empty() && self.live.is_some() {
+            ui.label(tr!("No duplicates found yet"));
+        } else if self.images.is_empty() {
+            ui.label(tr!("Done on {root}, found no duplicates", root = self.opts.root.display()));
+            if let Some(hint) = self.draw_empty_state(ui) {
+                let refining = self.refining.take();
+                return Ok(Some(StartupPhase::new_with_opts(self.opts.take()).with_focus(hint).with_refining(refining).into_dyn()));
+            }
+        }
+
+        if self.opts.history_panel {
+            self.draw_history_panel(ui)?;
+        }
+        if self.view == View::Results && self.opts.folder_panel && !self.images.is_empty() {
+            self.draw_folder_panel(ui);
+        }
+        match (self.view, self.opts.layout) {
+            (View::Folders, _) => self.draw_folder_waste(ui),
+            (View::Results, ResultsLayout::List) => match self.opts.density {
+                ResultsDensity::Comfortable => self.draw_output_table(ui)?,
+                ResultsDensity::Compact => self.draw_compact_table(ui)?,
+            },
+            (View::Results, ResultsLayout::Cards) => self.draw_output_cards(ui)?,
+        }
+        self.draw_errors(ctx);
+        self.draw_link_outcomes(ctx);
+        self.draw_trashed(ctx)?;
+        self.draw_hook_outcomes(ctx);
+        self.draw_preview_window(ctx);
+        self.draw_ignored(ctx)?;
+        self.draw_exclusions(ctx)?;
+        self.draw_plan(ctx)?;
+        self.draw_rename(ctx)?;
+        self.draw_move(ctx)?;
+
+        if let Some(folder) = self.refine.take() {
+            return Ok(Some(self.refine(folder)));
+        }
+        Ok(None)
+    }
+
+    // So a search still filling the results in keeps being read (and
+    // finishes) with the tab in back.
+    fn poll(&mut self, ctx: &egui::Context) -> Result<Option<DynPhase>> {
+        self.poll_live(ctx);
+        Ok(None)
+    }
+
+    fn title(&self) -> String {
+        folder_name(&self.opts.root)
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.opts.save(storage);
+
+        // There's nowhere to report an error from here, and a manual save
+        // will show it anyway.
+        if self.session_dirty {
+            let _ = self.save_session();
+        }
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        let mut commands = vec![Command::new("new_search", tr!("New search"))];
+        if !self.undo.is_empty() {
+            commands.push(Command::new("undo", tr!("Undo")));
+        }
+        if !self.images.is_empty() {
+            commands.push(Command::new("link", tr!("Deduplicate via hard links")));
+        }
+        if self.opts.compare && !self.images.is_empty() {
+            commands.push(Command::new("trash_candidates", tr!("Trash matching candidates")));
+        }
+        if !self.broken.is_empty() {
+            commands.push(Command::new("trash_broken", tr!("Trash all broken images")));
+        }
+        if self.bursts.contains(&true) {
+            commands.push(Command::new("keep_first", tr!("Keep first of each burst")));
+        }
+        if self.variants.iter().any(|x| *x != Variants::No) {
+            commands.push(Command::new("keep_largest", tr!("Resolve resolution variants (keep largest)")));
+        }
+        if self.keeper_preview.iter().any(Option::is_some) {
+            commands.push(Command::new("apply_keepers", tr!("Apply keeper choices")));
+        }
+        if self.keeper_preview.iter().any(Option::is_some) || self.selected.is_some() {
+            commands.push(Command::new("rename", tr!("Rename images…")));
+        }
+        if self.keeper_preview.iter().any(Option::is_some) {
+            commands.push(Command::new("move", tr!("Move images to a folder…")));
+        }
+        commands.push(Command::new("save_session", tr!("Save session")));
+        if !self.images.is_empty() && self.report.is_none() {
+            commands.push(Command::new("export_report", tr!("Export HTML report…")));
+        }
+        if !self.opts.hook_command.trim().is_empty() && !self.shown_groups.is_empty() && self.hook.is_none() {
+            commands.push(Command::new("run_hook", tr!("Run hook on all groups")));
+        }
+        commands.push(if self.dry_run {
+            Command::new("dry_run", tr!("Turn off dry run"))
+        } else {
+            Command::new("dry_run", tr!("Turn on dry run"))
+        });
+        if self.watch.is_some() {
+            commands.push(Command::new("watch", tr!("Stop watching for changes")));
+        } else if self.live.is_none() {
+            commands.push(Command::new("watch", tr!("Watch for changes")));
+        }
+        if self.live.is_some() {
+            commands.push(Command::new("cancel_live", tr!("Cancel search")));
+        }
+        match self.view {
+            View::Results => commands.push(Command::new("view_folders", tr!("Show wasted space by folder"))),
+            View::Folders => commands.push(Command::new("view_results", tr!("Show results"))),
+        }
+        if self.view == View::Results {
+            match self.opts.layout {
+                ResultsLayout::List => commands.push(Command::new("layout_cards", tr!("Show results as cards"))),
+                ResultsLayout::Cards => commands.push(Command::new("layout_list", tr!("Show results as a list"))),
+            }
+            match self.opts.density {
+                ResultsDensity::Comfortable => commands.push(Command::new("density_compact", tr!("Show a compact list"))),
+                ResultsDensity::Compact => commands.push(Command::new("density_comfortable", tr!("Show a comfortable list"))),
+            }
+            commands.push(match self.opts.folder_panel {
+                true => Command::new("folder_panel", tr!("Hide folder tree")),
+                false => Command::new("folder_panel", tr!("Show folder tree")),
+            });
+        }
+        commands.push(match self.opts.history_panel {
+            true => Command::new("history_panel", tr!("Hide history")),
+            false => Command::new("history_panel", tr!("Show history")),
+        });
+        if !self.history.is_empty() {
+            commands.push(Command::new("export_history", tr!("Export history…")));
+        }
+        if !self.ignored.is_empty() || !self.ignore_list.groups.is_empty() {
+            commands.push(Command::new("manage_ignored", tr!("Manage ignored groups")));
+        }
+        if !self.exclusions.entries.is_empty() {
+            commands.push(Command::new("manage_exclusions", tr!("Manage excluded files")));
+        }
+        if self.junk_groups.iter().any(|x| x.is_some()) {
+            commands.push(match self.filter.junk {
+                true => Command::new("toggle_junk", tr!("Hide system images")),
+                false => Command::new("toggle_junk", tr!("Show system images")),
+            });
+        }
+        if quick_look::SUPPORTED && self.focused.is_some() {
+            commands.push(Command::new("quick_look", tr!("Quick Look last hovered image")));
+        }
+        commands
+    }
+
+    fn run_command(&mut self, ctx: &egui::Context, id: &str) -> Result<Option<DynPhase>> {
+        match id {
+            "new_search" => return Ok(Some(self.new_search())),
+            "undo" => self.undo_last()?,
+            "link" => self.link_duplicates()?,
+            "trash_candidates" => self.trash_matching_candidates()?,
+            "trash_broken" => self.trash_broken()?,
+            "keep_first" => self.keep_first_of_bursts()?,
+            "rename" => self.open_rename(),
+            "move" => self.open_move(),
+            "keep_largest" => self.keep_largest_variants()?,
+            "apply_keepers" => self.apply_keepers()?,
+            "save_session" => self.save_session_clicked()?,
+            "export_report" => self.export_report(),
+            "run_hook" => self.run_hook(self.shown_groups.clone())?,
+            "dry_run" => self.dry_run = !self.dry_run,
+            "watch" => self.toggle_watch(ctx),
+            "cancel_live" => if let Some(live) = self.live.take() {
+                live.cancel();
+            },
+            "view_folders" => self.view = View::Folders,
+            "view_results" => self.view = View::Results,
+            "layout_cards" => self.opts.layout = ResultsLayout::Cards,
+            "layout_list" => self.opts.layout = ResultsLayout::List,
+            "density_compact" | "density_comfortable" => {
+                self.opts.layout = ResultsLayout::List;
+                self.opts.density = match id {
+                    "density_compact" => ResultsDensity::Compact,
+                    _ => ResultsDensity::Comfortable,
+                };
+                self.scroll_to_row = Some(self.top_row);
+            },
+            "folder_panel" => self.opts.folder_panel = !self.opts.folder_panel,
+            "history_panel" => self.opts.history_panel = !self.opts.history_panel,
+            "export_history" => self.export_history()?,
+            "manage_ignored" => self.show_ignored = true,
+            "manage_exclusions" => self.show_exclusions = true,
+            "toggle_junk" => self.toggle_junk(),
+            "quick_look" => self.quick_look_focused()?,
+            _ => (),
+        }
+        Ok(None)
+    }
+}
+
+
+// The name of a stage from SearchStats::stage_shares(), for display.
+fn tr_stage(stage: &str) -> &'static str {
+    match stage {
+        "decode" => tr!("decode"),
+        "hash" => tr!("hash"),
+        _ => tr!("walk"),
+    }
+}
+
+// The options that could have ruled out duplicates in a search that found
+// none, with how many files or matches each did rule out. Those that ruled
+// out nothing aren't worth suggesting.
+fn empty_state_suggestions(stats: &SearchStats) -> Vec<(FocusHint, usize)> {
+    [
+        (FocusHint::Extensions, stats.skipped_by_ext.values().sum::<usize>() + stats.skipped_no_ext),
+        (FocusHint::Matching, stats.color_rejected),
+        (FocusHint::SizeLimits, stats.skipped_too_large + stats.skipped_over_budget),
+    ].into_iter()
+        .filter(|(_, count)| *count > 0)
+        .collect()
+}
+
+// Errors as tab-separated lines of category, full path (empty if there's no
+// one file involved) and message, for the clipboard.
+fn errors_text(errors: &[&SearchError]) -> String {
+    errors.iter()
+        .map(|x| {
+            let path = x.path.as_deref().map(os_path::clipboard_text).unwrap_or_default();
+            format!("{}\t{}\t{}", x.kind.label(), path, x.msg)
+        })
+        .join("\n")
+}
+
+// Indices of the last row of each group, for groups with the given numbers of
+// rows laid out one after another. Empty groups have no rows, and so no end.
+fn group_ends(sizes: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let mut total = 0;
+    sizes.into_iter()
+        .filter(|x| *x > 0)
+        .map(|x| {
+            total += x;
+            total - 1
+        })
+        .collect()
+}
+
+// Splits each group's rows into lines of at most per_line, given the index of
+// each group's last row (as from group_ends()). Every group starts a new line.
+fn chunk_groups(group_ends: &[usize], per_line: usize) -> Vec<Range<usize>> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for end in group_ends.iter().map(|x| x + 1) {
+        while start < end {
+            let line_end = (start + per_line).min(end);
+            lines.push(start..line_end);
+            start = line_end;
+        }
+    }
+    lines
+}
+
+// The compact list's lines: each group's header, then its rows, given the
+// index of each group's last row (as from group_ends()).
+fn compact_rows(group_ends: &[usize]) -> Vec<CompactRow> {
+    let mut rows = vec![];
+    let mut start = 0;
+    for (group, end) in group_ends.iter().enumerate() {
+        rows.push(CompactRow::Header(group));
+        rows.extend((start..=*end).map(CompactRow::Member));
+        start = end + 1;
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image_hasher::HashAlg;
+
+    #[test]
+    fn group_ends_are_cumulative() {
+        assert_eq!(group_ends([]), Vec::<usize>::new());
+        assert_eq!(group_ends([1]), vec![0]);
+        assert_eq!(group_ends([2, 3, 1]), vec![1, 4, 5]);
+        assert_eq!(group_ends([2, 0, 2]), vec![1, 3]);
+        assert_eq!(group_ends([0, 1]), vec![0]);
+    }
+
+    #[test]
+    fn only_options_that_ruled_something_out_are_suggested() {
+        let mut stats = SearchStats::default();
+        assert_eq!(empty_state_suggestions(&stats), vec![]);
+
+        stats.skipped_over_budget = 2;
+        assert_eq!(empty_state_suggestions(&stats), vec![(FocusHint::SizeLimits, 2)]);
+
+        stats.skipped_by_ext.insert("txt".to_string(), 3);
+        stats.skipped_no_ext = 1;
+        stats.skipped_too_large = 1;
+        assert_eq!(empty_state_suggestions(&stats), vec![(FocusHint::Extensions, 4), (FocusHint::SizeLimits, 3)]);
+
+        stats.color_rejected = 5;
+        assert_eq!(empty_state_suggestions(&stats), vec![
+            (FocusHint::Extensions, 4),
+            (FocusHint::Matching, 5),
+            (FocusHint::SizeLimits, 3),
+        ]);
+    }
+
+    #[test]
+    fn copied_errors_include_paths() {
+        let errors = [
+            SearchError::new(ErrorKind::Decode, Some(PathBuf::from("/photos/a b.jpg")), "Bad header".to_string()),
+            SearchError::new(ErrorKind::Io, None, "Disk full".to_string()),
+        ];
+        let refs: Vec<&SearchError> = errors.iter().collect();
+        assert_eq!(errors_text(&refs), "Decode\t/photos/a b.jpg\tBad header\nI/O\t\tDisk full");
+        assert_eq!(errors_text(&[]), "");
+    }
+
+    #[test]
+    fn compact_rows_head_each_group() {
+        use CompactRow::{Header, Member};
+        assert_eq!(compact_rows(&[]), vec![]);
+        assert_eq!(compact_rows(&group_ends([2, 1])), vec![Header(0), Member(0), Member(1), Header(1), Member(2)]);
+    }
+
+    #[test]
+    fn results_have_screen_reader_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let members = |names: &[&str]| -> Vec<Image> {
+            names.iter().map(|x| Image::new(dir.path().join(x), vec![0; 2000], Some((40, 30)))).collect()
+        };
+        let results = SearchResults::restored(vec![members(&["a.png", "b.png"]), members(&["c.png", "d.png", "e.png"])], HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.path().to_path_buf()), results);
+
+        for (layout, density) in [
+            (ResultsLayout::List, ResultsDensity::Comfortable),
+            (ResultsLayout::List, ResultsDensity::Compact),
+            (ResultsLayout::Cards, ResultsDensity::Comfortable),
+        ] {
+            phase.opts.layout = layout;
+            let ctx = egui::Context::default();
+            ctx.enable_accesskit();
+            let output = ctx.run(egui::RawInput::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    let drawn = match (layout, density) {
+                        (ResultsLayout::List, ResultsDensity::Comfortable) => phase.draw_output_table(ui),
+                        (ResultsLayout::List, ResultsDensity::Compact) => phase.draw_compact_table(ui),
+                        (ResultsLayout::Cards, _) => phase.draw_output_cards(ui),
+                    };
+                    assert!(drawn.is_ok());
+                });
+            });
+
+            let tree = output.platform_output.accesskit_update.unwrap();
+            let named = |role, name: &str| tree.nodes.iter().any(|(_, x)| x.role() == role && x.name() == Some(name));
+            let group = phase.images.iter().position(|x| x.len() == 3).unwrap() + 1;
+            let member = phase.images[group - 1].iter().position(|x| x.path.ends_with("d.png")).unwrap() + 1;
+            let thumbnail = format!("Image d.png, 40 by 30, 2 kB, duplicate {member} of 3 in group {group}");
+            assert!(named(egui::accesskit::Role::Button, &thumbnail), "{layout:?}: {thumbnail}");
+            assert!(named(egui::accesskit::Role::Heading, &format!("Group {group}")), "{layout:?}");
+            // The compact list keeps its buttons in a context menu.
+            if density == ResultsDensity::Compact {
+                assert!(named(egui::accesskit::Role::Button, "Actions for d.png"));
+                assert!(named(egui::accesskit::Role::Button, &format!("Actions for group {group}")));
+                continue;
+            }
+            assert!(named(egui::accesskit::Role::Button, "Open d.png"), "{layout:?}");
+            assert!(named(egui::accesskit::Role::Button, &format!("Ignore group {group}")), "{layout:?}");
+        }
+    }
+
+    #[test]
+    fn large_results_open_without_decoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let groups = (0..2500)
+            .map(|x| (0..2).map(|y| {
+                let mut image = Image::new_lazy(dir.path().join(format!("{x}_{y}.jpg")), 1 << 20, Some((4000, 3000)));
+                image.hash = Some(ImageHash::from_bytes(&[x as u8; 8]).unwrap());
+                image
+            }).collect())
+            .collect();
+        let results = SearchResults::restored(groups, HashAlg::Gradient);
+
+        let mut phase = OutputPhase::new(UserOpts::new(dir.path().to_path_buf()), results);
+
+        // Only what's on screen is asked for, and the frame doesn't wait for it.
+        let ctx = egui::Context::default();
+        let screen = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1280.0, 800.0));
+        let input = egui::RawInput{screen_rect: Some(screen), ..Default::default()};
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                assert!(phase.draw_output_table(ui).is_ok());
+            });
+        });
+        let requested = phase.thumbnails.requested();
+        assert!(requested > 0 && requested < 50, "{requested}");
+    }
+
+    #[test]
+    fn huge_groups_are_shown_a_page_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let group: Vec<Image> = (0..5000)
+            .map(|x| {
+                let mut image = Image::new_lazy(dir.path().join(format!("frames/{x:04}.jpg")), 1 << 20, Some((4000, 3000)));
+                // Every one different, as near matches would be.
+                image.hash = Some(ImageHash::from_bytes(&[(x % 256) as u8, (x / 256) as u8, 0, 0, 0, 0, 0, 0]).unwrap());
+                image
+            })
+            .collect();
+        let small = (0..2).map(|x| Image::new_lazy(dir.path().join(format!("{x}.jpg")), 1 << 20, None)).collect();
+        let results = SearchResults::restored(vec![group, small], HashAlg::Gradient);
+
+        let mut phase = OutputPhase::new(UserOpts::new(dir.path().to_path_buf()), results);
+
+        let huge = phase.images.iter().position(|x| x.len() == 5000).unwrap();
+        assert_eq!(phase.rows.len(), 1 + 2);
+        phase.show_more(huge);
+        phase.show_more(huge);
+        assert_eq!(phase.rows.len(), 2 * UserOpts::DEFAULT_LARGE_GROUP + 2);
+
+        for (layout, density) in [
+            (ResultsLayout::List, ResultsDensity::Comfortable),
+            (ResultsLayout::List, ResultsDensity::Compact),
+            (ResultsLayout::Cards, ResultsDensity::Comfortable),
+        ] {
+            let ctx = egui::Context::default();
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    let drawn = match (layout, density) {
+                        (ResultsLayout::List, ResultsDensity::Comfortable) => phase.draw_output_table(ui),
+                        (ResultsLayout::List, ResultsDensity::Compact) => phase.draw_compact_table(ui),
+                        (ResultsLayout::Cards, _) => phase.draw_output_cards(ui),
+                    };
+                    assert!(drawn.is_ok());
+                });
+            });
+        }
+        assert!(phase.thumbnails.requested() <= 2 * UserOpts::DEFAULT_LARGE_GROUP + 2);
+
+        phase.collapse_large(huge);
+        assert_eq!(phase.rows.len(), 1 + 2);
+    }
+
+    #[test]
+    fn excluding_files_rebuilds_their_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let names = ["a.jpg", "b.jpg", "export1.jpg", "export2.jpg"];
+        let group: Vec<Image> = names.iter()
+            .map(|x| {
+                std::fs::write(dir.join(x), x).unwrap();
+                let mut image = Image::new_lazy(dir.join(x), x.len(), Some((40, 30)));
+                image.hash = Some(ImageHash::from_bytes(&[7; 8]).unwrap());
+                image
+            })
+            .collect();
+        let other: Vec<Image> = ["c.jpg", "d.jpg"].iter().map(|x| Image::new_lazy(dir.join(x), 1, None)).collect();
+        let results = SearchResults::restored(vec![group, other], HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.clone()), results);
+        let members = |phase: &OutputPhase| -> Vec<Vec<String>> {
+            let mut groups: Vec<Vec<String>> = phase.images.iter()
+                .map(|x| x.iter().map(|x| x.path.file_name().unwrap().to_string_lossy().into_owned()).sorted().collect())
+                .collect();
+            groups.sort();
+            groups
+        };
+        let group_of = |phase: &OutputPhase, name: &str| phase.images.iter().position(|x| x.iter().any(|x| x.path.ends_with(name))).unwrap();
+
+        let export1 = group_of(&phase, "export1.jpg");
+        phase.exclude_file(export1, dir.join("export1.jpg")).unwrap();
+        assert_eq!(members(&phase), [vec!["a.jpg", "b.jpg", "export2.jpg"], vec!["c.jpg", "d.jpg"]]);
+        assert_eq!(phase.excluded.len(), 1);
+        // Keyed by the group as found, not as shown
+        let found = group_id(&phase.excluded[0]);
+        assert!(phase.exclusions.entries.iter().any(|x| x.group == found));
+
+        let export2 = group_of(&phase, "export2.jpg");
+        phase.exclude_file(export2, dir.join("export2.jpg")).unwrap();
+        assert_eq!(members(&phase), [vec!["a.jpg", "b.jpg"], vec!["c.jpg", "d.jpg"]]);
+        assert_eq!(phase.excluded.len(), 1);
+        assert_eq!(phase.excluded[0].len(), 4);
+        let keys: Vec<_> = phase.exclusions.entries.iter().filter(|x| x.path.starts_with(&dir)).map(|x| &x.group).collect();
+        assert_eq!(keys, [&found, &found]);
+
+        // Excluding one of the pair left collapses the group.
+        let a = group_of(&phase, "a.jpg");
+        phase.exclude_file(a, dir.join("a.jpg")).unwrap();
+        assert_eq!(members(&phase), [vec!["c.jpg", "d.jpg"]]);
+
+        let entry = |phase: &OutputPhase, name: &str| phase.exclusions.entries.iter().position(|x| x.path == dir.join(name)).unwrap();
+        phase.unexclude(entry(&phase, "a.jpg")).unwrap();
+        phase.unexclude(entry(&phase, "export1.jpg")).unwrap();
+        assert_eq!(members(&phase), [vec!["a.jpg", "b.jpg", "export1.jpg"], vec!["c.jpg", "d.jpg"]]);
+        assert_eq!(phase.excluded.len(), 1);
+
+        // Whole again, e.g. for handing back to the form
+        phase.restore_excluded();
+        assert_eq!(members(&phase), [vec!["a.jpg", "b.jpg", "export1.jpg", "export2.jpg"], vec!["c.jpg", "d.jpg"]]);
+        assert!(phase.excluded.is_empty());
+
+        // The next search's results come out as the list has it.
+        let wholes = vec![phase.images[group_of(&phase, "a.jpg")].clone()];
+        phase.excluded = wholes.clone();
+        phase.reapply_exclusions(wholes);
+        assert_eq!(members(&phase), [vec!["a.jpg", "b.jpg", "export1.jpg"], vec!["c.jpg", "d.jpg"]]);
+    }
+
+    #[test]
+    fn refined_results_come_back_as_they_were() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let images = |names: &[&str]| -> Vec<Image> {
+            names.iter().map(|x| Image::new_lazy(dir.join(x), 1, None)).collect()
+        };
+        let results = SearchResults::restored(vec![images(&["a/1.jpg", "a/2.jpg"]), images(&["a/3.jpg", "b/4.jpg"])], HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.clone()), results);
+        let paths = |phase: &OutputPhase| -> Vec<Vec<PathBuf>> {
+            phase.images.iter().map(|x| x.iter().map(|x| x.path.clone()).sorted().collect()).sorted().collect()
+        };
+        let group = |phase: &OutputPhase| phase.images.iter().find(|x| x.iter().any(|x| x.path.ends_with("1.jpg"))).unwrap().clone();
+        phase.reviews.update(&group(&phase), |x| x.mark = Mark::Kept);
+        phase.record(HistoryEntry::new("Trash 1 images".to_string(), vec![]));
+        let before = paths(&phase);
+
+        assert_eq!(phase.refine_folder(&group(&phase)), Some(dir.join("a")));
+        let full = phase.take_full(dir.join("a"));
+        assert!(phase.images.is_empty() && phase.history.is_empty());
+        assert_eq!(full.folder, dir.join("a"));
+
+        // Display preferences come from the refined search's options.
+        let mut current = UserOpts::new(dir.join("a"));
+        current.density = ResultsDensity::Compact;
+        let back = full.restore(&current);
+        assert_eq!(paths(&back), before);
+        assert_eq!(back.opts.root, dir);
+        assert_eq!(back.opts.density, ResultsDensity::Compact);
+        assert_eq!(back.reviews.mark(&group(&back)), Mark::Kept);
+        assert_eq!(back.history.len(), 1);
+    }
+
+    #[test]
+    fn refining_stays_within_the_searched_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let mut opts = UserOpts::new(dir.join("photos"));
+        opts.reference = dir.join("backup");
+        let mut phase = OutputPhase::new(opts, SearchResults::restored(vec![], HashAlg::Gradient));
+        let group = |names: &[&str]| -> Vec<Image> {
+            names.iter().map(|x| Image::new_lazy(dir.join(x), 1, None)).collect()
+        };
+
+        assert_eq!(phase.refine_folder(&group(&["photos/2021/a.jpg", "photos/2021/b.jpg"])), Some(dir.join("photos/2021")));
+        assert_eq!(phase.refine_folder(&group(&["photos/a.jpg", "photos/2021/b.jpg"])), Some(dir.join("photos")));
+        // Spanning both, it's where most of the group is.
+        phase.opts.compare = true;
+        let spanning = group(&["photos/2021/a.jpg", "backup/a.jpg", "backup/2021/a.jpg"]);
+        assert_eq!(phase.refine_folder(&spanning), Some(dir.join("backup")));
+    }
+
+    #[test]
+    fn debug_info_has_the_options_and_each_member() {
+        let image = |name: &str, hash: [u8; 8], dimm: Option<(u32, u32)>| {
+            let mut image = Image::new_lazy(PathBuf::from(format!("/photos/{name}")), 1000, dimm);
+            image.hash = Some(ImageHash::from_bytes(&hash).unwrap());
+            image
+        };
+        let near = [1, 0, 0, 0, 0, 0, 0, 0];
+        let group = vec![
+            image("near.jpg", near, None),
+            image("a.jpg", [0; 8], Some((40, 30))),
+            image("b.jpg", [0; 8], Some((40, 30))),
+        ];
+        let mut opts = UserOpts::new(PathBuf::from("/photos"));
+        opts.max_distance = 2;
+        opts.center_crop = 80;
+        let phase = OutputPhase::new(opts, SearchResults::restored(vec![group], HashAlg::Gradient));
+
+        let info = phase.debug_info(0);
+        let lines: Vec<&str> = info.lines().collect();
+        let id = group_id(&phase.images[0]);
+        assert_eq!(lines[0], format!(
+            "Deckard {} on {}, group 1 ({id}): Gradient hashes, {HASH_SIZE}×{HASH_SIZE}, \
+            Strict grouping within 2 bits, central 80% hashed",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+        ));
+        // Closest first, as shown.
+        let zero = ImageHash::<Box<[u8]>>::from_bytes(&[0; 8]).unwrap().to_base64();
+        let near = ImageHash::<Box<[u8]>>::from_bytes(&near).unwrap().to_base64();
+        assert_eq!(&lines[1..], [
+            "/photos/a.jpg".to_string(),
+            format!("    1000 bytes, 40×30, hash {zero} (distance 0)"),
+            "/photos/b.jpg".to_string(),
+            format!("    1000 bytes, 40×30, hash {zero} (distance 0)"),
+            "/photos/near.jpg".to_string(),
+            format!("    1000 bytes, unknown size, hash {near} (distance 1)"),
+        ]);
+    }
+
+    #[test]
+    fn moved_files_leave_the_results_until_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let dest = dir.join("moved");
+        std::fs::create_dir(&dest).unwrap();
+        std::fs::write(dest.join("b.jpg"), "there first").unwrap();
+        let group: Vec<Image> = ["a.jpg", "b.jpg", "c.jpg"].iter()
+            .map(|x| {
+                std::fs::write(dir.join(x), x).unwrap();
+                Image::new_lazy(dir.join(x), x.len(), None)
+            })
+            .collect();
+        let results = SearchResults::restored(vec![group], HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.clone()), results);
+
+        let files = [dir.join("b.jpg"), dir.join("c.jpg")];
+        let plan = ActionPlan::move_to(&files, &dest, None, CollisionPolicy::Skip, |x| x.exists());
+        assert_eq!(plan.steps[0].action, Action::Skip);
+        phase.execute_plan(plan).unwrap();
+        // The skipped file stays, in its group.
+        assert!(dest.join("c.jpg").exists() && dir.join("b.jpg").exists());
+        assert_eq!(phase.images.len(), 1);
+        assert_eq!(phase.images[0].len(), 2);
+
+        let plan = ActionPlan::move_to(&files[..1], &dest, None, CollisionPolicy::Rename, |x| x.exists());
+        phase.execute_plan(plan).unwrap();
+        assert!(dest.join("b (1).jpg").exists());
+        assert!(phase.images.is_empty());
+        assert_eq!(phase.history.last().unwrap().files, 1);
+
+        phase.undo_last().unwrap();
+        phase.undo_last().unwrap();
+        assert!(dir.join("b.jpg").exists() && dir.join("c.jpg").exists());
+        assert_eq!(std::fs::read(dest.join("b.jpg")).unwrap(), b"there first");
+        assert_eq!(phase.images.len(), 1);
+        assert_eq!(phase.images[0].len(), 3);
+    }
+
+    #[test]
+    fn actions_are_recorded_with_what_they_did() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let image = |name: &str, contents: &str| {
+            std::fs::write(dir.join(name), contents).unwrap();
+            let mut image = Image::new_lazy(dir.join(name), contents.len(), None);
+            image.file_id = std::fs::metadata(&image.path).ok().and_then(|x| FileId::from_metadata(&x));
+            image
+        };
+        let groups = vec![
+            vec![image("a.jpg", "aaaa"), image("b.jpg", "bb")],
+            vec![image("c.jpg", "cccccc"), image("d.jpg", "cccccc")],
+        ];
+        std::fs::write(dir.join("b.xmp"), "sidecar").unwrap();
+        let results = SearchResults::restored(groups, HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.clone()), results);
+        let last = |phase: &OutputPhase| {
+            let entry = phase.history.last().unwrap();
+            (entry.action.clone(), entry.groups.len(), entry.files, entry.bytes, entry.failed)
+        };
+
+        // Sidecars count as files, and their bytes too.
+        let mut plan = ActionPlan::trash(&[dir.join("b.jpg")], "duplicates");
+        assert_eq!(plan.add_sidecars(&["xmp".to_string()]), 1);
+        phase.execute_plan(plan).unwrap();
+        assert_eq!(last(&phase), ("Trash 1 duplicates".to_string(), 1, 2, 9, 0));
+
+        phase.undo_last().unwrap();
+        assert!(dir.join("b.jpg").exists() && dir.join("b.xmp").exists());
+        assert_eq!(last(&phase), ("Undo \"Trash 1 duplicates\"".to_string(), 1, 2, 9, 0));
+
+        let group = phase.images.iter().find(|x| x[0].path.ends_with("c.jpg")).unwrap().clone();
+        phase.execute_plan(ActionPlan::link(&[group])).unwrap();
+        assert_eq!(last(&phase), ("Hard link 1 files".to_string(), 1, 1, 6, 0));
+
+        let files = [dir.join("a.jpg"), dir.join("c.jpg")];
+        phase.execute_plan(ActionPlan::tag(&files, LabelColor::Red)).unwrap();
+        let tagged = if cfg!(target_os = "macos") { (2, 10, 0) } else { (0, 0, 2) };
+        assert_eq!(last(&phase), ("Tag 2 files Red".to_string(), 2, tagged.0, tagged.1, tagged.2));
+        assert_eq!(phase.history.len(), 4);
+    }
+
+    #[test]
+    fn trashed_images_take_their_sidecars() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        for name in ["a.jpg", "a.xmp", "b.jpg", "b.CR2", "b.xmp", "c.jpg"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let group = ["a.jpg", "b.jpg", "c.jpg"].iter().map(|x| Image::new_lazy(dir.join(x), x.len(), None)).collect();
+        let results = SearchResults::restored(vec![group], HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.clone()), results);
+
+        phase.dry_run = true;
+        let trashed = [dir.join("a.jpg"), dir.join("b.jpg")];
+        phase.run_plan(ActionPlan::trash(&trashed, "duplicates"), "Trash", String::new()).unwrap();
+        let plan = phase.plan.take().unwrap();
+        let sidecars: Vec<_> = plan.steps.iter()
+            .filter(|x| x.sidecar_of.is_some())
+            .map(|x| (x.path.file_name().unwrap().to_str().unwrap(), x.action == Action::Trash))
+            .collect();
+        // The RAW's staying, and the .xmp could be its as well.
+        assert_eq!(sidecars, [("a.xmp", true), ("b.xmp", false)]);
+
+        phase.execute_plan(plan).unwrap();
+        assert!(!dir.join("a.jpg").exists() && !dir.join("a.xmp").exists());
+        assert!(!dir.join("b.jpg").exists() && dir.join("b.xmp").exists());
+        assert_eq!(phase.trashed.map(|x| x.2), Some(1));
+    }
+
+    #[test]
+    fn live_results_are_reconciled_with_the_final_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let images = |names: &[&str]| -> Vec<Image> {
+            names.iter().map(|x| Image::new_lazy(dir.join(x), x.len(), None)).collect()
+        };
+        for name in ["a.jpg", "b.jpg", "c.jpg", "d.jpg", "e.jpg"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let results = SearchResults::restored(vec![images(&["a.jpg", "b.jpg"]), images(&["c.jpg", "d.jpg"])], HashAlg::Gradient);
+        let mut phase = OutputPhase::new(UserOpts::new(dir.clone()), results);
+        for group in phase.images.clone() {
+            phase.reviews.update(&group, |x| x.mark = Mark::Kept);
+        }
+
+        // A near match joins one reviewed group, and files deleted since are dropped.
+        let last = SearchResults::restored(
+            vec![images(&["a.jpg", "b.jpg", "e.jpg"]), images(&["c.jpg", "d.jpg"]), images(&["gone1.jpg", "gone2.jpg"])],
+            HashAlg::Gradient,
+        );
+        phase.reconcile_live(last);
+        let group_of = |name: &str| phase.images.iter().find(|x| x.iter().any(|x| x.path.ends_with(name))).unwrap();
+        assert_eq!(group_of("a.jpg").len(), 3);
+        assert_eq!(group_of("c.jpg").len(), 2);
+        assert_eq!(phase.images.len(), 2);
+        assert_eq!(phase.reviews.mark(group_of("a.jpg")), Mark::NeedsAttention);
+        assert_eq!(phase.reviews.mark(group_of("c.jpg")), Mark::Kept);
+        assert_eq!(phase.regrouped, 1);
+        assert_eq!(phase.live_new, HashSet::from([dir.join("e.jpg")]));
+    }
+}
@@ -0,0 +1,159 @@
+use eframe::egui;
+
+
+// The command palette: Cmd/Ctrl+K anywhere brings up a filterable list of
+// whatever the current phase can do, so actions can be found (and done)
+// without hunting for their buttons.
+
+// An action a phase offers. Choosing one calls the phase's run_command() with
+// its id, so it goes through the same code, and phase transitions, as the
+// button it stands in for.
+pub struct Command {
+    pub id: &'static str,
+    pub name: String,
+}
+
+impl Command {
+    pub fn new(id: &'static str, name: impl Into<String>) -> Command {
+        Command{id, name: name.into()}
+    }
+}
+
+#[derive(Default)]
+pub struct Palette {
+    open: bool,
+    query: String,
+    selected: usize, // Among the matches
+}
+
+impl Palette {
+    const WIDTH: f32 = 400.0;
+    const TOP_OFFSET: f32 = 80.0;
+
+    const SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::K);
+
+    // Call before the phase renders, so keys meant for the palette (Escape in
+    // particular, which phases use too) are consumed before the phase sees
+    // them. Returns the command chosen with Enter, if any.
+    //
+    // Only the root viewport's input is read, so keys typed into another
+    // window never get here, and error dialogs are native and block updates
+    // entirely while they're up.
+    pub fn handle_keys(&mut self, ctx: &egui::Context, commands: &[Command]) -> Option<&'static str> {
+        if ctx.input_mut(|i| i.consume_shortcut(&Self::SHORTCUT)) {
+            if self.open {
+                self.close();
+            } else {
+                self.open = true;
+            }
+            return None;
+        }
+        if !self.open {
+            return None;
+        }
+
+        let count = self.matches(commands).len();
+        let (escape, up, down, enter) = ctx.input_mut(|i| (
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+        ));
+        if escape {
+            self.close();
+        } else if up {
+            self.selected = self.selected.saturating_sub(1);
+        } else if down && self.selected + 1 < count {
+            self.selected += 1;
+        } else if enter {
+            let chosen = self.matches(commands).get(self.selected).map(|x| x.id);
+            if chosen.is_some() {
+                self.close();
+            }
+            return chosen;
+        }
+        None
+    }
+
+    // Call after the phase renders, so the palette is on top. Returns the
+    // command clicked, if any.
+    pub fn draw(&mut self, ctx: &egui::Context, commands: &[Command]) -> Option<&'static str> {
+        if !self.open {
+            return None;
+        }
+
+        let mut chosen = None;
+        let resp = egui::Area::new(egui::Id::new("command_palette"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, Self::TOP_OFFSET))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(Self::WIDTH);
+                    let edit = egui::TextEdit::singleline(&mut self.query)
+                        .hint_text(tr!("Type a command…"))
+                        .desired_width(f32::INFINITY);
+                    let resp = ui.add(edit);
+                    resp.request_focus();
+                    if resp.changed() {
+                        self.selected = 0;
+                    }
+                    ui.separator();
+
+                    let matches = self.matches(commands);
+                    if matches.is_empty() {
+                        ui.label(egui::RichText::new(tr!("No matching commands")).italics());
+                    }
+                    for (idx, command) in matches.iter().enumerate() {
+                        let label = ui.selectable_label(idx == self.selected, &command.name);
+                        if idx == self.selected {
+                            label.scroll_to_me(None);
+                        }
+                        if label.clicked() {
+                            chosen = Some(command.id);
+                        }
+                    }
+                });
+            });
+
+        if chosen.is_some() || resp.response.clicked_elsewhere() {
+            self.close();
+        }
+        chosen
+    }
+
+    fn close(&mut self) {
+        *self = Palette::default();
+    }
+
+    // Best match first; ties keep the phase's order.
+    fn matches<'a>(&self, commands: &'a [Command]) -> Vec<&'a Command> {
+        let mut scored: Vec<(i32, &Command)> = commands.iter()
+            .filter_map(|x| fuzzy_score(&self.query, &x.name).map(|score| (score, x)))
+            .collect();
+        scored.sort_by_key(|(score, _)| -score);
+        scored.into_iter().map(|(_, x)| x).collect()
+    }
+}
+
+// Some if the query's characters all appear in the name, in order, ignoring
+// case. Higher is better: runs of consecutive characters, and characters at
+// the start of words, score extra.
+fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last = None;
+    for c in query.chars().flat_map(char::to_lowercase).filter(|x| !x.is_whitespace()) {
+        let found = pos + name[pos..].iter().position(|x| *x == c)?;
+        score += 1;
+        if last.is_some_and(|x| x + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
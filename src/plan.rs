@@ -0,0 +1,316 @@
+use crate::misc::{Image, find_sidecars, relative_path};
+use crate::hardlink;
+use crate::content::ContentKind;
+use crate::finder_tags::LabelColor;
+use crate::move_files::{self, CollisionPolicy, Resolved};
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+
+// What a bulk action will do, file by file. Actions always go through a plan,
+// whether it's shown (in a dry run) or carried out straight away, so what's
+// previewed is exactly what would be done.
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Action {
+    Trash,
+    Link{target: PathBuf}, // Replace with a hard link to target
+    Tag{tag: String}, // Add a Finder tag with this name
+    Move{target: PathBuf, replace: bool}, // Move to target, replacing the file there if replace
+    Skip, // Left alone; problem says why
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Step {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub action: Action,
+    pub problem: Option<String>, // Predicted from metadata; the step is still tried unless skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<u32>, // Hash distance from the rest of its group, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<ContentKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>, // Base64, as grouped by, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sidecar_of: Option<PathBuf>, // The image it goes along with, for sidecar files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>, // The ID of the group it's in, if any; see misc::group_id()
+}
+
+impl Step {
+    pub fn new(path: &Path, action: Action) -> Step {
+        Step {
+            path: path.to_path_buf(),
+            problem: predict_problem(path, &action),
+            action,
+            distance: None,
+            content: None,
+            hash: None,
+            sidecar_of: None,
+            group: None,
+        }
+    }
+
+    pub fn skip(path: &Path, reason: String) -> Step {
+        Step {
+            path: path.to_path_buf(),
+            action: Action::Skip,
+            problem: Some(reason),
+            distance: None,
+            content: None,
+            hash: None,
+            sidecar_of: None,
+            group: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PlanKind {
+    Trash{what: String}, // What's being trashed, e.g. "candidates", for descriptions
+    Link,
+    Tag(LabelColor),
+    Move{dest: PathBuf, policy: CollisionPolicy},
+}
+
+pub struct ActionPlan {
+    pub kind: PlanKind,
+    pub steps: Vec<Step>,
+}
+
+impl ActionPlan {
+    pub fn trash(paths: &[PathBuf], what: &str) -> ActionPlan {
+        ActionPlan {
+            kind: PlanKind::Trash{what: what.to_string()},
+            steps: paths.iter().map(|x| Step::new(x, Action::Trash)).collect(),
+        }
+    }
+
+    pub fn tag(paths: &[PathBuf], color: LabelColor) -> ActionPlan {
+        ActionPlan {
+            kind: PlanKind::Tag(color),
+            steps: paths.iter().map(|x| Step::new(x, Action::Tag{tag: color.name().to_string()})).collect(),
+        }
+    }
+
+    // Moves the files into dest, by name, or with root given, under the
+    // folders they're in under root. Where a name's taken, as exists() says,
+    // is up to policy; files that don't go anywhere are skipped.
+    pub fn move_to(paths: &[PathBuf], dest: &Path, root: Option<&Path>, policy: CollisionPolicy, exists: impl Fn(&Path) -> bool) -> ActionPlan {
+        let files: Vec<(PathBuf, PathBuf)> = paths.iter()
+            .map(|x| (x.clone(), move_files::destination(x, dest, root)))
+            .collect();
+        let steps = move_files::resolve(&files, policy, exists).into_iter()
+            .zip(paths)
+            .map(|(resolved, path)| match resolved {
+                Resolved::To(target) => Step::new(path, Action::Move{target, replace: false}),
+                Resolved::Replace(target) => Step::new(path, Action::Move{target, replace: true}),
+                Resolved::Skip(taken) => Step::skip(path, format!("{} is taken", taken.display())),
+            })
+            .collect();
+        ActionPlan{kind: PlanKind::Move{dest: dest.to_path_buf(), policy}, steps}
+    }
+
+    pub fn link(groups: &[Vec<Image>]) -> ActionPlan {
+        ActionPlan {
+            kind: PlanKind::Link,
+            steps: groups.iter().flat_map(|x| hardlink::plan_group(x)).collect(),
+        }
+    }
+
+    // Turns the steps acting on paths for which protected() is true into
+    // skips, giving reason. Returns how many.
+    pub fn protect(&mut self, protected: impl Fn(&Path) -> bool, reason: &str) -> usize {
+        let mut count = 0;
+        for step in &mut self.steps {
+            if step.action != Action::Skip && protected(&step.path) {
+                *step = Step::skip(&step.path, reason.to_string());
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Adds a step trashing each sidecar of the files being trashed (see
+    // misc::find_sidecars()), or skipping it if it could belong to a file
+    // that's staying. Returns how many will be trashed.
+    pub fn add_sidecars(&mut self, exts: &[String]) -> usize {
+        let trashed: HashSet<PathBuf> = self.steps.iter()
+            .filter(|x| x.action == Action::Trash)
+            .map(|x| x.path.clone())
+            .collect();
+        let mut seen = HashSet::new();
+        let mut sidecars = vec![];
+        for image in self.steps.iter().filter(|x| x.action == Action::Trash).map(|x| &x.path) {
+            for (sidecar, sharers) in find_sidecars(image, exts) {
+                if trashed.contains(&sidecar) || !seen.insert(sidecar.clone()) {
+                    continue;
+                }
+                let mut step = match sharers.iter().find(|x| !trashed.contains(*x)) {
+                    Some(staying) => Step::skip(&sidecar, format!("Also belongs to {}", staying.display())),
+                    None => Step::new(&sidecar, Action::Trash),
+                };
+                step.sidecar_of = Some(image.clone());
+                sidecars.push(step);
+            }
+        }
+        let count = sidecars.iter().filter(|x| x.action == Action::Trash).count();
+        self.steps.extend(sidecars);
+        count
+    }
+
+    // Fills in each step's distance, content kind, hash and group, for
+    // exports. groups has each image's group's ID.
+    pub fn annotate(&mut self, distances: &HashMap<PathBuf, u32>, images: &HashMap<&Path, &Image>, groups: &HashMap<&Path, String>) {
+        for step in &mut self.steps {
+            step.group = groups.get(step.path.as_path()).cloned();
+            let image = images.get(step.path.as_path());
+            step.distance = distances.get(&step.path).copied();
+            step.content = image.and_then(|x| x.content);
+            step.hash = image.and_then(|x| x.hash.as_ref()).map(|x| x.to_base64());
+        }
+    }
+
+    // e.g. "Trash 12 candidates", as used for undo.
+    pub fn description(&self) -> String {
+        let acted = self.steps.iter().filter(|x| x.action != Action::Skip && x.sidecar_of.is_none()).count();
+        match &self.kind {
+            PlanKind::Trash{what} => format!("Trash {acted} {what}"),
+            PlanKind::Link => format!("Hard link {acted} files"),
+            PlanKind::Tag(color) => format!("Tag {acted} files with {}", color.name()),
+            PlanKind::Move{dest, ..} => format!("Move {acted} files to {}", dest.display()),
+        }
+    }
+
+    // The plan with its paths relative to dir, e.g. the folder it's exported
+    // to, so it still applies wherever the files are mounted next. Paths with
+    // nothing in common with dir stay as they are.
+    pub fn relative_to(&self, dir: &Path) -> ActionPlan {
+        let relative = |path: &Path| relative_path(path, dir).unwrap_or_else(|| path.to_path_buf());
+        let steps = self.steps.iter()
+            .map(|step| {
+                let mut step = step.clone();
+                step.path = relative(&step.path);
+                step.sidecar_of = step.sidecar_of.as_deref().map(relative);
+                if let Action::Link{target} | Action::Move{target, ..} = &mut step.action {
+                    *target = relative(target);
+                }
+                step
+            })
+            .collect();
+        ActionPlan{kind: self.kind.clone(), steps}
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(&self.steps)
+            .map_err(|e| format!("Error serializing plan: {e}"))
+    }
+
+    pub fn to_csv(&self) -> String {
+        // Paths can contain anything, so every field is quoted.
+        let quote = |x: &str| format!("\"{}\"", x.replace('"', "\"\""));
+        let mut csv = "path,action,destination,problem,distance,content,hash,sidecar_of,group\n".to_string();
+        for step in &self.steps {
+            let (action, destination) = match &step.action {
+                Action::Trash => ("trash", String::new()),
+                Action::Link{target} => ("link", target.display().to_string()),
+                Action::Tag{tag} => ("tag", tag.clone()),
+                Action::Move{target, replace: false} => ("move", target.display().to_string()),
+                Action::Move{target, replace: true} => ("replace", target.display().to_string()),
+                Action::Skip => ("skip", String::new()),
+            };
+            csv += &[
+                quote(&step.path.display().to_string()),
+                quote(action),
+                quote(&destination),
+                quote(step.problem.as_deref().unwrap_or_default()),
+                step.distance.map(|x| x.to_string()).unwrap_or_default(),
+                step.content.map(|x| x.key().to_string()).unwrap_or_default(),
+                step.hash.as_deref().map(quote).unwrap_or_default(),
+                step.sidecar_of.as_ref().map(|x| quote(&x.display().to_string())).unwrap_or_default(),
+                step.group.as_deref().map(quote).unwrap_or_default(),
+            ].join(",");
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+// Problems visible from metadata alone, without trying anything.
+fn predict_problem(path: &Path, action: &Action) -> Option<String> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if matches!(action, Action::Tag{..}) => {
+            // Tags are written to the file itself.
+            return meta.permissions().readonly()
+                .then(|| "Permission denied: the file is read-only".to_string());
+        },
+        Ok(_) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Some("File missing".to_string()),
+        Err(e) => return Some(format!("Can't read file: {e}")),
+    }
+
+    // Removing or replacing a file takes write permission on its folder.
+    let parent = path.parent()?;
+    match std::fs::metadata(parent) {
+        Ok(meta) if meta.permissions().readonly() => {
+            Some("Permission denied: the folder is read-only".to_string())
+        },
+        Ok(_) => None,
+        Err(e) => Some(format!("Can't read folder: {e}")),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_are_exported() {
+        let paths = [PathBuf::from("/nonexistent/a.jpg"), PathBuf::from("/nonexistent/b.jpg")];
+        let mut plan = ActionPlan::trash(&paths, "candidates");
+        let distances = HashMap::from([(paths[0].clone(), 0), (PathBuf::from("/nonexistent/c.jpg"), 5)]);
+        plan.annotate(&distances, &HashMap::new(), &HashMap::new());
+        assert_eq!(plan.steps[0].distance, Some(0));
+        assert_eq!(plan.steps[1].distance, None);
+
+        let csv = plan.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0].split(',').nth(4), Some("distance"));
+        assert_eq!(lines[1].split(',').nth(4), Some("0"));
+        assert_eq!(lines[2].split(',').nth(4), Some(""));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+        assert_eq!(json[0]["distance"], 0);
+        assert!(json[1].get("distance").is_none());
+    }
+
+    #[test]
+    fn hashes_are_exported_as_grouped_by() {
+        let paths = [PathBuf::from("/nonexistent/a.jpg"), PathBuf::from("/nonexistent/b.jpg")];
+        let mut image = Image::new_lazy(paths[0].clone(), 1, None);
+        image.hash = Some(image_hasher::ImageHash::from_bytes(&[0xAB; 8]).unwrap());
+        let hash = image.hash.as_ref().unwrap().to_base64();
+        let unhashed = Image::new_lazy(paths[1].clone(), 1, None);
+
+        let mut plan = ActionPlan::trash(&paths, "candidates");
+        let images = HashMap::from([(paths[0].as_path(), &image), (paths[1].as_path(), &unhashed)]);
+        plan.annotate(&HashMap::new(), &images, &HashMap::new());
+
+        let csv = plan.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0].split(',').nth(6), Some("hash"));
+        // Base64 has no commas, so splitting is safe; it's quoted all the same.
+        assert_eq!(lines[1].split(',').nth(6), Some(format!("\"{hash}\"").as_str()));
+        assert_eq!(lines[2].split(',').nth(6), Some(""));
+
+        let json: serde_json::Value = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+        assert_eq!(json[0]["hash"], hash.as_str());
+        assert!(json[1].get("hash").is_none());
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+
+// Quick Look previews on macOS, via qlmanage. Elsewhere there's nothing to
+// call, and the action isn't offered at all.
+
+pub const SUPPORTED: bool = cfg!(target_os = "macos");
+
+// Only one preview is up at a time: showing another closes the last, as does
+// dropping this.
+#[derive(Default)]
+pub struct QuickLook {
+    child: Option<Child>,
+}
+
+impl QuickLook {
+    // Returns as soon as qlmanage is started; it runs until the preview is
+    // closed.
+    pub fn show(&mut self, path: &Path) -> std::io::Result<()> {
+        self.close();
+        // qlmanage logs plenty even when everything works.
+        let child = Command::new("qlmanage")
+            .arg("-p")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        self.child = Some(child);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            // Fails harmlessly if the preview was already closed. Waiting reaps
+            // it either way.
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for QuickLook {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,364 @@
+use crate::misc::{format_time, FileId};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+// Renaming files that are being kept, to a template like
+// "{exif_date}_{orig_name}", rather than trashing them. The template gives the
+// new name without its extension; each file keeps its own. A batch is only
+// renamed if every file in it can be: no two may end up with the same name,
+// and none may take the name of a file that's already there.
+
+pub const DEFAULT_TEMPLATE: &str = "{exif_date}_{orig_name}";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Placeholder {
+    ExifDate, // YYYY-MM-DD the photo was taken
+    Mtime, // YYYY-MM-DD the file was last modified, in UTC
+    OrigName, // The current name, without its extension
+    GroupId, // See misc::group_id()
+    Counter, // Position in the batch, from 1, padded to the same width for all
+}
+
+impl Placeholder {
+    pub const ALL: [Placeholder; 5] = [
+        Placeholder::ExifDate,
+        Placeholder::Mtime,
+        Placeholder::OrigName,
+        Placeholder::GroupId,
+        Placeholder::Counter,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Placeholder::ExifDate => "exif_date",
+            Placeholder::Mtime => "mtime",
+            Placeholder::OrigName => "orig_name",
+            Placeholder::GroupId => "group_id",
+            Placeholder::Counter => "counter",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Part {
+    Text(String),
+    Field(Placeholder),
+}
+
+// A parsed template. Braces are written doubled to be taken literally.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template, String> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("Unclosed {{{name} in the template")),
+                        }
+                    }
+                    let Some(field) = Placeholder::ALL.into_iter().find(|x| x.key() == name) else {
+                        return Err(format!("Unknown placeholder {{{name}}}"));
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                },
+                '}' => return Err("Unmatched } in the template; write }} for a literal one".to_string()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        if parts.is_empty() {
+            return Err("The template is empty".to_string());
+        }
+        Ok(Template{parts})
+    }
+
+    // The file's new name, without an extension. Counter is from 1.
+    fn render(&self, file: &RenameFile, counter: usize, width: usize) -> Result<String, String> {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name += text,
+                Part::Field(Placeholder::ExifDate) => {
+                    name += &file.taken.and_then(date).ok_or("No EXIF capture date")?;
+                },
+                Part::Field(Placeholder::Mtime) => {
+                    name += &file.mtime.and_then(date).ok_or("Modification time unknown")?;
+                },
+                Part::Field(Placeholder::OrigName) => {
+                    name += &file.path.file_stem().unwrap_or_default().to_string_lossy();
+                },
+                Part::Field(Placeholder::GroupId) => name += &file.group_id,
+                Part::Field(Placeholder::Counter) => name += &format!("{counter:0width$}"),
+            }
+        }
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+            return Err(format!("\"{name}\" isn't a valid file name"));
+        }
+        Ok(name)
+    }
+}
+
+// As YYYY-MM-DD, in UTC.
+fn date(time: SystemTime) -> Option<String> {
+    time.duration_since(UNIX_EPOCH).ok()?;
+    Some(format_time(time)[..10].to_string())
+}
+
+// What a file's new name can be made from.
+pub struct RenameFile {
+    pub path: PathBuf,
+    pub taken: Option<SystemTime>,
+    pub mtime: Option<SystemTime>,
+    pub group_id: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: Option<PathBuf>, // None if the name couldn't be made
+    pub problem: Option<String>, // Why it can't be renamed; then none of the batch is
+}
+
+impl Rename {
+    // Whether carrying it out would do anything.
+    pub fn changes(&self) -> bool {
+        self.to.as_ref().is_some_and(|x| *x != self.from)
+    }
+}
+
+// Names can differ only in case and still collide on macOS and Windows, so
+// they're compared without it everywhere.
+pub fn collision_key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+// Whether a file being at the new path means something else would be
+// replaced, rather than it just being the file itself. A path differing only
+// in case is the file itself on a case-insensitive filesystem, but another
+// file on a case-sensitive one; same_file says which.
+pub fn would_replace(
+    from: &Path,
+    to: &Path,
+    exists: impl Fn(&Path) -> bool,
+    same_file: impl Fn(&Path, &Path) -> bool,
+) -> bool {
+    if from == to || !exists(to) {
+        return false;
+    }
+    collision_key(from) != collision_key(to) || !same_file(from, to)
+}
+
+// Whether the two paths lead to the same file on disk, for would_replace().
+// Where files have no ID to compare (Windows, whose filesystems ignore case),
+// paths that both exist are taken to be the same file.
+pub fn same_file_on_disk(a: &Path, b: &Path) -> bool {
+    let (Ok(a), Ok(b)) = (std::fs::metadata(a), std::fs::metadata(b)) else {
+        return false;
+    };
+    match (FileId::from_metadata(&a), FileId::from_metadata(&b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+// Works out every file's new name, in the given order, and what would stop
+// each from being renamed. Exists and same_file are as for would_replace().
+pub fn plan(
+    template: &Template,
+    files: &[RenameFile],
+    exists: impl Fn(&Path) -> bool,
+    same_file: impl Fn(&Path, &Path) -> bool,
+) -> Vec<Rename> {
+    let width = files.len().to_string().len();
+    let mut renames: Vec<Rename> = files.iter()
+        .enumerate()
+        .map(|(idx, file)| match template.render(file, idx + 1, width) {
+            Ok(stem) => {
+                let mut name = std::ffi::OsString::from(stem);
+                if let Some(ext) = file.path.extension() {
+                    name.push(".");
+                    name.push(ext);
+                }
+                Rename{from: file.path.clone(), to: Some(file.path.with_file_name(name)), problem: None}
+            },
+            Err(e) => Rename{from: file.path.clone(), to: None, problem: Some(e)},
+        })
+        .collect();
+
+    let mut claimed: HashMap<String, Vec<usize>> = HashMap::new();
+    for (idx, rename) in renames.iter().enumerate() {
+        if let Some(to) = &rename.to {
+            claimed.entry(collision_key(to)).or_default().push(idx);
+        }
+    }
+    for idx in 0..renames.len() {
+        let Some(to) = renames[idx].to.clone() else {
+            continue;
+        };
+        let others: Vec<_> = claimed[&collision_key(&to)].iter().filter(|x| **x != idx).collect();
+        if let Some(other) = others.first() {
+            let other = renames[**other].from.file_name().unwrap_or_default().to_string_lossy().to_string();
+            renames[idx].problem = Some(format!("Same new name as {other}"));
+        } else if would_replace(&renames[idx].from, &to, &exists, &same_file) {
+            // Even if that file's being renamed too, since which goes first
+            // would matter.
+            renames[idx].problem = Some("A file with the new name already exists".to_string());
+        }
+    }
+    renames
+}
+
+// Whether the batch can be carried out, and would change anything.
+pub fn is_ready(renames: &[Rename]) -> bool {
+    renames.iter().all(|x| x.problem.is_none()) && renames.iter().any(Rename::changes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    // 2021-03-04 05:06:07 UTC
+    const TAKEN: u64 = 1614834367;
+
+    fn file(path: &str) -> RenameFile {
+        RenameFile {
+            path: PathBuf::from(path),
+            taken: Some(UNIX_EPOCH + Duration::from_secs(TAKEN)),
+            mtime: Some(UNIX_EPOCH + Duration::from_secs(TAKEN + 86400)),
+            group_id: "0123456789ab".to_string(),
+        }
+    }
+
+    fn names(renames: &[Rename]) -> Vec<Option<String>> {
+        renames.iter()
+            .map(|x| x.to.as_ref().map(|x| x.to_string_lossy().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn templates_parse_placeholders_and_escapes() {
+        let template = Template::parse("{{{exif_date}}}_{orig_name}").unwrap();
+        assert_eq!(template.parts, vec![
+            Part::Text("{".to_string()),
+            Part::Field(Placeholder::ExifDate),
+            Part::Text("}_".to_string()),
+            Part::Field(Placeholder::OrigName),
+        ]);
+        assert!(Template::parse(DEFAULT_TEMPLATE).is_ok());
+        assert_eq!(Template::parse("{date}").unwrap_err(), "Unknown placeholder {date}");
+        assert_eq!(Template::parse("a_{orig_name").unwrap_err(), "Unclosed {orig_name in the template");
+        assert!(Template::parse("a}b").is_err());
+        assert!(Template::parse("").is_err());
+    }
+
+    #[test]
+    fn placeholders_fill_in_and_extensions_stay() {
+        let template = Template::parse("{exif_date}_{mtime}_{orig_name}_{group_id}_{counter}").unwrap();
+        let files: Vec<_> = (1..=10).map(|x| file(&format!("/photos/IMG_{x}.JPG"))).collect();
+        let renames = plan(&template, &files, |_| false, |_, _| false);
+        assert_eq!(names(&renames)[0].as_deref(), Some("/photos/2021-03-04_2021-03-05_IMG_1_0123456789ab_01.JPG"));
+        assert_eq!(names(&renames)[9].as_deref(), Some("/photos/2021-03-04_2021-03-05_IMG_10_0123456789ab_10.JPG"));
+        assert!(is_ready(&renames));
+
+        let mut bare = file("/photos/scan");
+        bare.taken = None;
+        let renames = plan(&Template::parse("{mtime} {orig_name}").unwrap(), &[bare], |_| false, |_, _| false);
+        assert_eq!(names(&renames), vec![Some("/photos/2021-03-05 scan".to_string())]);
+    }
+
+    #[test]
+    fn missing_values_and_bad_names_are_problems() {
+        let mut undated = file("/photos/a.jpg");
+        undated.taken = None;
+        let renames = plan(&Template::parse("{exif_date}").unwrap(), &[undated, file("/photos/b.jpg")], |_| false, |_, _| false);
+        assert_eq!(renames[0].to, None);
+        assert_eq!(renames[0].problem.as_deref(), Some("No EXIF capture date"));
+        assert!(renames[1].problem.is_none());
+        assert!(!is_ready(&renames));
+
+        let renames = plan(&Template::parse("sub/{orig_name}").unwrap(), &[file("/photos/a.jpg")], |_| false, |_, _| false);
+        assert!(renames[0].problem.as_ref().unwrap().contains("valid file name"));
+    }
+
+    #[test]
+    fn collisions_within_the_batch_are_refused() {
+        // Same date, and names only differing in case
+        let files = [file("/photos/a.jpg"), file("/other/a.jpg"), file("/photos/b.JPG"), file("/photos/c.jpg")];
+        let renames = plan(&Template::parse("{exif_date}").unwrap(), &files, |_| false, |_, _| false);
+        assert_eq!(renames[0].problem.as_deref(), Some("Same new name as b.JPG"));
+        assert!(renames[1].problem.is_none());
+        assert_eq!(renames[2].problem.as_deref(), Some("Same new name as a.jpg"));
+        assert!(renames[3].problem.is_some());
+        assert!(!is_ready(&renames));
+    }
+
+    #[test]
+    fn existing_files_are_refused_but_unchanged_names_are_not() {
+        let taken = PathBuf::from("/photos/2021-03-04_a.jpg");
+        let files = [file("/photos/a.jpg"), file("/photos/b.jpg")];
+        let renames = plan(&Template::parse(DEFAULT_TEMPLATE).unwrap(), &files, |x| x == taken, |_, _| false);
+        assert_eq!(renames[0].problem.as_deref(), Some("A file with the new name already exists"));
+        assert!(renames[1].problem.is_none());
+
+        // Renaming to itself, or only changing case, isn't blocked by the
+        // file itself being there.
+        let files = [file("/photos/Keep.jpg")];
+        let renames = plan(&Template::parse("{orig_name}").unwrap(), &files, |_| true, |_, _| false);
+        assert!(renames[0].problem.is_none() && !renames[0].changes());
+        assert!(!is_ready(&renames));
+        let renames = plan(&Template::parse("keep").unwrap(), &files, |_| true, |_, _| true);
+        assert!(renames[0].problem.is_none() && renames[0].changes());
+
+        // But on a case-sensitive filesystem, keep.jpg can be another file
+        let renames = plan(&Template::parse("keep").unwrap(), &files, |_| true, |_, _| false);
+        assert_eq!(renames[0].problem.as_deref(), Some("A file with the new name already exists"));
+    }
+
+    #[test]
+    fn case_only_renames_check_the_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let upper = dir.path().join("Keep.jpg");
+        let lower = dir.path().join("keep.jpg");
+        std::fs::write(&upper, b"one").unwrap();
+        assert!(same_file_on_disk(&upper, &upper));
+        let exists = |x: &Path| x.exists();
+        if lower.exists() {
+            // Case-insensitive: it's the file itself
+            assert!(!would_replace(&upper, &lower, exists, same_file_on_disk));
+        } else {
+            std::fs::write(&lower, b"two").unwrap();
+            assert!(!same_file_on_disk(&upper, &lower));
+            assert!(would_replace(&upper, &lower, exists, same_file_on_disk));
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::misc::{Image, format_time};
+use crate::i18n::format_size;
+use crate::searcher::{self, SearchStats, SizeCap};
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+
+use image::ImageEncoder;
+
+
+// A self-contained HTML page of the results, thumbnails and all, to review
+// somewhere other than the machine that ran the search. It has no external
+// assets, so it works offline and can be passed around as one file.
+
+const THUMB_SIZE: u32 = 256;
+const THUMB_QUALITY: u8 = 75;
+
+// Roughly what each image adds to the page: a thumbnail at the size and
+// quality above, base64 encoded, plus its row. On the high side, so a free
+// space check errs toward asking.
+const BYTES_PER_IMAGE: u64 = 40 * 1024;
+
+// Decoding is capped well above the thumbnail size, so huge images are scaled
+// down as they're decoded rather than after. Not reduced to hashing size,
+// which is smaller than the thumbnails.
+const THUMB_DECODE_CAP: SizeCap = SizeCap {
+    max_pixels: 4 * 1024 * 1024,
+    skip: false,
+    max_bytes: 1024 * 1024 * 1024,
+    reduced_jpeg: false,
+};
+
+// Everything a report needs, copied so it can be written on another thread.
+pub struct ReportInput {
+    pub groups: Vec<Vec<Image>>,
+    pub stats: Option<SearchStats>,
+    pub options: Vec<(String, String)>, // Label and value of each search option
+}
+
+// A report being written in the background.
+pub struct ReportJob {
+    handle: JoinHandle<Result<(), String>>,
+    cancel: Arc<AtomicBool>,
+    done: Arc<AtomicUsize>, // Images thumbnailed so far
+    pub total: usize,
+    pub path: PathBuf,
+}
+
+impl ReportJob {
+    pub fn start(input: ReportInput, path: PathBuf) -> ReportJob {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicUsize::new(0));
+        let total = input.groups.iter().map(Vec::len).sum();
+        let handle = {
+            let (cancel, done, path) = (cancel.clone(), done.clone(), path.clone());
+            std::thread::spawn(move || write_report(&input, &path, &cancel, &done))
+        };
+        ReportJob{handle, cancel, done, total, path}
+    }
+
+    // About how big the report on these groups will be.
+    pub fn estimated_size(groups: &[Vec<Image>]) -> u64 {
+        groups.iter().map(|x| x.len() as u64).sum::<u64>() * BYTES_PER_IMAGE
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Err(None) if it was canceled.
+    pub fn join(self) -> Result<(), Option<String>> {
+        let canceled = self.cancel.load(Ordering::Relaxed);
+        match self.handle.join() {
+            Ok(Ok(())) if canceled => Err(None),
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(Some(e)),
+            Err(_) => Err(Some("The report writer crashed".to_string())),
+        }
+    }
+}
+
+fn write_report(input: &ReportInput, path: &Path, cancel: &AtomicBool, done: &AtomicUsize) -> Result<(), String> {
+    let mut html = String::new();
+    html += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Deckard report</title>\n";
+    html += STYLE;
+    html += "</head>\n<body>\n<h1>Deckard report</h1>\n";
+
+    html += "<h2>Options</h2>\n<table>\n";
+    for (label, value) in &input.options {
+        html += &format!("<tr><th>{}</th><td>{}</td></tr>\n", escape(label), escape(value));
+    }
+    html += "</table>\n";
+
+    let wasted: usize = input.groups.iter().map(|x| crate::misc::wasted_bytes(x)).sum();
+    html += "<h2>Summary</h2>\n<table>\n";
+    let mut summary = vec![
+        ("Duplicate groups", input.groups.len().to_string()),
+        ("Reclaimable", format_size(wasted)),
+    ];
+    if let Some(stats) = &input.stats {
+        summary.extend([
+            ("Files seen", stats.files_seen.to_string()),
+            ("Images hashed", stats.files_hashed.to_string()),
+            ("Decode failures", stats.decode_failures.to_string()),
+            ("Decodes saved by exact copies", stats.decodes_saved.to_string()),
+            ("Broken images", stats.broken.values().sum::<usize>().to_string()),
+            ("Contents don't match extension", stats.mismatched.len().to_string()),
+            ("Search time", crate::misc::format_duration(stats.elapsed)),
+        ]);
+    }
+    for (label, value) in summary {
+        html += &format!("<tr><th>{label}</th><td>{}</td></tr>\n", escape(&value));
+    }
+    html += "</table>\n";
+
+    html += "<h2>Groups</h2>\n<input id=\"search\" type=\"search\" placeholder=\"Filter by path\" oninput=\"filter(this.value)\">\n";
+    for (idx, group) in input.groups.iter().enumerate() {
+        let paths = group.iter().map(|x| x.path.display().to_string()).collect::<Vec<_>>().join("\n");
+        html += &format!(
+            "<section class=\"group\" id=\"group-{id}\" data-paths=\"{}\">\n<h3>Group {} <small>{id}</small></h3>\n<div class=\"members\">\n",
+            escape(&paths.to_lowercase()),
+            idx + 1,
+            id = crate::misc::group_id(group),
+        );
+        for image in group {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            html += &member_html(image);
+            done.fetch_add(1, Ordering::Relaxed);
+        }
+        html += "</div>\n</section>\n";
+    }
+
+    html += SCRIPT;
+    html += "</body>\n</html>\n";
+    crate::misc::write_atomic(path, html.as_bytes())
+}
+
+fn member_html(image: &Image) -> String {
+    let thumb = match thumbnail(&image.path) {
+        Some(data) => format!("<img src=\"data:image/jpeg;base64,{data}\" alt=\"\">"),
+        None => "<div class=\"missing\">No preview</div>".to_string(),
+    };
+    let mut details = vec![format_size(image.file_size)];
+    if let Some((width, height)) = image.dimm {
+        details.push(format!("{width}×{height}"));
+    }
+    if let Some(time) = image.taken {
+        details.push(format!("Taken {}", format_time(time)));
+    }
+    if let Some(time) = image.mtime {
+        details.push(format!("Modified {}", format_time(time)));
+    }
+    let details: String = details.iter().map(|x| format!("<div>{}</div>", escape(x))).collect();
+    format!(
+        "<div class=\"member\">{thumb}<div class=\"path\">{}</div>{details}</div>\n",
+        escape(&image.path.display().to_string()),
+    )
+}
+
+// A small JPEG, base64 encoded. None if it can't be read.
+fn thumbnail(path: &Path) -> Option<String> {
+    let opened = searcher::open_image(path, None, THUMB_DECODE_CAP).ok()?;
+    let thumb = opened.image.thumbnail(THUMB_SIZE, THUMB_SIZE).to_rgb8();
+    let mut jpeg = vec![];
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, THUMB_QUALITY)
+        .write_image(thumb.as_raw(), thumb.width(), thumb.height(), image::ColorType::Rgb8)
+        .ok()?;
+    Some(base64(&jpeg))
+}
+
+// Paths can contain anything, including markup and quotes.
+fn escape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => ret += "&amp;",
+            '<' => ret += "&lt;",
+            '>' => ret += "&gt;",
+            '"' => ret += "&quot;",
+            '\'' => ret += "&#39;",
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut ret = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (idx, x)| n | (*x as u32) << (16 - 8 * idx));
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                ret.push(ALPHABET[(n >> (18 - 6 * idx) & 0x3F) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { text-align: left; padding: 2px 12px 2px 0; vertical-align: top; }
+#search { width: 100%; max-width: 40em; padding: 4px; margin-bottom: 1em; }
+.group { border-top: 1px solid #ccc; padding: 0.5em 0; }
+.members { display: flex; flex-wrap: wrap; gap: 16px; }
+.member { width: 256px; font-size: 0.85em; }
+.member img { max-width: 256px; max-height: 256px; display: block; }
+.path { font-family: monospace; word-break: break-all; margin: 4px 0; }
+.missing { width: 256px; height: 64px; background: #eee; color: #888; text-align: center; line-height: 64px; }
+</style>
+";
+
+const SCRIPT: &str = "<script>
+function filter(query) {
+    query = query.toLowerCase();
+    for (const group of document.querySelectorAll('.group')) {
+        group.style.display = group.dataset.paths.includes(query) ? '' : 'none';
+    }
+}
+</script>
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_examples() {
+        let cases = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")];
+        for (input, expected) in cases {
+            assert_eq!(base64(input.as_bytes()), expected);
+        }
+    }
+
+    #[test]
+    fn escape_neutralizes_markup() {
+        assert_eq!(escape("<a href=\"x\">&'</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;");
+    }
+}
@@ -0,0 +1,226 @@
+use crate::misc::Image;
+use crate::os_path;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+
+// How far the user's got reviewing each group, for results reviewed over
+// several sittings. Saved with the session.
+//
+// A group's review is kept under the path of one of its members (the first,
+// when it was first marked), rather than its position, so it follows the group
+// through sorting, filtering and other groups being removed. If that member
+// leaves the group, the review moves to another one; see rekey().
+//
+// A group's keeper can be pinned, overriding the keeper strategy (see
+// keepers), by keeping its review under the keeper.
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Mark {
+    #[default]
+    Unreviewed,
+    Kept, // Looked at, and fine as it is
+    NeedsAttention,
+}
+
+impl Mark {
+    pub const ALL: [Mark; 3] = [Mark::Unreviewed, Mark::Kept, Mark::NeedsAttention];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Mark::Unreviewed => tr!("Unreviewed"),
+            Mark::Kept => tr!("Kept"),
+            Mark::NeedsAttention => tr!("Needs attention"),
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Mark::Unreviewed => "○",
+            Mark::Kept => "✔",
+            Mark::NeedsAttention => "⚠",
+        }
+    }
+
+    // What the shortcut cycles to.
+    pub fn next(self) -> Mark {
+        match self {
+            Mark::Unreviewed => Mark::Kept,
+            Mark::Kept => Mark::NeedsAttention,
+            Mark::NeedsAttention => Mark::Unreviewed,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct GroupReview {
+    #[serde(default)]
+    pub mark: Mark,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool, // The member it's kept under is the group's keeper
+}
+
+impl GroupReview {
+    fn is_empty(&self) -> bool {
+        self.mark == Mark::Unreviewed && self.note.is_empty() && !self.pinned
+    }
+}
+
+// As saved in a session.
+#[derive(Serialize, Deserialize)]
+pub struct SessionReview {
+    #[serde(with = "os_path::serde_path")]
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<usize>, // In portable sessions, which root path is relative to
+    #[serde(flatten)]
+    pub review: GroupReview,
+}
+
+// Every group's review, by the member it's kept under.
+#[derive(Clone, Default)]
+pub struct Reviews {
+    by_path: HashMap<PathBuf, GroupReview>,
+}
+
+impl Reviews {
+    pub fn from_session(saved: Vec<SessionReview>) -> Reviews {
+        Reviews{by_path: saved.into_iter().map(|x| (x.path, x.review)).collect()}
+    }
+
+    pub fn to_session(&self) -> Vec<SessionReview> {
+        self.by_path.iter()
+            .map(|(path, review)| SessionReview{path: path.clone(), root: None, review: review.clone()})
+            .collect()
+    }
+
+    // The member the group's review is kept under, if it has one.
+    fn key<'a>(&self, group: &'a [Image]) -> Option<&'a PathBuf> {
+        group.iter().map(|x| &x.path).find(|x| self.by_path.contains_key(*x))
+    }
+
+    pub fn get(&self, group: &[Image]) -> Option<&GroupReview> {
+        self.key(group).and_then(|x| self.by_path.get(x))
+    }
+
+    pub fn mark(&self, group: &[Image]) -> Mark {
+        self.get(group).map_or(Mark::Unreviewed, |x| x.mark)
+    }
+
+    // Changes the group's review, dropping it if it's back to nothing.
+    pub fn update(&mut self, group: &[Image], f: impl FnOnce(&mut GroupReview)) {
+        let Some(key) = self.key(group).or(group.first().map(|x| &x.path)).cloned() else {
+            return;
+        };
+        let review = self.by_path.entry(key.clone()).or_default();
+        f(review);
+        if review.is_empty() {
+            self.by_path.remove(&key);
+        }
+    }
+
+    // Moves reviews kept under a member that's about to leave its group to
+    // one that's staying. Give each group's members before and after. A
+    // pinned keeper that's leaving takes its pin with it.
+    pub fn rekey(&mut self, before: &[Image], after: &[Image]) {
+        let Some(old) = self.key(before) else {
+            return;
+        };
+        if after.iter().any(|x| x.path == *old) {
+            return;
+        }
+        let mut review = self.by_path.remove(old).unwrap();
+        review.pinned = false;
+        if let (Some(new), false) = (after.first(), review.is_empty()) {
+            self.by_path.insert(new.path.clone(), review);
+        }
+    }
+
+    // Moves the review kept under a file that's been renamed.
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if let Some(review) = self.by_path.remove(from) {
+            self.by_path.insert(to.to_path_buf(), review);
+        }
+    }
+
+    // The group's pinned keeper, by index.
+    pub fn pinned(&self, group: &[Image]) -> Option<usize> {
+        let key = self.key(group)?;
+        self.by_path[key].pinned.then(|| group.iter().position(|x| x.path == *key).unwrap())
+    }
+
+    // Pins the member as the group's keeper, or unpins it if it already is.
+    pub fn toggle_pin(&mut self, group: &[Image], member: usize) {
+        let was_pinned = self.pinned(group) == Some(member);
+        let mut review = match self.key(group).cloned() {
+            Some(key) => self.by_path.remove(&key).unwrap(),
+            None => GroupReview::default(),
+        };
+        review.pinned = !was_pinned;
+        if !review.is_empty() {
+            self.by_path.insert(group[member].path.clone(), review);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(names: &[&str]) -> Vec<Image> {
+        names.iter().map(|x| Image::new_lazy(PathBuf::from(x), 0, None)).collect()
+    }
+
+    #[test]
+    fn reviews_follow_their_group() {
+        let mut reviews = Reviews::default();
+        let abc = group(&["/a", "/b", "/c"]);
+        reviews.update(&abc, |x| x.mark = Mark::Kept);
+        reviews.update(&abc, |x| x.note = "check the EXIF".to_string());
+
+        assert_eq!(reviews.mark(&group(&["/c", "/b", "/a"])), Mark::Kept);
+
+        // Without the member it was kept under.
+        let cb = group(&["/c", "/b"]);
+        reviews.rekey(&abc, &cb);
+        assert_eq!(reviews.get(&group(&["/b", "/c"])).unwrap().note, "check the EXIF");
+        assert_eq!(reviews.mark(&group(&["/d", "/e"])), Mark::Unreviewed);
+
+        let saved = serde_json::to_string(&reviews.to_session()).unwrap();
+        let restored = Reviews::from_session(serde_json::from_str(&saved).unwrap());
+        assert_eq!(restored.mark(&cb), Mark::Kept);
+
+        reviews.update(&cb, |x| *x = GroupReview::default());
+        assert!(reviews.by_path.is_empty());
+    }
+
+    #[test]
+    fn pinned_keepers_stay_with_their_group() {
+        let mut reviews = Reviews::default();
+        let abc = group(&["/a", "/b", "/c"]);
+        reviews.update(&abc, |x| x.mark = Mark::NeedsAttention);
+        reviews.toggle_pin(&abc, 1);
+        assert_eq!(reviews.pinned(&group(&["/c", "/b", "/a"])), Some(1));
+        assert_eq!(reviews.mark(&abc), Mark::NeedsAttention);
+
+        // Pinning another moves the pin; pinning it again clears it.
+        reviews.toggle_pin(&abc, 2);
+        assert_eq!(reviews.pinned(&abc), Some(2));
+        reviews.toggle_pin(&abc, 2);
+        assert_eq!(reviews.pinned(&abc), None);
+        assert_eq!(reviews.mark(&abc), Mark::NeedsAttention);
+
+        // A keeper that leaves takes its pin, but not the rest of the review.
+        reviews.toggle_pin(&abc, 0);
+        let bc = group(&["/b", "/c"]);
+        reviews.rekey(&abc, &bc);
+        assert_eq!(reviews.pinned(&bc), None);
+        assert_eq!(reviews.mark(&bc), Mark::NeedsAttention);
+    }
+}
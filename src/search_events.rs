@@ -0,0 +1,78 @@
+use crate::searcher::ErrorKind;
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+// What a search is doing, as it does it, for anything that wants more than
+// Searcher's polled counts: the searching phase's display, and tests, which
+// can follow a search over a folder of fixtures without a UI. Asked for
+// before the search is launched; see Searcher::events().
+//
+// The workers never wait on whoever's reading them: events go into a bounded
+// channel with try_send(), and ones that don't fit are dropped and counted,
+// the count arriving with Finished. Finished can be dropped too, if the
+// channel's still full when the search ends, so readers shouldn't wait on it
+// past Searcher::is_finished().
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SearchEvent {
+    WalkStarted,
+    FileDiscovered(PathBuf), // Queued for hashing, as an image going by its extension or contents
+    FileHashed{path: PathBuf, micros: u64}, // Reading, decoding and hashing it took
+    FileSkipped{path: PathBuf, reason: SkipReason},
+    FileError{path: Option<PathBuf>, kind: ErrorKind, msg: String}, // As in the results' errors
+    GroupFound{members: Vec<PathBuf>}, // In the order of the results, once they're complete
+    StageChanged(Stage),
+    Finished(SearchSummary),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SkipReason {
+    Extension, // Not one being searched for, and, if sniffing, not an image
+    Date, // Modified outside the date range
+    Stopped, // Found after the search was stopped
+    Broken, // Empty, or cut short and undecodable; see broken
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Hashing, // Walking and hashing, which go on together
+    Grouping, // Working out which hashes go together
+    Loading, // Reading the members of each group
+    Crops, // Crop detection, if it's on
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SearchSummary {
+    pub groups: usize,
+    pub files_hashed: usize,
+    pub errors: usize,
+    pub canceled: bool,
+    pub dropped: usize, // Events that didn't fit in the channel, or had nobody left to read them
+}
+
+pub struct EventSink {
+    tx: mpsc::SyncSender<SearchEvent>,
+    dropped: AtomicUsize,
+}
+
+impl EventSink {
+    pub fn new(capacity: usize) -> (EventSink, mpsc::Receiver<SearchEvent>) {
+        let (tx, rx) = mpsc::sync_channel(capacity.max(1));
+        (EventSink{tx, dropped: AtomicUsize::new(0)}, rx)
+    }
+
+    pub fn send(&self, event: SearchEvent) {
+        if self.tx.try_send(event).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Sends Finished, with the drops so far.
+    pub fn finish(&self, summary: SearchSummary) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        self.send(SearchEvent::Finished(SearchSummary{dropped, ..summary}));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::searcher::{SearchOpts, SearchResults, SizeCap, SUPPORTED_EXTS};
+use crate::grouping::Grouping;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use image::ImageEncoder;
+use image_hasher::HashAlg;
+
+
+// Builds folder trees to search in tests, from small images drawn on the spot
+// so there are no binary fixtures to keep, and checks what any search of them
+// should come back with whatever's in them. Tree::random() makes a different
+// messy tree per seed: copies, near copies, broken files, nesting, links and
+// awkward names. Searches are run with searcher::search_with_options(), on the
+// test's own thread.
+//
+// The crate's only a binary, so this lives here, built only for tests, rather
+// than in an integration test that'd have nothing to link against.
+
+// The options tests search with, unless they say otherwise: everything
+// supported, nothing filtered, exact matches only.
+pub fn opts(root: &Path) -> SearchOpts {
+    SearchOpts {
+        root: root.to_path_buf(),
+        subfolders: vec![],
+        hash: HashAlg::Gradient,
+        follow_sym: true,
+        max_depth: None,
+        exts: SUPPORTED_EXTS.iter().map(|x| x.to_string()).collect(),
+        respect_ignore: false,
+        reference: None,
+        io_threads: 1,
+        queue_size: SearchOpts::DEFAULT_QUEUE_SIZE,
+        sniff: false,
+        max_entries: Some(1_000_000),
+        verify_color: false,
+        size_cap: SizeCap::default(),
+        detect_crops: false,
+        trim_borders: false,
+        center: 100,
+        checkpoint: false,
+        resume: None,
+        modified: None,
+        max_distance: 0,
+        grouping: Grouping::default(),
+        scan_libraries: false,
+    }
+}
+
+// Smooth, so scaling it doesn't change its hash. Mirrored, it's different
+// enough not to match.
+pub fn gradient(width: u32, height: u32, mirror: bool) -> image::RgbImage {
+    image::RgbImage::from_fn(width, height, |x, y| {
+        let fx = x as f32 / width as f32;
+        let fx = if mirror { 1.0 - fx } else { fx };
+        let fy = y as f32 / height as f32;
+        let blob = (-((fx - 0.3).powi(2) + (fy - 0.6).powi(2)) * 8.0).exp();
+        image::Rgb([(fx * 255.0) as u8, (fy * 255.0) as u8, (blob * 255.0) as u8])
+    })
+}
+
+pub fn write_image(image: &image::RgbImage, path: &Path) {
+    let ext = path.extension().and_then(|x| x.to_str()).unwrap_or_default();
+    std::fs::write(path, encode_image(image, ext)).unwrap();
+}
+
+// The file write_image() would write with this extension. Uses the encoders
+// directly, rather than save(), which would link in every encoder the image
+// crate has.
+pub fn encode_image(image: &image::RgbImage, ext: &str) -> Vec<u8> {
+    let mut buffer = vec![];
+    let (width, height) = image.dimensions();
+    let color = image::ColorType::Rgb8;
+    match ext {
+        "jpg" => image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, 90)
+            .write_image(image.as_raw(), width, height, color).unwrap(),
+        "png" => image::codecs::png::PngEncoder::new(&mut buffer)
+            .write_image(image.as_raw(), width, height, color).unwrap(),
+        _ => unimplemented!(),
+    }
+    buffer
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Pattern {
+    Solid([u8; 3]),
+    Gradient{mirror: bool},
+    Checkerboard{cell: u32}, // Side of each square, in pixels
+}
+
+impl Pattern {
+    pub fn draw(self, width: u32, height: u32) -> image::RgbImage {
+        match self {
+            Pattern::Solid(color) => image::RgbImage::from_pixel(width, height, image::Rgb(color)),
+            Pattern::Gradient{mirror} => gradient(width, height, mirror),
+            Pattern::Checkerboard{cell} => image::RgbImage::from_fn(width, height, |x, y| {
+                image::Rgb([if (x / cell + y / cell) % 2 == 0 { 230 } else { 20 }; 3])
+            }),
+        }
+    }
+}
+
+// A folder of files to search, deleted when dropped. Paths given to it are
+// relative to its root, with forward slashes; folders are made as needed.
+pub struct Tree {
+    dir: tempfile::TempDir,
+    pub images: Vec<PathBuf>, // Everything written as a whole image, copies included
+}
+
+impl Tree {
+    // Names that have tripped up path handling somewhere: spaces, non-ASCII,
+    // leading dots and dashes, brackets, and length.
+    const NAMES: &'static [&'static str] = &[
+        "photo", "IMG_0001", "with space", "café ☕", ".hidden", "-dash", "[brackets] (1)",
+        "#hash", "emoji 📷", "Ünïcødé", "a", "very long name ", // The last, repeated
+    ];
+
+    pub fn new() -> Tree {
+        Tree{dir: tempfile::tempdir().unwrap(), images: vec![]}
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.root().join(rel)
+    }
+
+    fn create(&self, rel: &str) -> PathBuf {
+        let path = self.path(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        path
+    }
+
+    pub fn image(&mut self, rel: &str, pattern: Pattern, (width, height): (u32, u32)) -> PathBuf {
+        let path = self.create(rel);
+        write_image(&pattern.draw(width, height), &path);
+        self.images.push(path.clone());
+        path
+    }
+
+    // Byte for byte.
+    pub fn copy(&mut self, from: &Path, rel: &str) -> PathBuf {
+        let path = self.create(rel);
+        std::fs::copy(from, &path).unwrap();
+        self.images.push(path.clone());
+        path
+    }
+
+    // Re-encoded at the given fraction of the size, in the format rel's
+    // extension says.
+    pub fn resized(&mut self, from: &Path, rel: &str, scale: f32) -> PathBuf {
+        let image = image::open(from).unwrap().to_rgb8();
+        let (width, height) = image.dimensions();
+        let size = |x: u32| ((x as f32 * scale) as u32).max(1);
+        let resized = image::imageops::resize(&image, size(width), size(height), image::imageops::FilterType::Triangle);
+        let path = self.create(rel);
+        write_image(&resized, &path);
+        self.images.push(path.clone());
+        path
+    }
+
+    pub fn rotated(&mut self, from: &Path, rel: &str) -> PathBuf {
+        let image = image::imageops::rotate90(&image::open(from).unwrap().to_rgb8());
+        let path = self.create(rel);
+        write_image(&image, &path);
+        self.images.push(path.clone());
+        path
+    }
+
+    // Garbage, named like an image.
+    pub fn garbage(&self, rel: &str, len: usize) -> PathBuf {
+        let path = self.create(rel);
+        std::fs::write(&path, (0..len).map(|x| (x * 7 + 3) as u8).collect::<Vec<_>>()).unwrap();
+        path
+    }
+
+    // The first part of an image, as an interrupted copy leaves it.
+    pub fn truncated(&self, from: &Path, rel: &str) -> PathBuf {
+        let bytes = std::fs::read(from).unwrap();
+        let path = self.create(rel);
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    pub fn symlink(&self, target: &Path, rel: &str) -> PathBuf {
+        let path = self.create(rel);
+        std::os::unix::fs::symlink(target, &path).unwrap();
+        path
+    }
+
+    // Somewhere for a file with the extension, up to four folders down.
+    fn place(rng: &mut fastrand::Rng, ext: &str) -> String {
+        let stem = match rng.usize(..Self::NAMES.len()) {
+            x if x == Self::NAMES.len() - 1 => Self::NAMES[x].repeat(10).trim().to_string(),
+            x => Self::NAMES[x].to_string(),
+        };
+        // The number keeps names unique, as stems repeat
+        let name = format!("{stem} {}.{ext}", rng.u32(..));
+        match Self::folder(rng) {
+            folder if folder.is_empty() => name,
+            folder => format!("{folder}/{name}"),
+        }
+    }
+
+    fn folder(rng: &mut fastrand::Rng) -> String {
+        (0..rng.usize(..5))
+            .map(|_| ["x", "sub dir", "ö", "deep"][rng.usize(..4)])
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // A tree of a few dozen files, different for each seed.
+    pub fn random(seed: u64) -> Tree {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let mut tree = Tree::new();
+        let patterns = [
+            Pattern::Gradient{mirror: false},
+            Pattern::Gradient{mirror: true},
+            Pattern::Checkerboard{cell: 4},
+            Pattern::Checkerboard{cell: 11},
+            Pattern::Solid([200, 30, 30]),
+        ];
+        for _ in 0..rng.usize(4..10) {
+            let pattern = patterns[rng.usize(..patterns.len())];
+            let size = (rng.u32(24..96), rng.u32(24..96));
+            let ext = ["png", "jpg"][rng.usize(..2)];
+            let path = Self::place(&mut rng, ext);
+            let original = tree.image(&path, pattern, size);
+            for _ in 0..rng.usize(..4) {
+                let path = Self::place(&mut rng, ext);
+                tree.copy(&original, &path);
+            }
+            if rng.bool() {
+                let path = Self::place(&mut rng, "png");
+                tree.resized(&original, &path, 0.5);
+            }
+            if rng.bool() {
+                let path = Self::place(&mut rng, "png");
+                tree.rotated(&original, &path);
+            }
+            if rng.bool() {
+                let path = Self::place(&mut rng, ext);
+                tree.truncated(&original, &path);
+            }
+            #[cfg(unix)]
+            if rng.bool() {
+                let path = Self::place(&mut rng, ext);
+                tree.symlink(&original, &path);
+            }
+        }
+        for _ in 0..rng.usize(..3) {
+            let path = Self::place(&mut rng, "jpg");
+            tree.garbage(&path, rng.usize(1..2000));
+        }
+        // A link back up, which the walk has to notice rather than follow
+        // forever
+        #[cfg(unix)]
+        if rng.bool() {
+            let path = Path::new(&Self::folder(&mut rng)).join("loop");
+            tree.symlink(tree.root(), path.to_str().unwrap());
+        }
+        tree
+    }
+
+    // Folders between the root and the path, plus one: 1 for a file in the
+    // root, as in SearchOpts::max_depth.
+    pub fn depth(&self, path: &Path) -> usize {
+        path.strip_prefix(self.root()).unwrap().components().count()
+    }
+}
+
+// What's true of any search's results, whatever it was searching.
+pub fn check_invariants(results: &SearchResults) {
+    let mut seen = HashMap::new();
+    for (idx, group) in results.duplicates.iter().enumerate() {
+        assert!(group.len() >= 2, "group {idx} has {} members", group.len());
+        for image in group {
+            if let Some(other) = seen.insert(&image.path, idx) {
+                panic!("{} is in groups {other} and {idx}", image.path.display());
+            }
+        }
+    }
+    for error in &results.errors {
+        if let Some(path) = &error.path {
+            assert!(!seen.contains_key(path), "{} is grouped, but also an error: {}", path.display(), error.msg);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::searcher::{Searcher, search_with_options};
+
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
+
+    const SEEDS: std::ops::Range<u64> = 0..12;
+
+    // Every pair of hashed files with the same bytes, however they got there.
+    fn identical_pairs(results: &SearchResults) -> Vec<(PathBuf, PathBuf)> {
+        let hashed: Vec<&PathBuf> = results.index.hashes.values().flatten().collect();
+        let mut by_bytes: HashMap<Vec<u8>, Vec<&PathBuf>> = HashMap::new();
+        for path in hashed {
+            by_bytes.entry(std::fs::read(path).unwrap()).or_default().push(path);
+        }
+        by_bytes.into_values()
+            .flat_map(|paths| {
+                let first = paths[0].clone();
+                paths.into_iter().skip(1).map(move |x| (first.clone(), x.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_files_are_always_grouped() {
+        for seed in SEEDS {
+            let tree = Tree::random(seed);
+            let results = search_with_options(opts(tree.root()));
+            check_invariants(&results);
+
+            let group_of: HashMap<&PathBuf, usize> = results.duplicates.iter()
+                .enumerate()
+                .flat_map(|(idx, group)| group.iter().map(move |x| (&x.path, idx)))
+                .collect();
+            let pairs = identical_pairs(&results);
+            assert!(!pairs.is_empty(), "seed {seed}");
+            for (a, b) in pairs {
+                assert!(group_of.contains_key(&a) && group_of.get(&a) == group_of.get(&b),
+                    "seed {seed}: {} and {} aren't grouped together", a.display(), b.display());
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_deeper_than_max_depth() {
+        for seed in SEEDS.take(4) {
+            let tree = Tree::random(seed);
+            for depth in 1..4 {
+                let results = search_with_options(SearchOpts{max_depth: Some(depth), ..opts(tree.root())});
+                check_invariants(&results);
+                let paths = results.duplicates.iter().flatten().map(|x| &x.path)
+                    .chain(results.index.hashes.values().flatten())
+                    .chain(results.errors.iter().filter_map(|x| x.path.as_ref()))
+                    .chain(results.broken.iter().map(|x| &x.path));
+                for path in paths {
+                    assert!(tree.depth(path) <= depth, "seed {seed}: {} is deeper than {depth}", path.display());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn broken_and_corrupt_files_are_never_grouped() {
+        let mut tree = Tree::new();
+        let original = tree.image("a.png", Pattern::Gradient{mirror: false}, (64, 48));
+        tree.copy(&original, "copies/a.png");
+        tree.truncated(&original, "cut.png");
+        tree.truncated(&original, "copies/cut.png");
+        tree.garbage("garbage.jpg", 500);
+        tree.garbage("copies/garbage.jpg", 500);
+
+        let results = search_with_options(opts(tree.root()));
+        check_invariants(&results);
+        let grouped: HashSet<&PathBuf> = results.duplicates.iter().flatten().map(|x| &x.path).collect();
+        assert_eq!(grouped, [original, tree.path("copies/a.png")].iter().collect());
+        assert_eq!(results.broken.len(), 2);
+        assert_eq!(results.errors.len(), 2);
+    }
+
+    #[test]
+    fn canceled_searches_return_promptly_with_nothing() {
+        let mut tree = Tree::new();
+        let original = tree.image("a.png", Pattern::Gradient{mirror: false}, (400, 300));
+        for idx in 0..200 {
+            tree.copy(&original, &format!("{}/{idx}.png", idx % 7));
+        }
+
+        let mut searcher = Searcher::new(opts(tree.root()));
+        let start = Instant::now();
+        searcher.launch_search();
+        searcher.cancel();
+        let results = searcher.wait_for_search().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5), "took {:?}", start.elapsed());
+        assert!(searcher.was_canceled());
+        assert!(results.duplicates.is_empty());
+    }
+}
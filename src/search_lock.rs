@@ -0,0 +1,51 @@
+use crate::misc::data_dir;
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+
+// An advisory lock on a folder being searched, so a second search of the same
+// folder (from another window, or another copy of the app) can warn first.
+// It's an OS file lock, so it's released when the holder exits, however it
+// exits, and there are no stale locks to clean up.
+
+const LOCK_DIR: &str = "locks";
+
+pub struct SearchLock {
+    _file: File, // Locked until dropped
+}
+
+pub enum LockResult {
+    Locked(SearchLock),
+    Busy, // Another search holds it
+}
+
+impl SearchLock {
+    // One lock file per folder, named for its canonical path so different
+    // spellings of the same folder share it. FNV-1a, rather than std's
+    // hasher, so every build of the app agrees on the name.
+    fn path(folder: &Path) -> Result<PathBuf, String> {
+        let folder = folder.canonicalize().unwrap_or_else(|_| folder.to_path_buf());
+        let hash = folder.as_os_str().as_encoded_bytes().iter()
+            .fold(0xcbf29ce484222325u64, |hash, x| (hash ^ *x as u64).wrapping_mul(0x100000001b3));
+        let dir = data_dir()?.join(LOCK_DIR);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Error creating {}: {e}", dir.display()))?;
+        Ok(dir.join(format!("{hash:016x}.lock")))
+    }
+
+    pub fn acquire(folder: &Path) -> Result<LockResult, String> {
+        let path = Self::path(folder)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("Error opening {}: {e}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => Ok(LockResult::Locked(SearchLock{_file: file})),
+            Err(TryLockError::WouldBlock) => Ok(LockResult::Busy),
+            Err(TryLockError::Error(e)) => Err(format!("Error locking {}: {e}", path.display())),
+        }
+    }
+}
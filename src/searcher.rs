@@ -1,17 +1,30 @@
-use crate::misc::Image;
+use crate::misc::{Image, Side, Semaphore};
+use crate::borders;
+use crate::checkpoint::{Checkpoint, CheckpointFile};
+use crate::session::to_secs;
+use crate::crop_match::{self, CropSig};
+use crate::content::ContentKind;
+use crate::grouping::{self, Grouping};
+use crate::sniff::{self, FileType};
+use crate::libraries::{Libraries, LibraryReport};
+use crate::search_events::{EventSink, SearchEvent, SearchSummary, SkipReason, Stage};
+use crate::broken::{self, BrokenImage, BrokenKind};
+use crate::sources::{self, DecodeRequest, Family};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::thread;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, AtomicU64, Ordering};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::thread::JoinHandle;
+use std::hash::Hasher as _;
+use std::time::{Duration, Instant, SystemTime};
 
-use walkdir::WalkDir;
+use ignore::WalkBuilder;
 
-use image_hasher::HasherConfig;
+use image_hasher::{HasherConfig, Hasher, ImageHash};
 
-use maplit::hashset;
+use image::DynamicImage;
 
 use lazy_static::lazy_static;
 
@@ -22,40 +35,735 @@ use dashmap::{DashMap, DashSet};
 use image_hasher::HashAlg;
 
 
+// What cameras and phones produce, and what photos tend to get converted to.
+pub const COMMON_PHOTO_FAMILIES: &[&str] = &["JPEG", "PNG", "TIFF", "WebP"];
+
 lazy_static! {
-    pub static ref SUPPORTED_EXTS: HashSet<&'static str> = hashset!{
-        "jpg",
-        "jpeg",
-        "avif",
-        "avif",
-        "bmp",
-        "dds",
-        "exr",
-        "gif",
-        "hdr",
-        "ico",
-        "png",
-        "pnm",
-        "qoi",
-        "tga",
-        "tif",
-        "tiff",
-        "webp",
-    };
+    // Every supported extension, grouped by format, in the order they're
+    // offered: whatever the sources compiled in read.
+    pub static ref FORMAT_FAMILIES: Vec<Family> = sources::all().iter()
+        .flat_map(|x| x.families().iter().copied())
+        .collect();
+
+    pub static ref SUPPORTED_EXTS: HashSet<&'static str> = FORMAT_FAMILIES.iter()
+        .flat_map(|(_, exts)| exts.iter().copied())
+        .collect();
+}
+
+// Searches that keep a checkpoint all keep it in the same file, so the app
+// only runs one at a time; see search_running(). Counts those launched, and
+// those that have ended, however they ended.
+static CHECKPOINTED_LAUNCHED: AtomicUsize = AtomicUsize::new(0);
+static CHECKPOINTED_ENDED: AtomicUsize = AtomicUsize::new(0);
+
+// Whether a search that keeps a checkpoint is running, in any tab.
+pub fn search_running() -> bool {
+    CHECKPOINTED_ENDED.load(Ordering::SeqCst) != CHECKPOINTED_LAUNCHED.load(Ordering::SeqCst)
+}
+
+// How many searches that keep a checkpoint have ended, so a checkpoint read
+// before one did can be read again.
+pub fn searches_ended() -> usize {
+    CHECKPOINTED_ENDED.load(Ordering::SeqCst)
+}
+
+// Counts its search as ended when dropped, which it is when the search thread
+// exits, panicking or not.
+struct CheckpointedSearch;
+
+impl CheckpointedSearch {
+    fn launch() -> CheckpointedSearch {
+        CHECKPOINTED_LAUNCHED.fetch_add(1, Ordering::SeqCst);
+        CheckpointedSearch
+    }
+}
+
+impl Drop for CheckpointedSearch {
+    fn drop(&mut self) {
+        CHECKPOINTED_ENDED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ErrorKind {
+    Walk, // Traversing the directory tree, including reading ignore files
+    Io, // Opening or reading a file
+    Decode, // The image crate couldn't make sense of the file
+    Panic, // The decoder panicked
+    TooLarge, // Skipped for having more pixels than the cap allows
+    OverBudget, // Skipped for needing more memory to decode than the budget allows
+    Hook, // The post-search hook failed on a group; see hook
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 7] = [
+        ErrorKind::Walk,
+        ErrorKind::Io,
+        ErrorKind::Decode,
+        ErrorKind::Panic,
+        ErrorKind::TooLarge,
+        ErrorKind::OverBudget,
+        ErrorKind::Hook,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorKind::Walk => tr!("Walk"),
+            ErrorKind::Io => tr!("I/O"),
+            ErrorKind::Decode => tr!("Decode"),
+            ErrorKind::Panic => tr!("Panic"),
+            ErrorKind::TooLarge => tr!("Too large"),
+            ErrorKind::OverBudget => tr!("Over memory budget"),
+            ErrorKind::Hook => tr!("Hook"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SearchError {
+    pub kind: ErrorKind,
+    pub path: Option<PathBuf>, // The file involved, if there was a specific one
+    pub msg: String, // Complete, human-readable description
+}
+
+impl SearchError {
+    pub fn new(kind: ErrorKind, path: Option<PathBuf>, msg: String) -> SearchError {
+        SearchError{kind, path, msg}
+    }
+
+    // Whether trying the same file again could plausibly succeed (e.g., a flaky
+    // network share).
+    pub fn retryable(&self) -> bool {
+        self.path.is_some() && matches!(self.kind, ErrorKind::Io | ErrorKind::Decode | ErrorKind::Panic)
+    }
+}
+
+impl std::fmt::Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+// Side length, in cells, of the grid images are hashed on (image_hasher's
+// default). Recorded with anything kept between runs alongside the algorithm,
+// since hashes of different sizes can't be compared either.
+pub const HASH_SIZE: u32 = 8;
+
+pub fn hasher(alg: HashAlg) -> Hasher {
+    HasherConfig::new().hash_alg(alg).hash_size(HASH_SIZE, HASH_SIZE).to_hasher()
+}
+
+// Every file successfully hashed, duplicated or not, so later additions (e.g.,
+// retries) can be matched against the whole search. Tagged with the algorithm
+// everything in it was hashed with, and anything added to it is hashed the
+// same way, so hashes that can't be compared never end up side by side.
+#[derive(Clone)]
+pub struct HashIndex {
+    pub alg: HashAlg,
+    pub hashes: HashMap<ImageHash, Vec<PathBuf>>,
+}
+
+impl HashIndex {
+    pub fn new(alg: HashAlg) -> HashIndex {
+        HashIndex{alg, hashes: HashMap::new()}
+    }
+}
+
+// Counts of what the search looked at and why files were skipped.
+#[derive(Clone, Default, Debug)]
+pub struct SearchStats {
+    pub files_seen: usize, // Everything but directories
+    pub dirs_visited: usize,
+    pub skipped_no_ext: usize,
+    pub skipped_by_ext: BTreeMap<String, usize>, // Extension (lowercase) -> count
+    pub sniffed: usize, // Included despite their extension because of their contents
+    pub decode_failures: usize,
+    pub decodes_saved: usize, // Files that were byte-identical copies of one already decoded
+    pub files_hashed: usize,
+    pub bytes_hashed: u64,
+    pub color_rejected: usize, // Pairs with the same hash split apart by color verification
+    pub skipped_too_large: usize,
+    pub skipped_over_budget: usize, // Would need more memory to decode than the budget allows
+    pub crop_matches: usize, // Pairs found by crop detection
+    pub skipped_by_date: usize, // Modified outside the date range
+    pub unknown_mtime: usize, // Kept despite the date range, since their times couldn't be read
+    pub not_searched: Option<usize>, // Images left unhashed, if the search was stopped early
+    pub mismatched: Vec<(PathBuf, FileType)>, // Files whose contents aren't what their extension says, by path
+    pub broken: BTreeMap<BrokenKind, usize>, // See broken
+    pub libraries: Vec<LibraryReport>, // Photo managers' libraries found, and what was left out of them
+    pub elapsed: Duration, // Wall time of the whole search
+    pub finalize_time: Duration, // Wall time of loading the groups, once they're known
+    // Time spent in each stage, summed across threads, so only meaningful
+    // relative to each other.
+    pub walk_time: Duration, // Listing directories
+    pub decode_time: Duration, // Reading and decoding files
+    pub hash_time: Duration, // Hashing, and computing signatures
+}
+
+impl SearchStats {
+    // If the search was stopped early, the percentage of the images it found
+    // that it hashed. Images it failed to read count as neither.
+    pub fn coverage(&self) -> Option<f64> {
+        let not_searched = self.not_searched?;
+        let total = self.files_hashed + not_searched;
+        Some(if total == 0 { 100.0 } else { 100.0 * self.files_hashed as f64 / total as f64 })
+    }
+
+    // Each stage's share of the time spent in all three, as percentages.
+    pub fn stage_shares(&self) -> Option<[(&'static str, f64); 3]> {
+        let total = (self.walk_time + self.decode_time + self.hash_time).as_secs_f64();
+        if total == 0.0 {
+            return None;
+        }
+        let share = |x: Duration| 100.0 * x.as_secs_f64() / total;
+        Some([
+            ("decode", share(self.decode_time)),
+            ("hash", share(self.hash_time)),
+            ("walk", share(self.walk_time)),
+        ])
+    }
+}
+
+// The live counterpart of SearchStats, updated from the worker threads. All
+// accesses are Relaxed; besides the rough progress counts, they're only read
+// once the workers are done.
+#[derive(Default)]
+struct StatCounters {
+    files_seen: AtomicUsize,
+    found: AtomicUsize, // Images the walk's queued for hashing, for progress
+    dirs_visited: AtomicUsize,
+    skipped_no_ext: AtomicUsize,
+    skipped_by_ext: DashMap<String, usize>,
+    sniffed: AtomicUsize,
+    decode_failures: AtomicUsize,
+    decodes_saved: AtomicUsize,
+    files_hashed: AtomicUsize,
+    bytes_hashed: AtomicU64,
+    color_rejected: AtomicUsize,
+    skipped_too_large: AtomicUsize,
+    skipped_over_budget: AtomicUsize,
+    crop_matches: AtomicUsize,
+    skipped_by_date: AtomicUsize,
+    unknown_mtime: AtomicUsize,
+    not_searched: AtomicUsize,
+    mismatched: DashMap<PathBuf, FileType>,
+    broken: DashMap<BrokenKind, usize>,
+    walk_nanos: AtomicU64,
+    decode_nanos: AtomicU64,
+    hash_nanos: AtomicU64,
+}
+
+impl StatCounters {
+    fn inc(counter: &AtomicUsize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn skip_by_ext(&self, path: &Path) {
+        match path.extension() {
+            Some(ext) => *self.skipped_by_ext.entry(ext.to_string_lossy().to_lowercase()).or_default() += 1,
+            None => Self::inc(&self.skipped_no_ext),
+        }
+    }
+
+    // Adds the time since start. Only called around whole stages of a file's
+    // processing, so the overhead is a clock read and an add per stage.
+    fn add_time(counter: &AtomicU64, start: Instant) {
+        counter.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> SearchStats {
+        let mut mismatched: Vec<_> = self.mismatched.iter().map(|x| (x.key().clone(), *x.value())).collect();
+        mismatched.sort_by(|a, b| a.0.cmp(&b.0));
+        SearchStats {
+            files_seen: self.files_seen.load(Ordering::Relaxed),
+            dirs_visited: self.dirs_visited.load(Ordering::Relaxed),
+            skipped_no_ext: self.skipped_no_ext.load(Ordering::Relaxed),
+            skipped_by_ext: self.skipped_by_ext.iter().map(|x| (x.key().clone(), *x.value())).collect(),
+            sniffed: self.sniffed.load(Ordering::Relaxed),
+            decode_failures: self.decode_failures.load(Ordering::Relaxed),
+            decodes_saved: self.decodes_saved.load(Ordering::Relaxed),
+            files_hashed: self.files_hashed.load(Ordering::Relaxed),
+            bytes_hashed: self.bytes_hashed.load(Ordering::Relaxed),
+            color_rejected: self.color_rejected.load(Ordering::Relaxed),
+            skipped_too_large: self.skipped_too_large.load(Ordering::Relaxed),
+            skipped_over_budget: self.skipped_over_budget.load(Ordering::Relaxed),
+            crop_matches: self.crop_matches.load(Ordering::Relaxed),
+            skipped_by_date: self.skipped_by_date.load(Ordering::Relaxed),
+            unknown_mtime: self.unknown_mtime.load(Ordering::Relaxed),
+            not_searched: None,
+            mismatched,
+            broken: self.broken.iter().map(|x| (*x.key(), *x.value())).collect(),
+            libraries: vec![],
+            elapsed: Duration::ZERO,
+            finalize_time: Duration::ZERO,
+            walk_time: Duration::from_nanos(self.walk_nanos.load(Ordering::Relaxed)),
+            decode_time: Duration::from_nanos(self.decode_nanos.load(Ordering::Relaxed)),
+            hash_time: Duration::from_nanos(self.hash_nanos.load(Ordering::Relaxed)),
+        }
+    }
 }
 
 pub struct SearchResults {
     pub duplicates: Vec<Vec<Image>>,
-    pub errors: Vec<String>,
+    pub errors: Vec<SearchError>,
+    pub broken: Vec<BrokenImage>, // By path; ones that decoded are hashed, and can be in duplicates too
+    pub index: HashIndex,
+    pub stats: Option<SearchStats>, // None if there was no search, e.g. restored results
 }
 
 impl SearchResults {
-    fn empty() -> SearchResults {
+    fn empty(alg: HashAlg) -> SearchResults {
         SearchResults {
             duplicates: vec![],
             errors: vec![],
+            broken: vec![],
+            index: HashIndex::new(alg),
+            stats: None,
+        }
+    }
+
+    // Results without a search behind them, e.g. restored from a session,
+    // whose images were hashed with the given algorithm.
+    pub fn restored(duplicates: Vec<Vec<Image>>, alg: HashAlg) -> SearchResults {
+        SearchResults{duplicates, ..SearchResults::empty(alg)}
+    }
+}
+
+// Reads the whole file. If given permits, holds one for the duration of the
+// read (but not the decode), so slow disks aren't thrashed by competing reads
+// while decoding still runs at full parallelism.
+fn read_file(path: &Path, permits: Option<&Semaphore>) -> Result<Vec<u8>, SearchError> {
+    let _permit = permits.map(Semaphore::acquire);
+    std::fs::read(path).map_err(|e| SearchError::new(
+        ErrorKind::Io,
+        Some(path.to_path_buf()),
+        format!("Error reading image {}: {e}", path.display()),
+    ))
+}
+
+// What to do with images with more pixels than are worth decoding in full,
+// and with images that would take more memory to decode than can be spared.
+#[derive(Clone, Copy, Debug)]
+pub struct SizeCap {
+    pub max_pixels: u64,
+    pub skip: bool, // Skip them, rather than hashing a downscaled copy
+    pub max_bytes: u64, // Memory budget for decoding any one image
+    pub reduced_jpeg: bool, // Decode JPEGs at a fraction of their size, which hashes the same; see decode()
+}
+
+impl SizeCap {
+    pub const DEFAULT_MAX_PIXELS: u64 = 40_000_000;
+
+    // The longest side a reduced JPEG decode asks for. The hashes only look
+    // at a few dozen pixels a side, and the decoder only scales by powers of
+    // two, so what comes out is between this and twice it.
+    const REDUCED_SIDE: u32 = 256;
+
+    // Past this, it's a corrupt or malicious header rather than a real image,
+    // and is skipped regardless.
+    const HARD_MAX_PIXELS: u64 = 1_000_000_000;
+
+    // Bounds on the automatic memory budget, so a machine that's short on
+    // memory at launch can still decode ordinary photos, and one with plenty
+    // doesn't spend it all on one image.
+    const MIN_AUTO_MAX_BYTES: u64 = 512 * 1024 * 1024;
+    const MAX_AUTO_MAX_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+    // A quarter of the memory available at launch, within the bounds above.
+    // Several images decode at once, and everything else still needs room.
+    pub fn auto_max_bytes() -> u64 {
+        (*AVAILABLE_MEMORY / 4).clamp(Self::MIN_AUTO_MAX_BYTES, Self::MAX_AUTO_MAX_BYTES)
+    }
+
+    // For hashing with the algorithm. Blockhash sums the whole image in
+    // blocks, which don't line up the same at another size, so its hashes
+    // drift a few bits when reduced; it always gets the full decode.
+    pub fn for_hash(self, alg: HashAlg) -> SizeCap {
+        SizeCap{reduced_jpeg: self.reduced_jpeg && alg != HashAlg::Blockhash, ..self}
+    }
+}
+
+impl Default for SizeCap {
+    fn default() -> Self {
+        SizeCap {
+            max_pixels: Self::DEFAULT_MAX_PIXELS,
+            skip: false,
+            max_bytes: Self::auto_max_bytes(),
+            reduced_jpeg: true,
+        }
+    }
+}
+
+lazy_static! {
+    // Memory available when first asked; 0 if it can't be determined, which
+    // leaves the budget at its minimum.
+    static ref AVAILABLE_MEMORY: u64 = {
+        let mut system = sysinfo::System::new();
+        system.refresh_memory();
+        system.available_memory()
+    };
+
+    // Bounds the memory all the images being decoded at once take, in MiB,
+    // across every search. Half of what was available at launch, but always
+    // enough for one image at the minimum budget.
+    static ref DECODE_PERMITS: Semaphore = Semaphore::new(
+        ((*AVAILABLE_MEMORY / 2).max(SizeCap::MIN_AUTO_MAX_BYTES) / MIB) as usize,
+    );
+}
+
+const MIB: u64 = 1024 * 1024;
+
+pub enum Decoded {
+    Image(DynamicImage),
+    Animation{sampled: Vec<DynamicImage>, count: usize}, // See sources::decode_frames()
+    TooLarge(u32, u32), // Dimensions
+    OverBudget(u64), // Bytes it would need
+}
+
+// Checks the dimensions in the header before decoding anything. Images over
+// the cap come out scaled down to fit it, which hashes the same; sources that
+// can scale while decoding (JPEGs) do, which is much faster and never needs
+// the full size in memory. With cap.reduced_jpeg, everything from such a
+// source is decoded that way, down to about SizeCap::REDUCED_SIDE, since
+// that's where most of a scan's time goes and the hashes don't need more.
+// Anything else that would need more memory than the budget is skipped, and
+// the decoders are held to the budget in case the estimate's low. While
+// decoding, the estimate is held against DECODE_PERMITS, so a folder of huge
+// TIFFs decodes a few at a time.
+fn decode(path: &Path, buffer: &[u8], cap: SizeCap) -> image::ImageResult<Decoded> {
+    let source = sources::for_file(path, buffer);
+    let (width, height) = source.dimensions(path, buffer)?;
+    let pixels = width as u64 * height as u64;
+
+    let oversized = pixels > cap.max_pixels;
+    if oversized && (cap.skip || pixels > SizeCap::HARD_MAX_PIXELS) {
+        return Ok(Decoded::TooLarge(width, height));
+    }
+    let scale = (cap.max_pixels as f64 / pixels as f64).sqrt().min(1.0);
+    let target = (
+        ((width as f64 * scale) as u32).max(1),
+        ((height as f64 * scale) as u32).max(1),
+    );
+    let reduced = cap.reduced_jpeg && source.scales() && width.max(height) > SizeCap::REDUCED_SIDE;
+    let request = match reduced {
+        true => {
+            let scale = SizeCap::REDUCED_SIDE as f64 / width.max(height) as f64;
+            (
+                (((width as f64 * scale) as u32).max(1)).min(target.0),
+                (((height as f64 * scale) as u32).max(1)).min(target.1),
+            )
+        },
+        false => target,
+    };
+    let scaled = source.scales() && (oversized || reduced);
+    let decoded_pixels = if scaled { request.0 as u64 * request.1 as u64 } else { pixels };
+    let needed = source.bytes_per_pixel(path, buffer).saturating_mul(decoded_pixels);
+    if needed > cap.max_bytes {
+        return Ok(Decoded::OverBudget(needed));
+    }
+    let _permits = DECODE_PERMITS.acquire_weighted(needed.div_ceil(MIB) as usize);
+
+    let size = (oversized || reduced).then_some(request);
+    let decoded = source.decode_for_hash(path, buffer, DecodeRequest{size, max_bytes: cap.max_bytes})?;
+    match decoded {
+        Decoded::Image(image) if oversized && !reduced => Ok(Decoded::Image(image.thumbnail(target.0, target.1))),
+        decoded => Ok(decoded), // Already within the cap
+    }
+}
+
+// Whether the file's header says it's one of the given extensions' formats.
+// Any problem reading it just means "no".
+fn sniff_matches(path: &Path, exts: &HashSet<String>) -> bool {
+    let Ok(header) = sniff::read_header(path) else {
+        return false;
+    };
+    sources::sniff(&header).is_some_and(|(_, family)| family.iter().any(|x| exts.contains(*x)))
+}
+
+// A decoded file. Animations keep a few of their frames, so they can be
+// compared as a whole rather than by their first frame alone.
+pub struct Opened {
+    pub image: DynamicImage, // The first frame, for an animation
+    sampled: Vec<DynamicImage>, // Including the first; empty if not animated
+    pub frame_count: usize,
+}
+
+impl Opened {
+    // An animation's hash is its sampled frames' hashes end to end. Being
+    // longer, it never matches a still image, even one of its own frames.
+    // With trim_borders, a still is hashed without its borders, and then with
+    // center under 100, only that percent of its middle; see borders.
+    pub fn hash(&self, hasher: &Hasher, trim_borders: bool, center: u32) -> ImageHash {
+        if self.sampled.is_empty() {
+            let trimmed = trim_borders.then(|| borders::trim(&self.image)).flatten();
+            let trimmed = trimmed.as_ref().unwrap_or(&self.image);
+            return match borders::central(trimmed, center) {
+                Some(middle) => hasher.hash_image(&middle),
+                None => hasher.hash_image(trimmed),
+            };
+        }
+        let bytes: Vec<u8> = self.sampled.iter()
+            .flat_map(|x| hasher.hash_image(x).as_bytes().to_vec())
+            .collect();
+        ImageHash::from_bytes(&bytes).expect("hash bytes are never empty")
+    }
+}
+
+pub fn open_image(path: &Path, permits: Option<&Semaphore>, cap: SizeCap) -> Result<Opened, SearchError> {
+    open_buffer(path, &read_file(path, permits)?, cap)
+}
+
+// The file's already been read into buffer. I have seen image::open() panic
+// on (presumably) malformed files.
+fn open_buffer(path: &Path, buffer: &[u8], cap: SizeCap) -> Result<Opened, SearchError> {
+    let opened = match std::panic::catch_unwind(|| decode(path, buffer, cap)) {
+        Ok(Ok(Decoded::Image(image))) => Opened{image, sampled: vec![], frame_count: 1},
+        Ok(Ok(Decoded::Animation{sampled, count})) => Opened{
+            image: sampled[0].clone(),
+            sampled,
+            frame_count: count,
+        },
+        Ok(Ok(Decoded::OverBudget(needed))) => return Err(SearchError::new(
+            ErrorKind::OverBudget,
+            Some(path.to_path_buf()),
+            format!(
+                "Skipped {}: too large for the memory budget (needs ~{})",
+                path.display(),
+                crate::i18n::format_size(needed),
+            ),
+        )),
+        Ok(Err(image::ImageError::Limits(_))) => return Err(SearchError::new(
+            ErrorKind::OverBudget,
+            Some(path.to_path_buf()),
+            format!("Skipped {}: too large for the memory budget", path.display()),
+        )),
+        Ok(Ok(Decoded::TooLarge(width, height))) => return Err(SearchError::new(
+            ErrorKind::TooLarge,
+            Some(path.to_path_buf()),
+            format!("Skipped {}: {width}×{height} exceeds the size cap", path.display()),
+        )),
+        Err(_) => return Err(SearchError::new(
+            ErrorKind::Panic,
+            Some(path.to_path_buf()),
+            format!("Panic opening image {}", path.display()),
+        )),
+        Ok(Err(e)) => {
+            let kind = match e {
+                image::ImageError::IoError(_) => ErrorKind::Io,
+                _ => ErrorKind::Decode,
+            };
+            return Err(SearchError::new(
+                kind,
+                Some(path.to_path_buf()),
+                format!("Error opening image {}: {e}", path.display()),
+            ));
+        },
+    };
+
+    Ok(opened)
+}
+
+// Identifies a file's exact contents, along with its size, so byte-identical
+// copies are only decoded once. Files that match are taken to be copies
+// without comparing their bytes, so it's 128 bits, from two SipHashes told
+// apart by what's hashed ahead of the contents. SipHash rather than anything
+// faster, as it's in std, and still far quicker than decoding what it's
+// hashed.
+fn content_hash(buffer: &[u8]) -> u128 {
+    let half = |salt: u8| {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        hasher.write_u8(salt);
+        hasher.write(buffer);
+        hasher.finish() as u128
+    };
+    half(0) << 64 | half(1)
+}
+
+// Everything the search works out from a file's contents, which is the same
+// for each byte-identical copy of it. What depends on its name too is worked
+// out per file.
+struct Analysis {
+    hash: ImageHash,
+    frame_count: usize,
+    color: Option<ColorSig>, // Only with verify_color
+    crop: Option<CropSig>, // Only with detect_crops
+    content: ContentKind, // From the pixels; see ContentKind::with_format()
+    dimensions: (u32, u32),
+    broken: Option<BrokenKind>, // Decoded, but only to a flat image or part of one
+}
+
+// A content's analysis, once its first file's is done, if it could be.
+type SharedAnalysis = Arc<OnceLock<Option<Arc<Analysis>>>>;
+
+// The hash and frame count.
+fn hash_file(hasher: &Hasher, path: &Path, cap: SizeCap, trim_borders: bool, center: u32) -> Result<(ImageHash, usize), SearchError> {
+    open_image(path, None, cap).map(|x| (x.hash(hasher, trim_borders, center), x.frame_count))
+}
+
+// A tiny RGB thumbnail, used to double check that images with the same
+// perceptual hash (which only looks at luminance structure) actually look
+// alike.
+#[derive(Clone)]
+pub struct ColorSig([u8; ColorSig::LEN]);
+
+impl ColorSig {
+    const SIDE: u32 = 4;
+    const LEN: usize = (Self::SIDE * Self::SIDE * 3) as usize;
+
+    fn from_bytes(bytes: &[u8]) -> Option<ColorSig> {
+        Some(ColorSig(bytes.try_into().ok()?))
+    }
+
+    // Mean per-channel difference above which two images aren't considered
+    // the same. Eyeballed: re-encodes and resizes land well under it, while
+    // different photos with similar composition land well over.
+    const THRESHOLD: u32 = 40;
+
+    fn new(image: &DynamicImage) -> ColorSig {
+        let thumb = image.thumbnail_exact(Self::SIDE, Self::SIDE).to_rgb8();
+        let mut sig = [0; Self::LEN];
+        sig.copy_from_slice(thumb.as_raw());
+        ColorSig(sig)
+    }
+
+    pub fn matches(&self, other: &ColorSig) -> bool {
+        let total: u32 = self.0.iter().zip(other.0.iter())
+            .map(|(a, b)| a.abs_diff(*b) as u32)
+            .sum();
+        total / Self::LEN as u32 <= Self::THRESHOLD
+    }
+}
+
+// Splits paths with the same hash into clusters that also match by color.
+// Each path joins the first cluster whose first member it matches. Returns the
+// clusters and the number of pairs that were split apart.
+pub fn split_by_color(paths: Vec<PathBuf>, sig_of: impl Fn(&Path) -> Option<ColorSig>) -> (Vec<Vec<PathBuf>>, usize) {
+    let total = paths.len();
+    let mut clusters: Vec<(Option<ColorSig>, Vec<PathBuf>)> = vec![];
+    for path in paths {
+        let sig = sig_of(&path);
+        let existing = clusters.iter_mut().find(|(rep, _)| match (rep, &sig) {
+            (Some(a), Some(b)) => a.matches(b),
+            _ => true, // Nothing to compare against, so trust the hash
+        });
+        match existing {
+            Some((_, members)) => members.push(path),
+            None => clusters.push((sig, vec![path])),
         }
     }
+
+    let pairs = |n: usize| n * n.saturating_sub(1) / 2;
+    let kept: usize = clusters.iter().map(|(_, x)| pairs(x.len())).sum();
+    (clusters.into_iter().map(|(_, x)| x).collect(), pairs(total) - kept)
+}
+
+pub fn load_image(path: PathBuf, hash: ImageHash, frame_count: usize) -> Result<Image, SearchError> {
+    let mut image = Image::load(path.clone())
+        .map_err(|e| SearchError::new(ErrorKind::Io, Some(path), e))?;
+    image.hash = Some(hash);
+    image.frame_count = frame_count;
+    Ok(image)
+}
+
+// Which files' modification times are of interest. Both ends are inclusive,
+// and either can be open.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DateRange {
+    pub after: Option<SystemTime>,
+    pub before: Option<SystemTime>,
+    // Rather than leaving files outside the range out entirely, report any
+    // group with at least one member inside it, e.g. a new file along with
+    // the old copy it duplicates.
+    pub any_member: bool,
+}
+
+impl DateRange {
+    // Unknown times count as in range, since there's no telling otherwise.
+    fn contains(&self, time: Option<SystemTime>) -> bool {
+        let Some(time) = time else {
+            return true;
+        };
+        self.after.is_none_or(|x| time >= x) && self.before.is_none_or(|x| time <= x)
+    }
+}
+
+// Everything that determines what a search does, already validated.
+pub struct SearchOpts {
+    pub root: PathBuf,
+    pub subfolders: Vec<PathBuf>, // Only walk these, under root; empty for all of it
+    pub hash: HashAlg,
+    pub follow_sym: bool,
+    pub max_depth: Option<usize>,
+    pub exts: HashSet<String>, // Extentions to consider
+    pub respect_ignore: bool, // Honor .gitignore and .ignore files
+    pub reference: Option<PathBuf>, // In compare mode, the folder root's contents are checked against
+    pub io_threads: usize, // Max files being read at once, independent of the CPU thread count
+    pub queue_size: usize, // Max files the walk can find ahead of hashing; see SearcherInner::search()
+    pub sniff: bool, // Check the contents of files with other (or no) extensions
+    pub max_entries: Option<usize>, // Stop after visiting this many entries, as a safety net
+    pub verify_color: bool, // Split hash groups whose members' colors differ
+    pub size_cap: SizeCap,
+    pub detect_crops: bool, // Also look for images that are crops of others; see crop_match
+    pub trim_borders: bool, // Hash images without uniform borders; see borders
+    pub center: u32, // Percent of each image's width and height hashed, from the middle; 100 for all of it
+    pub checkpoint: bool, // Periodically save progress, so the search can be resumed
+    pub resume: Option<Checkpoint>, // Files already hashed; must match fingerprint()
+    pub modified: Option<DateRange>, // Only files modified in this range count
+    pub max_distance: u32, // Hashes this many bits apart still match; 0 for exact matches
+    pub grouping: Grouping, // How near matches form groups; see grouping
+    pub scan_libraries: bool, // Also search photo libraries' own previews and thumbnails; see libraries
+}
+
+impl SearchOpts {
+    // Plenty to keep every thread busy, and little memory even for long
+    // paths.
+    pub const DEFAULT_QUEUE_SIZE: usize = 4096;
+
+    // Identifies the options that affect which files are hashed and how, so a
+    // checkpoint is only resumed by a search that would have produced it.
+    pub fn fingerprint(&self) -> String {
+        let mut exts: Vec<_> = self.exts.iter().collect();
+        exts.sort();
+        format!(
+            "{:?}",
+            (
+                (&self.root, &self.subfolders),
+                &self.reference,
+                self.hash,
+                self.follow_sym,
+                self.max_depth,
+                exts,
+                self.respect_ignore,
+                self.sniff,
+                self.max_entries,
+                self.verify_color,
+                // Not the memory budget, which can differ between runs by
+                // itself; files it skipped were never checkpointed anyway.
+                (self.size_cap.max_pixels, self.size_cap.skip, self.size_cap.reduced_jpeg),
+                (self.detect_crops, self.modified, self.trim_borders, self.center, self.scan_libraries),
+            ),
+        )
+    }
+}
+
+// A file, as soon as it's hashed, with what the search learned about it along
+// the way; see Searcher::stream().
+pub struct Hashed {
+    pub path: PathBuf,
+    pub hash: ImageHash,
+    pub color: Option<ColorSig>, // Only with verify_color
+    pub frame_count: usize,
+    pub content: Option<ContentKind>,
+    pub in_range: bool, // False only for files outside a date range with any_member
+}
+
+// Whether hashed files are being streamed. It can be asked for mid-search, so
+// the first file hashed after that brings everything hashed before it along.
+enum Stream {
+    Off,
+    Requested(mpsc::Sender<Hashed>),
+    On(mpsc::Sender<Hashed>),
+    Closed, // Hashing's over, so there's nothing (more) to send
 }
 
 // SearcherInner is the actual state state involved in setting up a search and
@@ -64,109 +772,864 @@ impl SearchResults {
 // it could just be moved, but SearcherInner::cancel couldn't be and this
 // simplifies things).
 struct SearcherInner {
-    root: PathBuf,
-    hash: HashAlg,
-    follow_sym: bool,
-    max_depth: Option<usize>,
-    exts: HashSet<String>, // Extentions to consider
-    cancel: AtomicBool,
+    opts: SearchOpts,
+    last_checkpoint: Mutex<Instant>,
+    io_permits: Semaphore, // Bounds concurrent reads; see SearchOpts::io_threads
+    cancel: AtomicBool, // Stop, throwing away the results
+    stop: AtomicBool, // Hash nothing more, but group what's been hashed
+    walked: AtomicBool, // The walk's found everything there is, so the checkpoint knows the total
+    resumed: AtomicUsize, // Files carried over from the checkpoint resumed, which the walk skips
+    finalize: FinalizeProgress,
+    stats: StatCounters, // Live, so progress can be reported while searching
+    stream: Mutex<Stream>,
+    events: Option<EventSink>, // See Searcher::events()
+    #[cfg(test)]
+    hash_hook: Option<fn(&SearcherInner, &Path)>, // Called before hashing each file, e.g. to inject a panic
+    #[cfg(test)]
+    naive: bool, // Decode every copy of a file, to check that sharing changes nothing
+}
+
+// Progress through grouping and loading the duplicates after hashing, which
+// takes a while on a huge library.
+#[derive(Default)]
+struct FinalizeProgress {
+    started: AtomicBool,
+    done: AtomicUsize, // Buckets of files with the same hash
+    total: AtomicUsize,
+}
+
+// A file the walk's found for hashing.
+struct Candidate {
+    entry: ignore::DirEntry,
+    sniff: bool, // Its extension isn't one being searched for, so it's only one if its contents say so
+}
+
+// If the walk error is a symlink loop, the ancestor the link points back to.
+fn loop_ancestor(err: &ignore::Error) -> Option<(&Path, &Path)> {
+    match err {
+        ignore::Error::Loop{ancestor, child} => Some((ancestor, child)),
+        ignore::Error::WithDepth{err, ..}
+            | ignore::Error::WithPath{err, ..}
+            | ignore::Error::WithLineNumber{err, ..} => loop_ancestor(err),
+        _ => None,
+    }
 }
 
 impl SearcherInner {
 
+    fn new(opts: SearchOpts) -> SearcherInner {
+        SearcherInner {
+            io_permits: Semaphore::new(opts.io_threads.max(1)),
+            opts,
+            last_checkpoint: Mutex::new(Instant::now()),
+            cancel: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+            walked: AtomicBool::new(false),
+            resumed: AtomicUsize::new(0),
+            finalize: FinalizeProgress::default(),
+            stats: StatCounters::default(),
+            stream: Mutex::new(Stream::Off),
+            events: None,
+            #[cfg(test)]
+            hash_hook: None,
+            #[cfg(test)]
+            naive: false,
+        }
+    }
+
+    // Often enough that little is lost if the app is killed, but the whole
+    // checkpoint is rewritten each time, and it grows with the search.
+    const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+    // Most pairs crop detection fully compares, each taking several ms, so
+    // that a huge library with a lot of similar images doesn't take forever.
+    const CROP_PAIR_BUDGET: usize = 10_000;
+
+    // In compare mode, which folder the path came from. The folders can't
+    // overlap, so a prefix check suffices.
+    fn side(&self, path: &Path) -> Option<Side> {
+        let reference = self.opts.reference.as_ref()?;
+        Some(if path.starts_with(reference) { Side::Reference } else { Side::Candidate })
+    }
+
+    // With the total, once the walk's found everything; files resumed from
+    // the last checkpoint aren't counted as found again.
+    fn save_checkpoint(&self, done: &DashMap<PathBuf, CheckpointFile>) -> Result<(), SearchError> {
+        let files = done.iter().map(|x| x.value().clone()).collect();
+        let total = self.walked.load(Ordering::Relaxed)
+            .then(|| self.stats.found.load(Ordering::Relaxed) + self.resumed.load(Ordering::Relaxed));
+        Checkpoint::new(self.opts.fingerprint(), self.opts.root.clone(), files, total)
+            .save()
+            .map_err(|e| SearchError::new(ErrorKind::Io, None, e))
+    }
+
+    // Saves a checkpoint if it's been long enough since the last one. Only one
+    // thread writes at a time; the rest carry on hashing.
+    fn maybe_save_checkpoint(&self, done: &DashMap<PathBuf, CheckpointFile>) -> Result<(), SearchError> {
+        let Ok(mut last) = self.last_checkpoint.try_lock() else {
+            return Ok(());
+        };
+        if last.elapsed() < Self::CHECKPOINT_INTERVAL {
+            return Ok(());
+        }
+        *last = Instant::now();
+        self.save_checkpoint(done)
+    }
+
+    // Whether byte-identical copies share a single decode. Only ever not in
+    // tests.
+    fn share_copies(&self) -> bool {
+        #[cfg(test)]
+        return !self.naive;
+        #[cfg(not(test))]
+        true
+    }
+
+    // Builds the event only if someone's listening, so it costs nothing
+    // otherwise.
+    fn emit(&self, event: impl FnOnce() -> SearchEvent) {
+        if let Some(sink) = &self.events {
+            sink.send(event());
+        }
+    }
+
+    fn finish_events(&self, groups: usize, errors: usize, canceled: bool) {
+        if let Some(sink) = &self.events {
+            let files_hashed = self.stats.files_hashed.load(Ordering::Relaxed);
+            sink.finish(SearchSummary{groups, files_hashed, errors, canceled, dropped: 0});
+        }
+    }
+
+    // The search was canceled; keeps its progress for next time.
+    fn canceled(&self, done: &DashMap<PathBuf, CheckpointFile>, errors: &DashSet<SearchError>) -> SearchResults {
+        *self.stream.lock().unwrap() = Stream::Closed;
+        if self.opts.checkpoint {
+            let _ = self.save_checkpoint(done);
+        }
+        self.finish_events(0, errors.len(), true);
+        SearchResults::empty(self.opts.hash)
+    }
+
     fn search(&self) -> SearchResults {
+        let start = Instant::now();
         let map = DashMap::new();
         let errors = DashSet::new();
+        let stats = &self.stats;
+
+        // Symlink loops are only reported once per ancestor, no matter how
+        // many links lead back to it: ancestor -> (a link, times found).
+        let loops: DashMap<PathBuf, (PathBuf, usize)> = DashMap::new();
+        let entries = AtomicUsize::new(0);
+        let cap_hit = AtomicBool::new(false);
+        let colors = DashMap::new(); // Only populated with verify_color
+        let frame_counts = DashMap::new(); // Only animations
+        let contents = DashMap::new();
+        let done = DashMap::new(); // Only populated with checkpoint
+        // Only populated with detect_crops. Not checkpointed, so a resumed
+        // search only finds crops among the files it hashes itself.
+        let crop_sigs = DashMap::new();
+        // Only populated with a date range with any_member.
+        let out_of_range = DashSet::new();
+        let broken = Mutex::new(vec![]);
+        // Each distinct content read so far, by size and content_hash(), with
+        // what its first file's analysis found, if it got that far. Files
+        // arrive from the walk as it goes, so there's no knowing up front
+        // which sizes collide; every file read is hashed, which costs little
+        // next to decoding it. Copies share the one analysis rather than each
+        // taking a copy of it.
+        let copies: DashMap<(usize, u128), SharedAnalysis> = DashMap::new();
+
+        let report = |e: SearchError| {
+            self.emit(|| SearchEvent::FileError{path: e.path.clone(), kind: e.kind, msg: e.msg.clone()});
+            errors.insert(e);
+        };
+        let skip = |path: &Path, reason: SkipReason| {
+            self.emit(|| SearchEvent::FileSkipped{path: path.to_path_buf(), reason});
+        };
+        let note_broken = |path: &Path, size: u64, kind: BrokenKind| {
+            *stats.broken.entry(kind).or_default() += 1;
+            broken.lock().unwrap().push(BrokenImage{path: path.to_path_buf(), size, kind});
+        };
+
+        // A resumed search starts with everything the checkpoint hashed (that
+        // still exists, unchanged), and skips those files during the walk.
+        // Files changed since are hashed again, like any other.
+        if let Some(resume) = &self.opts.resume {
+            for file in &resume.files {
+                let Ok(hash) = ImageHash::from_base64(&file.hash) else {
+                    continue;
+                };
+                let Ok(meta) = std::fs::metadata(&file.path) else {
+                    continue;
+                };
+                if !file.is_current(&meta) {
+                    continue;
+                }
+                self.resumed.fetch_add(1, Ordering::Relaxed);
+                // It isn't read again, so just its start is, for the scan
+                // details.
+                if let Some(detected) = sniff::read_header(&file.path).ok().and_then(|x| sniff::mismatch(&file.path, &x)) {
+                    stats.mismatched.insert(file.path.clone(), detected);
+                }
+                if let Some(range) = self.opts.modified {
+                    if !range.contains(meta.modified().ok()) {
+                        out_of_range.insert(file.path.clone());
+                    }
+                }
+                if let Some(sig) = file.color.as_deref().and_then(ColorSig::from_bytes) {
+                    colors.insert(file.path.clone(), sig);
+                }
+                if let Some(count) = file.frame_count {
+                    frame_counts.insert(file.path.clone(), count);
+                }
+                if let Some(content) = file.content {
+                    contents.insert(file.path.clone(), content);
+                }
+                StatCounters::inc(&stats.files_hashed);
+                stats.bytes_hashed.fetch_add(file.bytes, Ordering::Relaxed);
+                map.entry(hash).or_insert(DashSet::new()).insert(file.path.clone());
+                done.insert(file.path.clone(), file.clone());
+            }
+        }
+
+        let hashed = |path: &Path, hash: &ImageHash| Hashed {
+            path: path.to_path_buf(),
+            hash: hash.clone(),
+            color: colors.get(path).map(|x| x.clone()),
+            frame_count: frame_counts.get(path).map_or(1, |x| *x),
+            content: contents.get(path).map(|x| *x),
+            in_range: !out_of_range.contains(path),
+        };
+        // Sends a file that's just been hashed to the stream, if there is one,
+        // catching up on everything before it if the stream was only just
+        // asked for. Without a file, it just catches up. Catching up can send
+        // a file twice, if it's hashed meanwhile.
+        let send = |file: Option<(&Path, &ImageHash)>| {
+            let mut stream = self.stream.lock().unwrap();
+            match &*stream {
+                Stream::Off | Stream::Closed => (),
+                Stream::Requested(tx) => {
+                    let tx = tx.clone();
+                    for entry in map.iter() {
+                        for path in entry.value().iter() {
+                            let _ = tx.send(hashed(&path, entry.key()));
+                        }
+                    }
+                    *stream = Stream::On(tx);
+                },
+                Stream::On(tx) => if let Some((path, hash)) = file {
+                    let _ = tx.send(hashed(path, hash));
+                },
+            }
+        };
+
+        let hasher = hasher(self.opts.hash);
+        let cap = self.opts.size_cap.for_hash(self.opts.hash);
 
-        let hasher = HasherConfig::new().hash_alg(self.hash).to_hasher();
-        let mut walker = WalkDir::new(self.root.clone()).follow_links(self.follow_sym);
-        if let Some(d) = self.max_depth {
-            walker = walker.max_depth(d);
+        // Decodes and hashes a file that's been read.
+        let analyze = |path: &Path, buffer: &[u8]| -> Result<Arc<Analysis>, SearchError> {
+            let decode_start = Instant::now();
+            let opened = open_buffer(path, buffer, cap);
+            StatCounters::add_time(&stats.decode_nanos, decode_start);
+            let opened = opened?;
+
+            let hash_start = Instant::now();
+            #[cfg(test)]
+            if let Some(hook) = self.hash_hook {
+                hook(self, path);
+            }
+            let analysis = Analysis {
+                hash: opened.hash(&hasher, self.opts.trim_borders, self.opts.center),
+                frame_count: opened.frame_count,
+                color: self.opts.verify_color.then(|| ColorSig::new(&opened.image)),
+                crop: self.opts.detect_crops.then(|| CropSig::new(&opened.image)),
+                content: ContentKind::from_pixels(&opened.image),
+                dimensions: (opened.image.width(), opened.image.height()),
+                // Not animations, which can start on a flat frame.
+                broken: (opened.frame_count == 1).then(|| broken::check(buffer, Some(&opened.image))).flatten(),
+            };
+            StatCounters::add_time(&stats.hash_nanos, hash_start);
+            Ok(Arc::new(analysis))
+        };
+        // With respect_ignore off, all of the ignore crate's filtering is
+        // disabled, so this walks exactly what a plain WalkDir would (hidden
+        // files included).
+        // Each subfolder is walked as a root of its own, so depth limits count
+        // from it.
+        let mut roots: Vec<&Path> = self.opts.subfolders.iter().map(PathBuf::as_path).collect();
+        if roots.is_empty() {
+            roots.push(&self.opts.root);
+        }
+        roots.extend(self.opts.reference.as_deref());
+        let mut builder = WalkBuilder::new(roots[0]);
+        for root in &roots[1..] {
+            builder.add(root);
         }
+        // Photo libraries' own copies are pruned like ignored folders, so
+        // nothing in them is even listed.
+        let libraries = Arc::new(Libraries::new(&roots, self.opts.scan_libraries));
+        let pruning = libraries.clone();
+        builder.filter_entry(move |entry| !entry.file_type().is_some_and(|x| x.is_dir()) || pruning.allows(entry.path()));
+        let walker = builder
+            .follow_links(self.opts.follow_sym)
+            .max_depth(self.opts.max_depth)
+            .standard_filters(false)
+            .git_ignore(self.opts.respect_ignore)
+            .ignore(self.opts.respect_ignore)
+            .parents(self.opts.respect_ignore)
+            .require_git(false)
+            .build();
 
-        // The lambda returns Err(()) to break out of the iteration if it was
-        // canceled, otherwise Ok(()) is returned, regardless if there was
-        // an error. Actual errors are kept in the error variable and do not
-        // preclude continuing execution.
-        let _: Result<(), ()> = walker.into_iter().par_bridge().map(|entry| {
+        // Timed as it's iterated, which is where the directory reads happen.
+        let mut walker = walker.into_iter();
+        let walker = std::iter::from_fn(|| {
+            let start = Instant::now();
+            let entry = walker.next();
+            StatCounters::add_time(&stats.walk_nanos, start);
+            entry
+        });
 
+        // The walk runs on a thread of its own, queuing the files worth
+        // hashing for the pool. The queue's bounded, so on a huge tree the
+        // walk can only get so far ahead of the hashing, and memory stays flat;
+        // a cancel only waits for the files being hashed. Anything needing a
+        // read to decide on (sniffing, dates) is left to the pool too, so the
+        // walk only lists directories.
+        let (queue, queued) = mpsc::sync_channel::<Candidate>(self.opts.queue_size.max(1));
+        let walk = |queue: mpsc::SyncSender<Candidate>| {
+            self.emit(|| SearchEvent::WalkStarted);
+            for entry in walker {
+                if self.cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                // Following symlinks can, between loops the walker doesn't
+                // catch and sheer fan-out, make for a practically endless
+                // walk, so there has to be a limit.
+                let visited = entries.fetch_add(1, Ordering::Relaxed) + 1;
+                if self.opts.max_entries.is_some_and(|max| visited > max) {
+                    cap_hit.store(true, Ordering::Relaxed);
+                    return;
+                }
+
+                let entry = match entry {
+                    Ok(x) => x,
+                    Err(e) if loop_ancestor(&e).is_some() => {
+                        let (ancestor, child) = loop_ancestor(&e).unwrap();
+                        loops.entry(ancestor.to_path_buf())
+                            .or_insert_with(|| (child.to_path_buf(), 0))
+                            .1 += 1;
+                        continue;
+                    },
+                    Err(e) => {
+                        report(SearchError::new(
+                            ErrorKind::Walk,
+                            None,
+                            format!("Error walking directory: {e}"),
+                        ));
+                        continue;
+                    },
+                };
+
+                // Problems reading an ignore file are reported alongside the
+                // entry they affect, which is still usable.
+                if let Some(e) = entry.error() {
+                    report(SearchError::new(
+                        ErrorKind::Walk,
+                        Some(entry.path().to_path_buf()),
+                        format!("Error reading ignore file: {e}"),
+                    ));
+                }
+
+                if entry.file_type().is_none_or(|x| x.is_dir()) {
+                    StatCounters::inc(&stats.dirs_visited);
+                    continue;
+                }
+                StatCounters::inc(&stats.files_seen);
+
+                // The extension check is a fast path; with sniffing enabled,
+                // files that fail it get a second chance by their contents.
+                let path = entry.path();
+                let ext = path.extension().map(|x| x.to_string_lossy());
+                let sniff = !ext.as_ref().is_some_and(|x| self.opts.exts.contains(&**x));
+                if sniff && !self.opts.sniff {
+                    stats.skip_by_ext(path);
+                    skip(path, SkipReason::Extension);
+                    continue;
+                }
+                if done.contains_key(path) {
+                    continue;
+                }
+                if !sniff {
+                    StatCounters::inc(&stats.found);
+                    self.emit(|| SearchEvent::FileDiscovered(path.to_path_buf()));
+                }
+                // Only fails once the pool's given up, on being canceled.
+                if queue.send(Candidate{entry, sniff}).is_err() {
+                    return;
+                }
+            }
+            self.walked.store(true, Ordering::Relaxed);
+        };
+
+        // The closure returns Err(()) to stop hashing if the search was
+        // canceled, otherwise Ok(()), regardless of whether there was an
+        // error. Actual errors are kept in the errors variable and do not
+        // preclude continuing execution.
+        let hash_file = |Candidate{entry, sniff}: Candidate| -> Result<(), ()> {
             if self.cancel.load(Ordering::Relaxed) {
                 return Err(());
             }
 
-            let entry = match entry {
+            let path = entry.path();
+            if sniff {
+                if !sniff_matches(path, &self.opts.exts) {
+                    stats.skip_by_ext(path);
+                    skip(path, SkipReason::Extension);
+                    return Ok(());
+                }
+                StatCounters::inc(&stats.sniffed);
+                StatCounters::inc(&stats.found);
+                self.emit(|| SearchEvent::FileDiscovered(path.to_path_buf()));
+            }
+
+            // Checked before decoding, which is where the time goes.
+            if let Some(range) = self.opts.modified {
+                let mtime = entry.metadata().ok().and_then(|x| x.modified().ok());
+                if mtime.is_none() {
+                    StatCounters::inc(&stats.unknown_mtime);
+                }
+                if !range.contains(mtime) {
+                    if !range.any_member {
+                        StatCounters::inc(&stats.skipped_by_date);
+                        skip(path, SkipReason::Date);
+                        return Ok(());
+                    }
+                    out_of_range.insert(path.to_path_buf());
+                }
+            }
+
+            // Once stopped, the rest of the walk only counts what's left, so
+            // the results can say how much of the tree they cover. It's only
+            // listing directories, so it's quick.
+            if self.stop.load(Ordering::Relaxed) {
+                StatCounters::inc(&stats.not_searched);
+                skip(path, SkipReason::Stopped);
+                return Ok(());
+            }
+
+            // Nothing to read or decode. Anything else broken is only found
+            // once it's been read.
+            if entry.metadata().is_ok_and(|x| x.len() == 0) {
+                note_broken(path, 0, BrokenKind::Empty);
+                skip(path, SkipReason::Broken);
+                return Ok(());
+            }
+
+            let decode_start = Instant::now();
+            let buffer = read_file(path, Some(&self.io_permits));
+            StatCounters::add_time(&stats.decode_nanos, decode_start);
+            let buffer = match buffer {
                 Ok(x) => x,
                 Err(e) => {
-                    errors.insert(format!("Error walking directory: {e}"));
+                    report(e);
                     return Ok(());
                 },
             };
-            if entry.file_type().is_dir() {
-                return Ok(());
+            // Every file read is checked against its extension while it's in
+            // memory anyway, for the scan details.
+            if let Some(detected) = sniff::mismatch(path, &buffer) {
+                stats.mismatched.insert(path.to_path_buf(), detected);
             }
 
-            let path = entry.path();
-            let Some(ext) = path.extension() else {
-                return Ok(());
+            // Byte-identical copies are decoded and hashed once, by whichever
+            // gets here first, and the rest wait for it and take what it
+            // found; that way exact copies also always hash alike. If it
+            // couldn't be decoded, each copy tries for itself, so each gets
+            // its own error.
+            let copy = self.share_copies()
+                .then(|| copies.entry((buffer.len(), content_hash(&buffer))).or_default().clone());
+            let mut analyzed = None;
+            let shared = copy.as_ref().and_then(|x| x.get_or_init(|| {
+                let analysis = analyze(path, &buffer);
+                let shared = analysis.as_ref().ok().cloned();
+                analyzed = Some(analysis);
+                shared
+            }).as_ref());
+            let analysis = match (analyzed, shared) {
+                (Some(analysis), _) => analysis,
+                (None, Some(shared)) => {
+                    StatCounters::inc(&stats.decodes_saved);
+                    Ok(Arc::clone(shared))
+                },
+                (None, None) => analyze(path, &buffer),
             };
-            let s = ext.to_string_lossy();
-            if !self.exts.contains(&*s) {
-                return Ok(());
-            }
 
-            // I have seen image::open() panic on (presumably) malformed files.
-            let image = match std::panic::catch_unwind(|| image::open(path)) {
-                Ok(Ok(x)) => x,
-                err => { 
-                    let msg = match err {
-                        Err(_) => format!("Panic opening image {}", path.display()),
-                        Ok(Err(e)) => format!("Error opening image {}: {e}", path.display()),
-                        Ok(Ok(_)) => unreachable!(),
-                    };
-                    errors.insert(msg);
-                    return Ok(())
+            let analysis = match analysis {
+                Ok(x) => x,
+                Err(e) => {
+                    // Not ones skipped for their size, which were never
+                    // decoded. Broken ones are listed instead of reported.
+                    let decoded = matches!(e.kind, ErrorKind::Decode | ErrorKind::Panic);
+                    if let Some(kind) = broken::check(&buffer, None).filter(|_| decoded) {
+                        note_broken(path, buffer.len() as u64, kind);
+                        skip(path, SkipReason::Broken);
+                        return Ok(());
+                    }
+                    match e.kind {
+                        ErrorKind::Io => (),
+                        ErrorKind::TooLarge => StatCounters::inc(&stats.skipped_too_large),
+                        ErrorKind::OverBudget => StatCounters::inc(&stats.skipped_over_budget),
+                        _ => StatCounters::inc(&stats.decode_failures),
+                    }
+                    report(e);
+                    return Ok(());
                 },
             };
+            if let Some(kind) = analysis.broken {
+                note_broken(path, buffer.len() as u64, kind);
+            }
+            drop(buffer);
+            let Analysis{hash, frame_count, color, crop, content, dimensions, ..} = &*analysis;
+            let frame_count = *frame_count;
+            if let Some(color) = color {
+                colors.insert(path.to_path_buf(), color.clone());
+            }
+            if frame_count > 1 {
+                frame_counts.insert(path.to_path_buf(), frame_count);
+            }
+            if let Some(crop) = crop {
+                crop_sigs.insert(path.to_path_buf(), crop.clone());
+            }
+            let png = image::ImageFormat::from_path(path).is_ok_and(|x| x == image::ImageFormat::Png);
+            contents.insert(path.to_path_buf(), content.with_format(*dimensions, png));
+            StatCounters::inc(&stats.files_hashed);
+            let meta = entry.metadata().ok();
+            let bytes = meta.as_ref().map_or(0, |x| x.len());
+            stats.bytes_hashed.fetch_add(bytes, Ordering::Relaxed);
 
-            let hash = hasher.hash_image(&image);
-            map.entry(hash).or_insert(DashSet::new()).insert(path.to_path_buf());
+            if self.opts.checkpoint {
+                done.insert(path.to_path_buf(), CheckpointFile {
+                    path: path.to_path_buf(),
+                    hash: hash.to_base64(),
+                    bytes,
+                    mtime: meta.and_then(|x| x.modified().ok()).and_then(to_secs),
+                    frame_count: (frame_count > 1).then_some(frame_count),
+                    color: colors.get(path).map(|x| x.0.to_vec()),
+                    content: contents.get(path).map(|x| *x),
+                });
+                if let Err(e) = self.maybe_save_checkpoint(&done) {
+                    report(e);
+                }
+            }
+            map.entry(hash.clone()).or_insert(DashSet::new()).insert(path.to_path_buf());
+            send(Some((path, hash)));
+            self.emit(|| SearchEvent::FileHashed{path: path.to_path_buf(), micros: decode_start.elapsed().as_micros() as u64});
 
             Ok(())
-        }).collect();
+        };
+
+        // The queue's dropped once hashing's done, so a walk still going
+        // after a cancel stops at its next file.
+        self.emit(|| SearchEvent::StageChanged(Stage::Hashing));
+        std::thread::scope(|scope| {
+            scope.spawn(move || walk(queue));
+            let _ = queued.into_iter().par_bridge().try_for_each(hash_file);
+        });
+
+        if self.cancel.load(Ordering::Relaxed) {
+            return self.canceled(&done, &errors);
+        }
+        send(None);
+        *self.stream.lock().unwrap() = Stream::Closed;
+
+
+        for (ancestor, (child, count)) in loops {
+            let times = if count > 1 { format!(" ({count} links)") } else { String::new() };
+            report(SearchError::new(
+                ErrorKind::Walk,
+                Some(child.clone()),
+                format!(
+                    "Symlink loop at {} back to {}{times}, subtree skipped",
+                    child.display(),
+                    ancestor.display(),
+                ),
+            ));
+        }
+
+        if let (true, Some(max)) = (cap_hit.load(Ordering::Relaxed), self.opts.max_entries) {
+            report(SearchError::new(
+                ErrorKind::Walk,
+                None,
+                format!("Stopped after visiting the limit of {max} entries; results are incomplete"),
+            ));
+        }
 
+        // Loads a group's members, which reads each file again, so it's bounded
+        // by io_permits like the walk. None if canceled.
+        let load_group = |members: Vec<(PathBuf, ImageHash)>, possible_crop: bool| -> Option<Vec<Image>> {
+            members.into_par_iter()
+                .filter_map(|(path, hash)| {
+                    if self.cancel.load(Ordering::Relaxed) {
+                        return Some(None);
+                    }
+                    let side = self.side(&path);
+                    let frame_count = frame_counts.get(&path).map_or(1, |x| *x);
+                    let _permit = self.io_permits.acquire();
+                    match load_image(path, hash, frame_count) {
+                        Ok(mut x) => {
+                            x.side = side;
+                            x.possible_crop = possible_crop;
+                            x.content = contents.get(&x.path).map(|x| *x);
+                            Some(Some(x))
+                        },
+                        Err(e) => {
+                            report(e);
+                            None
+                        },
+                    }
+                })
+                .collect()
+        };
 
-        // This part doesn't take very long (and I see essentially 0 benefit for
-        // paralleization), and would require a lot of extra complexity to make
-        // it cancelable with rayon considering the nested loops.
-        let mut duplicates = vec![];
-        for (_, dups) in map.into_iter() {
+        let index = HashIndex {
+            alg: self.opts.hash,
+            hashes: map.into_iter().map(|(hash, dups)| (hash, dups.into_iter().collect())).collect(),
+        };
+        let hash_of: HashMap<&Path, &ImageHash> = index.hashes.iter()
+            .flat_map(|(hash, paths)| paths.iter().map(move |x| (x.as_path(), hash)))
+            .collect();
+
+        // Splits and loads the groups among files whose hashes were grouped
+        // together. None if canceled.
+        let finalize_cluster = |dups: Vec<PathBuf>| -> Option<Vec<Vec<Image>>> {
             if self.cancel.load(Ordering::Relaxed) {
-                return SearchResults::empty();
+                return None;
             }
-
             if dups.len() <= 1 {
-                continue;
+                return Some(vec![]);
             }
 
-            let mut v = vec![];
-            for path in dups {
-                match Image::load(path) {
-                    Ok(x) => v.push(x),
-                    Err(e) => { errors.insert(e); },
+            let clusters = if self.opts.verify_color {
+                let (clusters, rejected) = split_by_color(dups, |x| colors.get(x).map(|x| x.clone()));
+                stats.color_rejected.fetch_add(rejected, Ordering::Relaxed);
+                clusters
+            } else {
+                vec![dups]
+            };
+
+            let mut groups = vec![];
+            for dups in clusters {
+                if dups.len() <= 1 {
+                    continue;
                 }
 
-                if self.cancel.load(Ordering::Relaxed) {
-                    return SearchResults::empty();
+                if dups.iter().all(|x| out_of_range.contains(x)) {
+                    continue;
+                }
+
+                // In compare mode, only matches across the two folders count.
+                if self.opts.reference.is_some() {
+                    let sides: HashSet<_> = dups.iter().map(|x| self.side(x)).collect();
+                    if sides.len() < 2 {
+                        continue;
+                    }
                 }
+
+                let members = dups.into_iter()
+                    .map(|x| {
+                        let hash = hash_of[x.as_path()].clone();
+                        (x, hash)
+                    })
+                    .collect();
+                groups.push(load_group(members, false)?);
+            }
+            Some(groups)
+        };
+
+        // Files with the same hash always go together; with a distance, so do
+        // those whose hashes are grouped together. Each file ends up in at
+        // most one group either way.
+        let buckets: Vec<(&ImageHash, &Vec<PathBuf>)> = index.hashes.iter().collect();
+        let hashes: Vec<&ImageHash> = buckets.iter().map(|(hash, _)| *hash).collect();
+        self.emit(|| SearchEvent::StageChanged(Stage::Grouping));
+        let Some(clusters) = grouping::cluster(&hashes, self.opts.max_distance, self.opts.grouping, &self.cancel) else {
+            return self.canceled(&done, &errors);
+        };
+        let cluster_of: HashMap<&Path, usize> = clusters.iter()
+            .enumerate()
+            .flat_map(|(idx, cluster)| cluster.iter().flat_map(|x| buckets[*x].1.iter()).map(move |x| (x.as_path(), idx)))
+            .collect();
+
+        // Clusters are independent, so a huge library's worth of them can be
+        // worked through on every core.
+        self.emit(|| SearchEvent::StageChanged(Stage::Loading));
+        self.finalize.total.store(clusters.len(), Ordering::Relaxed);
+        self.finalize.started.store(true, Ordering::Relaxed);
+        let finalize_start = Instant::now();
+        let groups: Option<Vec<Vec<Vec<Image>>>> = clusters.par_iter()
+            .map(|cluster| {
+                let dups = cluster.iter().flat_map(|x| buckets[*x].1.iter().cloned()).collect();
+                let groups = finalize_cluster(dups);
+                StatCounters::inc(&self.finalize.done);
+                groups
+            })
+            .collect();
+        let Some(groups) = groups else {
+            return self.canceled(&done, &errors);
+        };
+        let finalize_time = finalize_start.elapsed();
+        let mut duplicates: Vec<Vec<Image>> = groups.into_iter().flatten().collect();
+
+        if self.opts.detect_crops {
+            self.emit(|| SearchEvent::StageChanged(Stage::Crops));
+            let sigs: Vec<_> = crop_sigs.into_iter().collect();
+            let grouped = |a: &Path, b: &Path| cluster_of.get(a) == cluster_of.get(b);
+            let (pairs, exhausted) = crop_match::find_crops(&sigs, grouped, Self::CROP_PAIR_BUDGET, &self.cancel);
+            if self.cancel.load(Ordering::Relaxed) {
+                return self.canceled(&done, &errors);
             }
-            duplicates.push(v);
+            if exhausted {
+                report(SearchError::new(
+                    ErrorKind::Walk,
+                    None,
+                    format!(
+                        "Crop detection stopped after comparing {} pairs; some crops may have been missed",
+                        Self::CROP_PAIR_BUDGET,
+                    ),
+                ));
+            }
+
+            let crops: Option<Vec<Vec<Image>>> = pairs.into_par_iter()
+                .filter(|(a, b)| self.opts.reference.is_none() || self.side(a) != self.side(b))
+                .filter(|(a, b)| !(out_of_range.contains(a) && out_of_range.contains(b)))
+                .map(|(a, b)| {
+                    let members = [a, b].into_iter()
+                        .map(|x| {
+                            let hash = hash_of[x.as_path()].clone();
+                            (x, hash)
+                        })
+                        .collect();
+                    load_group(members, true)
+                })
+                .collect();
+            let Some(crops) = crops else {
+                return self.canceled(&done, &errors);
+            };
+            for v in crops {
+                if v.len() == 2 {
+                    StatCounters::inc(&stats.crop_matches);
+                    duplicates.push(v);
+                }
+            }
+        }
+
+        // A search stopped early can be resumed for the rest.
+        let stopped = self.stop.load(Ordering::Relaxed);
+        if self.opts.checkpoint && stopped {
+            let _ = self.save_checkpoint(&done);
+        } else if self.opts.checkpoint {
+            Checkpoint::delete();
         }
 
+        sort_results(&mut duplicates);
+        for group in &duplicates {
+            self.emit(|| SearchEvent::GroupFound{members: group.iter().map(|x| x.path.clone()).collect()});
+        }
+        self.finish_events(duplicates.len(), errors.len(), false);
+
+        let mut stats = stats.snapshot();
+        stats.elapsed = start.elapsed();
+        stats.finalize_time = finalize_time;
+        stats.libraries = libraries.report();
+        if stopped {
+            stats.not_searched = Some(self.stats.not_searched.load(Ordering::Relaxed));
+        }
+        let mut broken = broken.into_inner().unwrap();
+        broken.sort_by(|a, b| a.path.cmp(&b.path));
         SearchResults {
             duplicates,
             errors: errors.into_iter().collect(),
+            broken,
+            index,
+            stats: Some(stats),
+        }
+    }
+}
+
+// Groups come out of the clusters in whatever order the threads finished
+// them, so they're put in one that only depends on what was found: members by
+// path, and groups by their first member. Two searches of the same files give
+// the same results in the same order, which the display then sorts further
+// with stable sorts.
+fn sort_results(duplicates: &mut [Vec<Image>]) {
+    for group in duplicates.iter_mut() {
+        group.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    duplicates.sort_by(|a, b| a.iter().map(|x| &x.path).cmp(b.iter().map(|x| &x.path)));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Images to add to the group with the given hash, creating it if there isn't
+// one yet.
+pub struct RetryResults {
+    pub additions: Vec<(ImageHash, Vec<Image>)>,
+    pub errors: Vec<SearchError>, // Files that failed again
+    pub index: HashIndex, // Updated copy of the index passed in
+}
+
+// Hashes just the given files (typically those that failed during a search) and
+// works out how they change the duplicate groups. Runs synchronously; it's
+// expected to be called from a background thread. Existing groups are only
+// ever extended. A file that now matches one seen only once before forms a new
+// group with it. Files are hashed with the index's algorithm.
+pub fn retry_files(paths: Vec<PathBuf>, cap: SizeCap, trim_borders: bool, center: u32, mut index: HashIndex) -> RetryResults {
+    let hasher = hasher(index.alg);
+    let cap = cap.for_hash(index.alg);
+    let results: Vec<_> = paths.into_par_iter()
+        .map(|path| hash_file(&hasher, &path, cap, trim_borders, center).map(|(hash, frames)| (hash, path, frames)))
+        .collect();
+
+    let mut errors = vec![];
+    let mut new_paths: HashMap<ImageHash, Vec<PathBuf>> = HashMap::new();
+    let mut frame_counts = HashMap::new(); // Only animations
+    for result in results {
+        match result {
+            Ok((hash, path, frames)) => {
+                if frames > 1 {
+                    frame_counts.insert(path.clone(), frames);
+                }
+                new_paths.entry(hash).or_default().push(path);
+            },
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut additions = vec![];
+    for (hash, paths) in new_paths {
+        let old = index.hashes.entry(hash.clone()).or_default();
+        let to_load = match old.len() {
+            0 if paths.len() <= 1 => vec![],
+            1 => old.iter().chain(&paths).cloned().collect(), // Previously unmatched
+            _ => paths.clone(), // Extending a group, or an entirely new one
+        };
+        old.extend(paths);
+
+        let mut images = vec![];
+        for path in to_load {
+            let frame_count = frame_counts.get(&path).copied().unwrap_or(1);
+            match load_image(path, hash.clone(), frame_count) {
+                Ok(x) => images.push(x),
+                Err(e) => errors.push(e),
+            }
+        }
+        if !images.is_empty() {
+            additions.push((hash, images));
         }
     }
+
+    RetryResults{additions, errors, index}
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -174,6 +1637,26 @@ impl SearcherInner {
 pub type PhantomUnsync = std::marker::PhantomData<std::cell::Cell<()>>;
 pub type PhantomUnsend = std::marker::PhantomData<std::sync::MutexGuard<'static, ()>>;
 
+// The search thread panicked, outside of the decoders (whose panics are caught
+// and reported per file).
+#[derive(Debug)]
+pub struct SearchPanicked {
+    pub msg: String, // The panic's message, if it had one that could be recovered
+}
+
+impl std::fmt::Display for SearchPanicked {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+// Runs a whole search on the calling thread, for tests, which have nothing
+// to do meanwhile; the app always searches in the background, via Searcher.
+#[cfg(test)]
+pub fn search_with_options(opts: SearchOpts) -> SearchResults {
+    SearcherInner::new(opts).search()
+}
+
 // Searcher is the public interface. Allows starting a search in the background,
 // check if its completed, and blocking until completion and getting the results.
 // Designed for (and only tested for) a single run, but in principle could be
@@ -190,22 +1673,9 @@ pub struct Searcher {
 }
 
 impl Searcher {
-    pub fn new(
-        root: PathBuf,
-        hash: HashAlg,
-        follow_sym: bool,
-        max_depth: Option<usize>,
-        exts: HashSet<String>
-    ) -> Searcher {
+    pub fn new(opts: SearchOpts) -> Searcher {
         Searcher {
-            inner: Arc::new(SearcherInner{
-                root,
-                hash,
-                follow_sym,
-                max_depth,
-                exts,
-                cancel: AtomicBool::new(false),
-            }),
+            inner: Arc::new(SearcherInner::new(opts)),
             thread: None,
             unsync: Default::default(),
             unsend: Default::default(),
@@ -220,37 +1690,677 @@ impl Searcher {
         self.inner.cancel.load(Ordering::Relaxed)
     }
 
+    // Stops hashing, but unlike cancel(), the search still finishes, with the
+    // duplicates among what it had hashed. Files already being hashed are
+    // finished first.
+    pub fn stop(&self) {
+        self.inner.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.inner.stop.load(Ordering::Relaxed)
+    }
+
+    // Once hashing is done and the results are being put together, how many
+    // sets of matching files have been, out of how many.
+    pub fn finalizing(&self) -> Option<(usize, usize)> {
+        let progress = &self.inner.finalize;
+        progress.started.load(Ordering::Relaxed).then(|| (
+            progress.done.load(Ordering::Relaxed),
+            progress.total.load(Ordering::Relaxed),
+        ))
+    }
+
+    // How many images the walk has found and how many have been hashed so
+    // far, for reporting progress. The walk can only be so far ahead; see
+    // SearchOpts::queue_size.
+    pub fn progress(&self) -> (usize, usize) {
+        let stats = &self.inner.stats;
+        (stats.found.load(Ordering::Relaxed), stats.files_hashed.load(Ordering::Relaxed))
+    }
+
+    // Starts sending each file as it's hashed, and everything hashed so far,
+    // for showing partial results. The channel closes once hashing's done, or
+    // the search is canceled. None if it's too late, or it was already asked
+    // for.
+    pub fn stream(&self) -> Option<mpsc::Receiver<Hashed>> {
+        let mut stream = self.inner.stream.lock().unwrap();
+        if !matches!(*stream, Stream::Off) {
+            return None;
+        }
+        let (tx, rx) = mpsc::channel();
+        *stream = Stream::Requested(tx);
+        Some(rx)
+    }
+
+    // Starts sending what the search does as it does it; see search_events.
+    // Only before launch_search(). Capacity is how many events can be waiting
+    // to be read before more are dropped.
+    pub fn events(&mut self, capacity: usize) -> mpsc::Receiver<SearchEvent> {
+        let inner = Arc::get_mut(&mut self.inner).expect("events() called after launch_search()");
+        let (sink, rx) = EventSink::new(capacity);
+        inner.events = Some(sink);
+        rx
+    }
+
     pub fn launch_search(&mut self) {
         assert!(
-            !self.thread.is_some(),
+            self.thread.is_none(),
             "launch_search() called twice without wait_for_search() between"
         );
         self.inner.cancel.store(false, Ordering::Relaxed);
+        self.inner.stop.store(false, Ordering::Relaxed);
+        self.inner.walked.store(false, Ordering::Relaxed);
+        self.inner.resumed.store(0, Ordering::Relaxed);
         let inner = self.inner.clone();
+        let counted = inner.opts.checkpoint.then(CheckpointedSearch::launch);
         self.thread = Some(thread::spawn(move || {
+            let _counted = counted;
             inner.search()
         }));
     }
     
-    // Panics if not launch_search was never called or hasn't been called (or if
-    // join() was already called)
+    // Whether wait_for_search() would return immediately, including because
+    // the search crashed. Panics if launch_search() was never called (or if
+    // wait_for_search() was already called).
     pub fn is_finished(&self) -> bool {
         self.thread.as_ref()
             .expect("thread missing")
             .is_finished()
     }
     
-    // Panics if not search_async was never called (or if join() was already
-    // called)
-    // Panics on thread join errors
-    pub fn wait_for_search(&mut self) -> SearchResults {
+    // Panics if launch_search() was never called (or if wait_for_search() was
+    // already called). A panic in the search thread is returned, rather than
+    // propagated, so the app can recover.
+    pub fn wait_for_search(&mut self) -> Result<SearchResults, SearchPanicked> {
         self.thread.take()
             .expect("thread missing")
             .join()
-            .expect("thread join error")
+            .map_err(|payload| {
+                let msg = payload.downcast_ref::<&str>().map(|x| x.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown error".to_string());
+                SearchPanicked{msg}
+            })
+    }
+}
 
+// A search nobody is waiting for anymore (e.g. its tab was closed) stops
+// soon after.
+impl Drop for Searcher {
+    fn drop(&mut self) {
+        self.cancel();
     }
 }
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::search_with_options as search;
+    use crate::search_harness::{opts, gradient, write_image};
+
+    use itertools::Itertools;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle_terminates_with_one_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for name in ["a", "b"] {
+            std::fs::create_dir(root.join(name)).unwrap();
+            std::os::unix::fs::symlink(root, root.join(name).join("loop")).unwrap();
+        }
+
+        let results = search(opts(root));
+        let loops: Vec<_> = results.errors.iter()
+            .filter(|x| x.msg.contains("Symlink loop"))
+            .collect();
+        assert_eq!(loops.len(), 1, "{:?}", results.errors);
+        assert!(loops[0].msg.contains("2 links"));
+        assert!(loops[0].msg.contains("subtree skipped"));
+    }
+
+    #[test]
+    fn oversized_images_match_normal_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(&gradient(1600, 1200, false), &dir.path().join("huge.jpg"));
+        write_image(&gradient(1200, 1600, true), &dir.path().join("huge_mirrored.png"));
+        write_image(&gradient(400, 300, false), &dir.path().join("normal.png"));
+        write_image(&gradient(300, 400, true), &dir.path().join("normal_mirrored.png"));
+
+        let size_cap = SizeCap{max_pixels: 400 * 300, skip: false, ..SizeCap::default()};
+        let results = search(SearchOpts{size_cap, ..opts(dir.path())});
+        assert!(results.errors.is_empty(), "{:?}", results.errors);
+
+        let mut groups: Vec<Vec<_>> = results.duplicates.iter()
+            .map(|group| group.iter().map(|x| x.path.file_name().unwrap().to_owned()).sorted().collect())
+            .collect();
+        groups.sort();
+        assert_eq!(groups, vec![
+            vec!["huge.jpg", "normal.png"],
+            vec!["huge_mirrored.png", "normal_mirrored.png"],
+        ]);
+    }
+
+    #[test]
+    fn oversized_images_can_be_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(&gradient(1600, 1200, false), &dir.path().join("huge.png"));
+        write_image(&gradient(400, 300, false), &dir.path().join("normal.png"));
+
+        let size_cap = SizeCap{max_pixels: 400 * 300, skip: true, ..SizeCap::default()};
+        let results = search(SearchOpts{size_cap, ..opts(dir.path())});
+        assert!(results.duplicates.is_empty());
+        assert_eq!(results.errors.len(), 1);
+        assert_eq!(results.errors[0].kind, ErrorKind::TooLarge);
+        assert_eq!(results.stats.unwrap().skipped_too_large, 1);
+    }
+
+    #[test]
+    fn results_come_out_the_same_every_time() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["b", "a", "c/d"] {
+            std::fs::create_dir_all(dir.path().join(folder)).unwrap();
+            write_image(&gradient(200, 150, false), &dir.path().join(folder).join("plain.png"));
+            write_image(&gradient(200, 150, true), &dir.path().join(folder).join("mirrored.png"));
+        }
+
+        let run = || {
+            let results = search(SearchOpts{io_threads: 4, ..opts(dir.path())});
+            results.duplicates.iter()
+                .map(|group| (crate::misc::group_id(group), group.iter().map(|x| x.path.clone()).collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+        };
+        let first = run();
+        assert_eq!(first.len(), 2);
+        assert!(first[0].1.is_sorted() && first[0].1[0].ends_with("a/mirrored.png"));
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn tiny_queue_finds_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..12 {
+            write_image(&gradient(120, 90, i % 2 == 1), &dir.path().join(format!("{i}.png")));
+        }
+        // Misnamed, so only sniffing, on the hashing side of the queue, finds it
+        std::fs::copy(dir.path().join("0.png"), dir.path().join("renamed.dat")).unwrap();
+
+        let results = search(SearchOpts{queue_size: 1, io_threads: 4, sniff: true, ..opts(dir.path())});
+        assert!(results.errors.is_empty(), "{:?}", results.errors);
+        let sizes: Vec<_> = results.duplicates.iter().map(|x| x.len()).sorted().collect();
+        assert_eq!(sizes, vec![6, 7]);
+        let stats = results.stats.unwrap();
+        assert_eq!((stats.files_hashed, stats.sniffed), (13, 1));
+    }
+
+    #[test]
+    fn images_over_memory_budget_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(&gradient(1600, 1200, false), &dir.path().join("huge.png"));
+        write_image(&gradient(1600, 1200, false), &dir.path().join("huge.jpg"));
+        write_image(&gradient(400, 300, false), &dir.path().join("normal.png"));
+
+        // Room for the small PNG, and for the JPEG scaled down while it's
+        // decoded, but not for the big PNG at full size.
+        let size_cap = SizeCap{max_pixels: 400 * 300, skip: false, max_bytes: 1024 * 1024, ..SizeCap::default()};
+        let results = search(SearchOpts{size_cap, ..opts(dir.path())});
+        assert_eq!(results.errors.len(), 1, "{:?}", results.errors);
+        assert_eq!(results.errors[0].kind, ErrorKind::OverBudget);
+        assert!(results.errors[0].msg.contains("huge.png"));
+        assert!(results.errors[0].msg.contains("needs ~"));
+        assert_eq!(results.stats.unwrap().skipped_over_budget, 1);
+        assert_eq!(results.duplicates.len(), 1);
+    }
+
+    #[test]
+    fn reduced_jpegs_hash_like_full_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        // Some texture as well, which scaling could plausibly blur away.
+        let ripples = |width: u32, height: u32| image::RgbImage::from_fn(width, height, |x, y| {
+            let (fx, fy) = (x as f32 / width as f32, y as f32 / height as f32);
+            let v = ((fx * 9.0).sin() * (fy * 5.0).cos() * 0.5 + 0.5) * 255.0;
+            image::Rgb([v as u8, (fy * 255.0) as u8, (255.0 - v) as u8])
+        });
+        let fixtures = [
+            ("wide.jpg", gradient(2400, 1000, false)),
+            ("tall.jpg", gradient(900, 1600, true)),
+            ("small.jpg", gradient(600, 400, false)),
+            ("ripples.jpg", ripples(2000, 1500)),
+        ];
+
+        let full = SizeCap{reduced_jpeg: false, ..SizeCap::default()};
+        for (name, image) in fixtures {
+            let path = dir.path().join(name);
+            write_image(&image, &path);
+            let large = open_image(&path, None, full).ok().unwrap();
+            assert_eq!((large.image.width(), large.image.height()), image.dimensions());
+
+            for alg in [HashAlg::Mean, HashAlg::Gradient, HashAlg::VertGradient, HashAlg::DoubleGradient, HashAlg::Blockhash] {
+                let small = open_image(&path, None, SizeCap::default().for_hash(alg)).ok().unwrap();
+                if alg != HashAlg::Blockhash {
+                    assert!(small.image.width().max(small.image.height()) <= 2 * SizeCap::REDUCED_SIDE, "{name}");
+                }
+                let hasher = hasher(alg);
+                let distance = small.hash(&hasher, false, 100).dist(&large.hash(&hasher, false, 100));
+                assert!(distance <= 1, "{name} {alg:?}: {distance}");
+            }
+        }
+    }
+
+    #[test]
+    fn letterboxed_copies_match_with_trimmed_borders() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = gradient(400, 300, false);
+        let letterboxed = image::RgbImage::from_fn(400, 400, |x, y| match y {
+            50..=349 => *original.get_pixel(x, y - 50),
+            _ => image::Rgb([0, 0, 0]),
+        });
+        write_image(&original, &dir.path().join("original.png"));
+        write_image(&letterboxed, &dir.path().join("letterboxed.png"));
+
+        let results = search(SearchOpts{trim_borders: true, ..opts(dir.path())});
+        assert_eq!(results.duplicates.len(), 1);
+        assert_eq!(results.duplicates[0].len(), 2);
+
+        let results = search(opts(dir.path()));
+        assert!(results.duplicates.is_empty());
+    }
+
+    #[test]
+    fn watermarked_copies_match_by_their_middles() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = gradient(400, 300, false);
+        // A strip along the bottom, light then dark, which trimming wouldn't
+        // take off since it isn't uniform.
+        let watermarked = image::RgbImage::from_fn(400, 300, |x, y| match (y, x) {
+            (272.., ..200) => image::Rgb([255, 255, 255]),
+            (272.., _) => image::Rgb([20, 20, 20]),
+            _ => *original.get_pixel(x, y),
+        });
+        write_image(&original, &dir.path().join("original.png"));
+        write_image(&watermarked, &dir.path().join("watermarked.png"));
+
+        let results = search(SearchOpts{center: 80, ..opts(dir.path())});
+        assert_eq!(results.duplicates.len(), 1);
+        assert_eq!(results.duplicates[0].len(), 2);
+
+        let results = search(opts(dir.path()));
+        assert!(results.duplicates.is_empty());
+
+        let results = search(SearchOpts{trim_borders: true, ..opts(dir.path())});
+        assert!(results.duplicates.is_empty());
+    }
+
+    #[test]
+    fn entry_cap_stops_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            std::fs::write(dir.path().join(format!("{i}.txt")), "").unwrap();
+        }
+
+        let results = search(SearchOpts{max_entries: Some(5), ..opts(dir.path())});
+        assert!(results.errors.iter().any(|x| x.msg.contains("limit of 5 entries")));
+    }
+
+    // Enough groups to spread across threads; the loads still have to all end
+    // up in the right ones.
+    #[test]
+    fn finalizing_loads_every_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let (count, copies) = (24, 3);
+        for i in 0..count {
+            // Random blocks, coarse enough to survive the hash's downscaling.
+            let mut rng = fastrand::Rng::with_seed(i);
+            let blocks: Vec<u8> = (0..64).map(|_| rng.u8(..)).collect();
+            let image = image::RgbImage::from_fn(64, 64, |x, y| {
+                let v = blocks[(y / 8 * 8 + x / 8) as usize];
+                image::Rgb([v, v, v])
+            });
+            for copy in 0..copies {
+                write_image(&image, &dir.path().join(format!("{i}_{copy}.png")));
+            }
+        }
+
+        let mut searcher = Searcher::new(SearchOpts{io_threads: 4, ..opts(dir.path())});
+        searcher.launch_search();
+        let results = searcher.wait_for_search().unwrap();
+        assert!(results.errors.is_empty(), "{:?}", results.errors);
+        for group in &results.duplicates {
+            let prefixes: HashSet<_> = group.iter()
+                .map(|x| x.path.file_name().unwrap().to_str().unwrap().split('_').next().unwrap().to_owned())
+                .collect();
+            assert_eq!(prefixes.len(), 1, "{prefixes:?}");
+        }
+        let found: usize = results.duplicates.iter().map(Vec::len).sum();
+        assert_eq!(found, count as usize * copies);
+
+        let (done, total) = searcher.finalizing().unwrap();
+        assert_eq!(done, total);
+        assert_eq!(total, results.index.hashes.len());
+    }
+
+    #[test]
+    fn search_panic_is_returned() {
+        let dir = tempfile::tempdir().unwrap();
+        write_image(&gradient(64, 48, false), &dir.path().join("a.png"));
+
+        let mut searcher = Searcher::new(opts(dir.path()));
+        Arc::get_mut(&mut searcher.inner).unwrap().hash_hook = Some(|_, _| panic!("injected"));
+        searcher.launch_search();
+        let err = searcher.wait_for_search().err().expect("search should have failed");
+        assert_eq!(err.msg, "injected");
+    }
+
+    #[test]
+    fn stopped_searches_group_what_they_hashed() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c", "d"] {
+            write_image(&gradient(64, 48, false), &dir.path().join(format!("{name}.png")));
+        }
+
+        // On one thread, nothing else is in flight when the first file stops
+        // the search.
+        let results = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap().install(|| {
+            SearcherInner {
+                hash_hook: Some(|inner, _| inner.stop.store(true, Ordering::Relaxed)),
+                ..SearcherInner::new(opts(dir.path()))
+            }.search()
+        });
+        let stats = results.stats.unwrap();
+        assert_eq!((stats.files_hashed, stats.not_searched), (1, Some(3)));
+        assert_eq!(stats.coverage(), Some(25.0));
+        assert_eq!(results.index.hashes.values().map(Vec::len).sum::<usize>(), 1);
+        assert!(results.duplicates.is_empty());
+    }
+
+    // However big the tree, a cancel only waits for what's already queued.
+    #[test]
+    fn canceling_mid_walk_stops_within_the_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("image.png");
+        write_image(&gradient(16, 16, false), &image);
+        let (folders, files) = (40, 100);
+        for folder in 0..folders {
+            let folder = dir.path().join(format!("{folder}"));
+            std::fs::create_dir(&folder).unwrap();
+            for file in 0..files {
+                std::fs::hard_link(&image, folder.join(format!("{file}.png"))).unwrap();
+            }
+        }
+
+        let queue_size = 64;
+        let inner = SearcherInner {
+            hash_hook: Some(|inner, _| inner.cancel.store(true, Ordering::Relaxed)),
+            ..SearcherInner::new(SearchOpts{queue_size, ..opts(dir.path())})
+        };
+        let results = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap().install(|| inner.search());
+        assert!(results.duplicates.is_empty());
+        // What was queued, the file being hashed, and the one waiting to be
+        // queued, give or take what the pool pulled in to work on.
+        let seen = inner.stats.files_seen.load(Ordering::Relaxed);
+        assert!(seen <= queue_size + 8, "walked {seen} of {} files", folders * files + 1);
+    }
+
+    #[test]
+    fn streams_asked_for_mid_search_catch_up_first() {
+        let dir = tempfile::tempdir().unwrap();
+        // Different files, so none are decoded just once for several
+        for (idx, name) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            write_image(&gradient(64 + idx as u32, 48, false), &dir.path().join(format!("{name}.png")));
+        }
+
+        // The stream's asked for just before the third file's hashed, so it
+        // gets that file along with the two before it, then the rest in turn.
+        static HASHED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+        static STREAM: Mutex<Option<mpsc::Receiver<Hashed>>> = Mutex::new(None);
+        let hook = |inner: &SearcherInner, path: &Path| {
+            let mut hashed = HASHED.lock().unwrap();
+            if hashed.len() == 2 {
+                let (tx, rx) = mpsc::channel();
+                *inner.stream.lock().unwrap() = Stream::Requested(tx);
+                *STREAM.lock().unwrap() = Some(rx);
+            }
+            hashed.push(path.to_path_buf());
+        };
+        rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap().install(|| {
+            SearcherInner{hash_hook: Some(hook), ..SearcherInner::new(opts(dir.path()))}.search()
+        });
+
+        let hashed = HASHED.lock().unwrap().clone();
+        let sent: Vec<PathBuf> = STREAM.lock().unwrap().take().unwrap().try_iter().map(|x| x.path).collect();
+        assert_eq!(hashed.len(), 5);
+        assert_eq!(sent.len(), 5, "{sent:?}");
+        assert_eq!(sent[..3].iter().collect::<HashSet<_>>(), hashed[..3].iter().collect());
+        assert_eq!(sent[3..], hashed[3..]);
+    }
+
+    #[test]
+    fn photo_libraries_are_searched_for_originals_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("Photos Library.photoslibrary");
+        for folder in ["originals/0", "resources/derivatives/0"] {
+            std::fs::create_dir_all(library.join(folder)).unwrap();
+            write_image(&gradient(64, 48, false), &library.join(folder).join("a.png"));
+        }
+        write_image(&gradient(64, 48, false), &dir.path().join("copy.png"));
+
+        let results = search(opts(dir.path()));
+        let stats = results.stats.unwrap();
+        assert_eq!(results.duplicates.len(), 1);
+        assert_eq!(results.duplicates[0].len(), 2);
+        assert!(results.duplicates[0].iter().all(|x| !x.path.starts_with(library.join("resources"))));
+        assert_eq!(stats.libraries.len(), 1);
+        assert_eq!((stats.libraries[0].name, stats.libraries[0].skipped), ("Apple Photos", 1));
+
+        let results = search(SearchOpts{scan_libraries: true, ..opts(dir.path())});
+        assert_eq!(results.duplicates[0].len(), 3);
+    }
+
+    #[test]
+    fn broken_images_are_listed_apart_from_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        write_image(&gradient(64, 48, false), &path("a.png"));
+        write_image(&gradient(64, 48, false), &path("b.png"));
+        std::fs::write(path("empty.jpg"), b"").unwrap();
+        let whole = std::fs::read(path("a.png")).unwrap();
+        std::fs::write(path("cut.png"), &whole[..whole.len() / 2]).unwrap();
+        write_image(&image::RgbImage::from_pixel(64, 48, image::Rgb([0, 0, 0])), &path("black.png"));
+        std::fs::write(path("garbage.jpg"), "not a jpeg").unwrap();
+
+        let results = search(opts(dir.path()));
+        let broken: Vec<_> = results.broken.iter().map(|x| (x.path.clone(), x.kind)).collect();
+        assert_eq!(broken, vec![
+            (path("black.png"), BrokenKind::Solid),
+            (path("cut.png"), BrokenKind::Truncated),
+            (path("empty.jpg"), BrokenKind::Empty),
+        ]);
+        // Only what isn't broken is an error
+        let errors: Vec<_> = results.errors.iter().map(|x| x.path.clone()).collect();
+        assert_eq!(errors, vec![Some(path("garbage.jpg"))]);
+        assert_eq!(results.duplicates.len(), 1);
+
+        let stats = results.stats.unwrap();
+        assert_eq!(stats.broken.values().sum::<usize>(), 3);
+        assert_eq!(stats.files_hashed, 3); // The pair, and the flat one, which did decode
+        assert_eq!(stats.decode_failures, 1);
+    }
+
+    #[test]
+    fn exact_copies_are_decoded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        for folder in ["x", "y/z"] {
+            std::fs::create_dir_all(path(folder)).unwrap();
+        }
+        write_image(&gradient(64, 48, false), &path("a.png"));
+        for copy in ["x/a.png", "y/a copy.png", "y/z/a.png"] {
+            std::fs::copy(path("a.png"), path(copy)).unwrap();
+        }
+        write_image(&gradient(64, 48, false), &path("a.jpg")); // The same picture, but not the same bytes
+        write_image(&gradient(48, 64, true), &path("b.png"));
+        std::fs::copy(path("b.png"), path("x/b.png")).unwrap();
+        let checks = image::RgbImage::from_fn(80, 80, |x, y| image::Rgb([if (x / 10 + y / 10) % 2 == 0 { 255 } else { 0 }; 3]));
+        write_image(&checks, &path("c.png")); // No copies
+
+        let opts = || SearchOpts{verify_color: true, ..opts(dir.path())};
+        let groups = |results: &SearchResults| {
+            let mut groups: Vec<Vec<PathBuf>> = results.duplicates.iter()
+                .map(|group| group.iter().map(|x| x.path.clone()).sorted().collect())
+                .collect();
+            groups.sort();
+            groups
+        };
+        let shared = search(opts());
+        let naive = SearcherInner{naive: true, ..SearcherInner::new(opts())}.search();
+        assert_eq!(groups(&shared), groups(&naive));
+        assert_eq!(groups(&shared).iter().map(Vec::len).collect::<Vec<_>>(), vec![4, 2]);
+        let hashes = |results: &SearchResults| results.index.hashes.iter()
+            .flat_map(|(hash, paths)| paths.iter().map(move |x| (x.clone(), hash.to_base64())))
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(hashes(&shared), hashes(&naive));
+
+        let (shared, naive) = (shared.stats.unwrap(), naive.stats.unwrap());
+        assert_eq!((shared.files_hashed, naive.files_hashed), (8, 8));
+        assert_eq!((shared.decodes_saved, naive.decodes_saved), (4, 0));
+    }
+
+    #[test]
+    fn exact_copies_go_by_their_own_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("shot.png");
+        write_image(&gradient(1280, 720, false), &png);
+        // The same PNG, misnamed, so it's no screenshot
+        std::fs::copy(&png, dir.path().join("shot.jpg")).unwrap();
+
+        let results = search(opts(dir.path()));
+        assert_eq!(results.stats.as_ref().unwrap().decodes_saved, 1);
+        let content = |name: &str| results.duplicates.iter()
+            .flatten()
+            .find(|x| x.path.ends_with(name))
+            .and_then(|x| x.content);
+        assert_eq!(content("shot.png"), Some(ContentKind::Screenshot));
+        assert!(content("shot.jpg").is_some_and(|x| x != ContentKind::Screenshot));
+    }
+
+    // Searches through Searcher, as the app does, with what it reported along
+    // the way.
+    fn search_events(opts: SearchOpts, hook: Option<fn(&SearcherInner, &Path)>) -> (SearchResults, Vec<SearchEvent>) {
+        let mut searcher = Searcher::new(opts);
+        let events = searcher.events(10_000);
+        Arc::get_mut(&mut searcher.inner).unwrap().hash_hook = hook;
+        searcher.launch_search();
+        let results = searcher.wait_for_search().unwrap();
+        (results, events.try_iter().collect())
+    }
+
+    #[test]
+    fn events_follow_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        write_image(&gradient(64, 48, false), &path("a.png"));
+        write_image(&gradient(64, 48, false), &path("b.png"));
+        write_image(&gradient(64, 48, true), &path("c.jpg"));
+        std::fs::write(path("notes.txt"), "not an image").unwrap();
+        std::fs::write(path("broken.jpg"), "not a jpeg either").unwrap();
+
+        let (results, events) = search_events(opts(dir.path()), None);
+        assert_eq!(results.duplicates.len(), 1);
+        assert_eq!(events[..2], [SearchEvent::StageChanged(Stage::Hashing), SearchEvent::WalkStarted]);
+
+        let discovered: HashSet<&PathBuf> = events.iter()
+            .filter_map(|x| match x { SearchEvent::FileDiscovered(path) => Some(path), _ => None })
+            .collect();
+        assert_eq!(discovered, [path("a.png"), path("b.png"), path("c.jpg"), path("broken.jpg")].iter().collect());
+        for (idx, event) in events.iter().enumerate() {
+            if let SearchEvent::FileHashed{path, ..} = event {
+                assert!(events[..idx].contains(&SearchEvent::FileDiscovered(path.clone())));
+            }
+        }
+        let hashed: HashSet<&PathBuf> = events.iter()
+            .filter_map(|x| match x { SearchEvent::FileHashed{path, ..} => Some(path), _ => None })
+            .collect();
+        assert_eq!(hashed, [path("a.png"), path("b.png"), path("c.jpg")].iter().collect());
+        assert!(events.contains(&SearchEvent::FileSkipped{path: path("notes.txt"), reason: SkipReason::Extension}));
+        let errors: Vec<_> = events.iter()
+            .filter_map(|x| match x { SearchEvent::FileError{path, kind, ..} => Some((path.clone(), *kind)), _ => None })
+            .collect();
+        assert_eq!(errors, vec![(Some(path("broken.jpg")), ErrorKind::Decode)]);
+
+        let stages: Vec<_> = events.iter()
+            .filter_map(|x| match x { SearchEvent::StageChanged(stage) => Some(*stage), _ => None })
+            .collect();
+        assert_eq!(stages, vec![Stage::Hashing, Stage::Grouping, Stage::Loading]);
+        let n = events.len();
+        assert_eq!(events[n - 2], SearchEvent::GroupFound{members: vec![path("a.png"), path("b.png")]});
+        assert_eq!(events[n - 1], SearchEvent::Finished(SearchSummary {
+            groups: 1,
+            files_hashed: 3,
+            errors: 1,
+            canceled: false,
+            dropped: 0,
+        }));
+    }
+
+    #[test]
+    fn canceled_searches_finish_without_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c"] {
+            write_image(&gradient(64, 48, false), &dir.path().join(format!("{name}.png")));
+        }
+
+        let (results, events) = search_events(opts(dir.path()), Some(|inner, _| inner.cancel.store(true, Ordering::Relaxed)));
+        assert!(results.duplicates.is_empty());
+        assert!(!events.iter().any(|x| matches!(x, SearchEvent::GroupFound{..} | SearchEvent::StageChanged(Stage::Grouping))));
+        assert!(matches!(events.last(), Some(SearchEvent::Finished(SearchSummary{canceled: true, groups: 0, ..}))));
+    }
+
+    #[test]
+    fn resumed_hashes_are_only_reused_for_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.png");
+        let b = dir.path().join("b.png");
+        write_image(&gradient(64, 48, false), &a);
+        write_image(&gradient(64, 48, true), &b);
+
+        // The checkpoint says they're the same, which they aren't, so they
+        // only group if its hashes are used.
+        let planted = ImageHash::<Box<[u8]>>::from_bytes(&[0xab; 8]).unwrap().to_base64();
+        let entry = |path: &Path| {
+            let meta = std::fs::metadata(path).unwrap();
+            CheckpointFile {
+                path: path.to_path_buf(),
+                hash: planted.clone(),
+                bytes: meta.len(),
+                mtime: meta.modified().ok().and_then(to_secs),
+                frame_count: None,
+                color: None,
+                content: None,
+            }
+        };
+        let resume = |files| search(SearchOpts {
+            resume: Some(Checkpoint::new(String::new(), dir.path().to_path_buf(), files, None)),
+            ..opts(dir.path())
+        });
+
+        assert_eq!(resume(vec![entry(&a), entry(&b)]).duplicates.len(), 1);
+        let resized = CheckpointFile{bytes: entry(&b).bytes + 1, ..entry(&b)};
+        assert!(resume(vec![entry(&a), resized]).duplicates.is_empty());
+        let touched = CheckpointFile{mtime: entry(&b).mtime.map(|x| x - 60), ..entry(&b)};
+        assert!(resume(vec![entry(&a), touched]).duplicates.is_empty());
+        let unknown = CheckpointFile{mtime: None, ..entry(&b)};
+        assert!(resume(vec![entry(&a), unknown]).duplicates.is_empty());
+
+        // Resumed files are checked against their extensions all the same.
+        let misnamed = dir.path().join("b.jpg");
+        std::fs::rename(&b, &misnamed).unwrap();
+        let stats = resume(vec![entry(&a), entry(&misnamed)]).stats.unwrap();
+        assert_eq!(stats.mismatched, [(misnamed, sniff::FileType::Image(image::ImageFormat::Png))]);
+    }
+}
@@ -1,62 +1,264 @@
 
-use crate::ROOT_KEY;
-
-use crate::{Phase, DynPhase, Result};
+use crate::{Phase, DynPhase, Result, Error};
+use crate::palette::Command;
 use crate::startup_phase::{StartupPhase, UserOpts};
-use crate::output_phase::OutputPhase;
-use crate::searcher::Searcher;
+use crate::output_phase::{OutputPhase, FullResults};
+use crate::searcher::{Searcher, SearchResults, HashIndex};
+use crate::search_events::{SearchEvent, Stage};
+use crate::search_lock::SearchLock;
+use crate::live_search::LiveSearch;
+use crate::misc::{folder_name, wasted_bytes};
+use crate::archive::Archive;
+use crate::i18n::format_size;
+
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
 
 use eframe::egui;
 
 pub struct SearchingPhase {
     opts: UserOpts,
-    searcher: Searcher,
+    searcher: Option<Searcher>, // Until it's handed off; see review_partial()
+    _locks: Vec<SearchLock>, // On the folders being searched, until it's over
+    refining: Option<FullResults>, // Passed along; see OutputPhase::refine()
+    started: Instant,
+    last_focused: Instant, // When the window last had focus, while searching
+    last_progress: Progress, // As of the last frame
+    events: Receiver<SearchEvent>,
+    stage: Stage,
+    last_hashed: Option<PathBuf>,
+    errors: usize, // So far, not counting any whose events were dropped
 }
 
+// Files seen and hashed, and once hashing's done, sets finalized out of how
+// many.
+type Progress = ((usize, usize), Option<(usize, usize)>);
+
 impl SearchingPhase {
 
-    // Eyeballed, seems good for a reasonable variety of window sizes
-    const SPINNER_SIZE: f32 = 256.0;
+    // The search can't wake the UI, so it's polled: often while it's making
+    // progress, so the counts look live, and less so while it isn't (e.g. on
+    // a big file), but still often enough to notice it finishing.
+    const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+    const IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+    // Events are read every frame, so this is a few frames' worth of a fast
+    // search's; any more are dropped rather than hold it up.
+    pub const EVENT_CAPACITY: usize = 4096;
 
-    pub fn new(opts: UserOpts, searcher: Searcher) -> SearchingPhase {
+    // Only searches this long are worth a notification; anything shorter, the
+    // user's probably still waiting on.
+    const NOTIFY_AFTER: Duration = Duration::from_secs(30);
+
+    // How long the window has to have been in the background for it, so
+    // switching away just as the search finishes doesn't count.
+    const NOTIFY_UNFOCUSED: Duration = Duration::from_secs(10);
+
+    pub fn new(opts: UserOpts, searcher: Searcher, events: Receiver<SearchEvent>, locks: Vec<SearchLock>) -> SearchingPhase {
         SearchingPhase {
             opts,
-            searcher,
+            searcher: Some(searcher),
+            _locks: locks,
+            refining: None,
+            started: Instant::now(),
+            last_focused: Instant::now(),
+            last_progress: Default::default(),
+            events,
+            stage: Stage::Hashing,
+            last_hashed: None,
+            errors: 0,
+        }
+    }
+
+    fn read_events(&mut self) {
+        for event in self.events.try_iter() {
+            match event {
+                SearchEvent::FileHashed{path, ..} => self.last_hashed = Some(path),
+                SearchEvent::FileError{..} => self.errors += 1,
+                SearchEvent::StageChanged(stage) => self.stage = stage,
+                _ => (),
+            }
         }
     }
 
+    pub fn with_refining(mut self, refining: Option<FullResults>) -> SearchingPhase {
+        self.refining = refining;
+        self
+    }
+
     pub fn into_dyn(self) -> DynPhase {
         Box::new(self)
     }
 
-    fn make_output_phase(&mut self) -> DynPhase {
-        assert!(self.searcher.is_finished());
-        let results = self.searcher.wait_for_search();
-        OutputPhase::new(self.opts.take(), results.duplicates, results.errors).into_dyn()
+    fn searcher(&self) -> &Searcher {
+        self.searcher.as_ref().expect("searcher handed off")
+    }
+
+    fn searcher_mut(&mut self) -> &mut Searcher {
+        self.searcher.as_mut().expect("searcher handed off")
+    }
+
+    // Back to the form, with the same options, if the search crashed.
+    fn make_output_phase(&mut self, ctx: &egui::Context) -> DynPhase {
+        assert!(self.searcher().is_finished());
+        match self.searcher_mut().wait_for_search() {
+            Ok(results) => {
+                Self::record(&self.opts, &results.index);
+                if Self::should_notify(self.opts.notify, self.started.elapsed(), self.last_focused.elapsed()) {
+                    self.notify(ctx, &results);
+                }
+                OutputPhase::new(self.opts.take(), results).with_refining(self.refining.take()).into_dyn()
+            },
+            Err(e) => {
+                Error::new(
+                    "Search failed".to_string(),
+                    tr!("The search crashed: {msg}", msg = e.msg),
+                ).show_modal();
+                StartupPhase::new_with_opts(self.opts.take()).with_refining(self.refining.take()).into_dyn()
+            },
+        }
+    }
+
+    // The folders a search with the options actually walked, to record in the
+    // archive index. Only those, so a search limited to some subfolders
+    // doesn't replace what's recorded for the rest.
+    pub fn walked_roots(opts: &UserOpts) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = opts.subfolders.iter()
+            .filter(|x| x.starts_with(&opts.root))
+            .cloned()
+            .collect();
+        if roots.is_empty() {
+            roots.push(opts.root.clone());
+        }
+        if opts.compare {
+            roots.push(opts.reference.clone());
+        }
+        roots
+    }
+
+    // Adds the files hashed to the archive index, for looking up single images
+    // later, in the background; it's a big write on a big library. Not if only
+    // the images' middles were hashed, since lookups hash them whole.
+    pub fn record(opts: &UserOpts, index: &HashIndex) {
+        if opts.center_crop < 100 {
+            return;
+        }
+        let roots = Self::walked_roots(opts);
+        let index = index.clone();
+        // Like a checkpoint, the index is only a convenience; a failure to
+        // write it isn't worth interrupting the results for.
+        std::thread::spawn(move || {
+            let _ = Archive::record(&roots, &index);
+        });
+    }
+
+    // Lets the user know the search is done while they're doing something
+    // else: a desktop notification, plus a bounce of the dock icon on macOS or
+    // a flash of the taskbar button on Windows.
+    // Given how long the search took and how long the window's been in the
+    // background.
+    fn should_notify(enabled: bool, searched: Duration, unfocused: Duration) -> bool {
+        enabled && searched >= Self::NOTIFY_AFTER && unfocused >= Self::NOTIFY_UNFOCUSED
+    }
+
+    fn notify(&self, ctx: &egui::Context, results: &SearchResults) {
+        let wasted: usize = results.duplicates.iter().map(|x| wasted_bytes(x)).sum();
+        let body = tr!(
+            "Search of {folder} finished: {count} duplicate groups, {size} reclaimable",
+            folder = folder_name(&self.opts.root),
+            count = results.duplicates.len(),
+            size = format_size(wasted),
+        );
+        let mut notification = notify_rust::Notification::new();
+        notification.summary("Deckard").body(&body);
+
+        // Clicking a notification brings its app to the front by itself on
+        // macOS; with freedesktop notifications, Deckard has to do it, so it
+        // waits for the click in the background.
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            notification.action("default", tr!("Show"));
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let Ok(handle) = notification.show() else {
+                    return;
+                };
+                handle.wait_for_action(|action| {
+                    if action == "default" {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                        ctx.request_repaint();
+                    }
+                });
+            });
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let _ = notification.show();
+
+        ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(egui::UserAttentionType::Informational));
+    }
+
+    // Moves on to the results found so far, leaving the search running to
+    // fill them in; see LiveSearch. None once it's too late to stream them,
+    // i.e. hashing's done, so the full results are coming soon anyway.
+    fn review_partial(&mut self, ctx: &egui::Context) -> Option<DynPhase> {
+        let stream = self.searcher().stream()?;
+        let opts = self.opts.take();
+        let searcher = self.searcher.take().unwrap();
+        let reference = opts.compare.then(|| opts.reference.clone());
+        let live = LiveSearch::new(searcher, stream, std::mem::take(&mut self._locks), reference, ctx.clone());
+        Some(OutputPhase::new_live(opts, live).with_refining(self.refining.take()).into_dyn())
+    }
+
+    fn cancel(&mut self) -> DynPhase {
+        // Waiting is usually brief (each worker finishes the image it's on),
+        // and means the search's checkpoint is on disk before the startup form
+        // looks for it.
+        self.searcher().cancel();
+        let _ = self.searcher_mut().wait_for_search(); // Canceled either way
+        StartupPhase::new_with_opts(self.opts.take()).with_refining(self.refining.take()).into_dyn()
     }
 }
 
 impl Phase for SearchingPhase {
-    fn render(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
-        if self.searcher.is_finished() {
-            assert!(!self.searcher.was_canceled());
-            return Ok(Some(self.make_output_phase()));
+    fn render(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
+        if ctx.input(|i| i.focused) {
+            self.last_focused = Instant::now();
+        }
+        if self.searcher().is_finished() {
+            assert!(!self.searcher().was_canceled());
+            return Ok(Some(self.make_output_phase(ctx)));
         }
 
         let resp = ui.horizontal(|ui| {
-            if ui.button("<- New Search").clicked() 
+            if ui.button(tr!("<- New Search")).clicked() 
                 || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
-
-                self.searcher.cancel();
-                return Some(StartupPhase::new_with_opts(self.opts.take()).into_dyn());
+                return Some(self.cancel());
             }
 
             ui.horizontal(|ui| {
-                ui.strong("Searching");
+                ui.strong(tr!("Searching"));
                 ui.monospace(self.opts.root.display().to_string());
             });
 
-            None
+            // Once hashing's done, there's nothing more to stream, or stop.
+            if self.searcher().finalizing().is_some() || self.searcher().is_stopping() {
+                return None;
+            }
+            let (review, stop) = ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let stop = ui.button(tr!("Stop and show what you've found"))
+                    .on_hover_text(tr!("Finish the images in progress, then show the duplicates among those \
+                        searched so far. The rest can be resumed later."))
+                    .clicked();
+                let review = ui.button(tr!("Review partial results"))
+                    .on_hover_text(tr!("Start reviewing the duplicates found so far while the search finishes"))
+                    .clicked();
+                (review, stop)
+            }).inner;
+            if stop {
+                self.searcher().stop();
+            }
+            review.then(|| self.review_partial(ctx)).flatten()
         });
 
         if resp.inner.is_some() {
@@ -64,17 +266,102 @@ impl Phase for SearchingPhase {
         }
 
         ui.separator();
+        self.read_events();
 
-        ui.centered_and_justified(|ui| {
-            let spinner = egui::widgets::Spinner::new().size(Self::SPINNER_SIZE);
-            ui.add(spinner);
+        // A spinner would repaint every frame for as long as the search runs;
+        // the counts only need it when they change.
+        let progress = (self.searcher().progress(), self.searcher().finalizing());
+        let interval = if progress != self.last_progress { Self::PROGRESS_INTERVAL } else { Self::IDLE_INTERVAL };
+        self.last_progress = progress;
+        ctx.request_repaint_after(interval);
+
+        let ((found, hashed), finalizing) = progress;
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.0);
+            // Grouping can take a while after hashing on a big library, and
+            // without this, it'd look like the search hung.
+            let text = match (self.stage, finalizing) {
+                (Stage::Grouping, _) => tr!("Grouping similar images…").to_string(),
+                (Stage::Crops, _) => tr!("Looking for crops…").to_string(),
+                (_, Some((done, total))) => tr!("Finalizing results… {done} of {total}", done = done, total = total),
+                (_, None) if self.searcher().is_stopping() => tr!(
+                    "Stopping after {hashed} files hashed; counting what's left…",
+                    hashed = hashed,
+                ),
+                (_, None) => tr!("{hashed} of {found} images hashed", hashed = hashed, found = found),
+            };
+            ui.heading(text);
+            if self.stage == Stage::Hashing {
+                if let Some(path) = &self.last_hashed {
+                    ui.label(egui::RichText::new(path.display().to_string()).weak().monospace());
+                }
+            }
+            if self.errors > 0 {
+                ui.label(egui::RichText::new(tr!("{count} errors so far", count = self.errors))
+                    .color(ui.visuals().warn_fg_color));
+            }
         });
 
         Ok(None)
     }
 
+    // A search in a tab in back still finishes, and notifies, as it would in
+    // front.
+    fn poll(&mut self, ctx: &egui::Context) -> Result<Option<DynPhase>> {
+        if ctx.input(|i| i.focused) {
+            self.last_focused = Instant::now();
+        }
+        if self.searcher().is_finished() {
+            return Ok(Some(self.make_output_phase(ctx)));
+        }
+        self.read_events();
+        ctx.request_repaint_after(Self::IDLE_INTERVAL);
+        Ok(None)
+    }
+
+    fn title(&self) -> String {
+        tr!("Searching {folder}", folder = folder_name(&self.opts.root))
+    }
+
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        storage.set_string(ROOT_KEY, self.opts.root.to_string_lossy().into());
+        self.opts.save(storage);
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        let mut commands = vec![Command::new("cancel", tr!("Cancel search"))];
+        if self.searcher().finalizing().is_none() && !self.searcher().is_stopping() {
+            commands.push(Command::new("stop", tr!("Stop and show what you've found")));
+        }
+        commands
+    }
+
+    fn run_command(&mut self, _ctx: &egui::Context, id: &str) -> Result<Option<DynPhase>> {
+        match id {
+            "cancel" => Ok(Some(self.cancel())),
+            "stop" => {
+                self.searcher().stop();
+                Ok(None)
+            },
+            _ => Ok(None),
+        }
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_long_searches_left_in_the_background_notify() {
+        let secs = Duration::from_secs;
+        let notify = SearchingPhase::should_notify;
+        assert!(notify(true, secs(30), secs(10)));
+        assert!(notify(true, secs(3600), secs(3600)));
+        assert!(!notify(false, secs(3600), secs(3600)));
+        assert!(!notify(true, secs(29), secs(3600)));
+        // Switched away just before it finished
+        assert!(!notify(true, secs(3600), secs(9)));
+    }
+}
@@ -0,0 +1,507 @@
+use crate::misc::{Image, Side, data_dir, write_atomic, folder_name, relative_path};
+use crate::encoding::Encoding;
+use crate::content::ContentKind;
+use crate::startup_phase::UserOpts;
+use crate::os_path;
+use crate::review::{Reviews, SessionReview};
+use crate::ignored::{IgnoreList, IgnoredGroup};
+use crate::history::HistoryEntry;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use image_hasher::ImageHash;
+
+use serde::{Serialize, Deserialize};
+
+
+const SESSION_FILE: &str = "session.json";
+
+// Where --headless-scan saves its results, apart from the session, so a
+// scheduled scan doesn't write over results still being reviewed.
+const SCAN_FILE: &str = "scan.json";
+
+// What portable sessions are called by default, in the root.
+pub const PORTABLE_FILE: &str = "deckard-session.json";
+
+// When the last unattended scan the user was offered finished, so it's only
+// offered once.
+const VIEWED_FILE: &str = "scan_viewed";
+
+// Bump when making a change old versions can't be read under; adding fields
+// with defaults doesn't require it.
+const SESSION_VERSION: u32 = 1;
+
+// A saved set of results, so they can be reviewed after restarting.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    #[serde(default)]
+    pub opts: UserOpts,
+    #[serde(default)]
+    pub groups: Vec<Vec<SessionImage>>,
+    #[serde(default)]
+    pub finished: Option<u64>, // When it was saved, seconds since the Unix epoch
+    #[serde(default)]
+    pub unattended: bool, // Saved by --headless-scan, rather than from the results
+    #[serde(default)]
+    pub reviews: Vec<SessionReview>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<PortableRoot>, // Only in portable sessions; see make_portable()
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignored: Vec<Vec<RootedPath>>, // Ignored groups among the roots, in portable sessions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<HistoryEntry>, // What's been done to the results, in this sitting and earlier ones
+}
+
+// Where one of a portable session's roots is, from the session file's folder.
+#[derive(Serialize, Deserialize)]
+pub struct PortableRoot {
+    #[serde(with = "os_path::serde_path")]
+    pub location: PathBuf,
+    pub name: String, // The folder's name when saved, for asking where it's gone
+}
+
+// A path in a portable session, relative to one of its roots.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct RootedPath {
+    pub root: usize,
+    #[serde(with = "os_path::serde_path")]
+    pub path: PathBuf,
+}
+
+// Which of the roots the path is in (the innermost, if they're nested), and
+// the path from there.
+fn rooted(path: &Path, roots: &[PathBuf]) -> Option<RootedPath> {
+    roots.iter()
+        .enumerate()
+        .filter_map(|(root, x)| Some(RootedPath{root, path: path.strip_prefix(x).ok()?.to_path_buf()}))
+        .min_by_key(|x| x.path.components().count())
+}
+
+// The other way, given where the roots are now.
+fn unrooted(root: usize, path: &Path, roots: &[PathBuf]) -> Result<PathBuf, String> {
+    roots.get(root)
+        .map(|x| x.join(path))
+        .ok_or_else(|| format!("The session refers to a root it doesn't have ({root})"))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+// What's shown when offering the results of an unattended scan, without
+// reading every group in.
+#[derive(Deserialize)]
+pub struct ScanSummary {
+    #[serde(default)]
+    pub opts: UserOpts,
+    #[serde(default, rename = "groups", deserialize_with = "count")]
+    pub group_count: usize,
+    #[serde(default)]
+    pub finished: Option<u64>,
+    #[serde(default)]
+    unattended: bool,
+}
+
+fn count<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    Ok(Vec::<serde::de::IgnoredAny>::deserialize(deserializer)?.len())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionImage {
+    #[serde(with = "os_path::serde_path")]
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<usize>, // In portable sessions, which root path is relative to
+    pub file_size: usize,
+    #[serde(default)]
+    pub dimm: Option<(u32, u32)>,
+    #[serde(default)]
+    pub mtime: Option<u64>, // Seconds since the Unix epoch
+    #[serde(default)]
+    pub hash: Option<String>, // Base64
+    #[serde(default)]
+    pub side: Option<Side>,
+    #[serde(default)]
+    pub frame_count: Option<usize>, // Only for animations
+    #[serde(default)]
+    pub taken: Option<u64>, // EXIF capture time, seconds since the Unix epoch
+    #[serde(default)]
+    pub possible_crop: bool,
+    #[serde(default)]
+    pub encoding: Option<Encoding>,
+    #[serde(default)]
+    pub content: Option<ContentKind>,
+}
+
+// As mtimes are kept, in sessions and checkpoints.
+pub fn to_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|x| x.as_secs())
+}
+
+impl SessionImage {
+    pub fn from_image(image: &Image) -> SessionImage {
+        SessionImage {
+            path: image.path.clone(),
+            root: None,
+            file_size: image.file_size,
+            dimm: image.dimm,
+            mtime: image.mtime.and_then(to_secs),
+            hash: image.hash.as_ref().map(ImageHash::to_base64),
+            side: image.side,
+            frame_count: image.is_animated().then_some(image.frame_count),
+            taken: image.taken.and_then(to_secs),
+            possible_crop: image.possible_crop,
+            encoding: image.encoding.clone(),
+            content: image.content,
+        }
+    }
+
+    // None if the file no longer exists.
+    fn into_image(self) -> Option<Image> {
+        let meta = std::fs::metadata(&self.path).ok()?;
+        let mut image = Image::new_lazy(self.path, self.file_size, self.dimm);
+        image.mtime = self.mtime.map(|x| UNIX_EPOCH + Duration::from_secs(x));
+        image.hash = self.hash.and_then(|x| ImageHash::from_base64(&x).ok());
+        image.frame_count = self.frame_count.unwrap_or(1);
+        image.taken = self.taken.map(|x| UNIX_EPOCH + Duration::from_secs(x));
+        image.possible_crop = self.possible_crop;
+        image.encoding = self.encoding;
+        image.content = self.content;
+        image.file_id = crate::misc::FileId::from_metadata(&meta);
+        image.side = self.side;
+        image.changed = meta.len() as usize != self.file_size
+            || (self.mtime.is_some() && meta.modified().ok().and_then(to_secs) != self.mtime);
+        Some(image)
+    }
+}
+
+impl Session {
+    pub fn new(opts: &UserOpts, groups: &[Vec<Image>], reviews: &Reviews) -> Session {
+        Session {
+            version: SESSION_VERSION,
+            opts: opts.clone(),
+            groups: groups.iter()
+                .map(|group| group.iter().map(SessionImage::from_image).collect())
+                .collect(),
+            finished: to_secs(SystemTime::now()),
+            unattended: false,
+            reviews: reviews.to_session(),
+            roots: vec![],
+            ignored: vec![],
+            history: vec![],
+        }
+    }
+
+    // The search's roots: the root, and the reference folder in compare mode.
+    fn search_roots(opts: &UserOpts) -> Vec<PathBuf> {
+        let mut roots = vec![opts.root.clone()];
+        if opts.compare {
+            roots.push(opts.reference.clone());
+        }
+        roots
+    }
+
+    // Rewrites every path relative to the root it's in, and records where the
+    // roots are relative to dir, where the session file's going. As long as
+    // the file travels with the roots (e.g. it's on the same drive), it opens
+    // wherever they're mounted next; see load_portable(). The ignored groups
+    // among the roots go along, so they stay ignored there.
+    pub fn make_portable(&mut self, dir: &Path, ignore_list: &IgnoreList) -> Result<(), String> {
+        let roots = Self::search_roots(&self.opts);
+        let dir = canonical(dir);
+        self.roots = roots.iter()
+            .map(|root| {
+                let location = relative_path(&canonical(root), &dir).ok_or_else(|| format!(
+                    "{} can't be reached from {} by a relative path",
+                    root.display(),
+                    dir.display(),
+                ))?;
+                Ok(PortableRoot{location, name: folder_name(root)})
+            })
+            .collect::<Result<_, String>>()?;
+
+        // Anything outside the roots stays absolute, for want of anything
+        // better.
+        for image in self.groups.iter_mut().flatten() {
+            if let Some(x) = rooted(&image.path, &roots) {
+                (image.root, image.path) = (Some(x.root), x.path);
+            }
+        }
+        for review in &mut self.reviews {
+            if let Some(x) = rooted(&review.path, &roots) {
+                (review.root, review.path) = (Some(x.root), x.path);
+            }
+        }
+        // Ignored paths are canonical.
+        let canonical_roots: Vec<_> = roots.iter().map(|x| canonical(x)).collect();
+        self.ignored = ignore_list.groups.iter()
+            .filter_map(|group| group.paths.iter().map(|x| rooted(x, &canonical_roots)).collect())
+            .collect();
+
+        let root = &self.opts.root;
+        self.opts.subfolders = self.opts.subfolders.iter()
+            .map(|x| x.strip_prefix(root).map_or_else(|_| x.clone(), Path::to_path_buf))
+            .collect();
+        self.opts.root = PathBuf::new();
+        if self.opts.compare {
+            self.opts.reference = PathBuf::new();
+        }
+        Ok(())
+    }
+
+    // Opens a session saved by make_portable() (or a plain one, which is
+    // returned as it is), wherever it's been moved along with its roots. Each
+    // root is looked for where it was relative to the file; failing that,
+    // locate is asked where it is, given its name, and None gives up. Returns
+    // the session with absolute paths again, and the ignored groups it
+    // brought along.
+    pub fn load_portable(
+        path: &Path,
+        mut locate: impl FnMut(&str) -> Option<PathBuf>,
+    ) -> Result<(Session, Vec<IgnoredGroup>), String> {
+        let mut session = Self::load_from(path)?;
+        if session.roots.is_empty() {
+            return Ok((session, vec![]));
+        }
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let roots = std::mem::take(&mut session.roots).into_iter()
+            .map(|root| match dir.join(&root.location).canonicalize() {
+                Ok(x) if x.is_dir() => Ok(x),
+                _ => locate(&root.name).ok_or_else(|| format!("Couldn't find {}", root.name)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        for image in session.groups.iter_mut().flatten() {
+            if let Some(root) = image.root.take() {
+                image.path = unrooted(root, &image.path, &roots)?;
+            }
+        }
+        for review in &mut session.reviews {
+            if let Some(root) = review.root.take() {
+                review.path = unrooted(root, &review.path, &roots)?;
+            }
+        }
+        let ignored = std::mem::take(&mut session.ignored).into_iter()
+            .map(|group| {
+                let paths = group.iter().map(|x| unrooted(x.root, &x.path, &roots)).collect::<Result<_, _>>()?;
+                Ok(IgnoredGroup{paths})
+            })
+            .collect::<Result<_, String>>()?;
+
+        let opts = &mut session.opts;
+        opts.root = roots[0].clone();
+        opts.subfolders = opts.subfolders.iter().map(|x| opts.root.join(x)).collect();
+        if opts.compare {
+            opts.reference = roots.get(1).cloned().ok_or("The session is missing its reference folder")?;
+        }
+        Ok((session, ignored))
+    }
+
+    fn path() -> Result<PathBuf, String> {
+        Ok(data_dir()?.join(SESSION_FILE))
+    }
+
+    fn scan_path() -> Result<PathBuf, String> {
+        Ok(data_dir()?.join(SCAN_FILE))
+    }
+
+    // The last unattended scan's results, if the user hasn't been offered
+    // them yet.
+    pub fn unviewed_scan() -> Option<ScanSummary> {
+        let json = std::fs::read(Self::scan_path().ok()?).ok()?;
+        let summary: ScanSummary = serde_json::from_slice(&json).ok()?;
+        let viewed = std::fs::read_to_string(data_dir().ok()?.join(VIEWED_FILE)).ok()
+            .and_then(|x| x.trim().parse::<u64>().ok());
+        (summary.unattended && summary.finished.is_some() && summary.finished != viewed).then_some(summary)
+    }
+
+    // Stops offering the unattended scan that finished then, whether it was
+    // viewed or dismissed.
+    pub fn mark_viewed(finished: Option<u64>) {
+        let Some(finished) = finished else {
+            return;
+        };
+        if let Ok(dir) = data_dir() {
+            // At worst it's offered again next launch.
+            let _ = write_atomic(&dir.join(VIEWED_FILE), finished.to_string().as_bytes());
+        }
+    }
+
+    pub fn exists() -> bool {
+        Self::path().is_ok_and(|x| x.exists())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&Self::path()?)
+    }
+
+    // As an unattended scan's results; see unviewed_scan().
+    pub fn save_scan(&self) -> Result<(), String> {
+        self.save_to(&Self::scan_path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| format!("Error serializing session: {e}"))?;
+        write_atomic(path, &json)
+    }
+
+    pub fn load() -> Result<Session, String> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn load_scan() -> Result<Session, String> {
+        Self::load_from(&Self::scan_path()?)
+    }
+
+    fn load_from(path: &Path) -> Result<Session, String> {
+        let json = std::fs::read(path)
+            .map_err(|e| format!("Error reading {}: {e}", path.display()))?;
+        let mut session: Session = serde_json::from_slice(&json)
+            .map_err(|e| format!("Error parsing {}: {e}", path.display()))?;
+        session.opts.clamp_center_crop();
+        if session.version > SESSION_VERSION {
+            return Err(format!(
+                "The session was saved by a newer version of Deckard (format {})",
+                session.version,
+            ));
+        }
+        Ok(session)
+    }
+
+    // Converts back to groups of images, dropping files that no longer exist
+    // and groups left with fewer than two members. Images' contents are only
+    // read once they're displayed.
+    pub fn into_groups(self) -> (UserOpts, Vec<Vec<Image>>, Reviews) {
+        let groups = self.groups.into_iter()
+            .map(|group| group.into_iter().filter_map(SessionImage::into_image).collect::<Vec<_>>())
+            .filter(|group| group.len() >= 2)
+            .collect();
+        (self.opts, groups, Reviews::from_session(self.reviews))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::review::Mark;
+
+    // A compare-mode search of a drive's Photos against its Archive, as
+    // mounted on one computer.
+    fn mount(mount: &Path) -> (UserOpts, Vec<Vec<Image>>) {
+        let (photos, archive) = (mount.join("Photos"), mount.join("Archive"));
+        for dir in [photos.join("2023"), archive.clone()] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let group: Vec<Image> = [photos.join("2023/a.jpg"), archive.join("a.jpg")].into_iter()
+            .map(|path| {
+                std::fs::write(&path, b"jpeg").unwrap();
+                Image::new_lazy(canonical(&path), 4, None)
+            })
+            .collect();
+        let mut opts = UserOpts::new(canonical(&photos));
+        opts.compare = true;
+        opts.reference = canonical(&archive);
+        opts.subfolders = vec![opts.root.join("2023")];
+        (opts, vec![group])
+    }
+
+    #[test]
+    fn portable_sessions_move_with_their_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let (desktop, laptop) = (dir.path().join("Volumes/Drive"), dir.path().join("media/user/Drive"));
+        let (opts, groups) = mount(&desktop);
+        let mut reviews = Reviews::default();
+        reviews.update(&groups[0], |x| x.mark = Mark::Kept);
+        let ignore_list = IgnoreList{groups: vec![IgnoredGroup::new(&groups[0])], ..IgnoreList::default()};
+
+        let file = opts.root.join(PORTABLE_FILE);
+        let mut session = Session::new(&opts, &groups, &reviews);
+        session.make_portable(&opts.root, &ignore_list).unwrap();
+        assert_eq!(session.roots[1].location, Path::new("../Archive"));
+        session.save_to(&file).unwrap();
+
+        // The same drive, mounted somewhere else.
+        std::fs::create_dir_all(laptop.parent().unwrap()).unwrap();
+        std::fs::rename(&desktop, &laptop).unwrap();
+        let (session, ignored) = Session::load_portable(&laptop.join("Photos").join(PORTABLE_FILE), |_| {
+            panic!("both roots should be found")
+        }).unwrap();
+        let (opts, groups, reviews) = session.into_groups();
+
+        let laptop = canonical(&laptop);
+        assert_eq!(opts.root, laptop.join("Photos"));
+        assert_eq!(opts.reference, laptop.join("Archive"));
+        assert_eq!(opts.subfolders, [laptop.join("Photos/2023")]);
+        let paths: Vec<_> = groups[0].iter().map(|x| x.path.clone()).collect();
+        assert_eq!(paths, [laptop.join("Photos/2023/a.jpg"), laptop.join("Archive/a.jpg")]);
+        assert_eq!(reviews.mark(&groups[0]), Mark::Kept);
+        assert!(ignored[0].paths.iter().eq(paths.iter().collect::<std::collections::BTreeSet<_>>()));
+    }
+
+    #[test]
+    fn moved_roots_are_asked_for() {
+        let dir = tempfile::tempdir().unwrap();
+        let (opts, groups) = mount(&dir.path().join("Drive"));
+        let file = dir.path().join("session.json");
+        let mut session = Session::new(&opts, &groups, &Reviews::default());
+        session.make_portable(dir.path(), &IgnoreList::default()).unwrap();
+        session.save_to(&file).unwrap();
+
+        // Only the archive moved.
+        let moved = dir.path().join("Elsewhere");
+        std::fs::rename(&opts.reference, &moved).unwrap();
+        let mut asked = vec![];
+        let (session, _) = Session::load_portable(&file, |name| {
+            asked.push(name.to_string());
+            Some(moved.clone())
+        }).unwrap();
+        assert_eq!(asked, ["Archive"]);
+        assert_eq!(session.opts.root, opts.root);
+        assert_eq!(session.groups[0][1].path, moved.join("a.jpg"));
+
+        assert!(Session::load_portable(&file, |_| None).is_err());
+    }
+
+    #[test]
+    fn loaded_central_regions_are_clamped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut opts, groups) = mount(&dir.path().join("Drive"));
+        opts.center_crop = 5;
+        let file = dir.path().join("session.json");
+        Session::new(&opts, &groups, &Reviews::default()).save_to(&file).unwrap();
+        assert_eq!(Session::load_from(&file).unwrap().opts.center_crop, 50);
+    }
+
+    #[test]
+    fn unattended_scans_are_offered_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (opts, groups) = mount(dir.path());
+
+        // Summaries count the groups without reading them in.
+        let session = Session::new(&opts, &groups, &Reviews::default());
+        let summary: ScanSummary = serde_json::from_slice(&serde_json::to_vec(&session).unwrap()).unwrap();
+        assert_eq!((summary.group_count, summary.finished, summary.unattended), (1, session.finished, false));
+        assert_eq!(summary.opts.root, opts.root);
+
+        let scan = |finished| {
+            let mut session = Session::new(&opts, &groups, &Reviews::default());
+            session.unattended = true;
+            session.finished = Some(finished);
+            session.save_scan().unwrap();
+        };
+        scan(1000);
+        assert_eq!(Session::unviewed_scan().unwrap().finished, Some(1000));
+        assert_eq!(Session::load_scan().unwrap().groups.len(), 1);
+        Session::mark_viewed(Some(1000));
+        assert!(Session::unviewed_scan().is_none());
+        // The next night's is new.
+        scan(2000);
+        assert_eq!(Session::unviewed_scan().unwrap().group_count, 1);
+        Session::mark_viewed(Some(2000));
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{MIN_ZOOM, MAX_ZOOM};
+use crate::startup_phase::{UserOpts, RecentRoot};
+use crate::output_phase::{ResultsLayout, ResultsDensity};
+use crate::window::WindowGeometry;
+use crate::i18n::{Language, SizeUnits};
+use crate::theme::Theme;
+use crate::os_path;
+use crate::clicks;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+
+// Everything kept between launches in eframe's storage, which is a string per
+// key, and all the reading and writing of it. Values are checked on the way
+// in: one that doesn't parse, or is out of the range the UI allows, reads as
+// missing, so whatever's using it falls back to its default, and is listed by
+// invalid() so the user can be told. Storage is also stamped with the format
+// it was written in, so values from older versions can be converted as
+// they're read (see migrate()), and ones from newer versions aren't
+// overwritten with something they can't read.
+
+// Bump when a value's format changes, and convert the old one in migrate().
+// Storage from before there was a version counts as 0.
+const VERSION: u32 = 1;
+const VERSION_KEY: &str = "APP_SETTINGS_VERSION";
+
+// Where eframe keeps its storage; see StoredSettings.
+pub const APP_ID: &str = "Deckard";
+
+pub trait Setting: Sized {
+    fn decode(text: &str) -> Option<Self>;
+    fn encode(&self) -> String;
+}
+
+pub struct Key<T> {
+    name: &'static str,
+    label: &'static str, // For telling the user it was reset
+    valid: fn(&T) -> bool,
+}
+
+impl<T> Key<T> {
+    const fn new(name: &'static str, label: &'static str) -> Key<T> {
+        Key{name, label, valid: |_| true}
+    }
+
+    const fn checked(name: &'static str, label: &'static str, valid: fn(&T) -> bool) -> Key<T> {
+        Key{name, label, valid}
+    }
+}
+
+pub const ROOT: Key<PathBuf> = Key::checked("STARTUPPHASE_ROOT", "Folder to search", |x| {
+    // A corrupted value can still decode, as a path nobody would have. Paths
+    // that aren't UTF-8 are stored raw, so never hold replacement characters
+    // as text.
+    let garbled = |s: &str| s.contains(char::REPLACEMENT_CHARACTER) || s.contains(char::is_control);
+    x.is_absolute() && !x.to_str().is_some_and(garbled)
+});
+pub const LAYOUT: Key<ResultsLayout> = Key::new("OUTPUTPHASE_LAYOUT", "Results layout");
+pub const DENSITY: Key<ResultsDensity> = Key::new("OUTPUTPHASE_DENSITY", "Results density");
+pub const IO_THREADS: Key<usize> = Key::checked("STARTUPPHASE_IO_THREADS", "I/O threads", |x| (1..=256).contains(x));
+pub const QUEUE_SIZE: Key<usize> = Key::checked("STARTUPPHASE_QUEUE_SIZE", "Walk ahead", |x| (16..=1_000_000).contains(x));
+pub const NOTIFY: Key<bool> = Key::new("SEARCHINGPHASE_NOTIFY", "Notify when done");
+pub const PORTABLE: Key<bool> = Key::new("OUTPUTPHASE_PORTABLE", "Portable reports");
+pub const FOLDER_PANEL: Key<bool> = Key::new("OUTPUTPHASE_FOLDER_PANEL", "Folder panel");
+pub const FOLDER_DEPTH: Key<usize> = Key::checked("OUTPUTPHASE_FOLDER_DEPTH", "Folder panel depth", |x| *x <= 16);
+pub const HISTORY_PANEL: Key<bool> = Key::new("OUTPUTPHASE_HISTORY_PANEL", "History panel");
+pub const ADVANCED_OPEN: Key<bool> = Key::new("STARTUPPHASE_ADVANCED_OPEN", "Advanced options");
+pub const JUNK_FILTER: Key<bool> = Key::new("STARTUPPHASE_JUNK_FILTER", "Hide system images");
+pub const CHECK_OPENS: Key<bool> = Key::new("OUTPUTPHASE_CHECK_OPENS", "Check before opening");
+pub const LARGE_GROUP: Key<usize> = Key::checked("OUTPUTPHASE_LARGE_GROUP", "Large group size", |x| *x <= 10_000);
+pub const THUMB_MEMORY: Key<u64> = Key::checked("OUTPUTPHASE_THUMB_MEMORY", "Thumbnail memory", |x| (64..=8192).contains(x));
+pub const THUMB_USAGE: Key<bool> = Key::new("OUTPUTPHASE_SHOW_THUMB_USAGE", "Show thumbnail memory use");
+// Their numbers are held to the same ranges as the settings of their own.
+pub const PRESETS: Key<BTreeMap<String, UserOpts>> = Key::checked("STARTUPPHASE_PRESETS", "Presets", |x| {
+    x.values().all(|x| {
+        (IO_THREADS.valid)(&x.io_threads)
+            && (QUEUE_SIZE.valid)(&x.queue_size)
+            && (THUMB_MEMORY.valid)(&x.thumb_memory_mb)
+            && (FOLDER_DEPTH.valid)(&x.folder_depth)
+            && (LARGE_GROUP.valid)(&x.large_group)
+            && UserOpts::CENTER_CROP.contains(&x.center_crop)
+    })
+});
+pub const LAST_PRESET: Key<String> = Key::new("STARTUPPHASE_LAST_PRESET", "Last preset");
+pub const RECENT_ROOTS: Key<Vec<RecentRoot>> = Key::new("STARTUPPHASE_RECENT_ROOTS", "Recent folders");
+pub const WELCOME: Key<bool> = Key::new("STARTUPPHASE_SHOW_WELCOME", "Welcome screen");
+pub const ZOOM: Key<f32> = Key::checked("APP_ZOOM", "Zoom", |x| (MIN_ZOOM..=MAX_ZOOM).contains(x));
+pub const LANGUAGE: Key<Language> = Key::new("APP_LANGUAGE", "Language");
+pub const UNITS: Key<SizeUnits> = Key::new("APP_SIZE_UNITS", "Size units");
+pub const THEME: Key<Theme> = Key::new("APP_THEME", "Theme");
+pub const CLICKS: Key<String> = Key::checked("APP_CLICK_ACTIONS", "Click actions", |x| clicks::is_valid_key(x));
+pub const WINDOW: Key<WindowGeometry> = Key::checked("APP_WINDOW", "Window size and position", WindowGeometry::is_sane);
+
+// Every key, for invalid().
+trait Check {
+    fn name(&self) -> &'static str;
+    fn label(&self) -> &'static str;
+    fn accepts(&self, text: &str) -> bool;
+}
+
+impl<T: Setting> Check for Key<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn label(&self) -> &'static str {
+        self.label
+    }
+
+    fn accepts(&self, text: &str) -> bool {
+        T::decode(text).is_some_and(|x| (self.valid)(&x))
+    }
+}
+
+const ALL: &[&dyn Check] = &[
+    &ROOT, &LAYOUT, &DENSITY, &IO_THREADS, &QUEUE_SIZE, &NOTIFY, &PORTABLE, &FOLDER_PANEL,
+    &FOLDER_DEPTH, &HISTORY_PANEL, &ADVANCED_OPEN, &JUNK_FILTER, &CHECK_OPENS, &LARGE_GROUP,
+    &THUMB_MEMORY, &THUMB_USAGE, &PRESETS, &LAST_PRESET, &RECENT_ROOTS, &WELCOME, &ZOOM,
+    &LANGUAGE, &UNITS, &THEME, &CLICKS, &WINDOW,
+];
+
+// The format the storage was written in.
+fn stored_version(storage: &dyn eframe::Storage) -> u32 {
+    storage.get_string(VERSION_KEY).and_then(|x| x.parse().ok()).unwrap_or(0)
+}
+
+// Converts a value stored by an older version to the current format. Nothing
+// has changed yet; version 0 is the same format without the stamp.
+fn migrate(_from: u32, _name: &str, text: String) -> String {
+    text
+}
+
+// The value as stored, in the current format.
+fn raw(storage: &dyn eframe::Storage, name: &str) -> Option<String> {
+    let text = storage.get_string(name)?;
+    Some(migrate(stored_version(storage), name, text))
+}
+
+// None if it isn't stored, or isn't valid.
+pub fn get<T: Setting>(storage: &dyn eframe::Storage, key: Key<T>) -> Option<T> {
+    T::decode(&raw(storage, key.name)?).filter(|x| (key.valid)(x))
+}
+
+// Callers check is_newer() first; see App::save().
+pub fn set<T: Setting>(storage: &mut dyn eframe::Storage, key: Key<T>, value: &T) {
+    storage.set_string(VERSION_KEY, VERSION.to_string());
+    storage.set_string(key.name, value.encode());
+}
+
+// Whether the storage was written by a newer version, in a format this one
+// may not know, so shouldn't be written over.
+pub fn is_newer(storage: &dyn eframe::Storage) -> bool {
+    stored_version(storage) > VERSION
+}
+
+// What's stored but can't be used, and so was reset to its default, as labels
+// for the user.
+pub fn invalid(storage: &dyn eframe::Storage) -> Vec<&'static str> {
+    if is_newer(storage) {
+        return vec![];
+    }
+    ALL.iter()
+        .filter(|x| raw(storage, x.name()).is_some_and(|text| !x.accepts(&text)))
+        .map(|x| x.label())
+        .collect()
+}
+
+// Whether eframe will be able to save the settings, by writing a file where
+// it keeps them. eframe doesn't say when its own saves fail, so otherwise
+// nothing would.
+pub fn probe_write() -> Result<(), String> {
+    let dir = eframe::storage_dir(APP_ID).ok_or("There's no folder for app settings on this system")?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Error creating {}: {e}", dir.display()))?;
+    let probe = dir.join(".write_probe");
+    std::fs::write(&probe, b"")
+        .map_err(|e| format!("Error writing to {}: {e}", dir.display()))?;
+    let _ = std::fs::remove_file(&probe);
+
+    // The folder can be writable and the file itself not.
+    let file = dir.join("app.ron");
+    if file.exists() {
+        std::fs::OpenOptions::new().append(true).open(&file)
+            .map_err(|e| format!("Error opening {}: {e}", file.display()))?;
+    }
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl Setting for bool {
+    fn decode(text: &str) -> Option<bool> {
+        text.parse().ok()
+    }
+
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Setting for usize {
+    fn decode(text: &str) -> Option<usize> {
+        text.parse().ok()
+    }
+
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Setting for u64 {
+    fn decode(text: &str) -> Option<u64> {
+        text.parse().ok()
+    }
+
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Setting for f32 {
+    fn decode(text: &str) -> Option<f32> {
+        text.parse().ok().filter(|x: &f32| x.is_finite())
+    }
+
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Setting for String {
+    fn decode(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    fn encode(&self) -> String {
+        self.clone()
+    }
+}
+
+impl Setting for PathBuf {
+    // Has to come back out exactly as it went in.
+    fn decode(text: &str) -> Option<PathBuf> {
+        os_path::decode(text).filter(|x| os_path::encode(x) == text)
+    }
+
+    fn encode(&self) -> String {
+        os_path::encode(self)
+    }
+}
+
+macro_rules! keyed_setting {
+    ($($ty:ty),+) => {$(
+        impl Setting for $ty {
+            fn decode(text: &str) -> Option<$ty> {
+                <$ty>::from_key(text)
+            }
+
+            fn encode(&self) -> String {
+                self.to_key().to_string()
+            }
+        }
+    )+};
+}
+
+keyed_setting!(ResultsLayout, ResultsDensity, Language, SizeUnits, Theme);
+
+macro_rules! json_setting {
+    ($($ty:ty),+) => {$(
+        impl Setting for $ty {
+            fn decode(text: &str) -> Option<$ty> {
+                from_json(text)
+            }
+
+            fn encode(&self) -> String {
+                to_json(self)
+            }
+        }
+    )+};
+}
+
+json_setting!(BTreeMap<String, UserOpts>, Vec<RecentRoot>, WindowGeometry);
+
+fn from_json<T: DeserializeOwned>(text: &str) -> Option<T> {
+    serde_json::from_str(text).ok()
+}
+
+// Everything stored this way serializes infallibly.
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Memory(HashMap<String, String>);
+
+    impl eframe::Storage for Memory {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn stored(values: &[(&str, &str)]) -> Memory {
+        Memory(values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn malformed_values_read_as_missing_and_are_listed() {
+        let storage = stored(&[
+            ("STARTUPPHASE_ROOT", "/home/\u{FFFD}\u{FFFD}/Pictures"),
+            ("OUTPUTPHASE_LAYOUT", "sideways"),
+            ("STARTUPPHASE_IO_THREADS", "0"),
+            ("STARTUPPHASE_QUEUE_SIZE", "-3"),
+            ("OUTPUTPHASE_FOLDER_PANEL", "yes"),
+            ("APP_ZOOM", "NaN"),
+            ("STARTUPPHASE_PRESETS", "{\"unfinished\": "),
+            ("APP_CLICK_ACTIONS", "open,explode"),
+            ("APP_WINDOW", "{\"size\":[0,0],\"position\":null,\"monitor\":null,\"maximized\":false}"),
+            // Fine
+            ("OUTPUTPHASE_DENSITY", "compact"),
+            ("OUTPUTPHASE_THUMB_MEMORY", "512"),
+        ]);
+        assert_eq!(get(&storage, ROOT), None);
+        assert_eq!(get(&storage, LAYOUT), None);
+        assert_eq!(get(&storage, IO_THREADS), None);
+        assert_eq!(get(&storage, QUEUE_SIZE), None);
+        assert_eq!(get(&storage, FOLDER_PANEL), None);
+        assert_eq!(get(&storage, ZOOM), None);
+        assert!(get(&storage, PRESETS).is_none());
+        assert_eq!(get(&storage, CLICKS), None);
+        assert_eq!(get(&storage, WINDOW), None);
+        assert_eq!(get(&storage, DENSITY), Some(ResultsDensity::Compact));
+        assert_eq!(get(&storage, THUMB_MEMORY), Some(512));
+        assert_eq!(get(&storage, NOTIFY), None);
+
+        assert_eq!(invalid(&storage), vec![
+            "Folder to search", "Results layout", "I/O threads", "Walk ahead", "Folder panel",
+            "Presets", "Zoom", "Click actions", "Window size and position",
+        ]);
+
+        // Presets that parse, but with numbers the form wouldn't allow
+        let preset = |change: fn(&mut UserOpts)| {
+            let mut opts = UserOpts::new(PathBuf::from("/photos"));
+            change(&mut opts);
+            let mut storage = Memory::default();
+            set(&mut storage, PRESETS, &BTreeMap::from([("weekly".to_string(), opts)]));
+            storage
+        };
+        assert!(get(&preset(|_| ()), PRESETS).is_some());
+        for change in [
+            |x: &mut UserOpts| x.io_threads = 0,
+            |x: &mut UserOpts| x.queue_size = 1,
+            |x: &mut UserOpts| x.thumb_memory_mb = 1 << 20,
+            |x: &mut UserOpts| x.center_crop = 10,
+        ] {
+            let storage = preset(change);
+            assert!(get(&storage, PRESETS).is_none());
+            assert_eq!(invalid(&storage), vec!["Presets"]);
+        }
+    }
+
+    #[test]
+    fn paths_must_round_trip() {
+        let mut storage = Memory::default();
+        let root = PathBuf::from("/photos/2021");
+        set(&mut storage, ROOT, &root);
+        assert_eq!(get(&storage, ROOT), Some(root));
+        assert!(invalid(&storage).is_empty());
+
+        // Marked as raw, but not valid hex, or relative
+        for text in ["\0zz", "photos"] {
+            let storage = stored(&[("STARTUPPHASE_ROOT", text)]);
+            assert_eq!(get(&storage, ROOT), None);
+            assert_eq!(invalid(&storage), vec!["Folder to search"]);
+        }
+    }
+
+    #[test]
+    fn versions_are_stamped_and_newer_ones_left_alone() {
+        let mut storage = Memory::default();
+        assert!(!is_newer(&storage));
+        set(&mut storage, NOTIFY, &true);
+        assert_eq!(storage.0[VERSION_KEY], VERSION.to_string());
+        assert_eq!(get(&storage, NOTIFY), Some(true));
+
+        let storage = stored(&[(VERSION_KEY, "999"), ("OUTPUTPHASE_LAYOUT", "hexagons")]);
+        assert!(is_newer(&storage));
+        assert!(invalid(&storage).is_empty());
+    }
+}
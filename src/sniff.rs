@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use image::ImageFormat;
+
+
+// What a file's first bytes say it is, for telling when its extension's
+// wrong. Opening a file goes by its extension, or by whatever the OS makes of
+// it, so a "photo" that's really a program or a web page is worth a second
+// look before it's launched.
+
+// Enough for image::guess_format(), which only looks at magic numbers, and for
+// the markup checks below.
+const SNIFF_BYTES: u64 = 4096;
+
+// The first few KB of the file, so directories of big non-images stay cheap.
+pub fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut header = vec![];
+    std::fs::File::open(path)?.take(SNIFF_BYTES).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileType {
+    Image(ImageFormat),
+    WindowsExecutable,
+    MachO,
+    Elf,
+    Script, // Starts with #!
+    Html,
+    Svg, // Which browsers open, running any scripts in it
+}
+
+impl FileType {
+    // Only the start of the buffer is looked at.
+    pub fn detect(buffer: &[u8]) -> Option<FileType> {
+        const MACH_O: [[u8; 4]; 5] = [
+            [0xFE, 0xED, 0xFA, 0xCE], [0xFE, 0xED, 0xFA, 0xCF],
+            [0xCE, 0xFA, 0xED, 0xFE], [0xCF, 0xFA, 0xED, 0xFE],
+            [0xCA, 0xFE, 0xBA, 0xBE], // Universal binary
+        ];
+        if buffer.starts_with(b"MZ") {
+            return Some(FileType::WindowsExecutable);
+        }
+        if buffer.starts_with(b"\x7FELF") {
+            return Some(FileType::Elf);
+        }
+        if MACH_O.iter().any(|x| buffer.starts_with(x)) {
+            return Some(FileType::MachO);
+        }
+        if buffer.starts_with(b"#!") {
+            return Some(FileType::Script);
+        }
+        if let Ok(format) = image::guess_format(buffer) {
+            return Some(FileType::Image(format));
+        }
+
+        // Markup can start with a byte order mark and whitespace, and has
+        // no fixed case.
+        let head = &buffer[..buffer.len().min(SNIFF_BYTES as usize)];
+        let text = String::from_utf8_lossy(head).to_lowercase();
+        let text = text.trim_start_matches('\u{FEFF}').trim_start();
+        if ["<!doctype html", "<html", "<head", "<body", "<script"].iter().any(|x| text.starts_with(x)) {
+            return Some(FileType::Html);
+        }
+        if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+            return Some(FileType::Svg);
+        }
+        None
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            FileType::Image(format) => {
+                let name = format.extensions_str().first().copied().unwrap_or("unknown");
+                tr!("{format} image", format = name.to_uppercase())
+            },
+            FileType::WindowsExecutable => tr!("Windows program").to_string(),
+            FileType::MachO => tr!("macOS program").to_string(),
+            FileType::Elf => tr!("Linux program").to_string(),
+            FileType::Script => tr!("script").to_string(),
+            FileType::Html => tr!("web page").to_string(),
+            FileType::Svg => tr!("SVG image").to_string(),
+        }
+    }
+}
+
+// What the file's contents say it is, if that's not what its extension says.
+// Anything that isn't an image is a mismatch, since only images are looked
+// for; an image is one if the extension names a different format. None if
+// the contents can't be told, which is usual for formats the image crate
+// doesn't know.
+pub fn mismatch(path: &Path, buffer: &[u8]) -> Option<FileType> {
+    let detected = FileType::detect(buffer)?;
+    let FileType::Image(format) = detected else {
+        return Some(detected);
+    };
+    let expected = ImageFormat::from_path(path).ok()?;
+    (expected != format).then_some(detected)
+}
+
+// Asks before opening a file whose contents don't match its extension. True
+// to go ahead: they match, can't be read, or the user chose to open it
+// anyway.
+pub fn confirm_contents(path: &Path) -> bool {
+    let Some(detected) = read_header(path).ok().and_then(|x| mismatch(path, &x)) else {
+        return true;
+    };
+    let answer = rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title(tr!("Contents don't match extension"))
+        .set_description(tr!(
+            "{path} looks like a {detected}, not what its extension says. Opening it may run it or \
+            show it in a browser. Open it anyway?",
+            path = path.display(),
+            detected = detected.label(),
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show();
+    answer == rfd::MessageDialogResult::Yes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_that_lie_are_caught() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(mismatch(Path::new("a.png"), png), None);
+        assert_eq!(mismatch(Path::new("a.jpg"), png), Some(FileType::Image(ImageFormat::Png)));
+        assert_eq!(mismatch(Path::new("a.heic"), png), None);
+        assert_eq!(mismatch(Path::new("a.jpg"), b"MZ\x90\0\x03\0"), Some(FileType::WindowsExecutable));
+        assert_eq!(mismatch(Path::new("a.jpg"), b"\xEF\xBB\xBF\n  <!DOCTYPE HTML><html>"), Some(FileType::Html));
+        assert_eq!(mismatch(Path::new("a.jpg"), b"<?xml version=\"1.0\"?>\n<svg onload=\"\">"), Some(FileType::Svg));
+        assert_eq!(mismatch(Path::new("a.jpg"), b"just some text"), None);
+    }
+}
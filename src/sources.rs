@@ -0,0 +1,345 @@
+use crate::searcher::Decoded;
+use crate::animation;
+
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, ImageResult};
+
+
+// What reads each format, so the searcher, thumbnails and Image::load() ask
+// for a file to be decoded without knowing what's in it. The first source in
+// SOURCES whose probe() takes a file reads it, and the extensions offered and
+// accepted (FORMAT_FAMILIES and SUPPORTED_EXTS) are whatever the sources say
+// they read. Formats that need a decoder crate of their own (HEIC, camera raw,
+// video frames) go in as sources of their own, behind a Cargo feature.
+//
+// TODO: HEIC, camera raw and video frame sources. Each needs a decoder crate
+// (and for HEIC and video, a C library) that isn't a dependency yet.
+//
+// Sources only know their formats. What's worth decoding, and how much memory
+// it can have, is the searcher's call; see searcher::decode().
+
+// A name, as offered in the form, and its extensions, lowercase.
+pub type Family = (&'static str, &'static [&'static str]);
+
+pub trait ImageSource: Sync {
+    // In the order they're offered.
+    fn families(&self) -> &'static [Family];
+
+    // Whether it reads the file, going by its path and first bytes (at least
+    // sniff::HEADER_LEN of them, if the file's that long).
+    fn probe(&self, path: &Path, header: &[u8]) -> bool;
+
+    // Which of its families the header alone says the file is in, whatever
+    // its extension; for files included by their contents.
+    fn sniff(&self, header: &[u8]) -> Option<&'static Family> {
+        let format = image::guess_format(header).ok()?;
+        self.families().iter()
+            .find(|(_, exts)| exts.iter().any(|x| ImageFormat::from_extension(x) == Some(format)))
+    }
+
+    // From the header, without decoding anything.
+    fn dimensions(&self, path: &Path, buffer: &[u8]) -> ImageResult<(u32, u32)>;
+
+    // Roughly what decoding it at full size takes, per pixel.
+    fn bytes_per_pixel(&self, path: &Path, buffer: &[u8]) -> u64;
+
+    // Whether decode_for_hash() can scale while decoding, which is much
+    // faster than decoding in full and then scaling.
+    fn scales(&self) -> bool {
+        false
+    }
+
+    fn decode_for_hash(&self, path: &Path, buffer: &[u8], request: DecodeRequest) -> ImageResult<Decoded>;
+
+    // Whole, or an animation's first frame.
+    fn load_for_display(&self, path: &Path, buffer: &[u8]) -> ImageResult<DynamicImage>;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DecodeRequest {
+    // About the size to decode to, for sources that scale(); others decode
+    // in full, leaving scaling to the caller. None for full size. Animations
+    // come back as their sampled frames either way, scaled down to fit it.
+    pub size: Option<(u32, u32)>,
+    pub max_bytes: u64, // For the decoders, in case the caller's estimate is low
+}
+
+// Decodes JPEGs scaled by a power of two as they're read, which is where
+// most of a scan's time would otherwise go.
+struct Jpeg;
+
+// Everything else the image crate reads, animations included. Last, so it
+// also gets files nothing else takes, to say what's wrong with them.
+struct ImageCrate;
+
+static SOURCES: &[&dyn ImageSource] = &[&Jpeg, &ImageCrate];
+
+// Frames kept from an animation for hashing, evenly spaced.
+const SAMPLED_FRAMES: usize = 4;
+
+// Frames in memory at once while sampling: the kept ones, the one being
+// decoded, and the canvas the decoder composes it on.
+const HELD_FRAMES: u64 = SAMPLED_FRAMES as u64 + 2;
+
+pub fn all() -> &'static [&'static dyn ImageSource] {
+    SOURCES
+}
+
+// The source to read the file with. The buffer can be the whole file, or
+// just its start.
+pub fn for_file(path: &Path, header: &[u8]) -> &'static dyn ImageSource {
+    SOURCES.iter()
+        .find(|x| x.probe(path, header))
+        .copied()
+        .unwrap_or(*SOURCES.last().unwrap())
+}
+
+// The family the contents say the file's in, by whichever source sniffs it.
+pub fn sniff(header: &[u8]) -> Option<&'static Family> {
+    SOURCES.iter().find_map(|x| x.sniff(header))
+}
+
+// The format the contents say they're in, falling back to the one the
+// extension implies if they're inconclusive (so a JPEG named .png still
+// works, and TGA, which has no magic number, works at all).
+fn format_of(path: &Path, header: &[u8]) -> Option<ImageFormat> {
+    image::guess_format(header).ok().or_else(|| ImageFormat::from_path(path).ok())
+}
+
+fn reader<'a>(path: &Path, buffer: &'a [u8]) -> ImageResult<image::io::Reader<Cursor<&'a [u8]>>> {
+    let mut reader = image::io::Reader::new(Cursor::new(buffer));
+    if let Ok(format) = ImageFormat::from_path(path) {
+        reader.set_format(format);
+    }
+    Ok(reader.with_guessed_format()?)
+}
+
+fn limits(max_bytes: u64) -> image::io::Limits {
+    let mut limits = image::io::Limits::default();
+    limits.max_alloc = Some(max_bytes);
+    limits
+}
+
+fn limited_reader<'a>(path: &Path, buffer: &'a [u8], max_bytes: u64) -> ImageResult<image::io::Reader<Cursor<&'a [u8]>>> {
+    let mut reader = reader(path, buffer)?;
+    reader.limits(limits(max_bytes));
+    Ok(reader)
+}
+
+fn no_frames() -> image::ImageError {
+    image::ImageError::Decoding(image::error::DecodingError::from_format_hint(
+        image::error::ImageFormatHint::Unknown,
+    ))
+}
+
+// Every frame of an animation has to be decoded (they're usually deltas from
+// the previous one), but only a few evenly spaced ones are kept, so it's
+// decoded twice: once to count the frames, and again to keep the picks.
+// Neither holds more than HELD_FRAMES. A single-frame GIF is just an image,
+// left for the caller to scale like any other.
+fn decode_frames<'a>(open: impl Fn() -> Option<image::Frames<'a>>, size: Option<(u32, u32)>) -> ImageResult<Decoded> {
+    let count = open().ok_or_else(no_frames)?
+        .try_fold(0, |count, frame| frame.map(|_| count + 1))?;
+    if count <= 1 {
+        let first = open().and_then(|mut x| x.next()).ok_or_else(no_frames)??;
+        return Ok(Decoded::Image(DynamicImage::ImageRgba8(first.into_buffer())));
+    }
+
+    let last = count - 1;
+    let picks: Vec<_> = (0..SAMPLED_FRAMES)
+        .map(|i| i * last / (SAMPLED_FRAMES - 1))
+        .collect();
+    let mut sampled = Vec::with_capacity(SAMPLED_FRAMES);
+    for (idx, frame) in open().ok_or_else(no_frames)?.enumerate() {
+        let frame = frame?;
+        if picks.contains(&idx) {
+            let frame = DynamicImage::ImageRgba8(frame.into_buffer());
+            sampled.push(match size {
+                Some((width, height)) => frame.thumbnail(width, height),
+                None => frame,
+            });
+        }
+    }
+    Ok(Decoded::Animation{sampled, count})
+}
+
+impl ImageSource for Jpeg {
+    fn families(&self) -> &'static [Family] {
+        &[("JPEG", &["jpg", "jpeg"])]
+    }
+
+    fn probe(&self, path: &Path, header: &[u8]) -> bool {
+        format_of(path, header) == Some(ImageFormat::Jpeg)
+    }
+
+    fn dimensions(&self, path: &Path, buffer: &[u8]) -> ImageResult<(u32, u32)> {
+        reader(path, buffer)?.into_dimensions()
+    }
+
+    fn bytes_per_pixel(&self, _path: &Path, _buffer: &[u8]) -> u64 {
+        4
+    }
+
+    fn scales(&self) -> bool {
+        true
+    }
+
+    fn decode_for_hash(&self, path: &Path, buffer: &[u8], request: DecodeRequest) -> ImageResult<Decoded> {
+        let Some((width, height)) = request.size else {
+            return limited_reader(path, buffer, request.max_bytes)?.decode().map(Decoded::Image);
+        };
+        let mut decoder = image::codecs::jpeg::JpegDecoder::new(Cursor::new(buffer))?;
+        decoder.scale(width.min(u16::MAX as u32) as u16, height.min(u16::MAX as u32) as u16)?;
+        DynamicImage::from_decoder(decoder).map(Decoded::Image)
+    }
+
+    fn load_for_display(&self, path: &Path, buffer: &[u8]) -> ImageResult<DynamicImage> {
+        reader(path, buffer)?.decode()
+    }
+}
+
+impl ImageSource for ImageCrate {
+    fn families(&self) -> &'static [Family] {
+        &[
+            ("PNG", &["png"]),
+            ("TIFF", &["tif", "tiff"]),
+            ("WebP", &["webp"]),
+            ("AVIF", &["avif"]),
+            ("GIF", &["gif"]),
+            ("BMP", &["bmp"]),
+            ("QOI", &["qoi"]),
+            ("TGA", &["tga"]),
+            ("PNM", &["pnm"]),
+            ("ICO", &["ico"]),
+            ("DDS", &["dds"]),
+            ("OpenEXR", &["exr"]),
+            ("HDR", &["hdr"]),
+        ]
+    }
+
+    fn probe(&self, path: &Path, header: &[u8]) -> bool {
+        format_of(path, header).is_some()
+    }
+
+    fn dimensions(&self, path: &Path, buffer: &[u8]) -> ImageResult<(u32, u32)> {
+        reader(path, buffer)?.into_dimensions()
+    }
+
+    // The most the format commonly decodes to. An animation's RGBA frames
+    // count HELD_FRAMES times over; GIFs can't say whether they're animated
+    // until they're decoded, so they all count as animations.
+    fn bytes_per_pixel(&self, path: &Path, buffer: &[u8]) -> u64 {
+        let format = format_of(path, buffer);
+        if format.and_then(|x| animation::frames(x, buffer, image::io::Limits::no_limits())).is_some() {
+            return 4 * HELD_FRAMES;
+        }
+        match format {
+            Some(ImageFormat::OpenExr | ImageFormat::Hdr) => 16, // 32-bit float RGBA
+            Some(ImageFormat::Tiff | ImageFormat::Png) => 8, // 16-bit RGBA
+            _ => 4,
+        }
+    }
+
+    fn decode_for_hash(&self, path: &Path, buffer: &[u8], request: DecodeRequest) -> ImageResult<Decoded> {
+        let reader = limited_reader(path, buffer, request.max_bytes)?;
+        let format = reader.format();
+        let open = || format.and_then(|x| animation::frames(x, buffer, limits(request.max_bytes)));
+        if open().is_some() {
+            // The frame decoders don't hold themselves to max_alloc.
+            let (width, height) = self.dimensions(path, buffer)?;
+            if (width as u64 * height as u64).saturating_mul(4 * HELD_FRAMES) > request.max_bytes {
+                return Err(image::ImageError::Limits(image::error::LimitError::from_kind(
+                    image::error::LimitErrorKind::InsufficientMemory,
+                )));
+            }
+            return decode_frames(open, request.size);
+        }
+        reader.decode().map(Decoded::Image)
+    }
+
+    fn load_for_display(&self, path: &Path, buffer: &[u8]) -> ImageResult<DynamicImage> {
+        reader(path, buffer)?.decode()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::search_harness::{gradient, write_image};
+
+    #[test]
+    fn files_go_to_the_source_for_their_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let jpeg = dir.path().join("a.jpg");
+        let png = dir.path().join("a.png");
+        write_image(&gradient(64, 48, false), &jpeg);
+        write_image(&gradient(64, 48, false), &png);
+        let misnamed = dir.path().join("jpeg.png");
+        std::fs::copy(&jpeg, &misnamed).unwrap();
+
+        let source = |path: &Path| for_file(path, &std::fs::read(path).unwrap()).families()[0].0;
+        assert_eq!(source(&jpeg), "JPEG");
+        assert_eq!(source(&png), "PNG");
+        assert_eq!(source(&misnamed), "JPEG"); // By contents, not name
+        // The last, to say what's wrong
+        assert_eq!(for_file(Path::new("unknown.xyz"), b"").families()[0].0, "PNG");
+
+        for path in [&jpeg, &png] {
+            let buffer = std::fs::read(path).unwrap();
+            let source = for_file(path, &buffer);
+            assert_eq!(source.dimensions(path, &buffer).unwrap(), (64, 48));
+            assert_eq!(source.load_for_display(path, &buffer).unwrap().width(), 64);
+        }
+
+        // Sniffing goes by contents alone.
+        let family = |path: &Path| sniff(&std::fs::read(path).unwrap()).map(|x| x.0);
+        assert_eq!(family(&misnamed), Some("JPEG"));
+        assert_eq!(family(&png), Some("PNG"));
+        assert_eq!(sniff(b"GIF89a\x01\0\x01\0").map(|x| x.0), Some("GIF"));
+        assert_eq!(sniff(b"not an image"), None);
+
+        // Every extension is read by exactly one source
+        let exts: Vec<&str> = all().iter().flat_map(|x| x.families()).flat_map(|(_, exts)| exts.iter().copied()).collect();
+        assert_eq!(exts.len(), exts.iter().collect::<std::collections::HashSet<_>>().len());
+    }
+
+    // Each frame a flat gray as light as its index, to tell them apart.
+    fn gif(frames: u8) -> Vec<u8> {
+        let mut buffer = vec![];
+        let frames = (0..frames).map(|x| image::Frame::new(image::RgbaImage::from_pixel(8, 8, image::Rgba([x * 10, x * 10, x * 10, 255]))));
+        image::codecs::gif::GifEncoder::new(&mut buffer).encode_frames(frames).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn animations_keep_evenly_spaced_frames() {
+        let path = Path::new("a.gif");
+        let request = DecodeRequest{size: None, max_bytes: 1 << 20};
+        let buffer = gif(7);
+        let Decoded::Animation{sampled, count} = ImageCrate.decode_for_hash(path, &buffer, request).unwrap() else {
+            panic!("not an animation");
+        };
+        assert_eq!(count, 7);
+        let shades: Vec<u8> = sampled.iter().map(|x| x.to_rgba8().get_pixel(0, 0).0[0]).collect();
+        assert_eq!(shades, [0, 20, 40, 60]);
+
+        assert!(matches!(ImageCrate.decode_for_hash(path, &gif(1), request).unwrap(), Decoded::Image(_)));
+
+        // Held to max_bytes, all the frames it holds at once included
+        let request = DecodeRequest{size: None, max_bytes: 8 * 8 * 4 * HELD_FRAMES - 1};
+        assert!(matches!(ImageCrate.decode_for_hash(path, &buffer, request), Err(image::ImageError::Limits(_))));
+        assert_eq!(ImageCrate.bytes_per_pixel(path, &buffer), 4 * HELD_FRAMES);
+
+        // Still an animation when asked for a smaller size, just scaled down
+        let request = DecodeRequest{size: Some((4, 4)), max_bytes: 1 << 20};
+        let Decoded::Animation{sampled, count} = ImageCrate.decode_for_hash(path, &buffer, request).unwrap() else {
+            panic!("not an animation");
+        };
+        assert_eq!(count, 7);
+        assert!(sampled.iter().all(|x| (x.width(), x.height()) == (4, 4)));
+    }
+}
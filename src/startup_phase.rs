@@ -1,12 +1,36 @@
 
-use crate::ROOT_KEY;
 
-use crate::{Phase, DynPhase, Error, Result};
+use crate::{Phase, DynPhase, Error, Result, MIN_ZOOM, MAX_ZOOM, set_zoom};
 use crate::searching_phase::SearchingPhase;
-use crate::searcher::{Searcher, SUPPORTED_EXTS};
+use crate::searcher::{self, Searcher, SearchOpts, SearchResults, SizeCap, SUPPORTED_EXTS, FORMAT_FAMILIES, COMMON_PHOTO_FAMILIES};
+use crate::archive::{Archive, Namespace};
+use crate::output_phase::{OutputPhase, ResultsLayout, ResultsDensity, FullResults};
+use crate::thumbnails::CacheLimits;
+use crate::keepers::KeeperStrategy;
+use crate::session::{Session, ScanSummary};
+use crate::ignored::IgnoreList;
+use crate::i18n::{Language, SizeUnits};
+use crate::theme::Theme;
+use crate::benchmark::{self, BenchmarkResults};
+use crate::checkpoint::Checkpoint;
+use crate::palette::Command;
+use crate::folder_tree::FolderTree;
+use crate::lookup::{Lookup, LookupAction};
+use crate::search_lock::{SearchLock, LockResult};
+use crate::misc::{self, is_sensitive_root};
+use crate::os_path;
+use crate::settings;
+use crate::clicks;
+use crate::options;
+use crate::grouping::Grouping;
+use crate::launch::{self, Launch};
 
-use std::path::PathBuf;
-use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::collections::{HashSet, BTreeMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use eframe::egui;
 use egui::widgets::text_edit::TextEdit;
@@ -15,30 +39,347 @@ use itertools::Itertools;
 
 use image_hasher::HashAlg;
 
+use serde::{Serialize, Deserialize};
 
-// User options
+
+// User options. Serializable so they can be stored with sessions; missing
+// fields take their defaults, so older files still load.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UserOpts {
+    #[serde(with = "os_path::serde_path")]
     pub root: PathBuf,
+    #[serde(with = "os_path::serde_paths")]
+    pub subfolders: Vec<PathBuf>, // Only search these, under root; empty for all of it
     pub follow_sym: bool,
+    pub respect_ignore: bool,
+    pub compare: bool, // Compare mode: only look for images in root that are also in reference
+    #[serde(with = "os_path::serde_path")]
+    pub reference: PathBuf,
     pub max_depth: String,
+    pub max_entries: String, // Empty for no limit
+    pub modified_after: String, // YYYY-MM-DD, or empty
+    pub modified_before: String,
+    pub date_any_member: bool, // Keep groups with any member in the date range
+    pub io_threads: usize,
+    pub queue_size: usize, // Files the walk can find ahead of hashing
     pub exts: String,
+    pub sniff: bool,
+    pub scan_libraries: bool, // Also search photo libraries' own previews and thumbnails
     pub hash: HashAlg,
+    pub verify_color: bool, // Split hash matches that differ in color
+    pub max_distance: u32, // Hashes this many bits apart still match
+    pub grouping: Grouping, // How near matches form groups
+    pub max_megapixels: u64, // Larger images are downscaled (or skipped) before hashing
+    pub skip_oversized: bool,
+    pub accurate_decode: bool, // Decode JPEGs in full for hashing, rather than scaled down
+    pub memory_budget_mb: Option<u64>, // Per image; None to go by the memory available
+    pub collapse_bursts: bool, // Collapse groups that look like a burst of shots
+    pub burst_window: f32, // Seconds between shots for them to count as a burst
+    pub detect_crops: bool, // Experimental; slow
+    pub trim_borders: bool, // Hash images without letterboxing or padding
+    pub center_crop: u32, // Percent of images' middles hashed, to look past watermarks and frames; 100 for all
+    pub derivative_patterns: String, // Comma separated; see derivative_patterns()
+    pub variant_factor: f32, // How much bigger the original of a group of resolution variants must be
+    pub sidecars: bool, // Trash sidecar files along with their images
+    pub sidecar_exts: String, // Comma separated; see sidecar_exts()
+    pub hook_command: String, // Run per group on request; empty for none. See hook
+    pub hook_confirmed: String, // The hook command the user last agreed to run
+    pub compare_command: String, // Opens a pair of images; empty for none. See compare_tool
+    pub layout: ResultsLayout,
+    pub density: ResultsDensity,
+    pub folder_panel: bool, // Show the results' folders in a tree beside them
+    pub folder_depth: usize, // How many levels below the root the by-folder view goes
+    pub history_panel: bool, // Show what's been done to the results in a panel under them
+    pub advanced_open: bool, // The form's Advanced section
+    pub notify: bool, // When a long search finishes while Deckard's in the background
+    pub portable: bool, // Save sessions and plan exports with relative paths; see Session::make_portable()
+    pub check_opens: bool, // Warn before opening a file whose contents don't match its extension
+    pub junk_filter: bool, // Hide groups of system images; see junk
+    pub keeper_strategy: KeeperStrategy, // Previewed on the results; kept with sessions, not as a preference
+    pub rename_template: String, // Last used to rename images in the results; see rename
+    pub large_group: usize, // Groups with more members start out collapsed, and page through them; 0 for never
+    pub thumb_memory_mb: u64, // Budget for the results' thumbnail textures
+    pub show_thumb_usage: bool, // Show how much of it's used, for debugging
+    #[serde(skip)]
+    pub presets: Presets,
+    #[serde(skip)]
+    pub recent_roots: RecentRoots,
+    #[serde(skip)]
+    pub restore_hash: Option<HashAlg>, // Put back on returning to the form, after searching with another just once
 }
 
 impl UserOpts {
+    pub const DEFAULT_MAX_ENTRIES: usize = 1_000_000;
+    pub const DEFAULT_FOLDER_DEPTH: usize = 2;
+    pub const DEFAULT_LARGE_GROUP: usize = 50;
+    pub const DEFAULT_BURST_WINDOW: f32 = 3.0;
+    pub const DEFAULT_VARIANT_FACTOR: f32 = 1.5;
+    pub const DEFAULT_SIDECAR_EXTS: &'static str = "xmp,aae,dop,pp3";
+    pub const CENTER_CROP: std::ops::RangeInclusive<u32> = 50..=100;
+
     pub fn new(root: PathBuf) -> Self {
         UserOpts {
             root,
-            exts: SUPPORTED_EXTS.iter().join(","),
+            subfolders: vec![],
+            exts: FORMAT_FAMILIES.iter().flat_map(|(_, exts)| exts.iter()).join(","),
+            sniff: false,
+            scan_libraries: false,
             hash: HashAlg::Gradient,
+            verify_color: true,
+            max_distance: 0,
+            grouping: Grouping::default(),
+            max_megapixels: SizeCap::DEFAULT_MAX_PIXELS / 1_000_000,
+            skip_oversized: false,
+            accurate_decode: false,
+            memory_budget_mb: None,
+            collapse_bursts: true,
+            burst_window: Self::DEFAULT_BURST_WINDOW,
+            detect_crops: false,
+            trim_borders: false,
+            center_crop: 100,
+            derivative_patterns: "".to_owned(),
+            variant_factor: Self::DEFAULT_VARIANT_FACTOR,
+            sidecars: true,
+            sidecar_exts: Self::DEFAULT_SIDECAR_EXTS.to_owned(),
+            hook_command: "".to_owned(),
+            hook_confirmed: "".to_owned(),
+            compare_command: "".to_owned(),
             follow_sym: false,
+            respect_ignore: false,
+            compare: false,
+            reference: PathBuf::default(),
             max_depth: "".to_owned(),
+            max_entries: Self::DEFAULT_MAX_ENTRIES.to_string(),
+            modified_after: "".to_owned(),
+            modified_before: "".to_owned(),
+            date_any_member: false,
+            io_threads: Self::default_io_threads(),
+            queue_size: SearchOpts::DEFAULT_QUEUE_SIZE,
+            layout: ResultsLayout::default(),
+            density: ResultsDensity::default(),
+            folder_panel: false,
+            folder_depth: Self::DEFAULT_FOLDER_DEPTH,
+            history_panel: false,
+            advanced_open: false,
+            notify: true,
+            portable: false,
+            check_opens: true,
+            junk_filter: true,
+            keeper_strategy: KeeperStrategy::default(),
+            rename_template: crate::rename::DEFAULT_TEMPLATE.to_string(),
+            large_group: Self::DEFAULT_LARGE_GROUP,
+            thumb_memory_mb: CacheLimits::DEFAULT_BUDGET_MB,
+            show_thumb_usage: false,
+            presets: Presets::default(),
+            recent_roots: RecentRoots::default(),
+            restore_hash: None,
+        }
+    }
+
+    // Options read from a file (a session, or the headless scan's) can say
+    // anything, so the central region's held to what the slider allows.
+    pub fn clamp_center_crop(&mut self) {
+        self.center_crop = self.center_crop.clamp(*Self::CENTER_CROP.start(), *Self::CENTER_CROP.end());
+    }
+
+    // Local disks handle as many concurrent reads as there are CPUs to decode
+    // them.
+    pub fn default_io_threads() -> usize {
+        std::thread::available_parallelism().map_or(1, Into::into)
+    }
+
+    pub fn size_cap(&self) -> SizeCap {
+        SizeCap {
+            max_pixels: self.max_megapixels.saturating_mul(1_000_000),
+            skip: self.skip_oversized,
+            reduced_jpeg: !self.accurate_decode,
+            max_bytes: self.memory_budget_mb.map_or_else(SizeCap::auto_max_bytes, |x| x.saturating_mul(1024 * 1024)),
         }
     }
 
+    // The extensions, unvalidated.
+    pub fn ext_set(&self) -> HashSet<String> {
+        self.exts
+            .split(',')
+            .map(|x| x.trim().trim_start_matches('.').to_owned())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+
+    // Replaces the extensions with the given supported ones, in the order
+    // they're offered, keeping any unsupported ones already there (so they're
+    // still flagged rather than silently dropped).
+    pub fn set_exts(&mut self, selected: &HashSet<&str>) {
+        let unsupported = self.ext_set().into_iter()
+            .filter(|x| !SUPPORTED_EXTS.contains(x.as_str()))
+            .sorted();
+        self.exts = FORMAT_FAMILIES.iter()
+            .flat_map(|(_, exts)| exts.iter())
+            .filter(|x| selected.contains(*x))
+            .map(|x| x.to_string())
+            .chain(unsupported)
+            .join(",");
+    }
+
+    pub fn burst_window(&self) -> Option<Duration> {
+        self.collapse_bursts.then(|| Duration::from_secs_f32(self.burst_window.max(0.0)))
+    }
+
+    // Parts of a file name that mark a file as an intentional derivative of
+    // one named the same without them, e.g. "_web" or "-edited". Lowercase,
+    // since names are compared ignoring case.
+    pub fn derivative_patterns(&self) -> Vec<String> {
+        self.derivative_patterns.split(',')
+            .map(|x| x.trim().to_lowercase())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+
+    // Extensions of files that hold metadata or edits for the image they're
+    // named after, e.g. XMP from Lightroom, AAE from iOS, DOP from DxO and
+    // PP3 from RawTherapee. Lowercase, without the dot.
+    pub fn sidecar_exts(&self) -> Vec<String> {
+        self.sidecar_exts.split(',')
+            .map(|x| x.trim().trim_start_matches('.').to_lowercase())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+
     pub fn take(&mut self) -> UserOpts {
         std::mem::take(self)
     }
+
+    // Restores the persisted options other than the root, which needs some
+    // validation, so is handled by StartupPhase::new_with_cc().
+    fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(layout) = settings::get(storage, settings::LAYOUT) {
+            self.layout = layout;
+        }
+        if let Some(density) = settings::get(storage, settings::DENSITY) {
+            self.density = density;
+        }
+        if let Some(shown) = settings::get(storage, settings::FOLDER_PANEL) {
+            self.folder_panel = shown;
+        }
+        if let Some(depth) = settings::get(storage, settings::FOLDER_DEPTH) {
+            self.folder_depth = depth;
+        }
+        if let Some(shown) = settings::get(storage, settings::HISTORY_PANEL) {
+            self.history_panel = shown;
+        }
+        if let Some(open) = settings::get(storage, settings::ADVANCED_OPEN) {
+            self.advanced_open = open;
+        }
+        if let Some(threads) = settings::get(storage, settings::IO_THREADS) {
+            self.io_threads = threads;
+        }
+        if let Some(size) = settings::get(storage, settings::QUEUE_SIZE) {
+            self.queue_size = size;
+        }
+        if let Some(notify) = settings::get(storage, settings::NOTIFY) {
+            self.notify = notify;
+        }
+        if let Some(portable) = settings::get(storage, settings::PORTABLE) {
+            self.portable = portable;
+        }
+        if let Some(check) = settings::get(storage, settings::CHECK_OPENS) {
+            self.check_opens = check;
+        }
+        if let Some(filter) = settings::get(storage, settings::JUNK_FILTER) {
+            self.junk_filter = filter;
+        }
+        if let Some(size) = settings::get(storage, settings::LARGE_GROUP) {
+            self.large_group = size;
+        }
+        if let Some(mb) = settings::get(storage, settings::THUMB_MEMORY) {
+            self.thumb_memory_mb = mb;
+        }
+        if let Some(show) = settings::get(storage, settings::THUMB_USAGE) {
+            self.show_thumb_usage = show;
+        }
+        self.presets.load(storage);
+        self.recent_roots.load(storage);
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        settings::set(storage, settings::ROOT, &self.root);
+        settings::set(storage, settings::LAYOUT, &self.layout);
+        settings::set(storage, settings::DENSITY, &self.density);
+        settings::set(storage, settings::FOLDER_PANEL, &self.folder_panel);
+        settings::set(storage, settings::FOLDER_DEPTH, &self.folder_depth);
+        settings::set(storage, settings::HISTORY_PANEL, &self.history_panel);
+        settings::set(storage, settings::ADVANCED_OPEN, &self.advanced_open);
+        settings::set(storage, settings::IO_THREADS, &self.io_threads);
+        settings::set(storage, settings::QUEUE_SIZE, &self.queue_size);
+        settings::set(storage, settings::NOTIFY, &self.notify);
+        settings::set(storage, settings::PORTABLE, &self.portable);
+        settings::set(storage, settings::CHECK_OPENS, &self.check_opens);
+        settings::set(storage, settings::JUNK_FILTER, &self.junk_filter);
+        settings::set(storage, settings::LARGE_GROUP, &self.large_group);
+        settings::set(storage, settings::THUMB_MEMORY, &self.thumb_memory_mb);
+        settings::set(storage, settings::THUMB_USAGE, &self.show_thumb_usage);
+        self.presets.save(storage);
+        self.recent_roots.save(storage);
+    }
+
+    // Replaces these options with a preset's, keeping the presets themselves,
+    // the recent roots, and display, notification and safety preferences,
+    // which are global.
+    fn apply_preset(&mut self, preset: &UserOpts) {
+        let presets = std::mem::take(&mut self.presets);
+        let recent_roots = std::mem::take(&mut self.recent_roots);
+        let (layout, density, folder_panel, notify) = (self.layout, self.density, self.folder_panel, self.notify);
+        let (folder_depth, advanced_open, check_opens) = (self.folder_depth, self.advanced_open, self.check_opens);
+        let (history_panel, junk_filter) = (self.history_panel, self.junk_filter);
+        let (keeper_strategy, large_group) = (self.keeper_strategy, self.large_group);
+        let (thumb_memory_mb, show_thumb_usage) = (self.thumb_memory_mb, self.show_thumb_usage);
+        *self = preset.clone();
+        self.presets = presets;
+        self.recent_roots = recent_roots;
+        self.layout = layout;
+        self.density = density;
+        self.folder_panel = folder_panel;
+        self.notify = notify;
+        self.folder_depth = folder_depth;
+        self.history_panel = history_panel;
+        self.advanced_open = advanced_open;
+        self.check_opens = check_opens;
+        self.junk_filter = junk_filter;
+        self.keeper_strategy = keeper_strategy;
+        self.large_group = large_group;
+        self.thumb_memory_mb = thumb_memory_mb;
+        self.show_thumb_usage = show_thumb_usage;
+    }
+
+    // The options selecting the named preset would put in the form, loaded
+    // from storage the way a new tab loads them.
+    pub fn from_preset(storage: &dyn eframe::Storage, name: &str) -> Result<UserOpts, String> {
+        let mut opts = UserOpts::default();
+        opts.load(storage);
+        let Some(preset) = opts.presets.saved.get(name).cloned() else {
+            let names = opts.presets.saved.keys().map(|x| format!("\"{x}\"")).join(", ");
+            return Err(match names.is_empty() {
+                true => format!("There's no preset named \"{name}\"; none are saved"),
+                false => format!("There's no preset named \"{name}\"; saved presets: {names}"),
+            });
+        };
+        opts.apply_preset(&preset);
+        Ok(opts)
+    }
+
+    // The options a restored session is shown with: its own, but with these
+    // global preferences, as a preset's would be.
+    fn with_session(&self, session: &UserOpts) -> UserOpts {
+        let mut opts = self.clone();
+        opts.apply_preset(session);
+        opts
+    }
+
+    // A copy suitable for storing as a preset.
+    fn to_preset(&self) -> UserOpts {
+        UserOpts{presets: Presets::default(), recent_roots: RecentRoots::default(), ..self.clone()}
+    }
 }
 
 impl Default for UserOpts {
@@ -49,24 +390,286 @@ impl Default for UserOpts {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+// Named sets of options. Carried along with UserOpts (but not serialized with
+// it) so they survive the round trip through the other phases, which only
+// have the options to save.
+#[derive(Clone, Default)]
+pub struct Presets {
+    saved: BTreeMap<String, UserOpts>,
+    last: Option<String>, // Most recently saved or selected
+}
+
+impl Presets {
+    // Each one is a few hundred bytes of JSON, and they all go in one storage
+    // value.
+    const MAX: usize = 32;
+
+    fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(saved) = settings::get(storage, settings::PRESETS) {
+            self.saved = saved;
+        }
+        self.last = settings::get(storage, settings::LAST_PRESET).filter(|x| self.saved.contains_key(x));
+    }
+
+    fn save(&self, storage: &mut dyn eframe::Storage) {
+        settings::set(storage, settings::PRESETS, &self.saved);
+        settings::set(storage, settings::LAST_PRESET, &self.last.clone().unwrap_or_default());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Folders recently searched, offered under the root field. Carried along with
+// UserOpts like the presets.
+#[derive(Clone, Default)]
+pub struct RecentRoots {
+    entries: Vec<RecentRoot>, // Most recently searched first
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct RecentRoot {
+    #[serde(with = "os_path::serde_path")]
+    path: PathBuf, // Canonical, as of when it was searched
+    pinned: bool, // Kept however many others are searched since
+}
+
+// What was clicked in the recent roots menu.
+enum RecentAction {
+    Choose(PathBuf),
+    TogglePin(usize),
+    Clear,
+}
+
+impl RecentRoots {
+    // Unpinned entries; pinned ones don't count against it.
+    const MAX: usize = 8;
+
+    fn load(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(entries) = settings::get(storage, settings::RECENT_ROOTS) {
+            self.entries = entries;
+        }
+    }
+
+    fn save(&self, storage: &mut dyn eframe::Storage) {
+        settings::set(storage, settings::RECENT_ROOTS, &self.entries);
+    }
+
+    // Moves the folder to the front, dropping the oldest unpinned entry if
+    // there are too many. Folders are compared canonicalized, so differently
+    // spelled paths to the same one don't both appear.
+    fn add(&mut self, root: &Path) {
+        let path = root.canonicalize().unwrap_or_else(|_| root.components().collect());
+        let pinned = match self.entries.iter().position(|x| x.path == path) {
+            Some(idx) => self.entries.remove(idx).pinned,
+            None => false,
+        };
+        self.entries.insert(0, RecentRoot{path, pinned});
+
+        let mut unpinned = 0;
+        self.entries.retain(|x| {
+            unpinned += usize::from(!x.pinned);
+            x.pinned || unpinned <= Self::MAX
+        });
+    }
+
+    fn toggle_pin(&mut self, idx: usize) {
+        if let Some(entry) = self.entries.get_mut(idx) {
+            entry.pinned = !entry.pinned;
+        }
+    }
+
+    // Pinned entries stay until they're unpinned.
+    fn clear(&mut self) {
+        self.entries.retain(|x| x.pinned);
+    }
+
+    // Drawn as a menu next to the root field.
+    fn draw(&self, ui: &mut egui::Ui) -> Option<RecentAction> {
+        let mut action = None;
+        ui.add_enabled_ui(!self.entries.is_empty(), |ui| {
+            ui.menu_button("🕓", |ui| {
+                for (idx, entry) in self.entries.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.selectable_label(entry.pinned, "📌")
+                            .on_hover_text(tr!("Pinned folders stay in the list"))
+                            .clicked() {
+                            action = Some(RecentAction::TogglePin(idx));
+                        }
+                        let text = entry.path.display().to_string();
+                        let resp = if entry.path.is_dir() {
+                            ui.button(text)
+                        } else {
+                            ui.button(egui::RichText::new(text).weak())
+                                .on_hover_text(tr!("Not found. If it's on a drive that isn't connected, \
+                                    connect it before searching."))
+                        };
+                        if resp.clicked() {
+                            action = Some(RecentAction::Choose(entry.path.clone()));
+                            ui.close_menu();
+                        }
+                    });
+                }
+                ui.separator();
+                if ui.button(tr!("Clear history")).on_hover_text(tr!("Forget every folder that isn't pinned")).clicked() {
+                    action = Some(RecentAction::Clear);
+                }
+            }).response.on_hover_text(tr!("Recent folders"));
+        });
+        action
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// A hash algorithm comparison, started from the advanced options.
+enum Benchmark {
+    Running{handle: JoinHandle<Option<BenchmarkResults>>, cancel: Arc<AtomicBool>},
+    Done(BenchmarkResults),
+}
+
+// Options another phase sent the user back to change; they're highlighted,
+// and the advanced section opened, on arrival.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FocusHint {
+    Extensions,
+    Matching, // Hash algorithm and color verification
+    SizeLimits, // Size cap and memory budget
+}
+
 pub struct StartupPhase {
     opts: UserOpts,
+    benchmark: Option<Benchmark>,
+    new_preset_name: Option<String>, // Name being typed after clicking "Save current as…"
+    notices: Vec<String>, // Non-modal messages shown above the form until dismissed
+    session_available: bool, // Checked once, rather than hitting the disk every frame
+    scan: Option<ScanSummary>, // An unattended scan's results, offered until viewed or dismissed
+    checkpoint: Option<Checkpoint>, // An unfinished search, offered if the options match
+    checkpoint_read: usize, // searcher::searches_ended() when it was read
+    folder_tree: Option<FolderTree>, // Subfolder picker, while it's open
+    lookup: Option<Lookup>, // Single-image lookup, while it's open
+    welcome: Option<bool>, // Whether to show the explainer on later launches; None if it's not offered
+    welcome_open: bool,
+    focus: Option<(FocusHint, Option<Instant>)>, // And when it was first drawn
+    refining: Option<FullResults>, // Passed along; see OutputPhase::refine()
+    search_now: bool, // Asked for on the command line; searches on the first frame
 }
 
 impl StartupPhase {
 
-    pub fn new_with_cc(cc: &eframe::CreationContext) -> StartupPhase {
-        let root = cc.storage.and_then(|x| x.get_string(ROOT_KEY))
-            .map(Into::into)
-            .unwrap_or_else(Self::default_root);
+    // Only the first tab says what's wrong with the stored settings, so it's
+    // said once. It's also the one the command line's folder goes to.
+    pub fn new_with_cc(cc: &eframe::CreationContext, launch: &Launch) -> StartupPhase {
+        let (root, problem) = match launch.checked_root() {
+            Some(Ok(root)) => (Some(root), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+        let search_now = launch.search && root.is_some();
+        let mut phase = Self::new_with_root(cc.storage, root);
+        phase.search_now = search_now;
+        phase.notices.extend(problem);
+        if let Err(e) = settings::probe_write() {
+            phase.notices.push(tr!("Settings can't be saved, so changes won't be kept after quitting. {error}", error = e));
+        }
+        let Some(storage) = cc.storage else {
+            return phase;
+        };
+        if settings::is_newer(storage) {
+            phase.notices.push(tr!("Settings were saved by a newer version of Deckard; changes won't be kept, so as not to overwrite them.").to_string());
+        }
+        let invalid = settings::invalid(storage);
+        if !invalid.is_empty() {
+            let list = invalid.into_iter().map(crate::i18n::tr).join(", ");
+            phase.notices.push(tr!("Some stored settings couldn't be read and were reset to their defaults: {list}.", list = list));
+        }
+        phase
+    }
+
+    pub fn new_with_storage(storage: Option<&dyn eframe::Storage>) -> StartupPhase {
+        Self::new_with_root(storage, None)
+    }
+
+    // With the root, if given, instead of the stored one.
+    fn new_with_root(storage: Option<&dyn eframe::Storage>, root: Option<PathBuf>) -> StartupPhase {
+        let stored = storage.and_then(|x| settings::get(x, settings::ROOT));
+
+        // Offered on the first launch, and each one after until it's turned
+        // off.
+        let welcome = storage.and_then(|x| settings::get(x, settings::WELCOME))
+            .unwrap_or(stored.is_none());
+
+        // The saved root may be on a drive that's no longer mounted; rather
+        // than greeting the user with a dead path, fall back to home and say so.
+        let mut notices = vec![];
+        let root = match (root, stored) {
+            (Some(root), _) => root,
+            (None, Some(path)) if path.is_dir() => path,
+            (None, Some(path)) => {
+                notices.push(tr!(
+                    "The last searched folder, {folder}, is no longer available; using your home folder instead.",
+                    folder = path.display(),
+                ));
+                Self::default_root()
+            },
+            (None, None) => Self::default_root(),
+        };
+
+        let mut opts = UserOpts::new(root);
+        if let Some(storage) = storage {
+            opts.load(storage);
+        }
+
+        StartupPhase {
+            opts,
+            benchmark: None,
+            new_preset_name: None,
+            notices,
+            session_available: Session::exists(),
+            scan: Session::unviewed_scan(),
+            checkpoint: Checkpoint::load(),
+            checkpoint_read: searcher::searches_ended(),
+            folder_tree: None,
+            lookup: None,
+            welcome: welcome.then_some(true),
+            welcome_open: welcome,
+            focus: None,
+            refining: None,
+            search_now: false,
+        }
+    }
 
+    pub fn new_with_opts(mut opts: UserOpts) -> StartupPhase {
+        if let Some(hash) = opts.restore_hash.take() {
+            opts.hash = hash;
+        }
         StartupPhase {
-            opts: UserOpts::new(root),
+            opts,
+            benchmark: None,
+            new_preset_name: None,
+            notices: vec![],
+            session_available: Session::exists(),
+            scan: None,
+            checkpoint: Checkpoint::load(),
+            checkpoint_read: searcher::searches_ended(),
+            folder_tree: None,
+            lookup: None,
+            welcome: None,
+            welcome_open: false,
+            focus: None,
+            refining: None,
+            search_now: false,
         }
     }
 
-    pub fn new_with_opts(opts: UserOpts) -> StartupPhase {
-        StartupPhase{opts}
+    pub fn with_refining(mut self, refining: Option<FullResults>) -> StartupPhase {
+        self.refining = refining;
+        self
+    }
+
+    // Highlights the options on arrival.
+    pub fn with_focus(mut self, hint: FocusHint) -> StartupPhase {
+        self.focus = Some((hint, None));
+        self
     }
 
     pub fn into_dyn(self) -> DynPhase {
@@ -77,142 +680,1414 @@ impl StartupPhase {
         homedir::get_my_home().ok().flatten().unwrap_or_else(|| PathBuf::from("/"))
     }
 
-    fn parse_max_depth(&self) -> Result<Option<usize>> {
-        let mut max_depth = None;
-        if !self.opts.max_depth.is_empty() {
-            let depth = self.opts.max_depth.parse::<usize>().map_err(|e| 
-                Error::new(
-                    "Error parsing depth limit".to_string(),
-                    e.to_string(),
-            ))?;
-            if depth == 0usize {
-                return Err(Error::new(
-                    "Invalid depth limit".to_string(),
-                    "A depth limit of 0 doesn't search at all".to_string(),
-                ));
+    fn pick_folder(folder: &mut PathBuf) {
+        if let Some(path) = rfd::FileDialog::new()
+            .set_directory(&*folder)
+            .pick_folder() {
+            *folder = path;
+        }
+    }
+
+    // A labeled text field and picker for a folder, tinted red if there's a
+    // problem with it.
+    fn folder_field(
+        ui: &mut egui::Ui,
+        label: &str,
+        folder: &mut PathBuf,
+        problem: &Option<String>,
+        recent: Option<&mut RecentRoots>,
+    ) {
+        ui.horizontal(|ui| {
+            ui.strong(label);
+
+            let mut buf = folder.to_string_lossy();
+            let mut textedit = egui::TextEdit::singleline(&mut buf).code_editor();
+            if problem.is_some() {
+                textedit = textedit.text_color(ui.visuals().error_fg_color);
+            }
+            let output = textedit.show(ui);
+            // Pasted paths are cleaned up right away, so what's shown is what'll
+            // be searched; typed ones once they're done, so a separator typed
+            // at the end isn't snatched away.
+            let pasted = ui.input(|i| i.events.iter().any(|x| matches!(x, egui::Event::Paste(_))));
+            if (output.response.changed() && pasted) || output.response.lost_focus() {
+                *folder = misc::normalize_path(&buf);
+            } else if output.response.changed() {
+                *folder = buf.to_string().into();
+            }
+
+            if let Some(recent) = recent {
+                match recent.draw(ui) {
+                    Some(RecentAction::Choose(path)) => *folder = path,
+                    Some(RecentAction::TogglePin(idx)) => recent.toggle_pin(idx),
+                    Some(RecentAction::Clear) => recent.clear(),
+                    None => (),
+                }
+            }
+
+            if ui.button(tr!("Choose...")).clicked() {
+                Self::pick_folder(folder);
             }
-            max_depth = Some(depth);
+        });
+
+        if let Some(problem) = problem {
+            ui.label(egui::RichText::new(problem).small().color(ui.visuals().error_fg_color));
         }
-        Ok(max_depth)
     }
 
-    fn parse_exts(&self) -> Result<HashSet<String>, Error> {
-        let exts: HashSet<String> = self.opts.exts
-            .split(',')
-            .map(|x| x.trim().trim_start_matches('.').to_owned())
-            .filter(|x| !x.is_empty())
+    // Shown under the extensions field as they're typed, so there's no
+    // surprise when Search is clicked.
+    fn exts_problem(&self) -> Option<String> {
+        let exts = self.opts.ext_set();
+        if exts.is_empty() {
+            return Some(tr!("No extensions selected").to_string());
+        }
+        let unsupported = exts.iter()
+            .filter(|x| !SUPPORTED_EXTS.contains(x.as_str()))
+            .sorted()
+            .join(", ");
+        (!unsupported.is_empty()).then(|| tr!("Not supported: {exts}", exts = unsupported))
+    }
+
+    // The checkbox popup for the extensions, kept in sync with the text.
+    fn draw_ext_picker(&mut self, ui: &mut egui::Ui) {
+        let current = self.opts.ext_set();
+        let mut selected: HashSet<&str> = SUPPORTED_EXTS.iter()
+            .copied()
+            .filter(|x| current.contains(*x))
             .collect();
+        let before = selected.clone();
 
-        for ext in &exts {
-            if !SUPPORTED_EXTS.contains(ext.as_str()) {
-                return Err(Error::new(
-                    "Extension Error".to_owned(),
-                    format!("Extension {ext} is not supported"),
-                ));
+        ui.horizontal(|ui| {
+            if ui.button(tr!("All")).clicked() {
+                selected = SUPPORTED_EXTS.clone();
+            }
+            if ui.button(tr!("None")).clicked() {
+                selected.clear();
             }
+            if ui.button(tr!("Common photos only")).clicked() {
+                selected = FORMAT_FAMILIES.iter()
+                    .filter(|(family, _)| COMMON_PHOTO_FAMILIES.contains(family))
+                    .flat_map(|(_, exts)| exts.iter().copied())
+                    .collect();
+            }
+        });
+        ui.separator();
+
+        egui::Grid::new("ext picker").num_columns(2).show(ui, |ui| {
+            for (family, exts) in FORMAT_FAMILIES.iter() {
+                ui.strong(*family);
+                ui.horizontal(|ui| {
+                    for ext in *exts {
+                        let mut checked = selected.contains(ext);
+                        if ui.checkbox(&mut checked, *ext).changed() {
+                            if checked {
+                                selected.insert(ext);
+                            } else {
+                                selected.remove(ext);
+                            }
+                        }
+                    }
+                });
+                ui.end_row();
+            }
+        });
+
+        if selected != before {
+            self.opts.set_exts(&selected);
         }
+    }
 
-        Ok(exts)
+    // The checkpoint, if it was left by a search with the current options.
+    // Not while another tab's searching, since that search's is the one on
+    // disk.
+    fn resumable(&self) -> Option<&Checkpoint> {
+        if searcher::search_running() {
+            return None;
+        }
+        let checkpoint = self.checkpoint.as_ref()?;
+        let opts = options::search_opts(&self.opts).ok()?;
+        (checkpoint.fingerprint == opts.fingerprint()).then_some(checkpoint)
     }
 
-    fn make_searching_phase(&mut self) -> Result<DynPhase> {
-        if !self.opts.root.exists() {
-            return Err(Error::new(
-                "Path Error".into(),
-                format!("{} doesn't exist", self.opts.root.display()),
-            ));
+    // Asks before searching a folder whose duplicates could include files the
+    // system needs; see is_sensitive_root(). True to go ahead.
+    fn confirm_sensitive(&self) -> bool {
+        let home = homedir::get_my_home().ok().flatten();
+        let mut folders = vec![&self.opts.root];
+        if self.opts.compare {
+            folders.push(&self.opts.reference);
         }
+        let Some(folder) = folders.into_iter().find(|x| is_sensitive_root(x, home.as_deref())) else {
+            return true;
+        };
+
+        let search = tr!("Search anyway").to_string();
+        let answer = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(tr!("Search a system folder?"))
+            .set_description(tr!(
+                "{folder} is a system folder, a drive, or contains home folders. Duplicates \
+                there can include files the system and other programs need, and trashing them \
+                can break things. Consider searching a specific folder of photos instead.",
+                folder = folder.display(),
+            ))
+            .set_buttons(rfd::MessageButtons::OkCancelCustom(search.clone(), tr!("Cancel").to_string()))
+            .show();
+        // Some backends report custom buttons as such, others as Ok.
+        matches!(answer, rfd::MessageDialogResult::Ok)
+            || answer == rfd::MessageDialogResult::Custom(search)
+    }
+
+    // Locks the folders about to be searched, asking first about any another
+    // search (in another tab, or another copy of the app) already holds. None
+    // if the user backed out. A lock that can't be taken at all isn't worth
+    // stopping for.
+    fn lock_folders(&self) -> Option<Vec<SearchLock>> {
+        let mut folders = vec![&self.opts.root];
+        if self.opts.compare {
+            folders.push(&self.opts.reference);
+        }
+
+        let mut locks = vec![];
+        for folder in folders {
+            match SearchLock::acquire(folder) {
+                Ok(LockResult::Locked(lock)) => locks.push(lock),
+                Ok(LockResult::Busy) => {
+                    let go = tr!("Continue anyway").to_string();
+                    let answer = rfd::MessageDialog::new()
+                        .set_level(rfd::MessageLevel::Warning)
+                        .set_title(tr!("Already being searched"))
+                        .set_description(tr!(
+                            "Another Deckard window is already searching {folder}. Searching it twice \
+                            at once will slow both down.",
+                            folder = folder.display(),
+                        ))
+                        .set_buttons(rfd::MessageButtons::OkCancelCustom(go.clone(), tr!("Cancel").to_string()))
+                        .show();
+                    if !matches!(answer, rfd::MessageDialogResult::Ok) && answer != rfd::MessageDialogResult::Custom(go) {
+                        return None;
+                    }
+                },
+                Err(_) => (),
+            }
+        }
+        Some(locks)
+    }
+
+    // Asks what to do if what's indexed for the folders was all hashed with
+    // another algorithm: hashes from different ones can't be compared, so
+    // searching with this one indexes them again from scratch. The algorithm
+    // to search with, or None if the user backed out.
+    fn choose_hash(&self) -> Option<HashAlg> {
+        let hash = self.opts.hash;
+        let mut folders = vec![&self.opts.root];
+        if self.opts.compare {
+            folders.push(&self.opts.reference);
+        }
+        // Usually already read by then, for the lookup window or a past
+        // search; if not, it's read here, once.
+        let archive = Archive::shared();
+        let mismatch = folders.into_iter()
+            .find_map(|x| archive.mismatch(x, Namespace::new(hash)).map(|old| (x, old)))
+            .filter(|(_, old)| old.hash != hash);
+        let Some((folder, old)) = mismatch else {
+            return Some(hash);
+        };
+
+        let rebuild = tr!("Index with {algorithm}", algorithm = format!("{hash:?}"));
+        let once = tr!("Use {algorithm} this once", algorithm = old.label());
+        let answer = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(tr!("Different hash algorithm"))
+            .set_description(tr!(
+                "{folder} was indexed with {old}, and this search uses {new}. Their hashes can't be \
+                compared, so searching with {new} indexes it again from scratch; what's indexed \
+                with {old} is kept, for lookups and for switching back.",
+                folder = folder.display(),
+                old = old.label(),
+                new = format!("{hash:?}"),
+            ))
+            .set_buttons(rfd::MessageButtons::YesNoCancelCustom(rebuild.clone(), once.clone(), tr!("Cancel").to_string()))
+            .show();
+        // Some backends report custom buttons as such, others as the standard
+        // ones in their place.
+        match answer {
+            rfd::MessageDialogResult::Yes => Some(hash),
+            rfd::MessageDialogResult::No => Some(old.hash),
+            rfd::MessageDialogResult::Custom(x) if x == rebuild => Some(hash),
+            rfd::MessageDialogResult::Custom(x) if x == once => Some(old.hash),
+            _ => None,
+        }
+    }
 
-        let max_depth = self.parse_max_depth()?;
-        let exts = self.parse_exts()?;
+    // Starting over discards any checkpoint, whether or not it matches; the
+    // new search will leave its own if it doesn't finish. None if the user
+    // backed out.
+    fn make_searching_phase(&mut self, resume: bool) -> Result<Option<DynPhase>> {
+        if searcher::search_running() {
+            self.notices.push(tr!("Another tab is still searching. Wait for it to finish, or cancel it, first.").to_string());
+            return Ok(None);
+        }
+        for folder in [&mut self.opts.root, &mut self.opts.reference] {
+            if let Some(text) = folder.to_str() {
+                *folder = misc::normalize_path(text);
+            }
+        }
+        if !self.confirm_sensitive() {
+            return Ok(None);
+        }
+        // A checkpoint was made with the algorithm it has, so resuming one
+        // sticks with it.
+        let hash = match resume {
+            true => self.opts.hash,
+            false => match self.choose_hash() {
+                Some(x) => x,
+                None => return Ok(None),
+            },
+        };
+        let Some(locks) = self.lock_folders() else {
+            return Ok(None);
+        };
+        let mut opts = options::search_opts(&self.opts)?;
+        opts.hash = hash;
+        if resume {
+            opts.resume = self.checkpoint.take();
+        } else {
+            self.checkpoint = None;
+            Checkpoint::delete();
+        }
 
-        let mut searcher = Searcher::new(
-            self.opts.root.clone(),
-            self.opts.hash,
-            self.opts.follow_sym,
-            max_depth,
-            exts,
-        );
+        let mut searcher = Searcher::new(opts);
+        let events = searcher.events(SearchingPhase::EVENT_CAPACITY);
         searcher.launch_search();
+        // The results have to say how they were hashed, so it's only put
+        // back once they're done with.
+        if hash != self.opts.hash {
+            self.opts.restore_hash = Some(self.opts.hash);
+            self.opts.hash = hash;
+        }
+        self.opts.recent_roots.add(&self.opts.root);
         let opts = std::mem::take(&mut self.opts);
-        Ok(SearchingPhase::new(opts, searcher).into_dyn())
+        Ok(Some(SearchingPhase::new(opts, searcher, events, locks).with_refining(self.refining.take()).into_dyn()))
     }
 }
 
-impl Phase for StartupPhase {
-    fn render(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
+impl StartupPhase {
+    fn save_preset(&mut self, name: String) -> Result<()> {
+        let presets = &mut self.opts.presets;
+        if presets.saved.contains_key(&name) {
+            let replace = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title(tr!("Save preset"))
+                .set_description(tr!("A preset named \"{name}\" already exists. Replace it?", name = name))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if replace != rfd::MessageDialogResult::Yes {
+                return Ok(());
+            }
+        } else if presets.saved.len() >= Presets::MAX {
+            return Err(Error::new(
+                "Too many presets".to_string(),
+                format!("Only {} presets can be saved; delete one first.", Presets::MAX),
+            ));
+        }
+
+        let preset = self.opts.to_preset();
+        let presets = &mut self.opts.presets;
+        presets.saved.insert(name.clone(), preset);
+        presets.last = Some(name);
+        self.new_preset_name = None;
+        Ok(())
+    }
+
+    fn delete_preset(&mut self, name: &str) {
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(tr!("Delete preset"))
+            .set_description(tr!("Delete the preset \"{name}\"?", name = name))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if confirmed == rfd::MessageDialogResult::Yes {
+            self.opts.presets.saved.remove(name);
+            self.opts.presets.last = None;
+        }
+    }
+
+    fn draw_presets(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        let mut selected = None;
+        let mut save = None;
+        let mut delete = false;
         ui.horizontal(|ui| {
-            ui.strong("Root Path: ".to_string());
+            ui.label(tr!("Preset:"));
+            let presets = &self.opts.presets;
+            let current = presets.last.as_deref().unwrap_or("");
+            egui::ComboBox::from_id_source("preset")
+                .selected_text(current)
+                .show_ui(ui, |ui| {
+                    for (name, preset) in &presets.saved {
+                        if ui.selectable_label(name == current, name).clicked() {
+                            selected = Some((name.clone(), preset.clone()));
+                        }
+                    }
+                });
+
+            match &mut self.new_preset_name {
+                Some(name) => {
+                    let resp = ui.add(TextEdit::singleline(name).hint_text(tr!("Name")).desired_width(150.0));
+                    let entered = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    let valid = !name.trim().is_empty();
+                    if (ui.add_enabled(valid, egui::Button::new(tr!("Save"))).clicked() || entered) && valid {
+                        save = Some(name.trim().to_string());
+                    }
+                    if ui.button(tr!("Cancel")).clicked() {
+                        self.new_preset_name = None;
+                    }
+                },
+                None => {
+                    if ui.button(tr!("Save current as…")).clicked() {
+                        self.new_preset_name = Some(String::new());
+                    }
+                },
+            }
 
-            let mut buf = self.opts.root.to_string_lossy();
-            let output = egui::TextEdit::singleline(&mut buf).code_editor().show(ui);
-            if output.response.changed() {
-                self.opts.root = buf.to_string().into();
+            delete = ui.add_enabled(presets.last.is_some(), egui::Button::new(tr!("Delete preset"))).clicked();
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), clicks::settings_button);
+        });
+
+        if let Some((name, preset)) = selected {
+            self.opts.apply_preset(&preset);
+            self.opts.presets.last = Some(name);
+        }
+        if delete {
+            if let Some(name) = self.opts.presets.last.clone() {
+                self.delete_preset(&name);
             }
+        }
+        if let Some(name) = save {
+            self.save_preset(name)?;
+        }
+        Ok(())
+    }
+
+    fn start_benchmark(&mut self) -> Result<()> {
+        let exts = options::parse_exts(&self.opts)?;
+        let root = self.opts.root.clone();
+        let cap = self.opts.size_cap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+        let handle = std::thread::spawn(move || benchmark::run(root, exts, cap, thread_cancel));
+        self.benchmark = Some(Benchmark::Running{handle, cancel});
+        Ok(())
+    }
+
+    fn poll_benchmark(&mut self, ctx: &egui::Context) {
+        let Some(Benchmark::Running{handle, ..}) = &self.benchmark else {
+            return;
+        };
+        if !handle.is_finished() {
+            ctx.request_repaint();
+            return;
+        }
+
+        let Some(Benchmark::Running{handle, ..}) = self.benchmark.take() else {
+            unreachable!();
+        };
+        // Canceled or panicked, there's nothing to show either way.
+        self.benchmark = handle.join().ok().flatten().map(Benchmark::Done);
+    }
+
+    fn draw_benchmark(&mut self, ctx: &egui::Context) {
+        let Some(Benchmark::Done(results)) = &self.benchmark else {
+            return;
+        };
+
+        let recommended = results.recommended();
+        let mut adopt = None;
+        let mut close = false;
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Algorithm comparison"));
+        let vid = egui::viewport::ViewportId::from_hash_of("benchmark window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                close = ctx.input(|i| i.viewport().close_requested());
+
+                ui.label(tr!(
+                    "Hashed {count} images sampled from {root} ({failed} couldn't be read)",
+                    count = results.sampled - results.failed,
+                    root = self.opts.root.display(),
+                    failed = results.failed,
+                ));
+                ui.separator();
+
+                egui::Grid::new("benchmark").num_columns(3).striped(true).show(ui, |ui| {
+                    ui.strong(tr!("Algorithm"));
+                    ui.strong(tr!("Speed"));
+                    ui.strong(tr!("Collisions"));
+                    ui.end_row();
+
+                    for result in &results.algs {
+                        let name = format!("{:?}", result.alg);
+                        if Some(result.alg) == recommended {
+                            ui.label(egui::RichText::new(format!("{name} ★")).strong());
+                        } else {
+                            ui.label(name);
+                        }
+                        ui.label(tr!("{rate} img/s", rate = format!("{:.0}", result.images_per_sec)));
+                        ui.label(result.collisions.to_string());
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.label(egui::RichText::new(tr!(
+                    "Collisions are pairs of sampled images with identical hashes. A random sample \
+                    rarely contains real duplicates, so fewer is usually better."
+                )).small());
+                if let Some(alg) = recommended {
+                    if ui.button(tr!("Use {alg}", alg = format!("{alg:?}"))).clicked() {
+                        adopt = Some(alg);
+                    }
+                }
+            });
+        });
+
+        if let Some(alg) = adopt {
+            self.opts.hash = alg;
+            self.opts.verify_color = matches!(alg, HashAlg::Mean | HashAlg::Gradient);
+            close = true;
+        }
+        if close {
+            self.benchmark = None;
+        }
+    }
+
+    fn draw_subfolders(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(tr!("Limit to subfolders…")).clicked() {
+                self.folder_tree = Some(FolderTree::default());
+            }
+            if self.opts.subfolders.is_empty() {
+                ui.label(egui::RichText::new(tr!("Searching the whole folder")).small());
+            } else {
+                ui.label(egui::RichText::new(tr!("Only {count} subfolders", count = self.opts.subfolders.len())).small())
+                    .on_hover_text(self.opts.subfolders.iter().map(|x| x.display()).join("\n"));
+                if ui.small_button(tr!("Clear")).clicked() {
+                    self.opts.subfolders.clear();
+                }
+            }
+        });
+    }
+
+    fn draw_folder_tree(&mut self, ctx: &egui::Context) {
+        let Some(tree) = &mut self.folder_tree else {
+            return;
+        };
+
+        let mut close = false;
+        let vb = egui::viewport::ViewportBuilder::default().with_title(tr!("Limit to subfolders"));
+        let vid = egui::viewport::ViewportId::from_hash_of("folder tree window");
+        ctx.show_viewport_immediate(vid, vb, |ctx, _| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                close = ctx.input(|i| i.viewport().close_requested());
+
+                ui.label(tr!("Only the checked folders will be searched. With none checked, the whole folder is."));
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Clear")).clicked() {
+                        self.opts.subfolders.clear();
+                    }
+                    close |= ui.button(tr!("Done")).clicked();
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().drag_to_scroll(false).show(ui, |ui| {
+                    tree.draw(ui, &self.opts.root, &mut self.opts.subfolders);
+                });
+            });
+        });
+
+        if close {
+            self.folder_tree = None;
+        }
+    }
+
+    // What Deckard does and what it won't do without asking, for new users.
+    fn draw_welcome(&mut self, ui: &mut egui::Ui) {
+        let Some(show_again) = &mut self.welcome else {
+            return;
+        };
+        if !self.welcome_open {
+            return;
+        }
 
-            if ui.button("Choose...").clicked() {
-                if let Some(path) = rfd::FileDialog::new()
-                    .set_directory(&self.opts.root)
-                    .pick_folder() {
-                    self.opts.root = path;
+        let mut close = false;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.heading(tr!("Welcome to Deckard"));
+            ui.label(tr!("Deckard finds images that look the same, even when they've been resized, \
+                recompressed or converted to another format. Pick a folder and press Search."));
+            ui.label(egui::RichText::new(tr!("Nothing is moved, linked or deleted without your confirmation. \
+                Turn on Dry run in the results to see what an action would do first.")).strong());
+            ui.label(tr!("Under Advanced, the hash algorithm trades speed against strictness. Gradient, \
+                the default, suits most photo libraries; Mean is the loosest, and DoubleGradient and \
+                Blockhash are stricter, for collections of very similar images. Compare algorithms \
+                tries them all on a sample of your own folder."));
+            ui.horizontal(|ui| {
+                let mut dont_show = !*show_again;
+                ui.checkbox(&mut dont_show, tr!("Don't show this again"));
+                *show_again = !dont_show;
+                close = ui.button(tr!("Got it")).clicked();
+            });
+        });
+        if close {
+            self.welcome_open = false;
+        }
+        ui.separator();
+    }
+
+    // The option to highlight this frame, if any, and whether it's the first
+    // frame it's highlighted. Highlights fade after a few seconds.
+    fn active_focus(&mut self, ctx: &egui::Context) -> Option<(FocusHint, bool)> {
+        const HIGHLIGHT: Duration = Duration::from_secs(3);
+
+        let (hint, since) = self.focus.as_mut()?;
+        let first = since.is_none();
+        let since = *since.get_or_insert_with(Instant::now);
+        if since.elapsed() > HIGHLIGHT {
+            self.focus = None;
+            return None;
+        }
+        ctx.request_repaint_after(HIGHLIGHT - since.elapsed());
+        Some((*hint, first))
+    }
+
+    // A label in the advanced options, highlighted (and scrolled to) if a
+    // focus hint points at it.
+    fn option_label(ui: &mut egui::Ui, text: &str, focus: Option<(FocusHint, bool)>, hint: FocusHint) {
+        match focus {
+            Some((x, first)) if x == hint => {
+                let resp = ui.label(egui::RichText::new(text).strong().color(ui.visuals().warn_fg_color));
+                if first {
+                    resp.scroll_to_me(Some(egui::Align::Center));
                 }
+            },
+            _ => {
+                ui.label(text);
+            },
+        }
+    }
+
+    fn draw_lookup(&mut self, ctx: &egui::Context) -> Result<()> {
+        let Some(lookup) = &mut self.lookup else {
+            return Ok(());
+        };
+        let (open, action) = lookup.draw(ctx, self.opts.hash, self.opts.size_cap(), self.opts.check_opens)?;
+        if !open {
+            self.lookup = None;
+        }
+        if let Some(LookupAction::Reindex(root, hash)) = action {
+            let opts = self.reindex_opts(root, hash)?;
+            if let Some(lookup) = &mut self.lookup {
+                lookup.reindex(opts);
             }
+        }
+        Ok(())
+    }
+
+    // Re-indexing a folder searches all of it, with the algorithm it was
+    // indexed with and the form's file options. Nothing it finds is shown, so
+    // the options that only filter the results don't apply.
+    fn reindex_opts(&self, root: PathBuf, hash: HashAlg) -> Result<SearchOpts> {
+        Ok(SearchOpts {
+            root,
+            subfolders: vec![],
+            hash,
+            follow_sym: self.opts.follow_sym,
+            max_depth: None,
+            exts: options::parse_exts(&self.opts)?,
+            respect_ignore: self.opts.respect_ignore,
+            reference: None,
+            io_threads: self.opts.io_threads,
+            queue_size: self.opts.queue_size,
+            sniff: self.opts.sniff,
+            max_entries: options::parse_max_entries(&self.opts)?,
+            verify_color: false,
+            size_cap: self.opts.size_cap(),
+            detect_crops: false,
+            trim_borders: false, // Lookups hash the image as is
+            center: 100,
+            checkpoint: false,
+            resume: None,
+            modified: None,
+            max_distance: 0, // The index is kept by exact hash
+            grouping: Grouping::default(),
+            scan_libraries: self.opts.scan_libraries,
+        })
+    }
+
+    // Offers the results of a search run with --headless-scan since the last
+    // launch, e.g. overnight from cron.
+    fn draw_scan_offer(&mut self, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
+        let Some(scan) = &self.scan else {
+            return Ok(None);
+        };
+        let finished = scan.finished.map(|x| misc::format_time(UNIX_EPOCH + Duration::from_secs(x)));
+        let (mut view, mut dismissed) = (false, false);
+        ui.horizontal(|ui| {
+            ui.label(tr!(
+                "Results from a scheduled scan of {folder} are ready (finished {time}, {groups} groups)",
+                folder = scan.opts.root.display(),
+                time = finished.unwrap_or_default(),
+                groups = scan.group_count,
+            ));
+            view = ui.button(tr!("View")).clicked();
+            dismissed = ui.small_button(tr!("Dismiss")).clicked();
         });
+        ui.separator();
+
+        // Whatever happens, it's been seen.
+        if dismissed || view {
+            Session::mark_viewed(scan.finished);
+            self.scan = None;
+        }
+        if view {
+            let session = Session::load_scan()
+                .map_err(|e| Error::new("Error restoring session".to_string(), e))?;
+            return Ok(Some(self.restored(session)));
+        }
+        Ok(None)
+    }
+
+    fn restore_session(&mut self) -> Result<DynPhase> {
+        let session = Session::load()
+            .map_err(|e| Error::new("Error restoring session".to_string(), e))?;
+        Ok(self.restored(session))
+    }
+
+    // Opens a session file the user picks, e.g. a portable one saved in the
+    // root on another computer. Roots that have moved relative to the file
+    // are asked for.
+    fn open_session(&mut self) -> Result<Option<DynPhase>> {
+        let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return Ok(None);
+        };
+        let locate = |name: &str| {
+            let locate = rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title(tr!("Folder not found"))
+                .set_description(tr!("{name} isn't where it was relative to the session. Locate it?", name = name))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if locate != rfd::MessageDialogResult::Yes {
+                return None;
+            }
+            rfd::FileDialog::new().set_title(tr!("Locate {name}", name = name)).pick_folder()
+        };
+        let error = |e| Error::new("Error opening session".to_string(), e);
+        let (session, ignored) = Session::load_portable(&path, locate).map_err(error)?;
+        if !ignored.is_empty() {
+            let mut list = IgnoreList::load().map_err(error)?;
+            if list.add(ignored) > 0 {
+                list.save().map_err(error)?;
+            }
+        }
+        Ok(Some(self.restored(session)))
+    }
+
+    fn restored(&mut self, mut session: Session) -> DynPhase {
+        let history = std::mem::take(&mut session.history);
+        let (opts, groups, reviews) = session.into_groups();
+        let opts = self.opts.with_session(&opts);
+        let hash = opts.hash;
+        OutputPhase::new(opts, SearchResults::restored(groups, hash))
+            .with_reviews(reviews)
+            .with_history(history)
+            .into_dyn()
+    }
+}
+
+impl Phase for StartupPhase {
+    fn render(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Result<Option<DynPhase>> {
+        // Sampled before any widgets are drawn: a single-line TextEdit gives up
+        // focus on the same frame Enter is pressed, so checking afterwards
+        // can't tell "Enter to search" from "Enter to finish editing".
+        let text_focused = ctx.wants_keyboard_input();
+
+        // A search in another tab may have left, replaced or deleted the
+        // checkpoint since it was read.
+        if searcher::searches_ended() != self.checkpoint_read {
+            self.checkpoint_read = searcher::searches_ended();
+            self.checkpoint = Checkpoint::load();
+        }
+
+        if std::mem::take(&mut self.search_now) {
+            if let Some(next) = self.make_searching_phase(false)? {
+                return Ok(Some(next));
+            }
+        }
+        // A folder dropped on the window is the one to search, as one given
+        // on the command line is.
+        if let Some(path) = ctx.input(|i| i.raw.dropped_files.iter().find_map(|x| x.path.clone())) {
+            match launch::checked_root(&path) {
+                Ok(root) => self.opts.root = root,
+                Err(e) => self.notices.push(e),
+            }
+        }
+
+        if !self.notices.is_empty() {
+            let mut dismissed = None;
+            for (idx, notice) in self.notices.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(notice).color(ui.visuals().warn_fg_color));
+                    if ui.small_button(tr!("Dismiss")).clicked() {
+                        dismissed = Some(idx);
+                    }
+                });
+            }
+            if let Some(idx) = dismissed {
+                self.notices.remove(idx);
+            }
+            ui.separator();
+        }
+
+        if let Some(next) = self.draw_scan_offer(ui)? {
+            return Ok(Some(next));
+        }
+        if let Some(full) = FullResults::draw_banner(&mut self.refining, ui, &self.opts) {
+            return Ok(Some(full));
+        }
+
+        self.draw_welcome(ui);
+        self.poll_benchmark(ctx);
+        self.draw_benchmark(ctx);
+
+        self.draw_presets(ui)?;
+        ui.separator();
+
+        let root_problem = options::root_problem(&self.opts);
+        let reference_problem = options::reference_problem(&self.opts);
+
+        ui.checkbox(&mut self.opts.compare, tr!("Compare two folders"))
+            .on_hover_text(tr!("Find images in the candidates folder that already exist in the reference folder"));
+
+        let old_root = self.opts.root.clone();
+        if self.opts.compare {
+            Self::folder_field(ui, tr!("Reference: "), &mut self.opts.reference, &reference_problem, None);
+            let recent = Some(&mut self.opts.recent_roots);
+            Self::folder_field(ui, tr!("Candidates: "), &mut self.opts.root, &root_problem, recent);
+        } else {
+            let recent = Some(&mut self.opts.recent_roots);
+            Self::folder_field(ui, tr!("Root Path: "), &mut self.opts.root, &root_problem, recent);
+        }
+        // The selection only makes sense under the root it was made in.
+        if self.opts.root != old_root {
+            self.opts.subfolders.clear();
+            self.folder_tree = None;
+        }
+        if root_problem.is_none() {
+            self.draw_subfolders(ui);
+        }
+        self.draw_folder_tree(ctx);
+        self.draw_lookup(ctx)?;
 
         ui.separator();
 
-        ui.collapsing("Advanced", |ui| {
+        let mut start_benchmark = false;
+        let focus = self.active_focus(ctx);
+        if focus.is_some_and(|(_, first)| first) {
+            self.opts.advanced_open = true;
+        }
+        // Explicit id, so it stays open when the language changes. Whether
+        // it's open is kept with the options, so it's remembered.
+        let advanced = egui::CollapsingHeader::new(tr!("Advanced")).id_source("advanced").open(Some(self.opts.advanced_open)).show(ui, |ui| {
             egui::Grid::new(0).num_columns(2).show(ui, |ui| {
-                ui.label("Follow Symlinks:");
+                ui.label(tr!("Follow Symlinks:"));
                 ui.checkbox(&mut self.opts.follow_sym, "");
                 ui.end_row();
+
+                ui.label(tr!("Entry Limit:"));
+                ui.add(TextEdit::singleline(&mut self.opts.max_entries).desired_width(100.0))
+                    .on_hover_text(tr!("Stop after visiting this many files and folders, in case \
+                        symlinks lead somewhere enormous. Leave empty for no limit."));
+                ui.end_row();
+
+                ui.label(tr!("Modified After:"));
+                ui.add(TextEdit::singleline(&mut self.opts.modified_after)
+                    .hint_text("YYYY-MM-DD")
+                    .desired_width(100.0));
+                ui.end_row();
+
+                ui.label(tr!("Modified Before:"));
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.opts.modified_before)
+                        .hint_text("YYYY-MM-DD")
+                        .desired_width(100.0));
+                    ui.checkbox(&mut self.opts.date_any_member, tr!("Include copies outside the dates"))
+                        .on_hover_text(tr!("Instead of ignoring files outside the dates, show any group \
+                            with at least one file inside them"));
+                });
+                ui.end_row();
+
+                Self::option_label(ui, tr!("Size Cap:"), focus, FocusHint::SizeLimits);
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.opts.max_megapixels)
+                        .clamp_range(1..=1000)
+                        .suffix(" MP"))
+                        .on_hover_text(tr!("Larger images are scaled down before hashing, which finds \
+                            the same duplicates much faster"));
+                    ui.checkbox(&mut self.opts.skip_oversized, tr!("Skip instead"));
+                });
+                ui.end_row();
+
+                Self::option_label(ui, tr!("Memory Budget:"), focus, FocusHint::SizeLimits);
+                ui.horizontal(|ui| {
+                    let auto_mb = SizeCap::auto_max_bytes() / (1024 * 1024);
+                    let mut auto = self.opts.memory_budget_mb.is_none();
+                    if ui.checkbox(&mut auto, tr!("Automatic"))
+                        .on_hover_text(tr!("A quarter of the memory that was free when Deckard started"))
+                        .changed() {
+                        self.opts.memory_budget_mb = (!auto).then_some(auto_mb);
+                    }
+                    let mut budget = self.opts.memory_budget_mb.unwrap_or(auto_mb);
+                    let resp = ui.add_enabled(!auto, egui::DragValue::new(&mut budget)
+                        .clamp_range(64..=65536)
+                        .speed(16)
+                        .suffix(" MB"))
+                        .on_hover_text(tr!("Images that would need more memory than this to decode \
+                            are skipped. Raise it if you have memory to spare."));
+                    if resp.changed() {
+                        self.opts.memory_budget_mb = Some(budget);
+                    }
+                });
+                ui.end_row();
+
+                ui.label(tr!("Accurate Decode:"));
+                ui.checkbox(&mut self.opts.accurate_decode, "")
+                    .on_hover_text(tr!("Decode JPEGs at full size before hashing. Much slower; only \
+                        worth it if scaled-down decoding seems to miss or mismatch images."));
+                ui.end_row();
+
+                ui.label(tr!("Respect Ignore Files:"));
+                ui.checkbox(&mut self.opts.respect_ignore, "")
+                    .on_hover_text(tr!("Skip anything excluded by .gitignore or .ignore files"));
+                ui.end_row();
+
+                ui.label(tr!("Post-search Hook:"));
+                ui.add(TextEdit::singleline(&mut self.opts.hook_command)
+                    .code_editor()
+                    .hint_text("/usr/local/bin/my-dedupe.sh {group_json}")
+                    .desired_width(f32::INFINITY))
+                    .on_hover_text(tr!("A command to run on a duplicate group when you ask for it from \
+                        the results. {paths} becomes the group's paths, which are also passed on \
+                        stdin, NUL-separated; {group_json} becomes a file with the group as JSON. \
+                        Never run automatically."));
+                ui.end_row();
+
+                ui.label(tr!("External Compare Tool:"));
+                ui.add(TextEdit::singleline(&mut self.opts.compare_command)
+                    .code_editor()
+                    .hint_text("/usr/local/bin/ksdiff {a} {b}")
+                    .desired_width(f32::INFINITY))
+                    .on_hover_text(tr!("A command to open a pair of images in, e.g. to diff them. {a} \
+                        and {b} become their paths. Offered on groups of two, and in the compact \
+                        list's menus when another image is selected."));
+                ui.end_row();
+
+                ui.label(tr!("I/O Parallelism:"));
+                ui.add(egui::DragValue::new(&mut self.opts.io_threads).clamp_range(1..=256))
+                    .on_hover_text(
+                        "The most files read at once. Decoding still uses every CPU. \
+                        The default suits local disks; for spinning disks and network \
+                        shares, 1 or 2 avoids competing reads and is often faster."
+                    );
+                ui.end_row();
+
+                ui.label(tr!("Walk Ahead:"));
+                ui.add(egui::DragValue::new(&mut self.opts.queue_size)
+                    .clamp_range(16..=1_000_000)
+                    .speed(16)
+                    .suffix(tr!(" files")))
+                    .on_hover_text(tr!("The most files the search lists before hashing catches up. \
+                        Higher can keep slow disks busier; lower uses less memory on huge folders."));
+                ui.end_row();
+
+                ui.label(tr!("Notify When Done:"));
+                ui.checkbox(&mut self.opts.notify, "")
+                    .on_hover_text(tr!("Show a notification when a search that took a while finishes \
+                        while Deckard's in the background"));
+                ui.end_row();
+
+                ui.label(tr!("Portable Sessions:"));
+                ui.checkbox(&mut self.opts.portable, "")
+                    .on_hover_text(tr!("Save sessions in the root, and plan exports, with paths relative to \
+                        where they're saved, so they still open when the library's mounted somewhere else"));
+                ui.end_row();
+
+                ui.label(tr!("Check Before Opening:"));
+                ui.checkbox(&mut self.opts.check_opens, "")
+                    .on_hover_text(tr!("Warn before opening a file whose contents don't match its extension, \
+                        e.g. a .jpg that's really a program or a web page"));
+                ui.end_row();
+
+                ui.label(tr!("Hide System Images:"));
+                ui.checkbox(&mut self.opts.junk_filter, "")
+                    .on_hover_text(tr!("Hide groups of app icons, stock wallpapers and the like, from a built-in \
+                        list and the groups you've marked, behind a toggle in the results"));
+                ui.end_row();
+
+                ui.label(tr!("Collapse Groups Over:"));
+                ui.add(egui::DragValue::new(&mut self.opts.large_group).clamp_range(0..=10_000).suffix(tr!(" images")))
+                    .on_hover_text(tr!("Show bigger groups as a summary, with their members a page at a time \
+                        on request. 0 shows every group in full."));
+                ui.end_row();
+                ui.end_row();
+
+                Self::option_label(ui, tr!("Hash Algorithm:"), focus, FocusHint::Matching);
+                // Wraps so longer labels in the first column don't push it offscreen.
+                let changed = ui.horizontal_wrapped(|ui| {
+                    [
+                        ui.selectable_value(&mut self.opts.hash, HashAlg::Mean, "Mean"),
+                        ui.selectable_value(&mut self.opts.hash, HashAlg::Gradient, "Gradient"),
+                        ui.selectable_value(&mut self.opts.hash, HashAlg::VertGradient, "VertGradient"),
+                        ui.selectable_value(&mut self.opts.hash, HashAlg::DoubleGradient, "DoubleGradient"),
+                        ui.selectable_value(&mut self.opts.hash, HashAlg::Blockhash, "Blockhash"),
+                    ].iter().any(|x| x.clicked())
+                }).inner;
+                if changed {
+                    // Mean and the single gradients only see structure, so they're the
+                    // ones that lump together differently colored images.
+                    self.opts.verify_color = matches!(self.opts.hash, HashAlg::Mean | HashAlg::Gradient);
+                }
+                ui.end_row();
+
+                Self::option_label(ui, tr!("Verify Matches by Color:"), focus, FocusHint::Matching);
+                ui.checkbox(&mut self.opts.verify_color, "")
+                    .on_hover_text(tr!("Split up matches whose colors differ, e.g. the same scene \
+                        photographed at dusk and at noon. Costs a little time per image."));
+                ui.end_row();
+
+                Self::option_label(ui, tr!("Match Distance:"), focus, FocusHint::Matching);
+                ui.horizontal_wrapped(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.opts.max_distance).clamp_range(0..=16))
+                        .on_hover_text(tr!("How many bits of the hash can differ for images to still \
+                            match. 0 only matches identical hashes; higher finds edited copies, and \
+                            compares every pair of images, which is slow on big libraries."));
+                    ui.add_enabled_ui(self.opts.max_distance > 0, |ui| {
+                        ui.selectable_value(&mut self.opts.grouping, Grouping::Strict, tr!("Strict"))
+                            .on_hover_text(tr!("Only group images that all match each other"));
+                        ui.selectable_value(&mut self.opts.grouping, Grouping::Chain, tr!("Chain"))
+                            .on_hover_text(tr!("Also group images that only match through others, \
+                                which can join quite different images by way of similar ones"));
+                    });
+                });
+                ui.end_row();
+
+                ui.label(tr!("Detect Crops (experimental):"));
+                ui.checkbox(&mut self.opts.detect_crops, "")
+                    .on_hover_text(tr!("Also look for images that are crops of others. Much slower, \
+                        and matches should be checked by eye."));
+                ui.end_row();
+
+                ui.label(tr!("Trim Uniform Borders:"));
+                ui.checkbox(&mut self.opts.trim_borders, "")
+                    .on_hover_text(tr!("Ignore solid-color bars along the edges when comparing, so \
+                        letterboxed or padded copies match the original"));
+                ui.end_row();
+
+                ui.label(tr!("Hash Central Region:"));
+                ui.horizontal(|ui| {
+                    ui.add(egui::Slider::new(&mut self.opts.center_crop, UserOpts::CENTER_CROP).suffix("%"))
+                        .on_hover_text(tr!("Only compare the middle of each image, so copies with a watermark \
+                            or frame added at the edges still match. 100% compares all of it."));
+                    if self.opts.center_crop < 100 {
+                        ui.colored_label(ui.visuals().warn_fg_color, "⚠")
+                            .on_hover_text(tr!("Images that differ only at their edges will match too, \
+                                so expect more false positives among photos with busy edges"));
+                    }
+                });
                 ui.end_row();
 
-                ui.label("Hash Algorithm:");
+                ui.label(tr!("Collapse Bursts:"));
                 ui.horizontal(|ui| {
-                    ui.selectable_value(&mut self.opts.hash, HashAlg::Mean, "Mean");
-                    ui.selectable_value(&mut self.opts.hash, HashAlg::Gradient, "Gradient");
-                    ui.selectable_value(&mut self.opts.hash, HashAlg::VertGradient, "VertGradient");
-                    ui.selectable_value(&mut self.opts.hash, HashAlg::DoubleGradient, "DoubleGradient");
-                    ui.selectable_value(&mut self.opts.hash, HashAlg::Blockhash, "Blockhash");
+                    ui.checkbox(&mut self.opts.collapse_bursts, "")
+                        .on_hover_text(tr!("Collapse groups whose images are all in one folder \
+                            and were taken within a few seconds of each other"));
+                    ui.add_enabled(self.opts.collapse_bursts, egui::DragValue::new(&mut self.opts.burst_window)
+                        .clamp_range(0.5..=60.0)
+                        .speed(0.1)
+                        .suffix(" s"));
                 });
                 ui.end_row();
 
-                ui.label("More Info:");
+                ui.label(tr!("Derivative Patterns:"));
+                ui.add(TextEdit::singleline(&mut self.opts.derivative_patterns)
+                    .hint_text("_web, _thumb, -edited")
+                    .desired_width(f32::INFINITY))
+                    .on_hover_text(tr!("Comma-separated parts of a file name that mark an intentional \
+                        derivative, e.g. \"_web\" for foo_web.jpg next to foo.jpg. Groups of just an \
+                        original and its derivatives are hidden."));
+                ui.end_row();
+
+                ui.label(tr!("Resolution Variants:"));
+                ui.add(egui::DragValue::new(&mut self.opts.variant_factor)
+                    .clamp_range(1.1..=10.0)
+                    .speed(0.05)
+                    .suffix("×"))
+                    .on_hover_text(tr!("Groups of the same picture where one copy is at least this many \
+                        times wider and taller than the rest are marked as resolution variants, and can \
+                        be resolved by keeping the largest"));
+                ui.end_row();
+
+                ui.label(tr!("Sidecar Files:"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.opts.sidecars, "")
+                        .on_hover_text(tr!("Trash sidecar files, e.g. IMG_1234.xmp or IMG_1234.jpg.xmp, \
+                            along with their images"));
+                    ui.add_enabled(self.opts.sidecars, TextEdit::singleline(&mut self.opts.sidecar_exts)
+                        .hint_text(UserOpts::DEFAULT_SIDECAR_EXTS)
+                        .desired_width(f32::INFINITY));
+                });
+                ui.end_row();
+
+                ui.label(tr!("More Info:"));
                 ui.add(
                     egui::widgets::Hyperlink::from_label_and_url(
-                        "link",
+                        tr!("link"),
                         "https://github.com/qarmin/img_hash/blob/23c3a436b92fc90c37346163c820bfdc5f2225c6/src/alg/mod.rs#L10-L76",
                     )
                 );
                 ui.end_row();
+
+                ui.label(tr!("Compare Algorithms:"));
+                ui.horizontal(|ui| {
+                    match &self.benchmark {
+                        Some(Benchmark::Running{cancel, ..}) => {
+                            ui.spinner();
+                            if ui.button(tr!("Cancel")).clicked() {
+                                cancel.store(true, Ordering::Relaxed);
+                            }
+                        },
+                        _ => {
+                            start_benchmark = ui.add_enabled(
+                                root_problem.is_none(),
+                                egui::Button::new(tr!("Compare algorithms on a sample")),
+                            ).on_hover_text(tr!(
+                                "Hash a few hundred random images from the root with each algorithm \
+                                and compare their speed and false matches"
+                            )).clicked();
+                        },
+                    }
+                });
+                ui.end_row();
+                ui.end_row();
+
+                Self::option_label(ui, tr!("Extensions:"), focus, FocusHint::Extensions);
+                let exts_problem = self.exts_problem();
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.menu_button(tr!("Choose…"), |ui| self.draw_ext_picker(ui));
+                        let mut textedit = TextEdit::singleline(&mut self.opts.exts)
+                            .desired_width(f32::INFINITY);
+                        if exts_problem.is_some() {
+                            textedit = textedit.text_color(ui.visuals().error_fg_color);
+                        }
+                        let resp = ui.add(textedit);
+                        if focus == Some((FocusHint::Extensions, true)) {
+                            resp.request_focus();
+                        }
+                    });
+                    if let Some(problem) = &exts_problem {
+                        ui.label(egui::RichText::new(problem).small().color(ui.visuals().error_fg_color));
+                    }
+                });
                 ui.end_row();
 
-                ui.label("Extensions:");
-                let textedit = TextEdit::singleline(&mut self.opts.exts)
-                    .desired_width(f32::INFINITY);
-                ui.add(textedit);
+                Self::option_label(ui, tr!("Detect Type by Content:"), focus, FocusHint::Extensions);
+                ui.checkbox(&mut self.opts.sniff, "")
+                    .on_hover_text(tr!("Also check the first few KB of files with other extensions (or none) \
+                        for the formats above. Slower on folders with many non-image files."));
+                ui.end_row();
+
+                ui.label(tr!("Scan All of Photo Libraries:"));
+                ui.checkbox(&mut self.opts.scan_libraries, "")
+                    .on_hover_text(tr!("Apple Photos, Lightroom and digiKam libraries keep their own previews \
+                        and thumbnails of every picture, which would match the originals. Normally only the \
+                        originals are searched; this searches everything in them."));
+                ui.end_row();
+
+                ui.end_row();
+
+                ui.label(tr!("UI Scale:"));
+                let mut zoom = ctx.zoom_factor();
+                let slider = egui::Slider::new(&mut zoom, MIN_ZOOM..=MAX_ZOOM)
+                    .fixed_decimals(1)
+                    .custom_formatter(|x, _| format!("{:.0}%", x * 100.0));
+                if ui.add(slider).on_hover_text(tr!("Also Ctrl/Cmd +, -, and 0")).changed() {
+                    set_zoom(ctx, zoom);
+                }
+                ui.end_row();
+
+                ui.label(tr!("Language:"));
+                let mut language = Language::current();
+                egui::ComboBox::from_id_source("language")
+                    .selected_text(language.name())
+                    .show_ui(ui, |ui| {
+                        for x in Language::ALL {
+                            ui.selectable_value(&mut language, x, x.name());
+                        }
+                    });
+                language.set_current();
+                ui.end_row();
+
+                ui.label(tr!("Size Units:"));
+                let mut units = SizeUnits::current();
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut units, SizeUnits::Decimal, tr!("Decimal (MB)"));
+                    ui.selectable_value(&mut units, SizeUnits::Binary, tr!("Binary (MiB)"));
+                });
+                units.set_current();
+                ui.end_row();
+
+                ui.label(tr!("Theme:"));
+                let mut theme = Theme::current();
+                ui.horizontal(|ui| {
+                    for x in Theme::ALL {
+                        ui.selectable_value(&mut theme, x, x.label());
+                    }
+                })
+                    .response
+                    .on_hover_text(tr!("System follows the operating system's light or dark mode"));
+                if theme != Theme::current() {
+                    theme.set_current();
+                    ctx.request_repaint(); // App applies it at the start of the next frame
+                }
                 ui.end_row();
 
-                ui.label("Supported:");
-                ui.label(SUPPORTED_EXTS.iter().join(","));
+                ui.label(tr!("Thumbnail Memory:"));
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.opts.thumb_memory_mb)
+                        .clamp_range(64..=8192)
+                        .speed(16)
+                        .suffix(" MB"))
+                        .on_hover_text(tr!("The most graphics memory the results' thumbnails can take. \
+                            Lower it if scrolling through many results slows everything else down."));
+                    ui.checkbox(&mut self.opts.show_thumb_usage, tr!("Show usage"))
+                        .on_hover_text(tr!("Show how much is in use above the results"));
+                });
                 ui.end_row();
 
             });
         });
+        if advanced.header_response.clicked() {
+            self.opts.advanced_open = !self.opts.advanced_open;
+        }
+
+        if start_benchmark {
+            self.start_benchmark()?;
+        }
 
         ui.separator();
 
-        if ui.button("Search").clicked() 
-            || ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-            return self.make_searching_phase().map(Some);
+        let valid = root_problem.is_none() && reference_problem.is_none() && self.exts_problem().is_none();
+        let (clicked, restore, open) = ui.horizontal(|ui| {
+            let running = searcher::search_running();
+            let mut search = ui.add_enabled(valid && !running, egui::Button::new(tr!("Search")));
+            if running {
+                search = search.on_disabled_hover_text(tr!("Another tab is still searching"));
+            }
+            let clicked = search.clicked();
+            let restore = ui.add_enabled(self.session_available, egui::Button::new(tr!("Restore last results")))
+                .on_hover_text(tr!("Reopen the results saved from the last session"))
+                .clicked();
+            let open = ui.button(tr!("Open session…"))
+                .on_hover_text(tr!("Open saved results from a file, e.g. a portable session saved in a library"))
+                .clicked();
+            if ui.button(tr!("Look up an image…"))
+                .on_hover_text(tr!("Check whether an image is already in a folder searched before"))
+                .clicked() {
+                self.lookup.get_or_insert_with(Lookup::new);
+            }
+            (clicked, restore, open)
+        }).inner;
+        if restore {
+            return self.restore_session().map(Some);
+        }
+        if open {
+            return self.open_session();
+        }
+
+        let mut resume = false;
+        if let Some(checkpoint) = self.resumable() {
+            // The total's only known if the search got to the end of its walk.
+            let prompt = match checkpoint.percent_done() {
+                Some(percent) => tr!("Resume the unfinished scan ({percent}% done)?", percent = percent),
+                None => tr!("Resume the unfinished scan ({count} images already hashed)?", count = checkpoint.files.len()),
+            };
+            let mut discard = false;
+            ui.horizontal(|ui| {
+                ui.label(prompt);
+                resume = ui.button(tr!("Resume")).clicked();
+                discard = ui.button(tr!("Discard")).clicked();
+            });
+            if discard {
+                self.checkpoint = None;
+                Checkpoint::delete();
+            }
+        }
+        if resume {
+            return self.make_searching_phase(true);
+        }
+
+        let entered = valid && !text_focused && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if clicked || entered {
+            return self.make_searching_phase(false);
         }
 
         Ok(None)
     }
 
+    fn title(&self) -> String {
+        tr!("New search").to_string()
+    }
+
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        storage.set_string(ROOT_KEY, self.opts.root.to_string_lossy().into());
+        self.opts.save(storage);
+        if let Some(show_again) = self.welcome {
+            settings::set(storage, settings::WELCOME, &show_again);
+        }
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        let mut commands = vec![];
+        if self.opts.compare {
+            commands.push(Command::new("choose_reference", tr!("Choose reference folder…")));
+            commands.push(Command::new("choose_root", tr!("Choose candidates folder…")));
+        } else {
+            commands.push(Command::new("choose_root", tr!("Choose root…")));
+        }
+        if options::root_problem(&self.opts).is_none() && options::reference_problem(&self.opts).is_none() && self.exts_problem().is_none()
+            && !searcher::search_running() {
+            commands.push(Command::new("search", tr!("Start search")));
+        }
+        if self.resumable().is_some() {
+            commands.push(Command::new("resume", tr!("Resume unfinished scan")));
+        }
+        if self.session_available {
+            commands.push(Command::new("restore", tr!("Restore last results")));
+        }
+        commands.push(Command::new("open_session", tr!("Open session…")));
+        commands.push(Command::new("lookup", tr!("Look up an image…")));
+        commands
+    }
+
+    fn run_command(&mut self, _ctx: &egui::Context, id: &str) -> Result<Option<DynPhase>> {
+        match id {
+            "choose_root" => Self::pick_folder(&mut self.opts.root),
+            "choose_reference" => Self::pick_folder(&mut self.opts.reference),
+            "search" => return self.make_searching_phase(false),
+            "resume" => return self.make_searching_phase(true),
+            "restore" => return self.restore_session().map(Some),
+            "open_session" => return self.open_session(),
+            "lookup" => {
+                self.lookup.get_or_insert_with(Lookup::new);
+            },
+            _ => (),
+        }
+        Ok(None)
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Memory(HashMap<String, String>);
+
+    impl eframe::Storage for Memory {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn presets_bring_their_search_options_only() {
+        let mut storage = Memory::default();
+        assert!(UserOpts::from_preset(&storage, "weekly").err().unwrap().ends_with("none are saved"));
+
+        let mut weekly = UserOpts::new(PathBuf::from("/photos"));
+        weekly.max_distance = 4;
+        weekly.density = ResultsDensity::Compact;
+        settings::set(&mut storage, settings::PRESETS, &BTreeMap::from([("weekly".to_string(), weekly)]));
+        settings::set(&mut storage, settings::DENSITY, &ResultsDensity::Comfortable);
+
+        let opts = UserOpts::from_preset(&storage, "weekly").unwrap();
+        assert_eq!(opts.root, Path::new("/photos"));
+        assert_eq!(opts.max_distance, 4);
+        // Display preferences are the app's, not the preset's.
+        assert_eq!(opts.density, ResultsDensity::Comfortable);
+        assert!(opts.presets.saved.contains_key("weekly"));
+
+        let err = UserOpts::from_preset(&storage, "nightly").err().unwrap();
+        assert_eq!(err, "There's no preset named \"nightly\"; saved presets: \"weekly\"");
+    }
+
+    #[test]
+    fn sessions_keep_global_preferences() {
+        let mut current = UserOpts::new(PathBuf::from("/current"));
+        current.density = ResultsDensity::Compact;
+        current.notify = false;
+        current.large_group = 50;
+        current.presets.saved.insert("weekly".to_string(), UserOpts::new(PathBuf::from("/photos")));
+
+        let mut session = UserOpts::new(PathBuf::from("/session"));
+        session.max_distance = 6;
+        session.density = ResultsDensity::Comfortable;
+        session.notify = true;
+
+        let opts = current.with_session(&session);
+        assert_eq!(opts.root, Path::new("/session"));
+        assert_eq!(opts.max_distance, 6);
+        assert_eq!(opts.density, ResultsDensity::Compact);
+        assert!(!opts.notify);
+        assert_eq!(opts.large_group, 50);
+        assert!(opts.presets.saved.contains_key("weekly"));
+    }
+
+    #[test]
+    fn recent_roots_dedupe_and_age_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let folders: Vec<PathBuf> = (0..10).map(|x| dir.path().join(x.to_string())).collect();
+        for folder in &folders {
+            std::fs::create_dir(folder).unwrap();
+        }
+        let canonical = |idx: usize| folders[idx].canonicalize().unwrap();
+
+        let mut recent = RecentRoots::default();
+        recent.add(&folders[0]);
+        recent.add(&dir.path().join("0/"));
+        recent.add(&dir.path().join("1/../0"));
+        assert_eq!(recent.entries, [RecentRoot{path: canonical(0), pinned: false}]);
+
+        recent.toggle_pin(0);
+        for folder in &folders[1..] {
+            recent.add(folder);
+        }
+        let paths: Vec<_> = recent.entries.iter().map(|x| x.path.clone()).collect();
+        let mut expected: Vec<_> = (2..10).rev().map(canonical).collect();
+        expected.push(canonical(0));
+        assert_eq!(paths, expected);
+
+        recent.add(&folders[0]);
+        assert_eq!(recent.entries[0], RecentRoot{path: canonical(0), pinned: true});
+        recent.clear();
+        assert_eq!(recent.entries, [RecentRoot{path: canonical(0), pinned: true}]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use eframe::egui;
+
+
+// Light or dark, following the OS unless the user picks one. A global like the
+// language, and for the same reason: it's wanted from every phase. Visuals are
+// per context rather than per viewport, so App setting them once covers every
+// window, deferred ones included.
+static THEME: AtomicU8 = AtomicU8::new(Theme::System as u8);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => tr!("System"),
+            Theme::Light => tr!("Light"),
+            Theme::Dark => tr!("Dark"),
+        }
+    }
+
+    pub fn to_key(self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|x| x.to_key() == key)
+    }
+
+    pub fn current() -> Theme {
+        Theme::ALL[THEME.load(Ordering::Relaxed) as usize]
+    }
+
+    pub fn set_current(self) {
+        THEME.store(self as u8, Ordering::Relaxed);
+    }
+
+    // Whether it's dark, given what the OS uses, if that's known. egui's own
+    // default is dark, so that's what an unknown OS theme gets.
+    pub fn is_dark(self, system: Option<eframe::Theme>) -> bool {
+        match self {
+            Theme::System => system != Some(eframe::Theme::Light),
+            Theme::Light => false,
+            Theme::Dark => true,
+        }
+    }
+}
+
+// Switches the context's visuals to the current theme, if they aren't already.
+// Returns whether they changed, as windows that only repaint for their own
+// input need to be asked to.
+pub fn apply(ctx: &egui::Context, system: Option<eframe::Theme>) -> bool {
+    let dark = Theme::current().is_dark(system);
+    if ctx.style().visuals.dark_mode == dark {
+        return false;
+    }
+    ctx.set_visuals(if dark { egui::Visuals::dark() } else { egui::Visuals::light() });
+    true
+}
+
+// For good news (exact matches, space to reclaim, the keeper), which egui's
+// visuals have no color for. The dark theme's green is too light to read on a
+// light background.
+pub fn success_color(visuals: &egui::Visuals) -> egui::Color32 {
+    match visuals.dark_mode {
+        true => egui::Color32::from_rgb(0x3C, 0xA0, 0x50),
+        false => egui::Color32::from_rgb(0x1E, 0x7A, 0x32),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_follows_system_unless_overridden() {
+        assert!(Theme::System.is_dark(Some(eframe::Theme::Dark)));
+        assert!(!Theme::System.is_dark(Some(eframe::Theme::Light)));
+        assert!(Theme::System.is_dark(None));
+        assert!(!Theme::Light.is_dark(Some(eframe::Theme::Dark)));
+        assert!(Theme::Dark.is_dark(Some(eframe::Theme::Light)));
+        for theme in Theme::ALL {
+            assert_eq!(Theme::from_key(theme.to_key()), Some(theme));
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::misc::Image;
+use crate::sources;
+use crate::os_path;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Condvar, OnceLock};
+
+use eframe::egui;
+
+
+// Results' thumbnails, decoded off the UI thread. Handing egui the raw bytes
+// instead would have it decode every image the first time it's drawn, all in
+// one frame, which with a screenful of large photos freezes the window for
+// seconds.
+//
+// Only what's drawn is requested, newest first, so a quick scroll doesn't
+// leave the workers busy with images long since scrolled past. Textures are
+// cached up to a budget of (estimated) bytes, least recently drawn evicted
+// first, and are dropped once they've gone undrawn for a while anyway, so a
+// long scroll through thousands of results doesn't use ever more GPU memory.
+
+pub enum Thumbnail {
+    Ready(egui::TextureHandle),
+    Loading,
+    Failed, // Couldn't be read or decoded
+}
+
+enum Entry {
+    Pending,
+    Ready(egui::TextureHandle),
+    Failed,
+}
+
+struct Queue {
+    pending: Vec<Image>, // Taken from the back
+    decoded: Vec<(PathBuf, Option<egui::ColorImage>)>, // Waiting to be uploaded
+    ctx: Option<egui::Context>, // To repaint when there's something new to show
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+// How much the cache can hold on to.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheLimits {
+    pub budget: u64, // Bytes of texture
+    pub idle_frames: u64, // Entries undrawn for longer are evicted, even under budget
+}
+
+impl CacheLimits {
+    pub const DEFAULT_BUDGET_MB: u64 = 512;
+
+    // A few seconds of scrolling, or a few minutes of sitting still (egui only
+    // draws when something changes).
+    pub const DEFAULT_IDLE_FRAMES: u64 = 600;
+
+    pub fn new(budget_mb: u64) -> CacheLimits {
+        CacheLimits{budget: budget_mb * 1024 * 1024, idle_frames: Self::DEFAULT_IDLE_FRAMES}
+    }
+}
+
+struct CacheEntry {
+    entry: Entry,
+    last_drawn: u64, // Frame number
+    bytes: u64, // Of the texture; estimated from the image's dimensions until it's decoded
+}
+
+struct Cache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    used: u64, // Sum of the entries' bytes
+    swept: u64, // Frame idle entries were last evicted in
+}
+
+pub struct Thumbnails {
+    shared: Arc<Shared>,
+    cache: Mutex<Cache>,
+    limits: CacheLimits,
+    started: OnceLock<()>, // Workers are only started once something's drawn
+}
+
+impl Thumbnails {
+    // Big enough for the hover preview. At up to 1 MB each, the default budget
+    // still covers several screenfuls.
+    const MAX_SIZE: u32 = 512;
+    const MAX_WORKERS: usize = 4;
+
+    pub fn new(limits: CacheLimits) -> Thumbnails {
+        let queue = Queue{pending: vec![], decoded: vec![], ctx: None, closed: false};
+        Thumbnails {
+            shared: Arc::new(Shared{queue: Mutex::new(queue), changed: Condvar::new()}),
+            cache: Mutex::new(Cache{entries: HashMap::new(), used: 0, swept: 0}),
+            limits,
+            started: OnceLock::new(),
+        }
+    }
+
+    // Bytes of texture held or on the way, and the budget for them.
+    pub fn usage(&self) -> (u64, u64) {
+        (self.cache.lock().unwrap().used, self.limits.budget)
+    }
+
+    // What the image's thumbnail will take once uploaded, as RGBA, scaled like
+    // DynamicImage::thumbnail() does (which scales small images up, too).
+    // Images of unknown size are assumed to be as big as thumbnails get.
+    fn estimate(image: &Image) -> u64 {
+        let max = Self::MAX_SIZE as f64;
+        let (width, height) = match image.dimm {
+            Some((width, height)) if width > 0 && height > 0 => {
+                let scale = max / width.max(height) as f64;
+                ((width as f64 * scale).round().max(1.0), (height as f64 * scale).round().max(1.0))
+            },
+            _ => (max, max),
+        };
+        width as u64 * height as u64 * 4
+    }
+
+    // The image's thumbnail, requesting it if it hasn't been yet.
+    pub fn get(&self, ctx: &egui::Context, image: &Image) -> Thumbnail {
+        self.started.get_or_init(|| self.start());
+        let frame = ctx.frame_nr();
+        let mut cache = self.cache.lock().unwrap();
+        let cache = &mut *cache; // So its fields can be borrowed separately
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.ctx.get_or_insert_with(|| ctx.clone());
+
+        // Whatever's been decoded since, unless it's been evicted meanwhile.
+        for (path, color) in queue.decoded.drain(..) {
+            let Some(cached @ CacheEntry{entry: Entry::Pending, ..}) = cache.entries.get_mut(&path) else {
+                continue;
+            };
+            let bytes = color.as_ref().map_or(0, |x| x.pixels.len() as u64 * 4);
+            cache.used = cache.used - cached.bytes + bytes;
+            cached.bytes = bytes;
+            cached.entry = match color {
+                Some(color) => {
+                    // By the raw path, as display() would give paths that
+                    // only differ in bytes that aren't UTF-8 the same name.
+                    let name = format!("thumbnail://{:016x}", os_path::raw_hash(&path));
+                    Entry::Ready(ctx.load_texture(name, color, Default::default()))
+                },
+                None => Entry::Failed,
+            };
+        }
+        // In case a decoded thumbnail came out bigger than estimated.
+        self.make_room(cache, &mut queue, frame, 0);
+
+        if cache.swept != frame {
+            cache.swept = frame;
+            self.evict_idle(cache, &mut queue, frame);
+        }
+
+        if let Some(cached) = cache.entries.get_mut(&image.path) {
+            cached.last_drawn = frame;
+            return match &cached.entry {
+                Entry::Pending => Thumbnail::Loading,
+                Entry::Ready(texture) => Thumbnail::Ready(texture.clone()),
+                Entry::Failed => Thumbnail::Failed,
+            };
+        }
+
+        // Room is made before it's requested, so the estimate never goes over
+        // budget. If it can't be (everything cached was drawn this frame, i.e.
+        // the budget doesn't cover a screenful), it's left as a placeholder.
+        let bytes = Self::estimate(image);
+        if !self.make_room(cache, &mut queue, frame, bytes) {
+            return Thumbnail::Loading;
+        }
+        cache.entries.insert(image.path.clone(), CacheEntry{entry: Entry::Pending, last_drawn: frame, bytes});
+        cache.used += bytes;
+        queue.pending.push(image.clone());
+        drop(queue);
+        self.shared.changed.notify_one();
+        Thumbnail::Loading
+    }
+
+    fn remove(cache: &mut Cache, queue: &mut Queue, path: &PathBuf) {
+        // Dropping the last handle to a texture frees it.
+        if let Some(cached) = cache.entries.remove(path) {
+            cache.used -= cached.bytes;
+            if let Entry::Pending = cached.entry {
+                queue.pending.retain(|x| x.path != *path);
+            }
+        }
+    }
+
+    fn evict_idle(&self, cache: &mut Cache, queue: &mut Queue, frame: u64) {
+        let idle: Vec<PathBuf> = cache.entries.iter()
+            .filter(|(_, x)| x.last_drawn + self.limits.idle_frames < frame)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in idle {
+            Self::remove(cache, queue, &path);
+        }
+    }
+
+    // Drops the least recently drawn entries until there's room for bytes
+    // more, but never ones drawn this frame. Returns whether there's room.
+    fn make_room(&self, cache: &mut Cache, queue: &mut Queue, frame: u64, bytes: u64) -> bool {
+        while cache.used + bytes > self.limits.budget {
+            let Some(path) = cache.entries.iter()
+                .filter(|(_, x)| x.last_drawn < frame)
+                .min_by_key(|(_, x)| x.last_drawn)
+                .map(|(path, _)| path.clone()) else {
+                return false;
+            };
+            Self::remove(cache, queue, &path);
+        }
+        true
+    }
+
+    fn start(&self) {
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |x| x.get())
+            .min(Self::MAX_WORKERS);
+        for _ in 0..workers {
+            let shared = self.shared.clone();
+            std::thread::spawn(move || Self::work(&shared));
+        }
+    }
+
+    fn work(shared: &Shared) {
+        loop {
+            let image = {
+                let mut queue = shared.changed
+                    .wait_while(shared.queue.lock().unwrap(), |x| x.pending.is_empty() && !x.closed)
+                    .unwrap();
+                if queue.closed {
+                    return;
+                }
+                queue.pending.pop().unwrap()
+            };
+
+            let color = Self::decode(&image);
+            let mut queue = shared.queue.lock().unwrap();
+            queue.decoded.push((image.path, color));
+            if let Some(ctx) = &queue.ctx {
+                ctx.request_repaint();
+            }
+        }
+    }
+
+    // Lazily loaded images are read here too, rather than on the UI thread.
+    fn decode(image: &Image) -> Option<egui::ColorImage> {
+        let buffer = image.buffer();
+        let decoded = sources::for_file(&image.path, &buffer).load_for_display(&image.path, &buffer).ok()?
+            .thumbnail(Self::MAX_SIZE, Self::MAX_SIZE)
+            .into_rgba8();
+        let size = [decoded.width() as usize, decoded.height() as usize];
+        Some(egui::ColorImage::from_rgba_unmultiplied(size, decoded.as_raw()))
+    }
+
+    #[cfg(test)]
+    pub fn requested(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+}
+
+impl Drop for Thumbnails {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_stays_within_budget() {
+        // 40 screenfuls' worth, each thumbnail 512×384.
+        let images: Vec<Image> = (0..10_000)
+            .map(|x| Image::new_lazy(PathBuf::from(format!("/nonexistent/{x}.jpg")), 1 << 20, Some((4000, 3000))))
+            .collect();
+        let budget = 64 * 1024 * 1024;
+        let thumbnails = Thumbnails::new(CacheLimits{budget, idle_frames: 600});
+        // No workers, so everything stays pending, holding its estimate.
+        thumbnails.started.set(()).unwrap();
+
+        // Down and back up, a screenful of 24 at a time, a few rows a frame.
+        const SCREEN: usize = 24;
+        let tops = (0..images.len() - SCREEN).step_by(7);
+        let ctx = egui::Context::default();
+        let mut peak = 0;
+        for top in tops.clone().chain(tops.rev()) {
+            let _ = ctx.run(Default::default(), |ctx| {
+                for image in &images[top..top + SCREEN] {
+                    thumbnails.get(ctx, image);
+                }
+            });
+            let (used, _) = thumbnails.usage();
+            assert!(used <= budget, "{used} over {budget} at {top}");
+            peak = peak.max(used);
+        }
+        // Flat at the budget, rather than evicting everything.
+        assert!(peak > budget - 2 * Thumbnails::estimate(&images[0]), "{peak}");
+    }
+}
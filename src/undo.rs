@@ -0,0 +1,81 @@
+use crate::misc::Image;
+
+use std::path::PathBuf;
+
+
+// A destructive action on the results that can be reversed. Only kept for the
+// life of the OutputPhase; a new search starts with a clean slate.
+pub struct UndoEntry {
+    pub description: String, // e.g. "Trash 12 candidates", for the button's tooltip
+    pub trashed: Vec<PathBuf>, // Original paths of the files sent to the trash
+    pub groups: Vec<Vec<Image>>, // Every group the action touched, as it was before
+    pub renamed: Vec<(PathBuf, PathBuf)>, // Old and new paths of the files renamed, or moved (then with their groups)
+}
+
+// The trash crate can only list (and so restore) trash items on Windows and
+// freedesktop systems.
+pub const TRASH_RESTORE_SUPPORTED: bool = cfg!(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")),
+));
+
+// Puts the given files back where they were. If a path was trashed more than
+// once, the most recent is the one restored.
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")),
+))]
+pub fn restore_from_trash(paths: &[PathBuf]) -> Result<(), String> {
+    use std::collections::HashMap;
+
+    let items = trash::os_limited::list().map_err(|e| format!("Error reading the trash: {e}"))?;
+    let mut latest: HashMap<PathBuf, trash::TrashItem> = HashMap::new();
+    for item in items {
+        let path = item.original_path();
+        if !paths.contains(&path) {
+            continue;
+        }
+        if latest.get(&path).is_none_or(|x| x.time_deleted < item.time_deleted) {
+            latest.insert(path, item);
+        }
+    }
+
+    if let Some(missing) = paths.iter().find(|x| !latest.contains_key(*x)) {
+        return Err(format!("{} is no longer in the trash", missing.display()));
+    }
+
+    trash::os_limited::restore_all(latest.into_values()).map_err(|e| match e {
+        trash::Error::RestoreCollision{path, ..} => {
+            format!("Can't restore {}, another file is already there", path.display())
+        },
+        e => format!("Error restoring from the trash: {e}"),
+    })
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")),
+)))]
+pub fn restore_from_trash(_paths: &[PathBuf]) -> Result<(), String> {
+    Err("Restoring from the trash isn't supported on this platform".to_string())
+}
+
+// Shows the trash in the file manager, e.g. for emptying it.
+pub fn open_trash() -> Result<(), String> {
+    let opened = if cfg!(target_os = "windows") {
+        opener::open("shell:RecycleBinFolder")
+    } else if cfg!(target_os = "macos") {
+        let home = homedir::get_my_home().ok().flatten().unwrap_or_default();
+        opener::open(home.join(".Trash"))
+    } else {
+        // File managers that know the trash open it as trash:///, with its
+        // empty button; others get the freedesktop folder behind it.
+        opener::open("trash:///").or_else(|_| {
+            let data = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+                .or_else(|| homedir::get_my_home().ok().flatten().map(|x| x.join(".local/share")))
+                .unwrap_or_default();
+            opener::open(data.join("Trash/files"))
+        })
+    };
+    opened.map_err(|e| e.to_string())
+}
@@ -0,0 +1,137 @@
+use crate::misc::Image;
+use crate::searcher::{self, SearchError, SizeCap, HashIndex};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use eframe::egui;
+
+use image_hasher::ImageHash;
+
+use notify::{RecursiveMode, Watcher};
+
+
+// Watches the searched folders after a scan, hashing files as they appear or
+// change and matching them against everything the scan hashed.
+
+// What changed in one batch of filesystem events.
+pub struct WatchUpdate {
+    pub removed: HashSet<PathBuf>, // Deleted, or modified and so rehashed
+    pub additions: Vec<(ImageHash, Vec<Image>)>, // As in RetryResults
+    pub errors: Vec<SearchError>,
+}
+
+pub struct WatchOpts {
+    pub roots: Vec<PathBuf>,
+    pub exts: HashSet<String>,
+    pub size_cap: SizeCap,
+    pub trim_borders: bool,
+    pub center: u32,
+}
+
+// Stops watching when dropped, waiting for the worker to finish whatever it's
+// hashing.
+pub struct Watch {
+    watcher: Option<notify::RecommendedWatcher>,
+    worker: Option<JoinHandle<()>>,
+    updates: Receiver<WatchUpdate>,
+    pub notify: Arc<AtomicBool>, // Show a desktop notification for new duplicates
+}
+
+impl Watch {
+    // Events are collected until there's been a lull this long, so a file
+    // being copied in is only hashed once it's (probably) complete, and a big
+    // import is handled as one batch.
+    const DEBOUNCE: Duration = Duration::from_millis(750);
+
+    pub fn start(opts: WatchOpts, index: HashIndex, ctx: egui::Context) -> Result<Watch, String> {
+        let (event_tx, event_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                let _ = event_tx.send(event.paths);
+            }
+        }).map_err(|e| format!("Error starting the watcher: {e}"))?;
+        for root in &opts.roots {
+            watcher.watch(root, RecursiveMode::Recursive)
+                .map_err(|e| format!("Error watching {}: {e}", root.display()))?;
+        }
+
+        let (update_tx, updates) = mpsc::channel();
+        let notify = Arc::new(AtomicBool::new(false));
+        let worker_notify = notify.clone();
+        let worker = std::thread::spawn(move || {
+            let mut index = index;
+            // Ends when the watcher is dropped, taking the event sender with it.
+            while let Some(paths) = Self::next_batch(&event_rx) {
+                let (update, new_index) = Self::process(&opts, paths, index);
+                index = new_index;
+                if !update.additions.is_empty() && worker_notify.load(Ordering::Relaxed) {
+                    Self::send_notification(&update);
+                }
+                if update_tx.send(update).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        Ok(Watch{watcher: Some(watcher), worker: Some(worker), updates, notify})
+    }
+
+    // Blocks for the first event, then gathers the rest until a lull. None
+    // once the watcher is gone.
+    fn next_batch(events: &Receiver<Vec<PathBuf>>) -> Option<HashSet<PathBuf>> {
+        let mut paths: HashSet<PathBuf> = events.recv().ok()?.into_iter().collect();
+        loop {
+            match events.recv_timeout(Self::DEBOUNCE) {
+                Ok(more) => paths.extend(more),
+                Err(RecvTimeoutError::Timeout) => return Some(paths),
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    fn process(opts: &WatchOpts, paths: HashSet<PathBuf>, mut index: HashIndex) -> (WatchUpdate, HashIndex) {
+        let is_image = |path: &Path| {
+            path.extension().is_some_and(|x| opts.exts.contains(&*x.to_string_lossy()))
+        };
+        let removed: HashSet<PathBuf> = paths.into_iter().filter(|x| is_image(x)).collect();
+        let changed: Vec<PathBuf> = removed.iter().filter(|x| x.is_file()).cloned().collect();
+
+        // Whatever these files were before, they aren't anymore.
+        for members in index.hashes.values_mut() {
+            members.retain(|x| !removed.contains(x));
+        }
+
+        let results = searcher::retry_files(changed, opts.size_cap, opts.trim_borders, opts.center, index);
+        let update = WatchUpdate{removed, additions: results.additions, errors: results.errors};
+        (update, results.index)
+    }
+
+    fn send_notification(update: &WatchUpdate) {
+        let count: usize = update.additions.iter().map(|x| x.1.len()).sum();
+        let _ = notify_rust::Notification::new()
+            .summary("Deckard")
+            .body(&tr!("Found {count} new duplicates", count = count))
+            .show();
+    }
+
+    // Updates since the last call, oldest first.
+    pub fn poll(&self) -> Vec<WatchUpdate> {
+        self.updates.try_iter().collect()
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        drop(self.watcher.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
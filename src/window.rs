@@ -0,0 +1,167 @@
+use eframe::egui;
+use serde::{Serialize, Deserialize};
+
+
+// The main window's size and position, kept between launches. eframe can keep
+// them itself, but only makes sure they're still on screen on Windows; these
+// are checked against the monitor the window comes back on, everywhere.
+//
+// Everything's in native points: the zoom factor changes what egui calls a
+// point, but not where the window is.
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct WindowGeometry {
+    size: [f32; 2], // Inner
+    position: Option<[f32; 2]>, // Outer, where the platform says
+    monitor: Option<[f32; 2]>, // Size of the monitor it was on
+    maximized: bool,
+}
+
+impl WindowGeometry {
+    // Some window managers report a few points off-screen for a window hard
+    // against the edge; nothing less than this much over it counts as off.
+    const MONITOR_SLACK: f32 = 50.0;
+
+    // Whether what's stored is usable.
+    pub fn is_sane(&self) -> bool {
+        let valid = |x: [f32; 2]| x.iter().all(|x| x.is_finite());
+        valid(self.size)
+            && self.size.iter().all(|x| *x > 0.0)
+            && self.position.is_none_or(valid)
+            && self.monitor.is_none_or(valid)
+    }
+
+    // None until the platform's said how big the window is.
+    pub fn capture(ctx: &egui::Context) -> Option<WindowGeometry> {
+        let zoom = ctx.zoom_factor();
+        let native = |x: egui::Vec2| [x.x * zoom, x.y * zoom];
+        ctx.input(|i| {
+            let info = i.viewport();
+            // Which reports nothing useful.
+            if info.minimized == Some(true) {
+                return None;
+            }
+            Some(WindowGeometry {
+                size: native(info.inner_rect?.size()),
+                position: info.outer_rect.map(|x| native(x.min.to_vec2())),
+                monitor: info.monitor_size.map(native),
+                maximized: info.maximized.unwrap_or(false),
+            })
+        })
+    }
+
+    // No smaller than min_size, which still applies.
+    pub fn apply(&self, builder: egui::ViewportBuilder, min_size: (f32, f32)) -> egui::ViewportBuilder {
+        let size = egui::vec2(self.size[0].max(min_size.0), self.size[1].max(min_size.1));
+        let mut builder = builder.with_inner_size(size).with_maximized(self.maximized);
+        if let Some([x, y]) = self.position {
+            builder = builder.with_position([x, y]);
+        }
+        builder
+    }
+
+    // Once the window's up: if the monitor it was saved on isn't the one it's
+    // on now (say an external display that's been unplugged), shrinks it to
+    // fit this one and, if it's not entirely on it, centers it there. Returns
+    // the commands to send, in egui's points, or None if the window hasn't
+    // reported enough to tell yet.
+    pub fn fit_to_monitor(&self, ctx: &egui::Context) -> Option<Vec<egui::ViewportCommand>> {
+        let zoom = ctx.zoom_factor();
+        let (monitor, outer, inner) = ctx.input(|i| {
+            let info = i.viewport();
+            Some((info.monitor_size?, info.outer_rect?, info.inner_rect.map_or(info.outer_rect?.size(), |x| x.size())))
+        })?;
+        let native_monitor = [monitor.x * zoom, monitor.y * zoom];
+        if self.maximized || self.monitor.is_none_or(|x| x == native_monitor) {
+            return Some(vec![]);
+        }
+        Some(fit(monitor, outer, inner))
+    }
+}
+
+// The commands that fit a window, outer and with the inner size given, on a
+// monitor of the size given. Only the monitor's size is known, not where it
+// is among the others, so it's taken to be the one the window's top left
+// corner is on, in a grid of monitors its size: exactly so for the main
+// monitor, which is at the origin, and ones of the same size beside it.
+fn fit(monitor: egui::Vec2, outer: egui::Rect, inner: egui::Vec2) -> Vec<egui::ViewportCommand> {
+    let mut commands = vec![];
+    let mut size = outer.size();
+    if size.x > monitor.x || size.y > monitor.y {
+        size = size.min(monitor);
+        let chrome = outer.size() - inner;
+        commands.push(egui::ViewportCommand::InnerSize(size - chrome));
+    }
+    let slack = WindowGeometry::MONITOR_SLACK;
+    let cell = |pos: f32, side: f32| ((pos + slack) / side).floor() * side;
+    let origin = egui::pos2(cell(outer.min.x, monitor.x), cell(outer.min.y, monitor.y));
+    let screen = egui::Rect::from_min_size(origin, monitor).expand(slack);
+    let rect = egui::Rect::from_min_size(outer.min, size);
+    if !screen.contains_rect(rect) {
+        let centered = ((monitor - size) / 2.0).max(egui::Vec2::ZERO);
+        commands.push(egui::ViewportCommand::OuterPosition(origin + centered));
+    }
+    commands
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use egui::{pos2, vec2, Rect, ViewportCommand};
+
+    fn geometry(size: [f32; 2]) -> WindowGeometry {
+        WindowGeometry{size, position: Some([100.0, 50.0]), monitor: Some([1920.0, 1080.0]), maximized: false}
+    }
+
+    #[test]
+    fn stored_geometry_is_checked() {
+        assert!(geometry([800.0, 600.0]).is_sane());
+        assert!(WindowGeometry{position: None, monitor: None, ..geometry([800.0, 600.0])}.is_sane());
+        assert!(!geometry([0.0, 600.0]).is_sane());
+        assert!(!geometry([800.0, -1.0]).is_sane());
+        assert!(!geometry([f32::NAN, 600.0]).is_sane());
+        assert!(!WindowGeometry{position: Some([f32::INFINITY, 0.0]), ..geometry([800.0, 600.0])}.is_sane());
+        assert!(!WindowGeometry{monitor: Some([f32::NAN, 0.0]), ..geometry([800.0, 600.0])}.is_sane());
+    }
+
+    #[test]
+    fn restored_windows_are_no_smaller_than_the_minimum() {
+        let min = (550.0, 400.0);
+        let builder = geometry([800.0, 600.0]).apply(egui::ViewportBuilder::default(), min);
+        assert_eq!(builder.inner_size, Some(vec2(800.0, 600.0)));
+        assert_eq!(builder.position, Some(pos2(100.0, 50.0)));
+
+        let builder = WindowGeometry{position: None, ..geometry([300.0, 900.0])}.apply(egui::ViewportBuilder::default(), min);
+        assert_eq!(builder.inner_size, Some(vec2(550.0, 900.0)));
+        assert_eq!(builder.position, None);
+    }
+
+    #[test]
+    fn windows_are_fit_to_the_monitor_they_are_on() {
+        let monitor = vec2(1280.0, 800.0);
+        let window = |x: f32, y: f32, w: f32, h: f32| Rect::from_min_size(pos2(x, y), vec2(w, h));
+        let chrome = vec2(0.0, 30.0);
+
+        // Fits as it is, on the main monitor or one beside it, either side
+        assert!(fit(monitor, window(100.0, 100.0, 800.0, 600.0), vec2(800.0, 570.0)).is_empty());
+        assert!(fit(monitor, window(1380.0, 100.0, 800.0, 600.0), vec2(800.0, 570.0)).is_empty());
+        assert!(fit(monitor, window(-1180.0, 100.0, 800.0, 600.0), vec2(800.0, 570.0)).is_empty());
+        // A few points over the edge is still on it.
+        assert!(fit(monitor, window(-10.0, -5.0, 800.0, 600.0), vec2(800.0, 570.0)).is_empty());
+
+        // Too big, and so hanging off the side
+        assert_eq!(fit(monitor, window(0.0, 0.0, 1920.0, 1080.0), vec2(1920.0, 1080.0) - chrome), [
+            ViewportCommand::InnerSize(monitor - chrome),
+        ]);
+        assert_eq!(fit(monitor, window(1480.0, 100.0, 1920.0, 1080.0), vec2(1920.0, 1080.0) - chrome), [
+            ViewportCommand::InnerSize(monitor - chrome),
+            ViewportCommand::OuterPosition(pos2(1280.0, 0.0)),
+        ]);
+        // Straddling two, centered on the one its corner's on
+        assert_eq!(fit(monitor, window(1000.0, 100.0, 800.0, 600.0), vec2(800.0, 570.0)), [
+            ViewportCommand::OuterPosition(pos2(240.0, 100.0)),
+        ]);
+    }
+}
@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::process::Command;
+
+
+// Loading the groups at the end of a search, which runs on every core,
+// against the same search on one, through the headless scan as a user would
+// run it. Everything the app keeps goes in a temporary home, so the user's
+// own sessions and settings are left alone.
+
+const GROUPS: u64 = 600;
+const COPIES: u64 = 3;
+
+// Enough that finalizing takes long enough to time. Run with --release and
+// --ignored.
+const BENCHMARK_GROUPS: u64 = 3000;
+
+// Random blocks, coarse enough to survive the hash's downscaling, so every
+// group has a hash of its own. As binary PPMs, which take no encoder to
+// write.
+fn write_library(root: &Path, groups: u64) {
+    for group in 0..groups {
+        let mut rng = fastrand::Rng::with_seed(group);
+        let blocks: Vec<u8> = (0..64).map(|_| rng.u8(..)).collect();
+        let mut image = b"P6\n64 64\n255\n".to_vec();
+        for y in 0..64 {
+            for x in 0..64 {
+                image.extend([blocks[y / 8 * 8 + x / 8]; 3]);
+            }
+        }
+        let folder = root.join(format!("{}", group % 20));
+        std::fs::create_dir_all(&folder).unwrap();
+        for copy in 0..COPIES {
+            std::fs::write(folder.join(format!("{group}_{copy}.pnm")), &image).unwrap();
+        }
+    }
+}
+
+// The finalization time, in milliseconds, and what the scan said it found.
+fn scan(home: &Path, options: &Path, threads: Option<usize>) -> (u64, String) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_deckard"));
+    command.arg("--headless-scan").arg(options)
+        .env("HOME", home)
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env_remove("RAYON_NUM_THREADS");
+    if let Some(threads) = threads {
+        command.env("RAYON_NUM_THREADS", threads.to_string());
+    }
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert_eq!(output.status.code(), Some(0), "{stdout}{}", String::from_utf8_lossy(&output.stderr));
+
+    let millis = stdout.lines()
+        .find_map(|x| x.strip_prefix("Results finalized in ")?.strip_suffix(" ms")?.parse().ok())
+        .unwrap_or_else(|| panic!("no finalization time in: {stdout}"));
+    let found = stdout.lines().find(|x| x.starts_with("Groups of duplicates found")).unwrap_or_default().to_string();
+    (millis, found)
+}
+
+// The finalization times on one thread and on every core, after checking
+// both found every group.
+fn scan_both(groups: u64) -> (u64, u64) {
+    let dir = tempfile::tempdir().unwrap();
+    let (root, home) = (dir.path().join("library"), dir.path().join("home"));
+    write_library(&root, groups);
+    let options = dir.path().join("options.json");
+    let json = serde_json::json!({"root": root.canonicalize().unwrap()});
+    std::fs::write(&options, json.to_string()).unwrap();
+
+    let (serial, found) = scan(&home, &options, Some(1));
+    let (parallel, found_parallel) = scan(&home, &options, None);
+    assert_eq!(found, format!("Groups of duplicates found: {groups}. Open Deckard to review them."));
+    assert_eq!(found_parallel, found);
+    (serial, parallel)
+}
+
+#[test]
+fn finalization_finds_the_same_groups_on_every_core() {
+    scan_both(GROUPS);
+}
+
+#[test]
+#[ignore]
+fn finalization_is_faster_on_every_core() {
+    let cores = std::thread::available_parallelism().map_or(1, |x| x.get());
+    if cores < 2 {
+        return;
+    }
+    let (serial, parallel) = scan_both(BENCHMARK_GROUPS);
+    // Allowing for the parts that don't parallelize, and for noise.
+    assert!(parallel * 10 < serial * 9, "{parallel} ms on {cores} cores, {serial} ms on one");
+}